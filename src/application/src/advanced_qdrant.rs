@@ -1,10 +1,18 @@
 //! Advanced Qdrant Configuration and Optimization
 //!
 //! This module provides advanced Qdrant features for production deployment,
-//! including quantization, HNSW optimization, and performance tuning.
-
+//! including quantization, HNSW optimization, and performance tuning, plus
+//! snapshot backup and restore of collections.
+
+use chrono::{DateTime, Utc};
+use infrastructure::qdrant_guard::QdrantGuard;
+use serde::{Deserialize, Serialize};
+use shared::cancellation::CancellationToken;
+use shared::platform;
 use shared::types::Result;
 use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct QdrantOptimizationConfig {
@@ -77,16 +85,26 @@ pub enum PayloadDataType {
 pub struct AdvancedQdrantManager {
     base_url: String,
     client: reqwest::Client,
+    guard: QdrantGuard,
 }
 
 impl AdvancedQdrantManager {
     pub fn new(qdrant_url: &str) -> Self {
+        let base_url = qdrant_url.trim_end_matches('/').to_string();
+        let limits = infrastructure::config::PowerUserConfig::load().qdrant;
         Self {
-            base_url: qdrant_url.trim_end_matches('/').to_string(),
+            guard: QdrantGuard::for_url(&base_url, limits),
+            base_url,
             client: reqwest::Client::new(),
         }
     }
 
+    /// Abort requests in flight, and fail later ones, once `cancel` fires
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.guard = self.guard.with_cancellation(cancel);
+        self
+    }
+
     /// Create an optimized collection with advanced configuration
     pub async fn create_optimized_collection(
         &self,
@@ -187,7 +205,10 @@ impl AdvancedQdrantManager {
 
         let url = format!("{}/collections/{}", self.base_url, collection_name);
 
-        let response = self.client.put(&url).json(&payload).send().await?;
+        let response = self
+            .guard
+            .call(self.client.put(&url).json(&payload).send())
+            .await?;
 
         if !response.status().is_success() {
             let error_text = response.text().await?;
@@ -255,7 +276,10 @@ impl AdvancedQdrantManager {
 
             let url = format!("{}/collections/{}/index", self.base_url, collection_name);
 
-            let response = self.client.put(&url).json(&payload).send().await?;
+            let response = self
+                .guard
+                .call(self.client.put(&url).json(&payload).send())
+                .await?;
 
             if !response.status().is_success() {
                 let error_text = response.text().await?;
@@ -281,7 +305,7 @@ impl AdvancedQdrantManager {
         // Force optimization
         let url = format!("{}/collections/{}/optimize", self.base_url, collection_name);
 
-        let response = self.client.post(&url).send().await?;
+        let response = self.guard.call(self.client.post(&url).send()).await?;
 
         if !response.status().is_success() {
             let error_text = response.text().await?;
@@ -300,7 +324,7 @@ impl AdvancedQdrantManager {
     ) -> Result<CollectionMetrics> {
         let url = format!("{}/collections/{}", self.base_url, collection_name);
 
-        let response = self.client.get(&url).send().await?;
+        let response = self.guard.call(self.client.get(&url).send()).await?;
         let data: serde_json::Value = response.json().await?;
 
         let result = data
@@ -401,6 +425,357 @@ impl AdvancedQdrantManager {
         println!("🎯 Production semantic memory collection ready!");
         Ok(())
     }
+
+    /// Create a snapshot of `collection` on the Qdrant server
+    pub async fn create_snapshot(&self, collection_name: &str) -> Result<SnapshotInfo> {
+        let url = format!(
+            "{}/collections/{}/snapshots?wait=true",
+            self.base_url, collection_name
+        );
+        let response = self.guard.call_slow(self.client.post(&url).send()).await?;
+        Self::snapshot_result(response, "create snapshot").await
+    }
+
+    /// Snapshots of `collection` currently stored on the server
+    pub async fn list_snapshots(&self, collection_name: &str) -> Result<Vec<SnapshotInfo>> {
+        let url = format!(
+            "{}/collections/{}/snapshots",
+            self.base_url, collection_name
+        );
+        let response = self.guard.call(self.client.get(&url).send()).await?;
+        Self::snapshot_result(response, "list snapshots").await
+    }
+
+    pub async fn delete_snapshot(&self, collection_name: &str, snapshot_name: &str) -> Result<()> {
+        let url = format!(
+            "{}/collections/{}/snapshots/{}",
+            self.base_url, collection_name, snapshot_name
+        );
+        let response = self.guard.call(self.client.delete(&url).send()).await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to delete snapshot {}: {}",
+                snapshot_name,
+                response.text().await?
+            ));
+        }
+        Ok(())
+    }
+
+    /// Snapshot `collection` and download it into `dir` with a metadata record.
+    /// The server-side copy is removed once the download completes.
+    pub async fn backup_collection(
+        &self,
+        collection_name: &str,
+        dir: &Path,
+    ) -> Result<(SnapshotMetadata, PathBuf)> {
+        let metrics = self.get_performance_metrics(collection_name).await?;
+        let snapshot = self.create_snapshot(collection_name).await?;
+
+        std::fs::create_dir_all(dir)?;
+        let created_at = Utc::now();
+        let stem = format!(
+            "{}-{}",
+            collection_name,
+            created_at.format("%Y%m%dT%H%M%SZ")
+        );
+        let file = PathBuf::from(format!("{}.snapshot", stem));
+        let snapshot_path = dir.join(&file);
+
+        let url = format!(
+            "{}/collections/{}/snapshots/{}",
+            self.base_url, collection_name, snapshot.name
+        );
+        let mut response = self.guard.call(self.client.get(&url).send()).await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to download snapshot {}: {}",
+                snapshot.name,
+                response.text().await?
+            ));
+        }
+        let mut out = std::fs::File::create(&snapshot_path)?;
+        let mut size_bytes = 0u64;
+        while let Some(chunk) = self.guard.call(response.chunk()).await? {
+            out.write_all(&chunk)?;
+            size_bytes += chunk.len() as u64;
+        }
+        out.flush()?;
+
+        let metadata = SnapshotMetadata {
+            collection: collection_name.to_string(),
+            snapshot_name: snapshot.name.clone(),
+            created_at,
+            points_count: metrics.points_count,
+            size_bytes,
+            checksum: snapshot.checksum.clone(),
+            file,
+            qdrant_url: self.base_url.clone(),
+        };
+        std::fs::write(
+            dir.join(format!("{}.json", stem)),
+            serde_json::to_string_pretty(&metadata)?,
+        )?;
+
+        if let Err(e) = self.delete_snapshot(collection_name, &snapshot.name).await {
+            eprintln!("⚠️  Snapshot downloaded but not removed from server: {}", e);
+        }
+        Ok((metadata, snapshot_path))
+    }
+
+    /// Restore a downloaded snapshot, replacing the collection's contents.
+    /// `target` restores into a differently named collection.
+    pub async fn restore_collection(
+        &self,
+        path: &Path,
+        target: Option<&str>,
+    ) -> Result<SnapshotMetadata> {
+        let (metadata, snapshot_path) = SnapshotMetadata::load(path)?;
+        let collection_name = target.unwrap_or(&metadata.collection);
+
+        let mut url = format!(
+            "{}/collections/{}/snapshots/upload?priority=snapshot&wait=true",
+            self.base_url, collection_name
+        );
+        if let Some(checksum) = &metadata.checksum {
+            url.push_str(&format!("&checksum={}", checksum));
+        }
+
+        let part = reqwest::multipart::Part::bytes(std::fs::read(&snapshot_path)?)
+            .file_name(metadata.file.to_string_lossy().to_string());
+        let form = reqwest::multipart::Form::new().part("snapshot", part);
+        let response = self
+            .guard
+            .call_slow(self.client.post(&url).multipart(form).send())
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to restore {} into '{}': {}",
+                snapshot_path.display(),
+                collection_name,
+                response.text().await?
+            ));
+        }
+        Ok(metadata)
+    }
+
+    /// Names of all collections on the server
+    pub async fn list_collections(&self) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Collections {
+            collections: Vec<Collection>,
+        }
+        #[derive(Deserialize)]
+        struct Collection {
+            name: String,
+        }
+
+        let url = format!("{}/collections", self.base_url);
+        let response = self.guard.call(self.client.get(&url).send()).await?;
+        let collections: Collections = Self::snapshot_result(response, "list collections").await?;
+        Ok(collections
+            .collections
+            .into_iter()
+            .map(|c| c.name)
+            .collect())
+    }
+
+    /// Back up semantic memory and every RAG index into `dir`, which must not
+    /// already hold a memory backup, and write a manifest listing them
+    pub async fn backup_memory(&self, dir: &Path) -> Result<MemoryBackup> {
+        let manifest_path = dir.join(MEMORY_MANIFEST);
+        if manifest_path.exists() {
+            return Err(anyhow::anyhow!(
+                "{} already contains a memory backup",
+                dir.display()
+            ));
+        }
+
+        let mut collections: Vec<String> = self
+            .list_collections()
+            .await?
+            .into_iter()
+            .filter(|name| is_memory_collection(name))
+            .collect();
+        collections.sort();
+        if collections.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No semantic memory or RAG collections on {}",
+                self.base_url
+            ));
+        }
+
+        let mut snapshots = Vec::with_capacity(collections.len());
+        for name in &collections {
+            let (metadata, _) = self.backup_collection(name, dir).await?;
+            snapshots.push(metadata);
+        }
+        let backup = MemoryBackup {
+            created_at: Utc::now(),
+            qdrant_url: self.base_url.clone(),
+            snapshots,
+        };
+        std::fs::write(&manifest_path, serde_json::to_string_pretty(&backup)?)?;
+        Ok(backup)
+    }
+
+    /// Restore every collection listed in the memory backup at `path` (its
+    /// directory or manifest), replacing collections that already exist
+    pub async fn restore_memory(&self, path: &Path) -> Result<MemoryBackup> {
+        let (backup, dir) = MemoryBackup::load(path)?;
+        for metadata in &backup.snapshots {
+            self.restore_collection(&dir.join(&metadata.file), None)
+                .await?;
+        }
+        Ok(backup)
+    }
+
+    async fn snapshot_result<T: serde::de::DeserializeOwned>(
+        response: reqwest::Response,
+        action: &str,
+    ) -> Result<T> {
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Failed to {}: {}",
+                action,
+                response.text().await?
+            ));
+        }
+        let data: serde_json::Value = response.json().await?;
+        let result = data
+            .get("result")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No result in response"))?;
+        Ok(serde_json::from_value(result)?)
+    }
+}
+
+/// Snapshot as reported by the Qdrant server
+#[derive(Debug, Clone, Deserialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    #[serde(default)]
+    pub creation_time: Option<String>,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+/// Local record written next to every downloaded snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotMetadata {
+    pub collection: String,
+    pub snapshot_name: String,
+    pub created_at: DateTime<Utc>,
+    pub points_count: u64,
+    pub size_bytes: u64,
+    /// SHA-256 reported by Qdrant, verified by the server on restore
+    pub checksum: Option<String>,
+    /// Snapshot file, relative to the metadata file's directory
+    pub file: PathBuf,
+    pub qdrant_url: String,
+}
+
+impl SnapshotMetadata {
+    /// Read the metadata for `path`, which may be the `.json` record or the
+    /// `.snapshot` file next to it; returns the metadata and the snapshot path
+    pub fn load(path: &Path) -> Result<(Self, PathBuf)> {
+        let metadata_path = path.with_extension("json");
+        let content = std::fs::read_to_string(&metadata_path).map_err(|e| {
+            anyhow::anyhow!("No snapshot metadata at {}: {}", metadata_path.display(), e)
+        })?;
+        let metadata: Self = serde_json::from_str(&content)?;
+        let snapshot = metadata_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(&metadata.file);
+        if !snapshot.is_file() {
+            return Err(anyhow::anyhow!(
+                "Snapshot file {} is missing",
+                snapshot.display()
+            ));
+        }
+        Ok((metadata, snapshot))
+    }
+}
+
+/// Manifest written by `backup_memory`
+pub const MEMORY_MANIFEST: &str = "memory-backup.json";
+
+/// Collection holding conversation memory
+pub const SEMANTIC_MEMORY_COLLECTION: &str = "conversation_memory";
+
+/// Semantic memory and RAG indexes, including those of linked repositories
+pub fn is_memory_collection(name: &str) -> bool {
+    name == SEMANTIC_MEMORY_COLLECTION
+        || name == crate::rag_service::DEFAULT_COLLECTION
+        || name.starts_with(&format!("{}_", crate::rag_service::DEFAULT_COLLECTION))
+}
+
+/// Set of collection snapshots taken together by `backup_memory`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryBackup {
+    pub created_at: DateTime<Utc>,
+    pub qdrant_url: String,
+    pub snapshots: Vec<SnapshotMetadata>,
+}
+
+impl MemoryBackup {
+    /// Read the manifest at `path`, a backup directory or the manifest itself;
+    /// returns it with the directory holding the snapshots
+    pub fn load(path: &Path) -> Result<(Self, PathBuf)> {
+        let manifest_path = if path.is_dir() {
+            path.join(MEMORY_MANIFEST)
+        } else {
+            path.to_path_buf()
+        };
+        let content = std::fs::read_to_string(&manifest_path).map_err(|e| {
+            anyhow::anyhow!("No memory backup at {}: {}", manifest_path.display(), e)
+        })?;
+        let backup: Self = serde_json::from_str(&content)?;
+        let dir = manifest_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+        // Check every file up front so a restore does not stop halfway
+        if let Some(missing) = backup
+            .snapshots
+            .iter()
+            .map(|metadata| dir.join(&metadata.file))
+            .find(|snapshot| !snapshot.is_file())
+        {
+            return Err(anyhow::anyhow!(
+                "Snapshot file {} is missing",
+                missing.display()
+            ));
+        }
+        Ok((backup, dir))
+    }
+
+    pub fn points_count(&self) -> u64 {
+        self.snapshots.iter().map(|m| m.points_count).sum()
+    }
+}
+
+/// Local snapshots in `dir`, newest first
+pub fn list_backups(dir: &Path) -> Result<Vec<(SnapshotMetadata, PathBuf)>> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut backups: Vec<_> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("json"))
+        .filter_map(|path| SnapshotMetadata::load(&path).ok())
+        .collect();
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.0.created_at));
+    Ok(backups)
+}
+
+/// Where `bro --snapshot` stores downloaded snapshots
+pub fn default_backup_dir() -> PathBuf {
+    platform::app_data_dir().join("qdrant_snapshots")
 }
 
 #[derive(Debug)]
@@ -440,3 +815,84 @@ impl Default for QdrantOptimizationConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backups_are_listed_from_metadata() {
+        let dir = std::env::temp_dir().join(format!("bro-snapshots-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for (stem, age_hours) in [("old", 2), ("new", 1)] {
+            let metadata = SnapshotMetadata {
+                collection: "vibe_rag".to_string(),
+                snapshot_name: format!("{}.snapshot", stem),
+                created_at: Utc::now() - chrono::Duration::hours(age_hours),
+                points_count: 10,
+                size_bytes: 4,
+                checksum: None,
+                file: PathBuf::from(format!("{}.snapshot", stem)),
+                qdrant_url: "http://localhost:6333".to_string(),
+            };
+            std::fs::write(dir.join(&metadata.file), b"data").unwrap();
+            std::fs::write(
+                dir.join(format!("{}.json", stem)),
+                serde_json::to_string(&metadata).unwrap(),
+            )
+            .unwrap();
+        }
+        // Metadata without its snapshot file is not a usable backup
+        std::fs::remove_file(dir.join("old.snapshot")).unwrap();
+
+        let backups = list_backups(&dir).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].0.snapshot_name, "new.snapshot");
+
+        let (metadata, snapshot) = SnapshotMetadata::load(&dir.join("new.snapshot")).unwrap();
+        assert_eq!(metadata.collection, "vibe_rag");
+        assert_eq!(snapshot, dir.join("new.snapshot"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_memory_collections_and_manifest() {
+        assert!(is_memory_collection("conversation_memory"));
+        assert!(is_memory_collection("vibe_rag"));
+        assert!(is_memory_collection("vibe_rag_my_app"));
+        assert!(!is_memory_collection("vibe_ragged"));
+        assert!(!is_memory_collection("metrics"));
+
+        let dir = std::env::temp_dir().join(format!("bro-memory-backup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let backup = MemoryBackup {
+            created_at: Utc::now(),
+            qdrant_url: "http://localhost:6333".to_string(),
+            snapshots: vec![SnapshotMetadata {
+                collection: "conversation_memory".to_string(),
+                snapshot_name: "memory.snapshot".to_string(),
+                created_at: Utc::now(),
+                points_count: 3,
+                size_bytes: 4,
+                checksum: None,
+                file: PathBuf::from("memory.snapshot"),
+                qdrant_url: "http://localhost:6333".to_string(),
+            }],
+        };
+        std::fs::write(
+            dir.join(MEMORY_MANIFEST),
+            serde_json::to_string(&backup).unwrap(),
+        )
+        .unwrap();
+        assert!(MemoryBackup::load(&dir).is_err());
+
+        std::fs::write(dir.join("memory.snapshot"), b"data").unwrap();
+        let (loaded, snapshot_dir) = MemoryBackup::load(&dir).unwrap();
+        assert_eq!(loaded.points_count(), 3);
+        assert_eq!(snapshot_dir, dir);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
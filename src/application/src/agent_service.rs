@@ -10,23 +10,44 @@
 //! - Calculates confidence from both reasoning depth and tool success.
 //! - Produces stable, deterministic JSON parsing and avoids panics.
 
-use crate::build_service::{BuildPlan, ComplexOperation, FileOperation, RiskLevel, ValidationRule};
+use crate::ask_user::{self, ASK_USER_TOOL};
+use crate::build_service::{
+    BuildPlan, ComplexOperation, FileOperation, OperationGroup, RiskLevel, ValidationRule,
+};
+use crate::memory_summarizer::{compact_history, SummarizationPolicy};
+use crate::prompts;
+use crate::sub_agents::{self, SubAgentLimits, SubAgentReport, SubAgentSpec};
+use crate::task_decomposer::{DecompositionStrategy, TaskDecomposer};
 use domain::models::{
     AgentContext, AgentRequest, AgentResponse, ConversationMessage, ParameterProperty, ToolCall,
-    ToolDefinition, ToolParameters, ToolResult,
+    ToolDefinition, ToolParameters, ToolResult, ToolRetry,
 };
 use infrastructure::{
     agent_control::{
         AgentController, AgentError, AgentExecutionState, AgentIterationResult, AgentResult,
         IterationRecord, SafeFailureHandler,
     },
+    capabilities::Capabilities,
+    cargo_workspace::{self, CargoWorkspace},
     config::Config,
+    context_window::{ContextWindow, MemoryScope, PromptSegments, TokenCounter},
+    dependency_ops, patch,
+    prompt_templates::{PromptTemplate, PromptTemplates},
+    run_checkpoint::RunCheckpoint,
     sandbox::Sandbox,
+    structured_output::{self, OutputSchema},
+    syntax_check::{self, SyntaxCheck},
+    todo_list::TodoList,
+    tool_cache::ToolCache,
     tools::{ToolArgs, ToolRegistry},
+    user_questions::QuestionQueue,
 };
 use serde_json::{json, Value};
+use shared::output_cleanup::clean_file_content;
+use shared::prompt_redaction::PromptRedactor;
 use shared::types::Result;
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 
 // Forward declare for now - actual implementation when both services are integrated
@@ -41,6 +62,9 @@ pub struct AgentService {
     pub agent_controller: AgentController,
     pub failure_handler: SafeFailureHandler,
     pub system_context: infrastructure::config::SystemContext,
+    /// Progress of the agent loop after every iteration, for resuming it;
+    /// starts from the iterations of the checkpoint it holds
+    pub run_checkpoint: Option<tokio::sync::watch::Sender<RunCheckpoint>>,
 }
 
 /// Artifacts returned when planning a build
@@ -86,18 +110,30 @@ pub enum FileOperationType {
     Unknown,  // Not yet determined
 }
 
+/// Repair generations requested for a file that does not parse
+const MAX_SYNTAX_REPAIRS: usize = 2;
+
+/// Existing files longer than this are edited with patches, not rewritten
+const PATCH_MIN_LINES: usize = 150;
+
 /// Stream-based incremental build planner with true real-time streaming
 pub struct IncrementalBuildPlanner {
     goal: String,
     context: Vec<String>,
     planning_state: PlanningState,
     completed_operations: Vec<FileOperation>,
+    /// Files the model said must change together
+    groups: Vec<OperationGroup>,
     complex_operations: Vec<ComplexOperation>,
     file_contexts: HashMap<String, FileContext>,
     keywords: Vec<String>,
     os_info: String,
     cwd: String,
     config: Config,
+    /// Keeps secrets in file contents out of prompts
+    redactor: PromptRedactor,
+    /// Crates of the Cargo workspace at `cwd`, if it is one
+    cargo: Option<CargoWorkspace>,
 }
 
 #[derive(Debug, Clone)]
@@ -113,13 +149,139 @@ enum PlanningState {
     Complete,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Deserialize)]
 struct FileSpec {
     path: String,
     action: String,
+    #[serde(default)]
+    reason: String,
+    /// Files sharing a group are applied together or not at all
+    #[serde(default)]
+    group: Option<String>,
+    /// Package added or removed by the `add_dependency` and
+    /// `remove_dependency` actions, `path` being its manifest
+    #[serde(default)]
+    dependency: Option<String>,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+impl FileSpec {
+    /// The operation of a dependency action; None for file actions
+    fn dependency_operation(&self) -> Option<FileOperation> {
+        let name = self.dependency.as_deref()?.trim().to_string();
+        let manifest = std::path::PathBuf::from(&self.path);
+        match self.action.as_str() {
+            "add_dependency" => Some(FileOperation::AddDependency {
+                manifest,
+                name,
+                version: self
+                    .version
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|version| !version.is_empty() && *version != "latest")
+                    .map(str::to_string),
+            }),
+            "remove_dependency" => Some(FileOperation::RemoveDependency { manifest, name }),
+            _ => None,
+        }
+    }
+}
+
+/// The MCP tool or question the model picks for a goal
+#[derive(Debug, serde::Deserialize)]
+struct ToolChoice {
+    tool: String,
+    #[serde(default)]
+    arguments: serde_json::Map<String, Value>,
+    #[serde(default)]
     reason: String,
 }
 
+impl OutputSchema for ToolChoice {
+    const NAME: &'static str = "tool choice";
+
+    fn json_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["tool"],
+            "properties": {
+                "tool": {"type": "string"},
+                "arguments": {"type": "object"},
+                "reason": {"type": "string"}
+            }
+        })
+    }
+}
+
+/// Corrected arguments of a failed tool call
+#[derive(Debug, serde::Deserialize)]
+struct RepairedArguments {
+    #[serde(default)]
+    arguments: serde_json::Map<String, Value>,
+}
+
+impl OutputSchema for RepairedArguments {
+    const NAME: &'static str = "tool arguments";
+
+    fn json_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["arguments"],
+            "properties": {
+                "arguments": {"type": "object"}
+            }
+        })
+    }
+}
+
+/// File operations the model proposes for a goal
+#[derive(Debug, serde::Deserialize)]
+struct FileSpecs {
+    files: Vec<FileSpec>,
+}
+
+impl OutputSchema for FileSpecs {
+    const NAME: &'static str = "file list";
+
+    fn json_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["files"],
+            "properties": {
+                "files": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "required": ["path", "action", "reason"],
+                        "properties": {
+                            "path": {"type": "string"},
+                            "action": {
+                                "type": "string",
+                                "enum": ["create", "update", "add_dependency", "remove_dependency"]
+                            },
+                            "reason": {"type": "string"},
+                            "group": {"type": "string"},
+                            "dependency": {"type": "string"},
+                            "version": {"type": "string"}
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    fn check(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| file.path.trim().is_empty())
+            .map(|(i, _)| format!("files[{}].path is empty", i))
+            .collect()
+    }
+}
+
 impl IncrementalBuildPlanner {
     pub fn new(goal: String, context: Vec<String>, config: Config) -> Self {
         let cwd = std::env::current_dir()
@@ -132,15 +294,30 @@ impl IncrementalBuildPlanner {
             context,
             planning_state: PlanningState::Initial,
             completed_operations: Vec::new(),
+            groups: Vec::new(),
             complex_operations: Vec::new(),
             file_contexts: HashMap::new(),
             keywords: Vec::new(),
             os_info: std::env::consts::OS.to_string(),
+            cargo: CargoWorkspace::detect(Path::new(&cwd)),
             cwd,
+            redactor: config.prompt_redactor(),
             config,
         }
     }
 
+    /// Generate with secrets in `prompt` redacted, restoring them in the reply
+    async fn generate_redacted(
+        &self,
+        inference_engine: &infrastructure::InferenceEngine,
+        prompt: &str,
+    ) -> Result<String> {
+        let reply = inference_engine
+            .generate(&self.redactor.redact(prompt)?)
+            .await?;
+        Ok(self.redactor.restore(&reply))
+    }
+
     /// Stream the next planning step with true real-time AI generation
     pub async fn stream_next_step(
         &mut self,
@@ -160,6 +337,16 @@ impl IncrementalBuildPlanner {
                 if files.is_empty() {
                     files = self.infer_files_from_goal(inference_engine).await?;
                 }
+                // Dependencies need no code; the package manager applies them
+                let (dependencies, files): (Vec<FileSpec>, Vec<FileSpec>) = files
+                    .into_iter()
+                    .partition(|spec| spec.dependency_operation().is_some());
+                self.completed_operations.extend(
+                    dependencies
+                        .iter()
+                        .filter_map(FileSpec::dependency_operation),
+                );
+                let files = self.place_in_crates(files);
 
                 if files.is_empty() {
                     self.planning_state = PlanningState::Finalizing;
@@ -180,6 +367,11 @@ impl IncrementalBuildPlanner {
                             });
                     }
 
+                    self.groups = operation_groups(
+                        files
+                            .iter()
+                            .filter_map(|spec| Some((spec.group.as_deref()?, spec.path.as_str()))),
+                    );
                     self.planning_state = PlanningState::GeneratingCode {
                         files,
                         current_index: 0,
@@ -192,6 +384,7 @@ impl IncrementalBuildPlanner {
                 current_index,
             } => {
                 if *current_index >= files.len() {
+                    self.declare_new_modules();
                     self.planning_state = PlanningState::Finalizing;
                     self.stream_finalizing_step().await
                 } else {
@@ -213,31 +406,16 @@ impl IncrementalBuildPlanner {
         // Build context summary from real file states
         let context_summary = self.build_context_summary();
 
-        let prompt = format!(
-            r#"Analyze this goal and determine the best approach for incremental implementation:
-
-GOAL: {}
-
-ACTUAL FILE CONTEXT:
-{}
-
-CONTEXT:
-{}
-
-Think step-by-step about:
-1. What kind of project/files are we working with? (Use the ACTUAL FILE CONTEXT above)
-2. What files exist vs need to be created? (Check the file states provided)
-3. What's the simplest, most direct approach given the current project state?
-4. What are the key files that need to be created/modified?
-5. What's the risk level (Low/Medium/High)?
-
-Provide a brief analysis (2-3 sentences) of your approach."#,
-            self.goal,
-            context_summary,
-            self.context.join("\n")
-        );
+        let prompt = PromptTemplates::global().render(
+            PromptTemplate::PlanAnalysis,
+            serde_json::json!({
+                "goal": self.goal,
+                "file_context": context_summary,
+                "context": self.context.join("\n"),
+            }),
+        )?;
 
-        let analysis = inference_engine.generate(&prompt).await?;
+        let analysis = self.generate_redacted(inference_engine, &prompt).await?;
         let confidence = self.calculate_confidence_from_response(&analysis, "analysis");
 
         Ok(Some(IncrementalPlanStep {
@@ -279,7 +457,7 @@ Do not include any other text or explanations."#,
             self.goal, context_summary
         );
 
-        let plan_text = inference_engine.generate(&prompt).await?;
+        let plan_text = self.generate_redacted(inference_engine, &prompt).await?;
         let confidence = self.calculate_confidence_from_response(&plan_text, "planning");
 
         Ok(Some(IncrementalPlanStep {
@@ -307,25 +485,29 @@ GOAL: {}
 
 FILE CONTEXT (from filesystem scan):
 {}
-
+{}
 CRITICAL INSTRUCTIONS:
 1. Read the FILE CONTEXT above carefully - it shows which files "EXISTS" or "DOES NOT EXIST"
-2. For ANY file listed as "EXISTS" → use ACTION: update
-3. For ANY file listed as "DOES NOT EXIST" → use ACTION: create
+2. For ANY file listed as "EXISTS" → use action "update"
+3. For ANY file listed as "DOES NOT EXIST" → use action "create"
 4. If no files are listed above, infer a minimal set of files to build the goal from scratch (return at least one).
 5. NEVER guess file existence - trust the FILE CONTEXT information; if context is empty, make clear you are creating new files.
+6. Give files that must change together to stay consistent (e.g. a renamed function and every call site) the same "group" name; omit "group" otherwise.
+7. To add or remove a package, use action "add_dependency" or "remove_dependency" with the manifest (Cargo.toml, package.json, requirements.txt) as "path" and the package as "dependency"; never "update" a manifest to change its dependencies. Give a "version" only when the goal asks for one; the package manager picks the latest otherwise.
 
-RESPONSE FORMAT (required):
-FILE: path/to/file.ext
-ACTION: update|create
-REASON: brief explanation
+RESPONSE FORMAT (required), JSON only:
+{{"files": [{{"path": "path/to/file.ext", "action": "update|create|add_dependency|remove_dependency", "reason": "brief explanation", "group": "optional-name"}}]}}
 
 Do not include examples; return only the operations in the required format."#,
-            self.goal, context_summary
+            self.goal,
+            context_summary,
+            self.cargo_block()
         );
 
-        let response = inference_engine.generate(&prompt).await?;
-        let files = self.parse_file_specs(&response);
+        let prompt = self.redactor.redact(&prompt)?;
+        let files = structured_output::generate::<FileSpecs>(inference_engine, &prompt)
+            .await?
+            .files;
 
         // Validate and filter files based on actual filesystem state
         let mut filtered_files: Vec<FileSpec> = Vec::new();
@@ -362,6 +544,16 @@ Do not include examples; return only the operations in the required format."#,
                         true
                     }
                 }
+                "add_dependency" | "remove_dependency" => {
+                    let known = dependency_ops::Ecosystem::of(Path::new(&file_spec.path)).is_some();
+                    if !known || file_spec.dependency.is_none() {
+                        eprintln!(
+                            "⚠️  Skipping {} without a package or a known manifest: '{}'",
+                            file_spec.action, file_spec.path
+                        );
+                    }
+                    known && file_spec.dependency.is_some()
+                }
                 _ => {
                     eprintln!(
                         "⚠️  Skipping invalid action '{}' for file '{}'",
@@ -391,20 +583,21 @@ GOAL: {}
 Rules:
 - Return at least one file.
 - Prefer 1-5 files that together produce a runnable, testable solution.
-- Use ACTION: create for each.
+- Use action "create" for each.
 - Choose sensible names and locations based on the goal; avoid placeholders.
 - If the goal implies an app/game/UI, include an entrypoint and any supporting files needed to run without external assets.
 - Keep the list concise.
-
-FORMAT (required):
-FILE: path/to/file.ext
-ACTION: create
-REASON: brief explanation"#,
-            self.goal
+{}
+FORMAT (required), JSON only:
+{{"files": [{{"path": "path/to/file.ext", "action": "create", "reason": "brief explanation"}}]}}"#,
+            self.goal,
+            self.cargo_block()
         );
 
-        let response = inference_engine.generate(&prompt).await?;
-        let files = self.parse_file_specs(&response);
+        let prompt = self.redactor.redact(&prompt)?;
+        let files = structured_output::generate::<FileSpecs>(inference_engine, &prompt)
+            .await?
+            .files;
 
         // Ensure action is create
         let normalized = files
@@ -441,10 +634,22 @@ REASON: brief explanation"#,
         let existing_content = self
             .file_contexts
             .get(&file_spec.path)
-            .and_then(|ctx| ctx.content.as_ref());
+            .and_then(|ctx| ctx.content.clone());
+
+        // Large files get a patch, leaving the rest of the file as it is
+        if let Some(existing) = existing_content.as_deref().filter(|content| {
+            file_spec.action == "update" && content.lines().count() > PATCH_MIN_LINES
+        }) {
+            if let Some(step) = self
+                .stream_patch_step(inference_engine, file_spec, existing, step_number)
+                .await?
+            {
+                return Ok(Some(step));
+            }
+        }
 
         let (prompt, is_update) = if file_spec.action == "update" && existing_content.is_some() {
-            let content = existing_content.unwrap();
+            let content = existing_content.as_deref().unwrap_or_default();
             let lines: Vec<&str> = content.lines().collect();
             let line_count = lines.len();
             let preview = self.create_numbered_preview(content, line_count);
@@ -456,7 +661,7 @@ REASON: brief explanation"#,
 GOAL: {}
 FILE: {}
 SIZE: {} lines
-
+{}
 CURRENT FILE (numbered):
 {}
 
@@ -467,7 +672,11 @@ INSTRUCTIONS:
 - Include imports/entrypoints needed to run the file as-is.
 - If unsure, prefer a minimal runnable version over partial edits.
 "#,
-                    self.goal, file_spec.path, line_count, preview
+                    self.goal,
+                    file_spec.path,
+                    line_count,
+                    self.crate_line(&file_spec.path),
+                    preview
                 ),
                 true,
             )
@@ -497,7 +706,7 @@ INSTRUCTIONS:
 GOAL: {}
 FILE TO CREATE: {}
 LANGUAGE/TYPE: {}
-
+{}
 INSTRUCTIONS:
 - Generate a complete, working {} file that runs as-is (no placeholders or TODOs)
 - Include necessary imports, dependencies, entrypoints, and minimal wiring to run
@@ -508,15 +717,46 @@ INSTRUCTIONS:
 - Return ONLY the file content (plain text)
 
 Generate the complete file content now:"#,
-                    self.goal, file_spec.path, language_hint, language_hint, language_hint
+                    self.goal,
+                    file_spec.path,
+                    language_hint,
+                    self.crate_line(&file_spec.path),
+                    language_hint,
+                    language_hint
                 ),
                 false,
             )
         };
 
-        let code = inference_engine.generate(&prompt).await?;
+        let code = self.generate_redacted(inference_engine, &prompt).await?;
         let confidence = self.calculate_confidence_from_response(&code, "code_generation");
 
+        // Only files that parse go to the apply queue; ask for repairs first
+        let path = Path::new(&file_spec.path);
+        let mut content = clean_file_content(&code, Some(path));
+        let mut repairs = 0;
+        let syntax_error = loop {
+            match syntax_check::check(path, &content).await {
+                SyntaxCheck::Failed(report) if repairs < MAX_SYNTAX_REPAIRS => {
+                    repairs += 1;
+                    tracing::info!(
+                        "{} does not parse, requesting a repair ({}/{})",
+                        file_spec.path,
+                        repairs,
+                        MAX_SYNTAX_REPAIRS
+                    );
+                    let repair_prompt =
+                        Self::syntax_repair_prompt(&file_spec.path, &content, &report);
+                    let repaired = self
+                        .generate_redacted(inference_engine, &repair_prompt)
+                        .await?;
+                    content = clean_file_content(&repaired, Some(path));
+                }
+                SyntaxCheck::Failed(report) => break Some(report),
+                SyntaxCheck::Passed | SyntaxCheck::Skipped => break None,
+            }
+        };
+
         // Update state for next file
         if let PlanningState::GeneratingCode {
             current_index: ref mut idx,
@@ -531,17 +771,16 @@ Generate the complete file content now:"#,
             self.completed_operations
                 .push(crate::build_service::FileOperation::Create {
                     path: std::path::PathBuf::from(&file_spec.path),
-                    content: code.trim().to_string(),
+                    content: content.clone(),
                 });
         } else if file_spec.action == "update" {
-            let old_content = existing_content.map_or(String::new(), |s| s.clone());
-            let new_content = code.trim().to_string();
+            let old_content = existing_content.unwrap_or_default();
 
             self.completed_operations
                 .push(crate::build_service::FileOperation::Update {
                     path: std::path::PathBuf::from(&file_spec.path),
                     old_content,
-                    new_content,
+                    new_content: content.clone(),
                 });
         }
 
@@ -557,22 +796,235 @@ Generate the complete file content now:"#,
                 file_spec.path
             ),
             reasoning: format!(
-                "{}{} with action: {}",
+                "{}{} with action: {}{}",
                 if is_update {
                     "Updating existing file "
                 } else {
                     "Creating new file "
                 },
                 file_spec.path,
-                file_spec.action
+                file_spec.action,
+                match &syntax_error {
+                    Some(report) => format!(
+                        " (still fails its syntax check after {} repair(s): {})",
+                        repairs,
+                        report.lines().next().unwrap_or_default()
+                    ),
+                    None => String::new(),
+                }
             ),
-            code_chunk: Some(code.trim().to_string()),
+            code_chunk: Some(content.trim().to_string()),
             file_path: Some(file_spec.path.clone()),
             operation_type: Some(file_spec.action.clone()),
             confidence: Some(confidence),
         }))
     }
 
+    /// Ask for `file_spec`'s edit as SEARCH/REPLACE hunks; None when the
+    /// reply doesn't apply to `existing` or breaks its syntax, so the file is
+    /// rewritten instead
+    async fn stream_patch_step(
+        &mut self,
+        inference_engine: &infrastructure::InferenceEngine,
+        file_spec: &FileSpec,
+        existing: &str,
+        step_number: usize,
+    ) -> Result<Option<IncrementalPlanStep>> {
+        let prompt = prompts::patch_edit_prompt(&self.goal, &file_spec.path, existing);
+        let reply = self.generate_redacted(inference_engine, &prompt).await?;
+        let patched = patch::parse(&reply).and_then(|hunks| {
+            let content = patch::apply(existing, &hunks)?;
+            Ok((hunks, content))
+        });
+        let (hunks, content) = match patched {
+            Ok(patched) => patched,
+            Err(e) => {
+                tracing::info!("Rewriting {} instead of patching it: {}", file_spec.path, e);
+                return Ok(None);
+            }
+        };
+        let path = Path::new(&file_spec.path);
+        if let SyntaxCheck::Failed(report) = syntax_check::check(path, &content).await {
+            tracing::info!(
+                "Rewriting {} instead of patching it: the patched file does not parse: {}",
+                file_spec.path,
+                report.lines().next().unwrap_or_default()
+            );
+            return Ok(None);
+        }
+
+        if let PlanningState::GeneratingCode {
+            current_index: ref mut idx,
+            ..
+        } = self.planning_state
+        {
+            *idx += 1;
+        }
+        let rendered = patch::render(&hunks);
+        let confidence = self.calculate_confidence_from_response(&rendered, "code_generation");
+        self.completed_operations.push(FileOperation::Patch {
+            path: path.to_path_buf(),
+            hunks,
+        });
+
+        Ok(Some(IncrementalPlanStep {
+            step_number,
+            description: format!("Generating a patch for {}", file_spec.path),
+            reasoning: format!(
+                "Patching existing file {} ({} lines) instead of rewriting it",
+                file_spec.path,
+                existing.lines().count()
+            ),
+            code_chunk: Some(rendered),
+            file_path: Some(file_spec.path.clone()),
+            operation_type: Some("patch".to_string()),
+            confidence: Some(confidence),
+        }))
+    }
+
+    /// The workspace's crates for file planning prompts, followed by a blank
+    /// line; empty outside a Cargo workspace
+    fn cargo_block(&self) -> String {
+        self.cargo
+            .as_ref()
+            .map(|workspace| format!("\n{}\n", workspace.prompt_block()))
+            .unwrap_or_default()
+    }
+
+    /// `CRATE:` line naming the crate a Rust file belongs to; empty otherwise
+    fn crate_line(&self, path: &str) -> String {
+        let krate = self
+            .cargo
+            .as_ref()
+            .filter(|_| path.ends_with(".rs"))
+            .and_then(|workspace| workspace.crate_for(Path::new(path)));
+        match krate {
+            Some(krate) => format!(
+                "CRATE: {} (refer to its own items through `crate::`, to other workspace crates by name, and add every `use` the code needs)\n",
+                krate.name.replace('-', "_")
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Move Rust files planned outside every crate's `src` into the crate
+    /// they belong to, so the compiler sees them
+    fn place_in_crates(&mut self, files: Vec<FileSpec>) -> Vec<FileSpec> {
+        let Some(workspace) = &self.cargo else {
+            return files;
+        };
+        let mut placed_files = Vec::with_capacity(files.len());
+        for mut spec in files {
+            if let Some(placed) = workspace.place(Path::new(&spec.path)) {
+                let placed = placed.to_string_lossy().to_string();
+                eprintln!(
+                    "⚠️  Correcting: '{}' is outside the crates' src directories - moving it to '{}'",
+                    spec.path, placed
+                );
+                spec.path = placed;
+                spec.action = "create".to_string();
+                if let Ok(content) = std::fs::read_to_string(&spec.path) {
+                    spec.action = "update".to_string();
+                    self.file_contexts
+                        .entry(spec.path.clone())
+                        .or_insert(FileContext {
+                            path: spec.path.clone(),
+                            exists: true,
+                            size_bytes: content.len() as u64,
+                            line_count: content.lines().count(),
+                            content: Some(content),
+                            modified: None,
+                            operation_type: FileOperationType::Update,
+                        });
+                }
+            }
+            placed_files.push(spec);
+        }
+        placed_files
+    }
+
+    /// Declare each new Rust module in its parent module, in the parent's
+    /// planned operation when there is one and in an added update otherwise;
+    /// the two are grouped so neither is applied without the other
+    fn declare_new_modules(&mut self) {
+        let Some(workspace) = &self.cargo else {
+            return;
+        };
+        let created: Vec<std::path::PathBuf> = self
+            .completed_operations
+            .iter()
+            .filter_map(|op| match op {
+                FileOperation::Create { path, .. } => Some(path.clone()),
+                _ => None,
+            })
+            .collect();
+        for path in created {
+            let Some((parent, declaration)) = workspace.module_declaration(&path) else {
+                continue;
+            };
+            let planned = self
+                .completed_operations
+                .iter_mut()
+                .find(|op| workspace.relative(op.path()) == parent);
+            let parent = planned
+                .as_ref()
+                .map(|op| op.path().to_path_buf())
+                .unwrap_or(parent);
+            match planned {
+                Some(FileOperation::Create { content, .. })
+                | Some(FileOperation::Update {
+                    new_content: content,
+                    ..
+                }) => {
+                    if let Some(declared) =
+                        cargo_workspace::with_module_declaration(content, &declaration)
+                    {
+                        *content = declared;
+                    }
+                }
+                Some(_) => {
+                    tracing::info!(
+                        "Not declaring {} in {}: the planned edit of it is not a rewrite",
+                        path.display(),
+                        parent.display()
+                    );
+                    continue;
+                }
+                None => {
+                    let Ok(existing) = std::fs::read_to_string(workspace.root.join(&parent)) else {
+                        tracing::info!(
+                            "Not declaring {}: its parent module {} does not exist",
+                            path.display(),
+                            parent.display()
+                        );
+                        continue;
+                    };
+                    let Some(declared) =
+                        cargo_workspace::with_module_declaration(&existing, &declaration)
+                    else {
+                        continue;
+                    };
+                    self.completed_operations.push(FileOperation::Update {
+                        path: parent.clone(),
+                        old_content: existing,
+                        new_content: declared,
+                    });
+                }
+            }
+            let grouped = |p: &Path| {
+                self.groups
+                    .iter()
+                    .any(|group| group.paths.iter().any(|member| member == p))
+            };
+            if !grouped(&path) && !grouped(&parent) {
+                self.groups.push(OperationGroup {
+                    name: format!("declare {}", path.display()),
+                    paths: vec![path.clone(), parent],
+                });
+            }
+        }
+    }
+
     async fn stream_finalizing_step(&self) -> Result<Option<IncrementalPlanStep>> {
         let operations_count = self.completed_operations.len();
         Ok(Some(IncrementalPlanStep {
@@ -589,50 +1041,6 @@ Generate the complete file content now:"#,
         }))
     }
 
-    fn parse_file_specs(&self, response: &str) -> Vec<FileSpec> {
-        let mut files = Vec::new();
-        let mut current_file: Option<FileSpec> = None;
-
-        for line in response.lines() {
-            let line = line.trim();
-            if line.starts_with("FILE:") {
-                if let Some(file) = current_file.take() {
-                    files.push(file);
-                }
-                let path = line.strip_prefix("FILE:").unwrap_or("").trim().to_string();
-                current_file = Some(FileSpec {
-                    path,
-                    action: String::new(),
-                    reason: String::new(),
-                });
-            } else if line.starts_with("ACTION:") && current_file.is_some() {
-                let action = line
-                    .strip_prefix("ACTION:")
-                    .unwrap_or("")
-                    .trim()
-                    .to_string();
-                if let Some(ref mut file) = current_file {
-                    file.action = action;
-                }
-            } else if line.starts_with("REASON:") && current_file.is_some() {
-                let reason = line
-                    .strip_prefix("REASON:")
-                    .unwrap_or("")
-                    .trim()
-                    .to_string();
-                if let Some(ref mut file) = current_file {
-                    file.reason = reason;
-                }
-            }
-        }
-
-        if let Some(file) = current_file {
-            files.push(file);
-        }
-
-        files
-    }
-
     fn create_operation_from_code(
         &self,
         file_spec: &FileSpec,
@@ -641,7 +1049,7 @@ Generate the complete file content now:"#,
         match file_spec.action.as_str() {
             "create" => Ok(FileOperation::Create {
                 path: std::path::PathBuf::from(&file_spec.path),
-                content: code.to_string(),
+                content: clean_file_content(code, Some(Path::new(&file_spec.path))),
             }),
             "update" => {
                 // For updates, we'd need to read existing content
@@ -649,7 +1057,7 @@ Generate the complete file content now:"#,
                 Ok(FileOperation::Update {
                     path: std::path::PathBuf::from(&file_spec.path),
                     old_content: String::new(), // Would read actual content
-                    new_content: code.to_string(),
+                    new_content: clean_file_content(code, Some(Path::new(&file_spec.path))),
                 })
             }
             _ => Err(anyhow::anyhow!("Unsupported action: {}", file_spec.action)),
@@ -864,6 +1272,16 @@ Generate the complete file content now:"#,
         preview
     }
 
+    /// Prompt to fix `content` of the file at `path` given the checker report
+    fn syntax_repair_prompt(path: &str, content: &str, report: &str) -> String {
+        format!(
+            "The generated file {} does not parse:\n\n{}\n\nCurrent content:\n{}\n\n\
+             Return the complete corrected file content only, without explanations \
+             or markdown fences.",
+            path, report, content
+        )
+    }
+
     /// Create numbered preview of file content for precise editing
     fn create_numbered_preview(&self, content: &str, total_lines: usize) -> String {
         let max_preview_lines = self.config.context.max_file_preview_lines;
@@ -1008,6 +1426,11 @@ Generate the complete file content now:"#,
         &self.completed_operations
     }
 
+    /// Atomic groups among the completed operations
+    pub fn operation_groups(&self) -> Vec<OperationGroup> {
+        self.groups.clone()
+    }
+
     pub fn context_stats(&self) -> (usize, usize, usize, String, String) {
         let files_scanned = self.file_contexts.len();
         let files_analyzed = self
@@ -1032,6 +1455,12 @@ pub struct AgentExecutionContext {
     pub config: Config,
     pub rag_service: Option<Arc<RagService>>,
     pub sandbox: Sandbox,
+    /// Results of read-only tool calls made during this run
+    pub tool_cache: Option<Arc<ToolCache>>,
+    /// Retries of failed tool calls made during this run
+    pub retries: std::sync::Mutex<Vec<ToolRetry>>,
+    /// Questions asked with `ask_user` during this run, with their answers
+    pub questions: std::sync::Mutex<Vec<String>>,
 }
 
 impl AgentExecutionContext {
@@ -1040,28 +1469,51 @@ impl AgentExecutionContext {
         config: Config,
         rag_service: Option<Arc<RagService>>,
     ) -> Self {
+        let sandbox = Sandbox::for_security(&config.security);
+        let tool_cache = ToolCache::for_scope(config.security.agent_execution.tool_cache);
         Self {
             inference_engine,
             config,
             rag_service,
-            sandbox: Sandbox::new(),
+            sandbox,
+            tool_cache,
+            retries: Default::default(),
+            questions: Default::default(),
         }
     }
+
+    fn questions(&self) -> Vec<String> {
+        self.questions
+            .lock()
+            .map(|questions| questions.clone())
+            .unwrap_or_default()
+    }
+
+    /// Retries recorded so far, leaving none behind
+    pub fn take_retries(&self) -> Vec<ToolRetry> {
+        self.retries
+            .lock()
+            .map(|mut retries| std::mem::take(&mut *retries))
+            .unwrap_or_default()
+    }
 }
 
 impl AgentService {
     pub fn new(inference_engine: infrastructure::InferenceEngine) -> Self {
         println!("📊 Gathering system context...");
         let system_context = infrastructure::config::SystemContext::gather();
+        let config = Config::load();
 
         Self {
             inference_engine,
             rag_service: None,
             semantic_memory: None,
-            config: Config::load(),
+            failure_handler: SafeFailureHandler::new()
+                .with_retry_policies(config.power_user.retry_policies.clone()),
+            config,
             agent_controller: AgentController::new(),
-            failure_handler: SafeFailureHandler::new(),
             system_context,
+            run_checkpoint: None,
         }
     }
 
@@ -1072,32 +1524,29 @@ impl AgentService {
     ) -> Self {
         println!("📊 Gathering system context...");
         let system_context = infrastructure::config::SystemContext::gather();
+        let config = Config::load();
 
         Self {
             inference_engine,
             rag_service: None,
             semantic_memory,
-            config: Config::load(),
+            failure_handler: SafeFailureHandler::new()
+                .with_retry_policies(config.power_user.retry_policies.clone()),
+            config,
             agent_controller: AgentController::new(),
-            failure_handler: SafeFailureHandler::new(),
             system_context,
+            run_checkpoint: None,
         }
     }
 
     /// Lightweight system context to avoid prompt bloat
     fn compact_system_context(&self) -> String {
-        format!(
-            "user={}@{}, os={} {}, distro={}, pkg_mgr={}, cwd={}, shell={}, display={}",
-            self.system_context.user,
-            self.system_context.hostname,
-            self.system_context.os_type,
-            self.system_context.kernel,
-            self.system_context.distro_id,
-            self.system_context.package_manager,
-            self.system_context.current_dir,
-            self.system_context.shell,
-            self.system_context.display_server
-        )
+        prompts::compact_system_context(&self.system_context)
+    }
+
+    /// What this installation can do, so plans stay within it
+    fn capabilities_context(&self) -> String {
+        Capabilities::gather(&self.config).prompt_block()
     }
 
     pub fn with_rag_service(
@@ -1106,15 +1555,18 @@ impl AgentService {
     ) -> Self {
         println!("📊 Gathering system context...");
         let system_context = infrastructure::config::SystemContext::gather();
+        let config = Config::load();
 
         Self {
             inference_engine,
             rag_service: None,
             semantic_memory: None,
-            config: Config::load(),
+            failure_handler: SafeFailureHandler::new()
+                .with_retry_policies(config.power_user.retry_policies.clone()),
+            config,
             agent_controller: AgentController::new(),
-            failure_handler: SafeFailureHandler::new(),
             system_context,
+            run_checkpoint: None,
         }
     }
 
@@ -1128,6 +1580,8 @@ USER REQUEST: {}
 SYSTEM CONTEXT:
 {}
 
+{}
+
 CRITICAL INSTRUCTIONS:
 1. Generate ONLY the command - no explanations, no markdown
 2. Use the actual paths and file names from the system context
@@ -1139,6 +1593,7 @@ CRITICAL INSTRUCTIONS:
 Generate the command now:"#,
             request,
             self.compact_system_context(),
+            self.capabilities_context(),
             self.system_context.package_manager,
             self.system_context.current_dir
         );
@@ -1179,8 +1634,9 @@ Generate the command now:"#,
             }
         }
 
-        let registry_tools = ToolRegistry::new().list_tools();
-        let allowed: std::collections::HashSet<String> = registry_tools.into_iter().collect();
+        let registry = ToolRegistry::new();
+        let allowed: std::collections::HashSet<String> =
+            registry.list_tools().into_iter().collect();
 
         let base_defs = vec![
             ToolDefinition {
@@ -1301,12 +1757,87 @@ Generate the command now:"#,
                     vec![],
                 ),
             },
+            ToolDefinition {
+                name: "todo_list".to_string(),
+                description: "Read or extend the project TODO list of deferred work".to_string(),
+                parameters: params(
+                    vec![
+                        param("action", "list (default), add or done"),
+                        param("text", "Item to add, e.g. 'add tests for X'"),
+                        param("index", "Number of the item to mark done"),
+                    ],
+                    vec![],
+                ),
+            },
+            ToolDefinition {
+                name: "config_edit".to_string(),
+                description: "Change values in a TOML, YAML or JSON config file by path \
+                    instead of rewriting it, keeping comments and key order"
+                    .to_string(),
+                parameters: params(
+                    vec![
+                        param("path", "Config file path"),
+                        param(
+                            "edits",
+                            "One per line, e.g. 'set server.port=8081' or 'delete features.beta'",
+                        ),
+                        param("dry_run", "true to only show the diff"),
+                    ],
+                    vec!["path", "edits"],
+                ),
+            },
         ];
 
+        let mcp_defs = registry.mcp_tools().map(|tool| ToolDefinition {
+            name: tool.qualified_name(),
+            description: tool.info.description.clone(),
+            parameters: ToolParameters {
+                param_type: "object".to_string(),
+                properties: tool
+                    .parameters()
+                    .into_iter()
+                    .map(|(name, param_type, description)| {
+                        (
+                            name,
+                            ParameterProperty {
+                                param_type,
+                                description,
+                                enum_values: None,
+                            },
+                        )
+                    })
+                    .collect(),
+                required: tool.required(),
+            },
+        });
+
+        // Handled by the agent itself rather than the registry
+        let asks_user = self
+            .config
+            .security
+            .agent_execution
+            .question_timeout_seconds
+            > 0;
+        let ask_user_def = asks_user.then(|| ToolDefinition {
+            name: ASK_USER_TOOL.to_string(),
+            description: "Ask the user a question and wait for the answer, when the goal \
+                leaves open a choice only they can make"
+                .to_string(),
+            parameters: params(
+                vec![
+                    param("question", "The question, e.g. 'Which database to use?'"),
+                    param("options", "Optional answers, e.g. 'PostgreSQL | SQLite'"),
+                ],
+                vec!["question"],
+            ),
+        });
+
         let mut dedup = std::collections::HashSet::new();
         base_defs
             .into_iter()
             .filter(|def| allowed.contains(&def.name))
+            .chain(mcp_defs)
+            .chain(ask_user_def)
             .filter(|def| dedup.insert(def.name.clone()))
             .collect()
     }
@@ -1347,9 +1878,204 @@ Generate the command now:"#,
             tool_results,
             final_response: agent_result.final_response,
             confidence: agent_result.confidence_score,
+            retries: execution_context.take_retries(),
+        })
+    }
+
+    /// Like [`Self::process_request`], with the goal decomposed and each
+    /// subtask worked by a bounded sub-agent; a goal that does not split
+    /// runs as usual
+    pub async fn process_request_with_sub_agents(
+        &self,
+        request: &AgentRequest,
+    ) -> Result<AgentResponse> {
+        let tasks = TaskDecomposer::new(DecompositionStrategy::Intelligent)
+            .decompose(&request.goal)
+            .unwrap_or_default();
+        if tasks.len() < 2 {
+            return self.process_request(request).await;
+        }
+        let specs = sub_agents::plan(
+            tasks,
+            &self.default_tool_definitions(),
+            SubAgentLimits::from_config(&self.config.security.agent_execution),
+        )?;
+        let execution_context = AgentExecutionContext::new(
+            self.inference_engine.clone(),
+            self.config.clone(),
+            self.rag_service.clone(),
+        );
+        infrastructure::mcp_client::mount_configured(
+            &self.config.power_user.mcp_servers,
+            &execution_context.sandbox,
+        )
+        .await;
+
+        // A resumed run keeps the reports of the sub-agents that finished
+        let resumed = self
+            .run_checkpoint
+            .as_ref()
+            .map(|checkpoint| checkpoint.borrow().clone());
+        let mut reports: Vec<SubAgentReport> = resumed
+            .iter()
+            .flat_map(|checkpoint| &checkpoint.subtask_reports)
+            .filter_map(|report| serde_json::from_value(report.clone()).ok())
+            .collect();
+        let mut all_tool_calls = Vec::new();
+        let mut all_tool_results = resumed
+            .map(|checkpoint| checkpoint.tool_results)
+            .unwrap_or_default();
+        for spec in &specs {
+            if let Some(report) = reports.iter().find(|report| report.task_id == spec.task.id) {
+                println!("🧩 Finished before resuming: {}", report.line());
+                continue;
+            }
+            println!("🧩 Sub-agent {}: {}", spec.task.id, spec.task.description);
+            let (report, tool_calls, tool_results) = self
+                .run_sub_agent(&request.goal, spec, &reports, &execution_context)
+                .await;
+            println!("   {}", report.line());
+            if let Some(checkpoint) = &self.run_checkpoint {
+                let saved = serde_json::to_value(&report).unwrap_or_default();
+                checkpoint
+                    .send_modify(|checkpoint| checkpoint.complete_subtask(saved, &tool_results));
+            }
+            reports.push(report);
+            all_tool_calls.extend(tool_calls);
+            all_tool_results.extend(tool_results);
+        }
+
+        let reasoning: Vec<String> = reports.iter().map(SubAgentReport::line).collect();
+        let final_response = self
+            .generate_final_response(&request.goal, &reasoning, &all_tool_results)
+            .await
+            .unwrap_or_else(|e| format!("Failed to generate final response: {e}"));
+        Ok(AgentResponse {
+            confidence: self.calculate_confidence(&reasoning, &all_tool_results),
+            reasoning,
+            tool_calls: all_tool_calls,
+            tool_results: all_tool_results,
+            final_response,
+            retries: execution_context.take_retries(),
         })
     }
 
+    /// Work one subtask in a conversation of its own, with the spec's tools
+    /// and limits; failures end up in the report rather than an error
+    async fn run_sub_agent(
+        &self,
+        goal: &str,
+        spec: &SubAgentSpec,
+        reports: &[SubAgentReport],
+        exec_context: &AgentExecutionContext,
+    ) -> (SubAgentReport, Vec<ToolCall>, Vec<ToolResult>) {
+        let task_goal = sub_agents::briefing(goal, spec);
+        let window = ContextWindow::new(&self.config.context, spec.limits.context_tokens);
+        let fitted = window
+            .fit(
+                PromptSegments {
+                    fixed: task_goal.clone(),
+                    turns: sub_agents::dependency_reports(spec, reports),
+                    chunks: Vec::new(),
+                },
+                &self.inference_engine,
+            )
+            .await;
+        if let Some(notice) = &fitted.notice {
+            println!("   ⚠️ {}", notice);
+        }
+        let mut agent_context = AgentContext {
+            available_tools: spec.tools.clone(),
+            conversation_history: fitted
+                .segments
+                .turns
+                .into_iter()
+                .chain(std::iter::once(task_goal.clone()))
+                .map(|content| ConversationMessage {
+                    role: "user".to_string(),
+                    content,
+                    tool_calls: None,
+                    tool_call_id: None,
+                })
+                .collect(),
+            working_memory: std::collections::HashMap::new(),
+        };
+
+        let mut report = SubAgentReport {
+            task_id: spec.task.id.clone(),
+            description: spec.task.description.clone(),
+            success: false,
+            summary: String::new(),
+            tools_used: Vec::new(),
+            iterations: 0,
+            error: None,
+        };
+        let mut all_tool_calls = Vec::new();
+        let mut all_tool_results = Vec::new();
+        for _ in 0..spec.limits.max_iterations {
+            if let Err(e) = self.agent_controller.check_token_budget() {
+                report.error = Some(e.to_string());
+                break;
+            }
+            report.iterations += 1;
+            let reasoning = match self.generate_reasoning(&task_goal, &agent_context).await {
+                Ok(reasoning) => reasoning,
+                Err(e) => {
+                    report.error = Some(e.to_string());
+                    break;
+                }
+            };
+            let mut tool_calls = if self.needs_tools(&task_goal, &reasoning, &agent_context) {
+                self.plan_tool_calls(&task_goal, &reasoning, &agent_context, exec_context)
+            } else {
+                Vec::new()
+            };
+            if tool_calls.is_empty() {
+                tool_calls = self
+                    .plan_model_tool_call(&task_goal, &agent_context, exec_context)
+                    .await;
+            }
+            // Tools outside the subtask's scope are never run
+            let mut tool_calls = self.filter_valid_tool_calls(&agent_context, tool_calls);
+            tool_calls.truncate(spec.limits.max_tools_per_iteration);
+            let tool_results = self
+                .execute_tool_calls(&tool_calls, &mut agent_context, exec_context)
+                .await
+                .unwrap_or_default();
+            report
+                .tools_used
+                .extend(tool_calls.iter().map(|call| call.name.clone()));
+
+            match self
+                .generate_final_response(&task_goal, &reasoning, &tool_results)
+                .await
+            {
+                Ok(answer) => {
+                    report.summary = answer.clone();
+                    report.success = tool_results.iter().all(|result| result.success);
+                    report.error = None;
+                    agent_context
+                        .conversation_history
+                        .push(ConversationMessage {
+                            role: "assistant".to_string(),
+                            content: answer,
+                            tool_calls: None,
+                            tool_call_id: None,
+                        });
+                }
+                Err(e) => report.error = Some(e.to_string()),
+            }
+            all_tool_calls.extend(tool_calls);
+            let called_tools = !tool_results.is_empty();
+            all_tool_results.extend(tool_results);
+            // Nothing more to look at: another round would repeat this one
+            if !called_tools {
+                break;
+            }
+        }
+        (report, all_tool_calls, all_tool_results)
+    }
+
     /// Create an incremental build planner for streaming planning
     pub async fn plan_build_incremental(&self, goal: &str) -> Result<IncrementalBuildPlanner> {
         let mut retrieved_context = Vec::new();
@@ -1360,6 +2086,10 @@ Generate the command now:"#,
             self.compact_system_context()
         ));
 
+        if let Some(todo) = TodoList::for_current_project().planning_context() {
+            retrieved_context.push(todo);
+        }
+
         // Step 1: Prepare file context by reading existing files
         println!("🔍 Analyzing project structure and existing files...");
         let file_contexts = self.prepare_file_context(goal).await?;
@@ -1422,6 +2152,11 @@ Generate the command now:"#,
         let mut retrieved_context = Vec::new();
         let mut planning_logs = Vec::new();
 
+        if let Some(todo) = TodoList::for_current_project().planning_context() {
+            retrieved_context.push(todo);
+            planning_logs.push("Included open TODO items".to_string());
+        }
+
         // Step 1: Retrieve relevant context using RAG or fast rg search
         let keywords = self.extract_keywords_from_goal(goal);
         let use_rag = self.should_use_rag(&keywords);
@@ -1882,48 +2617,11 @@ Generate the command now:"#,
     }
 
     fn create_build_planning_prompt(&self, goal: &str, context: &[String]) -> String {
-        let context_str = if context.is_empty() {
-            "No additional context available.".to_string()
-        } else {
-            context.join("\n\n")
-        };
-
-        format!(
-            r#"You are an expert engineer producing a compact, actionable build plan.
-
-GOAL:
-{goal}
-
-SYSTEM:
-{system}
-
-CONTEXT:
-{context}
-
-OUTPUT (plain text, no JSON):
-Build Plan:
-- Step 1: ...
-- Step 2: ...
-
-Files:
-- path: relative/path.ext
-- action: create|update
-- reason: short note
-- content in a fenced block:
-```file:path=relative/path.ext;action=create
-<full post-change content>
-```
-
-Safety: risks/backups/rollback
-Estimate: size/time
-Confidence: percentage
-
-Rules: keep it concise and deterministic; only include real files; if context is insufficient, reply 'Insufficient context to plan' and stop (do not invent files or behavior); if you cannot provide full content, say so and stop; prefer package manager {pkg_mgr}; consider display server {display_srv} for GUI hints."#,
-            goal = goal,
-            system = self.compact_system_context(),
-            context = context_str,
-            pkg_mgr = self.system_context.package_manager,
-            display_srv = self.system_context.display_server
+        prompts::build_planning_prompt(
+            goal,
+            context,
+            &self.system_context,
+            &self.capabilities_context(),
         )
     }
 
@@ -1954,6 +2652,7 @@ Rules: keep it concise and deterministic; only include real files; if context is
 
     fn parse_build_plan(&self, plan_text: &str, goal: &str) -> Result<BuildPlan> {
         let mut operations = Vec::new();
+        let mut members = Vec::new();
         let mut description = String::from("Build plan (markdown)");
         let estimated_risk = RiskLevel::Low;
 
@@ -1980,18 +2679,46 @@ Rules: keep it concise and deterministic; only include real files; if context is
 
             let mut path = "";
             let mut action = "create";
+            let mut group = None;
             for part in header.split(';') {
                 let part = part.trim();
                 if let Some(rest) = part.strip_prefix("path=") {
                     path = rest;
                 } else if let Some(rest) = part.strip_prefix("action=") {
                     action = rest;
+                } else if let Some(rest) = part.strip_prefix("group=") {
+                    group = Some(rest);
                 }
             }
 
             if path.is_empty() {
                 continue;
             }
+            if let Some(group) = group {
+                members.push((group, path));
+            }
+
+            // One package per line, e.g. `serde` or `serde@1.0`
+            if matches!(action, "add_dependency" | "remove_dependency") {
+                for line in content.lines().map(str::trim) {
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    let (name, version) = dependency_spec(line);
+                    operations.push(match action {
+                        "add_dependency" => FileOperation::AddDependency {
+                            manifest: std::path::PathBuf::from(path),
+                            name,
+                            version,
+                        },
+                        _ => FileOperation::RemoveDependency {
+                            manifest: std::path::PathBuf::from(path),
+                            name,
+                        },
+                    });
+                }
+                continue;
+            }
 
             let op = match action {
                 "update" => {
@@ -1999,15 +2726,20 @@ Rules: keep it concise and deterministic; only include real files; if context is
                     FileOperation::Update {
                         path: std::path::PathBuf::from(path),
                         old_content: existing,
-                        new_content: content.to_string(),
+                        new_content: clean_file_content(content, Some(Path::new(path))),
                     }
                 }
                 "delete" => FileOperation::Delete {
                     path: std::path::PathBuf::from(path),
                 },
+                "patch" => FileOperation::Patch {
+                    path: std::path::PathBuf::from(path),
+                    hunks: patch::parse(content)
+                        .map_err(|e| anyhow::anyhow!("Unreadable patch for {}: {}", path, e))?,
+                },
                 _ => FileOperation::Create {
                     path: std::path::PathBuf::from(path),
-                    content: content.to_string(),
+                    content: clean_file_content(content, Some(Path::new(path))),
                 },
             };
 
@@ -2025,6 +2757,7 @@ Rules: keep it concise and deterministic; only include real files; if context is
             operations,
             description,
             estimated_risk,
+            groups: operation_groups(members),
         })
     }
 
@@ -2235,6 +2968,16 @@ Rules: keep it concise and deterministic; only include real files; if context is
             );
         }
 
+        if lower_goal.contains("todo") || lower_goal.contains("deferred") {
+            self.maybe_push_call(
+                &context.available_tools,
+                &mut pending_calls,
+                "todo_list",
+                HashMap::new(),
+                "Check deferred work",
+            );
+        }
+
         let has_calls = !pending_calls.is_empty();
 
         if !has_calls && !context.available_tools.is_empty() {
@@ -2258,10 +3001,10 @@ Rules: keep it concise and deterministic; only include real files; if context is
     pub async fn execute_tool_calls(
         &self,
         tool_calls: &[ToolCall],
-        _context: &mut AgentContext,
-        _exec_context: &AgentExecutionContext,
+        context: &mut AgentContext,
+        exec_context: &AgentExecutionContext,
     ) -> Result<Vec<ToolResult>> {
-        let registry = ToolRegistry::new();
+        let registry = ToolRegistry::new().with_cache(exec_context.tool_cache.clone());
         let mut results = Vec::new();
 
         for tool_call in tool_calls {
@@ -2273,14 +3016,41 @@ Rules: keep it concise and deterministic; only include real files; if context is
                 };
                 params.insert(k.clone(), as_str);
             }
+            if tool_call.name == ASK_USER_TOOL {
+                results.push(
+                    self.ask_user(&tool_call.id, &params, context, exec_context)
+                        .await,
+                );
+                continue;
+            }
 
-            let args = ToolArgs {
-                parameters: params,
-                timeout: Some(std::time::Duration::from_secs(30)),
-                working_directory: Some(self.system_context.current_dir.clone()),
+            let run = |tool: String, parameters| {
+                let args = ToolArgs {
+                    parameters,
+                    timeout: Some(std::time::Duration::from_secs(30)),
+                    working_directory: Some(self.system_context.current_dir.clone()),
+                };
+                let registry = &registry;
+                async move { registry.execute_tool(&tool, args).await }
             };
+            let repair = |error, parameters| {
+                self.repair_tool_arguments(
+                    &*context,
+                    &tool_call.name,
+                    error,
+                    parameters,
+                    exec_context,
+                )
+            };
+            let (result, retries) = self
+                .failure_handler
+                .run_tool_call(&tool_call.id, &tool_call.name, params, run, repair)
+                .await;
+            if let Ok(mut recorded) = exec_context.retries.lock() {
+                recorded.extend(retries);
+            }
 
-            match registry.execute_tool(&tool_call.name, args).await {
+            match result {
                 Ok(output) => {
                     results.push(ToolResult {
                         tool_call_id: tool_call.id.clone(),
@@ -2309,6 +3079,121 @@ Rules: keep it concise and deterministic; only include real files; if context is
         Ok(results)
     }
 
+    /// Pause the run until the user answers the question of an `ask_user`
+    /// call, and add the answer to the conversation
+    async fn ask_user(
+        &self,
+        tool_call_id: &str,
+        params: &HashMap<String, String>,
+        context: &mut AgentContext,
+        exec_context: &AgentExecutionContext,
+    ) -> ToolResult {
+        let failed = |error: String| ToolResult {
+            tool_call_id: tool_call_id.to_string(),
+            success: false,
+            result: Value::Null,
+            error: Some(format!("{}: {}", ASK_USER_TOOL, error)),
+        };
+        let question = params.get("question").map_or("", |q| q.trim());
+        if question.is_empty() {
+            return failed("a question is required".to_string());
+        }
+        if exec_context.questions().len() >= ask_user::MAX_QUESTIONS {
+            return failed(format!(
+                "already asked {} questions; go on with your best judgement",
+                ask_user::MAX_QUESTIONS
+            ));
+        }
+
+        let options = ask_user::parse_options(params.get("options").map(String::as_str));
+        let execution = &self.config.security.agent_execution;
+        let timeout = std::time::Duration::from_secs(execution.question_timeout_seconds);
+        let queue = QuestionQueue::open_default();
+        let answered = match self
+            .agent_controller
+            .pause(ask_user::ask(&queue, question, &options, timeout))
+            .await
+        {
+            Ok(answered) => answered,
+            Err(e) => return failed(e.to_string()),
+        };
+        match &answered {
+            Some(answered) => println!(
+                "💬 {} ({})",
+                answered.answer.as_deref().unwrap_or_default(),
+                answered.answered_by.as_deref().unwrap_or("unknown")
+            ),
+            None => println!("💬 No answer; going on without one"),
+        }
+
+        let message = ask_user::context_message(question, answered.as_ref());
+        context.conversation_history.push(ConversationMessage {
+            role: "user".to_string(),
+            content: message.clone(),
+            tool_calls: None,
+            tool_call_id: None,
+        });
+        if let Ok(mut questions) = exec_context.questions.lock() {
+            questions.push(message.clone());
+        }
+        ToolResult {
+            tool_call_id: tool_call_id.to_string(),
+            success: answered.is_some(),
+            result: json!({
+                "tool": ASK_USER_TOOL,
+                "stdout": answered.and_then(|answered| answered.answer).unwrap_or_default(),
+                "message": message,
+            }),
+            error: None,
+        }
+    }
+
+    /// Arguments the model proposes for a call of `tool` that failed with
+    /// `error`; None when it has no fix
+    async fn repair_tool_arguments(
+        &self,
+        context: &AgentContext,
+        tool: &str,
+        error: String,
+        parameters: HashMap<String, String>,
+        exec_context: &AgentExecutionContext,
+    ) -> Option<HashMap<String, String>> {
+        let definition = context
+            .available_tools
+            .iter()
+            .find(|definition| definition.name == tool)
+            .map(|definition| serde_json::to_string(&definition.parameters).unwrap_or_default())
+            .unwrap_or_default();
+        let prompt = format!(
+            "The tool {} failed.\nARGUMENTS: {}\nERROR: {}\nSCHEMA: {}\n\nAnswer with corrected arguments in \"arguments\", or with the same arguments when the error is not caused by them.",
+            tool,
+            serde_json::to_string(&parameters).unwrap_or_default(),
+            error,
+            definition
+        );
+        match structured_output::generate::<RepairedArguments>(
+            &exec_context.inference_engine,
+            &prompt,
+        )
+        .await
+        {
+            Ok(repaired) => Some(
+                repaired
+                    .arguments
+                    .into_iter()
+                    .map(|(name, value)| match value {
+                        Value::String(value) => (name, value),
+                        other => (name, other.to_string()),
+                    })
+                    .collect(),
+            ),
+            Err(e) => {
+                eprintln!("⚠️  Could not repair arguments of {}: {}", tool, e);
+                None
+            }
+        }
+    }
+
     /// Generate final response
     pub async fn generate_final_response(
         &self,
@@ -2421,6 +3306,69 @@ Respond now."#,
         facts
     }
 
+    /// Let the model pick one of the MCP server tools for `goal`, or ask the
+    /// user about it; nothing when neither is available or fits
+    async fn plan_model_tool_call(
+        &self,
+        goal: &str,
+        context: &AgentContext,
+        exec_context: &AgentExecutionContext,
+    ) -> Vec<ToolCall> {
+        let questions = exec_context.questions();
+        let tools: Vec<&ToolDefinition> = context
+            .available_tools
+            .iter()
+            .filter(|tool| {
+                tool.name.starts_with("mcp__")
+                    || (tool.name == ASK_USER_TOOL && questions.len() < ask_user::MAX_QUESTIONS)
+            })
+            .collect();
+        if tools.is_empty() {
+            return Vec::new();
+        }
+        let listing = tools
+            .iter()
+            .map(|tool| {
+                format!(
+                    "- {}: {} (arguments: {})",
+                    tool.name,
+                    tool.description,
+                    serde_json::to_string(&tool.parameters).unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut prompt = format!("GOAL: {}\n\n", goal);
+        if !questions.is_empty() {
+            prompt.push_str(&format!("ALREADY ASKED:\n{}\n\n", questions.join("\n")));
+        }
+        prompt.push_str(&format!(
+            "TOOLS:\n{}\n\nIf one of these tools helps with the goal, answer with its name in \"tool\" and its arguments in \"arguments\". Use {} only for a choice the goal leaves open that you cannot settle yourself. Otherwise answer with an empty \"tool\".",
+            listing, ASK_USER_TOOL
+        ));
+        let choice = match structured_output::generate::<ToolChoice>(
+            &exec_context.inference_engine,
+            &prompt,
+        )
+        .await
+        {
+            Ok(choice) => choice,
+            Err(e) => {
+                eprintln!("⚠️  Could not choose a tool: {}", e);
+                return Vec::new();
+            }
+        };
+        let mut calls = Vec::new();
+        self.maybe_push_call(
+            &context.available_tools,
+            &mut calls,
+            choice.tool.trim(),
+            choice.arguments.into_iter().collect(),
+            &choice.reason,
+        );
+        calls
+    }
+
     fn maybe_push_call(
         &self,
         available_tools: &[ToolDefinition],
@@ -2461,6 +3409,12 @@ Respond now."#,
         request: &AgentRequest,
         exec_context: Arc<AgentExecutionContext>,
     ) -> Result<(AgentResult, Vec<ToolCall>, Vec<ToolResult>)> {
+        infrastructure::mcp_client::mount_configured(
+            &self.config.power_user.mcp_servers,
+            &exec_context.sandbox,
+        )
+        .await;
+
         // Build initial agent context with available tools + conversation.
         let mut agent_context = AgentContext {
             available_tools: self.default_tool_definitions(),
@@ -2468,47 +3422,67 @@ Respond now."#,
             working_memory: std::collections::HashMap::new(),
         };
 
-        // Retrieve relevant conversation memories if semantic memory is available
-        if let Some(semantic_memory) = &self.semantic_memory {
-            if let Some(conversation_id) = &request.conversation_id {
-                println!(
-                    "🧠 Retrieving conversation history for: {}",
-                    conversation_id
-                );
-                match semantic_memory
-                    .get_conversation_history(conversation_id)
-                    .await
-                {
-                    Ok(memories) => {
-                        println!("📚 Retrieved {} conversation memories", memories.len());
-                        // Convert memories back to conversation messages
-                        for memory in memories {
-                            agent_context
-                                .conversation_history
-                                .push(ConversationMessage {
-                                    role: memory.role,
-                                    content: memory.content,
-                                    tool_calls: memory.tool_calls,
-                                    tool_call_id: memory.tool_call_id,
-                                });
+        // Retrieve relevant conversation memories if semantic memory is
+        // available and the memory scope allows it
+        let scope = self.config.context.memory_scope;
+        let limit = self.config.context.memory_limit;
+        let mut used_memories = Vec::new();
+        match &self.semantic_memory {
+            Some(_) if scope == MemoryScope::Off => {
+                println!("🧠 Memory off: no earlier conversations used");
+            }
+            Some(semantic_memory) => {
+                if let Some(conversation_id) = &request.conversation_id {
+                    println!(
+                        "🧠 Retrieving conversation history for: {}",
+                        conversation_id
+                    );
+                    match semantic_memory
+                        .get_conversation_history(conversation_id)
+                        .await
+                    {
+                        Ok(memories) => {
+                            // Convert memories back to conversation messages
+                            for memory in memories {
+                                used_memories.push(memory.label());
+                                agent_context
+                                    .conversation_history
+                                    .push(ConversationMessage {
+                                        role: memory.role,
+                                        content: memory.content,
+                                        tool_calls: memory.tool_calls,
+                                        tool_call_id: memory.tool_call_id,
+                                    });
+                            }
+                        }
+                        Err(e) => {
+                            println!("⚠️ Failed to retrieve conversation history: {}", e);
                         }
                     }
-                    Err(e) => {
-                        println!("⚠️ Failed to retrieve conversation history: {}", e);
-                    }
-                }
-            } else {
-                // No conversation ID, try to find relevant past conversations
-                println!("🧠 Searching for relevant conversation context...");
-                match semantic_memory
-                    .retrieve_relevant_memories(goal, None, 5)
-                    .await
-                {
-                    Ok(memories) => {
-                        if !memories.is_empty() {
-                            println!("📚 Found {} relevant conversation memories", memories.len());
+                } else {
+                    // No conversation ID, try to find relevant past conversations
+                    println!(
+                        "🧠 Searching for relevant conversation context ({})...",
+                        scope.label()
+                    );
+                    let project = infrastructure::config::find_project_root();
+                    // Other projects' memories are filtered out after the search
+                    let candidates = match scope {
+                        MemoryScope::ProjectOnly => limit * 4,
+                        _ => limit,
+                    };
+                    match semantic_memory
+                        .retrieve_relevant_memories(goal, None, candidates)
+                        .await
+                    {
+                        Ok(memories) => {
                             // Add relevant memories as system context
-                            for memory in memories {
+                            for memory in memories
+                                .into_iter()
+                                .filter(|memory| memory.allowed(scope, project.as_deref()))
+                                .take(limit)
+                            {
+                                used_memories.push(memory.label());
                                 let context_message = format!(
                                     "Previous conversation context ({}): {}",
                                     memory.role, memory.content
@@ -2523,10 +3497,77 @@ Respond now."#,
                                     });
                             }
                         }
+                        Err(e) => {
+                            println!("⚠️ Failed to search conversation memories: {}", e);
+                        }
                     }
-                    Err(e) => {
-                        println!("⚠️ Failed to search conversation memories: {}", e);
-                    }
+                }
+                println!("📚 Memories used: {}", used_memories.len());
+                for label in &used_memories {
+                    println!("   - {}", label);
+                }
+            }
+            None => {}
+        }
+
+        // Summarize older turns of a long conversation, storing the summary
+        // in semantic memory in place of them
+        let memory = self
+            .semantic_memory
+            .as_deref()
+            .zip(request.conversation_id.as_deref());
+        match compact_history(
+            &self.inference_engine,
+            memory,
+            &mut agent_context.conversation_history,
+            &SummarizationPolicy::from_config(&self.config.context),
+            &TokenCounter::from_config(&self.config.context),
+        )
+        .await
+        {
+            Ok(0) => {}
+            Ok(summarized) => println!("🗜️ Summarized {} earlier conversation turns", summarized),
+            Err(e) => println!("⚠️ Failed to summarize conversation history: {}", e),
+        }
+
+        // Keep retrieved memories within the model's context window
+        if !agent_context.conversation_history.is_empty() {
+            let window =
+                ContextWindow::for_engine(&self.config.context, &self.inference_engine).await;
+            let turns = agent_context
+                .conversation_history
+                .iter()
+                .map(|m| format!("{}: {}", m.role, m.content))
+                .collect();
+            let fitted = window
+                .fit(
+                    PromptSegments {
+                        fixed: goal.to_string(),
+                        turns,
+                        chunks: Vec::new(),
+                    },
+                    &self.inference_engine,
+                )
+                .await;
+
+            if let Some(notice) = fitted.notice {
+                println!("⚠️ {}", notice);
+                if notice.summarized {
+                    agent_context.conversation_history = fitted
+                        .segments
+                        .turns
+                        .into_iter()
+                        .map(|content| ConversationMessage {
+                            role: "system".to_string(),
+                            content,
+                            tool_calls: None,
+                            tool_call_id: None,
+                        })
+                        .collect();
+                } else {
+                    agent_context
+                        .conversation_history
+                        .drain(..notice.dropped_turns);
                 }
             }
         }
@@ -2541,6 +3582,27 @@ Respond now."#,
                 tool_call_id: None,
             });
 
+        // A resumed run continues after the iterations it already completed
+        let resumed = self
+            .run_checkpoint
+            .as_ref()
+            .map(|checkpoint| checkpoint.borrow().clone())
+            .filter(|checkpoint| checkpoint.completed_iterations > 0);
+        if let Some(context) = resumed.as_ref().and_then(RunCheckpoint::resume_context) {
+            println!(
+                "⏯️  Resuming after {} completed iteration(s)",
+                resumed.as_ref().map_or(0, |c| c.completed_iterations)
+            );
+            agent_context
+                .conversation_history
+                .push(ConversationMessage {
+                    role: "system".to_string(),
+                    content: context,
+                    tool_calls: None,
+                    tool_call_id: None,
+                });
+        }
+
         let max_iters = 5; // Configurable iteration limit
 
         let mut execution_state = AgentExecutionState {
@@ -2561,23 +3623,36 @@ Respond now."#,
         };
 
         // Track full history for returning tool calls & confidence.
-        let mut all_reasoning: Vec<String> = Vec::new();
+        let (first_iteration, mut all_reasoning, mut all_tool_results) = match resumed {
+            Some(checkpoint) => (
+                checkpoint.completed_iterations as u32,
+                checkpoint.reasoning,
+                checkpoint.tool_results,
+            ),
+            None => (0, Vec::new(), Vec::new()),
+        };
         let mut all_tool_calls: Vec<ToolCall> = Vec::new();
-        let mut all_tool_results: Vec<ToolResult> = Vec::new();
 
-        for i in 0..max_iters {
+        for i in first_iteration..max_iters {
             execution_state.iteration_count = i as u32;
 
+            self.agent_controller.check_token_budget()?;
+
             // 1) Ask model for reasoning steps
             let reasoning_steps = self.generate_reasoning(goal, &agent_context).await?;
             all_reasoning.extend(reasoning_steps.clone());
 
             // 2) Decide whether tools are needed, then plan tool calls
-            let tool_calls = if self.needs_tools(goal, &reasoning_steps, &agent_context) {
+            let mut tool_calls = if self.needs_tools(goal, &reasoning_steps, &agent_context) {
                 self.plan_tool_calls(goal, &reasoning_steps, &agent_context, &exec_context)
             } else {
                 Vec::new()
             };
+            if tool_calls.is_empty() {
+                tool_calls = self
+                    .plan_model_tool_call(goal, &agent_context, &exec_context)
+                    .await;
+            }
 
             // Validate + only allow tools that exist in toolset
             // For now, just use tool_calls as-is (filtering would be implemented in a full version)
@@ -2606,6 +3681,11 @@ Respond now."#,
                 .generate_final_response(goal, &reasoning_steps, &tool_results)
                 .await
                 .unwrap_or_else(|e| format!("Failed to generate final response: {e}"));
+            if let Some(checkpoint) = &self.run_checkpoint {
+                checkpoint.send_modify(|checkpoint| {
+                    checkpoint.complete_iteration(&reasoning_steps, &tool_results)
+                });
+            }
 
             // Feed controller for optional stop/continue policy
             let iteration_result = AgentIterationResult {
@@ -2738,3 +3818,79 @@ Respond now."#,
         ))
     }
 }
+
+/// Groups from `(group, path)` pairs; a group of one file is just an operation
+fn operation_groups<'a>(
+    members: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Vec<OperationGroup> {
+    let mut groups: Vec<OperationGroup> = Vec::new();
+    for (name, path) in members {
+        let path = std::path::PathBuf::from(path);
+        match groups.iter_mut().find(|group| group.name == name) {
+            Some(group) if !group.paths.contains(&path) => group.paths.push(path),
+            Some(_) => {}
+            None => groups.push(OperationGroup {
+                name: name.to_string(),
+                paths: vec![path],
+            }),
+        }
+    }
+    groups.retain(|group| group.paths.len() > 1);
+    groups
+}
+
+/// Name and version of a package line: `serde`, `serde@1.0`, `serde 1.0`
+/// or a scoped npm package such as `@types/node@20`
+fn dependency_spec(line: &str) -> (String, Option<String>) {
+    let (name, version) = match line.split_once(char::is_whitespace) {
+        Some((name, version)) => (name, Some(version.trim())),
+        None => match line.rfind('@').filter(|&at| at > 0) {
+            Some(at) => (&line[..at], Some(&line[at + 1..])),
+            None => (line, None),
+        },
+    };
+    (
+        name.to_string(),
+        version
+            .filter(|version| !version.is_empty() && *version != "latest")
+            .map(str::to_string),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use infrastructure::{mock_inference::MockInference, InferenceEngine};
+
+    #[tokio::test]
+    async fn test_incremental_build_plan_with_mock_inference() {
+        let engine = InferenceEngine::Mock(
+            MockInference::new()
+                .with_response_containing("Analyze this goal", "Create one shell script.")
+                .with_response_containing(
+                    "what specific file operations",
+                    "FILE: greet_mock.sh\nACTION: create\nREASON: greeting",
+                )
+                .with_response_containing(
+                    "FILE CONTEXT (from filesystem scan)",
+                    r#"{"files": [{"path": "greet_mock.sh", "action": "create", "reason": "greeting"}]}"#,
+                )
+                .with_response_containing("FILE TO CREATE: greet_mock.sh", "echo hello\n"),
+        );
+        let mut planner = IncrementalBuildPlanner::new(
+            "create greet_mock.sh that prints hello".to_string(),
+            Vec::new(),
+            Config::load(),
+        );
+
+        while planner.stream_next_step(&engine).await.unwrap().is_some() {}
+
+        match planner.get_completed_operations() {
+            [FileOperation::Create { path, content }] => {
+                assert_eq!(path, std::path::Path::new("greet_mock.sh"));
+                assert_eq!(content, "echo hello\n");
+            }
+            other => panic!("unexpected operations: {:?}", other),
+        }
+    }
+}
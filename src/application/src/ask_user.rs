@@ -0,0 +1,137 @@
+//! The `ask_user` tool: the agent asks instead of guessing
+//!
+//! When a goal leaves open a choice only the user can make, the planner calls
+//! `ask_user` with a question. The agent controller pauses while the question
+//! waits in the question queue, where the web UI shows it and voice mode
+//! reads it out; with a terminal attached it is prompted there too. The first
+//! answer goes into the conversation and the run continues. A question nobody
+//! answers in time leaves the agent to go on with its own judgement.
+
+use infrastructure::user_questions::{PendingQuestion, QuestionQueue};
+use shared::types::Result;
+use std::time::Duration;
+
+pub const ASK_USER_TOOL: &str = "ask_user";
+
+/// Questions one run may ask
+pub const MAX_QUESTIONS: usize = 3;
+
+/// Recorded as the source of answers typed in the terminal
+const CLI_SOURCE: &str = "cli";
+
+/// Options of an `ask_user` call, given as `a | b | c` or a JSON list
+pub fn parse_options(options: Option<&str>) -> Vec<String> {
+    let options = options.unwrap_or_default();
+    serde_json::from_str::<Vec<String>>(options)
+        .unwrap_or_else(|_| options.split('|').map(str::to_string).collect())
+        .into_iter()
+        .map(|option| option.trim().to_string())
+        .filter(|option| !option.is_empty())
+        .collect()
+}
+
+/// Ask `question` and wait for the answer from the terminal, the web UI or
+/// voice mode; None when nobody answered within `timeout`
+pub async fn ask(
+    queue: &QuestionQueue,
+    question: &str,
+    options: &[String],
+    timeout: Duration,
+) -> Result<Option<PendingQuestion>> {
+    let pending = queue.submit(question, options, timeout)?;
+    println!("\n❓ {}", pending.prompt());
+
+    if let Some(answer) = shared::confirmation::scripted_answer(question) {
+        let answered = queue.answer(&pending.id, &answer?, CLI_SOURCE);
+        queue.remove(&pending.id)?;
+        return answered.map(Some);
+    }
+    if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        println!(
+            "   Waiting up to {}s for an answer from the web UI or voice mode",
+            timeout.as_secs()
+        );
+        return queue.wait(&pending.id).await;
+    }
+
+    // Polling the terminal and reading the answer block, so they run off the
+    // async runtime
+    let terminal_queue = queue.clone();
+    let id = pending.id.clone();
+    let answered =
+        tokio::task::spawn_blocking(move || answer_in_terminal(&terminal_queue, &id)).await??;
+    queue.remove(&pending.id)?;
+    Ok(answered)
+}
+
+/// Wait for a remote answer to `id` until a key is pressed, then read one
+/// from the terminal
+fn answer_in_terminal(queue: &QuestionQueue, id: &str) -> Result<Option<PendingQuestion>> {
+    let remote = shared::confirmation::wait_for_remote(
+        "   Waiting for an answer from the web UI or voice mode",
+        || match queue.get(id) {
+            Ok(Some(question)) if question.answer.is_some() => Some(Some(question)),
+            Ok(Some(question)) if !question.is_expired() => None,
+            _ => Some(None),
+        },
+    )?;
+    if let Some(answered) = remote {
+        return Ok(answered);
+    }
+    print!("   Answer: ");
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    match queue.answer(id, &line, CLI_SOURCE) {
+        Ok(answered) => Ok(Some(answered)),
+        // Answered elsewhere while typing, expired or left empty
+        Err(_) => Ok(queue.get(id)?.filter(|question| question.answer.is_some())),
+    }
+}
+
+/// What the agent is told about `question` and its answer
+pub fn context_message(question: &str, answered: Option<&PendingQuestion>) -> String {
+    match answered.and_then(|answered| answered.answer.as_deref()) {
+        Some(answer) => format!(
+            "You asked the user: {}\nThe user answered: {}",
+            question, answer
+        ),
+        None => format!(
+            "You asked the user: {}\nNo answer came; go on with your best judgement and say which assumption you made.",
+            question
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_asks_and_injects_the_answer() {
+        let dir = std::env::temp_dir().join(format!("bro-ask-user-{}", uuid::Uuid::new_v4()));
+        let queue = QuestionQueue::new(&dir);
+        let options = parse_options(Some("PostgreSQL | SQLite |"));
+        assert_eq!(options, ["PostgreSQL", "SQLite"]);
+        assert_eq!(parse_options(Some(r#"["PostgreSQL", "SQLite"]"#)), options);
+        assert!(parse_options(None).is_empty());
+
+        shared::confirmation::script_answers(["2".to_string()]);
+        let answered = ask(
+            &queue,
+            "Which database should the service use?",
+            &options,
+            Duration::from_secs(60),
+        )
+        .await
+        .unwrap();
+        shared::confirmation::end_script();
+        assert_eq!(
+            context_message("Which database should the service use?", answered.as_ref()),
+            "You asked the user: Which database should the service use?\nThe user answered: SQLite"
+        );
+        assert!(queue.list().unwrap().is_empty());
+        assert!(context_message("Deploy now?", None).contains("best judgement"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,9 +1,20 @@
 use crate::transaction::Transaction;
 use colored::Colorize;
+use infrastructure::cargo_workspace::CargoWorkspace;
+use infrastructure::dependency_ops;
+use infrastructure::diff_engine::{self, DiffEngine, DiffLineKind, SpanChange};
+use infrastructure::format_hooks::{FormatHooks, HookOutcome, LintFailed};
+use infrastructure::patch::{self, PatchHunk};
+use infrastructure::recycle_bin::RecycleBin;
+use infrastructure::test_gate::{TestCommand, TestGateFailed};
+use infrastructure::three_way_merge::{self, content_hash, MergeOutcome};
+use infrastructure::undo_journal::UndoJournal;
+use infrastructure::write_guards::WriteGuards;
 use serde::{Deserialize, Serialize};
 use shared::confirmation::ask_confirmation;
 use shared::types::Result;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 
 /// Represents a file operation in the build process
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +34,22 @@ pub enum FileOperation {
     Delete {
         path: PathBuf,
     },
+    /// Edit hunks applied to the file as it is when the operation runs
+    Patch {
+        path: PathBuf,
+        hunks: Vec<PatchHunk>,
+    },
+    /// Dependency added with the package manager owning `manifest`, e.g.
+    /// `cargo add`; the latest version when `version` is None
+    AddDependency {
+        manifest: PathBuf,
+        name: String,
+        version: Option<String>,
+    },
+    RemoveDependency {
+        manifest: PathBuf,
+        name: String,
+    },
 }
 
 /// Risk level for file operations
@@ -41,6 +68,70 @@ pub struct BuildPlan {
     pub operations: Vec<FileOperation>,
     pub description: String,
     pub estimated_risk: RiskLevel,
+    /// Operations that must be applied together or not at all
+    #[serde(default)]
+    pub groups: Vec<OperationGroup>,
+}
+
+/// Operations applied in one transaction, e.g. renaming a function and
+/// updating its call sites; every operation on one of `paths` belongs to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OperationGroup {
+    pub name: String,
+    pub paths: Vec<PathBuf>,
+}
+
+/// Operations applied in one transaction: a whole group or a lone operation
+#[derive(Debug, Clone)]
+pub struct PlanUnit<'a> {
+    pub group: Option<&'a str>,
+    pub operations: Vec<&'a FileOperation>,
+}
+
+impl BuildPlan {
+    /// The plan's operations in order, as units to apply; a group runs where
+    /// its first operation is
+    pub fn units(&self) -> Vec<PlanUnit<'_>> {
+        let mut units: Vec<PlanUnit<'_>> = Vec::new();
+        for operation in &self.operations {
+            let group = self
+                .groups
+                .iter()
+                .find(|group| group.paths.iter().any(|path| path == operation.path()));
+            let Some(group) = group else {
+                units.push(PlanUnit {
+                    group: None,
+                    operations: vec![operation],
+                });
+                continue;
+            };
+            match units
+                .iter_mut()
+                .find(|unit| unit.group == Some(group.name.as_str()))
+            {
+                Some(unit) => unit.operations.push(operation),
+                None => units.push(PlanUnit {
+                    group: Some(&group.name),
+                    operations: vec![operation],
+                }),
+            }
+        }
+        units
+    }
+}
+
+impl FileOperation {
+    pub fn path(&self) -> &Path {
+        match self {
+            FileOperation::Create { path, .. }
+            | FileOperation::Read { path }
+            | FileOperation::Update { path, .. }
+            | FileOperation::Delete { path }
+            | FileOperation::Patch { path, .. } => path,
+            FileOperation::AddDependency { manifest, .. }
+            | FileOperation::RemoveDependency { manifest, .. } => manifest,
+        }
+    }
 }
 
 /// Result of executing a build plan
@@ -53,6 +144,23 @@ pub struct BuildResult {
     pub rollback_performed: bool,
 }
 
+/// How to settle an update whose file changed on disk in a conflicting way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftChoice {
+    /// Write the merge with conflict markers to resolve by hand
+    Markers,
+    /// Write the planned content over the changes on disk
+    KeepPlan,
+    /// Leave the file as it is on disk
+    KeepDisk,
+    /// Fail the operation
+    Abort,
+}
+
+/// Asks how to settle the conflicts of merging an update into a file that
+/// changed on disk since planning
+pub type DriftResolver = Arc<dyn Fn(&Path, &MergeOutcome) -> DriftChoice + Send + Sync>;
+
 /// Confirmation mode for build operations
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConfirmationMode {
@@ -302,6 +410,62 @@ impl OperationGraph {
     }
 }
 
+/// Diff lines shown for an update before `--show-diff` is needed for the rest
+const DIFF_PREVIEW_LINES: usize = 40;
+
+/// Diff of an update from `engine`, colored for the terminal
+pub fn colored_diff(engine: DiffEngine, path: &Path, old: &str, new: &str) -> Vec<String> {
+    diff_engine::diff(engine, path, old, new)
+        .iter()
+        .map(|line| {
+            let mut text = line.prefix().to_string();
+            for span in &line.spans {
+                text.push_str(&match span.change {
+                    SpanChange::Same => span.text.clone(),
+                    SpanChange::Removed => span.text.red().strikethrough().to_string(),
+                    SpanChange::Added => span.text.green().bold().to_string(),
+                });
+            }
+            match line.kind {
+                DiffLineKind::Header => text.bold().to_string(),
+                DiffLineKind::Hunk => text.cyan().to_string(),
+                DiffLineKind::Context => text.dimmed().to_string(),
+                DiffLineKind::Removed => text.red().to_string(),
+                DiffLineKind::Added => text.green().to_string(),
+                DiffLineKind::Changed => text,
+                DiffLineKind::Note => text.yellow().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Print the diff of an update; unless `full`, long diffs are cut short
+pub fn print_diff(engine: DiffEngine, path: &Path, old: &str, new: &str, full: bool) {
+    let lines = colored_diff(engine, path, old, new);
+    if lines.is_empty() {
+        println!("{}", "(no changes)".dimmed());
+        return;
+    }
+    let shown = if full {
+        lines.len()
+    } else {
+        lines.len().min(DIFF_PREVIEW_LINES)
+    };
+    for line in &lines[..shown] {
+        println!("{}", line);
+    }
+    if shown < lines.len() {
+        println!(
+            "{}",
+            format!(
+                "... {} more diff lines (use --show-diff for the full diff)",
+                lines.len() - shown
+            )
+            .dimmed()
+        );
+    }
+}
+
 /// Service for managing build mode operations
 pub struct BuildService {
     /// Workspace root directory
@@ -314,6 +478,8 @@ pub struct BuildService {
     show_diff: bool,
     /// Whether to show verbose previews
     verbose: bool,
+    /// How update diffs are computed
+    diff_engine: DiffEngine,
     /// Buffered operations for incremental streaming
     buffered_operations: Vec<FileOperation>,
     /// Complex operations graph for dependency management
@@ -322,6 +488,21 @@ pub struct BuildService {
     project_root: PathBuf,
     /// Cached project scan for performance optimization
     cached_project_scan: Option<ProjectScanCache>,
+    /// Where deleted files go, so approved deletions can be undone
+    recycle_bin: RecycleBin,
+    /// Limits that flag generated writes as likely model failures
+    write_guards: WriteGuards,
+    /// Settles merge conflicts with files changed since planning; without
+    /// one such updates fail
+    drift_resolver: Option<DriftResolver>,
+    /// Tests that must pass after each operation, which is rolled back
+    /// otherwise
+    test_gate: Option<TestCommand>,
+    /// Formatter and linter run on every file an operation writes
+    format_hooks: Option<FormatHooks>,
+    /// Workspace whose crates are `cargo check`ed when an operation writes
+    /// Rust files in them
+    cargo_workspace: Option<CargoWorkspace>,
 }
 
 /// Cached project scan information for performance
@@ -350,10 +531,17 @@ impl BuildService {
             confirmation_mode: ConfirmationMode::Interactive,
             show_diff: false,
             verbose: false,
+            diff_engine: DiffEngine::default(),
             buffered_operations: Vec::new(),
             operation_graph: OperationGraph::new(),
+            recycle_bin: RecycleBin::for_project(&project_root),
             project_root,
             cached_project_scan: None,
+            write_guards: WriteGuards::default(),
+            drift_resolver: None,
+            test_gate: None,
+            format_hooks: None,
+            cargo_workspace: None,
         }
     }
 
@@ -391,26 +579,6 @@ impl BuildService {
         }
     }
 
-    fn strip_fences_for_preview(content: &str) -> String {
-        let trimmed = content.trim();
-        if trimmed.starts_with("```") && trimmed.ends_with("```") {
-            let mut lines: Vec<&str> = trimmed.lines().collect();
-            if !lines.is_empty()
-                && lines
-                    .first()
-                    .map(|l| l.trim().starts_with("```"))
-                    .unwrap_or(false)
-            {
-                lines.remove(0);
-            }
-            if !lines.is_empty() && lines.last().map(|l| l.trim() == "```").unwrap_or(false) {
-                lines.pop();
-            }
-            return lines.join("\n").trim().to_string();
-        }
-        trimmed.trim_matches('`').trim().to_string()
-    }
-
     /// Enable or disable dry-run mode
     pub fn set_dry_run(&mut self, dry_run: bool) {
         self.dry_run = dry_run;
@@ -421,6 +589,11 @@ impl BuildService {
         self.show_diff = show_diff;
     }
 
+    /// Choose how update diffs are computed
+    pub fn set_diff_engine(&mut self, engine: DiffEngine) {
+        self.diff_engine = engine;
+    }
+
     /// Enable verbose previews
     pub fn set_verbose(&mut self, verbose: bool) {
         self.verbose = verbose;
@@ -431,19 +604,94 @@ impl BuildService {
         self.confirmation_mode = mode;
     }
 
+    /// Set the size, binary and truncation limits for writes
+    pub fn set_write_guards(&mut self, guards: WriteGuards) {
+        self.write_guards = guards;
+    }
+
+    /// Ask `resolver` how to settle updates that conflict with changes made
+    /// on disk since planning
+    pub fn set_drift_resolver(&mut self, resolver: DriftResolver) {
+        self.drift_resolver = Some(resolver);
+    }
+
+    /// Run `command` after every operation and roll the operation back when
+    /// it fails
+    pub fn set_test_gate(&mut self, command: Option<TestCommand>) {
+        self.test_gate = command;
+    }
+
+    /// Format and lint every file an operation writes; a file they reject
+    /// rolls the operation back
+    pub fn set_format_hooks(&mut self, hooks: Option<FormatHooks>) {
+        self.format_hooks = hooks.filter(|hooks| hooks.enabled);
+    }
+
+    /// Run `cargo check -p` on the crates of `workspace` an operation writes
+    /// Rust files in, rolling the operation back when they don't compile
+    pub fn set_cargo_check(&mut self, workspace: Option<CargoWorkspace>) {
+        self.cargo_workspace = workspace;
+    }
+
+    /// Why a Create/Update looks like a model failure; empty when it doesn't
+    pub fn write_guard_violations(&self, operation: &FileOperation) -> Vec<String> {
+        match operation {
+            FileOperation::Create { content, .. } => self.write_guards.check(None, content),
+            FileOperation::Update {
+                old_content,
+                new_content,
+                ..
+            } => self.write_guards.check(Some(old_content), new_content),
+            FileOperation::Patch { path, hunks } => match patched_content(path, hunks) {
+                Ok((old_content, new_content)) => {
+                    self.write_guards.check(Some(&old_content), &new_content)
+                }
+                // Execution reports why the hunks don't apply
+                Err(_) => Vec::new(),
+            },
+            FileOperation::Read { .. }
+            | FileOperation::Delete { .. }
+            | FileOperation::AddDependency { .. }
+            | FileOperation::RemoveDependency { .. } => Vec::new(),
+        }
+    }
+
+    /// Refuse a write flagged by the guards, unless the user confirms it
+    /// again in interactive mode
+    fn enforce_write_guards(&self, path: &Path, operation: &FileOperation) -> Result<()> {
+        let violations = self.write_guard_violations(operation);
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let reasons = violations.join("; ");
+        if self.confirmation_mode == ConfirmationMode::Interactive {
+            eprintln!(
+                "{} {}: {}",
+                "WARNING:".red().bold(),
+                path.display(),
+                reasons
+            );
+            if ask_confirmation(
+                "This looks like a failed generation. Write it anyway?",
+                false,
+            )? {
+                return Ok(());
+            }
+        }
+        Err(anyhow::anyhow!("REFUSED: {} ({})", path.display(), reasons))
+    }
+
     /// Assess risk level of a file operation with project scoping
     pub fn assess_risk(&self, operation: &FileOperation) -> RiskLevel {
-        let path = match operation {
-            FileOperation::Read { path } => path,
-            FileOperation::Create { path, .. } => path,
-            FileOperation::Update { path, .. } => path,
-            FileOperation::Delete { path } => path,
-        };
+        let path = operation.path();
 
         // First, validate project scoping - if outside project, critical risk
         if !self.is_path_in_project(path) {
             return RiskLevel::Critical;
         }
+        if !self.write_guard_violations(operation).is_empty() {
+            return RiskLevel::Critical;
+        }
 
         match operation {
             FileOperation::Read { .. } => {
@@ -460,7 +708,7 @@ impl BuildService {
                     RiskLevel::Low
                 }
             }
-            FileOperation::Update { .. } => {
+            FileOperation::Update { .. } | FileOperation::Patch { .. } => {
                 if self.is_critical_path(path) {
                     RiskLevel::Critical
                 } else {
@@ -474,12 +722,22 @@ impl BuildService {
                     RiskLevel::High
                 }
             }
+            // The package manager edits the manifest, but fetches code to run
+            FileOperation::AddDependency { .. } | FileOperation::RemoveDependency { .. } => {
+                RiskLevel::High
+            }
         }
     }
 
-    /// Check if a path is within the project root (strict scoping)
+    /// Check if a path is within the project root (strict scoping). Relative
+    /// paths are taken from the workspace root, and `..` is never allowed
+    /// since the comparison is lexical.
     fn is_path_in_project(&self, path: &Path) -> bool {
-        path.starts_with(&self.project_root)
+        !path.components().any(|c| c == Component::ParentDir)
+            && self
+                .workspace_root
+                .join(path)
+                .starts_with(&self.project_root)
     }
 
     /// Validate a path against project boundaries - rejects any path outside project
@@ -534,6 +792,17 @@ impl BuildService {
             FileOperation::Delete { path } => {
                 println!("  {} DELETE: {}", risk_label, path.display());
             }
+            FileOperation::Patch { path, hunks } => {
+                println!(
+                    "  {} PATCH: {} ({} hunk(s))",
+                    risk_label,
+                    path.display(),
+                    hunks.len()
+                );
+            }
+            FileOperation::AddDependency { .. } | FileOperation::RemoveDependency { .. } => {
+                println!("  {} {}", risk_label, operation_summary(operation));
+            }
         }
     }
 
@@ -554,7 +823,7 @@ impl BuildService {
                 match operation {
                     FileOperation::Create { content, .. } => {
                         println!("\nContent preview:");
-                        let cleaned = Self::strip_fences_for_preview(content);
+                        let cleaned = shared::output_cleanup::strip_code_fences(content);
                         let snippet = if cleaned.len() > 200 {
                             &cleaned[..200]
                         } else {
@@ -567,7 +836,7 @@ impl BuildService {
                     }
                     FileOperation::Update { new_content, .. } => {
                         println!("\nContent preview:");
-                        let cleaned = Self::strip_fences_for_preview(new_content);
+                        let cleaned = shared::output_cleanup::strip_code_fences(new_content);
                         let snippet = if cleaned.len() > 200 {
                             &cleaned[..200]
                         } else {
@@ -593,11 +862,14 @@ impl BuildService {
 
         println!("\n{}", "─".repeat(60));
         self.display_operation(operation, risk);
+        for violation in self.write_guard_violations(operation) {
+            println!("{} {}", "WARNING:".red().bold(), violation);
+        }
 
         match operation {
             FileOperation::Create { path, content } => {
                 println!("\nContent to be created:");
-                let cleaned = Self::strip_fences_for_preview(content);
+                let cleaned = shared::output_cleanup::strip_code_fences(content);
                 let preview = if cleaned.len() > 500 {
                     format!("{}... ({} bytes total)", &cleaned[..500], cleaned.len())
                 } else {
@@ -611,58 +883,43 @@ impl BuildService {
                 new_content,
             } => {
                 println!("\nChanges:");
-                if self.show_diff || self.verbose {
-                    self.display_diff(old_content, new_content);
-                } else {
-                    println!("{}", "(use --show-diff for detailed diff)");
-                }
+                print_diff(
+                    self.diff_engine,
+                    path,
+                    old_content,
+                    new_content,
+                    self.show_diff || self.verbose,
+                );
             }
+            FileOperation::Patch { path, hunks } => match patched_content(path, hunks) {
+                Ok((old_content, new_content)) => {
+                    println!("\nChanges:");
+                    print_diff(
+                        self.diff_engine,
+                        path,
+                        &old_content,
+                        &new_content,
+                        self.show_diff || self.verbose,
+                    );
+                }
+                Err(e) => println!("{} {}", "WARNING:".red().bold(), e),
+            },
             FileOperation::Delete { path } => {
                 if path.exists() {
                     let size = std::fs::metadata(path)?.len();
                     println!("File size: {} bytes", size);
                 }
             }
-            FileOperation::Read { .. } => {
-                // No additional details for read operations
+            FileOperation::Read { .. }
+            | FileOperation::AddDependency { .. }
+            | FileOperation::RemoveDependency { .. } => {
+                // No additional details for read and dependency operations
             }
         }
 
         Ok(())
     }
 
-    /// Display a simple diff between old and new content
-    fn display_diff(&self, old_content: &str, new_content: &str) {
-        let old_lines: Vec<&str> = old_content.lines().collect();
-        let new_lines: Vec<&str> = new_content.lines().collect();
-
-        let max_lines = old_lines.len().max(new_lines.len()).min(20);
-
-        for i in 0..max_lines {
-            match (old_lines.get(i), new_lines.get(i)) {
-                (Some(old_line), Some(new_line)) => {
-                    if old_line != new_line {
-                        println!("{}", format!("- {}", old_line).red());
-                        println!("{}", format!("+ {}", new_line).green());
-                    } else {
-                        println!("{}", format!("  {}", old_line).bright_black());
-                    }
-                }
-                (Some(old_line), None) => {
-                    println!("{}", format!("- {}", old_line).red());
-                }
-                (None, Some(new_line)) => {
-                    println!("{}", format!("+ {}", new_line).green());
-                }
-                (None, None) => break,
-            }
-        }
-
-        if old_lines.len() > max_lines || new_lines.len() > max_lines {
-            println!("... (diff truncated)");
-        }
-    }
-
     /// Ask for user confirmation for an operation
     fn confirm_operation(
         &self,
@@ -722,13 +979,9 @@ impl BuildService {
         }
 
         // Validate project scoping before any operation
-        let path = match operation {
-            FileOperation::Read { path } => path,
-            FileOperation::Create { path, .. } => path,
-            FileOperation::Update { path, .. } => path,
-            FileOperation::Delete { path } => path,
-        };
+        let path = operation.path();
         self.validate_project_path(path)?;
+        self.enforce_write_guards(path, operation)?;
 
         match operation {
             FileOperation::Create { path, content } => {
@@ -761,27 +1014,45 @@ impl BuildService {
 
                 let current_content = std::fs::read_to_string(path)?;
 
-                // Verify old content matches (safety check)
-                if current_content != *old_content {
-                    return Err(anyhow::anyhow!(
-                        "File content has changed since plan creation: {}",
-                        path.display()
-                    ));
-                }
+                // A file edited since planning gets the update merged in
+                let content = if content_hash(&current_content) != content_hash(old_content) {
+                    match self.merge_drifted(path, old_content, new_content, &current_content)? {
+                        Some(merged) => merged,
+                        None => {
+                            println!("Kept {} as it is on disk", path.display());
+                            return Ok(());
+                        }
+                    }
+                } else {
+                    new_content.clone()
+                };
 
-                std::fs::write(path, new_content)?;
+                std::fs::write(path, content)?;
                 println!("{}", format!("Updated: {}", path.display()));
                 Ok(())
             }
+            FileOperation::Patch { path, hunks } => {
+                let (_, content) = patched_content(path, hunks)?;
+                std::fs::write(path, content)?;
+                println!("Patched: {} ({} hunk(s))", path.display(), hunks.len());
+                Ok(())
+            }
             FileOperation::Delete { path } => {
                 if !path.exists() {
                     return Err(anyhow::anyhow!("File does not exist: {}", path.display()));
                 }
 
-                std::fs::remove_file(path)?;
-                println!("{}", format!("Deleted: {}", path.display()));
+                let trashed = self.recycle_bin.trash(path)?;
+                println!(
+                    "Deleted: {} (kept in {})",
+                    path.display(),
+                    trashed.display()
+                );
                 Ok(())
             }
+            FileOperation::AddDependency { .. } | FileOperation::RemoveDependency { .. } => {
+                apply_dependency_change(operation).await
+            }
         }
     }
 
@@ -802,7 +1073,7 @@ impl BuildService {
         }
 
         // Create transaction for atomic operations
-        let mut transaction = Transaction::new();
+        let mut transaction = self.journaled_transaction(&plan.goal);
         transaction.begin()?;
 
         println!("\n[EXECUTING] {} operations...", plan.operations.len());
@@ -878,6 +1149,11 @@ impl BuildService {
         Ok(result)
     }
 
+    /// Transaction whose pre-images are kept for `--undo` once committed
+    fn journaled_transaction(&self, description: &str) -> Transaction {
+        Transaction::new().with_journal(UndoJournal::for_project(&self.project_root), description)
+    }
+
     /// Auto-commit changes to git if repository exists
     async fn git_commit_changes(&self, plan: &BuildPlan) -> Result<()> {
         // Check if we're in a git repository
@@ -916,13 +1192,9 @@ impl BuildService {
         }
 
         // Validate project scoping before any operation
-        let path = match operation {
-            FileOperation::Read { path } => path,
-            FileOperation::Create { path, .. } => path,
-            FileOperation::Update { path, .. } => path,
-            FileOperation::Delete { path } => path,
-        };
+        let path = operation.path();
         self.validate_project_path(path)?;
+        self.enforce_write_guards(path, operation)?;
 
         match operation {
             FileOperation::Create { path, content } => {
@@ -950,27 +1222,97 @@ impl BuildService {
 
                 let current_content = std::fs::read_to_string(path)?;
 
-                // Verify old content matches (safety check)
-                if current_content != *old_content {
-                    return Err(anyhow::anyhow!(
-                        "File content has changed since plan creation: {}",
-                        path.display()
-                    ));
-                }
+                // A file edited since planning gets the update merged in
+                let content = if content_hash(&current_content) != content_hash(old_content) {
+                    match self.merge_drifted(path, old_content, new_content, &current_content)? {
+                        Some(merged) => merged,
+                        None => {
+                            println!("Kept {} as it is on disk", path.display());
+                            return Ok(());
+                        }
+                    }
+                } else {
+                    new_content.clone()
+                };
 
-                transaction.write_file(path, new_content.as_bytes())?;
+                transaction.write_file(path, content.as_bytes())?;
                 println!("{}", format!("Updated: {}", path.display()));
                 Ok(())
             }
+            FileOperation::Patch { path, hunks } => {
+                let (_, content) = patched_content(path, hunks)?;
+                transaction.write_file(path, content.as_bytes())?;
+                println!("Patched: {} ({} hunk(s))", path.display(), hunks.len());
+                Ok(())
+            }
             FileOperation::Delete { path } => {
                 if !path.exists() {
                     return Err(anyhow::anyhow!("File does not exist: {}", path.display()));
                 }
 
-                transaction.delete_file(path)?;
-                println!("{}", format!("Deleted: {}", path.display()));
+                // Rollback restores the backup; the trash keeps a copy either way
+                transaction.backup_file(path)?;
+                let trashed = self.recycle_bin.trash(path)?;
+                println!(
+                    "Deleted: {} (kept in {})",
+                    path.display(),
+                    trashed.display()
+                );
                 Ok(())
             }
+            FileOperation::AddDependency { manifest, .. }
+            | FileOperation::RemoveDependency { manifest, .. } => {
+                // Rollback restores the manifest and lockfiles the package
+                // manager rewrote
+                for file in dependency_ops::touched_files(manifest) {
+                    transaction.backup_file(&file)?;
+                }
+                apply_dependency_change(operation).await
+            }
+        }
+    }
+
+    /// Three-way merge of an update planned against `old_content` into the
+    /// file's `current_content`; None keeps the file as it is
+    fn merge_drifted(
+        &self,
+        path: &Path,
+        old_content: &str,
+        new_content: &str,
+        current_content: &str,
+    ) -> Result<Option<String>> {
+        let merged =
+            three_way_merge::merge(old_content, new_content, current_content, "plan", "disk");
+        if merged.conflicts == 0 {
+            println!(
+                "{} {} changed since planning; merged the update with those changes",
+                "NOTE:".yellow(),
+                path.display()
+            );
+            return Ok(Some(merged.text));
+        }
+
+        let choice = match &self.drift_resolver {
+            Some(resolver) => resolver(path, &merged),
+            None => DriftChoice::Abort,
+        };
+        match choice {
+            DriftChoice::Markers => {
+                println!(
+                    "{} {} conflict(s) in {} marked with <<<<<<< plan / >>>>>>> disk",
+                    "WARNING:".red().bold(),
+                    merged.conflicts,
+                    path.display()
+                );
+                Ok(Some(merged.text))
+            }
+            DriftChoice::KeepPlan => Ok(Some(new_content.to_string())),
+            DriftChoice::KeepDisk => Ok(None),
+            DriftChoice::Abort => Err(anyhow::anyhow!(
+                "File content has changed since plan creation and {} change(s) conflict with the update: {}",
+                merged.conflicts,
+                path.display()
+            )),
         }
     }
 
@@ -987,6 +1329,7 @@ impl BuildService {
             operations: Vec::new(),
             description: format!("Build plan for: {}", goal),
             estimated_risk: RiskLevel::Low,
+            groups: Vec::new(),
         })
     }
 
@@ -1009,12 +1352,7 @@ impl BuildService {
         let mut warnings = Vec::new();
 
         for op in operations {
-            let path = match &op {
-                FileOperation::Create { path, .. }
-                | FileOperation::Read { path }
-                | FileOperation::Update { path, .. }
-                | FileOperation::Delete { path } => path,
-            };
+            let path = op.path();
 
             // Reject paths that escape the workspace root
             if path.is_absolute() && !path.starts_with(&self.workspace_root) {
@@ -1068,7 +1406,21 @@ impl BuildService {
                 println!("{} Updating: {}", risk_label, path.display());
                 if self.show_diff {
                     println!("\nChanges:");
-                    self.display_diff(old_content, new_content);
+                    print_diff(self.diff_engine, path, old_content, new_content, true);
+                }
+            }
+            FileOperation::Patch { path, hunks } => {
+                println!(
+                    "{} Patching: {} ({} hunk(s))",
+                    risk_label,
+                    path.display(),
+                    hunks.len()
+                );
+                if self.show_diff {
+                    if let Ok((old_content, new_content)) = patched_content(path, hunks) {
+                        println!("\nChanges:");
+                        print_diff(self.diff_engine, path, &old_content, &new_content, true);
+                    }
                 }
             }
             FileOperation::Read { path } => {
@@ -1081,6 +1433,9 @@ impl BuildService {
                     println!("  File size: {} bytes", size);
                 }
             }
+            FileOperation::AddDependency { .. } | FileOperation::RemoveDependency { .. } => {
+                println!("{} {}", risk_label, operation_summary(operation));
+            }
         }
 
         Ok(())
@@ -1146,7 +1501,8 @@ impl BuildService {
             )
         );
 
-        let mut transaction = crate::transaction::Transaction::new();
+        let names: Vec<&str> = operations.iter().map(|op| op.name.as_str()).collect();
+        let mut transaction = self.journaled_transaction(&names.join(", "));
         transaction.begin()?;
 
         for (idx, operation) in operations.iter().enumerate() {
@@ -1308,6 +1664,7 @@ impl BuildService {
             operations,
             description: "Buffered operations from incremental streaming".to_string(),
             estimated_risk: RiskLevel::Low, // Will be recalculated
+            groups: Vec::new(),
         };
 
         // Recalculate risk
@@ -1347,14 +1704,16 @@ impl BuildService {
         // Check for conflicting operations
         let mut paths_modified = std::collections::HashSet::new();
         for operation in &plan.operations {
-            let path = match operation {
-                FileOperation::Create { path, .. }
-                | FileOperation::Read { path }
-                | FileOperation::Update { path, .. }
-                | FileOperation::Delete { path } => path,
-            };
+            // Several dependencies of one manifest are expected
+            if matches!(
+                operation,
+                FileOperation::AddDependency { .. } | FileOperation::RemoveDependency { .. }
+            ) {
+                continue;
+            }
+            let path = operation.path();
 
-            if !paths_modified.insert(path.clone()) {
+            if !paths_modified.insert(path.to_path_buf()) {
                 warnings.push(format!(
                     "Path modified multiple times in plan: {}",
                     path.display()
@@ -1365,21 +1724,152 @@ impl BuildService {
         Ok(warnings)
     }
 
-    /// Execute a single operation with its own transaction
+    /// Execute a single operation with its own transaction; with a test gate
+    /// the operation is rolled back and [`TestGateFailed`] returned when the
+    /// tests fail afterwards
     pub async fn execute_operation_once(&self, operation: &FileOperation) -> Result<()> {
-        let mut transaction = Transaction::new();
+        self.execute_unit(&PlanUnit {
+            group: None,
+            operations: vec![operation],
+        })
+        .await
+    }
+
+    /// Execute a unit of the plan in one transaction. When one operation of
+    /// a group fails, the files of every operation before it are restored
+    /// too. The format hooks, `cargo check` and the test gate run once the
+    /// whole unit is applied; [`LintFailed`] or [`TestGateFailed`] roll it
+    /// back.
+    pub async fn execute_unit(&self, unit: &PlanUnit<'_>) -> Result<()> {
+        let summary = unit_summary(unit);
+        let mut transaction = self.journaled_transaction(&summary);
         transaction.begin()?;
-        self.execute_operation_transactional(operation, &mut transaction)
-            .await?;
+        for operation in &unit.operations {
+            if let Err(e) = self
+                .execute_operation_transactional(operation, &mut transaction)
+                .await
+            {
+                let Some(group) = unit.group else {
+                    return Err(e);
+                };
+                transaction.rollback()?;
+                return Err(e.context(format!(
+                    "{} failed; group '{}' was rolled back",
+                    operation_summary(operation),
+                    group
+                )));
+            }
+        }
+        let mut written = Vec::new();
+        for operation in &unit.operations {
+            if let FileOperation::Create { path, .. }
+            | FileOperation::Update { path, .. }
+            | FileOperation::Patch { path, .. } = operation
+            {
+                if !written.contains(&path) {
+                    written.push(path);
+                }
+            }
+        }
+        if let Some(hooks) = self.format_hooks.as_ref().filter(|_| !self.dry_run) {
+            for &path in &written {
+                let file = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+                match hooks.run(&file, &self.project_root).await? {
+                    HookOutcome::Passed {
+                        formatter: Some(formatter),
+                    } => println!("{} {} {}", "[FORMAT]".cyan(), formatter, path.display()),
+                    HookOutcome::Passed { formatter: None } => {}
+                    HookOutcome::Rejected { command, output } => {
+                        transaction.rollback()?;
+                        return Err(LintFailed {
+                            operation: summary,
+                            file: path.clone(),
+                            command,
+                            output,
+                        }
+                        .into());
+                    }
+                }
+            }
+        }
+        if let Some(workspace) = self.cargo_workspace.as_ref().filter(|_| !self.dry_run) {
+            // Removing a crate the code still uses breaks the build too
+            let manifests = unit
+                .operations
+                .iter()
+                .filter_map(|operation| match operation {
+                    FileOperation::AddDependency { manifest, .. }
+                    | FileOperation::RemoveDependency { manifest, .. } => Some(manifest),
+                    _ => None,
+                });
+            let mut crates = Vec::new();
+            for path in written
+                .iter()
+                .copied()
+                .filter(|path| path.extension() == Some("rs".as_ref()))
+                .chain(manifests.filter(|manifest| manifest.ends_with("Cargo.toml")))
+            {
+                if let Some(krate) = workspace.crate_for(path) {
+                    if !crates.contains(&krate) {
+                        crates.push(krate);
+                    }
+                }
+            }
+            for krate in crates {
+                let command = workspace.check_command(krate);
+                println!("{} Running {}...", "[CHECK]".cyan(), command);
+                let run = match command.run(&workspace.root).await {
+                    Ok(run) => run,
+                    Err(e) => {
+                        eprintln!("{} {}; skipping the check", "[CHECK]".yellow(), e);
+                        continue;
+                    }
+                };
+                if !run.passed {
+                    transaction.rollback()?;
+                    return Err(TestGateFailed {
+                        operation: summary,
+                        command,
+                        run,
+                    }
+                    .into());
+                }
+            }
+        }
+        if let Some(command) = &self.test_gate {
+            let writes = unit
+                .operations
+                .iter()
+                .any(|operation| !matches!(operation, FileOperation::Read { .. }));
+            if writes {
+                println!("{} Running {}...", "[GATE]".cyan(), command);
+                let run = command.run(&self.project_root).await?;
+                if !run.passed {
+                    transaction.rollback()?;
+                    return Err(TestGateFailed {
+                        operation: summary,
+                        command: command.clone(),
+                        run,
+                    }
+                    .into());
+                }
+                println!(
+                    "{} Tests passed in {:.1}s",
+                    "[GATE]".green(),
+                    run.elapsed.as_secs_f64()
+                );
+            }
+        }
         transaction.commit()?;
         Ok(())
     }
 
-    /// Commit current working tree with a custom message
-    pub async fn commit_message(&self, message: &str) -> Result<()> {
+    /// Commit current working tree with a custom message, returning the
+    /// short hash; None outside a git repository
+    pub async fn commit_message(&self, message: &str) -> Result<Option<String>> {
         let repo_path = std::env::current_dir()?;
         if !repo_path.join(".git").exists() {
-            return Ok(());
+            return Ok(None);
         }
 
         let repo = git2::Repository::open(&repo_path)
@@ -1423,17 +1913,91 @@ impl BuildService {
             .find_tree(tree_oid)
             .map_err(|e| anyhow::anyhow!("Failed to find tree: {}", e))?;
 
-        let _commit_oid = repo
+        let commit_oid = repo
             .commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
             .map_err(|e| anyhow::anyhow!("Failed to create commit: {}", e))?;
 
-        Ok(())
+        Ok(Some(commit_oid.to_string()[..7].to_string()))
+    }
+}
+
+/// One-line description of an operation, e.g. `Update src/lib.rs`
+pub fn operation_summary(operation: &FileOperation) -> String {
+    let (verb, path) = match operation {
+        FileOperation::Create { path, .. } => ("Create", path),
+        FileOperation::Read { path } => ("Read", path),
+        FileOperation::Update { path, .. } => ("Update", path),
+        FileOperation::Delete { path } => ("Delete", path),
+        FileOperation::Patch { path, .. } => ("Patch", path),
+        FileOperation::AddDependency {
+            manifest,
+            name,
+            version,
+        } => {
+            let version = version
+                .as_deref()
+                .map(|version| format!(" {}", version))
+                .unwrap_or_default();
+            return format!(
+                "Add dependency {}{} to {}",
+                name,
+                version,
+                manifest.display()
+            );
+        }
+        FileOperation::RemoveDependency { manifest, name } => {
+            return format!("Remove dependency {} from {}", name, manifest.display());
+        }
+    };
+    format!("{} {}", verb, path.display())
+}
+
+/// Run the package manager for a dependency operation
+async fn apply_dependency_change(operation: &FileOperation) -> Result<()> {
+    match operation {
+        FileOperation::AddDependency {
+            manifest,
+            name,
+            version,
+        } => {
+            dependency_ops::add(manifest, name, version.as_deref()).await?;
+            println!("Added dependency: {} to {}", name, manifest.display());
+        }
+        FileOperation::RemoveDependency { manifest, name } => {
+            dependency_ops::remove(manifest, name).await?;
+            println!("Removed dependency: {} from {}", name, manifest.display());
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// One-line description of a unit, e.g. `group 'rename' (Update a.rs, Update b.rs)`
+pub fn unit_summary(unit: &PlanUnit<'_>) -> String {
+    let operations: Vec<String> = unit
+        .operations
+        .iter()
+        .map(|operation| operation_summary(operation))
+        .collect();
+    match unit.group {
+        Some(group) => format!("group '{}' ({})", group, operations.join(", ")),
+        None => operations.join(", "),
     }
 }
 
+/// Content of `path` on disk, and that content with `hunks` applied
+pub fn patched_content(path: &Path, hunks: &[PatchHunk]) -> Result<(String, String)> {
+    let current = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot patch {}: {}", path.display(), e))?;
+    let patched =
+        patch::apply(&current, hunks).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    Ok((current, patched))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use infrastructure::format_hooks::ExtensionHooks;
 
     #[test]
     fn test_risk_assessment() {
@@ -1451,6 +2015,25 @@ mod tests {
         assert_eq!(service.assess_risk(&delete_critical), RiskLevel::Critical);
     }
 
+    #[test]
+    fn test_project_scope_resolves_relative_paths() {
+        let service = BuildService::new("/tmp/project");
+
+        assert!(service.validate_project_path(Path::new("hello.sh")).is_ok());
+        assert!(service
+            .validate_project_path(Path::new("/tmp/project/src/main.rs"))
+            .is_ok());
+        assert!(service
+            .validate_project_path(Path::new("../hello.sh"))
+            .is_err());
+        assert!(service
+            .validate_project_path(Path::new("/tmp/project/../etc/passwd"))
+            .is_err());
+        assert!(service
+            .validate_project_path(Path::new("/etc/passwd"))
+            .is_err());
+    }
+
     #[test]
     fn test_is_critical_path() {
         let service = BuildService::new("/tmp");
@@ -1484,4 +2067,251 @@ mod tests {
         assert!(matches!(scoped[0], FileOperation::Create { .. }));
         assert!(matches!(scoped[1], FileOperation::Read { .. }));
     }
+
+    #[tokio::test]
+    async fn test_guarded_writes_are_refused_without_confirmation() {
+        let root = std::env::temp_dir().join(format!("bro-guards-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut service = BuildService::new(&root);
+        service.set_confirmation_mode(ConfirmationMode::None);
+        let path = root.join("main.rs");
+        let original = "fn main() {}\n".repeat(100);
+        std::fs::write(&path, &original).unwrap();
+
+        let stub = FileOperation::Update {
+            path: path.clone(),
+            old_content: original.clone(),
+            new_content: "// ... existing code ...\n".to_string(),
+        };
+        assert_eq!(service.assess_risk(&stub), RiskLevel::Critical);
+        let error = service.execute_operation_once(&stub).await.unwrap_err();
+        assert!(error.to_string().starts_with("REFUSED"), "{}", error);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+
+        let edit = FileOperation::Update {
+            path: path.clone(),
+            old_content: original.clone(),
+            new_content: original.replace("main", "run"),
+        };
+        service.execute_operation_once(&edit).await.unwrap();
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_colored_diff_has_hunks() {
+        let old = "one\ntwo\nthree\n";
+        let new = "one\n2\nthree\nfour\n";
+        let lines = colored_diff(DiffEngine::Line, Path::new("src/lib.rs"), old, new);
+        assert!(lines[0].contains("--- a/src/lib.rs"));
+        assert!(lines[1].contains("+++ b/src/lib.rs"));
+        assert!(lines[2].contains("@@ -1,3 +1,4 @@"));
+        assert!(lines.iter().any(|line| line.contains("-two")));
+        assert!(lines.iter().any(|line| line.contains("+2")));
+        assert!(lines.iter().any(|line| line.contains("+four")));
+        assert!(colored_diff(DiffEngine::Line, Path::new("a"), old, old).is_empty());
+        assert!(colored_diff(DiffEngine::Word, Path::new("a"), old, old).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_merges_changes_made_since_planning() {
+        let root = std::env::temp_dir().join(format!("bro-drift-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut service = BuildService::new(&root);
+        service.set_confirmation_mode(ConfirmationMode::None);
+        let path = root.join("lib.rs");
+        let planned = "fn a() {}\n\nfn b() {}\n\nfn c() {}\n";
+        let update = FileOperation::Update {
+            path: path.clone(),
+            old_content: planned.to_string(),
+            new_content: planned.replace("fn a() {}", "fn a() { 1 }"),
+        };
+
+        std::fs::write(&path, planned.replace("fn c() {}", "fn c() { 3 }")).unwrap();
+        service.execute_operation_once(&update).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "fn a() { 1 }\n\nfn b() {}\n\nfn c() { 3 }\n"
+        );
+
+        let edited = planned.replace("fn a() {}", "fn a() { 2 }");
+        std::fs::write(&path, &edited).unwrap();
+        assert!(service.execute_operation_once(&update).await.is_err());
+        service.set_drift_resolver(Arc::new(|_: &Path, merged: &MergeOutcome| {
+            assert_eq!(merged.conflicts, 1);
+            DriftChoice::KeepDisk
+        }));
+        service.execute_operation_once(&update).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), edited);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_gate_rolls_back_operations_that_fail_the_tests() {
+        let root = std::env::temp_dir().join(format!("bro-gate-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut service = BuildService::new(&root);
+        service.set_confirmation_mode(ConfirmationMode::None);
+        service.set_test_gate(Some(TestCommand::new("grep", &["-q", "fine", "lib.rs"])));
+        let path = root.join("lib.rs");
+        let create = |content: &str| FileOperation::Create {
+            path: path.clone(),
+            content: content.to_string(),
+        };
+
+        let error = service
+            .execute_operation_once(&create("broken\n"))
+            .await
+            .unwrap_err();
+        assert!(
+            error.downcast_ref::<TestGateFailed>().is_some(),
+            "{}",
+            error
+        );
+        assert!(!path.exists());
+        service
+            .execute_operation_once(&create("fine\n"))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fine\n");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_lint_hook_rolls_back_files_it_rejects() {
+        let root = std::env::temp_dir().join(format!("bro-lint-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut service = BuildService::new(&root);
+        service.set_confirmation_mode(ConfirmationMode::None);
+        let mut hooks = FormatHooks::default();
+        hooks.extensions.insert(
+            "txt".to_string(),
+            ExtensionHooks {
+                formatter: None,
+                linter: Some(vec![
+                    "grep".into(),
+                    "-qv".into(),
+                    "TODO".into(),
+                    "{file}".into(),
+                ]),
+            },
+        );
+        service.set_format_hooks(Some(hooks));
+        let path = root.join("notes.txt");
+        let create = |content: &str| FileOperation::Create {
+            path: path.clone(),
+            content: content.to_string(),
+        };
+
+        let error = service
+            .execute_operation_once(&create("TODO\n"))
+            .await
+            .unwrap_err();
+        let failure = error.downcast_ref::<LintFailed>().expect("a lint failure");
+        assert_eq!(failure.file, path);
+        assert!(!path.exists());
+        service
+            .execute_operation_once(&create("done\n"))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "done\n");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_group_reverts_every_file_when_one_operation_fails() {
+        let root = std::env::temp_dir().join(format!("bro-group-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut service = BuildService::new(&root);
+        service.set_confirmation_mode(ConfirmationMode::None);
+        let lib = root.join("lib.rs");
+        let main = root.join("main.rs");
+        std::fs::write(&lib, "pub fn old_name() {}\n").unwrap();
+        std::fs::write(&main, "fn main() { lib::old_name() }\n").unwrap();
+
+        let plan = BuildPlan {
+            goal: "rename old_name".to_string(),
+            operations: vec![
+                FileOperation::Update {
+                    path: lib.clone(),
+                    old_content: "pub fn old_name() {}\n".to_string(),
+                    new_content: "pub fn new_name() {}\n".to_string(),
+                },
+                FileOperation::Create {
+                    path: root.join("notes.md"),
+                    content: "renamed\n".to_string(),
+                },
+                FileOperation::Update {
+                    path: main.clone(),
+                    old_content: "fn main() { lib::old_name() }\n".to_string(),
+                    new_content: "fn main() { lib::new_name() }\n".to_string(),
+                },
+                // Fails: the file already exists
+                FileOperation::Create {
+                    path: main.clone(),
+                    content: String::new(),
+                },
+            ],
+            description: String::new(),
+            estimated_risk: RiskLevel::Medium,
+            groups: vec![OperationGroup {
+                name: "rename".to_string(),
+                paths: vec![lib.clone(), main.clone()],
+            }],
+        };
+        let units = plan.units();
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[0].group, Some("rename"));
+        assert_eq!(units[0].operations.len(), 3);
+        assert_eq!(units[1].operations[0].path(), root.join("notes.md"));
+
+        let error = service.execute_unit(&units[0]).await.unwrap_err();
+        assert!(
+            error.to_string().contains("group 'rename' was rolled back"),
+            "{}",
+            error
+        );
+        assert_eq!(
+            std::fs::read_to_string(&lib).unwrap(),
+            "pub fn old_name() {}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&main).unwrap(),
+            "fn main() { lib::old_name() }\n"
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn test_patch_keeps_unrelated_edits() {
+        let root = std::env::temp_dir().join(format!("bro-patch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut service = BuildService::new(&root);
+        service.set_confirmation_mode(ConfirmationMode::None);
+        let path = root.join("lib.rs");
+        std::fs::write(&path, "fn a() {}\n\nfn b() { 2 }\n").unwrap();
+        let operation = FileOperation::Patch {
+            path: path.clone(),
+            hunks: vec![PatchHunk {
+                search: "fn a() {}\n".to_string(),
+                replace: "fn a() { 1 }\n".to_string(),
+            }],
+        };
+        assert_eq!(service.assess_risk(&operation), RiskLevel::Medium);
+        service.execute_operation_once(&operation).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "fn a() { 1 }\n\nfn b() { 2 }\n"
+        );
+
+        let error = service
+            .execute_operation_once(&operation)
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains("Hunk 1/1 does not apply"),
+            "{}",
+            error
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
@@ -3,13 +3,152 @@
 //! This module provides intelligent partitioning of semantic memory collections
 //! by programming language, domain, or project for better organization and
 //! performance in production deployments.
+//!
+//! It also splits a repository's RAG index in two: code, and the project's
+//! knowledge (READMEs, `docs/` and architecture decision records). Each has
+//! its own collection and [`RetrievalTuning`], so prose does not crowd code
+//! out of results and architecture questions can lean on the documents.
 
 use crate::advanced_qdrant::AdvancedQdrantManager;
 use crate::semantic_memory::{ConversationMemory, SemanticMemoryService};
+use infrastructure::config::Config;
 use shared::types::Result;
 use std::collections::HashMap;
+use std::path::{Component, Path};
 use std::sync::Arc;
 
+/// Directories whose documents belong to the knowledge collection
+const KNOWLEDGE_DIRS: [&str; 5] = ["docs", "doc", "adr", "adrs", "decisions"];
+
+/// Extensions of prose documents
+const PROSE_EXTENSIONS: [&str; 5] = ["md", "markdown", "rst", "adoc", "txt"];
+
+/// Words marking a question about design rather than code
+const ARCHITECTURE_TERMS: [&str; 12] = [
+    "architecture",
+    "architectural",
+    "design",
+    "decision",
+    "decisions",
+    "adr",
+    "rationale",
+    "tradeoff",
+    "tradeoffs",
+    "overview",
+    "layers",
+    "conventions",
+];
+
+/// Factor on knowledge scores for questions about architecture and design
+pub const ARCHITECTURE_BOOST: f32 = 1.5;
+
+/// Which collection of a repository a file is indexed into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    Code,
+    /// Prose about the project: READMEs, `docs/` and decision records
+    Knowledge,
+}
+
+impl ContentKind {
+    pub const ALL: [ContentKind; 2] = [ContentKind::Code, ContentKind::Knowledge];
+
+    /// Kind of `path`, relative to the repository root
+    pub fn of(path: &Path) -> Self {
+        let readme = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.eq_ignore_ascii_case("readme"));
+        let prose = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| PROSE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        let in_docs = path
+            .parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .any(|component| match component {
+                Component::Normal(name) => name
+                    .to_str()
+                    .is_some_and(|name| KNOWLEDGE_DIRS.contains(&name.to_lowercase().as_str())),
+                _ => false,
+            });
+        if readme || (prose && in_docs) {
+            ContentKind::Knowledge
+        } else {
+            ContentKind::Code
+        }
+    }
+}
+
+/// How many results a collection contributes and how much its scores count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetrievalTuning {
+    pub top_k: usize,
+    pub weight: f32,
+}
+
+/// Retrieval tuning of the code and knowledge collections
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContentTuning {
+    pub code: RetrievalTuning,
+    pub knowledge: RetrievalTuning,
+}
+
+impl ContentTuning {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            code: RetrievalTuning {
+                top_k: config.rag_code_top_k,
+                weight: config.rag_code_weight,
+            },
+            knowledge: RetrievalTuning {
+                top_k: config.rag_knowledge_top_k,
+                weight: config.rag_knowledge_weight,
+            },
+        }
+    }
+
+    /// Tuning of `kind` when answering `question`
+    pub fn for_question(&self, kind: ContentKind, question: &str) -> RetrievalTuning {
+        match kind {
+            ContentKind::Code => self.code,
+            ContentKind::Knowledge if is_architecture_question(question) => RetrievalTuning {
+                weight: self.knowledge.weight * ARCHITECTURE_BOOST,
+                ..self.knowledge
+            },
+            ContentKind::Knowledge => self.knowledge,
+        }
+    }
+
+    /// Results kept across both collections
+    pub fn top_k(&self) -> usize {
+        self.code.top_k + self.knowledge.top_k
+    }
+}
+
+/// Whether `question` asks about architecture or design decisions
+pub fn is_architecture_question(question: &str) -> bool {
+    let lower = question.to_lowercase();
+    lower.contains("why do we")
+        || lower.contains("why did we")
+        || lower
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| ARCHITECTURE_TERMS.contains(&word))
+}
+
+/// Qdrant collection and SQLite database of the knowledge belonging to the
+/// code index in `collection` at `db_path`
+pub fn knowledge_store(collection: &str, db_path: &str) -> (String, String) {
+    (
+        format!("{}_knowledge", collection),
+        Path::new(db_path)
+            .with_extension("knowledge.db")
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PartitionType {
     Language,
@@ -417,3 +556,59 @@ pub mod partition_presets {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_knowledge_is_split_from_code_and_boosted_for_design_questions() {
+        for knowledge in [
+            "README.md",
+            "crates/api/readme",
+            "docs/setup.md",
+            "docs/adr/0003-use-qdrant.md",
+            "ADR/0001-record.rst",
+        ] {
+            assert_eq!(
+                ContentKind::of(Path::new(knowledge)),
+                ContentKind::Knowledge
+            );
+        }
+        for code in ["src/lib.rs", "docs/conf.py", "CHANGELOG.md", "src/docs.rs"] {
+            assert_eq!(ContentKind::of(Path::new(code)), ContentKind::Code);
+        }
+
+        let tuning = ContentTuning {
+            code: RetrievalTuning {
+                top_k: 50,
+                weight: 1.0,
+            },
+            knowledge: RetrievalTuning {
+                top_k: 10,
+                weight: 0.8,
+            },
+        };
+        let question = "Why did we split the storage layers?";
+        assert!(is_architecture_question(question));
+        assert!(!is_architecture_question(
+            "Where is the designer view rendered?"
+        ));
+        assert_eq!(
+            tuning.for_question(ContentKind::Knowledge, question).weight,
+            0.8 * ARCHITECTURE_BOOST
+        );
+        assert_eq!(
+            tuning.for_question(ContentKind::Code, question),
+            tuning.code
+        );
+        assert_eq!(tuning.top_k(), 60);
+        assert_eq!(
+            knowledge_store("vibe_rag", "/data/rag.db"),
+            (
+                "vibe_rag_knowledge".to_string(),
+                "/data/rag.knowledge.db".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! Pending build confirmations shared between the CLI and the web UI
+//!
+//! A build plan waiting for approval is written to
+//! `<data dir>/confirmations/<id>.json` with a diff for every operation. The CLI
+//! session that owns the plan and the web server run in separate processes, so
+//! both read and answer the same files: an operation approved from a phone is
+//! picked up by the CLI prompt, and decisions typed in the terminal show up in
+//! the web UI.
+
+use crate::build_service::{patched_content, BuildPlan, FileOperation, RiskLevel};
+use chrono::{DateTime, Utc};
+use infrastructure::file_queue::{FileQueue, QueueEntry};
+use serde::{Deserialize, Serialize};
+use shared::platform;
+use shared::types::Result;
+use similar::TextDiff;
+use std::path::{Path, PathBuf};
+
+/// Entries older than this belong to sessions that exited without cleaning up
+const STALE_AFTER_HOURS: i64 = 24;
+
+/// Largest file read to show what a delete removes
+const MAX_DELETE_PREVIEW_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Approved,
+    Rejected,
+}
+
+impl Decision {
+    pub fn from_approved(approved: bool) -> Self {
+        if approved {
+            Decision::Approved
+        } else {
+            Decision::Rejected
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingOperation {
+    pub index: usize,
+    /// `create`, `update`, `delete` or `read`
+    pub action: String,
+    pub path: String,
+    pub risk: RiskLevel,
+    /// Unified diff of the change, if it modifies content
+    pub diff: Option<String>,
+    pub decision: Option<Decision>,
+    /// Who decided: `cli` or `web`
+    pub decided_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingConfirmation {
+    pub id: String,
+    pub goal: String,
+    pub description: String,
+    pub estimated_risk: RiskLevel,
+    pub created_at: DateTime<Utc>,
+    /// Decision on the plan as a whole
+    pub decision: Option<Decision>,
+    pub decided_by: Option<String>,
+    pub operations: Vec<PendingOperation>,
+}
+
+impl PendingConfirmation {
+    /// Every operation has been approved or rejected
+    pub fn is_resolved(&self) -> bool {
+        self.operations.iter().all(|op| op.decision.is_some())
+    }
+}
+
+impl QueueEntry for PendingConfirmation {
+    const KIND: &'static str = "confirmation";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfirmationQueue {
+    queue: FileQueue<PendingConfirmation>,
+}
+
+impl ConfirmationQueue {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            queue: FileQueue::new(dir),
+        }
+    }
+
+    /// Queue shared by all local `bro` processes
+    pub fn open_default() -> Self {
+        Self::new(platform::app_data_dir().join("confirmations"))
+    }
+
+    /// Publish a plan for approval
+    pub fn submit(
+        &self,
+        plan: &BuildPlan,
+        assess: impl Fn(&FileOperation) -> RiskLevel,
+    ) -> Result<PendingConfirmation> {
+        let pending = PendingConfirmation {
+            id: uuid::Uuid::new_v4().to_string(),
+            goal: plan.goal.clone(),
+            description: plan.description.clone(),
+            estimated_risk: plan.estimated_risk,
+            created_at: Utc::now(),
+            decision: None,
+            decided_by: None,
+            operations: Self::pending_operations(&plan.operations, &assess),
+        };
+        self.queue.insert(&pending)?;
+        Ok(pending)
+    }
+
+    /// Replace the operations after the plan was edited, keeping decisions for
+    /// the first `keep` operations that are already reviewed
+    pub fn update_operations(
+        &self,
+        id: &str,
+        operations: &[FileOperation],
+        keep: usize,
+        assess: impl Fn(&FileOperation) -> RiskLevel,
+    ) -> Result<PendingConfirmation> {
+        self.queue.modify(id, |pending| {
+            let mut updated = Self::pending_operations(operations, &assess);
+            for (op, previous) in updated.iter_mut().zip(&pending.operations).take(keep) {
+                op.decision = previous.decision;
+                op.decided_by = previous.decided_by.clone();
+            }
+            pending.operations = updated;
+            Ok(())
+        })
+    }
+
+    /// Pending confirmations, oldest first; stale entries are discarded
+    pub fn list(&self) -> Result<Vec<PendingConfirmation>> {
+        let cutoff = Utc::now() - chrono::Duration::hours(STALE_AFTER_HOURS);
+        self.queue
+            .list(|confirmation| confirmation.created_at < cutoff)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<PendingConfirmation>> {
+        self.queue.get(id)
+    }
+
+    /// Decision recorded for one operation, if any
+    pub fn operation_decision(&self, id: &str, index: usize) -> Option<Decision> {
+        self.get(id).ok().flatten()?.operations.get(index)?.decision
+    }
+
+    /// Approve or reject the plan; `all_operations` applies the decision to every
+    /// undecided operation as well
+    pub fn decide_plan(
+        &self,
+        id: &str,
+        decision: Decision,
+        all_operations: bool,
+        source: &str,
+    ) -> Result<PendingConfirmation> {
+        self.queue.modify(id, |pending| {
+            pending.decision = Some(decision);
+            pending.decided_by = Some(source.to_string());
+            // Rejecting the plan rejects everything in it
+            if all_operations || decision == Decision::Rejected {
+                for op in pending
+                    .operations
+                    .iter_mut()
+                    .filter(|op| op.decision.is_none())
+                {
+                    op.decision = Some(decision);
+                    op.decided_by = Some(source.to_string());
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn decide_operation(
+        &self,
+        id: &str,
+        index: usize,
+        decision: Decision,
+        source: &str,
+    ) -> Result<PendingConfirmation> {
+        self.queue.modify(id, |pending| {
+            let op = pending
+                .operations
+                .get_mut(index)
+                .ok_or_else(|| anyhow::anyhow!("Confirmation {} has no operation {}", id, index))?;
+            op.decision = Some(decision);
+            op.decided_by = Some(source.to_string());
+            Ok(())
+        })
+    }
+
+    /// Drop a confirmation once its plan has run or been cancelled
+    pub fn remove(&self, id: &str) -> Result<()> {
+        self.queue.remove(id)
+    }
+
+    fn pending_operations(
+        operations: &[FileOperation],
+        assess: &impl Fn(&FileOperation) -> RiskLevel,
+    ) -> Vec<PendingOperation> {
+        operations
+            .iter()
+            .enumerate()
+            .map(|(index, operation)| {
+                let (action, path, diff) = match operation {
+                    FileOperation::Create { path, content } => {
+                        ("create", path, Some(unified_diff(path, "", content)))
+                    }
+                    FileOperation::Update {
+                        path,
+                        old_content,
+                        new_content,
+                    } => (
+                        "update",
+                        path,
+                        Some(unified_diff(path, old_content, new_content)),
+                    ),
+                    FileOperation::Delete { path } => {
+                        let existing = std::fs::metadata(path)
+                            .ok()
+                            .filter(|meta| meta.len() <= MAX_DELETE_PREVIEW_BYTES)
+                            .and_then(|_| std::fs::read_to_string(path).ok());
+                        (
+                            "delete",
+                            path,
+                            existing.map(|old| unified_diff(path, &old, "")),
+                        )
+                    }
+                    FileOperation::Patch { path, hunks } => (
+                        "patch",
+                        path,
+                        patched_content(path, hunks)
+                            .ok()
+                            .map(|(old, new)| unified_diff(path, &old, &new)),
+                    ),
+                    FileOperation::Read { path } => ("read", path, None),
+                    FileOperation::AddDependency {
+                        manifest,
+                        name,
+                        version,
+                    } => (
+                        "add_dependency",
+                        manifest,
+                        Some(format!(
+                            "+ {} {}",
+                            name,
+                            version.as_deref().unwrap_or("latest")
+                        )),
+                    ),
+                    FileOperation::RemoveDependency { manifest, name } => {
+                        ("remove_dependency", manifest, Some(format!("- {}", name)))
+                    }
+                };
+                PendingOperation {
+                    index,
+                    action: action.to_string(),
+                    path: path.display().to_string(),
+                    risk: assess(operation),
+                    diff,
+                    decision: None,
+                    decided_by: None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A plan published by the CLI for the duration of its review; dropping it
+/// withdraws the plan from the queue
+pub struct QueuedPlan {
+    queue: ConfirmationQueue,
+    id: String,
+}
+
+impl QueuedPlan {
+    pub fn publish(
+        queue: ConfirmationQueue,
+        plan: &BuildPlan,
+        assess: impl Fn(&FileOperation) -> RiskLevel,
+    ) -> Result<Self> {
+        let id = queue.submit(plan, assess)?.id;
+        Ok(Self { queue, id })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Plan-level decision, by whoever made it
+    pub fn plan_decision(&self) -> Option<(Decision, Option<String>)> {
+        let pending = self.queue.get(&self.id).ok().flatten()?;
+        pending
+            .decision
+            .map(|decision| (decision, pending.decided_by))
+    }
+
+    pub fn operation_decision(&self, index: usize) -> Option<Decision> {
+        self.queue.operation_decision(&self.id, index)
+    }
+
+    /// Record a decision made in the terminal unless one was already made remotely
+    pub fn record_plan(&self, decision: Decision) {
+        if self.plan_decision().is_none() {
+            if let Err(e) = self.queue.decide_plan(&self.id, decision, false, "cli") {
+                tracing::warn!("Failed to record plan decision: {}", e);
+            }
+        }
+    }
+
+    pub fn record_operation(&self, index: usize, decision: Decision) {
+        if let Err(e) = self
+            .queue
+            .decide_operation(&self.id, index, decision, "cli")
+        {
+            tracing::warn!("Failed to record operation decision: {}", e);
+        }
+    }
+
+    pub fn update_operations(
+        &self,
+        operations: &[FileOperation],
+        keep: usize,
+        assess: impl Fn(&FileOperation) -> RiskLevel,
+    ) {
+        if let Err(e) = self
+            .queue
+            .update_operations(&self.id, operations, keep, assess)
+        {
+            tracing::warn!("Failed to update pending confirmation: {}", e);
+        }
+    }
+}
+
+impl Drop for QueuedPlan {
+    fn drop(&mut self) {
+        let _ = self.queue.remove(&self.id);
+    }
+}
+
+/// Unified diff of a file change, for display outside the terminal
+pub fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    let name = path.display().to_string();
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", name), &format!("b/{}", name))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan() -> BuildPlan {
+        BuildPlan {
+            goal: "rename greeting".to_string(),
+            description: "Update greeting".to_string(),
+            operations: vec![
+                FileOperation::Update {
+                    path: PathBuf::from("src/main.rs"),
+                    old_content: "fn main() {\n    println!(\"hi\");\n}\n".to_string(),
+                    new_content: "fn main() {\n    println!(\"hello\");\n}\n".to_string(),
+                },
+                FileOperation::Create {
+                    path: PathBuf::from("NOTES.md"),
+                    content: "notes\n".to_string(),
+                },
+            ],
+            estimated_risk: RiskLevel::Medium,
+            groups: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_queue_round_trip_and_decisions() {
+        let dir = std::env::temp_dir().join(format!("bro-confirmations-{}", std::process::id()));
+        let queue = ConfirmationQueue::new(&dir);
+
+        let pending = queue.submit(&plan(), |_| RiskLevel::Low).unwrap();
+        let diff = pending.operations[0].diff.as_deref().unwrap();
+        assert!(diff.contains("--- a/src/main.rs"));
+        assert!(diff.contains("-    println!(\"hi\");\n+    println!(\"hello\");"));
+        assert_eq!(pending.operations[1].action, "create");
+
+        queue
+            .decide_operation(&pending.id, 0, Decision::Approved, "web")
+            .unwrap();
+        assert_eq!(
+            queue.operation_decision(&pending.id, 0),
+            Some(Decision::Approved)
+        );
+        assert!(!queue.get(&pending.id).unwrap().unwrap().is_resolved());
+
+        let rejected = queue
+            .decide_plan(&pending.id, Decision::Rejected, false, "cli")
+            .unwrap();
+        assert!(rejected.is_resolved());
+        assert_eq!(rejected.operations[0].decision, Some(Decision::Approved));
+        assert_eq!(rejected.operations[1].decision, Some(Decision::Rejected));
+
+        assert_eq!(queue.list().unwrap().len(), 1);
+        queue.remove(&pending.id).unwrap();
+        assert!(queue.list().unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_decisions_are_all_kept() {
+        let dir = std::env::temp_dir().join(format!(
+            "bro-confirmations-concurrent-{}",
+            std::process::id()
+        ));
+        let queue = ConfirmationQueue::new(&dir);
+        let mut plan = plan();
+        plan.operations = (0..16)
+            .map(|i| FileOperation::Create {
+                path: PathBuf::from(format!("file{}.txt", i)),
+                content: String::new(),
+            })
+            .collect();
+        let pending = queue.submit(&plan, |_| RiskLevel::Low).unwrap();
+
+        std::thread::scope(|scope| {
+            for index in 0..16 {
+                let (queue, id) = (&queue, &pending.id);
+                scope.spawn(move || {
+                    queue
+                        .decide_operation(id, index, Decision::Approved, "web")
+                        .unwrap();
+                });
+            }
+        });
+        assert!(queue.get(&pending.id).unwrap().unwrap().is_resolved());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
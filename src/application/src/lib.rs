@@ -1,8 +1,10 @@
 pub mod advanced_qdrant;
 pub mod advanced_scheduler;
 pub mod agent_service;
+pub mod ask_user;
 pub mod build_service;
 pub mod collection_partitioner;
+pub mod confirmation_queue;
 pub mod context_aware_validator;
 pub mod dynamic_scaling;
 pub mod explain_service;
@@ -13,16 +15,21 @@ pub mod memory_dashboard;
 pub mod memory_summarizer;
 pub mod metrics_collector;
 pub mod parallel_agent;
+pub mod prompts;
 pub mod rag_service;
+pub mod research_service;
 pub mod result_aggregator;
 pub mod safety_service;
 pub mod semantic_memory;
 pub mod streaming_agent;
+pub mod sub_agents;
 pub mod task_decomposer;
 pub mod transaction;
 pub mod voice_command_processor;
 pub mod voice_processing_service;
 
+use infrastructure::sampling::GenerationMode;
+
 /// Default agent service creation - uses Ollama (recommended)
 pub async fn create_agent_service() -> shared::types::Result<agent_service::AgentService> {
     create_agent_service_with_ollama()
@@ -30,41 +37,92 @@ pub async fn create_agent_service() -> shared::types::Result<agent_service::Agen
 
 /// Convenience function to create an AgentService with Ollama (for backward compatibility)
 pub fn create_agent_service_with_ollama() -> shared::types::Result<agent_service::AgentService> {
-    use infrastructure::{ollama_client::OllamaClient, InferenceEngine};
-
-    let ollama_client = OllamaClient::new()?;
-    let inference_engine = InferenceEngine::Ollama(ollama_client);
+    let inference_engine = default_inference_engine(GenerationMode::Agent, None)?;
 
     Ok(agent_service::AgentService::new(inference_engine))
 }
 
-/// Convenience function to create a RagService with Ollama inference
+/// Ollama sampling for `mode`, or canned replies from the fixture directory in
+/// `VIBE_MOCK_FIXTURES` so end-to-end tests run deterministically without a model
+fn default_inference_engine(
+    mode: GenerationMode,
+    embedding_model: Option<&str>,
+) -> shared::types::Result<infrastructure::InferenceEngine> {
+    use infrastructure::{
+        mock_inference::MockInference, ollama_client::OllamaClient, InferenceEngine,
+    };
+
+    if let Some(dir) = std::env::var_os("VIBE_MOCK_FIXTURES").filter(|dir| !dir.is_empty()) {
+        return Ok(InferenceEngine::Mock(MockInference::from_fixtures(dir)));
+    }
+    let mut ollama_client = OllamaClient::new()?.with_mode(mode);
+    if let Some(model) = embedding_model {
+        ollama_client = ollama_client.with_embedding_model(model);
+    }
+    Ok(InferenceEngine::Ollama(ollama_client))
+}
+
+/// Convenience function to create a RagService with Ollama inference. The first
+/// root is the primary repository; further roots are linked and searched too,
+/// each in its own collection and database.
 pub async fn create_rag_service(
-    root_path: &str,
+    root_paths: &[&str],
     db_path: &str,
 ) -> shared::types::Result<rag_service::RagService> {
-    create_rag_service_with_qdrant(root_path, db_path, None).await
+    create_rag_service_with_qdrant(root_paths, db_path, None).await
 }
 
 /// Create RAG service with optional Qdrant support
 pub async fn create_rag_service_with_qdrant(
-    root_path: &str,
+    root_paths: &[&str],
     db_path: &str,
     qdrant_url: Option<String>,
 ) -> shared::types::Result<rag_service::RagService> {
-    use infrastructure::{config::Config, ollama_client::OllamaClient, InferenceEngine};
+    let config = infrastructure::config::Config::load();
+    create_rag_service_with_config(root_paths, db_path, qdrant_url, config).await
+}
 
-    // Create default config for RAG
-    let config = Config::load();
+/// Create RAG service using `config` for the embedding model and indexing options
+pub async fn create_rag_service_with_config(
+    root_paths: &[&str],
+    db_path: &str,
+    qdrant_url: Option<String>,
+    config: infrastructure::config::Config,
+) -> shared::types::Result<rag_service::RagService> {
+    let (primary, linked) = root_paths
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("At least one repository root is required"))?;
 
     // Create Ollama inference service for RAG
-    let ollama_client = OllamaClient::new()?;
-    let inference_engine = InferenceEngine::Ollama(ollama_client);
+    let inference_engine =
+        default_inference_engine(GenerationMode::Chat, config.embedding_model.as_deref())?;
 
     // Create RAG service with hybrid storage (Qdrant + SQLite fallback)
-    let rag_service =
-        rag_service::RagService::new(root_path, db_path, qdrant_url, inference_engine, config)
-            .await?;
+    let mut rag_service = rag_service::RagService::new(
+        primary,
+        db_path,
+        qdrant_url.clone(),
+        inference_engine.clone(),
+        config.clone(),
+    )
+    .await?;
+
+    for root in linked {
+        let collection = rag_service::collection_for_repo(&rag_service::repo_name(root));
+        let db = std::path::Path::new(db_path);
+        let stem = db.file_stem().unwrap_or_default().to_string_lossy();
+        let repo_db_path = db.with_file_name(format!("{}_{}.db", stem, collection));
+        let repo = rag_service::RagService::new_with_collection(
+            root,
+            &repo_db_path.to_string_lossy(),
+            qdrant_url.clone(),
+            inference_engine.clone(),
+            config.clone(),
+            &collection,
+        )
+        .await?;
+        rag_service.link(repo)?;
+    }
 
     Ok(rag_service)
 }
@@ -73,12 +131,11 @@ pub async fn create_rag_service_with_qdrant(
 pub async fn create_agent_service_with_semantic_memory(
     qdrant_url: &str,
 ) -> shared::types::Result<agent_service::AgentService> {
-    use infrastructure::{embedder::Embedder, ollama_client::OllamaClient, InferenceEngine};
+    use infrastructure::embedder::Embedder;
     use std::sync::Arc;
 
     // Create Ollama inference engine
-    let ollama_client = OllamaClient::new()?;
-    let inference_engine = InferenceEngine::Ollama(ollama_client);
+    let inference_engine = default_inference_engine(GenerationMode::Agent, None)?;
 
     // Create embedder for semantic memory
     let embedder = Arc::new(Embedder::new_with_inference_engine(
@@ -86,8 +143,12 @@ pub async fn create_agent_service_with_semantic_memory(
     ));
 
     // Create semantic memory service
-    let semantic_memory =
-        Arc::new(semantic_memory::SemanticMemoryService::new(qdrant_url, embedder).await?);
+    let conflict_policy = infrastructure::config::PowerUserConfig::load().write_conflict_policy;
+    let semantic_memory = Arc::new(
+        semantic_memory::SemanticMemoryService::new(qdrant_url, embedder)
+            .await?
+            .with_conflict_handling(conflict_policy, None),
+    );
 
     // Create agent service with semantic memory
     Ok(agent_service::AgentService::new_with_semantic_memory(
@@ -96,6 +157,17 @@ pub async fn create_agent_service_with_semantic_memory(
     ))
 }
 
+/// Create a research service whose searching is bounded by `limits`
+pub fn create_research_service(
+    limits: infrastructure::agent_control::AgentExecutionLimits,
+) -> shared::types::Result<research_service::ResearchService> {
+    let inference_engine = default_inference_engine(GenerationMode::Chat, None)?;
+    Ok(research_service::ResearchService::new(
+        inference_engine,
+        limits,
+    ))
+}
+
 /// Create health monitor for production monitoring
 pub fn create_health_monitor(
     qdrant_url: &str,
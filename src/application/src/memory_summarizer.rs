@@ -3,13 +3,119 @@
 //! This module provides intelligent summarization of conversation memories to enable
 //! long-term retention while maintaining context and reducing storage requirements.
 //! Summaries preserve key insights, decisions, and patterns while compressing verbose details.
+//!
+//! Live conversations are kept within `history_token_budget` of the context
+//! config: once the history grows past it, all but the last
+//! `history_recent_turns` turns are replaced by a summary, which is what gets
+//! stored in semantic memory for the conversation from then on.
 
 use crate::semantic_memory::{ConversationMemory, SemanticMemoryService};
+use domain::models::ConversationMessage;
+use infrastructure::config::ContextConfig;
+use infrastructure::context_window::TokenCounter;
 use infrastructure::InferenceEngine;
 use shared::types::Result;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Start of the message that stands in for summarized turns
+pub const HISTORY_SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+
+/// Longest part of a single turn given to the summarizer
+const MAX_SUMMARIZED_TURN_CHARS: usize = 2000;
+
+/// When a conversation is compressed and how much of it stays verbatim
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SummarizationPolicy {
+    /// History size in tokens above which older turns are summarized; 0 never
+    pub token_budget: usize,
+    /// Most recent turns always kept verbatim
+    pub keep_recent: usize,
+}
+
+impl SummarizationPolicy {
+    pub fn from_config(config: &ContextConfig) -> Self {
+        Self {
+            token_budget: config.history_token_budget,
+            keep_recent: config.history_recent_turns,
+        }
+    }
+
+    /// How many of the oldest turns, given their token counts, to summarize;
+    /// 0 while the history is within budget
+    pub fn turns_to_summarize(&self, turn_tokens: &[usize]) -> usize {
+        let total: usize = turn_tokens.iter().sum();
+        if self.token_budget == 0 || total <= self.token_budget {
+            return 0;
+        }
+        let older = turn_tokens.len().saturating_sub(self.keep_recent);
+        // A lone turn would only be replaced by a summary of itself
+        if older < 2 {
+            return 0;
+        }
+        older
+    }
+}
+
+/// Replace the older turns of `history` with a summary when it exceeds the
+/// policy's budget, rewriting the stored conversation in `memory` to match.
+/// Returns how many turns were summarized.
+pub async fn compact_history(
+    engine: &InferenceEngine,
+    memory: Option<(&SemanticMemoryService, &str)>,
+    history: &mut Vec<ConversationMessage>,
+    policy: &SummarizationPolicy,
+    counter: &TokenCounter,
+) -> Result<usize> {
+    let tokens: Vec<usize> = history
+        .iter()
+        .map(|message| counter.count(&message.content))
+        .collect();
+    let older = policy.turns_to_summarize(&tokens);
+    if older == 0 {
+        return Ok(0);
+    }
+
+    let transcript = history[..older]
+        .iter()
+        .map(|message| {
+            let content: String = message
+                .content
+                .chars()
+                .take(MAX_SUMMARIZED_TURN_CHARS)
+                .collect();
+            format!("{}: {}", message.role, content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let prompt = format!(
+        "Summarize this conversation between a user and an assistant in at most {} tokens. Keep goals, decisions, file names, commands and open questions; drop pleasantries.\n\nCONVERSATION:\n{}\n\nSUMMARY:",
+        (policy.token_budget / 4).max(64),
+        transcript
+    );
+    let summary = engine.generate(&prompt).await?;
+
+    let recent = history.split_off(older);
+    history.clear();
+    history.push(ConversationMessage {
+        role: "system".to_string(),
+        content: format!("{}{}", HISTORY_SUMMARY_PREFIX, summary.trim()),
+        tool_calls: None,
+        tool_call_id: None,
+    });
+    history.extend(recent);
+
+    if let Some((memory, conversation_id)) = memory {
+        memory.delete_conversation(conversation_id).await?;
+        for (index, message) in history.iter().enumerate() {
+            memory
+                .store_message(conversation_id, index, message)
+                .await?;
+        }
+    }
+    Ok(older)
+}
+
 #[derive(Debug, Clone)]
 pub struct ConversationSummary {
     pub conversation_id: String,
@@ -52,6 +158,25 @@ impl MemorySummarizer {
         }
     }
 
+    /// Keep the live `history` of `conversation_id` within `policy`, storing
+    /// the summary in semantic memory
+    pub async fn compact_conversation(
+        &self,
+        conversation_id: &str,
+        history: &mut Vec<ConversationMessage>,
+        policy: &SummarizationPolicy,
+        counter: &TokenCounter,
+    ) -> Result<usize> {
+        compact_history(
+            &self.inference_engine,
+            Some((&self.semantic_memory, conversation_id)),
+            history,
+            policy,
+            counter,
+        )
+        .await
+    }
+
     /// Summarize a conversation for long-term retention
     pub async fn summarize_conversation(
         &self,
@@ -261,7 +386,7 @@ impl MemorySummarizer {
 
         // Use inference engine to generate summary
         match &*self.inference_engine {
-            InferenceEngine::Ollama(client) => {
+            InferenceEngine::Ollama(_) | InferenceEngine::Mock(_) => {
                 // Simple implementation - in practice you'd want proper inference
                 Ok(format!(
                     "This {} conversation covered {} main topics with {} questions asked and {} key decisions made. The discussion lasted approximately {} minutes with an average complexity score of {:.1}.",
@@ -398,3 +523,60 @@ struct ConversationAnalysis {
     sentiment_score: f32,
     complexity_score: f32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use infrastructure::mock_inference::MockInference;
+
+    fn message(role: &str, content: &str) -> ConversationMessage {
+        ConversationMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_over_budget_keeps_recent_turns_verbatim() {
+        let policy = SummarizationPolicy {
+            token_budget: 20,
+            keep_recent: 2,
+        };
+        assert_eq!(policy.turns_to_summarize(&[5, 5, 5]), 0);
+        assert_eq!(policy.turns_to_summarize(&[15, 15]), 0);
+        assert_eq!(policy.turns_to_summarize(&[10, 10, 5, 5]), 2);
+
+        let engine = InferenceEngine::Mock(
+            MockInference::new()
+                .with_response_containing("CONVERSATION:", "User wants sled sessions."),
+        );
+        let counter = TokenCounter::Estimate {
+            chars_per_token: 1.0,
+        };
+        let mut history = vec![
+            message("user", "Store sessions in sled"),
+            message("assistant", "Done, sessions use sled"),
+            message("user", "Add encryption"),
+            message("assistant", "Encrypted"),
+        ];
+        let summarized = compact_history(&engine, None, &mut history, &policy, &counter)
+            .await
+            .unwrap();
+
+        assert_eq!(summarized, 2);
+        assert_eq!(history.len(), 3);
+        assert_eq!(
+            history[0].content,
+            format!("{}User wants sled sessions.", HISTORY_SUMMARY_PREFIX)
+        );
+        assert_eq!(history[1].content, "Add encryption");
+        assert_eq!(
+            compact_history(&engine, None, &mut history, &policy, &counter)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+}
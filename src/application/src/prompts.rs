@@ -0,0 +1,504 @@
+//! Prompts sent to the model, built only from their inputs
+//!
+//! Anything gathered from the machine, such as the system context, the
+//! directory listing or the capabilities, is passed in, so the exact text of
+//! each prompt is pinned by snapshot tests (`cargo insta review` after an
+//! intended change).
+
+use crate::research_service::Finding;
+use infrastructure::config::SystemContext;
+use infrastructure::prompt_templates::{PromptTemplate, PromptTemplates};
+use shared::content_sanitizer::ContentSanitizer;
+use shared::types::Result;
+
+/// Lines of the running services listing included in a command prompt
+const COMMAND_SERVICE_LINES: usize = 20;
+
+/// Lines of the directory listing included in a command prompt
+const COMMAND_DIRECTORY_LINES: usize = 15;
+
+/// Bytes of a build session's diff included in a squash message prompt
+const SQUASH_DIFF_BYTES: usize = 12_000;
+
+/// Prompt turning `request` into a single shell command
+pub fn command_prompt(
+    templates: &PromptTemplates,
+    request: &str,
+    system: &SystemContext,
+    shell: &str,
+    services: &str,
+    directory: &str,
+) -> Result<String> {
+    let head = |text: &str, lines: usize| text.lines().take(lines).collect::<Vec<_>>().join("\n");
+    templates.render(
+        PromptTemplate::Command,
+        serde_json::json!({
+            "request": request,
+            "distro": system.distro,
+            "package_manager": system.package_manager,
+            "shell": shell,
+            "services": head(services, COMMAND_SERVICE_LINES),
+            "directory": head(directory, COMMAND_DIRECTORY_LINES),
+        }),
+    )
+}
+
+/// Prompt answering `question` from the packed `context`
+pub fn rag_prompt(
+    sanitizer: &ContentSanitizer,
+    instructions: &str,
+    question: &str,
+    context: &str,
+) -> String {
+    sanitizer
+        .create_secure_prompt(instructions, question, &[context])
+        .unwrap_or_else(|_| {
+            format!(
+                "SYSTEM: {}\n\nQUESTION: {}\n\nCONTEXT:\n{}\n\nRESPONSE:",
+                instructions, question, context
+            )
+        })
+}
+
+/// One-line summary of the machine used in agent prompts
+pub fn compact_system_context(system: &SystemContext) -> String {
+    format!(
+        "user={}@{}, os={} {}, distro={}, pkg_mgr={}, cwd={}, shell={}, display={}",
+        system.user,
+        system.hostname,
+        system.os_type,
+        system.kernel,
+        system.distro_id,
+        system.package_manager,
+        system.current_dir,
+        system.shell,
+        system.display_server
+    )
+}
+
+/// Prompt asking for a build plan for `goal` given the retrieved `context`
+pub fn build_planning_prompt(
+    goal: &str,
+    context: &[String],
+    system: &SystemContext,
+    capabilities: &str,
+) -> String {
+    let context_str = if context.is_empty() {
+        "No additional context available.".to_string()
+    } else {
+        context.join("\n\n")
+    };
+
+    format!(
+        r#"You are an expert engineer producing a compact, actionable build plan.
+
+GOAL:
+{goal}
+
+SYSTEM:
+{system}
+
+{capabilities}
+
+CONTEXT:
+{context}
+
+OUTPUT (plain text, no JSON):
+Build Plan:
+- Step 1: ...
+- Step 2: ...
+
+Files:
+- path: relative/path.ext
+- action: create|update|patch|add_dependency|remove_dependency
+- reason: short note
+- content in a fenced block:
+```file:path=relative/path.ext;action=create
+<full post-change content>
+```
+- for small edits to a large existing file use action=patch, with SEARCH/REPLACE blocks in the fence instead of the full content:
+```file:path=relative/path.ext;action=patch
+<<<<<<< SEARCH
+exact existing lines
+=======
+replacement lines
+>>>>>>> REPLACE
+```
+- to add or remove packages never edit the manifest by hand; use a fence on the manifest (Cargo.toml, package.json, requirements.txt) with one package per line, `name` or `name@version`, and give a version only when the goal asks for one:
+```file:path=Cargo.toml;action=add_dependency
+serde
+```
+- add ;group=<name> to the fence headers of files that must change together (e.g. a renamed function and its call sites); a group is applied all or nothing
+
+Safety: risks/backups/rollback
+Estimate: size/time
+Confidence: percentage
+
+Rules: keep it concise and deterministic; only include real files; if context is insufficient, reply 'Insufficient context to plan' and stop (do not invent files or behavior); if you cannot provide full content, say so and stop; prefer package manager {pkg_mgr}; consider display server {display_srv} for GUI hints."#,
+        goal = goal,
+        system = compact_system_context(system),
+        capabilities = capabilities,
+        context = context_str,
+        pkg_mgr = system.package_manager,
+        display_srv = system.display_server
+    )
+}
+
+/// Numbered findings, as the `[n]` a research brief cites them by
+fn numbered_findings(findings: &[Finding]) -> String {
+    findings
+        .iter()
+        .enumerate()
+        .map(|(i, finding)| {
+            format!(
+                "[{}] {} ({})\n{}",
+                i + 1,
+                finding.title,
+                finding.source,
+                finding.excerpt.trim()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Prompt asking for up to `max` further search queries on `topic`, or DONE
+pub fn research_queries_prompt(
+    topic: &str,
+    asked: &[String],
+    findings: &[Finding],
+    max: usize,
+) -> String {
+    let titles = findings
+        .iter()
+        .map(|finding| format!("- {} ({})", finding.title, finding.source))
+        .collect::<Vec<_>>();
+    format!(
+        r#"You are researching a topic by searching a code index and the web.
+
+TOPIC:
+{topic}
+
+QUERIES ALREADY RUN:
+{asked}
+
+FOUND SO FAR:
+{found}
+
+Reply with up to {max} new search queries, one per line and nothing else, that would fill the biggest gaps in what was found. Reply with DONE alone when the findings already cover the topic."#,
+        topic = topic,
+        asked = asked.join("\n"),
+        found = if titles.is_empty() {
+            "Nothing yet.".to_string()
+        } else {
+            titles.join("\n")
+        },
+        max = max
+    )
+}
+
+/// Prompt asking for a Markdown brief on `topic` citing the numbered findings
+pub fn research_brief_prompt(topic: &str, findings: &[Finding]) -> String {
+    format!(
+        r#"Write a concise research brief in Markdown on the topic below, using only the findings.
+
+TOPIC:
+{topic}
+
+FINDINGS:
+{findings}
+
+Rules: start with a short summary, then group what was learned under `##` headings; cite findings as [n] after each claim they support; say what remains unclear instead of guessing; do not add a title or a list of sources."#,
+        topic = topic,
+        findings = numbered_findings(findings)
+    )
+}
+
+/// Prompt asking for one commit message covering a whole build session
+pub fn squash_message_prompt(goal: &str, diff: &str) -> String {
+    let mut end = diff.len().min(SQUASH_DIFF_BYTES);
+    while !diff.is_char_boundary(end) {
+        end -= 1;
+    }
+    let truncated = if end < diff.len() {
+        "\n[diff truncated]"
+    } else {
+        ""
+    };
+    format!(
+        r#"Write a git commit message for the changes below, made for this goal.
+
+GOAL:
+{goal}
+
+DIFF:
+{diff}{truncated}
+
+Rules: a conventional commit subject of at most 72 characters, a blank line, then a short body saying what changed and why, wrapped at 72 columns; describe the change itself, not the steps taken to make it; reply with the message only, without code fences."#,
+        goal = goal,
+        diff = diff[..end].trim_end(),
+        truncated = truncated
+    )
+}
+
+/// Prompt asking for an edit to an existing file as SEARCH/REPLACE blocks
+/// instead of the whole file
+pub fn patch_edit_prompt(goal: &str, path: &str, content: &str) -> String {
+    format!(
+        r#"Task: Edit the file below to satisfy the goal. Reply with SEARCH/REPLACE blocks only, not the whole file.
+
+GOAL: {goal}
+FILE: {path}
+
+CURRENT FILE:
+{content}
+
+FORMAT (one block per change, as many blocks as needed):
+<<<<<<< SEARCH
+exact lines copied from the current file
+=======
+the lines that replace them
+>>>>>>> REPLACE
+
+RULES:
+- Copy SEARCH lines exactly, including indentation, with enough context to match only one place.
+- Keep blocks small: the changed lines plus a line or two around them.
+- To add code, SEARCH for the lines next to where it goes and repeat them in REPLACE.
+- No explanations and no markdown fences."#,
+        goal = goal,
+        path = path,
+        content = content.trim_end()
+    )
+}
+
+/// Planning feedback after `--gate tests` rolled an operation back
+pub fn test_gate_feedback(operation: &str, command: &str, output: &str) -> String {
+    format!(
+        r#"PREVIOUS ATTEMPT ROLLED BACK: `{command}` failed after {operation}. The operations before it were kept. Test output:
+```
+{output}
+```
+Plan the remaining changes so that these tests pass after every operation."#,
+        command = command,
+        operation = operation,
+        output = output.trim_end()
+    )
+}
+
+/// Replanning note after a formatter or linter rejected a written file
+pub fn lint_feedback(operation: &str, command: &str, output: &str) -> String {
+    format!(
+        r#"PREVIOUS ATTEMPT ROLLED BACK: `{command}` rejected the file written by {operation}. The operations before it were kept. Tool output:
+```
+{output}
+```
+Plan the remaining changes so that the written files pass this check."#,
+        command = command,
+        operation = operation,
+        output = output.trim_end()
+    )
+}
+
+/// Code review of `path` for batch jobs; `instructions` narrow it down
+pub fn review_prompt(path: &str, content: &str, instructions: &str) -> String {
+    let focus = match instructions.trim() {
+        "" => String::new(),
+        instructions => format!("\nFOCUS: {}\n", instructions),
+    };
+    format!(
+        r#"Review this file as a senior engineer would before merging it.
+{focus}
+FILE: {path}
+```
+{content}
+```
+
+List each problem as `line N: problem - suggested fix`, most severe first: bugs, security issues, error handling, then unclear code. Say "No problems found." when there are none; do not restate what the code does."#,
+        focus = focus,
+        path = path,
+        content = content.trim_end()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use infrastructure::rag_feedback::{format_corrections, RagCorrection};
+
+    fn system() -> SystemContext {
+        SystemContext {
+            os_type: "linux".to_string(),
+            distro: "Arch Linux".to_string(),
+            distro_id: "arch".to_string(),
+            kernel: "6.9.1-arch1-1".to_string(),
+            hostname: "devbox".to_string(),
+            current_dir: "/home/dev/bro".to_string(),
+            home_dir: "/home/dev".to_string(),
+            shell: "zsh".to_string(),
+            user: "dev".to_string(),
+            architecture: "x86_64".to_string(),
+            cpu_model: "AMD Ryzen 7 5800X".to_string(),
+            cpu_cores: "16".to_string(),
+            gpu_model: "Unknown".to_string(),
+            gpu_driver: "Unknown".to_string(),
+            ram_total: "32Gi".to_string(),
+            ram_used: "9Gi".to_string(),
+            terminal: "alacritty".to_string(),
+            package_manager: "pacman".to_string(),
+            desktop_env: "Unknown".to_string(),
+            window_manager: "sway".to_string(),
+            display_server: "wayland".to_string(),
+            uptime: "3 hours".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_command_prompt() {
+        let directory: String = (1..=20).map(|i| format!("file{}.txt\n", i)).collect();
+        let prompt = command_prompt(
+            &PromptTemplates::builtin(),
+            "is sshd running",
+            &system(),
+            "zsh",
+            "sshd.service\nnginx.service",
+            &directory,
+        )
+        .unwrap();
+        insta::assert_snapshot!(prompt);
+    }
+
+    #[test]
+    fn test_rag_prompt() {
+        let correction = RagCorrection {
+            question: "Where are sessions stored?".to_string(),
+            rejected_answer: "In Redis".to_string(),
+            feedback: "We use sled, not Redis".to_string(),
+            accepted_answer: "In a sled database per project".to_string(),
+            recorded_at: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            embedding: Vec::new(),
+        };
+        let instructions = format!(
+            "{}\n\n{}",
+            PromptTemplates::builtin()
+                .render(PromptTemplate::Rag, ())
+                .unwrap(),
+            format_corrections(&[correction])
+        );
+        let context = "FILE: src/session_store.rs\nOFFSET: 0\nLINES: 1-3\nLICENSE: MIT\nORIGIN: bro\npub struct SessionStore {\n    db: Db,\n}\n\nFILE: src/main.rs\nOFFSET: 0\nLINES: 1-1\nORIGIN: bro\nfn main() {}";
+        let prompt = rag_prompt(
+            &ContentSanitizer::new(),
+            &instructions,
+            "How are sessions persisted?",
+            context,
+        );
+        insta::assert_snapshot!(prompt);
+    }
+
+    #[test]
+    fn test_build_planning_prompt() {
+        let context = vec![
+            "FILE: src/lib.rs\npub mod config;".to_string(),
+            "FILE: src/config.rs\npub struct Config;".to_string(),
+        ];
+        let prompt = build_planning_prompt(
+            "add a --dry-run flag",
+            &context,
+            &system(),
+            "CAPABILITIES:\n- inference: local ollama\n- rag: enabled",
+        );
+        insta::assert_snapshot!(prompt);
+        insta::assert_snapshot!(
+            "build_planning_prompt_without_context",
+            build_planning_prompt("add a --dry-run flag", &[], &system(), "CAPABILITIES:")
+        );
+    }
+
+    #[test]
+    fn test_research_prompts() {
+        let findings = vec![
+            Finding {
+                source: "src/session_store.rs:1-3".to_string(),
+                title: "struct SessionStore".to_string(),
+                excerpt: "pub struct SessionStore {\n    db: Db,\n}".to_string(),
+            },
+            Finding {
+                source: "https://docs.rs/sled".to_string(),
+                title: "sled docs".to_string(),
+                excerpt: "An embedded database".to_string(),
+            },
+        ];
+        insta::assert_snapshot!(
+            "research_queries_prompt",
+            research_queries_prompt(
+                "session storage",
+                &["session storage".to_string()],
+                &findings,
+                3
+            )
+        );
+        insta::assert_snapshot!(
+            "research_brief_prompt",
+            research_brief_prompt("session storage", &findings)
+        );
+    }
+
+    #[test]
+    fn test_squash_message_prompt() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1,2 @@\n pub mod cli;\n+pub mod logging;\n";
+        insta::assert_snapshot!(
+            "squash_message_prompt",
+            squash_message_prompt("add logging", diff)
+        );
+        let long = "+é\n".repeat(SQUASH_DIFF_BYTES);
+        assert!(squash_message_prompt("x", &long).contains("[diff truncated]"));
+    }
+
+    #[test]
+    fn test_test_gate_feedback() {
+        insta::assert_snapshot!(
+            "test_gate_feedback",
+            test_gate_feedback(
+                "Update src/lib.rs",
+                "cargo test --quiet",
+                "test parse ... FAILED\n\nfailures:\n    parse\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_lint_feedback() {
+        insta::assert_snapshot!(
+            "lint_feedback",
+            lint_feedback(
+                "Create app.py",
+                "ruff check app.py",
+                "app.py:1:8: F401 `os` imported but unused\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_review_prompt() {
+        insta::assert_snapshot!(
+            "review_prompt",
+            review_prompt(
+                "src/lib.rs",
+                "fn div(a: i32, b: i32) -> i32 {\n    a / b\n}\n",
+                "panics"
+            )
+        );
+    }
+
+    #[test]
+    fn test_patch_edit_prompt() {
+        insta::assert_snapshot!(
+            "patch_edit_prompt",
+            patch_edit_prompt(
+                "log the greeting",
+                "src/main.rs",
+                "fn main() {\n    println!(\"hi\");\n}\n"
+            )
+        );
+    }
+}
@@ -1,24 +1,284 @@
+use crate::collection_partitioner::{self, ContentKind, ContentTuning};
+use crate::prompts;
+use colored::Colorize;
+use domain::models::Embedding;
 use infrastructure::{
     config::Config,
+    context_window::{
+        ContextWindow, MemoryScope, PackedChunks, PromptBreakdown, PromptSegments, ScoredChunk,
+        TruncationStrategy,
+    },
     embedder::{Embedder, EmbeddingInput},
-    file_scanner::FileScanner,
+    embedding_storage::CompactionReport,
+    file_scanner::{FileChunk, FileScanner},
+    git_repo::GitRepo,
     hybrid_storage::HybridStorage,
+    prompt_templates::{PromptTemplate, PromptTemplates},
+    provenance::{LicenseResolver, Provenance},
+    rag_feedback::{self, RagCorrection, RagFeedbackStore},
     search::SearchEngine,
 };
 use md5;
+use serde::Serialize;
 use shared::{
-    content_sanitizer::ContentSanitizer, secrets_detector::SecretsDetector, types::Result,
+    cancellation::CancellationToken, content_sanitizer::ContentSanitizer,
+    secrets_detector::SecretsDetector, types::Result, utils::is_supported_file,
 };
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Notify, OnceCell};
+
+/// Quiet period after a file change before re-indexing, so a burst of events
+/// (save-and-format, branch switch) becomes one update
+const REINDEX_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Files changed since the index last caught up. Repeated events for one file
+/// collapse into one entry, so nothing has to be dropped while the index is
+/// busy or not loaded yet.
+#[derive(Default)]
+pub struct ChangedPaths {
+    state: Mutex<PendingPaths>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct PendingPaths {
+    paths: BTreeSet<PathBuf>,
+    closed: bool,
+}
+
+impl ChangedPaths {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, path: PathBuf) {
+        self.lock().paths.insert(path);
+        self.notify.notify_one();
+    }
+
+    /// No more changes will come; the indexer stops once it has taken the
+    /// pending ones
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.notify.notify_one();
+    }
+
+    /// Wait until a change is pending; false once closed with none left
+    async fn wait(&self) -> bool {
+        loop {
+            {
+                let state = self.lock();
+                if !state.paths.is_empty() {
+                    return true;
+                }
+                if state.closed {
+                    return false;
+                }
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Wait until no change arrives for `quiet`
+    async fn settle(&self, quiet: Duration) {
+        while tokio::time::timeout(quiet, self.notify.notified())
+            .await
+            .is_ok()
+        {}
+    }
+
+    fn take(&self) -> BTreeSet<PathBuf> {
+        std::mem::take(&mut self.lock().paths)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PendingPaths> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Manifest key of the synthetic directory-tree chunk
+const DIR_OVERVIEW_PATH: &str = "__dir_overview__";
+
+/// Commit snapshots kept for `--at` queries; older ones are garbage collected
+const MAX_SNAPSHOTS: usize = 3;
+
+/// Score of context included regardless of similarity (README, directory tree),
+/// which keeps it ahead of retrieved chunks
+const PINNED_SCORE: f32 = f32::MAX;
+
+/// Alternative queries requested when query expansion is on
+const QUERY_EXPANSIONS: usize = 4;
+
+/// Earlier corrections shown as examples for a similar question
+const MAX_CORRECTION_EXAMPLES: usize = 3;
+
+/// Qdrant collection of the primary repository
+pub const DEFAULT_COLLECTION: &str = "vibe_rag";
 
 pub struct RagService {
     scanner: FileScanner,
     storage: HybridStorage,
+    /// READMEs, docs and decision records, kept apart from the code
+    knowledge: HybridStorage,
+    /// Results taken from each of the two collections
+    tuning: ContentTuning,
     embedder: Embedder,
     inference_engine: infrastructure::InferenceEngine,
+    /// Engine writing query expansions, on `rag_expansion_model` when set
+    expansion_engine: infrastructure::InferenceEngine,
     config: Config,
     content_sanitizer: ContentSanitizer,
     secrets_detector: SecretsDetector,
+    context_window: OnceCell<ContextWindow>,
+    git: Option<GitRepo>,
+    /// Commit whose snapshot answers queries instead of the working tree
+    revision: Option<String>,
+    /// Repository name used to attribute results and scope queries
+    name: String,
+    /// Further repositories searched alongside this one
+    linked: Vec<RagService>,
+    /// Repositories queries draw from; all of them when unset
+    scope: Option<Vec<String>>,
+    /// Corrections from earlier feedback on answers in this repository
+    feedback: RagFeedbackStore,
+    /// What went into the last prompt
+    last_breakdown: Mutex<Option<PromptBreakdown>>,
+}
+
+/// Progress events emitted by `query_with_feedback_streaming_events`
+#[derive(Debug, Clone, PartialEq)]
+pub enum RagStreamEvent {
+    /// Embedding the question and searching the index
+    Retrieving,
+    /// Sources that made it into the prompt, in relevance order
+    Citations(Vec<RagCitation>),
+    /// Context is ready and the model has started generating
+    Generating,
+    /// A piece of the answer
+    Token(String),
+}
+
+/// Outcome of an incremental index update
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexUpdate {
+    /// Files whose content changed and were re-embedded
+    pub reindexed: usize,
+    /// Deleted files removed from the index
+    pub pruned: usize,
+}
+
+/// Source of a retrieved chunk that made it into the prompt
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RagCitation {
+    /// Repository the chunk came from, when several are searched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    pub path: String,
+    pub offset: Option<usize>,
+    /// 1-based inclusive line range; missing for chunks indexed before lines were recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    /// Cosine similarity to the question; missing for context added regardless
+    /// of similarity, such as the README
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+    /// `kind name` of the enclosing definition, e.g. `function parse_args`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// SPDX license of the file; missing for chunks indexed before licenses
+    /// were recorded, or when none was found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+}
+
+impl RagCitation {
+    /// Extract unique sources from the `FILE:`/`OFFSET:`/`LINES:`/`SYMBOL:` headers
+    /// written at index time, and the `REPO:` header added when several
+    /// repositories are searched
+    pub fn from_chunks(chunks: &[ScoredChunk]) -> Vec<RagCitation> {
+        let mut citations: Vec<RagCitation> = Vec::new();
+        for chunk in chunks {
+            let Some(citation) = Self::parse(&chunk.text, chunk.score) else {
+                continue;
+            };
+            let duplicate = citations.iter().any(|c| {
+                c.repo == citation.repo && c.path == citation.path && c.offset == citation.offset
+            });
+            if !duplicate {
+                citations.push(citation);
+            }
+        }
+        citations
+    }
+
+    fn parse(chunk: &str, score: f32) -> Option<RagCitation> {
+        let mut lines = chunk.lines().peekable();
+        let repo = lines
+            .next_if(|line| line.starts_with("REPO:"))
+            .map(|line| line["REPO:".len()..].trim().to_string());
+        let path = lines.next()?.strip_prefix("FILE:")?.trim();
+        // Snapshot chunks are stored as `<commit>:<path>`
+        let path = split_snapshot_key(path).map_or(path, |(_, path)| path);
+
+        let mut citation = RagCitation {
+            repo,
+            path: path.to_string(),
+            offset: None,
+            start_line: None,
+            end_line: None,
+            score: (score != PINNED_SCORE).then_some(score),
+            symbol: None,
+            license: None,
+        };
+        for line in lines {
+            if let Some(offset) = line.strip_prefix("OFFSET:") {
+                citation.offset = offset.trim().parse().ok();
+            } else if let Some(range) = line.strip_prefix("LINES:") {
+                let (start, end) = range.trim().split_once('-')?;
+                citation.start_line = start.parse().ok();
+                citation.end_line = end.parse().ok();
+            } else if let Some(symbol) = line.strip_prefix("SYMBOL:") {
+                citation.symbol = Some(symbol.trim().to_string());
+            } else if let Some(license) = line.strip_prefix("LICENSE:") {
+                citation.license = Some(license.trim().to_string());
+            } else if line.starts_with("ORIGIN:") || line.starts_with("VENDORED:") {
+                continue;
+            } else {
+                break;
+            }
+        }
+        Some(citation)
+    }
+}
+
+/// A generated answer and the sources it was grounded in
+#[derive(Debug, Clone, Serialize)]
+pub struct RagAnswer {
+    pub answer: String,
+    pub citations: Vec<RagCitation>,
+}
+
+impl RagAnswer {
+    /// An answer that did not come from retrieved context
+    fn uncited(answer: String) -> Self {
+        Self {
+            answer,
+            citations: Vec::new(),
+        }
+    }
+}
+
+enum PreparedQuery {
+    Prompt {
+        prompt: String,
+        citations: Vec<RagCitation>,
+    },
+    Answer(String),
 }
 
 impl RagService {
@@ -29,23 +289,489 @@ impl RagService {
         inference_engine: infrastructure::InferenceEngine,
         config: Config,
     ) -> Result<Self> {
+        Self::new_with_collection(
+            root_path,
+            db_path,
+            qdrant_url,
+            inference_engine,
+            config,
+            DEFAULT_COLLECTION,
+        )
+        .await
+    }
+
+    /// Service for one repository stored in its own Qdrant collection and
+    /// SQLite database, so several can be linked without mixing indexes
+    pub async fn new_with_collection(
+        root_path: &str,
+        db_path: &str,
+        qdrant_url: Option<String>,
+        inference_engine: infrastructure::InferenceEngine,
+        config: Config,
+        collection_name: &str,
+    ) -> Result<Self> {
+        let git = if config.rag_git_aware {
+            GitRepo::discover(std::path::Path::new(root_path))
+        } else {
+            None
+        };
+        let scanner = match &git {
+            Some(repo) => FileScanner::new(root_path).with_git(repo.clone()),
+            None => FileScanner::new(root_path),
+        };
+
+        let embedder = Embedder::new_with_inference_engine(inference_engine.clone())
+            .with_batching(config.embedding_batch_size, config.embedding_concurrency);
+        let spec = embedder.spec(config.embedding_dimensions).await?;
+        let storage = HybridStorage::new(
+            qdrant_url.clone(),
+            db_path,
+            collection_name.to_string(),
+            &spec,
+            config.rag_reembed,
+        )
+        .await?;
+        let (knowledge_collection, knowledge_db) =
+            collection_partitioner::knowledge_store(collection_name, db_path);
+        let knowledge = HybridStorage::new(
+            qdrant_url,
+            knowledge_db,
+            knowledge_collection,
+            &spec,
+            config.rag_reembed,
+        )
+        .await?;
+        let secrets_detector = SecretsDetector::with_patterns(&config.power_user.secret_patterns)
+            .unwrap_or_else(|e| {
+                tracing::warn!("Ignoring custom secret patterns: {}", e);
+                SecretsDetector::new()
+            });
+        let expansion_engine = match &config.rag_expansion_model {
+            Some(model) => inference_engine.with_generation_model(model),
+            None => inference_engine.clone(),
+        };
+
         Ok(Self {
-            scanner: FileScanner::new(root_path),
-            storage: HybridStorage::new(qdrant_url, db_path, "vibe_rag".to_string(), 768).await?,
-            embedder: Embedder::new_with_inference_engine(inference_engine.clone()),
+            scanner,
+            storage,
+            knowledge,
+            tuning: ContentTuning::from_config(&config),
+            embedder,
             inference_engine,
+            expansion_engine,
             config,
             content_sanitizer: ContentSanitizer::new(),
-            secrets_detector: SecretsDetector::new(),
+            secrets_detector,
+            context_window: OnceCell::new(),
+            git,
+            revision: None,
+            name: repo_name(root_path),
+            linked: Vec::new(),
+            scope: None,
+            feedback: RagFeedbackStore::for_project(Path::new(root_path)),
+            last_breakdown: Mutex::new(None),
         })
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Search `repo` alongside this repository, attributing results by name
+    pub fn link(&mut self, repo: RagService) -> Result<()> {
+        if self.repo_names().contains(&repo.name.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Repository name '{}' is used twice; rename one of the directories",
+                repo.name
+            ));
+        }
+        self.linked.push(repo);
+        Ok(())
+    }
+
+    /// Names of this repository and every linked one
+    pub fn repo_names(&self) -> Vec<&str> {
+        self.repos().map(|repo| repo.name.as_str()).collect()
+    }
+
+    /// Restrict queries to the named repositories
+    pub fn set_scope(&mut self, names: &[String]) -> Result<()> {
+        let available = self.repo_names();
+        if let Some(unknown) = names.iter().find(|n| !available.contains(&n.as_str())) {
+            return Err(anyhow::anyhow!(
+                "Unknown repository '{}' (available: {})",
+                unknown,
+                available.join(", ")
+            ));
+        }
+        self.scope = Some(names.to_vec());
+        Ok(())
+    }
+
+    fn repos(&self) -> impl Iterator<Item = &RagService> {
+        std::iter::once(self).chain(&self.linked)
+    }
+
+    fn in_scope(&self, name: &str) -> bool {
+        self.scope
+            .as_ref()
+            .map_or(true, |scope| scope.iter().any(|s| s == name))
+    }
+
+    async fn window(&self) -> &ContextWindow {
+        self.context_window
+            .get_or_init(|| ContextWindow::for_engine(&self.config.context, &self.inference_engine))
+            .await
+    }
+
+    /// Pack retrieved chunks into what is left of the model's context window
+    /// once the fixed prompt and `reserve` extra tokens are accounted for,
+    /// preferring relevance per token and trimming chunks that only partly
+    /// fit. With the summarize strategy, overflowing context is summarized
+    /// first. Returns the context pieces to send and the packed chunks they
+    /// came from.
+    async fn fit_to_context_window(
+        &self,
+        instructions: &str,
+        question: &str,
+        feedback: &str,
+        chunks: Vec<ScoredChunk>,
+        reserve: usize,
+    ) -> (Vec<String>, PackedChunks) {
+        let window = self.window().await;
+        let fixed = format!("{}\n{}\n{}", instructions, question, feedback);
+        let budget = window
+            .prompt_budget()
+            .saturating_sub(window.count_tokens(&fixed) + reserve);
+
+        let mut chunks = chunks;
+        if window.strategy() == TruncationStrategy::SummarizeContext {
+            let segments = PromptSegments {
+                fixed,
+                turns: Vec::new(),
+                chunks,
+            };
+            let fitted = window.fit(segments, &self.inference_engine).await;
+            if let Some(notice) = &fitted.notice {
+                eprintln!("{} {}", "Note:".yellow(), notice);
+            }
+            // Summaries come back as a turn; keep them ahead of surviving chunks
+            chunks = fitted
+                .segments
+                .turns
+                .into_iter()
+                .map(|text| ScoredChunk {
+                    text,
+                    score: PINNED_SCORE,
+                })
+                .chain(fitted.segments.chunks)
+                .collect();
+        }
+
+        let packed = window.pack_chunks(chunks, budget);
+        if packed.dropped > 0 || packed.trimmed > 0 {
+            eprintln!(
+                "{} context packed into {} of {} available tokens: {} chunk(s) left out, {} trimmed",
+                "Note:".yellow(),
+                packed.tokens,
+                budget,
+                packed.dropped,
+                packed.trimmed
+            );
+        }
+        let context = packed.chunks.iter().map(|c| c.text.clone()).collect();
+        (context, packed)
+    }
+
+    /// Tokens per part of the last prompt sent, with the sources of its
+    /// context
+    pub fn last_prompt_breakdown(&self) -> Option<PromptBreakdown> {
+        self.last_breakdown.lock().ok()?.clone()
+    }
+
     pub async fn build_index(&self) -> Result<()> {
-        let files = self.scanner.collect_files()?;
-        self.build_index_with_files(&files).await
+        for repo in self.repos().filter(|repo| self.in_scope(&repo.name)) {
+            let files = repo.scanner.collect_files()?;
+            repo.build_index_with_files(&files).await?;
+            repo.prune_deleted().await?;
+        }
+        Ok(())
+    }
+
+    /// Deduplicate and drop stale points from every repository's index,
+    /// reporting what was reclaimed per repository
+    pub async fn compact_index(&self) -> Result<Vec<(String, CompactionReport)>> {
+        let mut reports = Vec::new();
+        for repo in self.repos() {
+            reports.push((repo.name.clone(), repo.storage.compact().await?));
+            reports.push((
+                format!("{} (knowledge)", repo.name),
+                repo.knowledge.compact().await?,
+            ));
+        }
+        Ok(reports)
+    }
+
+    /// Re-index only the given paths, as reported by a file watcher. Unchanged
+    /// files are skipped by content hash; deleted files and directories are
+    /// pruned from the index.
+    pub async fn reindex_paths(&self, paths: &[PathBuf]) -> Result<IndexUpdate> {
+        let mut update = IndexUpdate::default();
+        for repo in self.repos() {
+            let repo_update = repo.reindex_own_paths(paths).await?;
+            update.reindexed += repo_update.reindexed;
+            update.pruned += repo_update.pruned;
+        }
+        Ok(update)
+    }
+
+    async fn reindex_own_paths(&self, paths: &[PathBuf]) -> Result<IndexUpdate> {
+        let mut changed = Vec::new();
+        let mut removed = Vec::new();
+        for path in paths {
+            let Some(path) = self.scanner.path_in_root(path) else {
+                continue;
+            };
+            if path.is_file() {
+                if is_supported_file(&path) {
+                    changed.push(path);
+                }
+            } else if !path.exists() {
+                removed.push(path);
+            }
+        }
+
+        let mut update = IndexUpdate::default();
+        if !removed.is_empty() {
+            for storage in self.storages() {
+                for indexed in storage.list_indexed_paths().await? {
+                    // A removed directory takes every file below it along
+                    if removed.iter().any(|r| Path::new(&indexed).starts_with(r)) {
+                        storage.prune_path(indexed).await?;
+                        update.pruned += 1;
+                    }
+                }
+            }
+        }
+
+        let changed = self.filter_files_by_patterns(&changed);
+        if !changed.is_empty() {
+            update.reindexed = self.build_index_with_files(&changed).await?;
+        }
+        Ok(update)
+    }
+
+    /// Keep the index current from file-change notifications until `changes`
+    /// is closed
+    pub async fn reindex_on_change(&self, changes: &ChangedPaths) {
+        while changes.wait().await {
+            changes.settle(REINDEX_DEBOUNCE).await;
+            let paths: Vec<PathBuf> = changes.take().into_iter().collect();
+            match self.reindex_paths(&paths).await {
+                Ok(update) if update != IndexUpdate::default() => tracing::info!(
+                    "Index updated: {} file(s) re-embedded, {} pruned",
+                    update.reindexed,
+                    update.pruned
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Incremental re-indexing failed: {}", e),
+            }
+        }
+    }
+
+    /// Index the repository as of `rev` and answer queries from that snapshot.
+    /// Snapshots are kept, so revisiting a recent commit is instant.
+    pub async fn build_index_at(&mut self, rev: &str) -> Result<String> {
+        let repo = self
+            .git
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Querying at a revision requires a git repository"))?;
+        let commit = repo.resolve(rev)?;
+        let marker = snapshot_key(&commit, "");
+
+        if self.storage.get_file_hash(marker.clone()).await?.is_none() {
+            eprintln!("Indexing snapshot {}...", &commit[..12.min(commit.len())]);
+            let files: Vec<PathBuf> = repo
+                .files_at(&commit)?
+                .into_iter()
+                .map(PathBuf::from)
+                .filter(|path| is_supported_file(path))
+                .collect();
+
+            let mut inputs = Vec::new();
+            let mut licenses = LicenseResolver::new("", |path: &Path| {
+                repo.read_at(&commit, &path.to_string_lossy()).ok()
+            });
+            for file in self.filter_files_by_patterns(&files) {
+                let relative = file.to_string_lossy();
+                // Binary or non-UTF-8 blobs are not indexable
+                let Ok(content) = repo.read_at(&commit, &relative) else {
+                    continue;
+                };
+                let key = snapshot_key(&commit, &relative);
+                let scan = self.scanner.scan_content(Path::new(&key), &content);
+                if scan.chunks.is_empty() {
+                    continue;
+                }
+                let provenance = licenses.provenance(&file, &content, &self.name);
+                inputs.extend(
+                    scan.chunks
+                        .into_iter()
+                        .map(|chunk| Self::chunk_input(chunk, Some(&commit), &provenance)),
+                );
+                self.storage.upsert_file_hash(key, scan.hash).await?;
+            }
+
+            if !inputs.is_empty() {
+                let embeddings = self
+                    .embedder
+                    .generate_embeddings_with_progress(&inputs)
+                    .await?;
+                self.storage.insert_embeddings(embeddings).await?;
+            }
+        }
+
+        // The marker's value records when the snapshot was last used
+        self.storage
+            .upsert_file_hash(marker, chrono::Utc::now().timestamp().to_string())
+            .await?;
+        self.revision = Some(commit.clone());
+        self.gc_snapshots(&repo).await?;
+        Ok(commit)
+    }
+
+    /// Drop snapshots of commits that no longer exist and all but the most
+    /// recently used `MAX_SNAPSHOTS`
+    async fn gc_snapshots(&self, repo: &GitRepo) -> Result<usize> {
+        let indexed = self.storage.list_indexed_paths().await?;
+
+        let mut snapshots: Vec<(i64, String)> = Vec::new();
+        for key in &indexed {
+            if let Some((commit, "")) = split_snapshot_key(key) {
+                let last_used = self
+                    .storage
+                    .get_file_hash(key.clone())
+                    .await?
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or(0);
+                snapshots.push((last_used, commit.to_string()));
+            }
+        }
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.0));
+        let keep: Vec<String> = snapshots
+            .into_iter()
+            .map(|(_, commit)| commit)
+            .filter(|commit| Some(commit) == self.revision.as_ref() || repo.has_commit(commit))
+            .take(MAX_SNAPSHOTS)
+            .collect();
+
+        let mut collected = BTreeSet::new();
+        for key in indexed {
+            let Some((commit, _)) = split_snapshot_key(&key) else {
+                continue;
+            };
+            // Keys without a marker belong to an interrupted snapshot
+            if !keep.iter().any(|kept| kept == commit) {
+                collected.insert(commit.to_string());
+                self.storage.prune_path(key).await?;
+            }
+        }
+        if !collected.is_empty() {
+            eprintln!("Garbage collected {} old snapshot(s)", collected.len());
+        }
+        Ok(collected.len())
+    }
+
+    /// Embeddings of `kind` that may answer queries from every repository in
+    /// scope. With several repositories, each chunk is prefixed with a `REPO:`
+    /// header.
+    async fn searchable_embeddings(&self, kind: ContentKind) -> Result<Vec<Embedding>> {
+        let mut embeddings = Vec::new();
+        for repo in self.repos().filter(|repo| self.in_scope(&repo.name)) {
+            let mut own = repo.own_embeddings(kind).await?;
+            if !self.linked.is_empty() {
+                for embedding in &mut own {
+                    embedding.text = format!("REPO: {}\n{}", repo.name, embedding.text);
+                }
+            }
+            embeddings.extend(own);
+        }
+        Ok(embeddings)
+    }
+
+    /// This repository's embeddings of `kind`: the selected snapshot, or the
+    /// working tree when no revision is selected. Snapshots keep their
+    /// documents with the code.
+    async fn own_embeddings(&self, kind: ContentKind) -> Result<Vec<Embedding>> {
+        let storage = match kind {
+            ContentKind::Code => &self.storage,
+            ContentKind::Knowledge if self.revision.is_some() => return Ok(Vec::new()),
+            ContentKind::Knowledge => &self.knowledge,
+        };
+        let mut embeddings = storage.get_all_embeddings().await?;
+        embeddings.retain(|embedding| {
+            let snapshot = split_snapshot_key(&embedding.path).map(|(commit, _)| commit);
+            snapshot == self.revision.as_deref()
+        });
+        Ok(embeddings)
+    }
+
+    fn chunk_input(
+        chunk: FileChunk,
+        commit: Option<&String>,
+        provenance: &Provenance,
+    ) -> EmbeddingInput {
+        let symbol_line = chunk
+            .symbol
+            .as_ref()
+            .map(|symbol| format!("SYMBOL: {} {}\n", symbol.kind, symbol.name))
+            .unwrap_or_default();
+        EmbeddingInput {
+            id: format!("{}:{}", chunk.path, chunk.start_offset),
+            text: format!(
+                "FILE: {}\nOFFSET: {}\nLINES: {}-{}\n{}{}{}",
+                chunk.path,
+                chunk.start_offset,
+                chunk.start_line,
+                chunk.end_line(),
+                symbol_line,
+                provenance.header(),
+                chunk.text
+            ),
+            path: chunk.path,
+            symbol: chunk.symbol,
+            commit: commit.cloned(),
+        }
+    }
+
+    /// Drop index entries for files that no longer exist
+    async fn prune_deleted(&self) -> Result<usize> {
+        let mut pruned = 0;
+        for storage in self.storages() {
+            for indexed in storage.list_indexed_paths().await? {
+                if indexed != DIR_OVERVIEW_PATH
+                    && split_snapshot_key(&indexed).is_none()
+                    && !Path::new(&indexed).exists()
+                {
+                    storage.prune_path(indexed).await?;
+                    pruned += 1;
+                }
+            }
+        }
+        if pruned > 0 {
+            eprintln!("Pruned {} deleted file(s) from the index", pruned);
+        }
+        Ok(pruned)
     }
 
     pub async fn build_index_for_keywords(&self, keywords: &[String]) -> Result<()> {
+        for repo in self.repos().filter(|repo| self.in_scope(&repo.name)) {
+            repo.build_own_index_for_keywords(keywords).await?;
+        }
+        Ok(())
+    }
+
+    async fn build_own_index_for_keywords(&self, keywords: &[String]) -> Result<()> {
         let mut files = self.scanner.collect_files()?;
 
         // Apply include/exclude patterns first
@@ -99,15 +825,17 @@ impl RagService {
                 .collect();
         }
 
-        self.build_index_with_files(&files).await
+        self.build_index_with_files(&files).await?;
+        self.prune_deleted().await?;
+        Ok(())
     }
 
     pub async fn query(&self, question: &str) -> Result<String> {
-        self.query_with_feedback(question, "").await
+        Ok(self.query_with_feedback(question, "").await?.answer)
     }
 
     /// Query with streaming response for real-time feedback
-    pub async fn query_streaming<F>(&self, question: &str, mut on_chunk: F) -> Result<String>
+    pub async fn query_streaming<F>(&self, question: &str, on_chunk: F) -> Result<String>
     where
         F: FnMut(&str) + Send,
     {
@@ -115,242 +843,393 @@ impl RagService {
             .await
     }
 
-    pub async fn query_with_feedback(&self, question: &str, feedback: &str) -> Result<String> {
-        let query_embedding = self.inference_engine.generate_embeddings(question).await?;
-        let all_embeddings = self.storage.get_all_embeddings().await?;
-        let mut relevant_chunks =
-            SearchEngine::find_relevant_chunks(&query_embedding, &all_embeddings, 50);
-
-        // For project-level questions, include README and directory tree if available
-        if question.to_lowercase().contains("project")
-            || question.to_lowercase().contains("what is")
-        {
-            if let Ok(readme_content) = tokio::fs::read_to_string("README.md").await {
-                relevant_chunks.insert(0, format!("FILE: README.md\n{}", readme_content));
-            }
-            let dir_overview = self.scanner.directory_overview(8, 2000);
-            if !dir_overview.is_empty() {
-                relevant_chunks.insert(0, format!("DIRECTORY TREE:\n{}", dir_overview));
-            }
-        }
-
-        // Check for secrets in retrieved content
-        let mut contains_high_severity_secrets = false;
-        for chunk in &relevant_chunks {
-            if self.secrets_detector.contains_high_severity_secrets(chunk) {
-                contains_high_severity_secrets = true;
-                break;
-            }
-        }
-
-        if contains_high_severity_secrets {
-            return Ok("__SECRETS_DETECTED__: Retrieved content contains sensitive information. You may choose to continue with a sanitized version that masks secrets.".to_string());
-        }
+    /// Answer `question` from the index, citing the chunks the answer was grounded in
+    pub async fn query_with_feedback(&self, question: &str, feedback: &str) -> Result<RagAnswer> {
+        self.answer(question, feedback, false).await
+    }
 
-        // Sanitize all context chunks
-        let sanitized_chunks: Vec<String> = relevant_chunks
-            .into_iter()
-            .map(|chunk| {
-                // First sanitize content, then scan for secrets
-                let sanitized = self.content_sanitizer.sanitize_rag_content(&chunk).content;
-                // Scan again after sanitization (secrets should be masked)
-                let secrets_scan = self.secrets_detector.scan_content(&sanitized);
-                secrets_scan.sanitized_content
-            })
-            .collect();
+    /// Query with feedback, forcing continuation even if secrets are detected
+    pub async fn query_with_feedback_force(
+        &self,
+        question: &str,
+        feedback: &str,
+    ) -> Result<RagAnswer> {
+        self.answer(question, feedback, true).await
+    }
 
-        let context = sanitized_chunks.join("\n\n");
-        if context.is_empty() {
-            return Ok("No relevant code context found for this query.".to_string());
+    async fn answer(&self, question: &str, feedback: &str, force: bool) -> Result<RagAnswer> {
+        match self.prepare_prompt(question, feedback, force).await? {
+            PreparedQuery::Answer(answer) => Ok(RagAnswer::uncited(answer)),
+            PreparedQuery::Prompt { prompt, citations } => Ok(RagAnswer {
+                answer: self.inference_engine.generate(&prompt).await?,
+                citations,
+            }),
         }
-
-        // Sanitize user inputs
-        let sanitized_question = self
-            .content_sanitizer
-            .sanitize_user_input(question)
-            .unwrap_or_else(|_| "Invalid question provided".to_string());
-
-        let sanitized_feedback = if feedback.is_empty() {
-            String::new()
-        } else {
-            match self.content_sanitizer.sanitize_user_input(feedback) {
-                Ok(f) => format!("\n\nUser feedback for improvement: {}", f),
-                Err(_) => String::new(),
-            }
-        };
-
-        // Create secure prompt with sanitized content
-        let context_refs: Vec<&str> = vec![&context];
-        let prompt = self.content_sanitizer.create_secure_prompt(
-            "Answer strictly from the provided context. If the context is insufficient, reply: 'Insufficient context to answer.'",
-            &sanitized_question,
-            &context_refs,
-        ).unwrap_or_else(|_| format!(
-            "SYSTEM: Answer strictly from the provided context. If insufficient, reply: 'Insufficient context to answer.'\n\nQUESTION: {}\n\nCONTEXT:\n{}\n\nRESPONSE:",
-            sanitized_question, context
-        ));
-        self.inference_engine.generate(&prompt).await
     }
 
     /// Query with feedback and streaming response
     pub async fn query_with_feedback_streaming<F>(
+        &self,
+        question: &str,
+        feedback: &str,
+        on_chunk: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.query_with_feedback_streaming_cancellable(
+            question,
+            feedback,
+            on_chunk,
+            &CancellationToken::new(),
+        )
+        .await
+    }
+
+    /// Streaming query that stops generating when `cancel` fires, returning the partial answer
+    pub async fn query_with_feedback_streaming_cancellable<F>(
         &self,
         question: &str,
         feedback: &str,
         mut on_chunk: F,
+        cancel: &CancellationToken,
     ) -> Result<String>
     where
         F: FnMut(&str) + Send,
     {
-        let query_embedding = self.inference_engine.generate_embeddings(question).await?;
-        let all_embeddings = self.storage.get_all_embeddings().await?;
-        let mut relevant_chunks =
-            SearchEngine::find_relevant_chunks(&query_embedding, &all_embeddings, 50);
+        let answer = self
+            .query_with_feedback_streaming_events(
+                question,
+                feedback,
+                |event| {
+                    if let RagStreamEvent::Token(token) = event {
+                        on_chunk(&token)
+                    }
+                },
+                cancel,
+            )
+            .await?;
+        Ok(answer.answer)
+    }
+
+    /// Streaming query that also reports retrieval progress and the sources used,
+    /// for frontends that render more than the raw token stream
+    pub async fn query_with_feedback_streaming_events<F>(
+        &self,
+        question: &str,
+        feedback: &str,
+        mut on_event: F,
+        cancel: &CancellationToken,
+    ) -> Result<RagAnswer>
+    where
+        F: FnMut(RagStreamEvent) + Send,
+    {
+        on_event(RagStreamEvent::Retrieving);
+        match self.prepare_prompt(question, feedback, false).await? {
+            PreparedQuery::Answer(answer) => {
+                on_event(RagStreamEvent::Token(answer.clone()));
+                Ok(RagAnswer::uncited(answer))
+            }
+            PreparedQuery::Prompt { prompt, citations } => {
+                on_event(RagStreamEvent::Citations(citations.clone()));
+                on_event(RagStreamEvent::Generating);
+                let answer = self
+                    .inference_engine
+                    .generate_streaming_cancellable(
+                        &prompt,
+                        |chunk| on_event(RagStreamEvent::Token(chunk.to_string())),
+                        cancel,
+                    )
+                    .await?;
+                Ok(RagAnswer { answer, citations })
+            }
+        }
+    }
+
+    /// Chunks most relevant to `question` that the context policy lets into a
+    /// prompt, without generating an answer
+    pub async fn search(&self, question: &str) -> Result<Vec<ScoredChunk>> {
+        let mut chunks = self.retrieve(question).await?;
+        self.apply_context_policy(&mut chunks);
+        Ok(chunks)
+    }
+
+    /// Drop chunks the `context_policy` keeps out of prompts, saying why
+    fn apply_context_policy(&self, chunks: &mut Vec<ScoredChunk>) {
+        let policy = &self.config.power_user.context_policy;
+        if policy.is_empty() {
+            return;
+        }
+        let before = chunks.len();
+        let mut reasons = BTreeSet::new();
+        chunks.retain(|chunk| {
+            let exclusion = Provenance::from_chunk(&chunk.text)
+                .and_then(|provenance| policy.exclusion(&provenance));
+            match exclusion {
+                Some(reason) => {
+                    reasons.insert(reason);
+                    false
+                }
+                None => true,
+            }
+        });
+        if !reasons.is_empty() {
+            eprintln!(
+                "{}",
+                format!(
+                    "Context policy left out {} chunk(s): {}",
+                    before - chunks.len(),
+                    reasons.into_iter().collect::<Vec<_>>().join(", ")
+                )
+                .dimmed()
+            );
+        }
+    }
+
+    /// Retrieve, sanitize and fit context for a query. Returns a canned answer
+    /// instead of a prompt when generation should not happen; `force` continues
+    /// past detected secrets, which are masked.
+    async fn prepare_prompt(
+        &self,
+        question: &str,
+        feedback: &str,
+        force: bool,
+    ) -> Result<PreparedQuery> {
+        let redactor = self.config.prompt_redactor();
+        let pii = self.config.pii_detector();
+        let template = PromptTemplates::global().render(PromptTemplate::Rag, ())?;
+        let corrections = self.corrections_for(question).await;
+        let corrections_section = if corrections.is_empty() {
+            String::new()
+        } else {
+            // Earlier answers quote the code as much as retrieved chunks do
+            let section = redactor.redact(&rag_feedback::format_corrections(&corrections))?;
+            match &pii {
+                Some(pii) => pii.apply(&section)?,
+                None => section,
+            }
+        };
+        let instructions = if corrections_section.is_empty() {
+            template.clone()
+        } else {
+            format!("{}\n\n{}", template, corrections_section)
+        };
+        let mut relevant_chunks = self.retrieve(question).await?;
+        self.apply_context_policy(&mut relevant_chunks);
 
         // For project-level questions, include README and directory tree if available
         if question.to_lowercase().contains("project")
             || question.to_lowercase().contains("what is")
         {
             if let Ok(readme_content) = tokio::fs::read_to_string("README.md").await {
-                relevant_chunks.insert(0, format!("FILE: README.md\n{}", readme_content));
+                relevant_chunks.insert(
+                    0,
+                    ScoredChunk {
+                        text: format!("FILE: README.md\n{}", readme_content),
+                        score: PINNED_SCORE,
+                    },
+                );
             }
             let dir_overview = self.scanner.directory_overview(8, 2000);
             if !dir_overview.is_empty() {
-                relevant_chunks.insert(0, format!("DIRECTORY TREE:\n{}", dir_overview));
+                relevant_chunks.insert(
+                    0,
+                    ScoredChunk {
+                        text: format!("DIRECTORY TREE:\n{}", dir_overview),
+                        score: PINNED_SCORE,
+                    },
+                );
             }
         }
 
         // Check for secrets in retrieved content
-        let mut contains_high_severity_secrets = false;
-        for chunk in &relevant_chunks {
-            if self.secrets_detector.contains_high_severity_secrets(chunk) {
-                contains_high_severity_secrets = true;
-                break;
-            }
+        let contains_high_severity_secrets = relevant_chunks.iter().any(|chunk| {
+            self.secrets_detector
+                .contains_high_severity_secrets(&chunk.text)
+        });
+        if contains_high_severity_secrets && !force {
+            return Ok(PreparedQuery::Answer("__SECRETS_DETECTED__: Retrieved content contains sensitive information. You may choose to continue with a sanitized version that masks secrets.".to_string()));
         }
 
-        if contains_high_severity_secrets {
-            return Ok("__SECRETS_DETECTED__: Retrieved content contains sensitive information. You may choose to continue with a sanitized version that masks secrets.".to_string());
-        }
-
-        // Sanitize all context chunks
-        let sanitized_chunks: Vec<String> = relevant_chunks
+        // Sanitize all context chunks, then replace secrets with placeholders
+        // and mask personal data for a remote model
+        let sanitized_chunks: Vec<ScoredChunk> = relevant_chunks
             .into_iter()
             .map(|chunk| {
-                // First sanitize content, then scan for secrets
-                let sanitized = self.content_sanitizer.sanitize_rag_content(&chunk).content;
-                // Scan again after sanitization (secrets should be masked)
-                let secrets_scan = self.secrets_detector.scan_content(&sanitized);
-                secrets_scan.sanitized_content
+                let sanitized = self
+                    .content_sanitizer
+                    .sanitize_rag_content(&chunk.text)
+                    .content;
+                let text = redactor.redact(&sanitized)?;
+                Ok(ScoredChunk {
+                    text: match &pii {
+                        Some(pii) => pii.apply(&text)?,
+                        None => text,
+                    },
+                    score: chunk.score,
+                })
             })
-            .collect();
-
-        let context = sanitized_chunks.join("\n\n");
-        if context.is_empty() {
-            return Ok("No relevant code context found for this query.".to_string());
-        }
+            .collect::<Result<_>>()?;
 
         // Sanitize user inputs
         let sanitized_question = self
             .content_sanitizer
             .sanitize_user_input(question)
             .unwrap_or_else(|_| "Invalid question provided".to_string());
+        let sanitized_question = match self.content_sanitizer.sanitize_user_input(feedback) {
+            Ok(feedback) if !feedback.trim().is_empty() => format!(
+                "{}\n\nFEEDBACK ON THE PREVIOUS ANSWER: {}",
+                sanitized_question,
+                feedback.trim()
+            ),
+            _ => sanitized_question,
+        };
 
-        let sanitized_feedback = if feedback.is_empty() {
-            String::new()
-        } else {
-            match self.content_sanitizer.sanitize_user_input(feedback) {
-                Ok(f) => format!("\n\nUser feedback for improvement: {}", f),
-                Err(_) => String::new(),
+        // The prompt wrapper adds tokens the packer cannot see; repack with
+        // that much less room until the final prompt fits the window
+        let window = self.window().await;
+        let mut reserve = 0;
+        let (prompt, citations) = loop {
+            let (context, packed) = self
+                .fit_to_context_window(
+                    &instructions,
+                    question,
+                    feedback,
+                    sanitized_chunks.clone(),
+                    reserve,
+                )
+                .await;
+            let context = context.join("\n\n");
+            if context.is_empty() {
+                return Ok(PreparedQuery::Answer(
+                    "No relevant code context found for this query.".to_string(),
+                ));
             }
+
+            // Create secure prompt with sanitized content
+            let prompt = prompts::rag_prompt(
+                &self.content_sanitizer,
+                &instructions,
+                &sanitized_question,
+                &context,
+            );
+            let overflow = window
+                .count_tokens(&prompt)
+                .saturating_sub(window.prompt_budget());
+            if overflow == 0 {
+                let mut breakdown = PromptBreakdown::new(window);
+                breakdown.add_section(window, "instructions", &template);
+                for correction in &corrections {
+                    breakdown.add_memory(
+                        window,
+                        &correction.label(),
+                        &rag_feedback::format_corrections(std::slice::from_ref(correction)),
+                    );
+                }
+                breakdown.add_section(window, "question and feedback", &sanitized_question);
+                for chunk in &packed.chunks {
+                    breakdown.add_chunk(window, &chunk_source(&chunk.text), &chunk.text);
+                }
+                breakdown.dropped_chunks = packed.dropped;
+                breakdown.trimmed_chunks = packed.trimmed;
+                if let Ok(mut last) = self.last_breakdown.lock() {
+                    *last = Some(breakdown);
+                }
+                break (prompt, RagCitation::from_chunks(&packed.chunks));
+            }
+            reserve += overflow;
         };
 
-        // Create secure prompt with sanitized content
-        let context_refs: Vec<&str> = vec![&context];
-        let prompt = self.content_sanitizer.create_secure_prompt(
-            "Answer strictly from the provided context. If the context is insufficient, reply: 'Insufficient context to answer.'",
-            &sanitized_question,
-            &context_refs,
-        ).unwrap_or_else(|_| format!(
-            "SYSTEM: Answer strictly from the provided context. If insufficient, reply: 'Insufficient context to answer.'\n\nQUESTION: {}\n\nCONTEXT:\n{}\n\nRESPONSE:",
-            sanitized_question, context
-        ));
-
-        // Use streaming inference for real-time response
-        self.inference_engine
-            .generate_streaming(&prompt, on_chunk)
-            .await
+        Ok(PreparedQuery::Prompt { prompt, citations })
     }
 
-    /// Query with feedback, forcing continuation even if secrets are detected
-    pub async fn query_with_feedback_force(
+    /// Remember how a rejected answer was corrected, so similar questions
+    /// are answered with the correction in mind
+    pub async fn record_correction(
         &self,
         question: &str,
+        rejected_answer: &str,
         feedback: &str,
-    ) -> Result<String> {
-        let query_embedding = self.inference_engine.generate_embeddings(question).await?;
-        let all_embeddings = self.storage.get_all_embeddings().await?;
-        let mut relevant_chunks =
-            SearchEngine::find_relevant_chunks(&query_embedding, &all_embeddings, 50);
+        accepted_answer: &str,
+    ) -> Result<()> {
+        let embedding = self.inference_engine.generate_embeddings(question).await?;
+        self.feedback.record(RagCorrection {
+            question: question.to_string(),
+            rejected_answer: rejected_answer.to_string(),
+            feedback: feedback.to_string(),
+            accepted_answer: accepted_answer.to_string(),
+            recorded_at: chrono::Utc::now(),
+            embedding,
+        })
+    }
 
-        // For project-level questions, include README and directory tree if available
-        if question.to_lowercase().contains("project")
-            || question.to_lowercase().contains("what is")
-        {
-            if let Ok(readme_content) = tokio::fs::read_to_string("README.md").await {
-                relevant_chunks.insert(0, format!("FILE: README.md\n{}", readme_content));
-            }
-            let dir_overview = self.scanner.directory_overview(8, 2000);
-            if !dir_overview.is_empty() {
-                relevant_chunks.insert(0, format!("DIRECTORY TREE:\n{}", dir_overview));
+    /// Corrections recorded for questions similar to `question`; they are
+    /// kept per project, so only turning memory off leaves them out
+    async fn corrections_for(&self, question: &str) -> Vec<RagCorrection> {
+        if self.config.context.memory_scope == MemoryScope::Off || self.feedback.is_empty() {
+            return Vec::new();
+        }
+        let limit = MAX_CORRECTION_EXAMPLES.min(self.config.context.memory_limit);
+        match self.inference_engine.generate_embeddings(question).await {
+            Ok(embedding) => self.feedback.similar(&embedding, limit),
+            Err(e) => {
+                tracing::warn!("Skipping earlier corrections: {}", e);
+                Vec::new()
             }
         }
+    }
 
-        // Force proceed with sanitization even if secrets detected
-
-        // Sanitize all context chunks
-        let sanitized_chunks: Vec<String> = relevant_chunks
-            .into_iter()
-            .map(|chunk| {
-                // First sanitize content, then scan for secrets
-                let sanitized = self.content_sanitizer.sanitize_rag_content(&chunk).content;
-                // Scan again after sanitization (secrets should be masked)
-                let secrets_scan = self.secrets_detector.scan_content(&sanitized);
-                secrets_scan.sanitized_content
-            })
-            .collect();
+    /// Chunks most similar to the question or, with query expansion, to any
+    /// of its variants, drawn from the code and knowledge collections as
+    /// tuned for the question
+    async fn retrieve(&self, question: &str) -> Result<Vec<ScoredChunk>> {
+        let query_embeddings = if self.config.rag_query_expansion {
+            let queries = self.expand_query(question).await;
+            self.inference_engine
+                .generate_embeddings_batch(queries)
+                .await?
+        } else {
+            vec![self.inference_engine.generate_embeddings(question).await?]
+        };
 
-        let context = sanitized_chunks.join("\n\n");
-        if context.is_empty() {
-            return Ok("No relevant code context found for this query.".to_string());
+        let mut results = Vec::new();
+        for kind in ContentKind::ALL {
+            let tuning = self.tuning.for_question(kind, question);
+            let embeddings = self.searchable_embeddings(kind).await?;
+            for query_embedding in &query_embeddings {
+                let mut chunks =
+                    SearchEngine::find_relevant_scored(query_embedding, &embeddings, tuning.top_k);
+                for chunk in &mut chunks {
+                    chunk.score *= tuning.weight;
+                }
+                results.push(chunks);
+            }
         }
+        Ok(union_results(results, self.tuning.top_k()))
+    }
 
-        // Sanitize user inputs
-        let sanitized_question = self
-            .content_sanitizer
-            .sanitize_user_input(question)
-            .unwrap_or_else(|_| "Invalid question provided".to_string());
-
-        let sanitized_feedback = if feedback.is_empty() {
-            String::new()
-        } else {
-            match self.content_sanitizer.sanitize_user_input(feedback) {
-                Ok(f) => format!("\n\nUser feedback for improvement: {}", f),
-                Err(_) => String::new(),
-            }
+    /// The question followed by model-written paraphrases and keyword
+    /// variants; just the question when the model fails
+    async fn expand_query(&self, question: &str) -> Vec<String> {
+        let prompt = PromptTemplates::global().render(
+            PromptTemplate::QueryExpansion,
+            serde_json::json!({ "question": question, "count": QUERY_EXPANSIONS }),
+        );
+        let reply = match prompt {
+            Ok(prompt) => self.expansion_engine.generate(&prompt).await,
+            Err(e) => Err(e),
         };
-
-        // Create secure prompt with sanitized content
-        let context_refs: Vec<&str> = vec![&context];
-        let prompt = self.content_sanitizer.create_secure_prompt(
-            "Answer strictly from the provided context. If the context is insufficient, reply: 'Insufficient context to answer.'",
-            &sanitized_question,
-            &context_refs,
-        ).unwrap_or_else(|_| format!(
-            "SYSTEM: Answer strictly from the provided context. If insufficient, reply: 'Insufficient context to answer.'\n\nQUESTION: {}\n\nCONTEXT:\n{}\n\nRESPONSE:",
-            sanitized_question, context
-        ));
-        self.inference_engine.generate(&prompt).await
+        match reply {
+            Ok(reply) => parse_expansions(question, &reply, QUERY_EXPANSIONS),
+            Err(e) => {
+                eprintln!(
+                    "{} query expansion failed, searching with the question only: {}",
+                    "Note:".yellow(),
+                    e
+                );
+                vec![question.to_string()]
+            }
+        }
     }
 
     fn filter_files_by_patterns(&self, files: &[PathBuf]) -> Vec<PathBuf> {
@@ -518,7 +1397,9 @@ impl RagService {
             .collect()
     }
 
-    async fn build_index_with_files(&self, files: &[PathBuf]) -> Result<()> {
+    /// Embed the given files, skipping those whose content hash is unchanged.
+    /// Returns the number of files re-embedded.
+    async fn build_index_with_files(&self, files: &[PathBuf]) -> Result<usize> {
         eprintln!("Scanning {} files...", files.len());
         let mut inputs: Vec<EmbeddingInput> = Vec::new();
 
@@ -528,63 +1409,320 @@ impl RagService {
             let dir_hash = format!("{:x}", md5::compute(dir_overview.as_bytes()));
             let meta = self
                 .storage
-                .get_file_hash("__dir_overview__".to_string())
+                .get_file_hash(DIR_OVERVIEW_PATH.to_string())
                 .await?;
             if meta.as_deref() != Some(dir_hash.as_str()) {
                 self.storage
-                    .delete_embeddings_for_path("__dir_overview__".to_string())
+                    .delete_embeddings_for_path(DIR_OVERVIEW_PATH.to_string())
                     .await?;
                 inputs.push(EmbeddingInput {
-                    id: format!("__dir_overview__:{dir_hash}"),
-                    path: "__dir_overview__".to_string(),
+                    id: format!("{DIR_OVERVIEW_PATH}:{dir_hash}"),
+                    path: DIR_OVERVIEW_PATH.to_string(),
                     text: format!("DIRECTORY TREE:\n{}", dir_overview),
+                    symbol: None,
+                    commit: None,
                 });
                 self.storage
-                    .upsert_file_hash("__dir_overview__".to_string(), dir_hash)
+                    .upsert_file_hash(DIR_OVERVIEW_PATH.to_string(), dir_hash)
                     .await?;
             }
         }
 
+        let commit = self.git.as_ref().and_then(GitRepo::head);
+        let root = self.scanner.root();
+        let mut licenses = LicenseResolver::new(root, |path: &Path| {
+            std::fs::read_to_string(root.join(path)).ok()
+        });
+        let mut reindexed = 0;
         let scans = self.scanner.scan_paths(files).await?;
         for scan in scans {
+            let (storage, other) = match self.content_kind(&scan.path) {
+                ContentKind::Code => (&self.storage, &self.knowledge),
+                ContentKind::Knowledge => (&self.knowledge, &self.storage),
+            };
             if scan.hash.is_empty() || scan.chunks.is_empty() {
+                // Emptied or grown past the size cap: stale chunks would linger
+                if storage.get_file_hash(scan.path.clone()).await?.is_some() {
+                    storage.prune_path(scan.path).await?;
+                }
                 continue;
             }
 
             eprintln!("Processing {}...", scan.path);
-            let previous_hash = self.storage.get_file_hash(scan.path.clone()).await?;
+            let previous_hash = storage.get_file_hash(scan.path.clone()).await?;
             if previous_hash.as_deref() == Some(scan.hash.as_str()) {
                 continue;
             }
+            // Indexed with the other kind by an older version
+            if previous_hash.is_none() && other.get_file_hash(scan.path.clone()).await?.is_some() {
+                other.prune_path(scan.path.clone()).await?;
+            }
 
             // File changed; drop old embeddings for this path.
-            self.storage
+            storage
                 .delete_embeddings_for_path(scan.path.clone())
                 .await?;
 
-            for chunk in scan.chunks {
-                let id = format!("{}:{}", chunk.path, chunk.start_offset);
-                let text = format!(
-                    "FILE: {}\nOFFSET: {}\n{}",
-                    chunk.path, chunk.start_offset, chunk.text
-                );
-                inputs.push(EmbeddingInput {
-                    id,
-                    path: chunk.path,
-                    text,
-                });
-            }
+            // License headers sit at the top of the file
+            let head = scan
+                .chunks
+                .iter()
+                .find(|chunk| chunk.start_offset == 0)
+                .map_or("", |chunk| chunk.text.as_str());
+            let provenance = licenses.provenance(Path::new(&scan.path), head, &self.name);
+            inputs.extend(
+                scan.chunks
+                    .into_iter()
+                    .map(|chunk| Self::chunk_input(chunk, commit.as_ref(), &provenance)),
+            );
 
-            self.storage.upsert_file_hash(scan.path, scan.hash).await?;
+            storage.upsert_file_hash(scan.path, scan.hash).await?;
+            reindexed += 1;
         }
 
         if !inputs.is_empty() {
-            eprintln!("Generating embeddings for {} chunks...", inputs.len());
-            let embeddings = self.embedder.generate_embeddings(&inputs).await?;
+            let embeddings = self
+                .embedder
+                .generate_embeddings_with_progress(&inputs)
+                .await?;
             eprintln!("Storing embeddings...");
-            self.storage.insert_embeddings(embeddings).await?;
+            let (knowledge, code): (Vec<_>, Vec<_>) =
+                embeddings.into_iter().partition(|embedding| {
+                    self.content_kind(&embedding.path) == ContentKind::Knowledge
+                });
+            if !knowledge.is_empty() {
+                eprintln!("{} chunks go to the knowledge collection", knowledge.len());
+                self.knowledge.insert_embeddings(knowledge).await?;
+            }
+            if !code.is_empty() {
+                self.storage.insert_embeddings(code).await?;
+            }
             eprintln!("Indexing complete - {} chunks processed", inputs.len());
         }
-        Ok(())
+        Ok(reindexed)
+    }
+
+    /// Whether `path` is code or project knowledge, judged below the root
+    fn content_kind(&self, path: &str) -> ContentKind {
+        let path = Path::new(path);
+        ContentKind::of(path.strip_prefix(self.scanner.root()).unwrap_or(path))
+    }
+
+    fn storages(&self) -> [&HybridStorage; 2] {
+        [&self.storage, &self.knowledge]
+    }
+}
+
+/// File a context chunk came from, or what it is when not from a file
+fn chunk_source(text: &str) -> String {
+    match RagCitation::parse(text, PINNED_SCORE) {
+        Some(RagCitation {
+            repo: Some(repo),
+            path,
+            ..
+        }) => format!("{}:{}", repo, path),
+        Some(citation) => citation.path,
+        None => text
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(':')
+            .chars()
+            .take(40)
+            .collect::<String>()
+            .to_lowercase(),
+    }
+}
+
+/// Repository name derived from its root directory
+pub fn repo_name(root_path: &str) -> String {
+    let root = std::fs::canonicalize(root_path).unwrap_or_else(|_| PathBuf::from(root_path));
+    root.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "root".to_string())
+}
+
+/// Qdrant collection for a linked repository, e.g. `vibe_rag_my_app`
+pub fn collection_for_repo(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}_{}", DEFAULT_COLLECTION, slug)
+}
+
+/// Manifest key and chunk path for a file in a commit snapshot: `<commit>:<path>`,
+/// the same spelling `git show` accepts
+fn snapshot_key(commit: &str, path: &str) -> String {
+    format!("{}:{}", commit, path)
+}
+
+/// The question plus up to `limit` distinct variants, one per reply line,
+/// with list markers and quotes stripped
+fn parse_expansions(question: &str, reply: &str, limit: usize) -> Vec<String> {
+    let mut queries = vec![question.trim().to_string()];
+    for line in reply.lines() {
+        let line = line.trim();
+        let unnumbered = line.trim_start_matches(|c: char| c.is_ascii_digit());
+        let line = match unnumbered.strip_prefix(['.', ')']) {
+            Some(rest) if unnumbered.len() < line.len() => rest,
+            _ => line,
+        };
+        let query = line
+            .trim_start_matches(['-', '*'])
+            .trim()
+            .trim_matches(['"', '\'', '`'])
+            .trim();
+        if query.is_empty() || query.ends_with(':') {
+            continue;
+        }
+        if !queries.iter().any(|q| q.eq_ignore_ascii_case(query)) {
+            queries.push(query.to_string());
+        }
+        if queries.len() > limit {
+            break;
+        }
+    }
+    queries
+}
+
+/// Union of several result lists, keeping each chunk once at its best score
+fn union_results(results: Vec<Vec<ScoredChunk>>, top_k: usize) -> Vec<ScoredChunk> {
+    let mut best: Vec<ScoredChunk> = Vec::new();
+    for chunk in results.into_iter().flatten() {
+        match best.iter_mut().find(|kept| kept.text == chunk.text) {
+            Some(kept) => kept.score = kept.score.max(chunk.score),
+            None => best.push(chunk),
+        }
+    }
+    best.sort_by(|a, b| b.score.total_cmp(&a.score));
+    best.truncate(top_k);
+    best
+}
+
+/// Split a snapshot key into commit and path; `None` for working-tree paths
+fn split_snapshot_key(key: &str) -> Option<(&str, &str)> {
+    let (commit, path) = key.split_once(':')?;
+    let is_hash = matches!(commit.len(), 40 | 64) && commit.bytes().all(|b| b.is_ascii_hexdigit());
+    is_hash.then_some((commit, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_changed_paths_coalesce_and_drain_after_close() {
+        let changes = ChangedPaths::new();
+        for i in 0..5000 {
+            changes.push(PathBuf::from(format!("src/file{}.rs", i % 2000)));
+        }
+        changes.push(PathBuf::from("src/main.rs"));
+        changes.close();
+
+        assert!(changes.wait().await);
+        changes.settle(Duration::from_millis(10)).await;
+        let batch = changes.take();
+        assert_eq!(batch.len(), 2001);
+        assert!(batch.contains(Path::new("src/main.rs")));
+        assert!(!changes.wait().await);
+    }
+
+    #[test]
+    fn test_snapshot_keys() {
+        let commit = "a".repeat(40);
+        let key = snapshot_key(&commit, "src/lib.rs");
+        assert_eq!(
+            split_snapshot_key(&key),
+            Some((commit.as_str(), "src/lib.rs"))
+        );
+        assert_eq!(
+            split_snapshot_key(&snapshot_key(&commit, "")),
+            Some((commit.as_str(), ""))
+        );
+        assert_eq!(split_snapshot_key("./src/lib.rs"), None);
+        assert_eq!(split_snapshot_key("C:\\repo\\lib.rs"), None);
+        assert_eq!(split_snapshot_key(DIR_OVERVIEW_PATH), None);
+    }
+
+    #[test]
+    fn test_citations_carry_repository() {
+        let scored = |text: &str, score: f32| ScoredChunk {
+            text: text.to_string(),
+            score,
+        };
+        let chunks = vec![
+            scored("REPO: api\nFILE: src/lib.rs\nOFFSET: 10\nfn a() {}", 0.8),
+            scored("FILE: README.md\nOFFSET: 0\n# Docs", PINNED_SCORE),
+        ];
+        let citations = RagCitation::from_chunks(&chunks);
+        assert_eq!(citations[0].repo.as_deref(), Some("api"));
+        assert_eq!(citations[0].path, "src/lib.rs");
+        assert_eq!(citations[0].offset, Some(10));
+        assert_eq!(citations[0].score, Some(0.8));
+        assert_eq!(citations[1].repo, None);
+        assert_eq!(citations[1].score, None);
+        assert_eq!(collection_for_repo("My-App"), "vibe_rag_my_app");
+    }
+
+    #[test]
+    fn test_citations_parse_line_ranges() {
+        let commit = "b".repeat(40);
+        let chunk = ScoredChunk {
+            text: format!(
+                "FILE: {}\nOFFSET: 120\nLINES: 7-19\nSYMBOL: function parse_args\nfn parse_args() {{}}",
+                snapshot_key(&commit, "src/cli.rs")
+            ),
+            score: 0.5,
+        };
+        let citation = &RagCitation::from_chunks(&[chunk])[0];
+        assert_eq!(citation.path, "src/cli.rs");
+        assert_eq!(
+            (citation.start_line, citation.end_line),
+            (Some(7), Some(19))
+        );
+        assert_eq!(citation.symbol.as_deref(), Some("function parse_args"));
+    }
+
+    #[test]
+    fn test_parse_expansions_strips_markers_and_duplicates() {
+        let reply = "Here are the queries:\n1. authentication middleware\n- \"login handler\"\n\n* Where is auth handled\n2) session token validation\n3. verify_password\n4. extra query";
+        assert_eq!(
+            parse_expansions("where is auth handled", reply, 4),
+            vec![
+                "where is auth handled",
+                "authentication middleware",
+                "login handler",
+                "session token validation",
+                "verify_password",
+            ]
+        );
+        assert_eq!(
+            parse_expansions("q", "2fa codes", 4),
+            vec!["q", "2fa codes"]
+        );
+    }
+
+    #[test]
+    fn test_union_results_keeps_best_score() {
+        let scored = |text: &str, score: f32| ScoredChunk {
+            text: text.to_string(),
+            score,
+        };
+        let merged = union_results(
+            vec![
+                vec![scored("a", 0.4), scored("b", 0.3)],
+                vec![scored("c", 0.9), scored("a", 0.6)],
+            ],
+            2,
+        );
+        let ranked: Vec<(&str, f32)> = merged.iter().map(|c| (c.text.as_str(), c.score)).collect();
+        assert_eq!(ranked, vec![("c", 0.9), ("a", 0.6)]);
     }
 }
@@ -0,0 +1,359 @@
+//! Time-boxed research on a topic
+//!
+//! The model proposes search queries, which are run against the indexed
+//! repositories and, when egress to the search host is allowed, the web. What
+//! turns up is compiled into a Markdown brief citing each finding. The
+//! iteration and time limits of `AgentExecutionLimits` are hard: every search
+//! and model call is cut off at the deadline, and searching stops early enough
+//! to leave time for writing the brief.
+
+use crate::prompts;
+use crate::rag_service::{RagCitation, RagService};
+use colored::Colorize;
+use infrastructure::agent_control::AgentExecutionLimits;
+use infrastructure::context_window::ScoredChunk;
+use infrastructure::network_security::NetworkSecurity;
+use infrastructure::web_search::{SearchOptions, WebSearch};
+use infrastructure::InferenceEngine;
+use shared::types::Result;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Host the web search talks to, which egress must allow
+const SEARCH_HOST: &str = "html.duckduckgo.com";
+
+/// Share of the time budget spent searching; the rest is for the brief
+const SEARCH_SHARE_PERCENT: u32 = 80;
+
+/// Longest excerpt of a finding kept for the brief
+const MAX_EXCERPT_CHARS: usize = 800;
+
+/// Reply by which the model says it has researched enough
+const DONE_MARKER: &str = "DONE";
+
+/// Something a search turned up, cited in the brief by its position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// File location or URL
+    pub source: String,
+    pub title: String,
+    pub excerpt: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResearchBrief {
+    pub markdown: String,
+    pub findings: Vec<Finding>,
+    pub iterations: u32,
+    /// Searching stopped at the time budget rather than by choice
+    pub timed_out: bool,
+}
+
+pub struct ResearchService {
+    inference_engine: InferenceEngine,
+    rag: Option<Arc<RagService>>,
+    web: Option<(WebSearch, NetworkSecurity)>,
+    limits: AgentExecutionLimits,
+}
+
+impl ResearchService {
+    pub fn new(inference_engine: InferenceEngine, limits: AgentExecutionLimits) -> Self {
+        Self {
+            inference_engine,
+            rag: None,
+            web: None,
+            limits,
+        }
+    }
+
+    /// Search the indexed repositories
+    pub fn with_index(mut self, rag: Arc<RagService>) -> Self {
+        self.rag = Some(rag);
+        self
+    }
+
+    /// Search the web too, if `security` allows the search host; result
+    /// links it does not allow are left out
+    pub fn with_web(mut self, security: NetworkSecurity) -> Self {
+        if let Err(e) = security.is_host_allowed(SEARCH_HOST) {
+            eprintln!(
+                "{} web search is off, egress to {} is not allowed: {}",
+                "Note:".yellow(),
+                SEARCH_HOST,
+                e
+            );
+            return self;
+        }
+        match WebSearch::new() {
+            Ok(web) => self.web = Some((web, security)),
+            Err(e) => eprintln!("{} web search is off: {}", "Note:".yellow(), e),
+        }
+        self
+    }
+
+    pub async fn research(&self, topic: &str) -> Result<ResearchBrief> {
+        if self.rag.is_none() && self.web.is_none() {
+            return Err(anyhow::anyhow!(
+                "Nothing to search: no index is available and web search is off"
+            ));
+        }
+
+        let start = Instant::now();
+        let budget = Duration::from_secs(self.limits.max_execution_time_seconds);
+        let deadline = start + budget;
+        let search_deadline = start + budget * SEARCH_SHARE_PERCENT / 100;
+        let per_iteration = self.limits.max_tools_per_iteration.max(1) as usize;
+
+        let mut findings: Vec<Finding> = Vec::new();
+        let mut asked: Vec<String> = Vec::new();
+        let mut queries = vec![topic.to_string()];
+        let mut iterations = 0;
+        let mut timed_out = false;
+
+        'research: while iterations < self.limits.max_iterations {
+            iterations += 1;
+            for query in queries.iter().take(per_iteration) {
+                asked.push(query.clone());
+                eprintln!("{}", format!("Searching: {}", query).dimmed());
+                match within(search_deadline, self.search(query)).await {
+                    Some(found) => merge_findings(&mut findings, found),
+                    None => {
+                        timed_out = true;
+                        break 'research;
+                    }
+                }
+            }
+
+            if iterations == self.limits.max_iterations {
+                break;
+            }
+            let prompt = prompts::research_queries_prompt(topic, &asked, &findings, per_iteration);
+            queries = match within(search_deadline, self.inference_engine.generate(&prompt)).await {
+                Some(Ok(reply)) => parse_next_queries(&reply, &asked, per_iteration),
+                Some(Err(e)) => {
+                    eprintln!("{} stopped searching: {}", "Note:".yellow(), e);
+                    break;
+                }
+                None => {
+                    timed_out = true;
+                    break;
+                }
+            };
+            if queries.is_empty() {
+                break;
+            }
+        }
+
+        let body = if findings.is_empty() {
+            None
+        } else {
+            let prompt = prompts::research_brief_prompt(topic, &findings);
+            match within(deadline, self.inference_engine.generate(&prompt)).await {
+                Some(Ok(reply)) if !reply.trim().is_empty() => Some(reply),
+                Some(Ok(_)) => None,
+                Some(Err(e)) => {
+                    eprintln!("{} listing findings only: {}", "Note:".yellow(), e);
+                    None
+                }
+                None => {
+                    timed_out = true;
+                    None
+                }
+            }
+        };
+
+        Ok(ResearchBrief {
+            markdown: render_brief(topic, body.as_deref(), &findings),
+            findings,
+            iterations,
+            timed_out,
+        })
+    }
+
+    /// Findings for one query from every source; a failing source is noted
+    /// and skipped
+    async fn search(&self, query: &str) -> Vec<Finding> {
+        let mut found = Vec::new();
+        if let Some(rag) = &self.rag {
+            match rag.search(query).await {
+                Ok(chunks) => found.extend(chunks.iter().filter_map(chunk_finding)),
+                Err(e) => eprintln!("{} index search failed: {}", "Note:".yellow(), e),
+            }
+        }
+        if let Some((web, security)) = &self.web {
+            match web
+                .search_programming(query, SearchOptions::default())
+                .await
+            {
+                Ok(results) => found.extend(
+                    WebSearch::validate_results(&results)
+                        .into_iter()
+                        .filter(|result| security.is_url_allowed(&result.url).is_ok())
+                        .map(|result| Finding {
+                            source: result.url,
+                            title: result.title,
+                            excerpt: result.snippet,
+                        }),
+                ),
+                Err(e) => eprintln!("{} web search failed: {}", "Note:".yellow(), e),
+            }
+        }
+        found
+    }
+}
+
+/// Run `future` until `deadline`; None when it was cut off
+async fn within<F: Future>(deadline: Instant, future: F) -> Option<F::Output> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    tokio::time::timeout(remaining, future).await.ok()
+}
+
+fn merge_findings(findings: &mut Vec<Finding>, found: Vec<Finding>) {
+    for finding in found {
+        if !findings.iter().any(|f| f.source == finding.source) {
+            findings.push(finding);
+        }
+    }
+}
+
+/// A retrieved chunk as a finding located by its file and lines
+fn chunk_finding(chunk: &ScoredChunk) -> Option<Finding> {
+    let citation = RagCitation::from_chunks(std::slice::from_ref(chunk))
+        .into_iter()
+        .next()?;
+    let mut source = match &citation.repo {
+        Some(repo) => format!("{}/{}", repo, citation.path),
+        None => citation.path.clone(),
+    };
+    if let (Some(start), Some(end)) = (citation.start_line, citation.end_line) {
+        source.push_str(&format!(":{}-{}", start, end));
+    }
+    let body: String = chunk
+        .text
+        .lines()
+        .skip_while(|line| is_chunk_header(line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Some(Finding {
+        source,
+        title: citation.symbol.unwrap_or(citation.path),
+        excerpt: body.chars().take(MAX_EXCERPT_CHARS).collect(),
+    })
+}
+
+fn is_chunk_header(line: &str) -> bool {
+    const HEADERS: [&str; 8] = [
+        "REPO:",
+        "FILE:",
+        "OFFSET:",
+        "LINES:",
+        "SYMBOL:",
+        "LICENSE:",
+        "ORIGIN:",
+        "VENDORED:",
+    ];
+    HEADERS.iter().any(|header| line.starts_with(header))
+}
+
+/// Queries from a reply listing one per line, skipping ones already run; empty
+/// when the model is done
+pub fn parse_next_queries(reply: &str, asked: &[String], max: usize) -> Vec<String> {
+    let mut queries: Vec<String> = Vec::new();
+    for line in reply.lines() {
+        let query = line
+            .trim()
+            .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '-' | '*' | '.' | ')'))
+            .trim()
+            .trim_matches('"')
+            .trim();
+        if query.eq_ignore_ascii_case(DONE_MARKER) {
+            break;
+        }
+        let seen = |q: &String| q.eq_ignore_ascii_case(query);
+        if query.is_empty() || asked.iter().any(seen) || queries.iter().any(seen) {
+            continue;
+        }
+        queries.push(query.to_string());
+        if queries.len() == max {
+            break;
+        }
+    }
+    queries
+}
+
+/// The brief with a numbered source list matching its `[n]` citations; without
+/// a written `body` the findings themselves are listed
+pub fn render_brief(topic: &str, body: Option<&str>, findings: &[Finding]) -> String {
+    let mut markdown = format!("# Research: {}\n\n", topic);
+    match body {
+        Some(body) => markdown.push_str(body.trim()),
+        None if findings.is_empty() => markdown.push_str("Nothing relevant was found."),
+        None => {
+            markdown.push_str("## Findings\n");
+            for (i, finding) in findings.iter().enumerate() {
+                let excerpt = finding.excerpt.split_whitespace().collect::<Vec<_>>();
+                markdown.push_str(&format!(
+                    "\n- **{}** [{}]: {}",
+                    finding.title,
+                    i + 1,
+                    excerpt.join(" ")
+                ));
+            }
+        }
+    }
+    markdown.push('\n');
+
+    if !findings.is_empty() {
+        markdown.push_str("\n## Sources\n");
+        for (i, finding) in findings.iter().enumerate() {
+            let source = if finding.source.starts_with("https://") {
+                format!("[{}]({})", finding.title, finding.source)
+            } else {
+                format!("`{}`", finding.source)
+            };
+            markdown.push_str(&format!("\n{}. {}", i + 1, source));
+        }
+        markdown.push('\n');
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_queries_and_brief() {
+        let asked = vec!["session storage".to_string()];
+        let reply =
+            "1. sled database layout\n- \"Session Storage\"\n* session locking\nDONE\nignored";
+        assert_eq!(
+            parse_next_queries(reply, &asked, 5),
+            vec!["sled database layout", "session locking"]
+        );
+        assert_eq!(parse_next_queries(reply, &asked, 1).len(), 1);
+        assert!(parse_next_queries("DONE", &asked, 5).is_empty());
+
+        let findings = vec![
+            Finding {
+                source: "src/session_store.rs:1-3".to_string(),
+                title: "struct SessionStore".to_string(),
+                excerpt: "pub struct SessionStore {\n    db: Db,\n}".to_string(),
+            },
+            Finding {
+                source: "https://docs.rs/sled".to_string(),
+                title: "sled docs".to_string(),
+                excerpt: "An embedded database".to_string(),
+            },
+        ];
+        assert_eq!(
+            render_brief("sessions", Some("Sessions live in sled [1][2].\n"), &findings),
+            "# Research: sessions\n\nSessions live in sled [1][2].\n\n## Sources\n\n1. `src/session_store.rs:1-3`\n2. [sled docs](https://docs.rs/sled)\n"
+        );
+        assert_eq!(
+            render_brief("sessions", None, &findings[1..]),
+            "# Research: sessions\n\n## Findings\n\n- **sled docs** [1]: An embedded database\n\n## Sources\n\n1. [sled docs](https://docs.rs/sled)\n"
+        );
+    }
+}
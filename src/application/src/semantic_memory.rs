@@ -6,12 +6,15 @@
 
 use domain::models::{AgentContext, ConversationMessage};
 use infrastructure::{
+    context_window::MemoryScope,
     embedder::{Embedder, EmbeddingInput},
     qdrant_storage::QdrantStorage,
+    write_conflicts::{ConflictChoice, ConflictPolicy, ConflictResolver, WriteConflict},
 };
 use serde::{Deserialize, Serialize};
 use shared::types::Result;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 /// Represents a stored conversation memory
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +26,34 @@ pub struct ConversationMemory {
     pub timestamp: i64,
     pub tool_calls: Option<Vec<domain::models::ToolCall>>,
     pub tool_call_id: Option<String>,
+    /// Bumped each time the message at this index is written
+    #[serde(default)]
+    pub version: u64,
+    /// Root of the project the conversation took place in
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+impl ConversationMemory {
+    /// Short description for the prompt context report
+    pub fn label(&self) -> String {
+        format!(
+            "{} #{} {}",
+            self.conversation_id,
+            self.message_index,
+            preview(&self.role, &self.content)
+        )
+    }
+
+    /// Whether the memory may go into a prompt under `scope` while working
+    /// in `project`
+    pub fn allowed(&self, scope: MemoryScope, project: Option<&str>) -> bool {
+        match scope {
+            MemoryScope::All => true,
+            MemoryScope::ProjectOnly => project.is_some() && self.project.as_deref() == project,
+            MemoryScope::Off => false,
+        }
+    }
 }
 
 /// Service for managing semantic conversation memory
@@ -30,6 +61,10 @@ pub struct SemanticMemoryService {
     qdrant: Arc<QdrantStorage>,
     embedder: Arc<Embedder>,
     collection_name: String,
+    conflict_policy: ConflictPolicy,
+    resolver: Option<ConflictResolver>,
+    /// Version this process last wrote at each conversation and index
+    own_writes: Mutex<HashMap<(String, usize), u64>>,
 }
 
 impl SemanticMemoryService {
@@ -48,15 +83,38 @@ impl SemanticMemoryService {
             qdrant,
             embedder,
             collection_name: "conversation_memory".to_string(),
+            conflict_policy: ConflictPolicy::default(),
+            resolver: None,
+            own_writes: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Settle messages another process wrote at the same place by `policy`,
+    /// asking `resolver` under the interactive policy
+    pub fn with_conflict_handling(
+        mut self,
+        policy: ConflictPolicy,
+        resolver: Option<ConflictResolver>,
+    ) -> Self {
+        self.conflict_policy = policy;
+        self.resolver = resolver;
+        self
+    }
+
+    fn own_write(&self, conversation_id: &str, message_index: usize) -> Option<u64> {
+        let own = self.own_writes.lock().unwrap_or_else(|e| e.into_inner());
+        own.get(&(conversation_id.to_string(), message_index))
+            .copied()
+    }
+
     /// Helper method to generate embedding for text
     async fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
         let input = EmbeddingInput {
             id: "temp".to_string(),
             path: "temp".to_string(),
             text: text.to_string(),
+            symbol: None,
+            commit: None,
         };
 
         let embeddings = self.embedder.as_ref().generate_embeddings(&[input]).await?;
@@ -67,13 +125,53 @@ impl SemanticMemoryService {
             .vector)
     }
 
-    /// Store a conversation message in semantic memory
+    /// Store a conversation message in semantic memory. A different message
+    /// another process stored at the same index is a conflict; merging keeps
+    /// it and stores this message after the conversation's last one.
     pub async fn store_message(
         &self,
         conversation_id: &str,
         message_index: usize,
         message: &ConversationMessage,
     ) -> Result<()> {
+        let mut message_index = message_index;
+        let mut version = 1;
+        if let Some(theirs) = self.stored_message(conversation_id, message_index).await? {
+            if theirs.role == message.role && theirs.content == message.content {
+                return Ok(());
+            }
+            version = theirs.version + 1;
+            let ours = self.own_write(conversation_id, message_index);
+            if ours != Some(theirs.version) {
+                let conflict = WriteConflict {
+                    what: format!(
+                        "message {} of conversation '{}'",
+                        message_index, conversation_id
+                    ),
+                    expected: ours.unwrap_or(0),
+                    found: theirs.version,
+                    ours: preview(&message.role, &message.content),
+                    theirs: preview(&theirs.role, &theirs.content),
+                };
+                match self
+                    .conflict_policy
+                    .resolve(&conflict, self.resolver.as_ref())
+                {
+                    ConflictChoice::KeepOurs => {}
+                    ConflictChoice::KeepTheirs => return Ok(()),
+                    ConflictChoice::Merge => {
+                        let history = self.get_conversation_history(conversation_id).await?;
+                        message_index = history
+                            .iter()
+                            .map(|m| m.message_index + 1)
+                            .max()
+                            .unwrap_or(0);
+                        version = 1;
+                    }
+                }
+            }
+        }
+
         // Generate embedding for the message content
         let embedding = self.embed_text(&message.content).await?;
 
@@ -89,6 +187,8 @@ impl SemanticMemoryService {
                 .as_secs() as i64,
             tool_calls: message.tool_calls.clone(),
             tool_call_id: message.tool_call_id.clone(),
+            version,
+            project: infrastructure::config::find_project_root(),
         };
 
         // Store in Qdrant with metadata
@@ -100,13 +200,37 @@ impl SemanticMemoryService {
                 id,
                 vector: embedding,
                 text: memory_json,
-                path: format!("conversation/{}/{}", conversation_id, message_index),
+                path: message_path(conversation_id, message_index),
+                symbol: None,
+                commit: None,
             }])
             .await?;
+        self.own_writes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert((conversation_id.to_string(), message_index), version);
 
         Ok(())
     }
 
+    /// The message stored at `message_index` of a conversation
+    async fn stored_message(
+        &self,
+        conversation_id: &str,
+        message_index: usize,
+    ) -> Result<Option<ConversationMemory>> {
+        let path = message_path(conversation_id, message_index);
+        // The path filter matches text, so `/1` also finds `/10`
+        let stored = self
+            .qdrant
+            .get_embeddings_by_path_prefix(&path, 100)
+            .await?;
+        Ok(stored
+            .into_iter()
+            .filter(|embedding| embedding.path == path)
+            .find_map(|embedding| serde_json::from_str(&embedding.text).ok()))
+    }
+
     /// Store an entire conversation context
     pub async fn store_conversation(
         &self,
@@ -260,3 +384,53 @@ impl SemanticMemoryService {
         ))
     }
 }
+
+fn message_path(conversation_id: &str, message_index: usize) -> String {
+    format!("conversation/{}/{}", conversation_id, message_index)
+}
+
+/// Start of a message for a conflict prompt
+fn preview(role: &str, content: &str) -> String {
+    let line = content.lines().next().unwrap_or_default();
+    let mut preview: String = line.chars().take(80).collect();
+    if preview.len() < content.len() {
+        preview.push('…');
+    }
+    format!("{}: {}", role, preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(project: Option<&str>) -> ConversationMemory {
+        ConversationMemory {
+            conversation_id: "conv".to_string(),
+            message_index: 0,
+            role: "user".to_string(),
+            content: "How do we deploy?".to_string(),
+            timestamp: 0,
+            tool_calls: None,
+            tool_call_id: None,
+            version: 0,
+            project: project.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_memories_follow_their_scope() {
+        let here = memory(Some("/work/here"));
+        let elsewhere = memory(Some("/work/elsewhere"));
+        let unknown = memory(None);
+
+        for memory in [&here, &elsewhere, &unknown] {
+            assert!(memory.allowed(MemoryScope::All, Some("/work/here")));
+            assert!(!memory.allowed(MemoryScope::Off, Some("/work/here")));
+        }
+        assert!(here.allowed(MemoryScope::ProjectOnly, Some("/work/here")));
+        assert!(!elsewhere.allowed(MemoryScope::ProjectOnly, Some("/work/here")));
+        assert!(!unknown.allowed(MemoryScope::ProjectOnly, Some("/work/here")));
+        // Outside any project nothing counts as this project's memory
+        assert!(!unknown.allowed(MemoryScope::ProjectOnly, None));
+    }
+}
@@ -0,0 +1,250 @@
+//! Bounded sub-agents for the parts of a decomposed goal
+//!
+//! One agent loop working a large goal loses track of it. With sub-agents the
+//! task decomposer splits the goal and each subtask runs as an agent of its
+//! own: a fresh conversation holding only the goal, the subtask and the
+//! reports of the subtasks it depends on, just the tools the subtask needs,
+//! and its own iteration, tool and context limits. Each hands a
+//! [`SubAgentReport`] back to the parent, which answers from the reports.
+
+use crate::parallel_agent::SubTask;
+use domain::models::ToolDefinition;
+use infrastructure::config::AgentExecutionConfig;
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+
+/// Tools that only look; subtasks that analyze get nothing else. `todo_list`
+/// is left out because adding or completing items rewrites `.bro/todo.md`.
+const READ_ONLY_TOOLS: &[&str] = &[
+    "file_read",
+    "directory_list",
+    "grep_search",
+    "find_files",
+    "process_list",
+    "git_status",
+    "git_diff",
+    "git_log",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubAgentLimits {
+    pub max_iterations: usize,
+    pub max_tools_per_iteration: usize,
+    /// Context window of the sub-agent, in tokens
+    pub context_tokens: usize,
+}
+
+impl SubAgentLimits {
+    pub fn from_config(config: &AgentExecutionConfig) -> Self {
+        Self {
+            max_iterations: config.sub_agent_iterations.max(1) as usize,
+            max_tools_per_iteration: config.max_tools_per_iteration.max(1) as usize,
+            context_tokens: config.sub_agent_context_tokens,
+        }
+    }
+}
+
+/// A subtask with the tools and limits of the agent that works it
+#[derive(Debug, Clone)]
+pub struct SubAgentSpec {
+    pub task: SubTask,
+    pub tools: Vec<ToolDefinition>,
+    pub limits: SubAgentLimits,
+}
+
+/// What a sub-agent hands back to its parent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubAgentReport {
+    pub task_id: String,
+    pub description: String,
+    pub success: bool,
+    /// The sub-agent's answer for its subtask
+    pub summary: String,
+    /// Tools called, in order
+    pub tools_used: Vec<String>,
+    pub iterations: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SubAgentReport {
+    /// One line for the parent's prompt and the reasoning shown to the user
+    pub fn line(&self) -> String {
+        let status = if self.success { "done" } else { "failed" };
+        let detail = match &self.error {
+            Some(error) => error.as_str(),
+            None => self.summary.lines().next().unwrap_or_default(),
+        };
+        format!(
+            "[{}] {} ({}): {}",
+            status, self.task_id, self.description, detail
+        )
+    }
+}
+
+/// Specs for `tasks` in the order they can run: every subtask after the
+/// ones it depends on, higher priority first among those ready
+pub fn plan(
+    tasks: Vec<SubTask>,
+    available: &[ToolDefinition],
+    limits: SubAgentLimits,
+) -> Result<Vec<SubAgentSpec>> {
+    let mut remaining = tasks;
+    let mut ordered: Vec<SubAgentSpec> = Vec::new();
+    while !remaining.is_empty() {
+        let next = remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| {
+                task.dependencies
+                    .iter()
+                    .all(|dep| ordered.iter().any(|spec| &spec.task.id == dep))
+            })
+            .max_by_key(|(index, task)| (task.priority, std::cmp::Reverse(*index)))
+            .map(|(index, _)| index)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Subtasks {} depend on each other or on missing subtasks",
+                    remaining
+                        .iter()
+                        .map(|task| task.id.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+        let task = remaining.remove(next);
+        ordered.push(SubAgentSpec {
+            tools: scoped_tools(&task, available),
+            task,
+            limits,
+        });
+    }
+    Ok(ordered)
+}
+
+/// Tools for `task`: read-only ones when it only analyzes, all otherwise
+pub fn scoped_tools(task: &SubTask, available: &[ToolDefinition]) -> Vec<ToolDefinition> {
+    let id = task.id.to_lowercase();
+    let description = task.description.to_lowercase();
+    let analyzes = ["analysis", "requirements", "review"]
+        .iter()
+        .any(|kind| id.contains(kind))
+        || description.starts_with("analyze");
+    available
+        .iter()
+        .filter(|tool| !analyzes || READ_ONLY_TOOLS.contains(&tool.name.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Goal of the sub-agent working `spec`, as part of the parent's `goal`
+pub fn briefing(goal: &str, spec: &SubAgentSpec) -> String {
+    format!(
+        "Overall goal: {}\nYour subtask ({}): {}\nWork on this subtask only and answer with its result.",
+        goal, spec.task.id, spec.task.description
+    )
+}
+
+/// Reports of the subtasks `spec` depends on, one message each
+pub fn dependency_reports(spec: &SubAgentSpec, reports: &[SubAgentReport]) -> Vec<String> {
+    reports
+        .iter()
+        .filter(|report| spec.task.dependencies.contains(&report.task_id))
+        .map(|report| {
+            format!(
+                "Result of subtask {} ({}):\n{}",
+                report.task_id,
+                if report.success { "done" } else { "failed" },
+                report.error.as_deref().unwrap_or(&report.summary)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::models::ToolParameters;
+
+    fn task(id: &str, description: &str, priority: u8, dependencies: &[&str]) -> SubTask {
+        SubTask {
+            id: id.to_string(),
+            description: description.to_string(),
+            priority,
+            dependencies: dependencies.iter().map(|dep| dep.to_string()).collect(),
+            estimated_complexity: 0.5,
+        }
+    }
+
+    fn tool(name: &str) -> ToolDefinition {
+        ToolDefinition {
+            name: name.to_string(),
+            description: String::new(),
+            parameters: ToolParameters {
+                param_type: "object".to_string(),
+                properties: Default::default(),
+                required: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_plans_scoped_sub_agents_in_dependency_order() {
+        let limits = SubAgentLimits {
+            max_iterations: 2,
+            max_tools_per_iteration: 1,
+            context_tokens: 2048,
+        };
+        let tools = [
+            tool("file_read"),
+            tool("file_write"),
+            tool("grep_search"),
+            tool("todo_list"),
+        ];
+        let specs = plan(
+            vec![
+                task("testing", "Write tests", 8, &["core_logic"]),
+                task("ui_layer", "Build the UI", 7, &["requirements"]),
+                task("core_logic", "Implement core logic", 9, &["requirements"]),
+                task("requirements", "Analyze requirements", 10, &[]),
+            ],
+            &tools,
+            limits,
+        )
+        .unwrap();
+
+        let order: Vec<&str> = specs.iter().map(|spec| spec.task.id.as_str()).collect();
+        assert_eq!(order, ["requirements", "core_logic", "testing", "ui_layer"]);
+        let names = |spec: &SubAgentSpec| -> Vec<String> {
+            spec.tools.iter().map(|tool| tool.name.clone()).collect()
+        };
+        assert_eq!(names(&specs[0]), ["file_read", "grep_search"]);
+        assert_eq!(names(&specs[1]).len(), 4);
+
+        let requirements = SubAgentReport {
+            task_id: "requirements".to_string(),
+            description: "Analyze requirements".to_string(),
+            success: true,
+            summary: "Needs a login form\nand a session store".to_string(),
+            tools_used: vec!["file_read".to_string()],
+            iterations: 1,
+            error: None,
+        };
+        assert_eq!(
+            dependency_reports(&specs[1], std::slice::from_ref(&requirements)),
+            ["Result of subtask requirements (done):\nNeeds a login form\nand a session store"]
+        );
+        assert!(dependency_reports(&specs[2], std::slice::from_ref(&requirements)).is_empty());
+        assert_eq!(
+            requirements.line(),
+            "[done] requirements (Analyze requirements): Needs a login form"
+        );
+
+        assert!(plan(
+            vec![task("a", "A", 1, &["b"]), task("b", "B", 1, &["a"])],
+            &tools,
+            limits
+        )
+        .is_err());
+    }
+}
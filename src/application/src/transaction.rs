@@ -1,4 +1,8 @@
+use crate::build_service::{BuildPlan, FileOperation};
+use chrono::{DateTime, Utc};
 use colored::Colorize;
+use infrastructure::dependency_ops;
+use infrastructure::undo_journal::UndoJournal;
 use serde::{Deserialize, Serialize};
 use shared::types::Result;
 use std::collections::HashMap;
@@ -28,6 +32,8 @@ pub struct Transaction {
     state: TransactionState,
     backups: HashMap<PathBuf, FileBackup>,
     operations_log: Vec<String>,
+    /// Where the pre-images go on commit, with a description of the change
+    journal: Option<(UndoJournal, String)>,
 }
 
 impl Transaction {
@@ -38,9 +44,17 @@ impl Transaction {
             state: TransactionState::Pending,
             backups: HashMap::new(),
             operations_log: Vec::new(),
+            journal: None,
         }
     }
 
+    /// Record what the files looked like before in `journal` on commit, so
+    /// the change can be undone later
+    pub fn with_journal(mut self, journal: UndoJournal, description: impl Into<String>) -> Self {
+        self.journal = Some((journal, description.into()));
+        self
+    }
+
     /// Get the transaction ID
     pub fn id(&self) -> &str {
         &self.id
@@ -150,6 +164,21 @@ impl Transaction {
         }
 
         self.state = TransactionState::Committed;
+        if let Some((journal, description)) = self.journal.take() {
+            let pre_images: Vec<(PathBuf, Option<Vec<u8>>)> = self
+                .backups
+                .values()
+                .map(|backup| (backup.path.clone(), backup.content.clone()))
+                .collect();
+            if !pre_images.is_empty() {
+                if let Err(e) = journal.record(&description, &pre_images) {
+                    eprintln!(
+                        "{}",
+                        format!("Warning: change not recorded for --undo: {}", e).yellow()
+                    );
+                }
+            }
+        }
         println!(
             "{}",
             format!(
@@ -303,6 +332,141 @@ impl Drop for TransactionGuard {
     }
 }
 
+/// Where named build checkpoints live, relative to the project root
+pub const CHECKPOINT_DIR: &str = ".bro/checkpoints";
+
+/// A build plan and the files it touches, saved under a name so a long build
+/// can be put back to that point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub plan: BuildPlan,
+    /// First step of the plan not yet reviewed when the checkpoint was taken
+    pub next_step: usize,
+    files: Vec<FileBackup>,
+}
+
+impl Checkpoint {
+    /// Files whose content the checkpoint holds
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|file| file.path.as_path())
+    }
+}
+
+/// Named checkpoints of one project, for `/checkpoint` and `/restore`
+#[derive(Debug, Clone)]
+pub struct Checkpoints {
+    root: PathBuf,
+}
+
+impl Checkpoints {
+    pub fn for_project(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.root.join(CHECKPOINT_DIR)
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(anyhow::anyhow!(
+                "Invalid checkpoint name '{}'; use letters, digits, '-', '_' and '.'",
+                name
+            ));
+        }
+        Ok(self.dir().join(format!("{}.json", name)))
+    }
+
+    /// Snapshot every file `plan` writes or deletes, as it is now, together
+    /// with the plan; an existing checkpoint of the same name is replaced
+    pub fn save(&self, name: &str, plan: &BuildPlan, next_step: usize) -> Result<Checkpoint> {
+        let path = self.path(name)?;
+        let mut snapshot = Transaction::new();
+        for operation in &plan.operations {
+            match operation {
+                FileOperation::Create { path, .. }
+                | FileOperation::Update { path, .. }
+                | FileOperation::Delete { path }
+                | FileOperation::Patch { path, .. } => snapshot.backup_file(path)?,
+                FileOperation::AddDependency { manifest, .. }
+                | FileOperation::RemoveDependency { manifest, .. } => {
+                    for file in dependency_ops::touched_files(manifest) {
+                        snapshot.backup_file(file)?;
+                    }
+                }
+                FileOperation::Read { .. } => {}
+            }
+        }
+        let mut files: Vec<FileBackup> = snapshot.backups.drain().map(|(_, file)| file).collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let checkpoint = Checkpoint {
+            name: name.to_string(),
+            created_at: Utc::now(),
+            plan: plan.clone(),
+            next_step: next_step.min(plan.operations.len()),
+            files,
+        };
+        std::fs::create_dir_all(self.dir())?;
+        std::fs::write(path, serde_json::to_vec(&checkpoint)?)?;
+        Ok(checkpoint)
+    }
+
+    pub fn load(&self, name: &str) -> Result<Checkpoint> {
+        let path = self.path(name)?;
+        let content =
+            std::fs::read(&path).map_err(|_| anyhow::anyhow!("No checkpoint named '{}'", name))?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// Every checkpoint, oldest first
+    pub fn list(&self) -> Result<Vec<Checkpoint>> {
+        let Ok(entries) = std::fs::read_dir(self.dir()) else {
+            return Ok(Vec::new());
+        };
+        let mut checkpoints = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                checkpoints.push(serde_json::from_slice::<Checkpoint>(&std::fs::read(
+                    &path,
+                )?)?);
+            }
+        }
+        checkpoints.sort_by_key(|checkpoint| checkpoint.created_at);
+        Ok(checkpoints)
+    }
+
+    /// Put the files of checkpoint `name` back in one transaction, recorded
+    /// for `--undo`; returns the checkpoint so its plan can be resumed
+    pub fn restore(&self, name: &str) -> Result<Checkpoint> {
+        let checkpoint = self.load(name)?;
+        let mut transaction = Transaction::new().with_journal(
+            UndoJournal::for_project(&self.root),
+            format!("Restore checkpoint {}", name),
+        );
+        transaction.begin()?;
+        for file in &checkpoint.files {
+            match &file.content {
+                Some(content) => transaction.write_file(&file.path, content)?,
+                None if file.path.exists() => transaction.delete_file(&file.path)?,
+                None => {}
+            }
+        }
+        transaction.commit()?;
+        Ok(checkpoint)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -398,4 +562,53 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_checkpoint_restores_files_and_plan() -> Result<()> {
+        let root = std::env::temp_dir().join(format!("bro-checkpoint-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root)?;
+        let existing = root.join("lib.rs");
+        let created = root.join("new.rs");
+        fs::write(&existing, "before")?;
+        let plan = BuildPlan {
+            goal: "refactor".to_string(),
+            operations: vec![
+                FileOperation::Update {
+                    path: existing.clone(),
+                    old_content: "before".to_string(),
+                    new_content: "after".to_string(),
+                },
+                FileOperation::Create {
+                    path: created.clone(),
+                    content: "new".to_string(),
+                },
+            ],
+            description: String::new(),
+            estimated_risk: crate::build_service::RiskLevel::Medium,
+            groups: Vec::new(),
+        };
+
+        let checkpoints = Checkpoints::for_project(&root);
+        checkpoints.save("before-refactor", &plan, 1)?;
+        assert!(checkpoints.save("../escape", &plan, 0).is_err());
+        fs::write(&existing, "after")?;
+        fs::write(&created, "new")?;
+
+        let restored = checkpoints.restore("before-refactor")?;
+        assert_eq!(restored.next_step, 1);
+        assert_eq!(restored.plan.operations.len(), 2);
+        assert_eq!(restored.paths().count(), 2);
+        assert_eq!(fs::read_to_string(&existing)?, "before");
+        assert!(!created.exists());
+        assert_eq!(checkpoints.list()?.len(), 1);
+        assert!(checkpoints.restore("missing").is_err());
+
+        // The restore itself can be undone
+        UndoJournal::for_project(&root).undo(1)?;
+        assert_eq!(fs::read_to_string(&existing)?, "after");
+
+        fs::remove_dir_all(&root)?;
+        Ok(())
+    }
 }
@@ -13,6 +13,19 @@ pub struct Embedding {
     pub vector: Vec<f32>,
     pub text: String,
     pub path: String,
+    /// Definition the chunk was cut around, for syntax-aware chunks
+    #[serde(default)]
+    pub symbol: Option<CodeSymbol>,
+    /// Git commit the chunk was indexed from, in git-aware mode
+    #[serde(default)]
+    pub commit: Option<String>,
+}
+
+/// A named definition in source code (function, struct, class, ...)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeSymbol {
+    pub name: String,
+    pub kind: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +42,9 @@ pub struct AgentResponse {
     pub tool_results: Vec<ToolResult>,
     pub final_response: String,
     pub confidence: f32,
+    /// Recovery attempts for failed tool calls, in order
+    #[serde(default)]
+    pub retries: Vec<ToolRetry>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,7 +55,7 @@ pub struct ToolCall {
     pub reasoning: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ToolResult {
     pub tool_call_id: String,
     pub success: bool,
@@ -47,6 +63,51 @@ pub struct ToolResult {
     pub error: Option<String>,
 }
 
+/// How a failed tool call was tried again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryStrategy {
+    /// The same call after a pause
+    Backoff,
+    /// Another tool doing the same job
+    Substitute,
+    /// The call with arguments the model corrected
+    RepairArguments,
+}
+
+/// One retry of a failed tool call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolRetry {
+    pub tool_call_id: String,
+    /// Tool run by the retry, which differs from the call's for substitutes
+    pub tool: String,
+    pub strategy: RetryStrategy,
+    /// The failure that led to the retry
+    pub error: String,
+    pub succeeded: bool,
+}
+
+impl ToolRetry {
+    pub fn line(&self) -> String {
+        let strategy = match self.strategy {
+            RetryStrategy::Backoff => "retried",
+            RetryStrategy::Substitute => "substituted",
+            RetryStrategy::RepairArguments => "repaired arguments",
+        };
+        format!(
+            "{} {} after \"{}\": {}",
+            self.tool,
+            strategy,
+            self.error,
+            if self.succeeded {
+                "succeeded"
+            } else {
+                "failed"
+            }
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversationMessage {
     pub role: String, // "user", "assistant", "system", "tool"
@@ -8,6 +8,11 @@ pub trait SpeechRecognitionService: Send + Sync {
     async fn recognize(&self, audio: AudioSample) -> Result<RecognitionResult>;
     async fn initialize(&self) -> Result<()>;
     async fn shutdown(&self) -> Result<()>;
+
+    /// Verify the backend can actually recognize speech (model loaded, devices present)
+    async fn health_check(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -16,6 +21,11 @@ pub trait TextToSpeechService: Send + Sync {
     async fn get_available_voices(&self) -> Result<Vec<String>>;
     async fn initialize(&self) -> Result<()>;
     async fn shutdown(&self) -> Result<()>;
+
+    /// Verify the backend can actually synthesize speech (engine and voices installed)
+    async fn health_check(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[async_trait]
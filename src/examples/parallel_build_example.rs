@@ -61,6 +61,7 @@ async fn example_build_service() -> anyhow::Result<()> {
             },
         ],
         estimated_risk: application::build_service::RiskLevel::Low,
+        groups: Vec::new(),
     };
 
     // Preview the plan
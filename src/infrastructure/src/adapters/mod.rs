@@ -2,5 +2,6 @@ pub mod audio_player;
 pub mod keyboard_simulator;
 pub mod microphone;
 pub mod screen;
+pub mod speech_providers;
 pub mod tts_adapter;
 pub mod vosk_adapter;
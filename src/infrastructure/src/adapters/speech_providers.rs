@@ -0,0 +1,241 @@
+//! Registry of speech recognition and synthesis backends
+//!
+//! Providers are looked up by the names in [`AudioConfig`], so alternative
+//! engines can be registered next to the built-in Vosk and Piper adapters and
+//! selected from config. Every provider is health-checked before use; the same
+//! report backs `bro --doctor` and the web `/health` endpoint.
+
+use crate::adapters::{tts_adapter::TtsAdapter, vosk_adapter::VoskAdapter};
+use crate::config::AudioConfig;
+use domain::services::{SpeechRecognitionService, TextToSpeechService};
+use serde::Serialize;
+use shared::types::Result;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+pub type SttFactory = fn(&AudioConfig) -> Result<Arc<dyn SpeechRecognitionService>>;
+pub type TtsFactory = fn(&AudioConfig) -> Result<Arc<dyn TextToSpeechService>>;
+
+/// `tts_provider` value that disables spoken feedback
+pub const NO_TTS: &str = "none";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    SpeechToText,
+    TextToSpeech,
+}
+
+impl ProviderKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProviderKind::SpeechToText => "Speech recognition",
+            ProviderKind::TextToSpeech => "Text-to-speech",
+        }
+    }
+}
+
+/// Outcome of constructing and health-checking one configured provider
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderHealth {
+    pub kind: ProviderKind,
+    pub provider: String,
+    pub healthy: bool,
+    pub message: String,
+}
+
+pub struct SpeechProviderRegistry {
+    stt: BTreeMap<String, SttFactory>,
+    tts: BTreeMap<String, TtsFactory>,
+}
+
+impl Default for SpeechProviderRegistry {
+    fn default() -> Self {
+        Self::with_builtin()
+    }
+}
+
+impl SpeechProviderRegistry {
+    /// Registry without any providers
+    pub fn empty() -> Self {
+        Self {
+            stt: BTreeMap::new(),
+            tts: BTreeMap::new(),
+        }
+    }
+
+    /// Registry with the bundled Vosk recognizer and Piper synthesizer
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::empty();
+        registry.register_stt("vosk", |config| {
+            let model = VoskAdapter::locate_model(config.stt_model_path.as_deref())?;
+            let adapter = VoskAdapter::new(&model.to_string_lossy(), config.sample_rate as f32)?;
+            Ok(Arc::new(adapter))
+        });
+        registry.register_tts("piper", |_| Ok(Arc::new(TtsAdapter::new()?)));
+        registry
+    }
+
+    pub fn register_stt(&mut self, name: &str, factory: SttFactory) {
+        self.stt.insert(name.to_lowercase(), factory);
+    }
+
+    pub fn register_tts(&mut self, name: &str, factory: TtsFactory) {
+        self.tts.insert(name.to_lowercase(), factory);
+    }
+
+    pub fn stt_providers(&self) -> Vec<&str> {
+        self.stt.keys().map(String::as_str).collect()
+    }
+
+    pub fn tts_providers(&self) -> Vec<&str> {
+        self.tts.keys().map(String::as_str).collect()
+    }
+
+    /// Construct the configured speech recognizer
+    pub fn create_stt(&self, config: &AudioConfig) -> Result<Arc<dyn SpeechRecognitionService>> {
+        let name = config.stt_provider.to_lowercase();
+        let factory = self.stt.get(&name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown speech recognition provider '{}' (available: {})",
+                config.stt_provider,
+                self.stt_providers().join(", ")
+            )
+        })?;
+        factory(config)
+    }
+
+    /// Construct the configured synthesizer, or `None` when TTS is disabled
+    pub fn create_tts(&self, config: &AudioConfig) -> Result<Option<Arc<dyn TextToSpeechService>>> {
+        let name = config.tts_provider.to_lowercase();
+        if name == NO_TTS {
+            return Ok(None);
+        }
+        let factory = self.tts.get(&name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown text-to-speech provider '{}' (available: {}, {})",
+                config.tts_provider,
+                self.tts_providers().join(", "),
+                NO_TTS
+            )
+        })?;
+        factory(config).map(Some)
+    }
+
+    /// Construct and health-check the configured providers
+    pub async fn check_health(&self, config: &AudioConfig) -> Vec<ProviderHealth> {
+        let stt = match self.create_stt(config) {
+            Ok(service) => service.health_check().await,
+            Err(e) => Err(e),
+        };
+        let tts = match self.create_tts(config) {
+            Ok(Some(service)) => service.health_check().await.map(|_| true),
+            Ok(None) => Ok(false),
+            Err(e) => Err(e),
+        };
+
+        vec![
+            ProviderHealth {
+                kind: ProviderKind::SpeechToText,
+                provider: config.stt_provider.clone(),
+                healthy: stt.is_ok(),
+                message: match stt {
+                    Ok(()) => "ready".to_string(),
+                    Err(e) => e.to_string(),
+                },
+            },
+            ProviderHealth {
+                kind: ProviderKind::TextToSpeech,
+                provider: config.tts_provider.clone(),
+                healthy: tts.is_ok(),
+                message: match tts {
+                    Ok(true) => "ready".to_string(),
+                    Ok(false) => "disabled".to_string(),
+                    Err(e) => e.to_string(),
+                },
+            },
+        ]
+    }
+
+    /// Construct the configured providers, failing if either is unhealthy
+    pub async fn create_checked(
+        &self,
+        config: &AudioConfig,
+    ) -> Result<(
+        Arc<dyn SpeechRecognitionService>,
+        Option<Arc<dyn TextToSpeechService>>,
+    )> {
+        let stt = self.create_stt(config)?;
+        stt.health_check().await.map_err(|e| {
+            anyhow::anyhow!(
+                "Speech recognition provider '{}' failed its health check: {}",
+                config.stt_provider,
+                e
+            )
+        })?;
+
+        let tts = self.create_tts(config)?;
+        if let Some(tts) = &tts {
+            tts.health_check().await.map_err(|e| {
+                anyhow::anyhow!(
+                    "Text-to-speech provider '{}' failed its health check: {} \
+                     (set tts_provider to \"{}\" to disable spoken feedback)",
+                    config.tts_provider,
+                    e,
+                    NO_TTS
+                )
+            })?;
+        }
+
+        Ok((stt, tts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use domain::entities::recognition_session::RecognitionResult;
+    use shared::types::AudioSample;
+
+    struct SilentRecognizer;
+
+    #[async_trait]
+    impl SpeechRecognitionService for SilentRecognizer {
+        async fn recognize(&self, _audio: AudioSample) -> Result<RecognitionResult> {
+            Ok(RecognitionResult::new(String::new(), 0.0))
+        }
+        async fn initialize(&self) -> Result<()> {
+            Ok(())
+        }
+        async fn shutdown(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_selects_and_checks_providers() {
+        let mut registry = SpeechProviderRegistry::empty();
+        registry.register_stt("Silent", |_| Ok(Arc::new(SilentRecognizer)));
+        registry.register_tts("broken", |_| Err(anyhow::anyhow!("engine missing")));
+
+        let mut config = AudioConfig {
+            stt_provider: "silent".to_string(),
+            tts_provider: NO_TTS.to_string(),
+            ..AudioConfig::default()
+        };
+        let (_, tts) = registry.create_checked(&config).await.unwrap();
+        assert!(tts.is_none());
+
+        config.tts_provider = "broken".to_string();
+        let report = registry.check_health(&config).await;
+        assert!(report[0].healthy);
+        assert!(!report[1].healthy);
+        assert_eq!(report[1].message, "engine missing");
+        assert!(registry.create_checked(&config).await.is_err());
+
+        config.stt_provider = "whisper".to_string();
+        let err = registry.create_stt(&config).err().unwrap().to_string();
+        assert!(err.contains("available: silent"));
+    }
+}
@@ -5,6 +5,7 @@ use std::process::Command;
 use uuid::Uuid;
 
 // Voice configuration removed - only Piper Amy model is supported
+const VOICE_MODEL_PATH: &str = "./models/en_US-amy-medium.onnx";
 
 pub struct TtsAdapter {
     sample_rate: u32,
@@ -59,7 +60,7 @@ impl TtsAdapter {
         let temp_path = format!("/tmp/vibespeak_tts_{}.wav", Uuid::new_v4());
 
         // Always use en_US-amy-medium model (only voice model supported)
-        let voice_model_path = "./models/en_US-amy-medium.onnx".to_string();
+        let voice_model_path = VOICE_MODEL_PATH.to_string();
 
         // Verify model exists
         if !std::path::Path::new(&voice_model_path).exists() {
@@ -269,4 +270,17 @@ impl TextToSpeechService for TtsAdapter {
         tracing::info!("TTS adapter shutdown");
         Ok(())
     }
+
+    async fn health_check(&self) -> Result<()> {
+        if !Self::check_piper_availability() {
+            return Err(anyhow::anyhow!("Piper binary is no longer available"));
+        }
+        if !std::path::Path::new(VOICE_MODEL_PATH).exists() {
+            return Err(anyhow::anyhow!(
+                "Piper voice model not found at: {}",
+                VOICE_MODEL_PATH
+            ));
+        }
+        Ok(())
+    }
 }
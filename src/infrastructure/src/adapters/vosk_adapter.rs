@@ -2,7 +2,9 @@ use anyhow::Result;
 use async_trait::async_trait;
 use domain::entities::recognition_session::RecognitionResult;
 use domain::services::SpeechRecognitionService;
+use shared::platform;
 use shared::types::AudioSample;
+use std::path::PathBuf;
 use std::sync::Arc;
 use vosk::{Model, Recognizer};
 
@@ -28,6 +30,48 @@ impl VoskAdapter {
         })
     }
 
+    /// Find a Vosk model, preferring `explicit` over the well-known install locations
+    pub fn locate_model(explicit: Option<&str>) -> Result<PathBuf> {
+        if let Some(path) = explicit {
+            let path = PathBuf::from(path);
+            return if path.exists() {
+                Ok(path)
+            } else {
+                Err(anyhow::anyhow!(
+                    "Configured Vosk model not found: {}",
+                    path.display()
+                ))
+            };
+        }
+
+        let candidates: Vec<PathBuf> = [
+            "model/vosk-model-en-us-0.22",
+            "model/vosk-model-small-en-us-0.15",
+            "models/vosk-model-en-us-0.22",
+            "models/vosk-model-small-en-us-0.15",
+            "models/vosk-model-en-us-0.22-lgraph",
+            "/usr/share/vosk/model",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .chain(std::iter::once(
+            platform::data_home().join("vosk").join("model"),
+        ))
+        .collect();
+
+        candidates
+            .iter()
+            .find(|path| path.exists())
+            .cloned()
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Vosk model not found. Please download a model from https://alphacephei.com/vosk/models\n\
+                     and place it in one of: {:?}",
+                    candidates
+                )
+            })
+    }
+
     pub fn with_grammar(model_path: &str, sample_rate: f32, grammar: Vec<String>) -> Result<Self> {
         let model = Model::new(model_path).ok_or_else(|| {
             anyhow::anyhow!(format!(
@@ -99,4 +143,16 @@ impl SpeechRecognitionService for VoskAdapter {
         tracing::info!("Vosk speech recognition adapter shutdown");
         Ok(())
     }
+
+    async fn health_check(&self) -> Result<()> {
+        // A model that loads but cannot build a recognizer is unusable (e.g. wrong sample rate)
+        Recognizer::new(&self.model, self.default_sample_rate)
+            .map(|_| ())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Vosk model cannot create a recognizer at {} Hz",
+                    self.default_sample_rate
+                )
+            })
+    }
 }
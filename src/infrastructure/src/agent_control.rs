@@ -1,5 +1,10 @@
+use crate::token_usage::{TokenUsage, UsageTracker};
+use crate::tools::{ToolError, ToolOutput};
+use domain::models::{RetryStrategy, ToolRetry};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::time::timeout;
 
@@ -7,6 +12,19 @@ use tokio::time::timeout;
 #[derive(Clone)]
 pub struct AgentController {
     config: crate::config::SecurityConfig,
+    token_accounting: Option<TokenAccounting>,
+    /// Milliseconds spent paused, e.g. waiting for the user to answer a
+    /// question; the execution time limits do not count them
+    paused_ms: Arc<AtomicU64>,
+}
+
+/// Token usage already spent before this run, plus the tracker counting new usage
+#[derive(Debug, Clone)]
+struct TokenAccounting {
+    tracker: UsageTracker,
+    baseline: TokenUsage,
+    session_tokens_before: u64,
+    daily_tokens_before: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +34,10 @@ pub struct AgentExecutionLimits {
     pub max_execution_time_seconds: u64,
     pub verification_timeout_seconds: u64,
     pub allow_iteration_on_failure: bool,
+    #[serde(default)]
+    pub max_session_tokens: Option<u64>,
+    #[serde(default)]
+    pub max_daily_tokens: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -135,6 +157,8 @@ impl Default for AgentExecutionLimits {
             max_execution_time_seconds: 120, // 2 minutes
             verification_timeout_seconds: 30,
             allow_iteration_on_failure: true,
+            max_session_tokens: None,
+            max_daily_tokens: None,
         }
     }
 }
@@ -143,11 +167,69 @@ impl AgentController {
     pub fn new() -> Self {
         Self {
             config: crate::config::SecurityConfig::default(),
+            token_accounting: None,
+            paused_ms: Arc::default(),
         }
     }
 
     pub fn with_config(config: crate::config::SecurityConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            token_accounting: None,
+            paused_ms: Arc::default(),
+        }
+    }
+
+    /// Enforce the configured token budget against usage counted by `tracker`,
+    /// on top of tokens already spent in this session and today
+    pub fn with_token_accounting(
+        mut self,
+        tracker: UsageTracker,
+        session_tokens_before: u64,
+        daily_tokens_before: u64,
+    ) -> Self {
+        self.token_accounting = Some(TokenAccounting {
+            baseline: tracker.snapshot(),
+            tracker,
+            session_tokens_before,
+            daily_tokens_before,
+        });
+        self
+    }
+
+    /// Abort with `BudgetExceeded` once the session or daily token budget is spent
+    pub fn check_token_budget(&self) -> Result<(), AgentError> {
+        let Some(accounting) = &self.token_accounting else {
+            return Ok(());
+        };
+
+        let spent = accounting
+            .tracker
+            .snapshot()
+            .since(&accounting.baseline)
+            .total();
+        self.config
+            .agent_execution
+            .token_budget()
+            .check(
+                accounting.session_tokens_before + spent,
+                accounting.daily_tokens_before + spent,
+            )
+            .map_err(AgentError::BudgetExceeded)
+    }
+
+    /// Await `wait` with the execution time limits stopped
+    pub async fn pause<F: std::future::Future>(&self, wait: F) -> F::Output {
+        let paused_at = Instant::now();
+        let output = wait.await;
+        self.paused_ms
+            .fetch_add(paused_at.elapsed().as_millis() as u64, Ordering::SeqCst);
+        output
+    }
+
+    /// Time spent paused so far
+    pub fn paused_time(&self) -> Duration {
+        Duration::from_millis(self.paused_ms.load(Ordering::SeqCst))
     }
 
     /// Estimate current memory usage in bytes
@@ -185,7 +267,11 @@ impl AgentController {
     /// Check if execution should be terminated due to resource constraints
     pub fn should_terminate_due_to_resources(&self, state: &AgentExecutionState) -> bool {
         // Check time bounds
-        let elapsed = state.start_time.elapsed().unwrap_or_default();
+        let elapsed = state
+            .start_time
+            .elapsed()
+            .unwrap_or_default()
+            .saturating_sub(self.paused_time());
         if elapsed
             > state
                 .time_bounds_per_iteration
@@ -214,8 +300,14 @@ impl AgentController {
         config.agent_execution.max_execution_time_seconds = limits.max_execution_time_seconds;
         config.agent_execution.verification_timeout_seconds = limits.verification_timeout_seconds;
         config.agent_execution.allow_iteration_on_failure = limits.allow_iteration_on_failure;
+        config.agent_execution.max_session_tokens = limits.max_session_tokens;
+        config.agent_execution.max_daily_tokens = limits.max_daily_tokens;
 
-        Self { config }
+        Self {
+            config,
+            token_accounting: None,
+            paused_ms: Arc::default(),
+        }
     }
 
     pub fn max_tools_per_iteration(&self) -> u32 {
@@ -257,8 +349,10 @@ impl AgentController {
         while state.iteration_count < self.config.agent_execution.max_iterations {
             state.iteration_count += 1;
 
+            self.check_token_budget()?;
+
             // Check total execution time
-            if start_time.elapsed()
+            if start_time.elapsed().saturating_sub(self.paused_time())
                 > Duration::from_secs(self.config.agent_execution.max_execution_time_seconds)
             {
                 return Err(AgentError::Timeout(format!(
@@ -498,8 +592,10 @@ impl AgentController {
         while state.iteration_count < self.config.agent_execution.max_iterations {
             state.iteration_count += 1;
 
+            self.check_token_budget()?;
+
             // Check total execution time
-            if start_time.elapsed()
+            if start_time.elapsed().saturating_sub(self.paused_time())
                 > Duration::from_secs(self.config.agent_execution.max_execution_time_seconds)
             {
                 return Err(anyhow::anyhow!(
@@ -933,6 +1029,7 @@ pub enum AgentError {
     ExecutionFailed(String),
     MaxIterationsExceeded(u32),
     VerificationError(String),
+    BudgetExceeded(String),
     InternalError(String),
 }
 
@@ -949,6 +1046,7 @@ impl std::fmt::Display for AgentError {
                 write!(f, "Maximum iterations exceeded: {}", max)
             }
             AgentError::VerificationError(msg) => write!(f, "Verification error: {}", msg),
+            AgentError::BudgetExceeded(msg) => write!(f, "Token budget exceeded: {}", msg),
             AgentError::InternalError(msg) => write!(f, "Internal error: {}", msg),
         }
     }
@@ -956,11 +1054,85 @@ impl std::fmt::Display for AgentError {
 
 impl std::error::Error for AgentError {}
 
+/// How failed calls of one tool are tried again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Runs of the call with unchanged arguments, the first included
+    pub max_attempts: u32,
+    /// Pause before the second run, multiplied for every further one
+    pub initial_backoff_ms: u64,
+    pub backoff_multiplier: f32,
+    /// Tried once the runs with unchanged arguments failed
+    pub substitute: Option<ToolSubstitute>,
+    /// Ask the model to correct the arguments as a last resort
+    pub repair_arguments: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff_ms: 500,
+            backoff_multiplier: 2.0,
+            substitute: None,
+            repair_arguments: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy for `tool` when none is configured: backoff for network
+    /// tools, plain grep for ripgrep searches, repaired arguments for tools
+    /// that only read
+    pub fn default_for(tool: &str) -> Self {
+        let policy = Self::default();
+        match tool {
+            "curl_fetch" | "web_search" => Self {
+                max_attempts: 3,
+                ..policy
+            },
+            "grep_search" => Self {
+                substitute: Some(ToolSubstitute {
+                    tool: "grep_search".to_string(),
+                    parameters: [("engine".to_string(), "grep".to_string())].into(),
+                }),
+                repair_arguments: true,
+                ..policy
+            },
+            "file_read" | "directory_list" | "find_files" | "git_diff" | "git_log" => Self {
+                repair_arguments: true,
+                ..policy
+            },
+            _ => policy,
+        }
+    }
+
+    /// Pause before run `attempt + 1`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self
+            .backoff_multiplier
+            .max(1.0)
+            .powi(attempt.saturating_sub(1) as i32);
+        Duration::from_millis((self.initial_backoff_ms as f32 * factor) as u64)
+    }
+}
+
+/// Tool doing the job of a failed one, with parameters added to the call's
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSubstitute {
+    pub tool: String,
+    #[serde(default)]
+    pub parameters: std::collections::BTreeMap<String, String>,
+}
+
 /// Safe failure behavior implementation
 pub struct SafeFailureHandler {
     max_retries: u32,
     backoff_multiplier: f32,
     enable_fallbacks: bool,
+    /// Configured retry policies by tool name
+    retry_policies: HashMap<String, RetryPolicy>,
 }
 
 impl SafeFailureHandler {
@@ -969,9 +1141,98 @@ impl SafeFailureHandler {
             max_retries: 3,
             backoff_multiplier: 1.5,
             enable_fallbacks: true,
+            retry_policies: HashMap::new(),
         }
     }
 
+    /// Use `policies` instead of the defaults for the tools they name
+    pub fn with_retry_policies(
+        mut self,
+        policies: impl IntoIterator<Item = (String, RetryPolicy)>,
+    ) -> Self {
+        self.retry_policies.extend(policies);
+        self
+    }
+
+    pub fn retry_policy(&self, tool: &str) -> RetryPolicy {
+        self.retry_policies
+            .get(tool)
+            .cloned()
+            .unwrap_or_else(|| RetryPolicy::default_for(tool))
+    }
+
+    /// Run the call `call_id` of `tool` through `run`, and when it fails try
+    /// what the tool's policy allows: the same call after backoff for
+    /// transient errors, then the substitute tool, then the arguments
+    /// `repair` proposes for the error. Policy denials are never retried.
+    pub async fn run_tool_call<Run, RunFut, Repair, RepairFut>(
+        &self,
+        call_id: &str,
+        tool: &str,
+        parameters: HashMap<String, String>,
+        run: Run,
+        repair: Repair,
+    ) -> (Result<ToolOutput, ToolError>, Vec<ToolRetry>)
+    where
+        Run: Fn(String, HashMap<String, String>) -> RunFut,
+        RunFut: std::future::Future<Output = Result<ToolOutput, ToolError>>,
+        Repair: FnOnce(String, HashMap<String, String>) -> RepairFut,
+        RepairFut: std::future::Future<Output = Option<HashMap<String, String>>>,
+    {
+        let policy = self.retry_policy(tool);
+        let mut retries = Vec::new();
+        let mut result = run(tool.to_string(), parameters.clone()).await;
+
+        let mut attempt = 1;
+        while tool_failed(&result) && attempt < policy.max_attempts {
+            let error = error_text(&result);
+            tokio::time::sleep(policy.backoff(attempt)).await;
+            attempt += 1;
+            result = run(tool.to_string(), parameters.clone()).await;
+            retries.push(retry(call_id, tool, RetryStrategy::Backoff, error, &result));
+        }
+
+        // Repairs apply to the last call tried, the substitute's if any
+        let mut last_tool = tool.to_string();
+        let mut last_parameters = parameters;
+        let substitute = policy.substitute.as_ref().filter(|_| self.enable_fallbacks);
+        if let (true, Some(substitute)) = (tool_failed(&result), substitute) {
+            let error = error_text(&result);
+            last_tool = substitute.tool.clone();
+            last_parameters.extend(substitute.parameters.clone());
+            result = run(last_tool.clone(), last_parameters.clone()).await;
+            retries.push(retry(
+                call_id,
+                &last_tool,
+                RetryStrategy::Substitute,
+                error,
+                &result,
+            ));
+        }
+
+        let wrong_arguments = matches!(result, Err(ToolError::ValidationError(_)));
+        if self.enable_fallbacks
+            && policy.repair_arguments
+            && (wrong_arguments || tool_failed(&result))
+        {
+            let error = error_text(&result);
+            if let Some(repaired) = repair(error.clone(), last_parameters.clone()).await {
+                if repaired != last_parameters {
+                    result = run(last_tool.clone(), repaired).await;
+                    retries.push(retry(
+                        call_id,
+                        &last_tool,
+                        RetryStrategy::RepairArguments,
+                        error,
+                        &result,
+                    ));
+                }
+            }
+        }
+
+        (result, retries)
+    }
+
     pub async fn execute_with_failure_handling<F, Fut, T>(
         &self,
         operation: F,
@@ -1024,3 +1285,150 @@ impl SafeFailureHandler {
         }
     }
 }
+
+/// Whether the tool could not do its job, as opposed to being refused
+fn tool_failed(result: &Result<ToolOutput, ToolError>) -> bool {
+    matches!(
+        result,
+        Err(ToolError::ExecutionError(_) | ToolError::TimeoutError)
+    )
+}
+
+fn error_text(result: &Result<ToolOutput, ToolError>) -> String {
+    result
+        .as_ref()
+        .err()
+        .map(ToString::to_string)
+        .unwrap_or_default()
+}
+
+fn retry(
+    call_id: &str,
+    tool: &str,
+    strategy: RetryStrategy,
+    error: String,
+    result: &Result<ToolOutput, ToolError>,
+) -> ToolRetry {
+    ToolRetry {
+        tool_call_id: call_id.to_string(),
+        tool: tool.to_string(),
+        strategy,
+        error,
+        succeeded: result.is_ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ResourceUsage;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn output() -> ToolOutput {
+        ToolOutput {
+            success: true,
+            stdout: "ok".to_string(),
+            stderr: String::new(),
+            exit_code: Some(0),
+            execution_time: Duration::ZERO,
+            resources_used: ResourceUsage {
+                memory_used_mb: 0,
+                cpu_time_seconds: 0.0,
+                processes_created: 0,
+                output_size: 2,
+            },
+        }
+    }
+
+    fn no_repair(
+        _: String,
+        _: HashMap<String, String>,
+    ) -> std::future::Ready<Option<HashMap<String, String>>> {
+        std::future::ready(None)
+    }
+
+    #[tokio::test]
+    async fn test_retries_failed_tool_calls_by_policy() {
+        let handler = SafeFailureHandler::new().with_retry_policies([(
+            "curl_fetch".to_string(),
+            RetryPolicy {
+                max_attempts: 3,
+                initial_backoff_ms: 1,
+                ..RetryPolicy::default()
+            },
+        )]);
+        let runs = AtomicU32::new(0);
+        let (result, retries) = handler
+            .run_tool_call(
+                "fetch-1",
+                "curl_fetch",
+                HashMap::new(),
+                |_, _| {
+                    let run = runs.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        match run {
+                            0 | 1 => Err(ToolError::ExecutionError("reset".to_string())),
+                            _ => Ok(output()),
+                        }
+                    }
+                },
+                no_repair,
+            )
+            .await;
+        assert!(result.is_ok());
+        let strategies: Vec<_> = retries.iter().map(|r| (r.strategy, r.succeeded)).collect();
+        assert_eq!(
+            strategies,
+            [
+                (RetryStrategy::Backoff, false),
+                (RetryStrategy::Backoff, true)
+            ]
+        );
+
+        // rg is missing: plain grep, then a corrected path
+        let params: HashMap<String, String> = [("path".to_string(), "scr".to_string())].into();
+        let (result, retries) = handler
+            .run_tool_call(
+                "grep-1",
+                "grep_search",
+                params,
+                |_, params: HashMap<String, String>| async move {
+                    match (params.get("engine"), params["path"].as_str()) {
+                        (None, _) => Err(ToolError::ExecutionError("rg not found".to_string())),
+                        (Some(_), "src") => Ok(output()),
+                        (Some(_), _) => Err(ToolError::ExecutionError("no such path".to_string())),
+                    }
+                },
+                |error, mut params| async move {
+                    assert!(error.contains("no such path"));
+                    params.insert("path".to_string(), "src".to_string());
+                    Some(params)
+                },
+            )
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(
+            retries.iter().map(|r| r.strategy).collect::<Vec<_>>(),
+            [RetryStrategy::Substitute, RetryStrategy::RepairArguments]
+        );
+        assert_eq!(
+            retries[0].line(),
+            "grep_search substituted after \"Execution error: rg not found\": failed"
+        );
+
+        let (result, retries) = handler
+            .run_tool_call(
+                "write-1",
+                "file_write",
+                HashMap::new(),
+                |_, _| async { Err(ToolError::SecurityViolation("denied".to_string())) },
+                no_repair,
+            )
+            .await;
+        assert!(result.is_err() && retries.is_empty());
+        assert_eq!(
+            RetryPolicy::default().backoff(3),
+            Duration::from_millis(2000)
+        );
+    }
+}
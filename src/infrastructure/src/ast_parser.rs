@@ -1,7 +1,9 @@
+use domain::models::CodeSymbol;
 use regex::Regex;
 use shared::types::Result;
 use std::collections::HashMap;
-use tree_sitter::{Language, Parser, Query, QueryCursor, StreamingIterator};
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser, Query, QueryCursor, StreamingIterator};
 
 /// AST Parser for semantic code analysis
 pub struct AstParser {
@@ -19,6 +21,15 @@ pub struct AstNode {
     pub metadata: HashMap<String, String>,
 }
 
+/// A syntactically complete slice of a source file
+#[derive(Debug, Clone)]
+pub struct SymbolChunk {
+    /// Definition the slice belongs to; `None` for top-level code between definitions
+    pub symbol: Option<CodeSymbol>,
+    pub text: String,
+    pub start_byte: usize,
+}
+
 #[derive(Debug)]
 pub enum ParseError {
     UnsupportedLanguage(String),
@@ -52,6 +63,10 @@ impl AstParser {
         ts_parser.set_language(&tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into())?;
         parsers.insert("ts".to_string(), ts_parser);
 
+        let mut tsx_parser = Parser::new();
+        tsx_parser.set_language(&tree_sitter_typescript::LANGUAGE_TSX.into())?;
+        parsers.insert("tsx".to_string(), tsx_parser);
+
         // Initialize language-specific queries
         Self::init_queries(&mut language_queries)?;
 
@@ -209,7 +224,7 @@ impl AstParser {
                 &tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
                 r#"
             (class_declaration
-                name: (identifier) @class_name
+                name: (type_identifier) @class_name
                 body: (class_body) @class_body) @class
             "#,
             )?,
@@ -238,6 +253,38 @@ impl AstParser {
         Ok(ast_node)
     }
 
+    /// Text of every token of `code`, without the whitespace between them
+    pub fn tokens(&mut self, code: &str, language: &str) -> Result<Vec<String>> {
+        let parser = self
+            .parsers
+            .get_mut(language)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported language: {}", language))?;
+        let tree = parser
+            .parse(code, None)
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse code"))?;
+
+        let mut tokens = Vec::new();
+        let mut cursor = tree.walk();
+        'walk: loop {
+            let node = cursor.node();
+            if node.child_count() == 0 {
+                if let Ok(text) = node.utf8_text(code.as_bytes()) {
+                    if !text.trim().is_empty() {
+                        tokens.push(text.to_string());
+                    }
+                }
+            } else if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    break 'walk;
+                }
+            }
+        }
+        Ok(tokens)
+    }
+
     /// Extract semantic chunks from parsed AST
     pub fn extract_semantic_chunks(&mut self, code: &str, language: &str) -> Result<Vec<String>> {
         let mut chunks = Vec::new();
@@ -257,6 +304,265 @@ impl AstParser {
         Ok(chunks)
     }
 
+    /// Parser key for a source file, if its language is supported
+    pub fn language_for_path(path: &Path) -> Option<&'static str> {
+        match path.extension()?.to_str()? {
+            "rs" => Some("rs"),
+            "py" => Some("py"),
+            "js" | "jsx" | "mjs" | "cjs" => Some("js"),
+            "ts" | "mts" | "cts" => Some("ts"),
+            "tsx" => Some("tsx"),
+            _ => None,
+        }
+    }
+
+    /// Split a file at definition boundaries.
+    ///
+    /// Every top-level function, type, class or impl becomes one chunk together
+    /// with its leading doc comments and attributes. Definitions larger than
+    /// `max_bytes` that contain nested definitions (impl blocks, classes,
+    /// modules) are split into their members instead. Code between definitions
+    /// is kept as separate chunks so nothing is lost; the returned chunks are in
+    /// source order and cover all non-blank text.
+    pub fn extract_symbol_chunks(
+        &mut self,
+        code: &str,
+        language: &str,
+        max_bytes: usize,
+    ) -> Result<Vec<SymbolChunk>> {
+        let parser = self
+            .parsers
+            .get_mut(language)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported language: {}", language))?;
+
+        let tree = parser
+            .parse(code, None)
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse code"))?;
+
+        let root = tree.root_node();
+        if root.has_error() {
+            return Err(anyhow::anyhow!("Syntax errors in {} source", language));
+        }
+
+        let mut chunks = Vec::new();
+        Self::collect_symbol_chunks(
+            root,
+            code,
+            language,
+            0..code.len(),
+            None,
+            max_bytes,
+            &mut chunks,
+        );
+        Ok(chunks)
+    }
+
+    /// Chunk the named children of `container`, filling gaps within `range`
+    /// with chunks attributed to `parent`
+    fn collect_symbol_chunks(
+        container: Node,
+        code: &str,
+        language: &str,
+        range: std::ops::Range<usize>,
+        parent: Option<&CodeSymbol>,
+        max_bytes: usize,
+        chunks: &mut Vec<SymbolChunk>,
+    ) {
+        let mut cursor = container.walk();
+        let children: Vec<Node> = container.named_children(&mut cursor).collect();
+
+        let mut covered = range.start;
+        let mut leading: Option<usize> = None;
+
+        for child in children {
+            if Self::is_leading_trivia(child.kind()) {
+                leading.get_or_insert(child.start_byte());
+                continue;
+            }
+
+            let in_type = parent.is_some_and(|p| p.kind != "module");
+            let Some(kind) = Self::symbol_kind(child, in_type) else {
+                leading = None;
+                continue;
+            };
+
+            let start = leading.take().unwrap_or(child.start_byte()).max(covered);
+            let end = child.end_byte();
+            Self::push_gap(code, covered..start, parent, chunks);
+
+            let name = Self::symbol_name(child, code).unwrap_or_else(|| "<anonymous>".to_string());
+            let symbol = CodeSymbol {
+                name: match parent {
+                    // `impl Display for Foo` members are qualified by the type
+                    Some(parent) => format!(
+                        "{}{}{}",
+                        parent.name.rsplit(" for ").next().unwrap_or(&parent.name),
+                        if language == "rs" { "::" } else { "." },
+                        name
+                    ),
+                    None => name,
+                },
+                kind: kind.to_string(),
+            };
+
+            match Self::symbol_body(child) {
+                Some(body) if end - start > max_bytes && Self::has_nested_symbols(body) => {
+                    Self::collect_symbol_chunks(
+                        body,
+                        code,
+                        language,
+                        start..end,
+                        Some(&symbol),
+                        max_bytes,
+                        chunks,
+                    );
+                }
+                _ => chunks.push(SymbolChunk {
+                    symbol: Some(symbol),
+                    text: code[start..end].to_string(),
+                    start_byte: start,
+                }),
+            }
+            covered = end;
+        }
+
+        Self::push_gap(code, covered..range.end, parent, chunks);
+    }
+
+    /// Keep code between definitions unless it is only whitespace and braces
+    fn push_gap(
+        code: &str,
+        range: std::ops::Range<usize>,
+        parent: Option<&CodeSymbol>,
+        chunks: &mut Vec<SymbolChunk>,
+    ) {
+        if range.start >= range.end {
+            return;
+        }
+        let text = &code[range.clone()];
+        if !text.chars().any(char::is_alphanumeric) {
+            return;
+        }
+        let trimmed_start = range.start + (text.len() - text.trim_start().len());
+        chunks.push(SymbolChunk {
+            symbol: parent.cloned(),
+            text: text.trim().to_string(),
+            start_byte: trimmed_start,
+        });
+    }
+
+    /// Comments and attributes that belong to the definition following them
+    fn is_leading_trivia(kind: &str) -> bool {
+        matches!(
+            kind,
+            "comment" | "line_comment" | "block_comment" | "attribute_item" | "decorator"
+        )
+    }
+
+    /// Chunk kind for definition nodes across the supported grammars
+    fn symbol_kind(node: Node, in_type: bool) -> Option<&'static str> {
+        let kind = match node.kind() {
+            "function_item"
+            | "function_definition"
+            | "function_declaration"
+            | "generator_function_declaration"
+            | "function_signature_item" => {
+                if in_type {
+                    "method"
+                } else {
+                    "function"
+                }
+            }
+            "method_definition" | "method_signature" | "abstract_method_signature" => "method",
+            "struct_item" | "union_item" => "struct",
+            "enum_item" | "enum_declaration" => "enum",
+            "trait_item" => "trait",
+            "impl_item" => "impl",
+            "mod_item" | "internal_module" | "module" => "module",
+            "macro_definition" => "macro",
+            "type_item" | "type_alias_declaration" => "type",
+            "const_item" | "static_item" => "const",
+            "class_definition" | "class_declaration" | "abstract_class_declaration" => "class",
+            "interface_declaration" => "interface",
+            "decorated_definition" => {
+                return node
+                    .child_by_field_name("definition")
+                    .and_then(|definition| Self::symbol_kind(definition, in_type));
+            }
+            "export_statement" => {
+                return node
+                    .child_by_field_name("declaration")
+                    .and_then(|declaration| Self::symbol_kind(declaration, in_type));
+            }
+            "lexical_declaration" | "variable_declaration" => {
+                // `const handler = () => {}` is a function in all but syntax
+                let value = Self::first_declarator(node)?.child_by_field_name("value")?;
+                match value.kind() {
+                    "arrow_function" | "function_expression" | "function" => "function",
+                    "class" => "class",
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    fn symbol_name(node: Node, code: &str) -> Option<String> {
+        let text = |n: Node| n.utf8_text(code.as_bytes()).ok().map(str::to_string);
+        match node.kind() {
+            "impl_item" => {
+                let ty = text(node.child_by_field_name("type")?)?;
+                match node.child_by_field_name("trait").and_then(text) {
+                    Some(tr) => Some(format!("{} for {}", tr, ty)),
+                    None => Some(ty),
+                }
+            }
+            "decorated_definition" => {
+                Self::symbol_name(node.child_by_field_name("definition")?, code)
+            }
+            "export_statement" => Self::symbol_name(node.child_by_field_name("declaration")?, code),
+            "lexical_declaration" | "variable_declaration" => {
+                text(Self::first_declarator(node)?.child_by_field_name("name")?)
+            }
+            _ => text(node.child_by_field_name("name")?),
+        }
+    }
+
+    fn first_declarator(node: Node) -> Option<Node> {
+        let mut cursor = node.walk();
+        let declarator = node
+            .named_children(&mut cursor)
+            .find(|child| child.kind() == "variable_declarator");
+        declarator
+    }
+
+    /// Node holding the members of a container definition
+    fn symbol_body(node: Node) -> Option<Node> {
+        match node.kind() {
+            "decorated_definition" => Self::symbol_body(node.child_by_field_name("definition")?),
+            "export_statement" => Self::symbol_body(node.child_by_field_name("declaration")?),
+            "impl_item"
+            | "trait_item"
+            | "mod_item"
+            | "class_definition"
+            | "class_declaration"
+            | "abstract_class_declaration"
+            | "internal_module"
+            | "module"
+            | "interface_declaration" => node.child_by_field_name("body"),
+            _ => None,
+        }
+    }
+
+    fn has_nested_symbols(body: Node) -> bool {
+        let mut cursor = body.walk();
+        let nested = body
+            .named_children(&mut cursor)
+            .any(|child| Self::symbol_kind(child, true).is_some());
+        nested
+    }
+
     /// Extract chunks using AST queries
     fn extract_chunks_ast(&mut self, code: &str, language: &str) -> Result<Vec<String>> {
         let parser = self
@@ -468,3 +774,77 @@ impl AstParser {
         Ok(docs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(chunks: &[SymbolChunk]) -> Vec<(String, String)> {
+        chunks
+            .iter()
+            .filter_map(|c| c.symbol.as_ref())
+            .map(|s| (s.kind.clone(), s.name.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_chunks_follow_definitions() {
+        let code = "use std::fmt;\n\n/// A point\n#[derive(Debug)]\npub struct Point {\n    x: i32,\n}\n\nimpl fmt::Display for Point {\n    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {\n        write!(f, \"{}\", self.x)\n    }\n\n    fn unused(&self) {}\n}\n";
+        let mut parser = AstParser::new().unwrap();
+
+        let whole = parser.extract_symbol_chunks(code, "rs", 2000).unwrap();
+        assert_eq!(whole[0].symbol, None);
+        assert_eq!(whole[0].text, "use std::fmt;");
+        assert!(whole[1]
+            .text
+            .starts_with("/// A point\n#[derive(Debug)]\npub struct Point"));
+        assert_eq!(
+            symbols(&whole),
+            vec![
+                ("struct".to_string(), "Point".to_string()),
+                ("impl".to_string(), "fmt::Display for Point".to_string()),
+            ]
+        );
+
+        // Oversized impl blocks are split into their methods
+        let split = parser.extract_symbol_chunks(code, "rs", 60).unwrap();
+        let names = symbols(&split);
+        assert!(names.contains(&("method".to_string(), "Point::fmt".to_string())));
+        assert!(names.contains(&("method".to_string(), "Point::unused".to_string())));
+        let fmt = split
+            .iter()
+            .find(|c| c.symbol.as_ref().is_some_and(|s| s.name == "Point::fmt"))
+            .unwrap();
+        assert_eq!(
+            &code[fmt.start_byte..fmt.start_byte + fmt.text.len()],
+            fmt.text
+        );
+        assert!(fmt.text.ends_with("}"));
+    }
+
+    #[test]
+    fn test_python_and_typescript_symbols() {
+        let mut parser = AstParser::new().unwrap();
+
+        let py = "import os\n\n@cache\ndef load(path):\n    return os.path.exists(path)\n\nclass Store:\n    def get(self):\n        return 1\n";
+        let chunks = parser.extract_symbol_chunks(py, "py", 2000).unwrap();
+        assert_eq!(
+            symbols(&chunks),
+            vec![
+                ("function".to_string(), "load".to_string()),
+                ("class".to_string(), "Store".to_string()),
+            ]
+        );
+        assert!(chunks[1].text.starts_with("@cache"));
+
+        let ts = "export interface User { id: number }\nexport const fetchUser = async (id: number) => {\n  return { id };\n};\n";
+        let chunks = parser.extract_symbol_chunks(ts, "ts", 2000).unwrap();
+        assert_eq!(
+            symbols(&chunks),
+            vec![
+                ("interface".to_string(), "User".to_string()),
+                ("function".to_string(), "fetchUser".to_string()),
+            ]
+        );
+    }
+}
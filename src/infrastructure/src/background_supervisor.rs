@@ -1,16 +1,30 @@
 use crate::compilation_watcher::CompilationWatcher;
 use crate::error_analyzer::{ErrorAnalyzer, ErrorContext};
 use crate::fix_applier::{FixApplier, FixConfidence};
+use crate::service_watchdog::{
+    CrashAction, ResourceLimits, ResourceSampler, RestartPolicy, RestartState,
+};
 use crate::session_store::SessionStore;
 use anyhow::Result;
 use flume::{Receiver, Sender};
+use futures::future::{BoxFuture, FutureExt};
 use notify::{Event, RecursiveMode, Watcher};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 
+/// How often the watchdog checks services and samples resource use
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Source name of the watchdog's reports in the event stream
+const WATCHDOG_SOURCE: &str = "watchdog";
+
+/// Starts a service's task; called again for every restart
+type ServiceFactory = Arc<dyn Fn() -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
 /// Background event types that can be broadcast to the UI
 #[derive(Debug, Clone)]
 pub enum BackgroundEvent {
@@ -88,13 +102,18 @@ pub struct BackgroundSupervisor {
     error_analyzer: ErrorAnalyzer,
     fix_applier: Option<FixApplier>,
     project_root: Option<PathBuf>,
+    restart_policy: RestartPolicy,
+    resource_limits: ResourceLimits,
 }
 
-#[derive(Debug)]
 struct BackgroundService {
-    name: String,
-    handle: JoinHandle<()>,
+    /// None while waiting to be restarted, or after being given up on
+    handle: Option<JoinHandle<Result<()>>>,
+    factory: ServiceFactory,
     status: ServiceStatus,
+    started_at: Instant,
+    restarts: RestartState,
+    restart_at: Option<Instant>,
 }
 
 #[derive(Debug, Clone)]
@@ -121,9 +140,23 @@ impl BackgroundSupervisor {
             error_analyzer: ErrorAnalyzer,
             fix_applier: None,
             project_root: None,
+            restart_policy: RestartPolicy::default(),
+            resource_limits: ResourceLimits::default(),
         }
     }
 
+    /// Backoff and give-up limits for restarting crashed services
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.restart_policy = policy;
+        self
+    }
+
+    /// CPU and memory use above which the watchdog reports a process
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.resource_limits = limits;
+        self
+    }
+
     /// Set the session store for background services
     pub fn with_session_store(mut self, store: Arc<RwLock<SessionStore>>) -> Self {
         self.session_store = Some(store);
@@ -164,31 +197,24 @@ impl BackgroundSupervisor {
     ) -> Result<()> {
         let event_tx = self.event_tx.clone();
 
-        let handle = tokio::spawn(async move {
-            match crate::test_watcher::TestWatcher::start_monitoring(
-                project_root,
-                event_tx,
-                session,
-            )
-            .await
-            {
-                Ok(_watcher) => {
+        self.spawn_service(
+            "test-watcher",
+            Arc::new(move || {
+                let (project_root, event_tx, session) =
+                    (project_root.clone(), event_tx.clone(), session.clone());
+                async move {
+                    let _watcher = crate::test_watcher::TestWatcher::start_monitoring(
+                        project_root,
+                        event_tx,
+                        session,
+                    )
+                    .await?;
                     // Test watcher is now monitoring
                     futures::future::pending::<()>().await;
+                    Ok(())
                 }
-                Err(e) => {
-                    eprintln!("Test watcher error: {}", e);
-                }
-            }
-        });
-
-        self.services.insert(
-            "test-watcher".to_string(),
-            BackgroundService {
-                name: "test-watcher".to_string(),
-                handle,
-                status: ServiceStatus::Running,
-            },
+                .boxed()
+            }),
         );
 
         Ok(())
@@ -198,19 +224,18 @@ impl BackgroundSupervisor {
     async fn start_compilation_watcher(&mut self, project_root: PathBuf) -> Result<()> {
         let event_tx = self.event_tx.clone();
 
-        let handle = tokio::spawn(async move {
-            if let Err(e) = CompilationWatcher::start_monitoring(project_root, event_tx).await {
-                eprintln!("Compilation watcher error: {}", e);
-            }
-        });
-
-        self.services.insert(
-            "compilation-watcher".to_string(),
-            BackgroundService {
-                name: "compilation-watcher".to_string(),
-                handle,
-                status: ServiceStatus::Running,
-            },
+        self.spawn_service(
+            "compilation-watcher",
+            Arc::new(move || {
+                let (project_root, event_tx) = (project_root.clone(), event_tx.clone());
+                async move {
+                    let _watcher =
+                        CompilationWatcher::start_monitoring(project_root, event_tx).await?;
+                    futures::future::pending::<()>().await;
+                    Ok(())
+                }
+                .boxed()
+            }),
         );
 
         Ok(())
@@ -220,25 +245,20 @@ impl BackgroundSupervisor {
     async fn start_lsp_client(&mut self, project_root: PathBuf) -> Result<()> {
         let event_tx = self.event_tx.clone();
 
-        let handle = tokio::spawn(async move {
-            match crate::lsp_client::LspClient::start_rust_analyzer(project_root, event_tx).await {
-                Ok(_client) => {
+        self.spawn_service(
+            "lsp-client",
+            Arc::new(move || {
+                let (project_root, event_tx) = (project_root.clone(), event_tx.clone());
+                async move {
+                    let _client =
+                        crate::lsp_client::LspClient::start_rust_analyzer(project_root, event_tx)
+                            .await?;
                     // Keep the client alive for the duration
                     futures::future::pending::<()>().await;
+                    Ok(())
                 }
-                Err(e) => {
-                    eprintln!("LSP client error: {}", e);
-                }
-            }
-        });
-
-        self.services.insert(
-            "lsp-client".to_string(),
-            BackgroundService {
-                name: "lsp-client".to_string(),
-                handle,
-                status: ServiceStatus::Running,
-            },
+                .boxed()
+            }),
         );
 
         Ok(())
@@ -249,22 +269,115 @@ impl BackgroundSupervisor {
         let event_tx = self.event_tx.clone();
         let shutdown_rx = self.shutdown_rx.clone();
 
-        let handle = tokio::spawn(async move {
-            if let Err(e) = Self::run_file_watcher(project_root, event_tx, shutdown_rx).await {
-                eprintln!("File watcher error: {}", e);
-            }
-        });
+        self.spawn_service(
+            "file-watcher",
+            Arc::new(move || {
+                Self::run_file_watcher(project_root.clone(), event_tx.clone(), shutdown_rx.clone())
+                    .boxed()
+            }),
+        );
 
+        Ok(())
+    }
+
+    /// Run `factory`'s task as service `name`, restarted by the watchdog when
+    /// it crashes
+    fn spawn_service(&mut self, name: &str, factory: ServiceFactory) {
+        let handle = tokio::spawn(factory());
         self.services.insert(
-            "file-watcher".to_string(),
+            name.to_string(),
             BackgroundService {
-                name: "file-watcher".to_string(),
-                handle,
+                handle: Some(handle),
+                factory,
                 status: ServiceStatus::Running,
+                started_at: Instant::now(),
+                restarts: RestartState::default(),
+                restart_at: None,
             },
         );
+    }
 
-        Ok(())
+    /// Supervise the services for as long as the runtime lives: restart
+    /// crashed ones with backoff and report crashes, give-ups and runaway
+    /// resource use as events
+    pub async fn run_watchdog(&mut self) {
+        let mut sampler = ResourceSampler::new();
+        let mut ticker = tokio::time::interval(WATCHDOG_INTERVAL);
+        loop {
+            ticker.tick().await;
+            self.check_services().await;
+            let usage = sampler.sample();
+            for report in sampler.over_limits(&usage, &self.resource_limits) {
+                self.report(LogLevel::Warn, report);
+            }
+        }
+    }
+
+    /// Notice exited services and restart those whose backoff has passed
+    async fn check_services(&mut self) {
+        let now = Instant::now();
+        let mut reports = Vec::new();
+        for (name, service) in self.services.iter_mut() {
+            if let Some(handle) = service
+                .handle
+                .as_mut()
+                .filter(|handle| handle.is_finished())
+            {
+                let reason = match handle.await {
+                    Ok(Ok(())) => "exited".to_string(),
+                    Ok(Err(e)) => format!("failed: {}", e),
+                    Err(e) if e.is_panic() => "panicked".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                service.handle = None;
+                let uptime = now.duration_since(service.started_at);
+                match service.restarts.on_crash(&self.restart_policy, uptime) {
+                    CrashAction::Restart { after, attempt } => {
+                        service.status = ServiceStatus::Failed(reason.clone());
+                        service.restart_at = Some(now + after);
+                        reports.push((
+                            LogLevel::Warn,
+                            format!(
+                                "{} {}; restarting in {}s (attempt {}/{})",
+                                name,
+                                reason,
+                                after.as_secs(),
+                                attempt,
+                                self.restart_policy.max_restarts
+                            ),
+                        ));
+                    }
+                    CrashAction::GiveUp { restarts } => {
+                        reports.push((
+                            LogLevel::Error,
+                            format!(
+                                "{} {} after {} restart(s); giving up on it",
+                                name, reason, restarts
+                            ),
+                        ));
+                        service.status = ServiceStatus::Failed(reason);
+                    }
+                }
+            }
+
+            if service.restart_at.is_some_and(|at| at <= now) {
+                service.restart_at = None;
+                service.handle = Some(tokio::spawn((service.factory)()));
+                service.status = ServiceStatus::Running;
+                service.started_at = now;
+            }
+        }
+        for (level, message) in reports {
+            self.report(level, message);
+        }
+    }
+
+    fn report(&self, level: LogLevel, message: String) {
+        let _ = self.event_tx.send(BackgroundEvent::LogEntry {
+            source: WATCHDOG_SOURCE.to_string(),
+            level,
+            message,
+        });
     }
 
     /// Run the file watcher loop with proper file system monitoring
@@ -312,6 +425,7 @@ impl BackgroundSupervisor {
                         });
 
                         if !should_ignore {
+                            crate::tool_cache::files_changed();
                             if event_tx.send(bg_event).is_err() {
                                 break; // UI receiver disconnected
                             }
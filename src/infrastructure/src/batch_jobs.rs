@@ -0,0 +1,223 @@
+//! Many inputs answered in one non-interactive run (`bro --batch`)
+//!
+//! Each line of the input file is a job: a question, or a file to explain
+//! or review. Jobs run with at most `--jobs` at a time, and each result is
+//! written to the output file as one JSON line, in input order, as soon as
+//! it and every job before it are done. A job that fails records its error
+//! and the batch carries on.
+
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchMode {
+    #[default]
+    Query,
+    Explain,
+    Review,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchJob {
+    /// Echoed in the result; the line number when not given
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub mode: BatchMode,
+    /// The question; for explain and review, extra instructions
+    #[serde(default, alias = "query", alias = "goal")]
+    pub input: String,
+    /// File explain and review work on
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub id: String,
+    pub mode: BatchMode,
+    pub input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+impl BatchResult {
+    fn new(job: BatchJob, outcome: Result<String>, elapsed: Duration) -> Self {
+        let (output, error) = match outcome {
+            Ok(output) => (Some(output), None),
+            Err(e) => (None, Some(format!("{:#}", e))),
+        };
+        Self {
+            id: job.id.unwrap_or_default(),
+            mode: job.mode,
+            input: job.input,
+            file: job.file,
+            ok: error.is_none(),
+            output,
+            error,
+            elapsed_ms: elapsed.as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Jobs of a batch file. Lines are JSON jobs, or plain text taken as a
+/// query; blank lines and lines starting with `#` are skipped.
+pub fn parse_jobs(text: &str) -> Result<Vec<BatchJob>> {
+    let mut jobs = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let number = index + 1;
+        let mut job = if line.starts_with('{') {
+            serde_json::from_str::<BatchJob>(line)
+                .map_err(|e| anyhow::anyhow!("Line {}: {}", number, e))?
+        } else {
+            BatchJob {
+                id: None,
+                mode: BatchMode::Query,
+                input: line.to_string(),
+                file: None,
+            }
+        };
+        match job.mode {
+            BatchMode::Query if job.input.trim().is_empty() => {
+                anyhow::bail!("Line {}: a query needs an \"input\"", number)
+            }
+            BatchMode::Explain | BatchMode::Review if job.file.is_none() => {
+                anyhow::bail!("Line {}: {:?} needs a \"file\"", number, job.mode)
+            }
+            _ => {}
+        }
+        job.id.get_or_insert_with(|| number.to_string());
+        jobs.push(job);
+    }
+    Ok(jobs)
+}
+
+/// Run `jobs` through `process` with at most `concurrency` at a time,
+/// writing each result to `out` in input order as soon as it is known;
+/// `on_result` sees every result as it is written
+pub async fn run<F, Fut, W>(
+    jobs: Vec<BatchJob>,
+    concurrency: usize,
+    out: &mut W,
+    process: F,
+    mut on_result: impl FnMut(&BatchResult),
+) -> Result<BatchSummary>
+where
+    F: Fn(BatchJob) -> Fut,
+    Fut: Future<Output = Result<String>>,
+    W: Write,
+{
+    let mut results = stream::iter(jobs.into_iter().map(|job| {
+        let started = Instant::now();
+        let outcome = process(job.clone());
+        async move { BatchResult::new(job, outcome.await, started.elapsed()) }
+    }))
+    .buffered(concurrency.max(1));
+
+    let mut summary = BatchSummary::default();
+    while let Some(result) = results.next().await {
+        if result.ok {
+            summary.succeeded += 1;
+        } else {
+            summary.failed += 1;
+        }
+        writeln!(out, "{}", serde_json::to_string(&result)?)?;
+        out.flush()?;
+        on_result(&result);
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_runs_jobs_in_input_order() {
+        let jobs = parse_jobs(
+            "# bulk review\n\
+             what is a monad?\n\
+             \n\
+             {\"id\": \"lib\", \"mode\": \"review\", \"file\": \"src/lib.rs\"}\n\
+             {\"mode\": \"explain\", \"file\": \"missing.rs\"}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            jobs.iter()
+                .map(|job| (job.id.as_deref().unwrap(), job.mode))
+                .collect::<Vec<_>>(),
+            [
+                ("2", BatchMode::Query),
+                ("lib", BatchMode::Review),
+                ("5", BatchMode::Explain)
+            ]
+        );
+        assert!(parse_jobs("{\"mode\": \"review\"}")
+            .unwrap_err()
+            .to_string()
+            .starts_with("Line 1"));
+
+        let mut out = Vec::new();
+        let mut seen = Vec::new();
+        let summary = run(
+            jobs,
+            3,
+            &mut out,
+            |job| async move {
+                // Later jobs finish first; results still come out in order
+                let delay = match job.mode {
+                    BatchMode::Query => 30,
+                    _ => 0,
+                };
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                match job.file {
+                    Some(file) if file.ends_with("missing.rs") => {
+                        Err(anyhow::anyhow!("cannot read {}", file.display()))
+                    }
+                    _ => Ok(format!("answer to {}", job.id.unwrap())),
+                }
+            },
+            |result| seen.push(result.id.clone()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            summary,
+            BatchSummary {
+                succeeded: 2,
+                failed: 1
+            }
+        );
+        assert_eq!(seen, ["2", "lib", "5"]);
+        let lines: Vec<BatchResult> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0].output.as_deref(), Some("answer to 2"));
+        assert_eq!(lines[2].error.as_deref(), Some("cannot read missing.rs"));
+    }
+}
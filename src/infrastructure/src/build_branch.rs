@@ -0,0 +1,274 @@
+//! Dedicated branches for `bro --build --branch`
+//!
+//! The build runs on `bro/<goal slug>`, created from HEAD, so the branch the
+//! user works on never sees unreviewed changes. When the build ends, whatever
+//! it left uncommitted is committed to the build branch and the starting
+//! branch is checked out again. A pull request for the branch is opened with
+//! `gh` when it is installed, otherwise through the GitHub API with
+//! `GITHUB_TOKEN` (or `GH_TOKEN`).
+
+use crate::git_repo::GitRepo;
+use shared::types::Result;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Prefix of every build branch
+pub const BRANCH_PREFIX: &str = "bro/";
+
+#[derive(Debug, Clone)]
+pub struct BuildBranch {
+    root: PathBuf,
+    /// Branch the build is applied on, e.g. `bro/add-logging`
+    pub name: String,
+    /// Branch checked out before the build; None on a detached HEAD
+    pub base: Option<String>,
+    base_commit: String,
+}
+
+impl BuildBranch {
+    /// Create a branch for `goal` from HEAD in the repository containing
+    /// `path` and check it out; the working tree must have no uncommitted
+    /// changes to tracked files, which would otherwise follow the build
+    pub fn start(path: &Path, goal: &str) -> Result<Self> {
+        let repo = GitRepo::discover(path)
+            .ok_or_else(|| anyhow::anyhow!("--branch needs a git repository"))?;
+        let base_commit = repo
+            .head()
+            .ok_or_else(|| anyhow::anyhow!("--branch needs at least one commit to branch from"))?;
+        let root = repo.root().to_path_buf();
+        let changes = git(&root, &["status", "--porcelain", "--untracked-files=no"])?;
+        if !changes.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "Uncommitted changes in {}; commit or stash them before building on a branch",
+                root.display()
+            ));
+        }
+
+        let slug = branch_slug(goal);
+        let mut name = format!("{}{}", BRANCH_PREFIX, slug);
+        let mut suffix = 2;
+        while repo.resolve(&format!("refs/heads/{}", name)).is_ok() {
+            name = format!("{}{}-{}", BRANCH_PREFIX, slug, suffix);
+            suffix += 1;
+        }
+        let base = repo.branch();
+        git(&root, &["checkout", "-q", "-b", &name])?;
+        Ok(Self {
+            root,
+            name,
+            base,
+            base_commit,
+        })
+    }
+
+    /// Commits on the branch that its starting point does not have
+    pub fn commits(&self) -> Result<usize> {
+        let range = format!("{}..{}", self.base_commit, self.name);
+        Ok(git(&self.root, &["rev-list", "--count", &range])?
+            .trim()
+            .parse()?)
+    }
+
+    /// Commit what the build left behind, check out the starting branch and
+    /// return the number of commits on the build branch; a branch without
+    /// commits is deleted
+    pub fn finish(&self, message: &str) -> Result<usize> {
+        // bro's own state under .bro/ stays out of the build's commits
+        git(&self.root, &["add", "-A", "--", ".", ":!.bro"])?;
+        let staged = git(&self.root, &["diff", "--cached", "--name-only"])?;
+        if !staged.trim().is_empty() {
+            git(&self.root, &["commit", "-q", "-m", message])?;
+        }
+
+        let back_to = self.base.as_deref().unwrap_or(&self.base_commit);
+        git(&self.root, &["checkout", "-q", back_to])?;
+        let commits = self.commits()?;
+        if commits == 0 {
+            git(&self.root, &["branch", "-q", "-D", &self.name])?;
+        }
+        Ok(commits)
+    }
+
+    /// Push the branch to `origin` and open a pull request against the
+    /// starting branch; returns its URL
+    pub async fn open_pull_request(&self, title: &str, body: &str) -> Result<String> {
+        let base = self.base.as_deref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "A pull request needs a base branch, but the build started on a detached HEAD"
+            )
+        })?;
+        git(&self.root, &["push", "-q", "-u", "origin", &self.name])?;
+
+        if Command::new("gh").arg("--version").output().is_ok() {
+            return self.gh_pull_request(base, title, body);
+        }
+        let token = std::env::var("GITHUB_TOKEN")
+            .or_else(|_| std::env::var("GH_TOKEN"))
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Opening a pull request needs the gh CLI or GITHUB_TOKEN; the branch {} was pushed",
+                    self.name
+                )
+            })?;
+        let remote = git(&self.root, &["remote", "get-url", "origin"])?;
+        let (owner, repo) = github_repo(remote.trim()).ok_or_else(|| {
+            anyhow::anyhow!("origin ({}) is not a GitHub repository", remote.trim())
+        })?;
+
+        let response = reqwest::Client::new()
+            .post(format!(
+                "https://api.github.com/repos/{}/{}/pulls",
+                owner, repo
+            ))
+            .bearer_auth(token)
+            .header(reqwest::header::USER_AGENT, "bro")
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .json(&serde_json::json!({
+                "title": title,
+                "head": self.name,
+                "base": base,
+                "body": body,
+            }))
+            .send()
+            .await?;
+        let status = response.status();
+        let created: serde_json::Value = response.json().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "GitHub refused the pull request ({}): {}",
+                status,
+                created["message"].as_str().unwrap_or("no details")
+            ));
+        }
+        created["html_url"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("GitHub's response has no pull request URL"))
+    }
+
+    fn gh_pull_request(&self, base: &str, title: &str, body: &str) -> Result<String> {
+        let mut child = Command::new("gh")
+            .current_dir(&self.root)
+            .args(["pr", "create", "--base", base, "--head", &self.name])
+            .args(["--title", title, "--body-file", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(body.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "gh pr create failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// Branch-safe name for a goal, e.g. `add-retry-logic`
+pub fn branch_slug(goal: &str) -> String {
+    let slug = goal
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    match slug.char_indices().nth(40) {
+        Some((end, _)) => slug[..end].trim_end_matches('-').to_string(),
+        None if slug.is_empty() => "build".to_string(),
+        None => slug,
+    }
+}
+
+/// Owner and name of a GitHub repository from an `origin` URL
+fn github_repo(remote: &str) -> Option<(String, String)> {
+    let path = remote
+        .strip_prefix("git@github.com:")
+        .or_else(|| remote.strip_prefix("ssh://git@github.com/"))
+        .or_else(|| remote.strip_prefix("https://github.com/"))
+        .or_else(|| remote.strip_prefix("http://github.com/"))?;
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (owner, repo) = path.split_once('/')?;
+    if owner.is_empty() || repo.is_empty() || repo.contains('/') {
+        return None;
+    }
+    Some((owner.to_string(), repo.to_string()))
+}
+
+fn git(root: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_runs_on_its_own_branch() {
+        let dir = std::env::temp_dir().join(format!("bro-branch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for args in [
+            &["init", "-q", "-b", "main"][..],
+            &["config", "user.name", "t"],
+            &["config", "user.email", "t@t"],
+        ] {
+            git(&dir, args).unwrap();
+        }
+        std::fs::write(dir.join("lib.rs"), "fn one() {}\n").unwrap();
+        git(&dir, &["add", "."]).unwrap();
+        git(&dir, &["commit", "-q", "-m", "first"]).unwrap();
+
+        let branch = BuildBranch::start(&dir, "Add retry logic!").unwrap();
+        assert_eq!(branch.name, "bro/add-retry-logic");
+        assert_eq!(branch.base.as_deref(), Some("main"));
+        std::fs::write(dir.join("lib.rs"), "fn two() {}\n").unwrap();
+        std::fs::create_dir_all(dir.join(".bro")).unwrap();
+        std::fs::write(dir.join(".bro/state"), "").unwrap();
+        assert_eq!(branch.finish("build: retry logic").unwrap(), 1);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("lib.rs")).unwrap(),
+            "fn one() {}\n"
+        );
+        let files = git(&dir, &["ls-tree", "-r", "--name-only", &branch.name]).unwrap();
+        assert_eq!(files.trim(), "lib.rs");
+
+        // The name is taken now; a build that commits nothing leaves no branch
+        let again = BuildBranch::start(&dir, "add retry logic").unwrap();
+        assert_eq!(again.name, "bro/add-retry-logic-2");
+        assert_eq!(again.finish("nothing").unwrap(), 0);
+        assert!(git(&dir, &["rev-parse", "--verify", "-q", &again.name]).is_err());
+
+        std::fs::write(dir.join("lib.rs"), "dirty").unwrap();
+        assert!(BuildBranch::start(&dir, "x").is_err());
+
+        assert_eq!(
+            github_repo("git@github.com:rendivs925/bro.git"),
+            Some(("rendivs925".to_string(), "bro".to_string()))
+        );
+        assert_eq!(
+            github_repo("https://github.com/rendivs925/bro"),
+            Some(("rendivs925".to_string(), "bro".to_string()))
+        );
+        assert_eq!(github_repo("https://gitlab.com/a/b.git"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+//! Self-report of what this installation can do
+//!
+//! Models readily plan around tools, flags and operations that do not exist
+//! here. `Capabilities::gather` describes the tools, modes, policies and
+//! limits that are actually available: as JSON for `bro --capabilities`, and
+//! as a compact block injected into agent prompts to ground their plans.
+
+use crate::capability_registry::{self, Availability, CapabilityRegistry};
+use crate::config::Config;
+use crate::policy_engine::PolicyEngine;
+use crate::tools::create_safe_tools;
+use serde::Serialize;
+
+/// Modes selectable on the command line, with what each is for
+const MODES: &[(&str, &str)] = &[
+    (
+        "(default)",
+        "turn a request into one shell command, confirmed before running",
+    ),
+    ("--chat", "interactive conversation"),
+    ("--run", "multi-step agent using the tools below"),
+    (
+        "--build",
+        "plan code changes, review them, then apply with backups",
+    ),
+    ("--plan", "produce a plan without executing it"),
+    ("--explain", "explain a file, command or error"),
+    ("--rag", "answer questions from the indexed codebase"),
+    ("--test", "run the project's tests and analyse failures"),
+    ("--voice", "voice input"),
+    ("--vision", "questions about screenshots"),
+    ("--tui", "full-screen interface"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub version: String,
+    pub os: String,
+    pub shell: String,
+    pub tools: Vec<NamedCapability>,
+    pub modes: Vec<NamedCapability>,
+    pub policies: Vec<NamedCapability>,
+    /// Commands provided by loaded plugins
+    pub plugins: Vec<String>,
+    pub limits: Limits,
+    pub rag: RagCapabilities,
+    /// Services and devices probed in this run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<CapabilityRegistry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NamedCapability {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Limits {
+    pub max_agent_iterations: u32,
+    pub max_tools_per_iteration: u32,
+    pub max_execution_time_seconds: u64,
+    pub max_file_size_bytes: u64,
+    pub max_files_in_context: usize,
+    pub sandbox_enabled: bool,
+    /// Domains network tools may reach
+    pub allowed_domains: Vec<String>,
+    pub max_session_tokens: Option<u64>,
+    pub max_daily_tokens: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RagCapabilities {
+    pub embedding_model: String,
+    pub git_aware: bool,
+    /// Linked repositories searchable with `--context`
+    pub repositories: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn gather(config: &Config) -> Self {
+        let named = |name: &str, description: &str| NamedCapability {
+            name: name.to_string(),
+            description: description.to_string(),
+        };
+        let security = &config.security;
+        let plugins = config
+            .plugin_manager
+            .as_ref()
+            .and_then(|manager| manager.try_read().ok().map(|m| m.list_plugins()))
+            .unwrap_or_default();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            shell: shared::platform::Shell::detect().to_string(),
+            tools: create_safe_tools()
+                .iter()
+                .map(|tool| named(tool.name(), tool.description()))
+                .chain(
+                    crate::mcp_client::mounted()
+                        .iter()
+                        .map(|tool| named(&tool.qualified_name(), &tool.info.description)),
+                )
+                .collect(),
+            modes: MODES
+                .iter()
+                .map(|(flag, description)| named(flag, description))
+                .collect(),
+            policies: PolicyEngine::new()
+                .describe_policies()
+                .iter()
+                .map(|(name, description)| named(name, description))
+                .collect(),
+            plugins,
+            limits: Limits {
+                max_agent_iterations: security.agent_execution.max_iterations,
+                max_tools_per_iteration: security.agent_execution.max_tools_per_iteration,
+                max_execution_time_seconds: security.agent_execution.max_execution_time_seconds,
+                max_file_size_bytes: config.context.max_file_size_bytes,
+                max_files_in_context: config.context.max_files_in_context,
+                sandbox_enabled: security.resource_limits.sandbox_enabled,
+                allowed_domains: security.network_security.allowed_domains.clone(),
+                max_session_tokens: security.agent_execution.max_session_tokens,
+                max_daily_tokens: security.agent_execution.max_daily_tokens,
+            },
+            rag: RagCapabilities {
+                embedding_model: config
+                    .embedding_model
+                    .clone()
+                    .unwrap_or_else(|| config.ollama_model.clone()),
+                git_aware: config.rag_git_aware,
+                repositories: config.rag_repositories.clone(),
+            },
+            runtime: capability_registry::probed(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Compact description for prompts, ending with an instruction to stay
+    /// within it
+    pub fn prompt_block(&self) -> String {
+        let list = |items: &[NamedCapability]| {
+            items
+                .iter()
+                .map(|item| format!("- {}: {}", item.name, item.description))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let limits = &self.limits;
+        let mut block = format!(
+            "CAPABILITIES (bro {} on {}, shell {}):\nTools:\n{}\nModes:\n{}\nPolicies:\n{}\n",
+            self.version,
+            self.os,
+            self.shell,
+            list(&self.tools),
+            list(&self.modes),
+            list(&self.policies)
+        );
+        if !self.plugins.is_empty() {
+            block.push_str(&format!("Plugins: {}\n", self.plugins.join(", ")));
+        }
+        block.push_str(&format!(
+            "Limits: {} iterations of up to {} tools, {}s per run, files up to {} KB, sandbox {}, network only to {}\n",
+            limits.max_agent_iterations,
+            limits.max_tools_per_iteration,
+            limits.max_execution_time_seconds,
+            limits.max_file_size_bytes / 1024,
+            if limits.sandbox_enabled { "on" } else { "off" },
+            limits.allowed_domains.join(", ")
+        ));
+        let unavailable = self
+            .runtime
+            .iter()
+            .flat_map(|registry| registry.iter())
+            .filter_map(|(capability, availability)| match availability {
+                Availability::Unavailable { reason } => {
+                    Some(format!("{} ({})", capability, reason))
+                }
+                Availability::Available { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        if !unavailable.is_empty() {
+            block.push_str(&format!("Unavailable: {}\n", unavailable.join(", ")));
+        }
+        block.push_str(
+            "Only plan tools, modes and operations listed here; say so if the task needs something else.",
+        );
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_cover_registered_tools() {
+        let capabilities = Capabilities::gather(&Config::load());
+        let registered = crate::tools::ToolRegistry::new().list_tools();
+        assert_eq!(capabilities.tools.len(), registered.len());
+        assert!(capabilities
+            .tools
+            .iter()
+            .all(|tool| registered.contains(&tool.name)));
+        assert!(!capabilities.policies.is_empty());
+
+        let json: serde_json::Value = serde_json::from_str(&capabilities.to_json()).unwrap();
+        assert_eq!(json["tools"].as_array().unwrap().len(), registered.len());
+
+        let block = capabilities.prompt_block();
+        assert!(block.contains("- file_read:"));
+        assert!(block.contains("- --build:"));
+        assert!(block.ends_with("needs something else."));
+    }
+}
@@ -0,0 +1,526 @@
+//! What is working on this machine right now, and what each mode needs
+//!
+//! The registry probes the services and devices bro depends on (Ollama,
+//! Qdrant, Docker, audio input, a display, git) when a mode starts. Each mode
+//! lists the capabilities it cannot run without and those it merely runs
+//! better with. A missing required capability fails the mode with one
+//! [`FeatureUnavailable`] message naming the cause and the fix, and a missing
+//! optional one is noted with what the mode does instead.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::process::Stdio;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// Longest a single probe may take
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Registry of the last probe, for prompts and `--capabilities`
+static PROBED: RwLock<Option<CapabilityRegistry>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Ollama,
+    Qdrant,
+    Docker,
+    Audio,
+    Display,
+    Git,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 6] = [
+        Capability::Ollama,
+        Capability::Qdrant,
+        Capability::Docker,
+        Capability::Audio,
+        Capability::Display,
+        Capability::Git,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Ollama => "Ollama",
+            Capability::Qdrant => "Qdrant",
+            Capability::Docker => "Docker",
+            Capability::Audio => "audio input",
+            Capability::Display => "a display",
+            Capability::Git => "git",
+        }
+    }
+
+    /// How to make the capability available
+    pub fn how_to_enable(self) -> &'static str {
+        match self {
+            Capability::Ollama => {
+                "start it with `ollama serve` (install from https://ollama.com) or point OLLAMA_BASE_URL at a running server"
+            }
+            Capability::Qdrant => {
+                "start it with `docker run -p 6333:6333 -p 6334:6334 qdrant/qdrant` or point QDRANT_URL at a running server"
+            }
+            Capability::Docker => "install Docker and start the daemon, e.g. `sudo systemctl start docker`",
+            Capability::Audio => {
+                "connect a microphone and make sure your user may open the sound devices"
+            }
+            Capability::Display => {
+                "run bro inside a graphical session, where DISPLAY or WAYLAND_DISPLAY is set"
+            }
+            Capability::Git => "install git from https://git-scm.com",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Availability {
+    Available { detail: String },
+    Unavailable { reason: String },
+}
+
+impl Availability {
+    pub fn is_available(&self) -> bool {
+        matches!(self, Availability::Available { .. })
+    }
+}
+
+/// Capabilities a mode needs, and what it does without the optional ones
+pub struct ModeRequirements {
+    pub mode: &'static str,
+    pub required: &'static [Capability],
+    pub optional: &'static [(Capability, &'static str)],
+}
+
+/// The degradation matrix: every mode that depends on more than the terminal
+pub const MODE_REQUIREMENTS: &[ModeRequirements] = &[
+    ModeRequirements {
+        mode: "query",
+        required: &[Capability::Ollama],
+        optional: &[],
+    },
+    ModeRequirements {
+        mode: "chat",
+        required: &[Capability::Ollama],
+        optional: &[],
+    },
+    ModeRequirements {
+        mode: "run",
+        required: &[Capability::Ollama],
+        optional: &[],
+    },
+    ModeRequirements {
+        mode: "ai_agent",
+        required: &[Capability::Ollama],
+        optional: &[],
+    },
+    ModeRequirements {
+        mode: "plan",
+        required: &[Capability::Ollama],
+        optional: &[],
+    },
+    ModeRequirements {
+        mode: "build",
+        required: &[Capability::Ollama],
+        optional: &[(Capability::Git, "applied steps are not committed")],
+    },
+    ModeRequirements {
+        mode: "build --branch",
+        required: &[Capability::Ollama, Capability::Git],
+        optional: &[],
+    },
+    ModeRequirements {
+        mode: "explain",
+        required: &[Capability::Ollama],
+        optional: &[],
+    },
+    ModeRequirements {
+        mode: "stream",
+        required: &[Capability::Ollama],
+        optional: &[],
+    },
+    ModeRequirements {
+        mode: "context",
+        required: &[Capability::Ollama],
+        optional: &[],
+    },
+    ModeRequirements {
+        mode: "rag",
+        required: &[Capability::Ollama],
+        optional: &[
+            (
+                Capability::Qdrant,
+                "the index is kept in SQLite, which is slower on large projects",
+            ),
+            (Capability::Git, "results are not ranked by recent changes"),
+        ],
+    },
+    ModeRequirements {
+        mode: "test",
+        required: &[],
+        optional: &[(Capability::Ollama, "failures are listed without analysis")],
+    },
+    ModeRequirements {
+        mode: "voice",
+        required: &[Capability::Audio, Capability::Ollama],
+        optional: &[(
+            Capability::Display,
+            "screen sharing and remote control commands do nothing",
+        )],
+    },
+    ModeRequirements {
+        mode: "vision",
+        required: &[],
+        optional: &[(
+            Capability::Docker,
+            "a local Playwright, geckodriver or chromedriver drives the browser instead",
+        )],
+    },
+];
+
+/// A mode was started without a capability it needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureUnavailable {
+    pub mode: String,
+    pub capability: Capability,
+    pub reason: String,
+}
+
+impl fmt::Display for FeatureUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is unavailable because {}. To enable it, {}.",
+            self.mode,
+            self.reason,
+            self.capability.how_to_enable()
+        )
+    }
+}
+
+impl std::error::Error for FeatureUnavailable {}
+
+/// Where the capabilities are looked for
+#[derive(Debug, Clone)]
+pub struct ProbeTargets {
+    pub ollama_url: String,
+    pub qdrant_url: String,
+}
+
+impl ProbeTargets {
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self {
+            ollama_url: config.ollama_base_url.clone(),
+            qdrant_url: qdrant_url(),
+        }
+    }
+}
+
+/// Qdrant REST endpoint, overridable with `QDRANT_URL`
+pub fn qdrant_url() -> String {
+    std::env::var("QDRANT_URL").unwrap_or_else(|_| "http://localhost:6333".to_string())
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(transparent)]
+pub struct CapabilityRegistry {
+    capabilities: BTreeMap<Capability, Availability>,
+}
+
+impl CapabilityRegistry {
+    /// Probe `capabilities` concurrently
+    pub async fn probe(targets: &ProbeTargets, capabilities: &[Capability]) -> Self {
+        let probes = capabilities
+            .iter()
+            .map(|&capability| async move { (capability, probe(targets, capability).await) });
+        Self {
+            capabilities: futures::future::join_all(probes)
+                .await
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Registry with known results, for tests and callers that probed already
+    pub fn with(mut self, capability: Capability, availability: Availability) -> Self {
+        self.capabilities.insert(capability, availability);
+        self
+    }
+
+    pub fn get(&self, capability: Capability) -> Option<&Availability> {
+        self.capabilities.get(&capability)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Capability, &Availability)> {
+        self.capabilities
+            .iter()
+            .map(|(capability, availability)| (*capability, availability))
+    }
+
+    /// Check `mode` against the matrix: the first missing required
+    /// capability is an error, missing optional ones come back as notes on
+    /// what the mode does without them. Capabilities that were not probed
+    /// count as available.
+    pub fn check(&self, mode: &str) -> Result<Vec<String>, FeatureUnavailable> {
+        let Some(requirements) = requirements_for(mode) else {
+            return Ok(Vec::new());
+        };
+        for &capability in requirements.required {
+            if let Some(Availability::Unavailable { reason }) = self.get(capability) {
+                return Err(FeatureUnavailable {
+                    mode: label(mode),
+                    capability,
+                    reason: reason.clone(),
+                });
+            }
+        }
+        Ok(requirements
+            .optional
+            .iter()
+            .filter_map(|(capability, effect)| match self.get(*capability) {
+                Some(Availability::Unavailable { reason }) => Some(format!(
+                    "Running without {} ({}): {}. To enable it, {}.",
+                    capability,
+                    reason,
+                    effect,
+                    capability.how_to_enable()
+                )),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+/// Requirements of `mode`, e.g. `rag` or `build --branch`
+pub fn requirements_for(mode: &str) -> Option<&'static ModeRequirements> {
+    MODE_REQUIREMENTS
+        .iter()
+        .find(|requirements| requirements.mode == mode)
+}
+
+/// How `mode` is named in messages
+fn label(mode: &str) -> String {
+    match mode {
+        "query" => "Answering questions".to_string(),
+        _ => format!("bro --{}", mode.replace('_', "-")),
+    }
+}
+
+/// Every capability `mode` uses, required or optional
+pub fn used_by(mode: &str) -> Vec<Capability> {
+    requirements_for(mode)
+        .map(|requirements| {
+            requirements
+                .required
+                .iter()
+                .chain(
+                    requirements
+                        .optional
+                        .iter()
+                        .map(|(capability, _)| capability),
+                )
+                .copied()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Remember `registry` as the state of this run
+pub fn install(registry: CapabilityRegistry) {
+    let mut probed = PROBED.write().unwrap();
+    match probed.as_mut() {
+        Some(existing) => existing.capabilities.extend(registry.capabilities),
+        None => *probed = Some(registry),
+    }
+}
+
+/// Capabilities probed so far in this run
+pub fn probed() -> Option<CapabilityRegistry> {
+    PROBED.read().unwrap().clone()
+}
+
+async fn probe(targets: &ProbeTargets, capability: Capability) -> Availability {
+    match capability {
+        Capability::Ollama => {
+            if std::env::var_os("VIBE_MOCK_FIXTURES").is_some_and(|dir| !dir.is_empty()) {
+                return Availability::Available {
+                    detail: "replies come from VIBE_MOCK_FIXTURES".to_string(),
+                };
+            }
+            probe_http("Ollama", &format!("{}/api/tags", targets.ollama_url)).await
+        }
+        Capability::Qdrant => probe_http("Qdrant", &targets.qdrant_url).await,
+        Capability::Docker => {
+            match command_output("docker", &["info", "--format", "{{.ServerVersion}}"]).await {
+                Ok((true, version)) => Availability::Available {
+                    detail: format!("Docker {}", version),
+                },
+                Ok((false, _)) => Availability::Unavailable {
+                    reason: "the Docker daemon is not running or not reachable".to_string(),
+                },
+                Err(reason) => Availability::Unavailable { reason },
+            }
+        }
+        Capability::Git => match command_output("git", &["--version"]).await {
+            Ok((true, version)) => Availability::Available { detail: version },
+            Ok((false, _)) => Availability::Unavailable {
+                reason: "`git --version` failed".to_string(),
+            },
+            Err(reason) => Availability::Unavailable { reason },
+        },
+        Capability::Display => display(),
+        Capability::Audio => {
+            let devices = tokio::task::spawn_blocking(
+                crate::adapters::microphone::MicrophoneCapture::list_devices,
+            )
+            .await;
+            match devices {
+                Ok(Ok(devices)) if !devices.is_empty() => Availability::Available {
+                    detail: format!("{} input device(s)", devices.len()),
+                },
+                Ok(Ok(_)) => Availability::Unavailable {
+                    reason: "no audio input device was found".to_string(),
+                },
+                Ok(Err(e)) => Availability::Unavailable {
+                    reason: e.to_string(),
+                },
+                Err(e) => Availability::Unavailable {
+                    reason: format!("listing audio devices failed: {}", e),
+                },
+            }
+        }
+    }
+}
+
+async fn probe_http(name: &str, url: &str) -> Availability {
+    let client = match reqwest::Client::builder().timeout(PROBE_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            return Availability::Unavailable {
+                reason: e.to_string(),
+            }
+        }
+    };
+    match client.get(url).send().await {
+        Ok(response) if response.status().is_success() => Availability::Available {
+            detail: url.to_string(),
+        },
+        Ok(response) => Availability::Unavailable {
+            reason: format!("{} at {} answered {}", name, url, response.status()),
+        },
+        Err(_) => Availability::Unavailable {
+            reason: format!("{} is not running at {}", name, url),
+        },
+    }
+}
+
+/// Whether `program` succeeded, and the first line of its output; Err when it
+/// could not be run at all
+async fn command_output(program: &str, args: &[&str]) -> Result<(bool, String), String> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    match tokio::time::timeout(PROBE_TIMEOUT, output).await {
+        Ok(Ok(output)) => Ok((
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        )),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(format!("{} is not installed", program))
+        }
+        Ok(Err(e)) => Err(format!("{} could not be run: {}", program, e)),
+        Err(_) => Err(format!(
+            "{} did not answer within {}s",
+            program,
+            PROBE_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+fn display() -> Availability {
+    if !cfg!(target_os = "linux") {
+        return Availability::Available {
+            detail: std::env::consts::OS.to_string(),
+        };
+    }
+    let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+    if set("WAYLAND_DISPLAY") {
+        Availability::Available {
+            detail: "Wayland".to_string(),
+        }
+    } else if set("DISPLAY") {
+        Availability::Available {
+            detail: "X11".to_string(),
+        }
+    } else {
+        Availability::Unavailable {
+            reason: "no graphical session was found".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_modes_fail_or_degrade_by_the_matrix() {
+        let down = |reason: &str| Availability::Unavailable {
+            reason: reason.to_string(),
+        };
+        let registry = CapabilityRegistry::default()
+            .with(
+                Capability::Ollama,
+                Availability::Available {
+                    detail: "http://localhost:11434".to_string(),
+                },
+            )
+            .with(
+                Capability::Qdrant,
+                down("Qdrant is not running at http://localhost:6333"),
+            )
+            .with(Capability::Git, down("git is not installed"));
+
+        let notes = registry.check("rag").unwrap();
+        assert_eq!(notes.len(), 2);
+        assert!(notes[0].starts_with("Running without Qdrant (Qdrant is not running"));
+        let error = registry.check("build --branch").unwrap_err();
+        assert_eq!(error.capability, Capability::Git);
+        assert_eq!(
+            error.to_string(),
+            "bro --build --branch is unavailable because git is not installed. To enable it, install git from https://git-scm.com."
+        );
+        // Not probed counts as available; modes outside the matrix need nothing
+        assert!(registry.check("voice").is_ok());
+        assert!(registry.check("todo").unwrap().is_empty());
+        assert_eq!(
+            used_by("rag"),
+            vec![Capability::Ollama, Capability::Qdrant, Capability::Git]
+        );
+
+        let targets = ProbeTargets {
+            ollama_url: "http://127.0.0.1:9".to_string(),
+            qdrant_url: "http://127.0.0.1:9".to_string(),
+        };
+        let probed = CapabilityRegistry::probe(&targets, &[Capability::Qdrant]).await;
+        assert_eq!(
+            probed.get(Capability::Qdrant),
+            Some(&down("Qdrant is not running at http://127.0.0.1:9"))
+        );
+        assert!(probed.get(Capability::Docker).is_none());
+    }
+}
@@ -0,0 +1,333 @@
+//! Layout of a Cargo workspace, for planning Rust changes
+//!
+//! Models asked to add Rust code tend to drop files at the repository root,
+//! where no crate compiles them. [`CargoWorkspace::detect`] reads the root
+//! manifest and its `members` so the planner can tell the model which crates
+//! exist, move stray files into the crate they belong to, declare new modules
+//! in their parent, and validate changes with `cargo check -p <crate>`.
+
+use crate::test_gate::TestCommand;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoCrate {
+    /// Package name, as passed to `cargo -p`
+    pub name: String,
+    /// Directory of the crate's manifest, relative to the workspace root
+    pub dir: PathBuf,
+    /// Whether the crate root is `src/lib.rs` rather than `src/main.rs`
+    pub lib: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoWorkspace {
+    pub root: PathBuf,
+    pub crates: Vec<CargoCrate>,
+}
+
+impl CargoWorkspace {
+    /// Workspace or single package whose manifest is at `root`
+    pub fn detect(root: &Path) -> Option<Self> {
+        let manifest = read_manifest(&root.join("Cargo.toml"))?;
+        let mut dirs = Vec::new();
+        if manifest.get("package").is_some() {
+            dirs.push(PathBuf::new());
+        }
+        let members = manifest
+            .get("workspace")
+            .and_then(|workspace| workspace.get("members"))
+            .and_then(|members| members.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|member| member.as_str());
+        for member in members {
+            dirs.extend(expand_member(root, member));
+        }
+
+        let crates: Vec<CargoCrate> = dirs
+            .into_iter()
+            .filter_map(|dir| {
+                let manifest = read_manifest(&root.join(&dir).join("Cargo.toml"))?;
+                let name = manifest.get("package")?.get("name")?.as_str()?.to_string();
+                let lib = root.join(&dir).join("src/lib.rs").is_file()
+                    || !root.join(&dir).join("src/main.rs").is_file();
+                Some(CargoCrate { name, dir, lib })
+            })
+            .collect();
+        (!crates.is_empty()).then(|| Self {
+            root: root.to_path_buf(),
+            crates,
+        })
+    }
+
+    /// Crate whose directory holds `path`, the innermost when crates nest
+    pub fn crate_for(&self, path: &Path) -> Option<&CargoCrate> {
+        let path = self.relative(path);
+        self.crates
+            .iter()
+            .filter(|krate| path.starts_with(&krate.dir))
+            .max_by_key(|krate| krate.dir.components().count())
+    }
+
+    /// Where a Rust file planned at `path` belongs: unchanged inside a
+    /// crate's `src`, otherwise moved into the `src` of the crate it names
+    /// or of the only crate. None when it is not a Rust file or the crate
+    /// cannot be told.
+    pub fn place(&self, path: &Path) -> Option<PathBuf> {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            return None;
+        }
+        let relative = self.relative(path);
+        if let Some(krate) = self.crate_for(&relative) {
+            let inside = relative.strip_prefix(&krate.dir).ok()?;
+            if inside.starts_with("src")
+                || ["tests", "benches", "examples", "build.rs"]
+                    .iter()
+                    .any(|dir| inside.starts_with(dir))
+            {
+                return None;
+            }
+            // Only the root package matches a stray file at the top
+            if !krate.dir.as_os_str().is_empty() || self.crates.len() == 1 {
+                return Some(krate.dir.join("src").join(inside));
+            }
+        }
+
+        let mut components = relative.components();
+        let first = components.next()?.as_os_str().to_str()?.to_string();
+        let rest = components.as_path();
+        let named = self.crates.iter().find(|krate| {
+            krate.name == first
+                || krate.name.replace('-', "_") == first
+                || krate.dir.file_name().and_then(|n| n.to_str()) == Some(first.as_str())
+        });
+        let (krate, inside) = match named {
+            Some(krate) if !rest.as_os_str().is_empty() => (krate, rest),
+            _ => match self.crates.as_slice() {
+                [only] => (only, relative.as_path()),
+                _ => return None,
+            },
+        };
+        let inside = inside.strip_prefix("src").unwrap_or(inside);
+        Some(krate.dir.join("src").join(inside))
+    }
+
+    /// The parent module file of a new Rust file and the line declaring it,
+    /// e.g. `src/lib.rs` and `pub mod parser;` for `src/parser.rs`
+    pub fn module_declaration(&self, path: &Path) -> Option<(PathBuf, String)> {
+        let krate = self.crate_for(path)?;
+        let relative = self.relative(path);
+        let inside = relative.strip_prefix(krate.dir.join("src")).ok()?;
+        let stem = inside.file_stem()?.to_str()?;
+        if matches!(stem, "lib" | "main" | "mod") || inside.starts_with("bin") {
+            return None;
+        }
+        let parent_dir = inside.parent().unwrap_or(Path::new(""));
+        let src = krate.dir.join("src");
+        let parent = if parent_dir.as_os_str().is_empty() {
+            src.join(if krate.lib { "lib.rs" } else { "main.rs" })
+        } else {
+            let as_file = src.join(parent_dir).with_extension("rs");
+            if self.root.join(&as_file).is_file() {
+                as_file
+            } else {
+                src.join(parent_dir).join("mod.rs")
+            }
+        };
+        let visibility = if krate.lib { "pub " } else { "" };
+        Some((parent, format!("{}mod {};", visibility, stem)))
+    }
+
+    /// `cargo check` limited to `krate`
+    pub fn check_command(&self, krate: &CargoCrate) -> TestCommand {
+        TestCommand::new(
+            "cargo",
+            &[
+                "check",
+                "--quiet",
+                "--message-format",
+                "short",
+                "-p",
+                &krate.name,
+            ],
+        )
+    }
+
+    /// Description of the crates for planning prompts
+    pub fn prompt_block(&self) -> String {
+        let crates = self
+            .crates
+            .iter()
+            .map(|krate| {
+                let dir = if krate.dir.as_os_str().is_empty() {
+                    ".".to_string()
+                } else {
+                    krate.dir.display().to_string()
+                };
+                format!(
+                    "- {} in {}/ ({})",
+                    krate.name,
+                    dir,
+                    if krate.lib { "library" } else { "binary" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            "CARGO WORKSPACE:\n{}\nPut Rust files in the src/ directory of the crate they belong to, never at the workspace root. Declare new modules with `mod` in their parent module and add the `use` statements the code needs, including for items from other crates of the workspace.",
+            crates
+        )
+    }
+
+    /// `path` relative to the workspace root
+    pub fn relative(&self, path: &Path) -> PathBuf {
+        let path = path.strip_prefix(&self.root).unwrap_or(path);
+        path.components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .collect()
+    }
+}
+
+/// `content` with `declaration` (e.g. `pub mod parser;`) added after its
+/// other module declarations, or after its inner doc comments and
+/// attributes; None when the module is declared already
+pub fn with_module_declaration(content: &str, declaration: &str) -> Option<String> {
+    let name = declaration.trim_end_matches(';').rsplit(' ').next()?;
+    let is_declaration = |line: &str| {
+        let line = line.trim();
+        line.strip_suffix(';')
+            .and_then(|line| line.rsplit_once("mod "))
+            .is_some_and(|(visibility, _)| {
+                visibility.is_empty() || visibility.trim_end().starts_with("pub")
+            })
+    };
+    if content
+        .lines()
+        .any(|line| is_declaration(line) && line.trim().ends_with(&format!("mod {};", name)))
+    {
+        return None;
+    }
+    let lines: Vec<&str> = content.lines().collect();
+    let after = match lines.iter().rposition(|line| is_declaration(line)) {
+        Some(last) => last + 1,
+        None => lines
+            .iter()
+            .take_while(|line| {
+                let line = line.trim();
+                line.starts_with("//!") || line.starts_with("#![") || line.is_empty()
+            })
+            .count(),
+    };
+    let mut updated: Vec<&str> = lines[..after].to_vec();
+    updated.push(declaration);
+    updated.extend_from_slice(&lines[after..]);
+    Some(updated.join("\n") + "\n")
+}
+
+fn read_manifest(path: &Path) -> Option<toml::Value> {
+    toml::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+/// Member directories matching `member`, which may end in `/*`
+fn expand_member(root: &Path, member: &str) -> Vec<PathBuf> {
+    let Some(parent) = member.strip_suffix("/*") else {
+        return vec![PathBuf::from(member)];
+    };
+    let Ok(entries) = std::fs::read_dir(root.join(parent)) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| entry.path().join("Cargo.toml").is_file())
+        .map(|entry| Path::new(parent).join(entry.file_name()))
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_places_files_in_workspace_crates() {
+        let root = std::env::temp_dir().join(format!("bro-cargo-ws-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (dir, name, entry) in [
+            ("crates/core", "core", "lib.rs"),
+            ("crates/cli-app", "cli-app", "main.rs"),
+        ] {
+            std::fs::create_dir_all(root.join(dir).join("src")).unwrap();
+            std::fs::write(
+                root.join(dir).join("Cargo.toml"),
+                format!("[package]\nname = \"{}\"\n", name),
+            )
+            .unwrap();
+            std::fs::write(root.join(dir).join("src").join(entry), "").unwrap();
+        }
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\n",
+        )
+        .unwrap();
+
+        let workspace = CargoWorkspace::detect(&root).unwrap();
+        assert_eq!(
+            workspace
+                .crates
+                .iter()
+                .map(|krate| (krate.name.as_str(), krate.lib))
+                .collect::<Vec<_>>(),
+            [("cli-app", false), ("core", true)]
+        );
+        let place = |path: &str| workspace.place(Path::new(path));
+        assert_eq!(place("crates/core/src/parser.rs"), None);
+        assert_eq!(place("README.md"), None);
+        assert_eq!(place("parser.rs"), None);
+        assert_eq!(
+            place("core/parser.rs"),
+            Some(PathBuf::from("crates/core/src/parser.rs"))
+        );
+        assert_eq!(
+            place("crates/cli-app/args.rs"),
+            Some(PathBuf::from("crates/cli-app/src/args.rs"))
+        );
+
+        assert_eq!(
+            workspace.module_declaration(&root.join("crates/core/src/parser.rs")),
+            Some((
+                PathBuf::from("crates/core/src/lib.rs"),
+                "pub mod parser;".to_string()
+            ))
+        );
+        assert_eq!(
+            workspace.module_declaration(Path::new("./crates/cli-app/src/cmd/run.rs")),
+            Some((
+                PathBuf::from("crates/cli-app/src/cmd/mod.rs"),
+                "mod run;".to_string()
+            ))
+        );
+        let krate = workspace.crate_for(Path::new("crates/core/src/parser.rs"));
+        assert_eq!(
+            workspace.check_command(krate.unwrap()).to_string(),
+            "cargo check --quiet --message-format short -p core"
+        );
+        assert!(workspace
+            .prompt_block()
+            .contains("- cli-app in crates/cli-app/ (binary)"));
+        assert_eq!(
+            with_module_declaration("//! Core\n\npub mod ast;\n\nfn f() {}\n", "pub mod parser;")
+                .unwrap(),
+            "//! Core\n\npub mod ast;\npub mod parser;\n\nfn f() {}\n"
+        );
+        assert_eq!(
+            with_module_declaration("use std::io;\n", "mod run;").unwrap(),
+            "mod run;\nuse std::io;\n"
+        );
+        assert_eq!(
+            with_module_declaration("pub(crate) mod run;\n", "mod run;"),
+            None
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+//! Tamper-evident log of executed commands
+//!
+//! Every command run through the sandbox, or directly when the user bypasses
+//! it, is appended to `command_audit.jsonl` in the data directory with the
+//! session and request it came from and its exit status. Each entry carries
+//! the hash of the one before it and a BLAKE3 hash of itself, so editing,
+//! reordering or removing entries (other than the newest) breaks the chain;
+//! [`verify`] finds where.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use shared::platform::{self, Shell};
+use shared::types::Result;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output};
+use std::sync::{Mutex, RwLock};
+
+/// `prev_hash` of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Longest request stored with an entry, in characters
+const MAX_PROMPT_CHARS: usize = 2000;
+
+/// End of the log read to find the last entry
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// Session and request the commands run now belong to
+static CONTEXT: RwLock<AuditContext> = RwLock::new(AuditContext {
+    session: None,
+    prompt: None,
+});
+
+/// Serializes appends within this process; other processes are kept out by a
+/// file lock where the platform has one
+static APPEND: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Default)]
+struct AuditContext {
+    session: Option<String>,
+    prompt: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, counting from 1
+    pub seq: u64,
+    /// RFC 3339, UTC
+    pub timestamp: String,
+    pub session: Option<String>,
+    /// User request that led to the command
+    pub prompt: Option<String>,
+    pub command: String,
+    /// `sandbox`, or `direct` when the sandbox was bypassed
+    pub via: String,
+    /// None when the command was killed by a signal, timed out or did not start
+    pub exit_status: Option<i32>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Hash over every field but `hash` itself
+    fn digest(&self) -> String {
+        let unsigned = AuditEntry {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let bytes = serde_json::to_vec(&unsigned).unwrap_or_default();
+        blake3::hash(&bytes).to_hex().to_string()
+    }
+}
+
+/// Where the chain first breaks
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainBreak {
+    /// Line of the log, counting from 1
+    pub line: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for ChainBreak {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+pub fn log_path() -> PathBuf {
+    match std::env::var_os("VIBE_AUDIT_LOG").filter(|path| !path.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => platform::app_data_dir().join("command_audit.jsonl"),
+    }
+}
+
+/// Attribute commands run from now on to `session` and `prompt`
+pub fn set_context(session: Option<String>, prompt: Option<String>) {
+    *CONTEXT.write().unwrap() = AuditContext { session, prompt };
+}
+
+/// Attribute commands run from now on to `prompt`, in the same session
+pub fn set_prompt(prompt: &str) {
+    CONTEXT.write().unwrap().prompt = Some(prompt.to_string());
+}
+
+/// Session commands are currently attributed to
+pub fn current_session() -> Option<String> {
+    CONTEXT.read().unwrap().session.clone()
+}
+
+/// Append `command` to the log; failures are reported, never fatal to the
+/// command itself
+pub fn record(command: &str, via: &str, status: Option<&ExitStatus>) {
+    if let Err(e) = append(&log_path(), command, via, status.and_then(ExitStatus::code)) {
+        eprintln!("Warning: could not write the command audit log: {}", e);
+    }
+}
+
+/// Run `command` through the platform shell outside the sandbox, and audit it
+pub fn run_direct(command: &str) -> std::io::Result<Output> {
+    let output = Shell::detect().command(command).output();
+    record(command, "direct", output.as_ref().ok().map(|o| &o.status));
+    output
+}
+
+fn append(path: &Path, command: &str, via: &str, exit_status: Option<i32>) -> Result<AuditEntry> {
+    let context = CONTEXT.read().unwrap().clone();
+    let _guard = APPEND.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)?;
+    lock_exclusive(&file);
+
+    let previous = last_entry(&mut file)?;
+    let mut entry = AuditEntry {
+        seq: previous.as_ref().map_or(1, |p| p.seq + 1),
+        timestamp: Utc::now().to_rfc3339(),
+        session: context.session,
+        prompt: context
+            .prompt
+            .map(|prompt| prompt.chars().take(MAX_PROMPT_CHARS).collect()),
+        command: command.to_string(),
+        via: via.to_string(),
+        exit_status,
+        prev_hash: previous.map_or_else(|| GENESIS_HASH.to_string(), |p| p.hash),
+        hash: String::new(),
+    };
+    entry.hash = entry.digest();
+
+    let mut line = serde_json::to_string(&entry)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    file.sync_data()?;
+    Ok(entry)
+}
+
+/// Last complete entry, read from the end of the file
+fn last_entry(file: &mut std::fs::File) -> Result<Option<AuditEntry>> {
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))?;
+    let mut tail = String::new();
+    file.read_to_string(&mut tail)?;
+    let Some(line) = tail.lines().rev().find(|line| !line.trim().is_empty()) else {
+        return Ok(None);
+    };
+    serde_json::from_str(line).map(Some).map_err(|e| {
+        anyhow::anyhow!(
+            "the last audit entry is unreadable ({}); run --audit verify",
+            e
+        )
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn lock_exclusive(file: &std::fs::File) {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: flock only takes the descriptor; the lock ends when it is closed
+    unsafe {
+        libc::flock(file.as_raw_fd(), libc::LOCK_EX);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lock_exclusive(_file: &std::fs::File) {}
+
+/// Every entry of the log at `path`, oldest first; unreadable lines are skipped
+pub fn entries(path: &Path) -> Vec<AuditEntry> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Check the whole chain: entry hashes, links and sequence numbers. Returns
+/// the number of entries when it is intact.
+pub fn verify(path: &Path) -> Result<std::result::Result<usize, ChainBreak>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Ok(0)),
+        Err(e) => return Err(e.into()),
+    };
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut count = 0;
+    for (i, line) in content.lines().enumerate() {
+        let broken = |reason: String| {
+            Ok(Err(ChainBreak {
+                line: i + 1,
+                reason,
+            }))
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: AuditEntry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            Err(e) => return broken(format!("not a valid entry ({})", e)),
+        };
+        if entry.seq != count as u64 + 1 {
+            return broken(format!(
+                "sequence number {} where {} was expected; entries were removed or reordered",
+                entry.seq,
+                count + 1
+            ));
+        }
+        if entry.prev_hash != prev_hash {
+            return broken("does not link to the entry before it".to_string());
+        }
+        if entry.digest() != entry.hash {
+            return broken("contents do not match its hash; the entry was edited".to_string());
+        }
+        prev_hash = entry.hash;
+        count += 1;
+    }
+    Ok(Ok(count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_detects_edits_and_removals() {
+        let path = std::env::temp_dir().join(format!("bro-audit-{}.jsonl", uuid::Uuid::new_v4()));
+        set_context(
+            Some("fix-build".to_string()),
+            Some("list files".to_string()),
+        );
+        let first = append(&path, "ls -la", "sandbox", Some(0)).unwrap();
+        let second = append(&path, "rm -r target", "direct", None).unwrap();
+        append(&path, "cargo build", "sandbox", Some(101)).unwrap();
+
+        assert_eq!(second.seq, 2);
+        assert_eq!(second.prev_hash, first.hash);
+        assert_eq!(first.session.as_deref(), Some("fix-build"));
+        assert_eq!(verify(&path).unwrap(), Ok(3));
+
+        let original = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = original.lines().collect();
+
+        std::fs::write(
+            &path,
+            original.replace("\"exit_status\":101", "\"exit_status\":0"),
+        )
+        .unwrap();
+        assert_eq!(verify(&path).unwrap().unwrap_err().line, 3);
+
+        std::fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        let broken = verify(&path).unwrap().unwrap_err();
+        assert_eq!(broken.line, 2);
+        assert!(broken.reason.contains("removed or reordered"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
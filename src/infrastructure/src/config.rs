@@ -8,10 +8,13 @@ use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use shared::platform::{self, Shell};
+
 use domain::entities::voice_command::VoiceCommand;
 use domain::entities::workflow::Workflow;
 
-fn find_project_root() -> Option<String> {
+/// Nearest directory up from the current one that looks like a project root
+pub fn find_project_root() -> Option<String> {
     let mut current = std::env::current_dir().ok()?;
     loop {
         // Check for various project indicators
@@ -74,6 +77,43 @@ pub struct AgentExecutionConfig {
     pub convergence_threshold: f32,
     pub time_bounds_per_iteration_seconds: u64,
     pub memory_limit_mb: Option<u64>,
+    #[serde(default)]
+    pub max_session_tokens: Option<u64>,
+    #[serde(default)]
+    pub max_daily_tokens: Option<u64>,
+    /// How long a destructive step waits for approval from the web UI when
+    /// no terminal is attached; unanswered requests are rejected
+    #[serde(default = "default_approval_timeout_seconds")]
+    pub approval_timeout_seconds: u64,
+    /// How long the agent waits for the user to answer an `ask_user`
+    /// question before going on without it; 0 keeps it from asking
+    #[serde(default = "default_question_timeout_seconds")]
+    pub question_timeout_seconds: u64,
+    /// Iterations each sub-agent of a decomposed goal may run
+    #[serde(default = "default_sub_agent_iterations")]
+    pub sub_agent_iterations: u32,
+    /// Context window of each sub-agent, in tokens
+    #[serde(default = "default_sub_agent_context_tokens")]
+    pub sub_agent_context_tokens: usize,
+    /// How long results of read-only tool calls are reused
+    #[serde(default)]
+    pub tool_cache: crate::tool_cache::ToolCacheScope,
+}
+
+fn default_approval_timeout_seconds() -> u64 {
+    600
+}
+
+fn default_question_timeout_seconds() -> u64 {
+    300
+}
+
+fn default_sub_agent_iterations() -> u32 {
+    3
+}
+
+fn default_sub_agent_context_tokens() -> usize {
+    4096
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,7 +123,17 @@ pub struct ResourceLimitsConfig {
     pub max_file_operations: u32,
     pub max_network_requests: u32,
     pub sandbox_enabled: bool,
+    /// Limit sandboxed commands with a cgroup v2 where possible, not `setrlimit`
     pub cgroups_enabled: bool,
+    /// Most processes a sandboxed command may run at once
+    #[serde(default = "default_max_processes")]
+    pub max_processes: u32,
+    /// How sandboxed commands are run
+    #[serde(default)]
+    pub sandbox_backend: crate::sandbox::SandboxBackend,
+    /// Image, runtime and network for the container backend
+    #[serde(default)]
+    pub container: crate::sandbox::ContainerConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -93,6 +143,13 @@ pub struct NetworkSecurityConfig {
     pub max_request_size_kb: u64,
     pub timeout_seconds: u64,
     pub enable_ssl_verification: bool,
+    /// Check the hosts agent commands and tools connect to against the lists
+    #[serde(default = "default_enforce_egress")]
+    pub enforce_egress: bool,
+}
+
+fn default_enforce_egress() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -138,10 +195,30 @@ impl Default for AgentExecutionConfig {
             convergence_threshold: 0.8,
             time_bounds_per_iteration_seconds: 60,
             memory_limit_mb: Some(512),
+            max_session_tokens: None,
+            max_daily_tokens: None,
+            approval_timeout_seconds: default_approval_timeout_seconds(),
+            question_timeout_seconds: default_question_timeout_seconds(),
+            sub_agent_iterations: default_sub_agent_iterations(),
+            sub_agent_context_tokens: default_sub_agent_context_tokens(),
+            tool_cache: crate::tool_cache::ToolCacheScope::default(),
+        }
+    }
+}
+
+impl AgentExecutionConfig {
+    pub fn token_budget(&self) -> crate::token_usage::TokenBudget {
+        crate::token_usage::TokenBudget {
+            max_session_tokens: self.max_session_tokens,
+            max_daily_tokens: self.max_daily_tokens,
         }
     }
 }
 
+fn default_max_processes() -> u32 {
+    256
+}
+
 impl Default for ResourceLimitsConfig {
     fn default() -> Self {
         Self {
@@ -151,6 +228,9 @@ impl Default for ResourceLimitsConfig {
             max_network_requests: 50,
             sandbox_enabled: true,
             cgroups_enabled: false,
+            max_processes: default_max_processes(),
+            sandbox_backend: crate::sandbox::SandboxBackend::default(),
+            container: crate::sandbox::ContainerConfig::default(),
         }
     }
 }
@@ -167,6 +247,7 @@ impl Default for NetworkSecurityConfig {
             max_request_size_kb: 1024,
             timeout_seconds: 30,
             enable_ssl_verification: true,
+            enforce_egress: true,
         }
     }
 }
@@ -234,9 +315,8 @@ impl SystemContext {
 
         // Helper function to run shell command
         let run_cmd = |cmd: &str| -> String {
-            Command::new("sh")
-                .arg("-c")
-                .arg(cmd)
+            Shell::detect()
+                .command(cmd)
                 .output()
                 .ok()
                 .and_then(|o| String::from_utf8(o.stdout).ok())
@@ -259,7 +339,7 @@ impl SystemContext {
 
         // User and directories
         let user = std::env::var("USER").unwrap_or_else(|_| run_cmd("whoami"));
-        let home_dir = std::env::var("HOME").unwrap_or_else(|_| "~".to_string());
+        let home_dir = platform::home_dir().to_string_lossy().to_string();
         let current_dir = std::env::current_dir()
             .ok()
             .and_then(|p| p.to_str().map(|s| s.to_string()))
@@ -290,7 +370,20 @@ impl SystemContext {
         });
 
         // Package manager detection
-        let package_manager = if Command::new("which")
+        let on_path = |program: &str| {
+            Command::new("where")
+                .arg(program)
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        };
+        let package_manager = if cfg!(windows) {
+            ["winget", "choco", "scoop"]
+                .into_iter()
+                .find(|pm| on_path(pm))
+                .map(|pm| format!("{} (Windows)", pm))
+                .unwrap_or_else(|| "unknown".to_string())
+        } else if Command::new("which")
             .arg("pacman")
             .output()
             .ok()
@@ -462,6 +555,14 @@ pub struct ContextConfig {
     pub max_keywords_for_search: usize,
     pub max_lines_per_keyword: usize,
     pub max_rg_context_snippets: usize,
+    pub model_context_tokens: Option<usize>, // None = ask the model backend
+    pub reserved_output_tokens: usize,       // Room left for the answer
+    pub truncation_strategy: crate::context_window::TruncationStrategy,
+    pub tokenizer_path: Option<String>, // HuggingFace tokenizer.json for exact counts
+    pub history_token_budget: usize,    // Conversation size that triggers a summary; 0 = never
+    pub history_recent_turns: usize,    // Turns kept verbatim when summarizing
+    pub memory_scope: crate::context_window::MemoryScope, // Memories put into prompts
+    pub memory_limit: usize,            // Most remembered conversations per prompt
 }
 
 impl Default for ContextConfig {
@@ -478,6 +579,14 @@ impl Default for ContextConfig {
             max_keywords_for_search: 5,            // More keywords
             max_lines_per_keyword: 10,             // More context per keyword
             max_rg_context_snippets: 15,           // More snippets
+            model_context_tokens: None,
+            reserved_output_tokens: 1024,
+            truncation_strategy: crate::context_window::TruncationStrategy::default(),
+            tokenizer_path: None,
+            history_token_budget: 6000,
+            history_recent_turns: 6,
+            memory_scope: crate::context_window::MemoryScope::default(),
+            memory_limit: 5,
         }
     }
 }
@@ -522,6 +631,80 @@ pub struct PowerUserConfig {
     #[serde(default)]
     pub scripts: ScriptConfig,
 
+    /// Speech recognition and synthesis backends
+    #[serde(default)]
+    pub audio: AudioConfig,
+
+    /// Throttling of background events shown in the interactive session
+    #[serde(default)]
+    pub events: EventStreamConfig,
+
+    /// Temperature, top_p, seed and reply length for each generation mode
+    #[serde(default)]
+    pub sampling: crate::sampling::SamplingConfig,
+
+    /// When to ask before agent steps, beyond the built-in checks
+    #[serde(default)]
+    pub confirmations: crate::sandbox::confirmation_rules::ConfirmationRules,
+
+    /// Size, binary and truncation limits for generated file writes
+    #[serde(default)]
+    pub write_guards: crate::write_guards::WriteGuards,
+
+    /// Formatter and linter run on each file a build writes, by extension
+    #[serde(default)]
+    pub format_hooks: crate::format_hooks::FormatHooks,
+
+    /// Secret patterns to detect in addition to the built-in ones
+    #[serde(default)]
+    pub secret_patterns: Vec<shared::secrets_detector::SecretPattern>,
+
+    /// Redact secrets from prompts, or refuse to send them
+    #[serde(default)]
+    pub prompt_secrets: shared::prompt_redaction::SecretHandling,
+
+    /// Masking of personal data in prompts for remote models
+    #[serde(default)]
+    pub pii: crate::privacy_controls::PiiSettings,
+
+    /// Redacted archive of prompts and responses kept for audits
+    #[serde(default)]
+    pub prompt_archive: crate::privacy_controls::ArchiveSettings,
+
+    /// Signed team policy bundle enforced before the local policy rules
+    #[serde(default)]
+    pub policy_bundle: Option<crate::policy_engine::bundle::PolicyBundleSource>,
+
+    /// OS keychain or Vault holding API keys and the web server token
+    #[serde(default)]
+    pub credentials: crate::credentials::CredentialSettings,
+
+    /// Encrypt stored sessions with a key kept in the credential store
+    #[serde(default)]
+    pub encrypt_sessions: bool,
+
+    /// How a session or memory write settles a change another process made
+    /// since it was read
+    #[serde(default)]
+    pub write_conflict_policy: crate::write_conflicts::ConflictPolicy,
+
+    /// Indexed code kept out of generation prompts by license or origin
+    #[serde(default)]
+    pub context_policy: crate::provenance::ContextPolicy,
+
+    /// Timeouts and circuit breaking for Qdrant calls
+    #[serde(default)]
+    pub qdrant: crate::qdrant_guard::QdrantLimits,
+
+    /// MCP servers whose tools the agent can call, by name
+    #[serde(default)]
+    pub mcp_servers: std::collections::BTreeMap<String, crate::mcp_client::McpServerConfig>,
+
+    /// How failed calls are retried, by tool name; unnamed tools keep
+    /// their default policy
+    #[serde(default)]
+    pub retry_policies: std::collections::BTreeMap<String, crate::agent_control::RetryPolicy>,
+
     /// Voice commands
     #[serde(default)]
     pub commands: Vec<domain::entities::voice_command::VoiceCommand>,
@@ -580,6 +763,12 @@ pub struct PluginConfig {
     pub settings: HashMap<String, HashMap<String, String>>,
     /// Plugin paths
     pub paths: Vec<String>,
+    /// Marketplace index, a URL or a local path such as a git checkout
+    #[serde(default)]
+    pub index_url: Option<String>,
+    /// Base64 Ed25519 public key the marketplace index must be signed with
+    #[serde(default)]
+    pub index_public_key: Option<String>,
 }
 
 impl Default for PluginConfig {
@@ -591,6 +780,8 @@ impl Default for PluginConfig {
                 "~/.config/vibe_cli/plugins".to_string(),
                 "~/.vibe_cli/plugins".to_string(),
             ],
+            index_url: None,
+            index_public_key: None,
         }
     }
 }
@@ -611,6 +802,13 @@ pub struct PerformanceConfig {
     pub startup_optimizations: bool,
     /// Model pre-warming on startup
     pub model_prewarming: bool,
+    /// Minimum cosine similarity for reusing a cached answer to a reworded query
+    #[serde(default = "default_semantic_cache_threshold")]
+    pub semantic_cache_threshold: f32,
+}
+
+fn default_semantic_cache_threshold() -> f32 {
+    shared::ultra_fast_cache::DEFAULT_SEMANTIC_THRESHOLD
 }
 
 impl Default for PerformanceConfig {
@@ -623,6 +821,7 @@ impl Default for PerformanceConfig {
             background_processing: true,
             startup_optimizations: true,
             model_prewarming: true, // Ultra-performance: pre-warm models
+            semantic_cache_threshold: default_semantic_cache_threshold(),
         }
     }
 }
@@ -835,6 +1034,74 @@ impl Default for ScriptConfig {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioConfig {
+    /// Speech-to-text provider name (see `SpeechProviderRegistry`)
+    #[serde(default = "default_stt_provider")]
+    pub stt_provider: String,
+    /// Text-to-speech provider name, or "none" to disable spoken feedback
+    #[serde(default = "default_tts_provider")]
+    pub tts_provider: String,
+    /// Explicit speech model location; searched in the usual places when unset
+    #[serde(default)]
+    pub stt_model_path: Option<String>,
+    /// Sample rate fed to the recognizer
+    #[serde(default = "default_stt_sample_rate")]
+    pub sample_rate: u32,
+}
+
+fn default_stt_provider() -> String {
+    "vosk".to_string()
+}
+
+fn default_tts_provider() -> String {
+    "piper".to_string()
+}
+
+fn default_stt_sample_rate() -> u32 {
+    16000
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            stt_provider: default_stt_provider(),
+            tts_provider: default_tts_provider(),
+            stt_model_path: None,
+            sample_rate: default_stt_sample_rate(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventStreamConfig {
+    /// Events are collected for this long before being printed together
+    pub batch_window_ms: u64,
+    /// Lowest log level shown: debug, info, warn or error
+    pub min_log_level: String,
+    /// Lowest diagnostic severity shown: hint, info, warning or error
+    pub min_diagnostic_severity: String,
+    /// Events each source may print per rate window; the rest are counted
+    pub max_events_per_source: usize,
+    pub rate_window_secs: u64,
+    /// More changed files than this in one batch are summarized
+    pub file_storm_threshold: usize,
+}
+
+impl Default for EventStreamConfig {
+    fn default() -> Self {
+        Self {
+            batch_window_ms: 500,
+            min_log_level: "warn".to_string(),
+            min_diagnostic_severity: "warning".to_string(),
+            max_events_per_source: 20,
+            rate_window_secs: 10,
+            file_storm_threshold: 5,
+        }
+    }
+}
+
 impl Default for PowerUserConfig {
     fn default() -> Self {
         Self {
@@ -847,6 +1114,24 @@ impl Default for PowerUserConfig {
             editors: EditorConfig::default(),
             batch: BatchConfig::default(),
             scripts: ScriptConfig::default(),
+            audio: AudioConfig::default(),
+            events: EventStreamConfig::default(),
+            sampling: crate::sampling::SamplingConfig::default(),
+            confirmations: crate::sandbox::confirmation_rules::ConfirmationRules::default(),
+            write_guards: crate::write_guards::WriteGuards::default(),
+            format_hooks: crate::format_hooks::FormatHooks::default(),
+            secret_patterns: Vec::new(),
+            prompt_secrets: shared::prompt_redaction::SecretHandling::default(),
+            pii: crate::privacy_controls::PiiSettings::default(),
+            prompt_archive: crate::privacy_controls::ArchiveSettings::default(),
+            policy_bundle: None,
+            credentials: crate::credentials::CredentialSettings::default(),
+            encrypt_sessions: false,
+            write_conflict_policy: crate::write_conflicts::ConflictPolicy::default(),
+            context_policy: crate::provenance::ContextPolicy::default(),
+            qdrant: crate::qdrant_guard::QdrantLimits::default(),
+            mcp_servers: std::collections::BTreeMap::new(),
+            retry_policies: std::collections::BTreeMap::new(),
             commands: Vec::new(),
             workflows: Vec::new(),
         }
@@ -897,14 +1182,14 @@ impl PowerUserConfig {
     /// Get configuration file paths to search (in order of priority)
     pub fn get_config_paths() -> Vec<PathBuf> {
         let mut paths = Vec::new();
-        let home = env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        let config_dir = platform::app_config_dir();
 
         // Global config files
-        paths.push(PathBuf::from(&home).join(".config/vibe_cli/config.yaml"));
-        paths.push(PathBuf::from(&home).join(".config/vibe_cli/config.yml"));
-        paths.push(PathBuf::from(&home).join(".config/vibe_cli/config.json"));
-        paths.push(PathBuf::from(&home).join(".config/vibe_cli/config.toml"));
-        paths.push(PathBuf::from(&home).join(".vibe_cli/config.yaml"));
+        paths.push(config_dir.join("config.yaml"));
+        paths.push(config_dir.join("config.yml"));
+        paths.push(config_dir.join("config.json"));
+        paths.push(config_dir.join("config.toml"));
+        paths.push(platform::home_dir().join(".vibe_cli").join("config.yaml"));
 
         // Project-specific config files (higher priority)
         if let Some(project_root) = find_project_root() {
@@ -960,6 +1245,114 @@ impl PowerUserConfig {
             config.performance.parallel_jobs = parallel.parse().unwrap_or(num_cpus::get());
         }
 
+        if let Ok(threshold) = env::var("VIBE_SEMANTIC_CACHE_THRESHOLD") {
+            config.performance.semantic_cache_threshold = threshold
+                .parse()
+                .unwrap_or_else(|_| default_semantic_cache_threshold());
+        }
+
+        // Load audio backends
+        if let Ok(provider) = env::var("VIBE_STT_PROVIDER") {
+            config.audio.stt_provider = provider;
+        }
+
+        if let Ok(provider) = env::var("VIBE_TTS_PROVIDER") {
+            config.audio.tts_provider = provider;
+        }
+
+        if let Ok(model_path) = env::var("VIBE_STT_MODEL") {
+            config.audio.stt_model_path = Some(model_path);
+        }
+
+        // Load plugin marketplace settings
+        if let Ok(index_url) = env::var("VIBE_PLUGIN_INDEX") {
+            config.plugins.index_url = Some(index_url);
+        }
+
+        if let Ok(public_key) = env::var("VIBE_PLUGIN_INDEX_KEY") {
+            config.plugins.index_public_key = Some(public_key);
+        }
+
+        // Load sampling parameters per mode
+        if let Ok(sampling) = env::var("VIBE_SAMPLING") {
+            if let Ok(sampling) = serde_json::from_str(&sampling) {
+                config.sampling = sampling;
+            }
+        }
+
+        // Load confirmation rules
+        if let Ok(rules) = env::var("VIBE_CONFIRMATION_RULES") {
+            if let Ok(rules) = serde_json::from_str(&rules) {
+                config.confirmations = rules;
+            }
+        }
+
+        // Load write guards
+        if let Ok(guards) = env::var("VIBE_WRITE_GUARDS") {
+            if let Ok(guards) = serde_json::from_str(&guards) {
+                config.write_guards = guards;
+            }
+        }
+
+        // Load formatter and linter hooks
+        if let Ok(hooks) = env::var("VIBE_FORMAT_HOOKS") {
+            if let Ok(hooks) = serde_json::from_str(&hooks) {
+                config.format_hooks = hooks;
+            }
+        }
+
+        // Load custom secret patterns
+        if let Ok(patterns) = env::var("VIBE_SECRET_PATTERNS") {
+            if let Ok(patterns) = serde_json::from_str(&patterns) {
+                config.secret_patterns = patterns;
+            }
+        }
+        if let Ok(handling) = env::var("VIBE_PROMPT_SECRETS") {
+            if let Ok(handling) = serde_json::from_value(serde_json::Value::String(handling)) {
+                config.prompt_secrets = handling;
+            }
+        }
+        if let Ok(pii) = env::var("VIBE_PII") {
+            if let Ok(pii) = serde_json::from_str(&pii) {
+                config.pii = pii;
+            }
+        }
+        if let Ok(archive) = env::var("VIBE_PROMPT_ARCHIVE") {
+            if let Ok(archive) = serde_json::from_str(&archive) {
+                config.prompt_archive = archive;
+            }
+        }
+
+        // Load the team policy bundle source
+        if let Ok(bundle) = env::var("VIBE_POLICY_BUNDLE") {
+            if let Ok(bundle) = serde_json::from_str(&bundle) {
+                config.policy_bundle = Some(bundle);
+            }
+        }
+        if let Ok(credentials) = env::var("VIBE_CREDENTIALS") {
+            if let Ok(credentials) = serde_json::from_str(&credentials) {
+                config.credentials = credentials;
+            }
+        }
+        if let Ok(encrypt) = env::var("VIBE_ENCRYPT_SESSIONS") {
+            config.encrypt_sessions = encrypt.parse().unwrap_or(false);
+        }
+        if let Ok(policy) = env::var("VIBE_CONTEXT_POLICY") {
+            if let Ok(policy) = serde_json::from_str(&policy) {
+                config.context_policy = policy;
+            }
+        }
+        if let Ok(servers) = env::var("VIBE_MCP_SERVERS") {
+            if let Ok(servers) = serde_json::from_str(&servers) {
+                config.mcp_servers = servers;
+            }
+        }
+        if let Ok(policies) = env::var("VIBE_RETRY_POLICIES") {
+            if let Ok(policies) = serde_json::from_str(&policies) {
+                config.retry_policies = policies;
+            }
+        }
+
         // Load theme settings
         if let Ok(theme_name) = env::var("VIBE_THEME") {
             config.theme.name = theme_name;
@@ -1521,6 +1914,38 @@ pub struct Config {
     pub db_path: String,
     pub rag_include_patterns: Vec<String>,
     pub rag_exclude_patterns: Vec<String>,
+    /// List files through git inside a repository (skipping ignored artifacts)
+    /// and tag chunks with the commit they came from
+    pub rag_git_aware: bool,
+    /// Related repositories searched alongside the current project, scoped
+    /// per query with `--context`
+    pub rag_repositories: Vec<String>,
+    /// Command outputs retained for `@out:N` references
+    pub output_history_limit: usize,
+    /// Model used for RAG embeddings; the chat model when unset
+    pub embedding_model: Option<String>,
+    /// Vector size of `embedding_model`; asked of the model when unset
+    pub embedding_dimensions: Option<usize>,
+    /// Chunks sent per embedding request while indexing
+    pub embedding_batch_size: usize,
+    /// Embedding requests in flight while indexing
+    pub embedding_concurrency: usize,
+    /// Rebuild indexes made with a different embedding model instead of
+    /// refusing to open them
+    pub rag_reembed: bool,
+    /// Expand each question into paraphrases and keyword variants and search
+    /// with all of them, which helps vague questions find the right code
+    pub rag_query_expansion: bool,
+    /// Model writing query expansions; the chat model when unset
+    pub rag_expansion_model: Option<String>,
+    /// Chunks retrieved from the code collection, and the factor on their
+    /// scores
+    pub rag_code_top_k: usize,
+    pub rag_code_weight: f32,
+    /// Chunks retrieved from the knowledge collection of READMEs, docs and
+    /// decision records, and the factor on their scores
+    pub rag_knowledge_top_k: usize,
+    pub rag_knowledge_weight: f32,
     pub security: SecurityConfig,
     pub context: ContextConfig,
     pub power_user: PowerUserConfig,
@@ -1531,11 +1956,7 @@ impl Config {
     pub fn load() -> Self {
         dotenv().ok();
         let db_path = env::var("DB_PATH").unwrap_or_else(|_| {
-            let home = env::var("HOME").unwrap_or_else(|_| ".".to_string());
-            let mut path = PathBuf::from(home);
-            path.push(".local");
-            path.push("share");
-            path.push("vibe_cli");
+            let mut path = platform::app_data_dir();
             let suffix = project_cache_suffix();
             path.push(format!("{}_embeddings.db", suffix));
             path.to_string_lossy().to_string()
@@ -1555,6 +1976,62 @@ impl Config {
             .map(|s| s.trim().to_string())
             .collect();
 
+        let rag_git_aware = env::var("RAG_GIT_AWARE")
+            .map(|v| !matches!(v.trim(), "0" | "false" | "off"))
+            .unwrap_or(true);
+
+        let rag_repositories = env::var("RAG_REPOSITORIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let output_history_limit = env::var("OUTPUT_HISTORY_LIMIT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(crate::output_history::DEFAULT_LIMIT);
+
+        let embedding_model = env::var("EMBEDDING_MODEL")
+            .ok()
+            .filter(|m| !m.trim().is_empty());
+        let embedding_dimensions = env::var("EMBEDDING_DIMENSIONS")
+            .ok()
+            .and_then(|s| s.parse().ok());
+        let embedding_batch_size = env::var("EMBEDDING_BATCH_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(crate::embedder::DEFAULT_BATCH_SIZE);
+        let embedding_concurrency = env::var("EMBEDDING_CONCURRENCY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(crate::embedder::DEFAULT_CONCURRENCY);
+        let rag_reembed = env::var("RAG_REEMBED")
+            .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+            .unwrap_or(false);
+        let rag_query_expansion = env::var("RAG_QUERY_EXPANSION")
+            .map(|v| matches!(v.trim(), "1" | "true" | "on"))
+            .unwrap_or(false);
+        let rag_expansion_model = env::var("RAG_EXPANSION_MODEL")
+            .ok()
+            .filter(|m| !m.trim().is_empty());
+        let rag_code_top_k = env::var("RAG_CODE_TOP_K")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(50);
+        let rag_code_weight = env::var("RAG_CODE_WEIGHT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1.0);
+        let rag_knowledge_top_k = env::var("RAG_KNOWLEDGE_TOP_K")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10);
+        let rag_knowledge_weight = env::var("RAG_KNOWLEDGE_WEIGHT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1.0);
+
         // Load security configuration
         let security = Self::load_security_config();
 
@@ -1605,6 +2082,37 @@ impl Config {
                 .ok()
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(defaults.max_rg_context_snippets),
+            model_context_tokens: env::var("CONTEXT_WINDOW_TOKENS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .or(defaults.model_context_tokens),
+            reserved_output_tokens: env::var("CONTEXT_RESERVED_OUTPUT_TOKENS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.reserved_output_tokens),
+            truncation_strategy: env::var("CONTEXT_TRUNCATION_STRATEGY")
+                .ok()
+                .and_then(|s| crate::context_window::TruncationStrategy::parse(&s))
+                .unwrap_or(defaults.truncation_strategy),
+            tokenizer_path: env::var("CONTEXT_TOKENIZER_PATH")
+                .ok()
+                .or(defaults.tokenizer_path),
+            history_token_budget: env::var("CONTEXT_HISTORY_TOKENS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.history_token_budget),
+            history_recent_turns: env::var("CONTEXT_HISTORY_RECENT_TURNS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.history_recent_turns),
+            memory_scope: env::var("CONTEXT_MEMORY")
+                .ok()
+                .and_then(|s| crate::context_window::MemoryScope::parse(&s))
+                .unwrap_or(defaults.memory_scope),
+            memory_limit: env::var("CONTEXT_MEMORY_LIMIT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.memory_limit),
         };
 
         Self {
@@ -1615,6 +2123,20 @@ impl Config {
             db_path,
             rag_include_patterns,
             rag_exclude_patterns,
+            rag_git_aware,
+            rag_repositories,
+            output_history_limit,
+            embedding_model,
+            embedding_dimensions,
+            embedding_batch_size,
+            embedding_concurrency,
+            rag_reembed,
+            rag_query_expansion,
+            rag_expansion_model,
+            rag_code_top_k,
+            rag_code_weight,
+            rag_knowledge_top_k,
+            rag_knowledge_weight,
             security,
             context,
             power_user: PowerUserConfig::load(),
@@ -1622,6 +2144,24 @@ impl Config {
         }
     }
 
+    /// Redactor for text going into prompts, with the custom secret patterns
+    pub fn prompt_redactor(&self) -> shared::prompt_redaction::PromptRedactor {
+        let detector = shared::secrets_detector::SecretsDetector::with_patterns(
+            &self.power_user.secret_patterns,
+        )
+        .unwrap_or_else(|e| {
+            tracing::warn!("Ignoring custom secret patterns: {}", e);
+            shared::secrets_detector::SecretsDetector::new()
+        });
+        shared::prompt_redaction::PromptRedactor::new(detector, self.power_user.prompt_secrets)
+    }
+
+    /// Detector for personal data in prompts, when the model is remote or
+    /// the settings ask for local models too
+    pub fn pii_detector(&self) -> Option<shared::pii_detector::PiiDetector> {
+        self.power_user.pii.detector_for(&self.ollama_base_url)
+    }
+
     /// Initialize plugins asynchronously
     pub async fn initialize_plugins(&self) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(plugin_manager) = &self.plugin_manager {
@@ -1700,6 +2240,32 @@ impl Config {
                 memory_limit_mb: env::var("VIBE_MEMORY_LIMIT_MB")
                     .ok()
                     .and_then(|s| s.parse().ok()),
+                max_session_tokens: env::var("VIBE_MAX_SESSION_TOKENS")
+                    .ok()
+                    .and_then(|s| s.parse().ok()),
+                max_daily_tokens: env::var("VIBE_MAX_DAILY_TOKENS")
+                    .ok()
+                    .and_then(|s| s.parse().ok()),
+                approval_timeout_seconds: env::var("VIBE_APPROVAL_TIMEOUT_SECONDS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(default_approval_timeout_seconds),
+                question_timeout_seconds: env::var("VIBE_QUESTION_TIMEOUT_SECONDS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(default_question_timeout_seconds),
+                sub_agent_iterations: env::var("VIBE_SUB_AGENT_ITERATIONS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(default_sub_agent_iterations),
+                sub_agent_context_tokens: env::var("VIBE_SUB_AGENT_CONTEXT_TOKENS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(default_sub_agent_context_tokens),
+                tool_cache: env::var("VIBE_TOOL_CACHE")
+                    .ok()
+                    .and_then(|s| crate::tool_cache::ToolCacheScope::parse(&s))
+                    .unwrap_or_default(),
             },
             resource_limits: ResourceLimitsConfig {
                 max_memory_mb: env::var("VIBE_MAX_MEMORY_MB")
@@ -1726,6 +2292,25 @@ impl Config {
                     .unwrap_or_else(|_| "false".to_string())
                     .parse()
                     .unwrap_or(false),
+                max_processes: env::var("VIBE_MAX_PROCESSES")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or_else(default_max_processes),
+                sandbox_backend: env::var("VIBE_SANDBOX_BACKEND")
+                    .ok()
+                    .and_then(|s| crate::sandbox::SandboxBackend::parse(&s))
+                    .unwrap_or_default(),
+                container: {
+                    let defaults = crate::sandbox::ContainerConfig::default();
+                    crate::sandbox::ContainerConfig {
+                        runtime: env::var("VIBE_SANDBOX_RUNTIME").ok(),
+                        image: env::var("VIBE_SANDBOX_IMAGE").unwrap_or(defaults.image),
+                        network: env::var("VIBE_SANDBOX_NETWORK")
+                            .ok()
+                            .and_then(|s| s.parse().ok())
+                            .unwrap_or(defaults.network),
+                    }
+                },
             },
             network_security: NetworkSecurityConfig {
                 allowed_domains: env::var("VIBE_ALLOWED_DOMAINS")
@@ -1753,6 +2338,10 @@ impl Config {
                     .unwrap_or_else(|_| "true".to_string())
                     .parse()
                     .unwrap_or(true),
+                enforce_egress: env::var("VIBE_ENFORCE_EGRESS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(true),
             },
             content_sanitization: ContentSanitizationConfig {
                 prompt_injection_detection: env::var("VIBE_PROMPT_INJECTION_DETECTION")
@@ -0,0 +1,896 @@
+//! Path-based edits of TOML, YAML and JSON config files
+//!
+//! The `config_edit` tool takes edits like `set server.port=8081` or
+//! `delete features.beta` and changes only the values they name, so comments,
+//! key order and formatting elsewhere in the file survive. TOML is edited
+//! through `toml_edit`; YAML and JSON are edited in place in the text, which
+//! also keeps comments in JSONC files such as `tsconfig.json`. Every result is
+//! parsed again and checked to hold the new value before it is written.
+//!
+//! Paths are dotted keys; a number indexes an array (`servers.0.host`) and a
+//! quoted segment may contain dots (`env."log.level"`). Values are read as
+//! JSON when they parse, e.g. `8081`, `true`, `["a", "b"]` or `"8081"`, and as
+//! plain strings otherwise.
+
+use shared::types::Result;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Indentation per level assumed for YAML files that have no nesting yet
+const DEFAULT_YAML_INDENT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match extension.as_str() {
+            "toml" => Ok(Self::Toml),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "json" | "jsonc" => Ok(Self::Json),
+            _ => Err(anyhow::anyhow!(
+                "{} is not a TOML, YAML or JSON file",
+                path.display()
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Segment {
+    /// The segment as a map key; numbers are keys in maps
+    fn key(&self) -> String {
+        match self {
+            Self::Key(key) => key.clone(),
+            Self::Index(index) => index.to_string(),
+        }
+    }
+}
+
+/// Parse a dotted path such as `servers.0.host` or `env."log.level"`
+pub fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut rest = path.trim();
+    while !rest.is_empty() {
+        let (segment, after) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted
+                    .find('"')
+                    .ok_or_else(|| anyhow::anyhow!("Unterminated quote in path '{}'", path))?;
+                (Segment::Key(quoted[..end].to_string()), &quoted[end + 1..])
+            }
+            None => {
+                let end = rest.find('.').unwrap_or(rest.len());
+                let part = rest[..end].trim();
+                let segment = match part.parse() {
+                    Ok(index) => Segment::Index(index),
+                    Err(_) => Segment::Key(part.to_string()),
+                };
+                (segment, &rest[end..])
+            }
+        };
+        if segment == Segment::Key(String::new()) {
+            return Err(anyhow::anyhow!("Empty key in path '{}'", path));
+        }
+        segments.push(segment);
+        rest = match after.strip_prefix('.') {
+            Some(next) if !next.is_empty() => next,
+            Some(_) => return Err(anyhow::anyhow!("Path '{}' ends with a dot", path)),
+            None if after.is_empty() => after,
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Expected '.' after a quoted key in '{}'",
+                    path
+                ))
+            }
+        };
+    }
+    if segments.is_empty() {
+        return Err(anyhow::anyhow!("Empty path"));
+    }
+    Ok(segments)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigChange {
+    Set {
+        path: Vec<Segment>,
+        value: serde_json::Value,
+    },
+    Delete {
+        path: Vec<Segment>,
+    },
+}
+
+impl ConfigChange {
+    pub fn path(&self) -> &[Segment] {
+        match self {
+            Self::Set { path, .. } | Self::Delete { path } => path,
+        }
+    }
+}
+
+impl FromStr for ConfigChange {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (verb, rest) = spec.split_once(char::is_whitespace).unwrap_or((spec, ""));
+        match verb {
+            "set" => {
+                let (path, value) = rest.split_once('=').ok_or_else(|| {
+                    anyhow::anyhow!("Expected `set <path>=<value>`, got '{}'", spec)
+                })?;
+                let value = value.trim();
+                Ok(Self::Set {
+                    path: parse_path(path)?,
+                    value: serde_json::from_str(value)
+                        .unwrap_or_else(|_| serde_json::Value::String(value.to_string())),
+                })
+            }
+            "delete" => Ok(Self::Delete {
+                path: parse_path(rest)?,
+            }),
+            _ => Err(anyhow::anyhow!(
+                "Unknown edit '{}'; use `set <path>=<value>` or `delete <path>`",
+                spec
+            )),
+        }
+    }
+}
+
+/// Edits one per line or separated by `;`
+pub fn parse_changes(spec: &str) -> Result<Vec<ConfigChange>> {
+    spec.split(['\n', ';'])
+        .filter(|line| !line.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// `content` with `changes` applied, leaving everything they do not touch as
+/// it was
+pub fn apply_changes(
+    format: ConfigFormat,
+    content: &str,
+    changes: &[ConfigChange],
+) -> Result<String> {
+    let edited = match format {
+        ConfigFormat::Toml => edit_toml(content, changes)?,
+        ConfigFormat::Yaml => {
+            let mut lines = YamlLines::new(content);
+            for change in changes {
+                lines.apply(change)?;
+            }
+            lines.to_string()
+        }
+        ConfigFormat::Json => {
+            let mut text = content.to_string();
+            for change in changes {
+                edit_json(&mut text, change)?;
+            }
+            text
+        }
+    };
+    verify(format, content, &edited, changes)?;
+    Ok(edited)
+}
+
+/// Unified diff of an edit
+pub fn diff(path: &Path, old: &str, new: &str) -> String {
+    let name = path.display().to_string();
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(2)
+        .header(&format!("a/{}", name), &format!("b/{}", name))
+        .to_string()
+}
+
+fn describe(path: &[Segment]) -> String {
+    path.iter()
+        .map(|segment| match segment {
+            Segment::Key(key) if key.contains('.') => format!("\"{}\"", key),
+            segment => segment.key(),
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Parse the edited document and check each change took effect
+fn verify(
+    format: ConfigFormat,
+    original: &str,
+    edited: &str,
+    changes: &[ConfigChange],
+) -> Result<()> {
+    let parsed: serde_json::Value = match format {
+        ConfigFormat::Toml => serde_json::to_value(toml::from_str::<toml::Value>(edited)?)?,
+        ConfigFormat::Yaml => {
+            serde_json::to_value(serde_yaml::from_str::<serde_yaml::Value>(edited)?)?
+        }
+        ConfigFormat::Json => {
+            // JSON with comments cannot be checked by a strict parser
+            if serde_json::from_str::<serde_json::Value>(original).is_err() {
+                return Ok(());
+            }
+            serde_json::from_str(edited)?
+        }
+    };
+    // Later changes may overwrite earlier ones; check each path's last change
+    for (i, change) in changes.iter().enumerate() {
+        if changes[i + 1..].iter().any(|later| {
+            later.path().starts_with(change.path()) || change.path().starts_with(later.path())
+        }) {
+            continue;
+        }
+        let pointer: String = change
+            .path()
+            .iter()
+            .map(|segment| format!("/{}", segment.key().replace('~', "~0").replace('/', "~1")))
+            .collect();
+        let found = parsed.pointer(&pointer);
+        let ok = match change {
+            ConfigChange::Set { value, .. } => found == Some(value),
+            ConfigChange::Delete { .. } => found.is_none(),
+        };
+        if !ok {
+            return Err(anyhow::anyhow!(
+                "Editing {} did not produce the expected document",
+                describe(change.path())
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn edit_toml(content: &str, changes: &[ConfigChange]) -> Result<String> {
+    let mut document: toml_edit::DocumentMut = content.parse()?;
+    for change in changes {
+        let value = match change {
+            ConfigChange::Set { value, .. } => Some(toml_value(value)?),
+            ConfigChange::Delete { .. } => None,
+        };
+        edit_toml_table(document.as_table_mut(), change.path(), value, false)
+            .map_err(|e| anyhow::anyhow!("{}: {}", describe(change.path()), e))?;
+    }
+    Ok(document.to_string())
+}
+
+/// Set or, for `None`, delete `path` below `table`; tables created on the
+/// way are inline inside inline tables
+fn edit_toml_table(
+    table: &mut dyn toml_edit::TableLike,
+    path: &[Segment],
+    value: Option<toml_edit::Value>,
+    inline: bool,
+) -> Result<()> {
+    let key = path[0].key();
+    if path.len() == 1 {
+        let Some(new) = value else {
+            return table
+                .remove(&key)
+                .map(|_| ())
+                .ok_or_else(|| anyhow::anyhow!("no such key"));
+        };
+        if let Some(toml_edit::Item::Value(existing)) = table.get_mut(&key) {
+            // Keep the comment after the old value
+            let decor = existing.decor().clone();
+            *existing = new;
+            *existing.decor_mut() = decor;
+        } else {
+            table.insert(&key, toml_edit::Item::Value(new));
+        }
+        return Ok(());
+    }
+
+    if table.get(&key).is_none() {
+        if value.is_none() {
+            return Err(anyhow::anyhow!("no such key"));
+        }
+        let child = if inline {
+            toml_edit::Item::Value(toml_edit::InlineTable::new().into())
+        } else {
+            let mut child = toml_edit::Table::new();
+            child.set_implicit(true);
+            toml_edit::Item::Table(child)
+        };
+        table.insert(&key, child);
+    }
+    let child = table.get_mut(&key).expect("inserted above");
+    match (child, &path[1]) {
+        (toml_edit::Item::Table(child), _) => edit_toml_table(child, &path[1..], value, false),
+        (toml_edit::Item::Value(toml_edit::Value::InlineTable(child)), _) => {
+            edit_toml_table(child, &path[1..], value, true)
+        }
+        (toml_edit::Item::ArrayOfTables(tables), Segment::Index(index)) if path.len() > 2 => {
+            let count = tables.len();
+            let table = tables
+                .get_mut(*index)
+                .ok_or_else(|| anyhow::anyhow!("index {} out of {} tables", index, count))?;
+            edit_toml_table(table, &path[2..], value, false)
+        }
+        (toml_edit::Item::Value(toml_edit::Value::Array(array)), Segment::Index(index)) => {
+            let index = *index;
+            if path.len() > 2 {
+                return match array.get_mut(index) {
+                    Some(toml_edit::Value::InlineTable(child)) => {
+                        edit_toml_table(child, &path[2..], value, true)
+                    }
+                    Some(_) => Err(anyhow::anyhow!("element {} is not a table", index)),
+                    None => Err(anyhow::anyhow!("index {} out of range", index)),
+                };
+            }
+            match value {
+                Some(new) if index < array.len() => {
+                    array.replace(index, new);
+                }
+                Some(new) if index == array.len() => array.push(new),
+                None if index < array.len() => {
+                    array.remove(index);
+                }
+                _ => return Err(anyhow::anyhow!("index {} out of range", index)),
+            }
+            Ok(())
+        }
+        _ => Err(anyhow::anyhow!("'{}' is not a table or array", key)),
+    }
+}
+
+fn toml_value(value: &serde_json::Value) -> Result<toml_edit::Value> {
+    use serde_json::Value;
+    Ok(match value {
+        Value::Null => return Err(anyhow::anyhow!("TOML has no null value")),
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64().unwrap_or_default().into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => toml_edit::Value::Array(
+            items
+                .iter()
+                .map(toml_value)
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .collect(),
+        ),
+        Value::Object(map) => {
+            let mut table = toml_edit::InlineTable::new();
+            for (key, value) in map {
+                table.insert(key, toml_value(value)?);
+            }
+            toml_edit::Value::InlineTable(table)
+        }
+    })
+}
+
+/// A member of a JSON object or element of an array, by byte ranges
+struct JsonEntry {
+    start: usize,
+    key: Option<String>,
+    /// From the end of the key to the start of the value, e.g. `": "`
+    separator: Range<usize>,
+    value: Range<usize>,
+}
+
+fn skip_trivia(bytes: &[u8], mut i: usize) -> usize {
+    loop {
+        match bytes.get(i..) {
+            Some([c, ..]) if c.is_ascii_whitespace() => i += 1,
+            Some([b'/', b'/', ..]) => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            Some([b'/', b'*', ..]) => {
+                i += 2;
+                while i < bytes.len() && !bytes[i..].starts_with(b"*/") {
+                    i += 1;
+                }
+                i = (i + 2).min(bytes.len());
+            }
+            _ => return i,
+        }
+    }
+}
+
+fn json_string_end(bytes: &[u8], start: usize) -> Result<usize> {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+    Err(anyhow::anyhow!("Unterminated string in JSON"))
+}
+
+fn json_value_end(bytes: &[u8], start: usize) -> Result<usize> {
+    match bytes.get(start) {
+        Some(b'"') => json_string_end(bytes, start),
+        Some(b'{' | b'[') => {
+            let (_, close) = json_entries(bytes, start)?;
+            Ok(close + 1)
+        }
+        Some(_) => {
+            let mut i = start;
+            while i < bytes.len()
+                && !matches!(bytes[i], b',' | b'}' | b']' | b'/')
+                && !bytes[i].is_ascii_whitespace()
+            {
+                i += 1;
+            }
+            Ok(i)
+        }
+        None => Err(anyhow::anyhow!("Unexpected end of JSON")),
+    }
+}
+
+/// Entries of the object or array opening at `open`, and where it closes
+fn json_entries(bytes: &[u8], open: usize) -> Result<(Vec<JsonEntry>, usize)> {
+    let close_byte = if bytes[open] == b'{' { b'}' } else { b']' };
+    let mut entries = Vec::new();
+    let mut i = skip_trivia(bytes, open + 1);
+    loop {
+        if bytes.get(i) == Some(&close_byte) {
+            return Ok((entries, i));
+        }
+        let start = i;
+        let (key, separator) = if close_byte == b'}' {
+            let key_end = json_string_end(bytes, i)?;
+            let key: String = serde_json::from_slice(&bytes[i..key_end])?;
+            let colon = skip_trivia(bytes, key_end);
+            if bytes.get(colon) != Some(&b':') {
+                return Err(anyhow::anyhow!(
+                    "Expected ':' after key \"{}\" in JSON",
+                    key
+                ));
+            }
+            i = skip_trivia(bytes, colon + 1);
+            (Some(key), key_end..i)
+        } else {
+            (None, i..i)
+        };
+        let end = json_value_end(bytes, i)?;
+        entries.push(JsonEntry {
+            start,
+            key,
+            separator,
+            value: i..end,
+        });
+        i = skip_trivia(bytes, end);
+        match bytes.get(i) {
+            Some(b',') => i = skip_trivia(bytes, i + 1),
+            Some(&c) if c == close_byte => {}
+            _ => return Err(anyhow::anyhow!("Malformed JSON near byte {}", i)),
+        }
+    }
+}
+
+/// Indentation of the line containing byte `at`
+fn line_indent(text: &str, at: usize) -> &str {
+    let line_start = text[..at].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[line_start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// `value` as JSON; containers are pretty-printed, continuing at `indent`
+fn render_json(value: &serde_json::Value, indent: &str) -> String {
+    let compact = serde_json::to_string(value).unwrap_or_default();
+    let is_empty_container = match value {
+        serde_json::Value::Object(map) => map.is_empty(),
+        serde_json::Value::Array(items) => items.is_empty(),
+        _ => true,
+    };
+    if is_empty_container {
+        return compact;
+    }
+    serde_json::to_string_pretty(value)
+        .unwrap_or(compact)
+        .replace('\n', &format!("\n{}", indent))
+}
+
+/// `value` nested under the keys of `path`, for paths not in the document yet
+fn nest(path: &[Segment], value: &serde_json::Value) -> Result<serde_json::Value> {
+    let mut nested = value.clone();
+    for segment in path.iter().rev() {
+        match segment {
+            Segment::Key(key) => {
+                let mut map = serde_json::Map::new();
+                map.insert(key.clone(), nested);
+                nested = serde_json::Value::Object(map);
+            }
+            Segment::Index(_) => {
+                return Err(anyhow::anyhow!(
+                    "cannot create an array element; set the whole array instead"
+                ))
+            }
+        }
+    }
+    Ok(nested)
+}
+
+fn edit_json(text: &mut String, change: &ConfigChange) -> Result<()> {
+    let path = change.path();
+    let fail = |message: String| anyhow::anyhow!("{}: {}", describe(path), message);
+    let value = match change {
+        ConfigChange::Set { value, .. } => Some(value),
+        ConfigChange::Delete { .. } => None,
+    };
+
+    let mut open = skip_trivia(text.as_bytes(), 0);
+    for (depth, segment) in path.iter().enumerate() {
+        let bytes = text.as_bytes();
+        if !matches!(bytes.get(open), Some(b'{' | b'[')) {
+            return Err(fail(format!(
+                "'{}' is not an object or array",
+                describe(&path[..depth])
+            )));
+        }
+        let is_object = bytes[open] == b'{';
+        let (entries, close) = json_entries(bytes, open)?;
+        let found = match (is_object, segment) {
+            (true, _) => entries
+                .iter()
+                .position(|entry| entry.key.as_deref() == Some(segment.key().as_str())),
+            (false, Segment::Index(index)) => (*index < entries.len()).then_some(*index),
+            (false, Segment::Key(key)) => {
+                return Err(fail(format!("'{}' indexes an array", key)));
+            }
+        };
+        let last = depth + 1 == path.len();
+
+        match (found, value) {
+            (Some(n), Some(value)) if last => {
+                let entry = &entries[n];
+                let rendered = render_json(value, line_indent(text, entry.start));
+                text.replace_range(entry.value.clone(), &rendered);
+                return Ok(());
+            }
+            (Some(n), None) if last => {
+                let range = if n + 1 < entries.len() {
+                    entries[n].start..entries[n + 1].start
+                } else if n > 0 {
+                    entries[n - 1].value.end..entries[n].value.end
+                } else {
+                    open + 1..close
+                };
+                text.replace_range(range, "");
+                return Ok(());
+            }
+            (Some(n), _) => open = entries[n].value.start,
+            (None, None) => return Err(fail("no such key".to_string())),
+            (None, Some(value)) => {
+                if matches!(segment, Segment::Index(index) if !is_object && *index != entries.len())
+                {
+                    return Err(fail(format!("index out of {} elements", entries.len())));
+                }
+                let nested = nest(&path[depth + 1..], value).map_err(|e| fail(e.to_string()))?;
+                let (at, lead, separator) = match entries.last() {
+                    Some(last) => {
+                        let before = &text[..last.start];
+                        let lead = &before[before.trim_end().len()..];
+                        (
+                            last.value.end,
+                            format!(",{}", lead),
+                            text[last.separator.clone()].to_string(),
+                        )
+                    }
+                    None => {
+                        // Empty container: put the entry on its own line
+                        let indent = line_indent(text, open).to_string();
+                        text.replace_range(open + 1..close, &format!("\n{}", indent));
+                        (open + 1, format!("\n{}  ", indent), ": ".to_string())
+                    }
+                };
+                let indent = lead.rsplit('\n').next().unwrap_or_default().to_string();
+                let entry = if is_object {
+                    format!(
+                        "{}{}{}",
+                        serde_json::to_string(&segment.key())?,
+                        separator,
+                        render_json(&nested, &indent)
+                    )
+                } else {
+                    render_json(&nested, &indent)
+                };
+                text.insert_str(at, &format!("{}{}", lead, entry));
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A YAML document edited line by line; block mappings can be edited,
+/// sequences only replaced as a whole
+struct YamlLines {
+    lines: Vec<String>,
+    newline: &'static str,
+    trailing_newline: bool,
+    indent_step: usize,
+}
+
+impl YamlLines {
+    fn new(content: &str) -> Self {
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let indent_step = lines
+            .iter()
+            .filter(|line| is_content(line))
+            .map(|line| indent_of(line))
+            .filter(|indent| *indent > 0)
+            .min()
+            .unwrap_or(DEFAULT_YAML_INDENT);
+        Self {
+            lines,
+            newline: if content.contains("\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            },
+            trailing_newline: content.ends_with('\n') || content.is_empty(),
+            indent_step,
+        }
+    }
+
+    /// Lines of the first document, after a leading `---`
+    fn document(&self) -> Range<usize> {
+        let start = self
+            .lines
+            .iter()
+            .position(|line| is_content(line))
+            .filter(|&i| self.lines[i].trim_end() == "---")
+            .map_or(0, |marker| marker + 1);
+        let end = (start..self.lines.len())
+            .find(|&i| matches!(self.lines[i].trim_end(), "---" | "..."))
+            .unwrap_or(self.lines.len());
+        start..end
+    }
+
+    /// End of the block of the key at line `at`: its nested lines, without
+    /// trailing blank lines and comments
+    fn block_end(&self, at: usize, limit: usize) -> usize {
+        let indent = indent_of(&self.lines[at]);
+        let mut end = at + 1;
+        while end < limit && (!is_content(&self.lines[end]) || indent_of(&self.lines[end]) > indent)
+        {
+            end += 1;
+        }
+        while end > at + 1 && !is_content(&self.lines[end - 1]) {
+            end -= 1;
+        }
+        end
+    }
+
+    fn apply(&mut self, change: &ConfigChange) -> Result<()> {
+        let path = change.path();
+        let fail = |message: String| anyhow::anyhow!("{}: {}", describe(path), message);
+        let value = match change {
+            ConfigChange::Set { value, .. } => Some(value),
+            ConfigChange::Delete { .. } => None,
+        };
+
+        let mut block = self.document();
+        let mut parent_indent: Option<usize> = None;
+        for (depth, segment) in path.iter().enumerate() {
+            let first = block.clone().find(|&i| is_content(&self.lines[i]));
+            if let Some(first) = first {
+                if self.lines[first].trim_start().starts_with('-') {
+                    return Err(fail(format!(
+                        "'{}' is a sequence; set the whole list instead",
+                        describe(&path[..depth])
+                    )));
+                }
+            }
+            let indent = first.map_or_else(
+                || parent_indent.map_or(0, |parent| parent + self.indent_step),
+                |first| indent_of(&self.lines[first]),
+            );
+            let key = segment.key();
+            let found = block.clone().find(|&i| {
+                is_content(&self.lines[i])
+                    && indent_of(&self.lines[i]) == indent
+                    && split_key(&self.lines[i]).is_some_and(|(k, _)| k == key)
+            });
+            let last = depth + 1 == path.len();
+
+            match (found, value) {
+                (Some(at), Some(value)) if last => {
+                    let end = self.block_end(at, block.end);
+                    let line = &self.lines[at];
+                    let (_, colon) = split_key(line).expect("matched above");
+                    let (_, comment) = split_comment(&line[colon..]);
+                    let replaced =
+                        format!("{}: {}{}", &line[..colon - 1], render_yaml(value), comment);
+                    self.lines.splice(at..end, [replaced]);
+                    return Ok(());
+                }
+                (Some(at), None) if last => {
+                    let end = self.block_end(at, block.end);
+                    self.lines.drain(at..end);
+                    return Ok(());
+                }
+                (Some(at), _) => {
+                    let line = &self.lines[at];
+                    let (_, colon) = split_key(line).expect("matched above");
+                    let (inline, _) = split_comment(&line[colon..]);
+                    if !inline.trim().is_empty() {
+                        return Err(fail(format!(
+                            "'{}' is not a block mapping",
+                            describe(&path[..=depth])
+                        )));
+                    }
+                    block = at + 1..self.block_end(at, block.end);
+                    parent_indent = Some(indent);
+                }
+                (None, None) => return Err(fail("no such key".to_string())),
+                (None, Some(value)) => {
+                    let at = block
+                        .clone()
+                        .rev()
+                        .find(|&i| is_content(&self.lines[i]))
+                        .map_or(block.start, |i| i + 1);
+                    let mut inserted = Vec::new();
+                    for (level, segment) in path[depth..].iter().enumerate() {
+                        let key = segment.key();
+                        let key = if key
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+                        {
+                            key
+                        } else {
+                            serde_json::to_string(&key)?
+                        };
+                        let pad = " ".repeat(indent + level * self.indent_step);
+                        if depth + level + 1 == path.len() {
+                            inserted.push(format!("{}{}: {}", pad, key, render_yaml(value)));
+                        } else {
+                            inserted.push(format!("{}{}:", pad, key));
+                        }
+                    }
+                    self.lines.splice(at..at, inserted);
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for YamlLines {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.lines.join(self.newline))?;
+        if self.trailing_newline && !self.lines.is_empty() {
+            f.write_str(self.newline)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Key of a `key: value` line and the byte offset after its colon
+fn split_key(line: &str) -> Option<(String, usize)> {
+    let start = indent_of(line);
+    let rest = &line[start..];
+    if let Some(quote @ ('"' | '\'')) = rest.chars().next() {
+        let end = rest[1..].find(quote)? + 1;
+        let after = &rest[end + 1..];
+        let colon = after.len() - after.trim_start().len();
+        return after[colon..]
+            .starts_with(':')
+            .then(|| (rest[1..end].to_string(), start + end + 1 + colon + 1));
+    }
+    let colon = rest
+        .match_indices(':')
+        .map(|(i, _)| i)
+        .find(|&i| rest[i + 1..].is_empty() || rest[i + 1..].starts_with([' ', '\t']))?;
+    Some((rest[..colon].trim_end().to_string(), start + colon + 1))
+}
+
+/// Split a value from its trailing comment, which keeps its leading space
+fn split_comment(rest: &str) -> (&str, &str) {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '#') if previous.is_whitespace() => {
+                let value = rest[..i].trim_end();
+                return (value, &rest[value.len()..]);
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    (rest, "")
+}
+
+/// `value` as a YAML scalar, or in flow style for lists and maps
+fn render_yaml(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) if !s.contains('\n') => serde_yaml::to_string(s)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_else(|_| value.to_string()),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(format: ConfigFormat, content: &str, spec: &str) -> String {
+        apply_changes(format, content, &parse_changes(spec).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_edits_keep_comments_and_order() {
+        let toml = "# Server\n[server]\nport = 8080 # dev port\nhost = \"localhost\"\n\n[features]\nbeta = true\n";
+        assert_eq!(
+            edit(
+                ConfigFormat::Toml,
+                toml,
+                "set server.port=8081; delete features.beta"
+            ),
+            "# Server\n[server]\nport = 8081 # dev port\nhost = \"localhost\"\n\n[features]\n"
+        );
+
+        let yaml = "# Server\nserver:\n  port: 8080  # dev port\n  host: localhost\nlogging:\n  level: info\n";
+        assert_eq!(
+            edit(
+                ConfigFormat::Yaml,
+                yaml,
+                "set server.port=8081\nset server.tls.enabled=true\ndelete logging.level"
+            ),
+            "# Server\nserver:\n  port: 8081  # dev port\n  host: localhost\n  tls:\n    enabled: true\nlogging:\n"
+        );
+        assert!(apply_changes(
+            ConfigFormat::Yaml,
+            "items:\n  - a\n",
+            &parse_changes("set items.0=b").unwrap()
+        )
+        .is_err());
+
+        let json = "{\n  // dev settings\n  \"server\": {\n    \"port\": 8080,\n    \"host\": \"localhost\"\n  },\n  \"tags\": [\"a\", \"b\"]\n}\n";
+        assert_eq!(
+            edit(
+                ConfigFormat::Json,
+                json,
+                "set server.port=8081; set server.tls={\"enabled\": true}; delete tags.0"
+            ),
+            "{\n  // dev settings\n  \"server\": {\n    \"port\": 8081,\n    \"host\": \"localhost\",\n    \"tls\": {\n      \"enabled\": true\n    }\n  },\n  \"tags\": [\"b\"]\n}\n"
+        );
+        assert_eq!(
+            parse_path("env.\"log.level\".0").unwrap(),
+            vec![
+                Segment::Key("env".to_string()),
+                Segment::Key("log.level".to_string()),
+                Segment::Index(0)
+            ]
+        );
+    }
+}
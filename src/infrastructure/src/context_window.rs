@@ -0,0 +1,790 @@
+use crate::config::ContextConfig;
+use crate::InferenceEngine;
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::sync::Arc;
+
+/// How to shrink an assembled prompt that does not fit the model's context window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TruncationStrategy {
+    /// Drop conversation turns, oldest first
+    DropOldestTurns,
+    /// Replace turns and chunks with a model-generated summary
+    SummarizeContext,
+    /// Drop retrieved chunks, lowest relevance score first
+    #[default]
+    DropLowestScoredChunks,
+}
+
+impl TruncationStrategy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().replace('-', "_").as_str() {
+            "drop_oldest_turns" | "oldest" => Some(Self::DropOldestTurns),
+            "summarize_context" | "summarize" => Some(Self::SummarizeContext),
+            "drop_lowest_scored_chunks" | "lowest_scored" => Some(Self::DropLowestScoredChunks),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::DropOldestTurns => "drop oldest turns",
+            Self::SummarizeContext => "summarize context",
+            Self::DropLowestScoredChunks => "drop lowest-scored chunks",
+        }
+    }
+}
+
+/// Which remembered conversations and learned corrections go into prompts.
+///
+/// The history of the conversation being continued is used under any scope
+/// but `Off`; `ProjectOnly` narrows the search over earlier conversations.
+/// Corrections are stored per project, so only `Off` leaves them out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryScope {
+    /// Memories from every project
+    #[default]
+    All,
+    /// Memories recorded in the current project only
+    ProjectOnly,
+    /// No memories at all
+    Off,
+}
+
+impl MemoryScope {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().replace('_', "-").as_str() {
+            "all" | "on" => Some(Self::All),
+            "project-only" | "project" => Some(Self::ProjectOnly),
+            "off" | "none" => Some(Self::Off),
+            _ => None,
+        }
+    }
+
+    /// Scope for one query: `--no-memory` wins over `--memory`, which wins
+    /// over the configured scope (`CONTEXT_MEMORY`, `all` by default)
+    pub fn resolve(no_memory: bool, requested: Option<&str>, configured: Self) -> Self {
+        if no_memory {
+            return Self::Off;
+        }
+        requested.and_then(Self::parse).unwrap_or(configured)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::ProjectOnly => "project-only",
+            Self::Off => "off",
+        }
+    }
+}
+
+/// Counts tokens with the model's tokenizer when one is configured, otherwise
+/// falls back to the chars-per-token estimate from `ContextConfig`
+#[derive(Clone)]
+pub enum TokenCounter {
+    Tokenizer(Arc<tokenizers::Tokenizer>),
+    Estimate { chars_per_token: f32 },
+}
+
+impl TokenCounter {
+    pub fn from_config(config: &ContextConfig) -> Self {
+        if let Some(path) = &config.tokenizer_path {
+            match tokenizers::Tokenizer::from_file(path) {
+                Ok(tokenizer) => return Self::Tokenizer(Arc::new(tokenizer)),
+                Err(e) => eprintln!(
+                    "Warning: Failed to load tokenizer from {}: {} - using estimates",
+                    path, e
+                ),
+            }
+        }
+        Self::Estimate {
+            chars_per_token: config.token_estimation_ratio.max(1.0),
+        }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Self::Tokenizer(tokenizer) => match tokenizer.encode(text, false) {
+                Ok(encoding) => encoding.len(),
+                Err(_) => (text.len() as f32 / 4.0).ceil() as usize,
+            },
+            Self::Estimate { chars_per_token } => {
+                (text.len() as f32 / chars_per_token).ceil() as usize
+            }
+        }
+    }
+}
+
+/// A retrieved context chunk with its relevance score (higher is more relevant)
+#[derive(Debug, Clone)]
+pub struct ScoredChunk {
+    pub text: String,
+    pub score: f32,
+}
+
+/// Prompt pieces that the window manager may shrink. `fixed` (instructions and
+/// the question itself) is never truncated.
+#[derive(Debug, Clone, Default)]
+pub struct PromptSegments {
+    pub fixed: String,
+    /// Conversation turns, oldest first
+    pub turns: Vec<String>,
+    pub chunks: Vec<ScoredChunk>,
+}
+
+impl PromptSegments {
+    fn token_count(&self, counter: &TokenCounter) -> usize {
+        counter.count(&self.fixed)
+            + self.turns.iter().map(|t| counter.count(t)).sum::<usize>()
+            + self
+                .chunks
+                .iter()
+                .map(|c| counter.count(&c.text))
+                .sum::<usize>()
+    }
+}
+
+/// What was removed to make a prompt fit, shown to the user
+#[derive(Debug, Clone)]
+pub struct TruncationNotice {
+    pub strategy: TruncationStrategy,
+    pub original_tokens: usize,
+    pub final_tokens: usize,
+    pub limit_tokens: usize,
+    pub dropped_turns: usize,
+    pub dropped_chunks: usize,
+    pub summarized: bool,
+}
+
+impl std::fmt::Display for TruncationNotice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Context exceeded the model window ({} > {} tokens); applied '{}'",
+            self.original_tokens,
+            self.limit_tokens,
+            self.strategy.label()
+        )?;
+        if self.summarized {
+            write!(f, ", summarized earlier context")?;
+        }
+        if self.dropped_turns > 0 {
+            write!(f, ", dropped {} oldest turn(s)", self.dropped_turns)?;
+        }
+        if self.dropped_chunks > 0 {
+            write!(
+                f,
+                ", dropped {} low-relevance chunk(s)",
+                self.dropped_chunks
+            )?;
+        }
+        write!(f, " -> {} tokens", self.final_tokens)
+    }
+}
+
+/// Prompt segments after fitting, plus a notice when anything was removed
+#[derive(Debug, Clone)]
+pub struct FittedPrompt {
+    pub segments: PromptSegments,
+    pub notice: Option<TruncationNotice>,
+}
+
+/// Chunks selected to fill a token budget, and what had to give way
+#[derive(Debug, Clone, Default)]
+pub struct PackedChunks {
+    /// Selected chunks, most relevant first
+    pub chunks: Vec<ScoredChunk>,
+    /// Chunks left out entirely
+    pub dropped: usize,
+    /// Chunks cut short to fit
+    pub trimmed: usize,
+    /// Tokens used by the selected chunks and their separators
+    pub tokens: usize,
+}
+
+/// Context tokens contributed by one source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceUsage {
+    /// File path, or a description for context that is not from a file
+    pub source: String,
+    pub chunks: usize,
+    pub tokens: usize,
+}
+
+/// What went into a prompt, for `--verbose` and the TUI context view
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptBreakdown {
+    pub window_tokens: usize,
+    pub budget_tokens: usize,
+    /// Fixed parts such as instructions, history and the question
+    pub sections: Vec<(String, usize)>,
+    /// Retrieved context by source, largest first
+    pub sources: Vec<SourceUsage>,
+    /// Remembered conversations and corrections, one row each
+    pub memories: Vec<(String, usize)>,
+    pub dropped_chunks: usize,
+    pub trimmed_chunks: usize,
+}
+
+/// One line of a [`PromptBreakdown`]; sources are indented under the context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakdownRow {
+    pub label: String,
+    pub tokens: usize,
+    pub indented: bool,
+}
+
+impl PromptBreakdown {
+    pub fn new(window: &ContextWindow) -> Self {
+        Self {
+            window_tokens: window.window_tokens,
+            budget_tokens: window.prompt_budget(),
+            ..Self::default()
+        }
+    }
+
+    /// Count `text` as the section `name`; empty sections are left out
+    pub fn add_section(&mut self, window: &ContextWindow, name: &str, text: &str) {
+        if !text.trim().is_empty() {
+            self.sections
+                .push((name.to_string(), window.count_tokens(text)));
+        }
+    }
+
+    /// Count `text` as a chunk from `source`
+    pub fn add_chunk(&mut self, window: &ContextWindow, source: &str, text: &str) {
+        let tokens = window.count_tokens(text);
+        match self.sources.iter_mut().find(|usage| usage.source == source) {
+            Some(usage) => {
+                usage.chunks += 1;
+                usage.tokens += tokens;
+            }
+            None => self.sources.push(SourceUsage {
+                source: source.to_string(),
+                chunks: 1,
+                tokens,
+            }),
+        }
+        self.sources
+            .sort_by_key(|usage| std::cmp::Reverse(usage.tokens));
+    }
+
+    /// Count `text` as the memory described by `label`
+    pub fn add_memory(&mut self, window: &ContextWindow, label: &str, text: &str) {
+        self.memories
+            .push((label.to_string(), window.count_tokens(text)));
+    }
+
+    pub fn memory_tokens(&self) -> usize {
+        self.memories.iter().map(|(_, tokens)| tokens).sum()
+    }
+
+    pub fn context_tokens(&self) -> usize {
+        self.sources.iter().map(|usage| usage.tokens).sum()
+    }
+
+    pub fn total_tokens(&self) -> usize {
+        self.sections
+            .iter()
+            .map(|(_, tokens)| tokens)
+            .sum::<usize>()
+            + self.memory_tokens()
+            + self.context_tokens()
+    }
+
+    pub fn rows(&self) -> Vec<BreakdownRow> {
+        let mut rows: Vec<BreakdownRow> = self
+            .sections
+            .iter()
+            .map(|(name, tokens)| BreakdownRow {
+                label: name.clone(),
+                tokens: *tokens,
+                indented: false,
+            })
+            .collect();
+        if !self.memories.is_empty() {
+            rows.push(BreakdownRow {
+                label: format!("memories: {} used", self.memories.len()),
+                tokens: self.memory_tokens(),
+                indented: false,
+            });
+            rows.extend(self.memories.iter().map(|(label, tokens)| BreakdownRow {
+                label: label.clone(),
+                tokens: *tokens,
+                indented: true,
+            }));
+        }
+        let chunks: usize = self.sources.iter().map(|usage| usage.chunks).sum();
+        rows.push(BreakdownRow {
+            label: format!(
+                "context: {} chunk(s) from {} source(s)",
+                chunks,
+                self.sources.len()
+            ),
+            tokens: self.context_tokens(),
+            indented: false,
+        });
+        rows.extend(self.sources.iter().map(|usage| BreakdownRow {
+            label: format!("{} ({})", usage.source, usage.chunks),
+            tokens: usage.tokens,
+            indented: true,
+        }));
+        rows
+    }
+}
+
+impl std::fmt::Display for PromptBreakdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Prompt: {} of {} tokens available ({} token window)",
+            self.total_tokens(),
+            self.budget_tokens,
+            self.window_tokens
+        )?;
+        for row in self.rows() {
+            let indent = if row.indented { "    " } else { "  " };
+            writeln!(
+                f,
+                "{}{:<width$} {:>7}",
+                indent,
+                row.label,
+                row.tokens,
+                width = 52 - indent.len()
+            )?;
+        }
+        if self.dropped_chunks > 0 || self.trimmed_chunks > 0 {
+            writeln!(
+                f,
+                "  {} chunk(s) left out, {} trimmed to fit",
+                self.dropped_chunks, self.trimmed_chunks
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Appended to a chunk that was cut short
+const TRIM_MARKER: &str = "\n[... truncated]";
+
+/// Smallest room worth filling with the head of a chunk that does not fit
+const MIN_TRIMMED_CHUNK_TOKENS: usize = 32;
+
+/// Detects context window overflow before a prompt is sent and shrinks it
+#[derive(Clone)]
+pub struct ContextWindow {
+    counter: TokenCounter,
+    window_tokens: usize,
+    reserved_output_tokens: usize,
+    strategy: TruncationStrategy,
+}
+
+impl ContextWindow {
+    pub fn new(config: &ContextConfig, window_tokens: usize) -> Self {
+        Self {
+            counter: TokenCounter::from_config(config),
+            window_tokens,
+            reserved_output_tokens: config.reserved_output_tokens,
+            strategy: config.truncation_strategy,
+        }
+    }
+
+    /// Build using the configured window size, or ask the backend for the model's
+    /// context length, falling back to `max_context_tokens`
+    pub async fn for_engine(config: &ContextConfig, engine: &InferenceEngine) -> Self {
+        let window_tokens = match config.model_context_tokens {
+            Some(tokens) => tokens,
+            None => engine
+                .context_length()
+                .await
+                .unwrap_or(config.max_context_tokens),
+        };
+        Self::new(config, window_tokens)
+    }
+
+    pub fn strategy(&self) -> TruncationStrategy {
+        self.strategy
+    }
+
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.counter.count(text)
+    }
+
+    /// Tokens available for the prompt once room for the answer is reserved
+    pub fn prompt_budget(&self) -> usize {
+        self.window_tokens
+            .saturating_sub(self.reserved_output_tokens)
+            .max(1)
+    }
+
+    pub fn fits(&self, prompt: &str) -> bool {
+        self.counter.count(prompt) <= self.prompt_budget()
+    }
+
+    /// Shrink `segments` using the configured strategy. Summarization needs the
+    /// engine; the drop strategies are used as a fallback if it fails or is not enough.
+    pub async fn fit(&self, segments: PromptSegments, engine: &InferenceEngine) -> FittedPrompt {
+        let budget = self.prompt_budget();
+        let original_tokens = segments.token_count(&self.counter);
+        if original_tokens <= budget {
+            return FittedPrompt {
+                segments,
+                notice: None,
+            };
+        }
+
+        let mut notice = TruncationNotice {
+            strategy: self.strategy,
+            original_tokens,
+            final_tokens: original_tokens,
+            limit_tokens: budget,
+            dropped_turns: 0,
+            dropped_chunks: 0,
+            summarized: false,
+        };
+
+        let mut segments = segments;
+        if self.strategy == TruncationStrategy::SummarizeContext {
+            match self.summarize(&segments, engine).await {
+                Ok(summary) => {
+                    segments.turns = vec![format!("Summary of earlier context:\n{}", summary)];
+                    segments.chunks.clear();
+                    notice.summarized = true;
+                }
+                Err(e) => eprintln!("Warning: Context summarization failed: {}", e),
+            }
+        }
+
+        let segments = self.drop_until_fits(segments, &mut notice);
+        FittedPrompt {
+            segments,
+            notice: Some(notice),
+        }
+    }
+
+    /// Apply the drop strategies without any model calls
+    pub fn truncate(&self, segments: PromptSegments) -> FittedPrompt {
+        let budget = self.prompt_budget();
+        let original_tokens = segments.token_count(&self.counter);
+        if original_tokens <= budget {
+            return FittedPrompt {
+                segments,
+                notice: None,
+            };
+        }
+
+        let mut notice = TruncationNotice {
+            strategy: self.strategy,
+            original_tokens,
+            final_tokens: original_tokens,
+            limit_tokens: budget,
+            dropped_turns: 0,
+            dropped_chunks: 0,
+            summarized: false,
+        };
+        let segments = self.drop_until_fits(segments, &mut notice);
+        FittedPrompt {
+            segments,
+            notice: Some(notice),
+        }
+    }
+
+    /// Greedily fill `budget` tokens with the chunks that carry the most
+    /// relevance per token. A chunk that does not fit whole is cut at a
+    /// syntactic boundary when enough room remains; chunks are joined with a
+    /// blank line, which is counted too.
+    pub fn pack_chunks(&self, chunks: Vec<ScoredChunk>, budget: usize) -> PackedChunks {
+        let separator = self.counter.count("\n\n");
+        let mut candidates: Vec<(ScoredChunk, usize)> = chunks
+            .into_iter()
+            .map(|chunk| {
+                let tokens = self.counter.count(&chunk.text).max(1);
+                (chunk, tokens)
+            })
+            .collect();
+        let density =
+            |(chunk, tokens): &(ScoredChunk, usize)| chunk.score.max(f32::EPSILON) / *tokens as f32;
+        candidates.sort_by(|a, b| density(b).total_cmp(&density(a)));
+
+        let mut packed = PackedChunks::default();
+        for (chunk, tokens) in candidates {
+            let remaining = budget.saturating_sub(packed.tokens + separator);
+            if tokens <= remaining {
+                packed.tokens += tokens + separator;
+                packed.chunks.push(chunk);
+                continue;
+            }
+            let trimmed = (remaining >= MIN_TRIMMED_CHUNK_TOKENS)
+                .then(|| self.trim_to_tokens(&chunk.text, remaining))
+                .flatten();
+            match trimmed {
+                Some(text) => {
+                    packed.tokens += self.counter.count(&text) + separator;
+                    packed.chunks.push(ScoredChunk {
+                        text,
+                        score: chunk.score,
+                    });
+                    packed.trimmed += 1;
+                }
+                None => packed.dropped += 1,
+            }
+        }
+
+        packed.chunks.sort_by(|a, b| b.score.total_cmp(&a.score));
+        packed
+    }
+
+    /// Longest head of `text` within `max_tokens`, ending after a blank line
+    /// or a line closing a statement or block when one is reasonably close to
+    /// the limit, otherwise at a line break
+    fn trim_to_tokens(&self, text: &str, max_tokens: usize) -> Option<String> {
+        let limit = max_tokens.saturating_sub(self.counter.count(TRIM_MARKER));
+        let mut end = 0;
+        let mut boundary = 0;
+        for line in text.split_inclusive('\n') {
+            if self.counter.count(&text[..end + line.len()]) > limit {
+                break;
+            }
+            end += line.len();
+            let line = line.trim();
+            if line.is_empty() || line.ends_with(['}', ';']) {
+                boundary = end;
+            }
+        }
+        let cut = if boundary * 2 >= end { boundary } else { end };
+        let head = text[..cut].trim_end();
+        (!head.is_empty()).then(|| format!("{}{}", head, TRIM_MARKER))
+    }
+
+    fn drop_until_fits(
+        &self,
+        mut segments: PromptSegments,
+        notice: &mut TruncationNotice,
+    ) -> PromptSegments {
+        let budget = self.prompt_budget();
+        let mut tokens = segments.token_count(&self.counter);
+
+        // Lowest score last so `pop` removes it
+        segments.chunks.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let drop_turns_first = self.strategy == TruncationStrategy::DropOldestTurns;
+        while tokens > budget && (!segments.turns.is_empty() || !segments.chunks.is_empty()) {
+            let drop_turn = if drop_turns_first {
+                !segments.turns.is_empty()
+            } else {
+                segments.chunks.is_empty()
+            };
+
+            if drop_turn {
+                let turn = segments.turns.remove(0);
+                tokens -= self.counter.count(&turn).min(tokens);
+                notice.dropped_turns += 1;
+            } else if let Some(chunk) = segments.chunks.pop() {
+                tokens -= self.counter.count(&chunk.text).min(tokens);
+                notice.dropped_chunks += 1;
+            }
+        }
+
+        notice.final_tokens = segments.token_count(&self.counter);
+        segments
+    }
+
+    async fn summarize(
+        &self,
+        segments: &PromptSegments,
+        engine: &InferenceEngine,
+    ) -> Result<String> {
+        // The summarization request itself must fit, so feed it only what does
+        let mut material = String::new();
+        let budget = self.prompt_budget().saturating_sub(200);
+        let mut used = 0;
+        let pieces = segments
+            .turns
+            .iter()
+            .map(String::as_str)
+            .chain(segments.chunks.iter().map(|c| c.text.as_str()));
+        for piece in pieces {
+            let tokens = self.counter.count(piece);
+            if used + tokens > budget {
+                break;
+            }
+            used += tokens;
+            material.push_str(piece);
+            material.push_str("\n\n");
+        }
+
+        let target_tokens = (self.prompt_budget() / 4).max(64);
+        let prompt = format!(
+            "Summarize the following context in at most {} tokens. Keep file names, identifiers, decisions and facts; drop pleasantries.\n\nCONTEXT:\n{}\nSUMMARY:",
+            target_tokens, material
+        );
+        Ok(engine.generate(&prompt).await?.trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(strategy: TruncationStrategy, window_tokens: usize) -> ContextWindow {
+        let config = ContextConfig {
+            token_estimation_ratio: 1.0,
+            reserved_output_tokens: 0,
+            truncation_strategy: strategy,
+            ..ContextConfig::default()
+        };
+        ContextWindow::new(&config, window_tokens)
+    }
+
+    fn chunk(text: &str, score: f32) -> ScoredChunk {
+        ScoredChunk {
+            text: text.to_string(),
+            score,
+        }
+    }
+
+    #[test]
+    fn test_fitting_prompt_is_untouched() {
+        let window = window(TruncationStrategy::DropOldestTurns, 100);
+        let fitted = window.truncate(PromptSegments {
+            fixed: "question".to_string(),
+            turns: vec!["turn".to_string()],
+            chunks: vec![chunk("chunk", 1.0)],
+        });
+        assert!(fitted.notice.is_none());
+        assert_eq!(fitted.segments.turns.len(), 1);
+    }
+
+    #[test]
+    fn test_drop_oldest_turns() {
+        let window = window(TruncationStrategy::DropOldestTurns, 25);
+        let fitted = window.truncate(PromptSegments {
+            fixed: "0123456789".to_string(),
+            turns: vec!["oldest....".to_string(), "newest....".to_string()],
+            chunks: vec![],
+        });
+        let notice = fitted.notice.expect("should truncate");
+        assert_eq!(notice.dropped_turns, 1);
+        assert_eq!(fitted.segments.turns, vec!["newest....".to_string()]);
+    }
+
+    #[test]
+    fn test_drop_lowest_scored_chunks() {
+        let window = window(TruncationStrategy::DropLowestScoredChunks, 25);
+        let fitted = window.truncate(PromptSegments {
+            fixed: "0123456789".to_string(),
+            turns: vec![],
+            chunks: vec![chunk("relevant..", 0.9), chunk("irrelevant", 0.1)],
+        });
+        let notice = fitted.notice.expect("should truncate");
+        assert_eq!(notice.dropped_chunks, 1);
+        assert_eq!(fitted.segments.chunks[0].text, "relevant..");
+    }
+
+    #[test]
+    fn test_strategy_parsing() {
+        assert_eq!(
+            TruncationStrategy::parse("drop-oldest-turns"),
+            Some(TruncationStrategy::DropOldestTurns)
+        );
+        assert_eq!(
+            TruncationStrategy::parse("summarize"),
+            Some(TruncationStrategy::SummarizeContext)
+        );
+        assert_eq!(TruncationStrategy::parse("bogus"), None);
+        assert_eq!(
+            MemoryScope::parse("project_only"),
+            Some(MemoryScope::ProjectOnly)
+        );
+        assert_eq!(MemoryScope::parse("off"), Some(MemoryScope::Off));
+    }
+
+    #[test]
+    fn test_memory_scope_precedence() {
+        let configured = MemoryScope::ProjectOnly;
+        assert_eq!(MemoryScope::resolve(false, None, configured), configured);
+        assert_eq!(
+            MemoryScope::resolve(false, Some("all"), configured),
+            MemoryScope::All
+        );
+        assert_eq!(
+            MemoryScope::resolve(true, Some("all"), configured),
+            MemoryScope::Off
+        );
+        assert_eq!(
+            MemoryScope::resolve(false, Some("bogus"), configured),
+            configured
+        );
+    }
+
+    #[test]
+    fn test_pack_prefers_relevance_per_token() {
+        let window = window(TruncationStrategy::DropLowestScoredChunks, 1000);
+        let packed = window.pack_chunks(
+            vec![
+                chunk(&"x".repeat(60), 0.9),
+                chunk(&"y".repeat(20), 0.6),
+                chunk(&"z".repeat(20), 0.5),
+            ],
+            50,
+        );
+        // Two short chunks beat one long chunk with a slightly higher score
+        let texts: Vec<char> = packed
+            .chunks
+            .iter()
+            .map(|c| c.text.chars().next().unwrap())
+            .collect();
+        assert_eq!(texts, vec!['y', 'z']);
+        assert_eq!(packed.dropped, 1);
+        assert!(packed.tokens <= 50);
+    }
+
+    #[test]
+    fn test_pack_trims_at_syntactic_boundary() {
+        let window = window(TruncationStrategy::DropLowestScoredChunks, 1000);
+        let text =
+            "FILE: src/lib.rs\nfn a() {\n    one();\n}\n\nfn b() {\n    two();\n    three();\n}\n";
+        let packed = window.pack_chunks(vec![chunk(text, 0.8)], 60);
+        assert_eq!(packed.trimmed, 1);
+        let trimmed = &packed.chunks[0].text;
+        assert!(trimmed.ends_with("}\n[... truncated]"), "{}", trimmed);
+        assert!(trimmed.contains("fn a()"));
+        assert!(!trimmed.contains("two()"));
+        assert!(packed.tokens <= 60);
+    }
+
+    #[test]
+    fn test_prompt_breakdown_groups_context_by_source() {
+        let window = window(TruncationStrategy::DropLowestScoredChunks, 1000);
+        let mut breakdown = PromptBreakdown::new(&window);
+        breakdown.add_section(&window, "instructions", "0123456789");
+        breakdown.add_section(&window, "history", "  ");
+        breakdown.add_chunk(&window, "src/a.rs", "aaaa");
+        breakdown.add_chunk(&window, "src/b.rs", "bbbbbbbb");
+        breakdown.add_chunk(&window, "src/a.rs", "aaaaaa");
+        breakdown.add_memory(&window, "feedback on \"why\"", "mmmm");
+
+        assert_eq!(breakdown.total_tokens(), 32);
+        let rows: Vec<(String, usize)> = breakdown
+            .rows()
+            .into_iter()
+            .map(|row| (row.label, row.tokens))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("instructions".to_string(), 10),
+                ("memories: 1 used".to_string(), 4),
+                ("feedback on \"why\"".to_string(), 4),
+                ("context: 3 chunk(s) from 2 source(s)".to_string(), 18),
+                ("src/a.rs (2)".to_string(), 10),
+                ("src/b.rs (1)".to_string(), 8),
+            ]
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Credentials kept out of the config file
+//!
+//! API keys and the web server token are stored in the OS keychain (Secret
+//! Service on Linux, Keychain on macOS, the DPAPI-protected Credential
+//! Manager on Windows) or in a HashiCorp Vault KV v2 engine, as chosen by
+//! `credentials` in the config:
+//!
+//! ```yaml
+//! credentials:
+//!   backend: vault
+//!   address: https://vault.example.com
+//!   path: teams/dev/bro
+//! ```
+//!
+//! A `BRO_<NAME>` environment variable, e.g. `BRO_WEB_TOKEN`, takes precedence
+//! over the store, for machines without a keychain.
+
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+
+/// Keychain service the credentials are stored under
+const KEYCHAIN_SERVICE: &str = "bro";
+
+/// Credentials bro reads, with what each is for
+pub const KNOWN_CREDENTIALS: &[(&str, &str)] = &[
+    (
+        "web-token",
+        "Bearer token clients must send to the web server",
+    ),
+    ("openai-api-key", "API key for OpenAI-compatible backends"),
+    ("anthropic-api-key", "API key for Anthropic backends"),
+    (
+        "session-key",
+        "Key sessions are encrypted with, created on first use",
+    ),
+];
+
+/// Credential for the web server token
+pub const WEB_TOKEN: &str = "web-token";
+
+/// Credential the session encryption keys are derived from
+pub const SESSION_KEY: &str = "session-key";
+
+/// Where credentials are stored, `credentials` in the config
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum CredentialSettings {
+    #[default]
+    Keychain,
+    Vault {
+        address: String,
+        /// Mount point of the KV v2 engine
+        #[serde(default = "default_mount")]
+        mount: String,
+        /// Secret path under the mount; each credential is a secret below it
+        #[serde(default = "default_vault_path")]
+        path: String,
+        /// Environment variable holding the Vault token
+        #[serde(default = "default_token_env")]
+        token_env: String,
+    },
+}
+
+fn default_mount() -> String {
+    "secret".to_string()
+}
+
+fn default_vault_path() -> String {
+    "bro".to_string()
+}
+
+fn default_token_env() -> String {
+    "VAULT_TOKEN".to_string()
+}
+
+impl CredentialSettings {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Keychain => "OS keychain".to_string(),
+            Self::Vault {
+                address,
+                mount,
+                path,
+                ..
+            } => format!("Vault {}/v1/{}/data/{}", address, mount, path),
+        }
+    }
+}
+
+/// Environment variable overriding a credential, e.g. `BRO_WEB_TOKEN`
+pub fn env_var(name: &str) -> String {
+    format!("BRO_{}", name.to_uppercase().replace('-', "_"))
+}
+
+/// Credential names are lowercase words joined by dashes
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid credential name '{}'; use lowercase letters, digits and dashes",
+            name
+        ));
+    }
+    Ok(())
+}
+
+pub struct CredentialStore {
+    settings: CredentialSettings,
+    http: reqwest::Client,
+}
+
+impl CredentialStore {
+    pub fn new(settings: CredentialSettings) -> Self {
+        Self {
+            settings,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &CredentialSettings {
+        &self.settings
+    }
+
+    /// The credential from the environment or the store
+    pub async fn get(&self, name: &str) -> Result<Option<String>> {
+        validate_name(name)?;
+        if let Ok(value) = std::env::var(env_var(name)) {
+            return Ok(Some(value));
+        }
+        match &self.settings {
+            CredentialSettings::Keychain => {
+                match keychain(name, |entry| entry.get_password()).await? {
+                    Ok(value) => Ok(Some(value)),
+                    Err(keyring::Error::NoEntry) => Ok(None),
+                    Err(e) => Err(keychain_error(e)),
+                }
+            }
+            CredentialSettings::Vault { .. } => {
+                let response = self
+                    .vault(reqwest::Method::GET, "data", name)?
+                    .send()
+                    .await?;
+                if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                let body: serde_json::Value = vault_response(response).await?.json().await?;
+                Ok(body["data"]["data"]["value"].as_str().map(str::to_string))
+            }
+        }
+    }
+
+    pub async fn set(&self, name: &str, value: &str) -> Result<()> {
+        validate_name(name)?;
+        match &self.settings {
+            CredentialSettings::Keychain => {
+                let value = value.to_string();
+                keychain(name, move |entry| entry.set_password(&value))
+                    .await?
+                    .map_err(keychain_error)
+            }
+            CredentialSettings::Vault { .. } => {
+                let request = self
+                    .vault(reqwest::Method::POST, "data", name)?
+                    .json(&serde_json::json!({ "data": { "value": value } }));
+                vault_response(request.send().await?).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Delete the stored credential; false when there was none
+    pub async fn delete(&self, name: &str) -> Result<bool> {
+        validate_name(name)?;
+        match &self.settings {
+            CredentialSettings::Keychain => {
+                match keychain(name, |entry| entry.delete_credential()).await? {
+                    Ok(()) => Ok(true),
+                    Err(keyring::Error::NoEntry) => Ok(false),
+                    Err(e) => Err(keychain_error(e)),
+                }
+            }
+            CredentialSettings::Vault { .. } => {
+                // Deleting the metadata removes every version
+                let response = self
+                    .vault(reqwest::Method::DELETE, "metadata", name)?
+                    .send()
+                    .await?;
+                if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Ok(false);
+                }
+                vault_response(response).await?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// [`CredentialStore::get`] for sync callers, which may themselves run
+    /// on an async runtime
+    pub fn get_blocking(&self, name: &str) -> Result<Option<String>> {
+        blocking(self.get(name))
+    }
+
+    pub fn set_blocking(&self, name: &str, value: &str) -> Result<()> {
+        blocking(self.set(name, value))
+    }
+
+    fn vault(
+        &self,
+        method: reqwest::Method,
+        kind: &str,
+        name: &str,
+    ) -> Result<reqwest::RequestBuilder> {
+        let CredentialSettings::Vault {
+            address,
+            mount,
+            path,
+            token_env,
+        } = &self.settings
+        else {
+            unreachable!("only called for the Vault backend");
+        };
+        let token = std::env::var(token_env)
+            .map_err(|_| anyhow::anyhow!("Set {} to a Vault token", token_env))?;
+        let url = format!(
+            "{}/v1/{}/{}/{}/{}",
+            address.trim_end_matches('/'),
+            mount.trim_matches('/'),
+            kind,
+            path.trim_matches('/'),
+            name
+        );
+        Ok(self
+            .http
+            .request(method, url)
+            .header("X-Vault-Token", token))
+    }
+}
+
+/// Drive `future` on a thread of its own, outside any runtime of the caller
+fn blocking<T: Send>(future: impl std::future::Future<Output = Result<T>> + Send) -> Result<T> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?
+                    .block_on(future)
+            })
+            .join()
+            .map_err(|_| anyhow::anyhow!("Credential lookup panicked"))?
+    })
+}
+
+/// Run a keychain call on the blocking pool; platform keychains block, and
+/// Secret Service runs its own event loop
+async fn keychain<T: Send + 'static>(
+    name: &str,
+    call: impl FnOnce(&keyring::Entry) -> keyring::Result<T> + Send + 'static,
+) -> Result<keyring::Result<T>> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, name).map_err(keychain_error)?;
+    Ok(tokio::task::spawn_blocking(move || call(&entry)).await?)
+}
+
+fn keychain_error(error: keyring::Error) -> anyhow::Error {
+    anyhow::anyhow!("OS keychain unavailable: {}", error)
+}
+
+async fn vault_response(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    // Vault explains failures in an `errors` list
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    let errors = body["errors"]
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(|e| e.as_str())
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+        .unwrap_or_default();
+    Err(anyhow::anyhow!("Vault returned {}: {}", status, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_settings_and_environment_overrides() {
+        assert!(validate_name("web-token").is_ok());
+        assert!(validate_name("Web_Token").is_err());
+        assert!(validate_name("-token").is_err());
+        assert_eq!(env_var("openai-api-key"), "BRO_OPENAI_API_KEY");
+
+        let vault: CredentialSettings =
+            serde_yaml::from_str("backend: vault\naddress: https://vault.example.com\n").unwrap();
+        assert_eq!(
+            vault.describe(),
+            "Vault https://vault.example.com/v1/secret/data/bro"
+        );
+        let keychain: CredentialSettings = serde_yaml::from_str("backend: keychain").unwrap();
+        assert_eq!(keychain, CredentialSettings::Keychain);
+    }
+}
@@ -0,0 +1,308 @@
+//! Adding and removing dependencies through the project's package manager
+//!
+//! Asked for "serde support", models hand-edit Cargo.toml with a version
+//! they made up. Dependency operations run `cargo add`, `npm install --save`
+//! or `pip install` instead, which resolve a real version and keep the
+//! lockfile in step. pip has no manifest of its own, so the requirements
+//! file is updated here with the version pip installed.
+
+use crate::test_gate::TestCommand;
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ecosystem {
+    Cargo,
+    Npm,
+    Pip,
+}
+
+impl Ecosystem {
+    /// Package manager owning `manifest`, told by its file name
+    pub fn of(manifest: &Path) -> Option<Self> {
+        let name = manifest.file_name()?.to_str()?;
+        match name {
+            "Cargo.toml" => Some(Ecosystem::Cargo),
+            "package.json" => Some(Ecosystem::Npm),
+            _ if name.starts_with("requirements") && name.ends_with(".txt") => Some(Ecosystem::Pip),
+            _ => None,
+        }
+    }
+
+    /// Files next to the manifest the package manager rewrites as well
+    pub fn lockfiles(self) -> &'static [&'static str] {
+        match self {
+            Ecosystem::Cargo => &["Cargo.lock"],
+            Ecosystem::Npm => &["package-lock.json", "npm-shrinkwrap.json"],
+            Ecosystem::Pip => &[],
+        }
+    }
+}
+
+/// Files an operation on `manifest` may change: the manifest and the
+/// lockfiles beside it, including the workspace's Cargo.lock
+pub fn touched_files(manifest: &Path) -> Vec<PathBuf> {
+    let mut files = vec![manifest.to_path_buf()];
+    let Some(ecosystem) = Ecosystem::of(manifest) else {
+        return files;
+    };
+    for dir in manifest.ancestors().skip(1) {
+        for lockfile in ecosystem.lockfiles() {
+            let path = dir.join(lockfile);
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+        if ecosystem != Ecosystem::Cargo || files.len() > 1 {
+            break;
+        }
+    }
+    files
+}
+
+/// Command adding `name` to `manifest`, at `version` or the latest; it runs
+/// in the manifest's directory
+pub fn add_command(manifest: &Path, name: &str, version: Option<&str>) -> Result<TestCommand> {
+    check_argument("package name", name)?;
+    if let Some(version) = version {
+        check_argument("version", version)?;
+    }
+    match ecosystem(manifest)? {
+        Ecosystem::Cargo => {
+            let spec = match version {
+                Some(version) => format!("{}@{}", name, version),
+                None => name.to_string(),
+            };
+            Ok(TestCommand::new(
+                "cargo",
+                &["add", &spec, "--manifest-path", &manifest_name(manifest)],
+            ))
+        }
+        Ecosystem::Npm => {
+            let spec = match version {
+                Some(version) => format!("{}@{}", name, version),
+                None => name.to_string(),
+            };
+            Ok(TestCommand::new("npm", &["install", "--save", &spec]))
+        }
+        Ecosystem::Pip => Ok(TestCommand::new(
+            "python3",
+            &["-m", "pip", "install", &pip_requirement(name, version)],
+        )),
+    }
+}
+
+/// Command removing `name` from `manifest`; None for pip, where only the
+/// requirements file changes and the installed package is left alone
+pub fn remove_command(manifest: &Path, name: &str) -> Result<Option<TestCommand>> {
+    check_argument("package name", name)?;
+    Ok(match ecosystem(manifest)? {
+        Ecosystem::Cargo => Some(TestCommand::new(
+            "cargo",
+            &["remove", name, "--manifest-path", &manifest_name(manifest)],
+        )),
+        Ecosystem::Npm => Some(TestCommand::new("npm", &["uninstall", "--save", name])),
+        Ecosystem::Pip => None,
+    })
+}
+
+/// `manifest` as seen from its own directory, where the commands run
+fn manifest_name(manifest: &Path) -> String {
+    manifest
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+}
+
+/// Add `name` to `manifest` with its package manager
+pub async fn add(manifest: &Path, name: &str, version: Option<&str>) -> Result<()> {
+    let command = add_command(manifest, name, version)?;
+    run(&command, manifest).await?;
+    if ecosystem(manifest)? == Ecosystem::Pip {
+        let pinned = match version {
+            Some(version) => pip_requirement(name, Some(version)),
+            None => pip_requirement(name, installed_version(manifest, name).await.as_deref()),
+        };
+        let content = std::fs::read_to_string(manifest).unwrap_or_default();
+        std::fs::write(manifest, requirements_with(&content, name, &pinned))?;
+    }
+    Ok(())
+}
+
+/// Remove `name` from `manifest` with its package manager
+pub async fn remove(manifest: &Path, name: &str) -> Result<()> {
+    match remove_command(manifest, name)? {
+        Some(command) => run(&command, manifest).await,
+        None => {
+            let content = std::fs::read_to_string(manifest)?;
+            let updated = requirements_without(&content, name)
+                .ok_or_else(|| anyhow::anyhow!("{} is not in {}", name, manifest.display()))?;
+            std::fs::write(manifest, updated)?;
+            Ok(())
+        }
+    }
+}
+
+/// `content` of a requirements file with `requirement` replacing the line
+/// for `name`, or appended when there is none
+pub fn requirements_with(content: &str, name: &str, requirement: &str) -> String {
+    let mut replaced = false;
+    let mut lines: Vec<&str> = content
+        .lines()
+        .map(|line| {
+            if !replaced && requirement_is(line, name) {
+                replaced = true;
+                requirement
+            } else {
+                line
+            }
+        })
+        .collect();
+    if !replaced {
+        lines.push(requirement);
+    }
+    lines.join("\n") + "\n"
+}
+
+/// `content` of a requirements file without the line for `name`; None when
+/// it has none
+pub fn requirements_without(content: &str, name: &str) -> Option<String> {
+    let kept: Vec<&str> = content
+        .lines()
+        .filter(|line| !requirement_is(line, name))
+        .collect();
+    if kept.len() == content.lines().count() {
+        return None;
+    }
+    Some(kept.iter().map(|line| format!("{}\n", line)).collect())
+}
+
+fn ecosystem(manifest: &Path) -> Result<Ecosystem> {
+    Ecosystem::of(manifest).ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} is not a Cargo.toml, package.json or requirements file",
+            manifest.display()
+        )
+    })
+}
+
+/// Names and versions come from the model; refuse anything a package
+/// manager would take as an option or that is not one argument
+fn check_argument(what: &str, value: &str) -> Result<()> {
+    if value.is_empty() || value.starts_with('-') || value.chars().any(char::is_whitespace) {
+        anyhow::bail!("Invalid {}: {:?}", what, value);
+    }
+    Ok(())
+}
+
+/// `name==version`, or `name` plus the constraint when `version` is one
+fn pip_requirement(name: &str, version: Option<&str>) -> String {
+    match version {
+        Some(version) if version.starts_with(['=', '<', '>', '~', '!']) => {
+            format!("{}{}", name, version)
+        }
+        Some(version) => format!("{}=={}", name, version),
+        None => name.to_string(),
+    }
+}
+
+/// Whether a requirements line is about `name`, compared the way pip does
+fn requirement_is(line: &str, name: &str) -> bool {
+    let line = line.split('#').next().unwrap_or_default().trim();
+    let end = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(line.len());
+    !line[..end].is_empty() && normalize(&line[..end]) == normalize(name)
+}
+
+/// PEP 503 name: lowercase, with runs of `-`, `_` and `.` as one `-`
+fn normalize(name: &str) -> String {
+    let mut normalized = String::new();
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+    normalized
+}
+
+async fn run(command: &TestCommand, manifest: &Path) -> Result<()> {
+    let dir = match manifest.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let run = command.run(dir).await?;
+    if !run.passed {
+        anyhow::bail!("`{}` failed:\n{}", command, run.output);
+    }
+    Ok(())
+}
+
+/// Version pip reports for `name`, to pin it in the requirements file
+async fn installed_version(manifest: &Path, name: &str) -> Option<String> {
+    let dir = manifest.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let run = TestCommand::new("python3", &["-m", "pip", "show", name])
+        .run(dir.unwrap_or(Path::new(".")))
+        .await
+        .ok()?;
+    run.output
+        .lines()
+        .find_map(|line| line.strip_prefix("Version:"))
+        .map(|version| version.trim().to_string())
+        .filter(|version| run.passed && !version.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_package_manager_commands() {
+        let add = |manifest: &str, version| {
+            add_command(Path::new(manifest), "serde", version)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            add("crates/core/Cargo.toml", Some("1.0")),
+            "cargo add serde@1.0 --manifest-path Cargo.toml"
+        );
+        assert_eq!(add("web/package.json", None), "npm install --save serde");
+        assert_eq!(
+            add("requirements-dev.txt", Some(">=1.2")),
+            "python3 -m pip install serde>=1.2"
+        );
+        assert!(add_command(Path::new("Cargo.toml"), "--git=evil", None).is_err());
+        assert!(add_command(Path::new("setup.py"), "serde", None).is_err());
+        assert_eq!(
+            remove_command(Path::new("package.json"), "left-pad")
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "npm uninstall --save left-pad"
+        );
+        assert_eq!(
+            remove_command(Path::new("requirements.txt"), "flask").unwrap(),
+            None
+        );
+
+        let requirements = "# web\nFlask==2.0  # pinned\nrequests>=2\n";
+        assert_eq!(
+            requirements_with(requirements, "flask", "flask==3.0.3"),
+            "# web\nflask==3.0.3\nrequests>=2\n"
+        );
+        assert_eq!(
+            requirements_with("requests>=2", "python_dateutil", "python-dateutil==2.9"),
+            "requests>=2\npython-dateutil==2.9\n"
+        );
+        assert_eq!(
+            requirements_without(requirements, "Requests").unwrap(),
+            "# web\nFlask==2.0  # pinned\n"
+        );
+        assert_eq!(requirements_without(requirements, "django"), None);
+        assert_eq!(normalize("Python__Dateutil.x"), "python-dateutil-x");
+    }
+}
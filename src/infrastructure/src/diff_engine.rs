@@ -0,0 +1,408 @@
+//! Diffs of planned file updates, in three levels of detail
+//!
+//! `line` is a plain unified diff. `word` shows replaced lines once, with the
+//! removed and added words marked inside them, which keeps one-word edits in
+//! long lines readable. `semantic` hides formatting-only changes: lines that
+//! differ only in whitespace count as unchanged, and a rewrapped block whose
+//! tokens are the same (parsed with tree-sitter where the language is
+//! supported) is shown as context, so what remains are the real changes.
+
+use crate::ast_parser::AstParser;
+use serde::{Deserialize, Serialize};
+use similar::{Algorithm, DiffOp, DiffTag, TextDiff};
+use std::path::Path;
+
+/// Unchanged lines shown around each change
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffEngine {
+    #[default]
+    Line,
+    Word,
+    Semantic,
+}
+
+impl DiffEngine {
+    pub const ALL: [DiffEngine; 3] = [DiffEngine::Line, DiffEngine::Word, DiffEngine::Semantic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DiffEngine::Line => "line",
+            DiffEngine::Word => "word",
+            DiffEngine::Semantic => "semantic",
+        }
+    }
+}
+
+impl std::str::FromStr for DiffEngine {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        DiffEngine::ALL
+            .into_iter()
+            .find(|engine| engine.name() == name.trim().to_lowercase())
+            .ok_or_else(|| {
+                anyhow::anyhow!("Unknown diff engine '{}'; use line, word or semantic", name)
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+    /// `--- a/path` and `+++ b/path`
+    Header,
+    /// `@@ -1,3 +1,4 @@`
+    Hunk,
+    Context,
+    Removed,
+    Added,
+    /// A replaced line with its removed and added words marked
+    Changed,
+    /// Remark about the diff itself
+    Note,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanChange {
+    Same,
+    Removed,
+    Added,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffSpan {
+    pub text: String,
+    pub change: SpanChange,
+}
+
+/// One line of a diff, without its trailing newline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub spans: Vec<DiffSpan>,
+}
+
+impl DiffLine {
+    fn new(kind: DiffLineKind, text: &str) -> Self {
+        Self {
+            kind,
+            spans: vec![DiffSpan {
+                text: text.trim_end_matches(['\n', '\r']).to_string(),
+                change: SpanChange::Same,
+            }],
+        }
+    }
+
+    /// Marker in front of the line: `-`, `+`, `~` or a space for context
+    pub fn prefix(&self) -> &'static str {
+        match self.kind {
+            DiffLineKind::Context => " ",
+            DiffLineKind::Removed => "-",
+            DiffLineKind::Added => "+",
+            DiffLineKind::Changed => "~",
+            DiffLineKind::Header | DiffLineKind::Hunk | DiffLineKind::Note => "",
+        }
+    }
+
+    /// The line as plain text, words marked `[-removed-]{+added+}`
+    pub fn plain(&self) -> String {
+        let mut text = self.prefix().to_string();
+        for span in &self.spans {
+            match span.change {
+                SpanChange::Same => text.push_str(&span.text),
+                SpanChange::Removed => text.push_str(&format!("[-{}-]", span.text)),
+                SpanChange::Added => text.push_str(&format!("{{+{}+}}", span.text)),
+            }
+        }
+        text
+    }
+}
+
+/// A rendered line and how many lines of the old and new file it stands for;
+/// hidden rows only keep the line numbers right
+struct Row {
+    line: Option<DiffLine>,
+    old: usize,
+    new: usize,
+    changed: bool,
+}
+
+impl Row {
+    fn shown(line: DiffLine, old: usize, new: usize) -> Self {
+        let changed = line.kind != DiffLineKind::Context;
+        Self {
+            line: Some(line),
+            old,
+            new,
+            changed,
+        }
+    }
+}
+
+/// Diff of `path` going from `old` to `new`; empty when nothing changed that
+/// the engine shows
+pub fn diff(engine: DiffEngine, path: &Path, old: &str, new: &str) -> Vec<DiffLine> {
+    if old == new {
+        return Vec::new();
+    }
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let rows = match engine {
+        DiffEngine::Line => line_rows(&old_lines, &new_lines),
+        DiffEngine::Word => word_rows(&old_lines, &new_lines),
+        DiffEngine::Semantic => semantic_rows(path, &old_lines, &new_lines),
+    };
+    if !rows.iter().any(|row| row.changed) {
+        return vec![DiffLine::new(
+            DiffLineKind::Note,
+            "(formatting-only changes)",
+        )];
+    }
+
+    let name = path.display();
+    let mut lines = vec![
+        DiffLine::new(DiffLineKind::Header, &format!("--- a/{}", name)),
+        DiffLine::new(DiffLineKind::Header, &format!("+++ b/{}", name)),
+    ];
+    lines.extend(hunks(rows));
+    lines
+}
+
+fn line_ops(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    similar::capture_diff_slices(Algorithm::Myers, old, new)
+}
+
+fn line_rows(old: &[&str], new: &[&str]) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for op in line_ops(old, new) {
+        push_op_rows(&mut rows, &op, old, new);
+    }
+    rows
+}
+
+/// Rows of one op as whole removed, added or context lines
+fn push_op_rows(rows: &mut Vec<Row>, op: &DiffOp, old: &[&str], new: &[&str]) {
+    if op.tag() == DiffTag::Equal {
+        for line in &new[op.new_range()] {
+            rows.push(Row::shown(DiffLine::new(DiffLineKind::Context, line), 1, 1));
+        }
+        return;
+    }
+    for line in &old[op.old_range()] {
+        rows.push(Row::shown(DiffLine::new(DiffLineKind::Removed, line), 1, 0));
+    }
+    for line in &new[op.new_range()] {
+        rows.push(Row::shown(DiffLine::new(DiffLineKind::Added, line), 0, 1));
+    }
+}
+
+fn word_rows(old: &[&str], new: &[&str]) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for op in line_ops(old, new) {
+        if op.tag() != DiffTag::Replace {
+            push_op_rows(&mut rows, &op, old, new);
+            continue;
+        }
+        let (before, after) = (old[op.old_range()].concat(), new[op.new_range()].concat());
+        let words = TextDiff::from_words(before.as_str(), after.as_str());
+        let mut line = Vec::new();
+        let mut changed_lines = Vec::new();
+        for change in words.iter_all_changes() {
+            let kind = match change.tag() {
+                similar::ChangeTag::Equal => SpanChange::Same,
+                similar::ChangeTag::Delete => SpanChange::Removed,
+                similar::ChangeTag::Insert => SpanChange::Added,
+            };
+            // Removed line breaks do not break the shown (new) lines
+            let value = match kind {
+                SpanChange::Removed => change.value().replace('\n', " "),
+                _ => change.value().to_string(),
+            };
+            for (i, piece) in value.split('\n').enumerate() {
+                if i > 0 {
+                    changed_lines.push(std::mem::take(&mut line));
+                }
+                if !piece.is_empty() {
+                    line.push(DiffSpan {
+                        text: piece.to_string(),
+                        change: kind,
+                    });
+                }
+            }
+        }
+        if !line.is_empty() {
+            changed_lines.push(line);
+        }
+        // The whole replaced block counts against its first row
+        for (i, spans) in changed_lines.into_iter().enumerate() {
+            let (old_count, new_count) = if i == 0 {
+                (op.old_range().len(), op.new_range().len())
+            } else {
+                (0, 0)
+            };
+            let line = DiffLine {
+                kind: DiffLineKind::Changed,
+                spans,
+            };
+            rows.push(Row::shown(line, old_count, new_count));
+        }
+    }
+    rows
+}
+
+fn semantic_rows(path: &Path, old: &[&str], new: &[&str]) -> Vec<Row> {
+    let normalize = |lines: &[&str]| -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect()
+    };
+    let (old_keys, new_keys) = (normalize(old), normalize(new));
+    let ops = similar::capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys);
+
+    let mut tokenizer = Tokenizer::for_path(path);
+    let mut rows = Vec::new();
+    for op in ops {
+        let formatting_only = op.tag() != DiffTag::Equal
+            && tokenizer.tokens(&old[op.old_range()].concat())
+                == tokenizer.tokens(&new[op.new_range()].concat());
+        if !formatting_only {
+            push_op_rows(&mut rows, &op, old, new);
+            continue;
+        }
+        // Show the new formatting as context; blank lines dropped or added
+        // on one side leave a hidden row so the line numbers stay right
+        let (old_count, new_lines) = (op.old_range().len(), &new[op.new_range()]);
+        if new_lines.is_empty() {
+            rows.push(Row {
+                line: None,
+                old: old_count,
+                new: 0,
+                changed: false,
+            });
+        }
+        for (i, line) in new_lines.iter().enumerate() {
+            let old_share = if i == 0 { old_count } else { 0 };
+            rows.push(Row::shown(
+                DiffLine::new(DiffLineKind::Context, line),
+                old_share,
+                1,
+            ));
+        }
+    }
+    rows
+}
+
+/// Splits code into tokens, ignoring the whitespace between them
+struct Tokenizer {
+    parser: Option<(AstParser, &'static str)>,
+}
+
+impl Tokenizer {
+    fn for_path(path: &Path) -> Self {
+        let parser = AstParser::language_for_path(path)
+            .and_then(|language| Some((AstParser::new().ok()?, language)));
+        Self { parser }
+    }
+
+    fn tokens(&mut self, code: &str) -> Vec<String> {
+        if let Some((parser, language)) = &mut self.parser {
+            if let Ok(tokens) = parser.tokens(code, language) {
+                return tokens;
+            }
+        }
+        // Without a parser, only whitespace is known to be insignificant
+        vec![code.split_whitespace().collect()]
+    }
+}
+
+/// Changed rows with up to `CONTEXT_LINES` rows around them, under hunk headers
+fn hunks(rows: Vec<Row>) -> Vec<DiffLine> {
+    let changed: Vec<usize> = (0..rows.len()).filter(|&i| rows[i].changed).collect();
+    let mut windows: Vec<(usize, usize)> = Vec::new();
+    for i in changed {
+        let (start, end) = (
+            i.saturating_sub(CONTEXT_LINES),
+            (i + CONTEXT_LINES + 1).min(rows.len()),
+        );
+        match windows.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => windows.push((start, end)),
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (start, end) in windows {
+        let old_before: usize = rows[..start].iter().map(|row| row.old).sum();
+        let new_before: usize = rows[..start].iter().map(|row| row.new).sum();
+        let old_len: usize = rows[start..end].iter().map(|row| row.old).sum();
+        let new_len: usize = rows[start..end].iter().map(|row| row.new).sum();
+        lines.push(DiffLine::new(
+            DiffLineKind::Hunk,
+            &format!(
+                "@@ -{},{} +{},{} @@",
+                old_before + usize::from(old_len > 0),
+                old_len,
+                new_before + usize::from(new_len > 0),
+                new_len
+            ),
+        ));
+        lines.extend(rows[start..end].iter().filter_map(|row| row.line.clone()));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(engine: DiffEngine, path: &str, old: &str, new: &str) -> Vec<String> {
+        diff(engine, Path::new(path), old, new)
+            .iter()
+            .map(DiffLine::plain)
+            .collect()
+    }
+
+    #[test]
+    fn test_engines_show_lines_words_and_real_changes() {
+        let old = "one\ntwo\nthree\n";
+        let new = "one\n2\nthree\nfour\n";
+        assert_eq!(
+            plain(DiffEngine::Line, "a.txt", old, new),
+            vec![
+                "--- a/a.txt",
+                "+++ b/a.txt",
+                "@@ -1,3 +1,4 @@",
+                " one",
+                "-two",
+                "+2",
+                " three",
+                "+four"
+            ]
+        );
+
+        let old = "let total = price * count;\n";
+        let new = "let total = price * quantity;\n";
+        assert_eq!(
+            plain(DiffEngine::Word, "a.rs", old, new)[3],
+            "~let total = price * [-count;-]{+quantity;+}"
+        );
+
+        let old = "fn main() {\n    let x = call(a, b);\n}\n";
+        let reformatted = "fn main() {\n  let x = call(\n      a,\n      b\n  );\n}\n";
+        assert_eq!(
+            plain(DiffEngine::Semantic, "main.rs", old, reformatted),
+            vec!["(formatting-only changes)"]
+        );
+        let changed = "fn main() {\n  let x = call(\n      a,\n      c,\n  );\n}\n";
+        let lines = plain(DiffEngine::Semantic, "main.rs", old, changed);
+        assert!(lines.contains(&"-    let x = call(a, b);".to_string()));
+        assert!(lines.contains(&"+      c,".to_string()));
+        assert!(plain(DiffEngine::Semantic, "notes.txt", "a  b\n", "a b\n").len() == 1);
+
+        assert_eq!("Word".parse::<DiffEngine>().unwrap(), DiffEngine::Word);
+        assert!("ast".parse::<DiffEngine>().is_err());
+    }
+}
@@ -1,12 +1,64 @@
 use super::{ollama_client::OllamaClient, InferenceEngine};
-use domain::models::Embedding;
+use domain::models::{CodeSymbol, Embedding};
 use futures::stream::{self, StreamExt};
 use shared::performance_monitor::GLOBAL_METRICS;
+use shared::terminal;
 use shared::types::Result;
+use std::fmt;
+use std::io::{IsTerminal, Write};
 use std::time::Instant;
 
+/// Embedding model and vector size an index is built with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddingSpec {
+    pub model: String,
+    pub dimensions: usize,
+}
+
+impl fmt::Display for EmbeddingSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} dimensions)", self.model, self.dimensions)
+    }
+}
+
+/// A stored index was built with a different embedding model or vector size
+/// than the configured one, so its vectors cannot be compared with new queries
+#[derive(Debug, Clone)]
+pub struct EmbeddingMismatch {
+    /// Database file or Qdrant collection holding the old vectors
+    pub store: String,
+    /// Model recorded for the index, unknown for indexes built before it was recorded
+    pub indexed_model: Option<String>,
+    pub indexed_dimensions: usize,
+    pub configured: EmbeddingSpec,
+}
+
+impl fmt::Display for EmbeddingMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} was indexed with {} ({} dimensions) but {} is configured; \
+             re-embed it with `bro --rag --reembed` (or RAG_REEMBED=1)",
+            self.store,
+            self.indexed_model.as_deref().unwrap_or("an unknown model"),
+            self.indexed_dimensions,
+            self.configured
+        )
+    }
+}
+
+impl std::error::Error for EmbeddingMismatch {}
+
+/// Texts sent per embedding request when `EMBEDDING_BATCH_SIZE` is not set
+pub const DEFAULT_BATCH_SIZE: usize = 32;
+
+/// Embedding requests in flight when `EMBEDDING_CONCURRENCY` is not set
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
 pub struct Embedder {
     inference_engine: InferenceEngine,
+    batch_size: usize,
+    concurrency: usize,
 }
 
 #[derive(Clone)]
@@ -14,209 +66,142 @@ pub struct EmbeddingInput {
     pub id: String,
     pub path: String,
     pub text: String,
+    pub symbol: Option<CodeSymbol>,
+    pub commit: Option<String>,
 }
 
 impl Embedder {
     pub fn new(client: OllamaClient) -> Self {
         // For backward compatibility, wrap OllamaClient in InferenceEngine
+        Self::new_with_inference_engine(InferenceEngine::Ollama(client))
+    }
+
+    pub fn new_with_inference_engine(inference_engine: InferenceEngine) -> Self {
         Self {
-            inference_engine: InferenceEngine::Ollama(client),
+            inference_engine,
+            batch_size: DEFAULT_BATCH_SIZE,
+            concurrency: DEFAULT_CONCURRENCY,
         }
     }
 
-    pub fn new_with_inference_engine(inference_engine: InferenceEngine) -> Self {
-        Self { inference_engine }
+    /// Texts per request and requests in flight; zero is treated as one
+    pub fn with_batching(mut self, batch_size: usize, concurrency: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Configured model and vector size; the size is asked of the model by
+    /// embedding a probe text unless `dimensions` is given
+    pub async fn spec(&self, dimensions: Option<usize>) -> Result<EmbeddingSpec> {
+        let model = self.inference_engine.embedding_model().to_string();
+        let dimensions = match dimensions {
+            Some(dimensions) => dimensions,
+            None => self
+                .inference_engine
+                .generate_embeddings("dimension probe")
+                .await
+                .map(|vector| vector.len())
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "Failed to ask embedding model '{}' for its dimensions \
+                         (set EMBEDDING_DIMENSIONS to skip this): {}",
+                        model,
+                        e
+                    )
+                })?,
+        };
+        if dimensions == 0 {
+            return Err(anyhow::anyhow!(
+                "Embedding model '{}' returned an empty vector; is it an embedding model?",
+                model
+            ));
+        }
+        Ok(EmbeddingSpec { model, dimensions })
     }
 
+    /// Embed `inputs` in batches, keeping their order
     pub async fn generate_embeddings(&self, inputs: &[EmbeddingInput]) -> Result<Vec<Embedding>> {
-        let mut embeddings = Vec::with_capacity(inputs.len());
+        self.generate_in_batches(inputs, |_| {}).await
+    }
 
-        let mut start_idx = 0;
-        while start_idx < inputs.len() {
-            let batch_size = self
-                .calculate_dynamic_batch_size(inputs.len() - start_idx)
-                .await;
-            let end_idx = (start_idx + batch_size).min(inputs.len());
-            let chunk = &inputs[start_idx..end_idx];
+    /// Like `generate_embeddings`, with a progress bar on stderr for indexing
+    pub async fn generate_embeddings_with_progress(
+        &self,
+        inputs: &[EmbeddingInput],
+    ) -> Result<Vec<Embedding>> {
+        let interactive = std::io::stderr().is_terminal();
+        let started = Instant::now();
+        let draw = |done: usize| {
+            if interactive {
+                eprint!(
+                    "\rEmbedding [{}] {}/{} chunks",
+                    terminal::progress_bar(done, inputs.len(), 30),
+                    done,
+                    inputs.len()
+                );
+                let _ = std::io::stderr().flush();
+            }
+        };
 
+        draw(0);
+        let result = self.generate_in_batches(inputs, draw).await;
+        if interactive {
+            eprintln!();
+        }
+        if result.is_ok() {
             eprintln!(
-                "Generating embeddings for {} chunks (batch size: {})...",
-                chunk.len(),
-                batch_size
+                "Embedded {} chunks in {:.1}s ({} per request, {} in flight)",
+                inputs.len(),
+                started.elapsed().as_secs_f64(),
+                self.batch_size,
+                self.concurrency
             );
-
-            let batch_start = Instant::now();
-            let batch_embeddings = self.generate_batch_embeddings(chunk).await?;
-            let batch_duration = batch_start.elapsed();
-
-            // Record performance metrics for dynamic adjustment
-            GLOBAL_METRICS.end_operation("embedding_batch").await;
-
-            embeddings.extend(batch_embeddings);
-            start_idx = end_idx;
         }
-        Ok(embeddings)
+        result
     }
 
-    async fn generate_batch_embeddings(&self, inputs: &[EmbeddingInput]) -> Result<Vec<Embedding>> {
-        // Use pipelined inference for small batches (better HTTP/2 utilization)
-        if inputs.len() <= 32 {
-            return self.generate_batch_embeddings_pipelined(inputs).await;
-        }
-
-        // Ultra-high performance: use all CPU cores with work-stealing for larger batches
-        let num_concurrent = std::cmp::min(inputs.len(), num_cpus::get() * 4);
-
-        let futures: Vec<_> = inputs
-            .iter()
-            .map(|input| {
-                let inference_engine = &self.inference_engine;
-                async move {
-                    let vector = inference_engine.generate_embeddings(&input.text).await?;
-                    Ok(Embedding {
-                        id: input.id.clone(),
-                        vector,
-                        text: input.text.clone(),
-                        path: input.path.clone(),
-                    }) as Result<Embedding>
-                }
-            })
+    /// Send `batch_size` texts per request with up to `concurrency` requests in
+    /// flight, calling `on_progress` with the number embedded so far
+    async fn generate_in_batches<F>(
+        &self,
+        inputs: &[EmbeddingInput],
+        mut on_progress: F,
+    ) -> Result<Vec<Embedding>>
+    where
+        F: FnMut(usize),
+    {
+        let requests: Vec<_> = inputs
+            .chunks(self.batch_size)
+            .map(|batch| self.generate_batch(batch))
             .collect();
+        let mut batches = stream::iter(requests).buffered(self.concurrency);
 
-        // Maximize concurrency for ultra-fast processing
-        let results = stream::iter(futures)
-            .buffer_unordered(num_concurrent)
-            .collect::<Vec<_>>()
-            .await;
-
-        results.into_iter().collect()
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        while let Some(batch) = batches.next().await {
+            embeddings.extend(batch?);
+            on_progress(embeddings.len());
+        }
+        Ok(embeddings)
     }
 
-    /// Generate batch embeddings using HTTP/2 pipelining for optimal concurrency
-    async fn generate_batch_embeddings_pipelined(
-        &self,
-        inputs: &[EmbeddingInput],
-    ) -> Result<Vec<Embedding>> {
-        let texts: Vec<String> = inputs.iter().map(|input| input.text.clone()).collect();
-
-        // Use OllamaClient's pipelined method if available
-        let embeddings = match &self.inference_engine {
-            InferenceEngine::Ollama(client) => client.generate_embeddings_pipelined(texts).await?,
-            // Fallback to individual requests for other inference engines
-            _ => {
-                let mut embeddings = Vec::with_capacity(inputs.len());
-                for input in inputs {
-                    let vector = self
-                        .inference_engine
-                        .generate_embeddings(&input.text)
-                        .await?;
-                    embeddings.push(vector);
-                }
-                embeddings
-            }
-        };
+    async fn generate_batch(&self, inputs: &[EmbeddingInput]) -> Result<Vec<Embedding>> {
+        GLOBAL_METRICS.start_operation("embedding_batch").await;
+        let texts = inputs.iter().map(|input| input.text.clone()).collect();
+        let vectors = self.inference_engine.generate_embeddings_batch(texts).await;
+        GLOBAL_METRICS.end_operation("embedding_batch").await;
 
-        // Convert to Embedding structs
-        let results: Vec<Embedding> = inputs
+        Ok(inputs
             .iter()
-            .zip(embeddings.into_iter())
+            .zip(vectors?)
             .map(|(input, vector)| Embedding {
                 id: input.id.clone(),
                 vector,
                 text: input.text.clone(),
                 path: input.path.clone(),
+                symbol: input.symbol.clone(),
+                commit: input.commit.clone(),
             })
-            .collect();
-
-        Ok(results)
-    }
-
-    /// Calculate optimal batch size based on system load and performance metrics
-    async fn calculate_dynamic_batch_size(&self, remaining_items: usize) -> usize {
-        // Start with performance monitoring
-        GLOBAL_METRICS.start_operation("embedding_batch").await;
-
-        // Base parameters
-        let min_batch_size = 16;
-        let max_batch_size = 512;
-        let default_batch_size = 128;
-
-        // Get system information
-        let num_cpus = num_cpus::get();
-        let available_parallelism = num_cpus as f32;
-
-        // Get recent performance metrics to inform batch sizing
-        let recent_latency = GLOBAL_METRICS.average_latency("embedding_batch").await;
-        let recent_throughput = GLOBAL_METRICS.throughput("embedding_batch").await;
-
-        // Estimate current system load (simplified heuristic)
-        // In a production system, this would use actual CPU/memory monitoring
-        let estimated_load_factor = self.estimate_system_load().await;
-
-        // Calculate adaptive batch size
-        let mut optimal_batch_size = default_batch_size;
-
-        // Adjust based on CPU availability
-        if available_parallelism >= 8.0 {
-            // High-core systems can handle larger batches
-            optimal_batch_size = (optimal_batch_size as f32 * 1.5) as usize;
-        } else if available_parallelism <= 2.0 {
-            // Low-core systems need smaller batches
-            optimal_batch_size = (optimal_batch_size as f32 * 0.7) as usize;
-        }
-
-        // Adjust based on system load
-        if estimated_load_factor > 0.8 {
-            // High load - reduce batch size to be more responsive
-            optimal_batch_size = (optimal_batch_size as f32 * 0.6) as usize;
-        } else if estimated_load_factor < 0.3 {
-            // Low load - increase batch size for better throughput
-            optimal_batch_size = (optimal_batch_size as f32 * 1.3) as usize;
-        }
-
-        // Adjust based on recent performance
-        if let Some(latency) = recent_latency {
-            if latency.as_millis() > 2000 {
-                // Slow performance - reduce batch size
-                optimal_batch_size = (optimal_batch_size as f32 * 0.8) as usize;
-            } else if latency.as_millis() < 500 {
-                // Fast performance - can handle larger batches
-                optimal_batch_size = (optimal_batch_size as f32 * 1.2) as usize;
-            }
-        }
-
-        // Adjust based on throughput
-        if let Some(throughput) = recent_throughput {
-            if throughput < 2.0 {
-                // Low throughput - reduce batch size
-                optimal_batch_size = (optimal_batch_size as f32 * 0.9) as usize;
-            } else if throughput > 10.0 {
-                // High throughput - increase batch size
-                optimal_batch_size = (optimal_batch_size as f32 * 1.1) as usize;
-            }
-        }
-
-        // Ensure batch size is within bounds
-        optimal_batch_size = optimal_batch_size.clamp(min_batch_size, max_batch_size);
-
-        // Don't exceed remaining items
-        optimal_batch_size.min(remaining_items)
-    }
-
-    /// Estimate current system load (simplified heuristic)
-    async fn estimate_system_load(&self) -> f32 {
-        // This is a simplified load estimation
-        // In production, would use actual system monitoring (CPU, memory, I/O)
-
-        let system_stats = GLOBAL_METRICS.system_stats().await;
-        let active_operations = system_stats.active_operations as f32;
-        let total_operations = system_stats.total_operations as f32;
-
-        // Simple load factor based on active operations
-        if total_operations > 0.0 {
-            (active_operations / total_operations.min(100.0)).clamp(0.0, 1.0)
-        } else {
-            0.1 // Base load assumption
-        }
+            .collect())
     }
 }
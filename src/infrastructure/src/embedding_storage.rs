@@ -1,74 +1,210 @@
+use crate::embedder::EmbeddingSpec;
+use crate::schema_migrations::{self, has_column, Migration, SqliteMigration};
 use domain::models::Embedding;
-use rusqlite::{params, Connection, Result as SqlResult};
+use rusqlite::{params, Connection, OptionalExtension, Result as SqlResult};
 use shared::types::Result;
-use std::path::Path;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::task;
 
+/// Schema history of the embeddings database. Databases created before
+/// versioning start at 0, so these steps skip work that is already done.
+const MIGRATIONS: &[SqliteMigration] = &[
+    Migration {
+        version: 1,
+        description: "embeddings and file hash tables",
+        apply: |conn| {
+            conn.execute_batch(
+                "
+                CREATE TABLE IF NOT EXISTS embeddings (
+                    id TEXT PRIMARY KEY,
+                    vector BLOB NOT NULL,
+                    text TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_embeddings_vector ON embeddings(vector);
+                CREATE TABLE IF NOT EXISTS file_meta (
+                    path TEXT PRIMARY KEY,
+                    hash TEXT NOT NULL
+                );
+            ",
+            )
+        },
+    },
+    Migration {
+        version: 2,
+        description: "source path per embedding",
+        apply: |conn| {
+            if !has_column(conn, "embeddings", "path")? {
+                conn.execute(
+                    "ALTER TABLE embeddings ADD COLUMN path TEXT NOT NULL DEFAULT ''",
+                    [],
+                )?;
+            }
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS idx_embeddings_path ON embeddings(path)",
+                [],
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 3,
+        description: "git commit per embedding",
+        apply: |conn| {
+            if !has_column(conn, "embeddings", "commit_hash")? {
+                conn.execute("ALTER TABLE embeddings ADD COLUMN commit_hash TEXT", [])?;
+            }
+            Ok(())
+        },
+    },
+    Migration {
+        version: 4,
+        description: "index metadata (embedding model and dimensions)",
+        apply: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS index_meta (
+                    key TEXT PRIMARY KEY,
+                    value TEXT NOT NULL
+                );",
+            )
+        },
+    },
+];
+
+/// What a compaction removed from an index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Points whose content was already stored under another point
+    pub duplicates: usize,
+    /// Points for paths no longer in the index manifest
+    pub stale: usize,
+    pub reclaimed_bytes: u64,
+}
+
+impl CompactionReport {
+    pub fn removed(&self) -> usize {
+        self.duplicates + self.stale
+    }
+
+    pub fn merge(&mut self, other: CompactionReport) {
+        self.duplicates += other.duplicates;
+        self.stale += other.stale;
+        self.reclaimed_bytes += other.reclaimed_bytes;
+    }
+}
+
+/// Key under which identical chunks are deduplicated
+pub fn content_hash(text: &str) -> String {
+    format!("{:x}", md5::compute(text.as_bytes()))
+}
+
 pub struct EmbeddingStorage {
     conn: Arc<Mutex<Connection>>,
+    db_path: PathBuf,
 }
 
 impl EmbeddingStorage {
     pub async fn new(db_path: impl AsRef<Path>) -> Result<Self> {
         let db_path = db_path.as_ref().to_path_buf();
+        let path = db_path.clone();
         let conn = task::spawn_blocking(move || -> Result<Connection> {
-            if let Some(parent) = db_path.parent() {
+            if let Some(parent) = path.parent() {
                 std::fs::create_dir_all(parent)?;
             }
-            let conn = Connection::open(&db_path)?;
-            Self::setup_db(&conn)?;
+            let conn = Connection::open(&path)?;
+            Self::setup_db(&conn, &path)?;
             Ok(conn)
         })
         .await??;
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            db_path,
+        })
+    }
+
+    pub fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+
+    /// Model and vector size the stored embeddings were made with. Indexes
+    /// built before this was recorded report the size of a stored vector and
+    /// no model; an empty index reports nothing.
+    pub async fn indexed_spec(&self) -> Result<Option<(Option<String>, usize)>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let meta = |key: &str| -> SqlResult<Option<String>> {
+                conn.query_row(
+                    "SELECT value FROM index_meta WHERE key = ?1",
+                    [key],
+                    |row| row.get(0),
+                )
+                .optional()
+            };
+            let model = meta("embedding_model")?;
+            if let Some(dimensions) = meta("embedding_dimensions")?.and_then(|d| d.parse().ok()) {
+                return Ok(Some((model, dimensions)));
+            }
+
+            let vector: Option<Vec<u8>> = conn
+                .query_row("SELECT vector FROM embeddings LIMIT 1", [], |row| {
+                    row.get(0)
+                })
+                .optional()?;
+            match vector {
+                Some(bytes) => {
+                    let vector: Vec<f32> = bincode::deserialize(&bytes)?;
+                    Ok(Some((None, vector.len())))
+                }
+                None => Ok(None),
+            }
         })
+        .await?
     }
 
-    fn setup_db(conn: &Connection) -> SqlResult<()> {
+    pub async fn set_embedding_spec(&self, spec: &EmbeddingSpec) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        let spec = spec.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            conn.execute(
+                "INSERT OR REPLACE INTO index_meta (key, value) VALUES ('embedding_model', ?1), ('embedding_dimensions', ?2)",
+                params![spec.model, spec.dimensions.to_string()],
+            )?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Back up the database, then drop every embedding and file hash so the
+    /// next index build embeds everything again. Returns the backup location.
+    pub async fn reset(&self) -> Result<PathBuf> {
+        let conn = Arc::clone(&self.conn);
+        let db_path = self.db_path.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+            let backup = schema_migrations::backup_sqlite(&conn, &db_path, version)?;
+            conn.execute_batch(
+                "DELETE FROM embeddings; DELETE FROM file_meta; DELETE FROM index_meta;",
+            )?;
+            Ok(backup)
+        })
+        .await?
+    }
+
+    fn setup_db(conn: &Connection, db_path: &Path) -> Result<()> {
         conn.execute_batch(
             "
             PRAGMA journal_mode=WAL;
             PRAGMA synchronous=NORMAL;
             PRAGMA cache_size=-64000;
             PRAGMA temp_store=MEMORY;
-            CREATE TABLE IF NOT EXISTS embeddings (
-                id TEXT PRIMARY KEY,
-                vector BLOB NOT NULL,
-                text TEXT NOT NULL,
-                path TEXT NOT NULL DEFAULT ''
-            );
-            CREATE INDEX IF NOT EXISTS idx_embeddings_vector ON embeddings(vector);
-            CREATE TABLE IF NOT EXISTS file_meta (
-                path TEXT PRIMARY KEY,
-                hash TEXT NOT NULL
-            );
         ",
         )?;
-        // Backfill missing path column for existing DBs.
-        let mut stmt = conn.prepare("PRAGMA table_info(embeddings)")?;
-        let mut rows = stmt.query([])?;
-        let mut has_path = false;
-        while let Some(row) = rows.next()? {
-            let col_name: String = row.get(1)?;
-            if col_name == "path" {
-                has_path = true;
-                break;
-            }
-        }
-        if !has_path {
-            conn.execute(
-                "ALTER TABLE embeddings ADD COLUMN path TEXT NOT NULL DEFAULT ''",
-                [],
-            )?;
-        }
-        // Ensure the path index exists once the column is known to be present.
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_embeddings_path ON embeddings(path)",
-            [],
-        )?;
+        schema_migrations::migrate_sqlite(conn, db_path, MIGRATIONS)?;
         Ok(())
     }
 
@@ -79,7 +215,7 @@ impl EmbeddingStorage {
             let tx = conn.unchecked_transaction()?;
             {
                 let mut stmt = tx.prepare(
-                    "INSERT OR REPLACE INTO embeddings (id, vector, text, path) VALUES (?, ?, ?, ?)",
+                    "INSERT OR REPLACE INTO embeddings (id, vector, text, path, commit_hash) VALUES (?, ?, ?, ?, ?)",
                 )?;
                 for embedding in &embeddings {
                     let vector_bytes = bincode::serialize(&embedding.vector)?;
@@ -87,7 +223,8 @@ impl EmbeddingStorage {
                         &embedding.id,
                         vector_bytes,
                         &embedding.text,
-                        &embedding.path
+                        &embedding.path,
+                        &embedding.commit
                     ])?;
                 }
             }
@@ -102,7 +239,8 @@ impl EmbeddingStorage {
         let conn = Arc::clone(&self.conn);
         task::spawn_blocking(move || {
             let conn = conn.blocking_lock();
-            let mut stmt = conn.prepare("SELECT id, vector, text, path FROM embeddings")?;
+            let mut stmt =
+                conn.prepare("SELECT id, vector, text, path, commit_hash FROM embeddings")?;
             let mut rows = stmt.query([])?;
             let mut embeddings = Vec::new();
             while let Some(row) = rows.next()? {
@@ -110,12 +248,15 @@ impl EmbeddingStorage {
                 let vector_bytes: Vec<u8> = row.get(1)?;
                 let text: String = row.get(2)?;
                 let path: String = row.get(3)?;
+                let commit: Option<String> = row.get(4)?;
                 let vector: Vec<f32> = bincode::deserialize(&vector_bytes)?;
                 embeddings.push(Embedding {
                     id,
                     vector,
                     text,
                     path,
+                    symbol: None,
+                    commit,
                 });
             }
             Ok(embeddings)
@@ -151,6 +292,76 @@ impl EmbeddingStorage {
         .await?
     }
 
+    /// Paths recorded in the content-hash manifest
+    pub async fn list_file_paths(&self) -> Result<Vec<String>> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let mut stmt = conn.prepare("SELECT path FROM file_meta")?;
+            let paths = stmt
+                .query_map([], |row| row.get(0))?
+                .collect::<SqlResult<Vec<String>>>()?;
+            Ok(paths)
+        })
+        .await?
+    }
+
+    pub async fn delete_file_hash(&self, path: String) -> Result<()> {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            conn.execute("DELETE FROM file_meta WHERE path = ?1", params![path])?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Drop embeddings for paths missing from the manifest and all but the
+    /// first copy of identical chunks, then vacuum the database file
+    pub async fn compact(&self) -> Result<CompactionReport> {
+        let conn = Arc::clone(&self.conn);
+        let db_path = self.db_path.clone();
+        task::spawn_blocking(move || {
+            let conn = conn.blocking_lock();
+            let size_before = database_size(&db_path);
+
+            let stale = conn.execute(
+                "DELETE FROM embeddings WHERE path NOT IN (SELECT path FROM file_meta)",
+                [],
+            )?;
+
+            let mut seen = HashSet::new();
+            let mut duplicates = Vec::new();
+            {
+                let mut stmt = conn.prepare("SELECT rowid, text FROM embeddings ORDER BY rowid")?;
+                let mut rows = stmt.query([])?;
+                while let Some(row) = rows.next()? {
+                    let rowid: i64 = row.get(0)?;
+                    let text: String = row.get(1)?;
+                    if !seen.insert(content_hash(&text)) {
+                        duplicates.push(rowid);
+                    }
+                }
+            }
+            let tx = conn.unchecked_transaction()?;
+            {
+                let mut stmt = tx.prepare("DELETE FROM embeddings WHERE rowid = ?1")?;
+                for rowid in &duplicates {
+                    stmt.execute([rowid])?;
+                }
+            }
+            tx.commit()?;
+
+            conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE); VACUUM;")?;
+            Ok(CompactionReport {
+                duplicates: duplicates.len(),
+                stale,
+                reclaimed_bytes: size_before.saturating_sub(database_size(&db_path)),
+            })
+        })
+        .await?
+    }
+
     pub async fn delete_embeddings_for_path(&self, path: String) -> Result<()> {
         let conn = Arc::clone(&self.conn);
         task::spawn_blocking(move || {
@@ -161,3 +372,13 @@ impl EmbeddingStorage {
         .await?
     }
 }
+
+/// Bytes on disk used by the database and its write-ahead log
+fn database_size(db_path: &Path) -> u64 {
+    let wal = PathBuf::from(format!("{}-wal", db_path.display()));
+    [db_path, wal.as_path()]
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|meta| meta.len())
+        .sum()
+}
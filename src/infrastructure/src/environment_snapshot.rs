@@ -0,0 +1,278 @@
+//! What a build ran with, recorded so runs can be compared later
+//!
+//! Each build run stores an [`EnvironmentSnapshot`] in its session: the OS,
+//! the versions of the toolchains a build is likely to call, and the
+//! settings that change what the model is asked and allowed to do. When a
+//! build that worked yesterday fails today, [`EnvironmentSnapshot::diff`]
+//! shows what changed in between.
+
+use crate::config::Config;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::process::Command;
+
+/// Tools whose version is recorded, with the argument printing it
+const TOOLS: &[(&str, &str)] = &[
+    ("rustc", "--version"),
+    ("cargo", "--version"),
+    ("node", "--version"),
+    ("npm", "--version"),
+    ("python3", "--version"),
+    ("go", "version"),
+    ("docker", "--version"),
+    ("git", "--version"),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnvironmentSnapshot {
+    pub taken_at: DateTime<Utc>,
+    /// Goal of the build the snapshot was taken for
+    pub goal: String,
+    pub bro_version: String,
+    pub os: String,
+    pub arch: String,
+    /// First line of each tool's version output; None when not installed
+    pub tools: BTreeMap<String, Option<String>>,
+    /// Settings flattened to dotted names
+    pub config: BTreeMap<String, String>,
+}
+
+/// A value that differs between two snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvironmentChange {
+    pub name: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl EnvironmentSnapshot {
+    /// Snapshot the current environment for a build of `goal`; runs the
+    /// version commands in parallel
+    pub fn capture(config: &Config, goal: &str) -> Self {
+        let tools = std::thread::scope(|scope| {
+            let handles: Vec<_> = TOOLS
+                .iter()
+                .map(|(tool, arg)| (tool, scope.spawn(move || tool_version(tool, arg))))
+                .collect();
+            handles
+                .into_iter()
+                .map(|(tool, handle)| (tool.to_string(), handle.join().ok().flatten()))
+                .collect()
+        });
+
+        Self {
+            taken_at: Utc::now(),
+            goal: goal.to_string(),
+            bro_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: os_description(),
+            arch: std::env::consts::ARCH.to_string(),
+            tools,
+            config: config_values(config),
+        }
+    }
+
+    /// What changed from `self` to `later`, in name order
+    pub fn diff(&self, later: &Self) -> Vec<EnvironmentChange> {
+        let mut before = self.flatten();
+        let after = later.flatten();
+        let mut changes: Vec<EnvironmentChange> = after
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let old = before.remove(&name).flatten();
+                (old != value).then_some(EnvironmentChange {
+                    name,
+                    before: old,
+                    after: value,
+                })
+            })
+            .collect();
+        changes.extend(before.into_iter().filter(|(_, value)| value.is_some()).map(
+            |(name, value)| EnvironmentChange {
+                name,
+                before: value,
+                after: None,
+            },
+        ));
+        changes.sort_by(|a, b| a.name.cmp(&b.name));
+        changes
+    }
+
+    fn flatten(&self) -> BTreeMap<String, Option<String>> {
+        let mut values = BTreeMap::new();
+        values.insert("bro".to_string(), Some(self.bro_version.clone()));
+        values.insert("os".to_string(), Some(self.os.clone()));
+        values.insert("arch".to_string(), Some(self.arch.clone()));
+        for (tool, version) in &self.tools {
+            values.insert(format!("tool.{}", tool), version.clone());
+        }
+        for (name, value) in &self.config {
+            values.insert(format!("config.{}", name), Some(value.clone()));
+        }
+        values
+    }
+}
+
+fn tool_version(tool: &str, arg: &str) -> Option<String> {
+    let output = Command::new(tool).arg(arg).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // Some tools print their version to stderr
+    let text = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+    String::from_utf8_lossy(&text)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+}
+
+/// OS name and release, e.g. `linux 6.8.0-45-generic`
+fn os_description() -> String {
+    let release = Command::new("uname")
+        .arg("-r")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    match release {
+        Some(release) if !release.is_empty() => {
+            format!("{} {}", std::env::consts::OS, release)
+        }
+        _ => std::env::consts::OS.to_string(),
+    }
+}
+
+/// Settings that shape a build, with secret-looking values left out
+fn config_values(config: &Config) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    values.insert(
+        "ollama_base_url".to_string(),
+        config.ollama_base_url.clone(),
+    );
+    values.insert("ollama_model".to_string(), config.ollama_model.clone());
+    if let Some(model) = &config.embedding_model {
+        values.insert("embedding_model".to_string(), model.clone());
+    }
+    values.insert(
+        "context.max_context_tokens".to_string(),
+        config.context.max_context_tokens.to_string(),
+    );
+    values.insert(
+        "context.max_file_size_bytes".to_string(),
+        config.context.max_file_size_bytes.to_string(),
+    );
+    for (prefix, value) in [
+        ("security", serde_json::to_value(&config.security)),
+        ("power_user", serde_json::to_value(&config.power_user)),
+    ] {
+        if let Ok(value) = value {
+            flatten_json(prefix, &value, &mut values);
+        }
+    }
+    values
+}
+
+fn flatten_json(name: &str, value: &serde_json::Value, values: &mut BTreeMap<String, String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter().filter(|(key, _)| !is_secret_name(key)) {
+                flatten_json(&format!("{}.{}", name, key), value, values);
+            }
+        }
+        serde_json::Value::Null => {}
+        serde_json::Value::String(text) => {
+            values.insert(name.to_string(), text.clone());
+        }
+        other => {
+            values.insert(name.to_string(), other.to_string());
+        }
+    }
+}
+
+/// Names like `api_key`, `auth_token` or `db_password`
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.ends_with("key")
+        || name.ends_with("token")
+        || name.contains("secret")
+        || name.contains("password")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(tools: &[(&str, Option<&str>)], config: &[(&str, &str)]) -> EnvironmentSnapshot {
+        EnvironmentSnapshot {
+            taken_at: Utc::now(),
+            goal: "build".to_string(),
+            bro_version: "0.1.0".to_string(),
+            os: "linux 6.8.0".to_string(),
+            arch: "x86_64".to_string(),
+            tools: tools
+                .iter()
+                .map(|(tool, version)| (tool.to_string(), version.map(str::to_string)))
+                .collect(),
+            config: config
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_between_runs() {
+        let yesterday = snapshot(
+            &[("rustc", Some("rustc 1.79.0")), ("node", None)],
+            &[
+                ("ollama_model", "qwen2.5-coder"),
+                ("power_user.batch.size", "4"),
+            ],
+        );
+        let today = snapshot(
+            &[("rustc", Some("rustc 1.80.1")), ("node", Some("v20.11.0"))],
+            &[("ollama_model", "qwen2.5-coder")],
+        );
+
+        let changes = yesterday.diff(&today);
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|c| (c.name.as_str(), c.before.as_deref(), c.after.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("config.power_user.batch.size", Some("4"), None),
+                ("tool.node", None, Some("v20.11.0")),
+                ("tool.rustc", Some("rustc 1.79.0"), Some("rustc 1.80.1")),
+            ]
+        );
+        assert!(today.diff(&today).is_empty());
+    }
+
+    #[test]
+    fn test_secret_settings_are_not_recorded() {
+        let value = serde_json::json!({
+            "plugins": { "registry": "https://example.com", "api_key": "abc" },
+            "auth_token": "xyz",
+            "max_tokens": 3,
+            "unset": null
+        });
+        let mut values = BTreeMap::new();
+        flatten_json("power_user", &value, &mut values);
+        assert_eq!(
+            values.into_iter().collect::<Vec<_>>(),
+            [
+                ("power_user.max_tokens".to_string(), "3".to_string()),
+                (
+                    "power_user.plugins.registry".to_string(),
+                    "https://example.com".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -1,5 +1,9 @@
+use crate::capability_registry::{self, Capability, FeatureUnavailable};
+use crate::privacy_controls::is_local_url;
 use anyhow::Result;
 use regex::Regex;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
 
 /// Error analysis and fix generation engine
 #[derive(Clone)]
@@ -406,3 +410,292 @@ impl From<&super::background_supervisor::BackgroundEvent> for ErrorContext {
         }
     }
 }
+
+/// How long a started service gets to answer its health check
+const SERVICE_START_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// A service bro depends on that failed, recognised from an error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceFailure {
+    /// Nothing answers at the Ollama URL
+    OllamaDown { url: String },
+    /// Ollama answers but does not have the model
+    ModelMissing { model: String },
+    /// Nothing answers at the Qdrant URL
+    QdrantUnreachable { url: String },
+}
+
+/// A step that may fix a [`ServiceFailure`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecoveryAction {
+    /// Run the first of `commands` that works and wait for `health_url` to
+    /// answer; long-running commands such as `ollama serve` are left running
+    StartService {
+        name: String,
+        commands: Vec<Vec<String>>,
+        health_url: String,
+    },
+    /// Download a model with `ollama pull`
+    PullModel { model: String },
+    /// Use another installed model for the rest of the run
+    SwitchModel { model: String },
+}
+
+impl ServiceFailure {
+    /// The failure behind `error`, judged by the messages in its chain;
+    /// `ollama_url` tells Ollama connection errors from others
+    pub fn classify(error: &anyhow::Error, ollama_url: &str) -> Option<Self> {
+        if let Some(unavailable) = error.downcast_ref::<FeatureUnavailable>() {
+            return match unavailable.capability {
+                Capability::Ollama => Some(Self::OllamaDown {
+                    url: ollama_url.trim_end_matches('/').to_string(),
+                }),
+                Capability::Qdrant => Some(Self::QdrantUnreachable {
+                    url: capability_registry::qdrant_url(),
+                }),
+                _ => None,
+            };
+        }
+        let chain = format!("{:#}", error);
+        let lower = chain.to_lowercase();
+        let model_missing =
+            Regex::new(r#"model\s+\\?["']([^"'\\]+)\\?["']\s+not found"#).expect("valid regex");
+        if let Some(captures) = model_missing.captures(&chain) {
+            return Some(Self::ModelMissing {
+                model: captures[1].to_string(),
+            });
+        }
+
+        let unreachable = [
+            "connection refused",
+            "error sending request",
+            "tcp connect error",
+            "transport error",
+            "failed to connect",
+        ];
+        if !unreachable.iter().any(|marker| lower.contains(marker)) {
+            return None;
+        }
+        if lower.contains("qdrant") {
+            let url = Regex::new(r"https?://[\w.\-\[\]:]+\b")
+                .expect("valid regex")
+                .find(&chain)
+                .map_or("http://localhost:6334", |url| url.as_str())
+                .to_string();
+            return Some(Self::QdrantUnreachable { url });
+        }
+        let ollama_url = ollama_url.trim_end_matches('/');
+        (chain.contains(ollama_url) || lower.contains("ollama")).then(|| Self::OllamaDown {
+            url: ollama_url.to_string(),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::OllamaDown { url } => format!("Ollama is not running at {}", url),
+            Self::ModelMissing { model } => {
+                format!("The model '{}' is not installed in Ollama", model)
+            }
+            Self::QdrantUnreachable { url } => format!("Qdrant is not reachable at {}", url),
+        }
+    }
+
+    /// What may fix the failure, most direct first; `installed_models` are
+    /// offered in place of a missing model. Services are only started when
+    /// they are expected on this machine.
+    pub fn recovery_actions(&self, installed_models: &[String]) -> Vec<RecoveryAction> {
+        let words = |command: &str| command.split_whitespace().map(str::to_string).collect();
+        match self {
+            Self::OllamaDown { url } if is_local_url(url) => vec![RecoveryAction::StartService {
+                name: "Ollama".to_string(),
+                commands: vec![words("ollama serve")],
+                health_url: format!("{}/api/tags", url),
+            }],
+            Self::ModelMissing { model } => std::iter::once(RecoveryAction::PullModel {
+                model: model.clone(),
+            })
+            .chain(
+                installed_models
+                    .iter()
+                    .filter(|installed| {
+                        *installed != model && **installed != format!("{}:latest", model)
+                    })
+                    .take(3)
+                    .map(|installed| RecoveryAction::SwitchModel {
+                        model: installed.clone(),
+                    }),
+            )
+            .collect(),
+            Self::QdrantUnreachable { url } if is_local_url(url) => {
+                vec![RecoveryAction::StartService {
+                    name: "Qdrant".to_string(),
+                    commands: vec![
+                        words("docker start qdrant"),
+                        words(
+                            "docker run -d --name qdrant -p 6333:6333 -p 6334:6334 qdrant/qdrant",
+                        ),
+                    ],
+                    health_url: "http://localhost:6333/healthz".to_string(),
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl RecoveryAction {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::StartService { name, commands, .. } => format!(
+                "Start {} (`{}`)",
+                name,
+                commands.first().map(|c| c.join(" ")).unwrap_or_default()
+            ),
+            Self::PullModel { model } => format!("Pull the model (`ollama pull {}`)", model),
+            Self::SwitchModel { model } => format!("Use the installed model '{}' instead", model),
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        match self {
+            Self::StartService {
+                name,
+                commands,
+                health_url,
+            } => start_service(name, commands, health_url).await,
+            Self::PullModel { model } => {
+                let status = tokio::process::Command::new("ollama")
+                    .args(["pull", model])
+                    .status()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Cannot run ollama: {}", e))?;
+                if !status.success() {
+                    return Err(anyhow::anyhow!("ollama pull {} failed ({})", model, status));
+                }
+                Ok(())
+            }
+            Self::SwitchModel { model } => {
+                // Clients created from here on read the model from the environment
+                std::env::set_var("BASE_MODEL", model);
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn start_service(name: &str, commands: &[Vec<String>], health_url: &str) -> Result<()> {
+    let mut failure = format!("no command to start {}", name);
+    for command in commands {
+        let Some((program, args)) = command.split_first() else {
+            continue;
+        };
+        let mut child = match std::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                failure = format!("cannot run {}: {}", program, e);
+                continue;
+            }
+        };
+        let deadline = Instant::now() + SERVICE_START_TIMEOUT;
+        while Instant::now() < deadline {
+            if responds(health_url).await {
+                return Ok(());
+            }
+            if let Ok(Some(status)) = child.try_wait() {
+                if !status.success() {
+                    failure = format!("`{}` failed ({})", command.join(" "), status);
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        if Instant::now() >= deadline {
+            failure = format!("{} did not answer at {}", name, health_url);
+        }
+    }
+    Err(anyhow::anyhow!("Could not start {}: {}", name, failure))
+}
+
+async fn responds(url: &str) -> bool {
+    let Ok(client) = reqwest::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+    else {
+        return false;
+    };
+    client.get(url).send().await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLLAMA: &str = "http://localhost:11434";
+
+    #[test]
+    fn test_classify_service_failures() {
+        let refused = anyhow::anyhow!("tcp connect error: Connection refused (os error 111)")
+            .context("error sending request for url (http://localhost:11434/api/chat)");
+        assert_eq!(
+            ServiceFailure::classify(&refused, OLLAMA),
+            Some(ServiceFailure::OllamaDown {
+                url: OLLAMA.to_string()
+            })
+        );
+        let unavailable = anyhow::Error::new(FeatureUnavailable {
+            mode: "bro --chat".to_string(),
+            capability: Capability::Ollama,
+            reason: "Ollama is not running at http://localhost:11434".to_string(),
+        });
+        assert_eq!(
+            ServiceFailure::classify(&unavailable, OLLAMA),
+            Some(ServiceFailure::OllamaDown {
+                url: OLLAMA.to_string()
+            })
+        );
+
+        let missing = anyhow::anyhow!(
+            r#"Ollama API error: {{"error":"model \"llama3\" not found, try pulling it first"}}"#
+        );
+        let failure = ServiceFailure::classify(&missing, OLLAMA).unwrap();
+        assert_eq!(
+            failure,
+            ServiceFailure::ModelMissing {
+                model: "llama3".to_string()
+            }
+        );
+        let installed = ["llama3:latest".to_string(), "qwen2.5:1.5b".to_string()];
+        assert_eq!(
+            failure.recovery_actions(&installed),
+            [
+                RecoveryAction::PullModel {
+                    model: "llama3".to_string()
+                },
+                RecoveryAction::SwitchModel {
+                    model: "qwen2.5:1.5b".to_string()
+                },
+            ]
+        );
+
+        let qdrant =
+            anyhow::anyhow!("Failed to connect to Qdrant at http://10.0.0.5:6334: transport error");
+        let failure = ServiceFailure::classify(&qdrant, OLLAMA).unwrap();
+        assert_eq!(
+            failure,
+            ServiceFailure::QdrantUnreachable {
+                url: "http://10.0.0.5:6334".to_string()
+            }
+        );
+        // A remote Qdrant is not ours to start
+        assert!(failure.recovery_actions(&[]).is_empty());
+
+        let unrelated = anyhow::anyhow!("error sending request for url (https://crates.io/)");
+        assert_eq!(ServiceFailure::classify(&unrelated, OLLAMA), None);
+    }
+}
@@ -0,0 +1,242 @@
+//! Throttling for the background event stream
+//!
+//! Watchers can emit hundreds of events a second (a `cargo build` touching
+//! `target/`, a log tailer following a noisy service), which would bury the
+//! interactive prompt. Events are collected into batches that are flushed on a
+//! timer: low-severity entries are dropped, identical events are collapsed into
+//! one with a repeat count, file changes are deduplicated by path, and each
+//! source may only emit a limited number of events per rate window.
+
+use crate::background_supervisor::{BackgroundEvent, DiagnosticSeverity, FileChangeType, LogLevel};
+use crate::config::EventStreamConfig;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Events that survived filtering during one batch window
+#[derive(Debug, Default)]
+pub struct EventBatch {
+    /// Distinct events in arrival order, with how often each was seen
+    pub events: Vec<(BackgroundEvent, usize)>,
+    /// Changed files, one entry per path with its latest change
+    pub file_changes: Vec<(PathBuf, FileChangeType)>,
+    /// Events dropped by the rate limit, per source
+    pub suppressed: BTreeMap<String, usize>,
+}
+
+impl EventBatch {
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty() && self.file_changes.is_empty() && self.suppressed.is_empty()
+    }
+}
+
+pub struct EventThrottle {
+    config: EventStreamConfig,
+    min_log_level: u8,
+    min_severity: u8,
+    batch: EventBatch,
+    /// Index into `batch.events` by rendered event, for deduplication
+    seen: HashMap<String, usize>,
+    /// Start of the current rate window and events let through in it, per source
+    windows: HashMap<String, (Instant, usize)>,
+}
+
+impl EventThrottle {
+    pub fn new(config: EventStreamConfig) -> Self {
+        Self {
+            min_log_level: log_level_rank(&config.min_log_level),
+            min_severity: severity_rank(&config.min_diagnostic_severity),
+            config,
+            batch: EventBatch::default(),
+            seen: HashMap::new(),
+            windows: HashMap::new(),
+        }
+    }
+
+    /// How often the batch should be flushed
+    pub fn batch_window(&self) -> Duration {
+        Duration::from_millis(self.config.batch_window_ms.max(1))
+    }
+
+    /// More changed files than this in one batch are shown as a summary
+    pub fn file_storm_threshold(&self) -> usize {
+        self.config.file_storm_threshold
+    }
+
+    pub fn push(&mut self, event: BackgroundEvent, now: Instant) {
+        if !self.passes_filter(&event) {
+            return;
+        }
+
+        if let BackgroundEvent::FileChanged { path, change_type } = event {
+            match self.batch.file_changes.iter_mut().find(|(p, _)| *p == path) {
+                Some(entry) => entry.1 = change_type,
+                None => self.batch.file_changes.push((path, change_type)),
+            }
+            return;
+        }
+
+        let key = format!("{:?}", event);
+        if let Some(&index) = self.seen.get(&key) {
+            self.batch.events[index].1 += 1;
+            return;
+        }
+
+        let source = source_of(&event);
+        if !self.allow(&source, now) {
+            *self.batch.suppressed.entry(source).or_default() += 1;
+            return;
+        }
+        self.seen.insert(key, self.batch.events.len());
+        self.batch.events.push((event, 1));
+    }
+
+    /// Take the events collected since the last flush
+    pub fn flush(&mut self) -> EventBatch {
+        self.seen.clear();
+        std::mem::take(&mut self.batch)
+    }
+
+    fn passes_filter(&self, event: &BackgroundEvent) -> bool {
+        match event {
+            BackgroundEvent::LogEntry { level, .. } => {
+                log_level_rank_of(level) >= self.min_log_level
+            }
+            BackgroundEvent::LspDiagnostic { severity, .. } => {
+                severity_rank_of(severity) >= self.min_severity
+            }
+            _ => true,
+        }
+    }
+
+    fn allow(&mut self, source: &str, now: Instant) -> bool {
+        let window = Duration::from_secs(self.config.rate_window_secs);
+        let (start, count) = self.windows.entry(source.to_string()).or_insert((now, 0));
+        if now.duration_since(*start) >= window {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= self.config.max_events_per_source {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+/// Name used to rate-limit an event's origin
+pub fn source_of(event: &BackgroundEvent) -> String {
+    match event {
+        BackgroundEvent::FileChanged { .. } => "files".to_string(),
+        BackgroundEvent::TestResult { session, .. } => format!("test:{}", session),
+        BackgroundEvent::LogEntry { source, .. } => source.clone(),
+        BackgroundEvent::LspDiagnostic { .. } => "lsp".to_string(),
+        BackgroundEvent::GitStatus { .. } => "git".to_string(),
+    }
+}
+
+fn log_level_rank(name: &str) -> u8 {
+    match name.to_lowercase().as_str() {
+        "debug" => 0,
+        "warn" | "warning" => 2,
+        "error" => 3,
+        _ => 1,
+    }
+}
+
+fn log_level_rank_of(level: &LogLevel) -> u8 {
+    match level {
+        LogLevel::Debug => 0,
+        LogLevel::Info => 1,
+        LogLevel::Warn => 2,
+        LogLevel::Error => 3,
+    }
+}
+
+fn severity_rank(name: &str) -> u8 {
+    match name.to_lowercase().as_str() {
+        "hint" => 0,
+        "info" | "information" => 1,
+        "error" => 3,
+        _ => 2,
+    }
+}
+
+fn severity_rank_of(severity: &DiagnosticSeverity) -> u8 {
+    match severity {
+        DiagnosticSeverity::Hint => 0,
+        DiagnosticSeverity::Information => 1,
+        DiagnosticSeverity::Warning => 2,
+        DiagnosticSeverity::Error => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(source: &str, level: LogLevel, message: &str) -> BackgroundEvent {
+        BackgroundEvent::LogEntry {
+            source: source.to_string(),
+            level,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_batches_filter_and_collapse_events() {
+        let mut throttle = EventThrottle::new(EventStreamConfig::default());
+        let now = Instant::now();
+
+        throttle.push(log("cargo", LogLevel::Debug, "fresh"), now);
+        for _ in 0..3 {
+            throttle.push(log("cargo", LogLevel::Warn, "unused import"), now);
+        }
+        for change in [FileChangeType::Created, FileChangeType::Modified] {
+            throttle.push(
+                BackgroundEvent::FileChanged {
+                    path: PathBuf::from("src/lib.rs"),
+                    change_type: change,
+                },
+                now,
+            );
+        }
+
+        let batch = throttle.flush();
+        assert_eq!(batch.events.len(), 1);
+        assert_eq!(batch.events[0].1, 3);
+        assert_eq!(batch.file_changes.len(), 1);
+        assert!(matches!(batch.file_changes[0].1, FileChangeType::Modified));
+        assert!(throttle.flush().is_empty());
+    }
+
+    #[test]
+    fn test_rate_limit_is_per_source_and_resets() {
+        let config = EventStreamConfig {
+            max_events_per_source: 2,
+            rate_window_secs: 10,
+            ..EventStreamConfig::default()
+        };
+        let mut throttle = EventThrottle::new(config);
+        let now = Instant::now();
+
+        for i in 0..5 {
+            throttle.push(log("cargo", LogLevel::Error, &format!("error {}", i)), now);
+        }
+        throttle.push(log("server", LogLevel::Error, "crashed"), now);
+        let batch = throttle.flush();
+        assert_eq!(batch.events.len(), 3);
+        assert_eq!(batch.suppressed.get("cargo"), Some(&3));
+
+        throttle.push(
+            log("cargo", LogLevel::Error, "error 5"),
+            now + Duration::from_secs(1),
+        );
+        assert!(throttle.flush().events.is_empty());
+        throttle.push(
+            log("cargo", LogLevel::Error, "error 6"),
+            now + Duration::from_secs(11),
+        );
+        assert_eq!(throttle.flush().events.len(), 1);
+    }
+}
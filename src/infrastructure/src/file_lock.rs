@@ -0,0 +1,46 @@
+//! Advisory locks shared by `bro` processes
+//!
+//! Files that the CLI, the web server and other sessions all rewrite are
+//! guarded by `flock` on a companion lock file, held for the whole
+//! read-modify-write. The lock is dropped with the file descriptor, so a
+//! process that dies never leaves it held. Elsewhere than Linux locking is a
+//! no-op.
+
+use shared::types::Result;
+use std::fs::File;
+use std::path::Path;
+
+/// Exclusive lock held until dropped
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Block until this process holds the lock on `path`, creating the file
+    /// and its directory if needed
+    pub fn exclusive(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        lock_exclusive(&file);
+        Ok(Self { _file: file })
+    }
+}
+
+/// Block until this process holds an exclusive lock on `file`
+#[cfg(target_os = "linux")]
+pub fn lock_exclusive(file: &File) {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: flock only takes the descriptor; the lock ends when it is closed
+    unsafe {
+        libc::flock(file.as_raw_fd(), libc::LOCK_EX);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn lock_exclusive(_file: &File) {}
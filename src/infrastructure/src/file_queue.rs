@@ -0,0 +1,253 @@
+//! Directory of JSON entries shared by `bro` processes
+//!
+//! Build confirmations, approvals and agent questions are each written to
+//! `<dir>/<id>.json` by the process that waits on them and read or decided by
+//! others, such as the web server. Entries are written to a temporary file and
+//! renamed, so readers never see a partial one, and every read-check-write
+//! holds an exclusive [`FileLock`] on `<dir>/.lock`, so two decisions cannot
+//! both see an entry undecided.
+
+use crate::file_lock::FileLock;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use shared::types::Result;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often a waiting process checks its entry
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+const LOCK_FILE: &str = ".lock";
+
+/// An entry kept in a [`FileQueue`]
+pub trait QueueEntry: Serialize + DeserializeOwned {
+    /// What entries are called in errors, e.g. `approval`
+    const KIND: &'static str;
+
+    fn id(&self) -> &str;
+
+    fn created_at(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug)]
+pub struct FileQueue<T> {
+    dir: PathBuf,
+    entries: PhantomData<fn() -> T>,
+}
+
+// Derived `Clone` would require `T: Clone`
+impl<T> Clone for FileQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            dir: self.dir.clone(),
+            entries: PhantomData,
+        }
+    }
+}
+
+impl<T: QueueEntry> FileQueue<T> {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            entries: PhantomData,
+        }
+    }
+
+    pub fn insert(&self, entry: &T) -> Result<()> {
+        self.write(entry)
+    }
+
+    /// Entries oldest first; those `discard` picks are deleted instead
+    pub fn list(&self, discard: impl Fn(&T) -> bool) -> Result<Vec<T>> {
+        let Ok(files) = std::fs::read_dir(&self.dir) else {
+            return Ok(Vec::new());
+        };
+        let mut entries = Vec::new();
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match Self::read_file(&path) {
+                Ok(entry) if discard(&entry) => {
+                    let _ = std::fs::remove_file(&path);
+                }
+                Ok(entry) => entries.push(entry),
+                Err(e) => tracing::warn!("Skipping unreadable {} {:?}: {}", T::KIND, path, e),
+            }
+        }
+        entries.sort_by_key(|entry| entry.created_at());
+        Ok(entries)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<T>> {
+        let path = self.path_for(id)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::read_file(&path).map(Some)
+    }
+
+    /// Apply `change` to entry `id` and write it back under the lock; nothing
+    /// is written when `change` fails
+    pub fn modify(&self, id: &str, change: impl FnOnce(&mut T) -> Result<()>) -> Result<T> {
+        let _lock = FileLock::exclusive(&self.dir.join(LOCK_FILE))?;
+        let mut entry = self
+            .get(id)?
+            .ok_or_else(|| anyhow::anyhow!("No pending {} with id {}", T::KIND, id))?;
+        change(&mut entry)?;
+        self.write(&entry)?;
+        Ok(entry)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let path = self.path_for(id)?;
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Wait until entry `id` is `done`; None when it is `expired` or
+    /// disappears first. The entry is removed either way.
+    pub async fn wait(
+        &self,
+        id: &str,
+        done: impl Fn(&T) -> bool,
+        expired: impl Fn(&T) -> bool,
+    ) -> Result<Option<T>> {
+        let finished = loop {
+            match self.get(id)? {
+                Some(entry) if done(&entry) => break Some(entry),
+                Some(entry) if !expired(&entry) => tokio::time::sleep(POLL_INTERVAL).await,
+                _ => break None,
+            }
+        };
+        self.remove(id)?;
+        Ok(finished)
+    }
+
+    fn path_for(&self, id: &str) -> Result<PathBuf> {
+        // Ids come from URLs; never let one escape the queue directory
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(anyhow::anyhow!("Invalid {} id: {}", T::KIND, id));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    fn read_file(path: &Path) -> Result<T> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn write(&self, entry: &T) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path_for(entry.id())?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(entry)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Tally {
+        id: String,
+        created_at: DateTime<Utc>,
+        count: usize,
+    }
+
+    impl QueueEntry for Tally {
+        const KIND: &'static str = "tally";
+
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn created_at(&self) -> DateTime<Utc> {
+            self.created_at
+        }
+    }
+
+    fn tally(count: usize) -> Tally {
+        Tally {
+            id: uuid::Uuid::new_v4().to_string(),
+            created_at: Utc::now(),
+            count,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_round_trip_and_stay_in_the_directory() {
+        let dir = std::env::temp_dir().join(format!("bro-queue-{}", uuid::Uuid::new_v4()));
+        let queue = FileQueue::<Tally>::new(&dir);
+        let (kept, stale) = (tally(1), tally(0));
+        queue.insert(&kept).unwrap();
+        queue.insert(&stale).unwrap();
+
+        assert_eq!(
+            queue.list(|entry| entry.count == 0).unwrap(),
+            std::slice::from_ref(&kept)
+        );
+        assert!(queue.get(&stale.id).unwrap().is_none());
+        assert!(queue.get("../secrets").is_err());
+        assert!(queue
+            .modify(&kept.id, |_| Err(anyhow::anyhow!("refused")))
+            .is_err());
+        assert_eq!(queue.get(&kept.id).unwrap().unwrap(), kept);
+
+        let remote = queue.clone();
+        let id = kept.id.clone();
+        tokio::spawn(async move {
+            remote.modify(&id, |entry| {
+                entry.count += 1;
+                Ok(())
+            })
+        });
+        let done = queue
+            .wait(&kept.id, |entry| entry.count > 1, |_| false)
+            .await
+            .unwrap();
+        assert_eq!(done.unwrap().count, 2);
+        assert!(queue.get(&kept.id).unwrap().is_none());
+
+        let expired = tally(1);
+        queue.insert(&expired).unwrap();
+        assert!(queue
+            .wait(&expired.id, |_| false, |_| true)
+            .await
+            .unwrap()
+            .is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_concurrent_changes_are_all_kept() {
+        let dir = std::env::temp_dir().join(format!("bro-queue-{}", uuid::Uuid::new_v4()));
+        let queue = FileQueue::<Tally>::new(&dir);
+        let entry = tally(0);
+        queue.insert(&entry).unwrap();
+
+        std::thread::scope(|scope| {
+            for _ in 0..16 {
+                let (queue, id) = (&queue, &entry.id);
+                scope.spawn(move || {
+                    queue
+                        .modify(id, |entry| {
+                            entry.count += 1;
+                            Ok(())
+                        })
+                        .unwrap()
+                });
+            }
+        });
+        assert_eq!(queue.get(&entry.id).unwrap().unwrap().count, 16);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,18 +1,30 @@
+use crate::ast_parser::AstParser;
+use crate::git_repo::GitRepo;
+use domain::models::CodeSymbol;
 use futures::{stream, StreamExt};
 use md5;
 use memmap2::Mmap;
 use rayon::prelude::*;
 use shared::types::Result;
 use shared::utils::is_supported_file;
+use std::cell::RefCell;
 use std::collections::HashSet;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 use tokio::fs;
 
+const MAX_CHUNK_SIZE: usize = 2000;
+
+thread_local! {
+    // Parsers and queries are built once per worker thread rather than per file
+    static AST_PARSER: RefCell<Option<AstParser>> = const { RefCell::new(None) };
+}
+
 pub struct FileScanner {
     root_path: PathBuf,
     ignored_dirs: HashSet<String>,
     max_file_bytes: u64,
+    git: Option<GitRepo>,
 }
 
 impl FileScanner {
@@ -43,9 +55,21 @@ impl FileScanner {
             .collect(),
             // Cap per-file scanning to keep indexing responsive; adjust if needed.
             max_file_bytes: 2 * 1024 * 1024,
+            git: None,
         }
     }
 
+    /// List files through `repo` so untracked build artifacts and anything
+    /// else matched by `.gitignore` stays out of the index
+    pub fn with_git(mut self, repo: GitRepo) -> Self {
+        self.git = Some(repo);
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root_path
+    }
+
     pub async fn scan_files(&self) -> Result<Vec<FileScanResult>> {
         let files = self.collect_files()?;
         self.scan_paths(&files).await
@@ -78,11 +102,44 @@ impl FileScanner {
     }
 
     pub fn collect_files(&self) -> Result<Vec<PathBuf>> {
+        if let Some(repo) = &self.git {
+            match repo.working_tree_files() {
+                Ok(listed) => {
+                    return Ok(listed
+                        .iter()
+                        .filter(|path| is_supported_file(path))
+                        .filter_map(|path| self.path_in_root(path))
+                        .collect());
+                }
+                Err(e) => eprintln!("git listing failed, scanning directories: {}", e),
+            }
+        }
+
         let mut files = Vec::new();
         self.collect_files_recursive(&self.root_path, &mut files)?;
         Ok(files)
     }
 
+    /// Map a path reported by a file watcher (usually absolute) onto the form
+    /// produced by `collect_files`, or `None` if it is outside the root or in
+    /// an ignored directory
+    pub fn path_in_root(&self, path: &Path) -> Option<PathBuf> {
+        let relative = match path.strip_prefix(&self.root_path) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => {
+                let root = std::fs::canonicalize(&self.root_path).ok()?;
+                path.strip_prefix(root).ok()?.to_path_buf()
+            }
+        };
+        let ignored = relative.components().any(|component| {
+            component
+                .as_os_str()
+                .to_str()
+                .is_some_and(|name| self.ignored_dirs.contains(name))
+        });
+        (!ignored).then(|| self.root_path.join(relative))
+    }
+
     /// Async version for ultra-fast file collection
     pub async fn collect_files_async(&self) -> Result<Vec<PathBuf>> {
         self.collect_files() // For now, reuse sync version - could be optimized later
@@ -200,17 +257,35 @@ impl FileScanner {
 
         // Ultra-fast async file reading with memory mapping
         let content = fs::read_to_string(path).await?;
-        let hash = format!("{:x}", md5::compute(content.as_bytes()));
-        let chunks = self.chunk_text(&content, path);
-        Ok(FileScanResult {
+        Ok(self.scan_content(path, &content))
+    }
+
+    /// Hash and chunk content that did not come from the working tree, such as
+    /// a file read from a git commit; `path` is recorded on every chunk
+    pub fn scan_content(&self, path: &Path, content: &str) -> FileScanResult {
+        if content.len() as u64 > self.max_file_bytes {
+            return FileScanResult {
+                path: path.to_string_lossy().to_string(),
+                hash: String::new(),
+                chunks: Vec::new(),
+            };
+        }
+        let mut chunks = self.chunk_text(content, path);
+        for chunk in &mut chunks {
+            chunk.start_line = line_at(content, chunk.start_offset);
+        }
+        FileScanResult {
             path: path.to_string_lossy().to_string(),
-            hash,
+            hash: format!("{:x}", md5::compute(content.as_bytes())),
             chunks,
-        })
+        }
     }
 
     fn chunk_text(&self, text: &str, path: &Path) -> Vec<FileChunk> {
-        const MAX_CHUNK_SIZE: usize = 2000;
+        if let Some(chunks) = self.chunk_by_symbols(text, path) {
+            return chunks;
+        }
+
         const MIN_CHUNK_SIZE: usize = 500;
 
         let mut chunks = Vec::new();
@@ -231,6 +306,8 @@ impl FileScanner {
                         path: path_str.clone(),
                         text: current_chunk.clone(),
                         start_offset,
+                        start_line: 1,
+                        symbol: None,
                     });
                 }
                 current_chunk.clear();
@@ -249,6 +326,8 @@ impl FileScanner {
                         path: path_str.clone(),
                         text: current_chunk.clone(),
                         start_offset,
+                        start_line: 1,
+                        symbol: None,
                     });
                 }
                 current_chunk.clear();
@@ -265,6 +344,8 @@ impl FileScanner {
                     path: path_str.clone(),
                     text: current_chunk,
                     start_offset,
+                    start_line: 1,
+                    symbol: None,
                 });
             }
         }
@@ -277,6 +358,58 @@ impl FileScanner {
         }
     }
 
+    /// Cut source files at function/type boundaries so every chunk is
+    /// syntactically complete. Returns `None` for unsupported languages and
+    /// files that do not parse, which use paragraph chunking instead.
+    fn chunk_by_symbols(&self, text: &str, path: &Path) -> Option<Vec<FileChunk>> {
+        let language = AstParser::language_for_path(path)?;
+        let symbol_chunks = AST_PARSER.with(|cell| {
+            let mut parser = cell.borrow_mut();
+            if parser.is_none() {
+                *parser = AstParser::new().ok();
+            }
+            parser
+                .as_mut()?
+                .extract_symbol_chunks(text, language, MAX_CHUNK_SIZE)
+                .ok()
+        })?;
+        if symbol_chunks.is_empty() {
+            return None;
+        }
+
+        let mut chunks = Vec::new();
+        let mut seen_hashes = HashSet::new();
+        let path_str = path.to_string_lossy().to_string();
+
+        for chunk in symbol_chunks {
+            if chunk.text.len() <= MAX_CHUNK_SIZE {
+                let hash = format!("{:x}", md5::compute(chunk.text.as_bytes()));
+                if seen_hashes.insert(hash) {
+                    chunks.push(FileChunk {
+                        path: path_str.clone(),
+                        text: chunk.text,
+                        start_offset: chunk.start_byte,
+                        start_line: 1,
+                        symbol: chunk.symbol,
+                    });
+                }
+                continue;
+            }
+
+            // A single oversized function: split it but keep its symbol
+            for mut piece in self.chunk_fixed_size_dedup(&chunk.text, path) {
+                piece.start_offset += chunk.start_byte;
+                piece.symbol = chunk.symbol.clone();
+                let hash = format!("{:x}", md5::compute(piece.text.as_bytes()));
+                if seen_hashes.insert(hash) {
+                    chunks.push(piece);
+                }
+            }
+        }
+
+        Some(chunks)
+    }
+
     fn chunk_fixed_size_dedup(&self, text: &str, path: &Path) -> Vec<FileChunk> {
         const CHUNK_SIZE: usize = 1000;
         const OVERLAP: usize = 200;
@@ -301,6 +434,8 @@ impl FileScanner {
                     path: path_str.clone(),
                     text: chunk_text,
                     start_offset: start,
+                    start_line: 1,
+                    symbol: None,
                 });
             }
 
@@ -322,6 +457,27 @@ pub struct FileChunk {
     pub path: String,
     pub text: String,
     pub start_offset: usize,
+    /// 1-based line of `start_offset`, filled in once the whole file is chunked
+    pub start_line: usize,
+    /// Enclosing definition for chunks cut at syntax boundaries
+    pub symbol: Option<CodeSymbol>,
+}
+
+impl FileChunk {
+    /// 1-based line the chunk's text ends on
+    pub fn end_line(&self) -> usize {
+        self.start_line + self.text.trim_end().matches('\n').count()
+    }
+}
+
+/// 1-based line containing byte `offset` of `content`
+fn line_at(content: &str, offset: usize) -> usize {
+    let offset = offset.min(content.len());
+    content.as_bytes()[..offset]
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+        + 1
 }
 
 #[derive(Debug, Clone)]
@@ -330,3 +486,42 @@ pub struct FileScanResult {
     pub hash: String,
     pub chunks: Vec<FileChunk>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_in_root_matches_collected_paths() {
+        let root = std::env::temp_dir().join(format!("bro-scanner-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let scanner = FileScanner::new(&root);
+
+        assert_eq!(
+            scanner.path_in_root(&root.join("src/lib.rs")),
+            Some(root.join("src/lib.rs"))
+        );
+        let canonical = std::fs::canonicalize(&root).unwrap();
+        assert_eq!(
+            scanner.path_in_root(&canonical.join("src/lib.rs")),
+            Some(root.join("src/lib.rs"))
+        );
+        assert_eq!(scanner.path_in_root(&root.join("target/debug/x.rs")), None);
+        assert_eq!(scanner.path_in_root(Path::new("/elsewhere/x.rs")), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_chunks_record_line_range() {
+        let scanner = FileScanner::new(".");
+        let content = "first line\nsecond line\n";
+        let result = scanner.scan_content(Path::new("notes.txt"), content);
+        assert_eq!(result.chunks[0].start_line, 1);
+        assert_eq!(result.chunks[0].end_line(), 2);
+
+        assert_eq!(line_at(content, 0), 1);
+        assert_eq!(line_at(content, 11), 2);
+        assert_eq!(line_at(content, 1000), 3);
+    }
+}
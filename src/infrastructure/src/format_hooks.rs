@@ -0,0 +1,232 @@
+//! Formatters and linters run on the files a build writes
+//!
+//! After a Create/Update the file is formatted with the tool configured for
+//! its extension (rustfmt, prettier or black unless configured otherwise),
+//! then checked by its linter when one is set. A file either tool rejects
+//! comes back as [`HookOutcome::Rejected`] so the build can roll it back and
+//! replan with the findings instead of committing them. Tools that are not
+//! installed are skipped.
+
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+/// Longest a formatter or linter may take on one file
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Characters of tool output kept from the end
+const OUTPUT_TAIL_CHARS: usize = 4000;
+
+/// Placeholder replaced by the file's path in hook commands
+const FILE_PLACEHOLDER: &str = "{file}";
+
+/// Extensions prettier formats by default
+const PRETTIER_EXTENSIONS: [&str; 9] = [
+    "js", "jsx", "mjs", "ts", "tsx", "json", "css", "scss", "html",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatHooks {
+    pub enabled: bool,
+    /// Hooks by extension without the dot; an extension listed here replaces
+    /// the built-in hooks for it, so `"rs": {}` turns rustfmt off
+    pub extensions: BTreeMap<String, ExtensionHooks>,
+}
+
+impl Default for FormatHooks {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            extensions: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtensionHooks {
+    /// Command rewriting the file in place, e.g. `["black", "-q", "{file}"]`
+    pub formatter: Option<Vec<String>>,
+    /// Command that fails when the file has problems
+    pub linter: Option<Vec<String>>,
+}
+
+/// Result of the hooks on one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookOutcome {
+    /// The file passed; `formatter` is the program that reformatted it
+    Passed { formatter: Option<String> },
+    /// The formatter or linter failed on the file
+    Rejected { command: String, output: String },
+}
+
+/// A linter or formatter rejected a file written by a build operation, which
+/// was rolled back
+#[derive(Debug, Clone)]
+pub struct LintFailed {
+    /// The operation, e.g. `Update src/lib.rs`
+    pub operation: String,
+    pub file: PathBuf,
+    pub command: String,
+    /// End of the tool's output
+    pub output: String,
+}
+
+impl fmt::Display for LintFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` rejected {} after {}; the operation was rolled back",
+            self.command,
+            self.file.display(),
+            self.operation
+        )
+    }
+}
+
+impl std::error::Error for LintFailed {}
+
+impl FormatHooks {
+    /// Hooks for files ending in `extension`
+    pub fn for_extension(&self, extension: &str) -> ExtensionHooks {
+        if let Some(hooks) = self.extensions.get(extension) {
+            return hooks.clone();
+        }
+        let command = |words: &[&str]| Some(words.iter().map(|w| w.to_string()).collect());
+        let formatter = match extension {
+            "rs" => command(&["rustfmt", "--edition", "2021", FILE_PLACEHOLDER]),
+            "py" => command(&["black", "-q", FILE_PLACEHOLDER]),
+            ext if PRETTIER_EXTENSIONS.contains(&ext) => command(&[
+                "prettier",
+                "--write",
+                "--log-level",
+                "warn",
+                FILE_PLACEHOLDER,
+            ]),
+            _ => None,
+        };
+        ExtensionHooks {
+            formatter,
+            linter: None,
+        }
+    }
+
+    /// Format `file`, then lint it, running the tools in `root`
+    pub async fn run(&self, file: &Path, root: &Path) -> Result<HookOutcome> {
+        let hooks = file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| self.for_extension(ext))
+            .unwrap_or_default();
+        let mut formatter = None;
+        for (command, is_formatter) in [(&hooks.formatter, true), (&hooks.linter, false)] {
+            let Some(command) = command else {
+                continue;
+            };
+            let Some((program, args)) = command.split_first() else {
+                continue;
+            };
+            let args: Vec<String> = args
+                .iter()
+                .map(|arg| arg.replace(FILE_PLACEHOLDER, &file.to_string_lossy()))
+                .collect();
+            let rendered = std::iter::once(program.clone())
+                .chain(args.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(" ");
+            match run_tool(program, &args, root).await? {
+                None => tracing::debug!("{} is not installed; skipping it", program),
+                Some((true, _)) if is_formatter => formatter = Some(program.clone()),
+                Some((true, _)) => {}
+                Some((false, output)) => {
+                    return Ok(HookOutcome::Rejected {
+                        command: rendered,
+                        output,
+                    })
+                }
+            }
+        }
+        Ok(HookOutcome::Passed { formatter })
+    }
+}
+
+/// Whether `program` succeeded and the end of its output; None when it is
+/// not installed
+async fn run_tool(program: &str, args: &[String], root: &Path) -> Result<Option<(bool, String)>> {
+    let child = tokio::process::Command::new(program)
+        .args(args)
+        .current_dir(root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(anyhow::anyhow!("Could not run `{}`: {}", program, e)),
+    };
+    let Ok(output) = tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output()).await else {
+        return Ok(Some((
+            false,
+            format!("Timed out after {}s", HOOK_TIMEOUT.as_secs()),
+        )));
+    };
+    let output = output?;
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    let text = text.trim_end();
+    let skip = text.chars().count().saturating_sub(OUTPUT_TAIL_CHARS);
+    Ok(Some((
+        output.status.success(),
+        text.chars().skip(skip).collect(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_formats_then_lints_by_extension() {
+        let root = std::env::temp_dir().join(format!("bro-format-hooks-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("notes.txt");
+        std::fs::write(&file, "b\na\n").unwrap();
+
+        let words = |command: &str| Some(command.split(' ').map(str::to_string).collect());
+        let mut hooks = FormatHooks::default();
+        assert_eq!(hooks.for_extension("rs").formatter.unwrap()[0], "rustfmt");
+        assert_eq!(hooks.for_extension("txt"), ExtensionHooks::default());
+        hooks.extensions.insert(
+            "txt".to_string(),
+            ExtensionHooks {
+                formatter: words("sort -o {file} {file}"),
+                linter: words("grep -q c {file}"),
+            },
+        );
+        assert_eq!(
+            hooks.run(&file, &root).await.unwrap(),
+            HookOutcome::Rejected {
+                command: format!("grep -q c {}", file.display()),
+                output: String::new(),
+            }
+        );
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a\nb\n");
+
+        hooks.extensions.get_mut("txt").unwrap().linter = words("bro-no-such-linter {file}");
+        assert_eq!(
+            hooks.run(&file, &root).await.unwrap(),
+            HookOutcome::Passed {
+                formatter: Some("sort".to_string())
+            }
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,749 @@
+//! Predicted filesystem effects of a dry run
+//!
+//! [`FsSimulation`] replays planned shell commands and file operations against
+//! an overlay of the real filesystem, which it never touches, and reports the
+//! difference: "would delete 3 files, create 2 dirs, modify 1 file". It
+//! understands rm, rmdir, mv, cp, mkdir, touch, tee, `sed -i`, cd, output
+//! redirections and `sh -c` scripts; commands that may change files in ways it
+//! cannot predict are listed as unanalyzed instead of being guessed at.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// Programs that do not change files, whatever their arguments
+const READ_ONLY_PROGRAMS: &[&str] = &[
+    "ls", "cat", "less", "more", "head", "tail", "grep", "rg", "echo", "printf", "pwd", "wc",
+    "which", "whoami", "date", "df", "du", "ps", "top", "free", "uname", "true", "false", "test",
+    "[", "stat", "file", "sleep", "sort", "uniq", "cut", "tr", "diff", "tree", "id", "env",
+    "printenv", "hostname", "uptime", "lsblk", "ss", "netstat", "ping", "type",
+];
+
+/// Entries listed per kind of change before the rest are counted
+const MAX_LISTED: usize = 20;
+
+/// Files looked at under a directory that would be deleted or copied
+const MAX_WALKED: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Node {
+    File,
+    Dir,
+}
+
+/// Changes a simulation predicts, relative to the filesystem as it is now
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FsDiff {
+    pub deleted_files: BTreeSet<PathBuf>,
+    pub deleted_dirs: BTreeSet<PathBuf>,
+    pub created_files: BTreeSet<PathBuf>,
+    pub created_dirs: BTreeSet<PathBuf>,
+    pub modified_files: BTreeSet<PathBuf>,
+    /// Commands whose effects could not be predicted
+    pub unanalyzed: Vec<String>,
+}
+
+impl FsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.deleted_files.is_empty()
+            && self.deleted_dirs.is_empty()
+            && self.created_files.is_empty()
+            && self.created_dirs.is_empty()
+            && self.modified_files.is_empty()
+    }
+
+    /// One line: "would delete 3 files, create 2 dirs, modify 1 file"
+    pub fn summary(&self) -> String {
+        let counted = |files: usize, dirs: usize| {
+            let mut parts = Vec::new();
+            if files > 0 {
+                parts.push(plural(files, "file"));
+            }
+            if dirs > 0 {
+                parts.push(plural(dirs, "dir"));
+            }
+            parts.join(" and ")
+        };
+        let changes: Vec<String> = [
+            (
+                "delete",
+                counted(self.deleted_files.len(), self.deleted_dirs.len()),
+            ),
+            (
+                "create",
+                counted(self.created_files.len(), self.created_dirs.len()),
+            ),
+            ("modify", counted(self.modified_files.len(), 0)),
+        ]
+        .into_iter()
+        .filter(|(_, counts)| !counts.is_empty())
+        .map(|(verb, counts)| format!("{} {}", verb, counts))
+        .collect();
+
+        let mut summary = if changes.is_empty() {
+            "no predicted filesystem changes".to_string()
+        } else {
+            format!("would {}", changes.join(", "))
+        };
+        if !self.unanalyzed.is_empty() {
+            summary.push_str(&format!(
+                " ({} not analyzed)",
+                plural(self.unanalyzed.len(), "command")
+            ));
+        }
+        summary
+    }
+}
+
+impl fmt::Display for FsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.summary())?;
+        let groups = [
+            ("-", &self.deleted_files, ""),
+            ("-", &self.deleted_dirs, "/"),
+            ("+", &self.created_files, ""),
+            ("+", &self.created_dirs, "/"),
+            ("~", &self.modified_files, ""),
+        ];
+        for (sign, paths, suffix) in groups {
+            for path in paths.iter().take(MAX_LISTED) {
+                write!(f, "\n  {} {}{}", sign, path.display(), suffix)?;
+            }
+            if paths.len() > MAX_LISTED {
+                write!(f, "\n  {} ... {} more", sign, paths.len() - MAX_LISTED)?;
+            }
+        }
+        for command in &self.unanalyzed {
+            write!(f, "\n  ? {}", command)?;
+        }
+        Ok(())
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// Filesystem as it would be after the simulated commands
+#[derive(Debug, Clone)]
+pub struct FsSimulation {
+    cwd: PathBuf,
+    /// Simulated state of changed paths; None once deleted
+    overlay: HashMap<PathBuf, Option<Node>>,
+    /// Files that existed and would be written to
+    written: HashSet<PathBuf>,
+    unanalyzed: Vec<String>,
+}
+
+impl FsSimulation {
+    /// Simulation starting in `cwd` with the filesystem as it is
+    pub fn new(cwd: impl Into<PathBuf>) -> Self {
+        Self {
+            cwd: cwd.into(),
+            overlay: HashMap::new(),
+            written: HashSet::new(),
+            unanalyzed: Vec::new(),
+        }
+    }
+
+    /// Simulate a shell command line, which may chain several commands
+    pub fn run_command(&mut self, command: &str) {
+        for simple in parse_script(command) {
+            self.run_simple(&simple, command);
+        }
+    }
+
+    /// A file being created, or overwritten when it exists
+    pub fn write_file(&mut self, path: &Path) {
+        let path = self.resolve(path);
+        match self.node(&path) {
+            Some(Node::Dir) => {}
+            Some(Node::File) => {
+                self.written.insert(path.clone());
+                self.overlay.insert(path, Some(Node::File));
+            }
+            None => {
+                self.create_parents(&path);
+                self.overlay.insert(path, Some(Node::File));
+            }
+        }
+    }
+
+    /// A file or directory being deleted, with everything in it
+    pub fn delete(&mut self, path: &Path) {
+        let path = self.resolve(path);
+        self.remove(&path, true);
+    }
+
+    /// What would change, compared with the filesystem now
+    pub fn diff(&self) -> FsDiff {
+        let mut diff = FsDiff {
+            unanalyzed: self.unanalyzed.clone(),
+            ..FsDiff::default()
+        };
+        for (path, simulated) in &self.overlay {
+            let path = path.clone();
+            match (real_node(&path), simulated) {
+                (None, Some(Node::File)) => {
+                    diff.created_files.insert(path);
+                }
+                (None, Some(Node::Dir)) => {
+                    diff.created_dirs.insert(path);
+                }
+                (Some(Node::File), None) => {
+                    diff.deleted_files.insert(path);
+                }
+                (Some(Node::Dir), None) => {
+                    diff.deleted_dirs.insert(path);
+                }
+                (Some(Node::File), Some(Node::File)) if self.written.contains(&path) => {
+                    diff.modified_files.insert(path);
+                }
+                (Some(Node::File), Some(Node::Dir)) => {
+                    diff.deleted_files.insert(path.clone());
+                    diff.created_dirs.insert(path);
+                }
+                (Some(Node::Dir), Some(Node::File)) => {
+                    diff.deleted_dirs.insert(path.clone());
+                    diff.created_files.insert(path);
+                }
+                _ => {}
+            }
+        }
+        diff
+    }
+
+    fn run_simple(&mut self, simple: &SimpleCommand, line: &str) {
+        for target in &simple.writes {
+            let target = self.expand(target);
+            if target != "/dev/null" {
+                self.write_file(Path::new(&target));
+            }
+        }
+
+        let words: Vec<&String> = simple
+            .words
+            .iter()
+            .skip_while(|w| is_assignment(w))
+            .collect();
+        let Some((program, args)) = words.split_first() else {
+            return;
+        };
+        let name = Path::new(program.as_str())
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+
+        match name.as_str() {
+            "sudo" | "env" | "nohup" | "time" | "exec" | "command" | "nice" => {
+                let rest: Vec<String> = args
+                    .iter()
+                    .skip_while(|arg| arg.starts_with('-') || is_assignment(arg))
+                    .cloned()
+                    .collect();
+                if !rest.is_empty() || name != "env" {
+                    let inner = SimpleCommand {
+                        words: rest,
+                        writes: Vec::new(),
+                    };
+                    self.run_simple(&inner, line);
+                }
+            }
+            "sh" | "bash" | "zsh" | "dash" => {
+                match args.iter().position(|arg| arg == "-c") {
+                    Some(i) if i + 1 < args.len() => self.run_command(&args[i + 1]),
+                    // A script file: no telling what it does
+                    _ if !args.is_empty() => self.unanalyzed.push(line.to_string()),
+                    _ => {}
+                }
+            }
+            "cd" => {
+                let target = args
+                    .iter()
+                    .find(|arg| !arg.starts_with('-'))
+                    .map(|arg| self.expand(arg))
+                    .unwrap_or_else(|| shared::platform::home_dir().to_string_lossy().to_string());
+                self.cwd = self.resolve(Path::new(&target));
+            }
+            "rm" => {
+                let (flags, paths) = self.split_args(&args);
+                let recursive = has_flag(&flags, 'r') || has_flag(&flags, 'R');
+                for path in paths {
+                    self.remove(&path, recursive);
+                }
+            }
+            "rmdir" | "unlink" => {
+                let (_, paths) = self.split_args(&args);
+                for path in paths {
+                    self.remove(&path, false);
+                }
+            }
+            "mkdir" => {
+                let (flags, paths) = self.split_args(&args);
+                for path in paths {
+                    if has_flag(&flags, 'p') {
+                        self.create_parents(&path);
+                    }
+                    if self.node(&path).is_none() {
+                        self.overlay.insert(path, Some(Node::Dir));
+                    }
+                }
+            }
+            "touch" => {
+                let (_, paths) = self.split_args(&args);
+                for path in paths {
+                    if self.node(&path).is_none() {
+                        self.overlay.insert(path, Some(Node::File));
+                    }
+                }
+            }
+            "tee" => {
+                let (_, paths) = self.split_args(&args);
+                for path in paths {
+                    self.write_file(&path);
+                }
+            }
+            "cp" | "mv" => {
+                let (flags, mut paths) = self.split_args(&args);
+                let Some(destination) = paths.pop() else {
+                    return;
+                };
+                let into_dir = paths.len() > 1 || self.node(&destination) == Some(Node::Dir);
+                for source in paths {
+                    let target = match source.file_name() {
+                        Some(file_name) if into_dir => destination.join(file_name),
+                        _ => destination.clone(),
+                    };
+                    if name == "mv" {
+                        self.copy(&source, &target, true);
+                        self.remove(&source, true);
+                    } else {
+                        let recursive = has_flag(&flags, 'r') || has_flag(&flags, 'R');
+                        self.copy(&source, &target, recursive || has_flag(&flags, 'a'));
+                    }
+                }
+            }
+            "sed" if args.iter().any(|arg| arg.starts_with("-i")) => {
+                // The first operand is the script, whether or not it follows -e
+                let files: Vec<&String> = args
+                    .iter()
+                    .filter(|arg| !arg.starts_with('-'))
+                    .skip(1)
+                    .collect();
+                for file in files {
+                    let path = self.resolve(Path::new(&self.expand(file)));
+                    self.write_file(&path);
+                }
+            }
+            "find"
+                if args
+                    .iter()
+                    .any(|arg| arg == "-delete" || arg.starts_with("-exec")) =>
+            {
+                self.unanalyzed.push(line.to_string());
+            }
+            "git"
+                if !args
+                    .first()
+                    .is_some_and(|sub| GIT_READ_ONLY.contains(&sub.as_str())) =>
+            {
+                self.unanalyzed.push(line.to_string());
+            }
+            "find" | "git" => {}
+            _ if READ_ONLY_PROGRAMS.contains(&name.as_str()) => {}
+            _ => self.unanalyzed.push(line.to_string()),
+        }
+    }
+
+    /// Flags and the expanded, resolved paths among `args`
+    fn split_args(&self, args: &[String]) -> (Vec<String>, Vec<PathBuf>) {
+        let mut flags = Vec::new();
+        let mut paths = Vec::new();
+        let mut options_done = false;
+        for arg in args {
+            if !options_done && arg == "--" {
+                options_done = true;
+            } else if !options_done && arg.starts_with('-') && arg.len() > 1 {
+                flags.push(arg.clone());
+            } else {
+                paths.extend(self.glob(&self.expand(arg)));
+            }
+        }
+        (flags, paths)
+    }
+
+    /// `~` and `$HOME` replaced
+    fn expand(&self, word: &str) -> String {
+        let home = shared::platform::home_dir().to_string_lossy().to_string();
+        let word = word.replace("${HOME}", &home).replace("$HOME", &home);
+        match word.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home, rest),
+            _ => word,
+        }
+    }
+
+    /// Paths a word names, expanding wildcards in its last component
+    fn glob(&self, word: &str) -> Vec<PathBuf> {
+        let path = self.resolve(Path::new(word));
+        let pattern = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !pattern.contains(['*', '?']) {
+            return vec![path];
+        }
+        let Some(parent) = path.parent() else {
+            return vec![path];
+        };
+        let mut matches: Vec<PathBuf> = self
+            .children(parent)
+            .into_iter()
+            .filter(|child| {
+                child.file_name().is_some_and(|name| {
+                    let name = name.to_string_lossy();
+                    // Like the shell, `*` does not match dotfiles
+                    (!name.starts_with('.') || pattern.starts_with('.'))
+                        && wildcard_match(&pattern, &name)
+                })
+            })
+            .collect();
+        matches.sort();
+        if matches.is_empty() {
+            // An unmatched pattern is passed on as it is
+            vec![path]
+        } else {
+            matches
+        }
+    }
+
+    fn resolve(&self, path: &Path) -> PathBuf {
+        normalize(&self.cwd.join(path))
+    }
+
+    /// Simulated state of `path`
+    fn node(&self, path: &Path) -> Option<Node> {
+        if let Some(state) = self.overlay.get(path) {
+            return *state;
+        }
+        // Anything under a deleted directory or a file is gone too
+        for ancestor in path.ancestors().skip(1) {
+            match self.overlay.get(ancestor) {
+                Some(None) | Some(Some(Node::File)) => return None,
+                _ => {}
+            }
+        }
+        real_node(path)
+    }
+
+    /// Simulated entries of the directory at `dir`
+    fn children(&self, dir: &Path) -> Vec<PathBuf> {
+        if self.node(dir) != Some(Node::Dir) {
+            return Vec::new();
+        }
+        let mut children: BTreeSet<PathBuf> = std::fs::read_dir(dir)
+            .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+            .unwrap_or_default();
+        children.extend(
+            self.overlay
+                .keys()
+                .filter(|path| path.parent() == Some(dir))
+                .cloned(),
+        );
+        children
+            .into_iter()
+            .filter(|child| self.node(child).is_some())
+            .collect()
+    }
+
+    fn create_parents(&mut self, path: &Path) {
+        let missing: Vec<PathBuf> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| self.node(ancestor).is_none())
+            .map(Path::to_path_buf)
+            .collect();
+        for ancestor in missing {
+            self.overlay.insert(ancestor, Some(Node::Dir));
+        }
+    }
+
+    fn remove(&mut self, path: &Path, recursive: bool) {
+        match self.node(path) {
+            None => {}
+            Some(Node::File) => {
+                self.overlay.insert(path.to_path_buf(), None);
+            }
+            Some(Node::Dir) => {
+                let children = self.children(path);
+                if !recursive && !children.is_empty() {
+                    return;
+                }
+                for child in children.into_iter().take(MAX_WALKED) {
+                    self.remove(&child, true);
+                }
+                self.overlay.insert(path.to_path_buf(), None);
+            }
+        }
+    }
+
+    fn copy(&mut self, source: &Path, target: &Path, recursive: bool) {
+        match self.node(source) {
+            Some(Node::File) => self.write_file(target),
+            Some(Node::Dir) if recursive => {
+                if self.node(target).is_none() {
+                    self.create_parents(target);
+                    self.overlay.insert(target.to_path_buf(), Some(Node::Dir));
+                }
+                for child in self.children(source).into_iter().take(MAX_WALKED) {
+                    if let Some(name) = child.file_name() {
+                        self.copy(&child, &target.join(name), true);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// git subcommands that leave the working tree alone
+const GIT_READ_ONLY: &[&str] = &[
+    "status",
+    "log",
+    "diff",
+    "show",
+    "branch",
+    "remote",
+    "config",
+    "blame",
+    "grep",
+    "ls-files",
+    "rev-parse",
+    "describe",
+    "shortlog",
+    "tag",
+    "fetch",
+];
+
+fn real_node(path: &Path) -> Option<Node> {
+    let meta = std::fs::symlink_metadata(path).ok()?;
+    Some(if meta.is_dir() { Node::Dir } else { Node::File })
+}
+
+/// `path` with `.` and `..` removed, without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn has_flag(flags: &[String], short: char) -> bool {
+    flags.iter().any(|flag| match flag.strip_prefix("--") {
+        Some(long) => match short {
+            'r' | 'R' => long == "recursive",
+            'p' => long == "parents",
+            'a' => long == "archive",
+            _ => false,
+        },
+        None => flag.contains(short),
+    })
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// `*` and `?` wildcards
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// One command of a script: its words and the files it writes by redirection
+#[derive(Debug, Default, PartialEq)]
+struct SimpleCommand {
+    words: Vec<String>,
+    writes: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Redirect {
+    /// The next word is a file written to
+    Output,
+    /// The next word is an input file or a duplicated descriptor
+    Other,
+}
+
+/// Simple commands of a script, with quotes removed
+fn parse_script(script: &str) -> Vec<SimpleCommand> {
+    let mut commands = vec![SimpleCommand::default()];
+    let mut word = String::new();
+    let mut quote = None;
+    let mut redirect = None;
+    let mut previous = ' ';
+
+    for ch in script.chars().chain(['\n']) {
+        match (quote, ch) {
+            (Some(open), _) if ch == open => quote = None,
+            (Some(_), _) => word.push(ch),
+            (None, '"' | '\'') => quote = Some(ch),
+            // `2>&1` duplicates a descriptor rather than naming a file
+            (None, '&') if previous == '>' => redirect = Some(Redirect::Other),
+            (None, _) if !" \t\n;|&()`<>".contains(ch) => word.push(ch),
+            (None, _) => {
+                // The descriptor number in `2>` is not an argument
+                let descriptor =
+                    ch == '>' && !word.is_empty() && word.chars().all(|c| c.is_ascii_digit());
+                if !word.is_empty() && !descriptor {
+                    let word = std::mem::take(&mut word);
+                    let command = commands.last_mut().unwrap();
+                    match redirect.take() {
+                        Some(Redirect::Output) => command.writes.push(word),
+                        Some(Redirect::Other) => {}
+                        None => command.words.push(word),
+                    }
+                }
+                word.clear();
+                match ch {
+                    '>' => redirect = Some(Redirect::Output),
+                    '<' => redirect = Some(Redirect::Other),
+                    ' ' | '\t' => {}
+                    _ => {
+                        redirect = None;
+                        commands.push(SimpleCommand::default());
+                    }
+                }
+            }
+        }
+        previous = ch;
+    }
+    commands.retain(|command| !command.words.is_empty() || !command.writes.is_empty());
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("bro-fs-sim-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("build/cache")).unwrap();
+        for file in [
+            "build/a.o",
+            "build/b.o",
+            "build/cache/c.bin",
+            "notes.txt",
+            "main.rs",
+        ] {
+            std::fs::write(root.join(file), "x").unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_commands_predict_a_diff_without_touching_files() {
+        let root = workspace();
+        let mut simulation = FsSimulation::new(&root);
+        simulation.run_command("rm -rf build && mkdir -p out/logs");
+        simulation.run_command("cargo run 2>&1 > out/run.log; echo done >> notes.txt");
+        simulation.run_command("mv main.rs src.rs");
+        simulation.run_command("cat *.txt | tee copy.txt > /dev/null");
+
+        let diff = simulation.diff();
+        assert_eq!(diff.deleted_files.len(), 4);
+        assert_eq!(diff.deleted_dirs.len(), 2);
+        assert_eq!(
+            diff.created_dirs,
+            BTreeSet::from([root.join("out"), root.join("out/logs")])
+        );
+        assert_eq!(
+            diff.created_files,
+            BTreeSet::from([
+                root.join("out/run.log"),
+                root.join("src.rs"),
+                root.join("copy.txt")
+            ])
+        );
+        assert_eq!(
+            diff.modified_files,
+            BTreeSet::from([root.join("notes.txt")])
+        );
+        assert_eq!(
+            diff.unanalyzed,
+            ["cargo run 2>&1 > out/run.log; echo done >> notes.txt"]
+        );
+        assert_eq!(
+            diff.summary(),
+            "would delete 4 files and 2 dirs, create 3 files and 2 dirs, modify 1 file \
+             (1 command not analyzed)"
+        );
+        assert!(root.join("build/a.o").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_file_operations_and_changes_that_cancel_out() {
+        let root = workspace();
+        let mut simulation = FsSimulation::new(&root);
+        simulation.write_file(Path::new("src/lib.rs"));
+        simulation.write_file(&root.join("main.rs"));
+        simulation.delete(Path::new("notes.txt"));
+        simulation.run_command("touch scratch && rm scratch && cd build && rm *.o");
+
+        let diff = simulation.diff();
+        assert_eq!(
+            diff.created_files,
+            BTreeSet::from([root.join("src/lib.rs")])
+        );
+        assert_eq!(diff.created_dirs, BTreeSet::from([root.join("src")]));
+        assert_eq!(diff.modified_files, BTreeSet::from([root.join("main.rs")]));
+        assert_eq!(
+            diff.deleted_files,
+            BTreeSet::from([
+                root.join("notes.txt"),
+                root.join("build/a.o"),
+                root.join("build/b.o")
+            ])
+        );
+        assert!(diff.unanalyzed.is_empty());
+        assert!(FsSimulation::new(&root).diff().is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,157 @@
+//! Read-only access to the git repository being indexed
+//!
+//! Used by git-aware RAG indexing: the working tree is listed through git so
+//! ignored build artifacts are skipped, and historical snapshots are read
+//! straight from the object database without touching the checkout.
+
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone)]
+pub struct GitRepo {
+    root: PathBuf,
+}
+
+impl GitRepo {
+    /// Repository containing `path`, if any
+    pub fn discover(path: &Path) -> Option<Self> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .args(["rev-parse", "--show-toplevel"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let root = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Some(Self {
+            root: PathBuf::from(root),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Full hash of the commit `rev` points to
+    pub fn resolve(&self, rev: &str) -> Result<String> {
+        let hash = self.git(&["rev-parse", "--verify", &format!("{}^{{commit}}", rev)])?;
+        Ok(hash.trim().to_string())
+    }
+
+    /// Commit currently checked out, `None` before the first commit
+    pub fn head(&self) -> Option<String> {
+        self.resolve("HEAD").ok()
+    }
+
+    /// Branch currently checked out, `None` on a detached HEAD
+    pub fn branch(&self) -> Option<String> {
+        let name = self.git(&["symbolic-ref", "--short", "-q", "HEAD"]).ok()?;
+        Some(name.trim().to_string()).filter(|name| !name.is_empty())
+    }
+
+    /// Whether the commit still exists, e.g. was not rebased away and collected
+    pub fn has_commit(&self, commit: &str) -> bool {
+        self.git(&["cat-file", "-e", &format!("{}^{{commit}}", commit)])
+            .is_ok()
+    }
+
+    /// Tracked files plus untracked files that are not ignored, as absolute paths
+    pub fn working_tree_files(&self) -> Result<Vec<PathBuf>> {
+        let listing = self.git(&[
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ])?;
+        Ok(split_nul(&listing)
+            .map(|path| self.root.join(path))
+            .filter(|path| path.is_file())
+            .collect())
+    }
+
+    /// Files in `commit`, relative to the repository root
+    pub fn files_at(&self, commit: &str) -> Result<Vec<String>> {
+        let listing = self.git(&["ls-tree", "-r", "-z", "--name-only", commit])?;
+        Ok(split_nul(&listing).map(str::to_string).collect())
+    }
+
+    /// Content of `path` as of `commit`
+    pub fn read_at(&self, commit: &str, path: &str) -> Result<String> {
+        self.git(&["show", &format!("{}:{}", commit, path)])
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.root)
+            .args(args)
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
+
+fn split_nul(listing: &str) -> impl Iterator<Item = &str> {
+    listing.split('\0').filter(|path| !path.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_and_working_tree_listing() {
+        let dir = std::env::temp_dir().join(format!("bro-git-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| {
+            let ok = Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .output()
+                .map(|out| out.status.success())
+                .unwrap_or(false);
+            assert!(ok, "git {:?} failed", args);
+        };
+        git(&["init", "-q"]);
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(dir.join("lib.rs"), "fn one() {}\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "first"]);
+
+        let repo = GitRepo::discover(&dir).unwrap();
+        let first = repo.head().unwrap();
+        std::fs::write(dir.join("lib.rs"), "fn two() {}\n").unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::write(dir.join("target/out.rs"), "").unwrap();
+        std::fs::write(dir.join("new.rs"), "").unwrap();
+
+        let mut files: Vec<_> = repo
+            .working_tree_files()
+            .unwrap()
+            .into_iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec![".gitignore", "lib.rs", "new.rs"]);
+
+        assert_eq!(repo.files_at(&first).unwrap(), vec![".gitignore", "lib.rs"]);
+        assert_eq!(repo.read_at(&first, "lib.rs").unwrap(), "fn one() {}\n");
+        assert_eq!(repo.resolve("HEAD").unwrap(), first);
+        assert!(repo.has_commit(&first));
+        assert!(repo.resolve("no-such-rev").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,10 @@
-use crate::embedding_storage::EmbeddingStorage;
-use crate::qdrant_storage::QdrantStorage;
+use crate::embedder::{EmbeddingMismatch, EmbeddingSpec};
+use crate::embedding_storage::{CompactionReport, EmbeddingStorage};
+use crate::qdrant_storage::{DimensionMismatch, QdrantStorage};
 use domain::models::Embedding;
+use shared::cancellation::CancellationToken;
 use shared::types::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 /// Hybrid storage manager that automatically chooses between Qdrant and SQLite
@@ -13,26 +15,74 @@ pub struct HybridStorage {
 }
 
 impl HybridStorage {
-    /// Create hybrid storage with automatic fallback
+    /// Create hybrid storage with automatic fallback.
+    ///
+    /// Stores indexed with a different embedding model or vector size than
+    /// `spec` fail with [`EmbeddingMismatch`], unless `reembed` is set: then
+    /// both are backed up and emptied so the next index build re-embeds
+    /// everything with the configured model.
     pub async fn new(
         qdrant_url: Option<String>,
         sqlite_path: impl AsRef<Path>,
         collection_name: String,
-        vector_dim: usize,
+        spec: &EmbeddingSpec,
+        reembed: bool,
     ) -> Result<Self> {
         let sqlite = EmbeddingStorage::new(&sqlite_path).await?;
+        let mut mismatch = Self::sqlite_mismatch(&sqlite, spec).await?;
+
         let qdrant = if let Some(url) = qdrant_url {
-            match QdrantStorage::new(Some(url), collection_name, vector_dim).await {
+            match QdrantStorage::new(Some(url.clone()), collection_name.clone(), spec.dimensions)
+                .await
+            {
                 Ok(storage) => Some(storage),
-                Err(e) => {
-                    eprintln!("Warning: Qdrant initialization failed: {}", e);
-                    None
-                }
+                Err(e) => match e.downcast_ref::<DimensionMismatch>() {
+                    Some(found) if reembed => {
+                        mismatch.get_or_insert(EmbeddingMismatch {
+                            store: format!("Qdrant collection '{}'", found.collection),
+                            indexed_model: None,
+                            indexed_dimensions: found.found,
+                            configured: spec.clone(),
+                        });
+                        Some(
+                            QdrantStorage::recreate(Some(url), collection_name, spec.dimensions)
+                                .await?,
+                        )
+                    }
+                    Some(found) => {
+                        return Err(EmbeddingMismatch {
+                            store: format!("Qdrant collection '{}'", found.collection),
+                            indexed_model: None,
+                            indexed_dimensions: found.found,
+                            configured: spec.clone(),
+                        }
+                        .into())
+                    }
+                    None => {
+                        eprintln!("Warning: Qdrant initialization failed: {}", e);
+                        None
+                    }
+                },
             }
         } else {
             None
         };
 
+        if let Some(mismatch) = mismatch {
+            if !reembed {
+                return Err(mismatch.into());
+            }
+            let backup = sqlite.reset().await?;
+            eprintln!(
+                "Re-embedding with {}: {} had {}-dimensional vectors (local index backed up to {})",
+                spec,
+                mismatch.store,
+                mismatch.indexed_dimensions,
+                backup.display()
+            );
+        }
+        sqlite.set_embedding_spec(spec).await?;
+
         let use_qdrant = qdrant.is_some();
 
         Ok(Self {
@@ -42,17 +92,56 @@ impl HybridStorage {
         })
     }
 
+    async fn sqlite_mismatch(
+        sqlite: &EmbeddingStorage,
+        spec: &EmbeddingSpec,
+    ) -> Result<Option<EmbeddingMismatch>> {
+        let Some((model, dimensions)) = sqlite.indexed_spec().await? else {
+            return Ok(None);
+        };
+        let same_model = model.as_ref().map_or(true, |m| *m == spec.model);
+        if same_model && dimensions == spec.dimensions {
+            return Ok(None);
+        }
+        Ok(Some(EmbeddingMismatch {
+            store: sqlite.db_path().display().to_string(),
+            indexed_model: model,
+            indexed_dimensions: dimensions,
+            configured: spec.clone(),
+        }))
+    }
+
+    /// Qdrant, unless it is off or skipped after repeated failures
+    fn live_qdrant(&self) -> Option<&QdrantStorage> {
+        self.qdrant
+            .as_ref()
+            .filter(|qdrant| self.use_qdrant && !qdrant.is_unavailable())
+    }
+
+    /// Whether a failed Qdrant call tripped its breaker, so SQLite takes over
+    fn falls_back(qdrant: &QdrantStorage, error: &anyhow::Error) -> bool {
+        if qdrant.is_unavailable() {
+            eprintln!("Warning: {}; using the local index", error);
+            return true;
+        }
+        false
+    }
+
+    /// Abort Qdrant calls in flight once `cancel` fires
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.qdrant = self.qdrant.map(|qdrant| qdrant.with_cancellation(cancel));
+        self
+    }
+
     /// Insert embeddings using the best available storage
     pub async fn insert_embeddings(&self, embeddings: Vec<Embedding>) -> Result<()> {
-        if self.use_qdrant {
-            if let Some(qdrant) = &self.qdrant {
-                qdrant.insert_embeddings(embeddings).await
-            } else {
-                self.sqlite.insert_embeddings(embeddings).await
+        if let Some(qdrant) = self.live_qdrant() {
+            match qdrant.insert_embeddings(embeddings.clone()).await {
+                Err(e) if Self::falls_back(qdrant, &e) => {}
+                result => return result,
             }
-        } else {
-            self.sqlite.insert_embeddings(embeddings).await
         }
+        self.sqlite.insert_embeddings(embeddings).await
     }
 
     /// Search for similar embeddings
@@ -61,15 +150,13 @@ impl HybridStorage {
         query_vector: &[f32],
         limit: usize,
     ) -> Result<Vec<Embedding>> {
-        if self.use_qdrant {
-            if let Some(qdrant) = &self.qdrant {
-                qdrant.search_similar(query_vector, limit).await
-            } else {
-                self.fallback_search(query_vector, limit).await
+        if let Some(qdrant) = self.live_qdrant() {
+            match qdrant.search_similar(query_vector, limit).await {
+                Err(e) if Self::falls_back(qdrant, &e) => {}
+                result => return result,
             }
-        } else {
-            self.fallback_search(query_vector, limit).await
         }
+        self.fallback_search(query_vector, limit).await
     }
 
     /// Fallback search using SQLite
@@ -116,33 +203,61 @@ impl HybridStorage {
         self.sqlite.upsert_file_hash(path, hash).await
     }
 
+    /// Paths with a recorded file hash, i.e. everything currently indexed
+    pub async fn list_indexed_paths(&self) -> Result<Vec<String>> {
+        self.sqlite.list_file_paths().await
+    }
+
+    /// Remove a file from the index: its embeddings and its manifest entry
+    pub async fn prune_path(&self, path: String) -> Result<()> {
+        self.delete_embeddings_for_path(path.clone()).await?;
+        self.sqlite.delete_file_hash(path).await
+    }
+
     /// Delete embeddings for path
     pub async fn delete_embeddings_for_path(&self, path: String) -> Result<()> {
-        if self.use_qdrant {
-            if let Some(qdrant) = &self.qdrant {
-                qdrant.delete_embeddings_for_path(path.as_str()).await?;
+        if let Some(qdrant) = self.live_qdrant() {
+            match qdrant.delete_embeddings_for_path(path.as_str()).await {
+                Err(e) if !Self::falls_back(qdrant, &e) => return Err(e),
+                _ => {}
             }
         }
         self.sqlite.delete_embeddings_for_path(path).await
     }
 
+    /// Deduplicate identical chunks and drop points for paths that are no
+    /// longer indexed, in Qdrant and the local database
+    pub async fn compact(&self) -> Result<CompactionReport> {
+        let mut report = CompactionReport::default();
+        if let Some(qdrant) = self.live_qdrant() {
+            let live_paths: HashSet<String> =
+                self.sqlite.list_file_paths().await?.into_iter().collect();
+            match qdrant.compact(&live_paths).await {
+                Ok(qdrant_report) => report.merge(qdrant_report),
+                Err(e) if !Self::falls_back(qdrant, &e) => return Err(e),
+                Err(_) => {}
+            }
+        }
+        report.merge(self.sqlite.compact().await?);
+        Ok(report)
+    }
+
     /// Get storage statistics
     pub async fn get_stats(&self) -> Result<HashMap<String, String>> {
         let mut stats = HashMap::new();
 
-        if self.use_qdrant {
-            if let Some(qdrant) = &self.qdrant {
-                if let Ok(qdrant_stats) = qdrant.get_stats().await {
-                    stats.extend(qdrant_stats);
-                }
+        if let Some(qdrant) = self.live_qdrant() {
+            if let Ok(qdrant_stats) = qdrant.get_stats().await {
+                stats.extend(qdrant_stats);
             }
         }
 
         // Add hybrid-specific stats
-        stats.insert("hybrid_mode".to_string(), self.use_qdrant.to_string());
+        let on_qdrant = self.is_qdrant_available();
+        stats.insert("hybrid_mode".to_string(), on_qdrant.to_string());
         stats.insert(
             "primary_storage".to_string(),
-            if self.use_qdrant { "qdrant" } else { "sqlite" }.to_string(),
+            if on_qdrant { "qdrant" } else { "sqlite" }.to_string(),
         );
 
         Ok(stats)
@@ -150,7 +265,7 @@ impl HybridStorage {
 
     /// Check if Qdrant is available and working
     pub fn is_qdrant_available(&self) -> bool {
-        self.use_qdrant && self.qdrant.is_some()
+        self.live_qdrant().is_some()
     }
 
     /// Force fallback to SQLite
@@ -158,3 +273,116 @@ impl HybridStorage {
         self.use_qdrant = false;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(model: &str, dimensions: usize) -> EmbeddingSpec {
+        EmbeddingSpec {
+            model: model.to_string(),
+            dimensions,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embedding_model_change_requires_reembed() {
+        let dir = std::env::temp_dir().join(format!("bro-hybrid-{}", std::process::id()));
+        let db_path = dir.join("index.db");
+        let original = spec("nomic-embed-text", 3);
+
+        let storage = HybridStorage::new(None, &db_path, "test".into(), &original, false)
+            .await
+            .unwrap();
+        storage
+            .insert_embeddings(vec![Embedding {
+                id: "a".to_string(),
+                vector: vec![0.1, 0.2, 0.3],
+                text: "fn main() {}".to_string(),
+                path: "src/main.rs".to_string(),
+                symbol: None,
+                commit: None,
+            }])
+            .await
+            .unwrap();
+        storage
+            .upsert_file_hash("src/main.rs".into(), "abc".into())
+            .await
+            .unwrap();
+        drop(storage);
+
+        // Reopening with the same model keeps the index
+        let storage = HybridStorage::new(None, &db_path, "test".into(), &original, false)
+            .await
+            .unwrap();
+        assert_eq!(storage.get_all_embeddings().await.unwrap().len(), 1);
+        drop(storage);
+
+        let changed = spec("mxbai-embed-large", 4);
+        let err = HybridStorage::new(None, &db_path, "test".into(), &changed, false)
+            .await
+            .err()
+            .unwrap();
+        let mismatch = err.downcast_ref::<EmbeddingMismatch>().unwrap();
+        assert_eq!(mismatch.indexed_model.as_deref(), Some("nomic-embed-text"));
+        assert_eq!(mismatch.indexed_dimensions, 3);
+
+        let storage = HybridStorage::new(None, &db_path, "test".into(), &changed, true)
+            .await
+            .unwrap();
+        assert!(storage.get_all_embeddings().await.unwrap().is_empty());
+        assert!(storage
+            .get_file_hash("src/main.rs".into())
+            .await
+            .unwrap()
+            .is_none());
+        drop(storage);
+        assert!(
+            HybridStorage::new(None, &db_path, "test".into(), &changed, false)
+                .await
+                .is_ok()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_compact_removes_duplicates_and_stale_points() {
+        let dir = std::env::temp_dir().join(format!("bro-compact-{}", std::process::id()));
+        let db_path = dir.join("index.db");
+        let storage = HybridStorage::new(None, &db_path, "test".into(), &spec("m", 2), false)
+            .await
+            .unwrap();
+
+        let embedding = |id: &str, path: &str, text: &str| Embedding {
+            id: id.to_string(),
+            vector: vec![0.1, 0.2],
+            text: text.to_string(),
+            path: path.to_string(),
+            symbol: None,
+            commit: None,
+        };
+        storage
+            .insert_embeddings(vec![
+                embedding("a:0", "a.rs", "fn a() {}"),
+                embedding("a:0-old", "a.rs", "fn a() {}"),
+                embedding("gone:0", "gone.rs", "fn gone() {}"),
+            ])
+            .await
+            .unwrap();
+        storage
+            .upsert_file_hash("a.rs".into(), "h".into())
+            .await
+            .unwrap();
+
+        let report = storage.compact().await.unwrap();
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(report.stale, 1);
+        let remaining = storage.get_all_embeddings().await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, "a:0");
+
+        // Compacting again finds nothing left to do
+        assert_eq!(storage.compact().await.unwrap().removed(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
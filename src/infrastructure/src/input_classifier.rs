@@ -33,6 +33,7 @@ pub enum InputType {
     CodeSnippet,   // Code to analyze/explain
     FileOperation, // File operations (read, write, etc.)
     SystemQuery,   // System information queries
+    CodeChange,    // Change to the project's code, for build mode
     Ambiguous,     // Cannot determine with confidence
 }
 
@@ -93,12 +94,13 @@ impl InputClassifier {
 - CodeSnippet: Code that needs analysis or explanation
 - FileOperation: File read/write/create operations
 - SystemQuery: System information or status queries
+- CodeChange: Request to change the project's source code
 
 Input: \"{}\"
 
 Respond with JSON in this format:
 {{
-  \"category\": \"Command|Question|Conversation|CodeSnippet|FileOperation|SystemQuery\",
+  \"category\": \"Command|Question|Conversation|CodeSnippet|FileOperation|SystemQuery|CodeChange\",
   \"confidence\": 0.0-1.0,
   \"reasoning\": \"brief explanation\",
   \"suggested_action\": \"what the system should do\"
@@ -140,6 +142,7 @@ Respond with JSON in this format:
             "CodeSnippet" => InputType::CodeSnippet,
             "FileOperation" => InputType::FileOperation,
             "SystemQuery" => InputType::SystemQuery,
+            "CodeChange" => InputType::CodeChange,
             _ => InputType::Ambiguous,
         };
 
@@ -170,6 +173,7 @@ Respond with JSON in this format:
             InputType::CodeSnippet => "Analyze and explain the provided code".to_string(),
             InputType::FileOperation => "Perform the requested file operation safely".to_string(),
             InputType::SystemQuery => "Query system information and provide status".to_string(),
+            InputType::CodeChange => "Plan and apply the change in build mode".to_string(),
             InputType::Ambiguous => "Ask user for clarification on their request".to_string(),
         };
 
@@ -279,6 +283,7 @@ Respond with JSON in this format:
             InputType::Question => 0.7,      // Medium confidence for questions
             InputType::CodeSnippet => 0.8,   // High confidence for code
             InputType::SystemQuery => 0.8,   // High confidence for system queries
+            InputType::CodeChange => 0.8,    // High confidence before switching modes
             InputType::Conversation => 0.6,  // Lower threshold for conversation
             InputType::Ambiguous => 0.0,     // Always ambiguous
         }
@@ -377,6 +382,14 @@ impl HeuristicClassifier {
             );
         }
 
+        if looks_like_code_change(input) {
+            return (
+                InputType::CodeChange,
+                0.8,
+                "Reads like a change to the project's code".to_string(),
+            );
+        }
+
         // Check for questions
         if question_score > 0.2 || input.ends_with('?') {
             return (
@@ -444,3 +457,112 @@ impl HeuristicClassifier {
         }
     }
 }
+
+/// Verbs that on their own describe work on code
+const CODE_VERBS: &[&str] = &["implement", "refactor", "rename", "extract"];
+
+/// Verbs that describe work on code when the request names something in it
+const CHANGE_VERBS: &[&str] = &[
+    "add",
+    "fix",
+    "rewrite",
+    "introduce",
+    "support",
+    "handle",
+    "optimize",
+    "change",
+    "update",
+    "remove",
+    "replace",
+];
+
+const CODE_NOUNS: &[&str] = &[
+    "function",
+    "functions",
+    "method",
+    "methods",
+    "struct",
+    "enum",
+    "trait",
+    "class",
+    "module",
+    "field",
+    "test",
+    "tests",
+    "logging",
+    "endpoint",
+    "handler",
+    "parser",
+    "error",
+    "errors",
+    "docs",
+    "docstring",
+    "comments",
+    "validation",
+    "cli",
+    "flag",
+    "api",
+];
+
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "ts", "tsx", "js", "jsx", "go", "java", "kt", "c", "cc", "cpp", "h", "hpp", "rb",
+    "swift", "cs",
+];
+
+/// Whether a bare query reads like a build goal ("add logging to rag_service")
+/// rather than a request for a shell command ("add user to docker group")
+pub fn looks_like_code_change(input: &str) -> bool {
+    let lower = input.trim().to_lowercase();
+    let lower = lower.strip_prefix("please ").unwrap_or(&lower);
+    let mut words = lower.split_whitespace();
+    let Some(verb) = words.next() else {
+        return false;
+    };
+    if CODE_VERBS.contains(&verb) {
+        return true;
+    }
+    CHANGE_VERBS.contains(&verb)
+        && (words.any(|w| CODE_NOUNS.contains(&w.trim_matches(|c: char| !c.is_alphanumeric())))
+            || input.split_whitespace().skip(1).any(is_code_identifier))
+}
+
+/// `snake_case`, `CamelCase`, `path::to`, `call()` or a source file name
+fn is_code_identifier(word: &str) -> bool {
+    let word = word.trim_matches(|c: char| matches!(c, ',' | '.' | '`' | '"' | '\'' | ':' | ';'));
+    if word.contains("::") || word.ends_with("()") {
+        return true;
+    }
+    if let Some((_, ext)) = word.rsplit_once('.') {
+        return SOURCE_EXTENSIONS.contains(&ext);
+    }
+    let has_inner_upper = word
+        .chars()
+        .zip(word.chars().skip(1))
+        .any(|(a, b)| a.is_lowercase() && b.is_uppercase());
+    let is_snake = word.contains('_')
+        && !word.contains('/')
+        && word.chars().all(|c| c.is_alphanumeric() || c == '_');
+    is_snake || has_inner_upper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_change_heuristic() {
+        assert!(looks_like_code_change("add logging to rag_service"));
+        assert!(looks_like_code_change("Add error handling to the parser"));
+        assert!(looks_like_code_change("refactor the session store"));
+        assert!(looks_like_code_change("fix the panic in OllamaClient"));
+        assert!(looks_like_code_change("update cli.rs to accept --json"));
+
+        assert!(!looks_like_code_change("add user to docker group"));
+        assert!(!looks_like_code_change("fix permissions on ~/.ssh"));
+        assert!(!looks_like_code_change("remove old backups.tar.gz"));
+        assert!(!looks_like_code_change("list files in src"));
+
+        let (input_type, _, _) = HeuristicClassifier::new().classify("add logging to rag_service");
+        assert_eq!(input_type, InputType::CodeChange);
+    }
+}
@@ -2,49 +2,103 @@ pub mod adapters;
 pub mod agent_control;
 pub mod ast_parser;
 pub mod background_supervisor;
+pub mod batch_jobs;
 pub mod browser_automation;
+pub mod build_branch;
+pub mod capabilities;
+pub mod capability_registry;
+pub mod cargo_workspace;
 pub mod chatgpt_browser;
 pub mod chatgpt_ocr;
+pub mod command_audit;
 pub mod command_interpreter;
 pub mod compilation_watcher;
 pub mod config;
+pub mod config_edit;
+pub mod context_window;
+pub mod credentials;
+pub mod dependency_ops;
+pub mod diff_engine;
 pub mod embedder;
 pub mod embedding_storage;
+pub mod environment_snapshot;
 pub mod error_analyzer;
+pub mod event_stream;
 pub mod expert_resolver;
 pub mod feature_flags;
+pub mod file_lock;
+pub mod file_queue;
 pub mod file_scanner;
 pub mod fix_applier;
+pub mod format_hooks;
+pub mod fs_simulation;
+pub mod git_repo;
 pub mod hybrid_storage;
 pub mod input_classifier;
 pub mod log_tailer;
+pub mod logging;
 pub mod lsp_client;
+pub mod mcp_client;
+pub mod mock_inference;
+pub mod model_comparison;
 pub mod network_security;
 pub mod observability;
 pub mod ollama_client;
+pub mod output_history;
+pub mod patch;
+pub mod plugin_marketplace;
 pub mod plugin_registry;
 pub mod policy_engine;
 pub mod privacy_controls;
+pub mod prompt_archive;
+pub mod prompt_templates;
+pub mod provenance;
 pub mod qdrant_advanced;
+pub mod qdrant_guard;
 pub mod qdrant_storage;
+pub mod rag_feedback;
+pub mod recycle_bin;
 pub mod repositories;
 pub mod resource_enforcement;
+pub mod run_checkpoint;
 pub mod safety;
+pub mod sampling;
 pub mod sandbox;
+pub mod scaffold;
+pub mod schema_migrations;
 pub mod script_executor;
 pub mod search;
+pub mod service_watchdog;
+pub mod session_merge;
+pub mod session_squash;
 pub mod session_store;
+pub mod session_sync;
+pub mod session_timeline;
 pub mod shell_monitor;
 pub mod smart_router;
+pub mod structured_output;
+pub mod syntax_check;
+pub mod test_gate;
 pub mod test_watcher;
+pub mod three_way_merge;
+pub mod todo_list;
+pub mod token_usage;
+pub mod tool_cache;
 pub mod tools;
+pub mod undo_journal;
+pub mod user_questions;
 pub mod web_search;
 pub mod workflow_executor;
+pub mod workspace_lock;
+pub mod write_conflicts;
+pub mod write_guards;
 
 /// Common inference enum for different backends (Ollama, etc.)
 #[derive(Clone)]
 pub enum InferenceEngine {
     Ollama(ollama_client::OllamaClient),
+    /// Canned replies for deterministic tests
+    Mock(mock_inference::MockInference),
 }
 
 impl InferenceEngine {
@@ -52,6 +106,7 @@ impl InferenceEngine {
     pub async fn generate(&self, prompt: &str) -> shared::types::Result<String> {
         match self {
             InferenceEngine::Ollama(client) => client.generate_response(prompt).await,
+            InferenceEngine::Mock(mock) => mock.generate(prompt),
         }
     }
 
@@ -59,6 +114,57 @@ impl InferenceEngine {
     pub async fn generate_embeddings(&self, text: &str) -> shared::types::Result<Vec<f32>> {
         match self {
             InferenceEngine::Ollama(client) => client.generate_embedding(text).await,
+            InferenceEngine::Mock(mock) => Ok(mock.embed(text)),
+        }
+    }
+
+    /// Generate embeddings for several texts, in order
+    pub async fn generate_embeddings_batch(
+        &self,
+        texts: Vec<String>,
+    ) -> shared::types::Result<Vec<Vec<f32>>> {
+        match self {
+            InferenceEngine::Ollama(client) => client.generate_embeddings_batch(texts).await,
+            InferenceEngine::Mock(mock) => Ok(texts.iter().map(|text| mock.embed(text)).collect()),
+        }
+    }
+
+    /// Model that produces embeddings
+    pub fn embedding_model(&self) -> &str {
+        match self {
+            InferenceEngine::Ollama(client) => client.embedding_model(),
+            InferenceEngine::Mock(mock) => mock.model(),
+        }
+    }
+
+    /// Same backend generating with `model`, e.g. a small model for cheap
+    /// auxiliary prompts; embeddings are unaffected
+    pub fn with_generation_model(&self, model: &str) -> Self {
+        match self {
+            InferenceEngine::Ollama(client) => {
+                InferenceEngine::Ollama(client.clone().with_model(model))
+            }
+            InferenceEngine::Mock(mock) => InferenceEngine::Mock(mock.clone()),
+        }
+    }
+
+    /// Same backend with replies constrained to JSON matching `schema`
+    pub fn with_output_format(&self, schema: serde_json::Value) -> Self {
+        match self {
+            InferenceEngine::Ollama(client) => {
+                InferenceEngine::Ollama(client.clone().with_format(schema))
+            }
+            InferenceEngine::Mock(mock) => InferenceEngine::Mock(mock.clone()),
+        }
+    }
+
+    /// Same backend sampling as configured for `mode`
+    pub fn with_mode(&self, mode: sampling::GenerationMode) -> Self {
+        match self {
+            InferenceEngine::Ollama(client) => {
+                InferenceEngine::Ollama(client.clone().with_mode(mode))
+            }
+            InferenceEngine::Mock(mock) => InferenceEngine::Mock(mock.clone()),
         }
     }
 
@@ -66,7 +172,7 @@ impl InferenceEngine {
     pub async fn generate_streaming<F>(
         &self,
         prompt: &str,
-        on_chunk: F,
+        mut on_chunk: F,
     ) -> shared::types::Result<String>
     where
         F: FnMut(&str) + Send,
@@ -75,6 +181,54 @@ impl InferenceEngine {
             InferenceEngine::Ollama(client) => {
                 client.generate_response_streaming(prompt, on_chunk).await
             }
+            InferenceEngine::Mock(mock) => {
+                let reply = mock.generate(prompt)?;
+                on_chunk(&reply);
+                Ok(reply)
+            }
+        }
+    }
+
+    /// Streaming generation that can be aborted mid-stream, returning the partial output
+    pub async fn generate_streaming_cancellable<F>(
+        &self,
+        prompt: &str,
+        mut on_chunk: F,
+        cancel: &shared::cancellation::CancellationToken,
+    ) -> shared::types::Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        match self {
+            InferenceEngine::Ollama(client) => {
+                client
+                    .generate_response_streaming_cancellable(prompt, on_chunk, cancel)
+                    .await
+            }
+            InferenceEngine::Mock(mock) => {
+                if cancel.is_cancelled() {
+                    return Ok(String::new());
+                }
+                let reply = mock.generate(prompt)?;
+                on_chunk(&reply);
+                Ok(reply)
+            }
+        }
+    }
+
+    /// Context window size of the selected model, if the backend reports it
+    pub async fn context_length(&self) -> Option<usize> {
+        match self {
+            InferenceEngine::Ollama(client) => client.fetch_context_length().await.ok().flatten(),
+            InferenceEngine::Mock(_) => None,
+        }
+    }
+
+    /// Token usage accumulated by this engine's backend
+    pub fn usage(&self) -> token_usage::TokenUsage {
+        match self {
+            InferenceEngine::Ollama(client) => client.usage_tracker().snapshot(),
+            InferenceEngine::Mock(mock) => mock.usage_tracker().snapshot(),
         }
     }
 
@@ -87,6 +241,12 @@ impl InferenceEngine {
                 backend: "Ollama".to_string(),
                 device: "Remote".to_string(),
             },
+            InferenceEngine::Mock(mock) => ModelInfo {
+                model_id: mock.model().to_string(),
+                architecture: "Canned responses".to_string(),
+                backend: "Mock".to_string(),
+                device: "None".to_string(),
+            },
         }
     }
 }
@@ -0,0 +1,108 @@
+//! Log output for `-v`/`-vv`/`-vvv` and `--debug module=...`
+//!
+//! Verbosity sets the level of the workspace crates: warnings only by default,
+//! then info and debug; `-vvv` traces dependencies too. `--debug` raises
+//! chosen subsystems to debug (trace at `-vvv`) without touching the rest. A
+//! subsystem is a module name such as `rag_service`, matched in every
+//! workspace crate, or a full tracing target such as `infrastructure::sandbox`.
+//! `RUST_LOG`, when set, is applied first.
+
+use shared::types::Result;
+use tracing_subscriber::EnvFilter;
+
+/// Crates a bare module name is looked up in
+const WORKSPACE_CRATES: [&str; 5] = [
+    "domain",
+    "application",
+    "infrastructure",
+    "presentation",
+    "shared",
+];
+
+/// Install the stderr log subscriber; later calls keep the first one
+pub fn init(verbosity: u8, debug: &[String]) -> Result<()> {
+    let mut filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(""));
+    for directive in directives(verbosity, debug)? {
+        filter = filter.add_directive(directive.parse()?);
+    }
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .without_time()
+        .try_init();
+    Ok(())
+}
+
+/// Filter directives for a verbosity and `--debug` values such as
+/// `module=rag_service,sandbox`
+pub fn directives(verbosity: u8, debug: &[String]) -> Result<Vec<String>> {
+    let mut directives = match verbosity {
+        0 => vec!["warn".to_string()],
+        1 | 2 => {
+            let level = if verbosity == 1 { "info" } else { "debug" };
+            std::iter::once("warn".to_string())
+                .chain(
+                    WORKSPACE_CRATES
+                        .iter()
+                        .map(|krate| format!("{}={}", krate, level)),
+                )
+                .collect()
+        }
+        _ => vec!["trace".to_string()],
+    };
+    let module_level = if verbosity >= 3 { "trace" } else { "debug" };
+
+    for value in debug {
+        let modules = value.strip_prefix("module=").unwrap_or(value);
+        for module in modules.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+            let valid = module.split("::").all(|part| {
+                !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            });
+            if !valid {
+                return Err(anyhow::anyhow!(
+                    "Invalid --debug module '{}'; expected e.g. module=rag_service,sandbox",
+                    module
+                ));
+            }
+            if module.contains("::") {
+                directives.push(format!("{}={}", module, module_level));
+            } else {
+                directives.extend(
+                    WORKSPACE_CRATES
+                        .iter()
+                        .map(|krate| format!("{}::{}={}", krate, module, module_level)),
+                );
+            }
+        }
+    }
+    Ok(directives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_and_module_filters() {
+        assert_eq!(directives(0, &[]).unwrap(), vec!["warn"]);
+        let debug_level = directives(2, &[]).unwrap();
+        assert_eq!(debug_level[0], "warn");
+        assert!(debug_level.contains(&"application=debug".to_string()));
+        assert_eq!(directives(7, &[]).unwrap(), vec!["trace"]);
+
+        let debug = vec!["module=rag_service, infrastructure::sandbox".to_string()];
+        let found = directives(0, &debug).unwrap();
+        assert_eq!(found[0], "warn");
+        assert!(found.contains(&"application::rag_service=debug".to_string()));
+        assert!(found.contains(&"infrastructure::sandbox=debug".to_string()));
+        assert_eq!(found.len(), 1 + WORKSPACE_CRATES.len() + 1);
+        assert!(directives(3, &debug).unwrap()[1].ends_with("=trace"));
+        for directive in found {
+            assert!(directive
+                .parse::<tracing_subscriber::filter::Directive>()
+                .is_ok());
+        }
+
+        assert!(directives(0, &["module=rag-service".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,588 @@
+//! Model Context Protocol client for tools served by other processes
+//!
+//! Servers configured under `mcp_servers` (filesystem, GitHub, databases...)
+//! are started over stdio the first time the agent runs, and the tools they
+//! list are mounted in every [`ToolRegistry`](crate::tools::ToolRegistry) as
+//! `mcp__<server>__<tool>`. Calls go through the registry's policy checks
+//! like the built-in tools. Servers are started through the sandbox, so its
+//! checks, egress policy and isolation apply, and they see only the
+//! environment configured for them. A server the sandbox does not allow to
+//! run, or that fails to start, is reported and skipped.
+
+use crate::sandbox::Sandbox;
+use crate::tools::{ResourceUsage, ToolError, ToolOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shared::types::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{Mutex, OnceCell};
+
+/// Protocol revision sent in the handshake
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Longest a server may take to answer one request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Prefix of mounted tool names
+const TOOL_PREFIX: &str = "mcp__";
+
+/// JSON-RPC error for server requests the client does not handle
+const METHOD_NOT_FOUND: i64 = -32601;
+
+static MOUNTED: OnceLock<Vec<McpTool>> = OnceLock::new();
+static CONNECTED: OnceCell<()> = OnceCell::const_new();
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct McpServerConfig {
+    /// Program starting the server, e.g. `npx`
+    pub command: String,
+    pub args: Vec<String>,
+    /// Extra environment, e.g. `GITHUB_PERSONAL_ACCESS_TOKEN`
+    pub env: BTreeMap<String, String>,
+    /// Only these tools of the server are mounted when set
+    pub tools: Option<Vec<String>>,
+}
+
+/// A tool as a server lists it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// JSON schema of the arguments
+    #[serde(default, rename = "inputSchema")]
+    pub input_schema: Value,
+}
+
+type Reader = Box<dyn AsyncBufRead + Send + Unpin>;
+type Writer = Box<dyn AsyncWrite + Send + Unpin>;
+
+struct Connection {
+    reader: Reader,
+    writer: Writer,
+}
+
+/// Connection to one server; requests are answered one at a time
+pub struct McpClient {
+    server: String,
+    connection: Mutex<Connection>,
+    next_id: AtomicU64,
+    /// The server process, killed with the client
+    _child: Option<tokio::process::Child>,
+}
+
+impl McpClient {
+    /// Start the server of `config` through `sandbox` and go through the
+    /// handshake
+    pub async fn spawn(server: &str, config: &McpServerConfig, sandbox: &Sandbox) -> Result<Self> {
+        let command = sandbox.server_command(&config.command, &config.args, &config.env)?;
+        let mut child = tokio::process::Command::from(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Could not start `{}`: {}", config.command, e))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            anyhow::bail!("No stdio for `{}`", config.command);
+        };
+        let mut client = Self::over(server, BufReader::new(stdout), stdin);
+        client._child = Some(child);
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// Client speaking newline-delimited JSON-RPC over `reader` and `writer`
+    pub fn over(
+        server: &str,
+        reader: impl AsyncBufRead + Send + Unpin + 'static,
+        writer: impl AsyncWrite + Send + Unpin + 'static,
+    ) -> Self {
+        Self {
+            server: server.to_string(),
+            connection: Mutex::new(Connection {
+                reader: Box::new(reader),
+                writer: Box::new(writer),
+            }),
+            next_id: AtomicU64::new(1),
+            _child: None,
+        }
+    }
+
+    pub async fn initialize(&self) -> Result<()> {
+        self.request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": {"name": "bro", "version": env!("CARGO_PKG_VERSION")},
+            }),
+        )
+        .await?;
+        self.notify("notifications/initialized").await
+    }
+
+    /// Every tool the server offers, across pages
+    pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        let mut seen = HashSet::new();
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let mut page = self.request("tools/list", params).await?;
+            let listed: Vec<McpToolInfo> = serde_json::from_value(page["tools"].take())
+                .map_err(|e| anyhow::anyhow!("{} listed unreadable tools: {}", self.server, e))?;
+            tools.extend(listed);
+            cursor = page["nextCursor"].as_str().map(str::to_string);
+            match &cursor {
+                None => return Ok(tools),
+                Some(next) if !seen.insert(next.clone()) => {
+                    anyhow::bail!("{} repeated the tools/list cursor '{}'", self.server, next)
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Call `tool` with `arguments`: the text of its reply, and whether the
+    /// server reported the call as failed
+    pub async fn call_tool(&self, tool: &str, arguments: Value) -> Result<(String, bool)> {
+        let result = self
+            .request(
+                "tools/call",
+                json!({ "name": tool, "arguments": arguments }),
+            )
+            .await?;
+        let text = result["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|item| match item["type"].as_str() {
+                Some("text") => item["text"].as_str().unwrap_or_default().to_string(),
+                Some("resource") => match item["resource"]["text"].as_str() {
+                    Some(text) => text.to_string(),
+                    None => format!(
+                        "[resource {}]",
+                        item["resource"]["uri"].as_str().unwrap_or_default()
+                    ),
+                },
+                Some(other) => format!("[{} content]", other),
+                None => String::new(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok((text, result["isError"].as_bool().unwrap_or(false)))
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut connection = self.connection.lock().await;
+        connection
+            .send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await?;
+        let reply = tokio::time::timeout(REQUEST_TIMEOUT, connection.reply(id))
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "{} did not answer {} within {}s",
+                    self.server,
+                    method,
+                    REQUEST_TIMEOUT.as_secs()
+                )
+            })?
+            .map_err(|e| anyhow::anyhow!("{}: {}", self.server, e))?;
+        if let Some(error) = reply.get("error") {
+            anyhow::bail!(
+                "{} {} failed: {}",
+                self.server,
+                method,
+                error["message"].as_str().unwrap_or("unknown error")
+            );
+        }
+        Ok(reply.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    async fn notify(&self, method: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .await
+            .send(&json!({ "jsonrpc": "2.0", "method": method }))
+            .await
+    }
+}
+
+impl Connection {
+    async fn send(&mut self, message: &Value) -> Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    /// The response to request `id`. Notifications in between are skipped,
+    /// and requests from the server are answered: pings with an empty
+    /// result, anything else as not supported.
+    async fn reply(&mut self, id: u64) -> Result<Value> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line).await? == 0 {
+                anyhow::bail!("the server closed the connection");
+            }
+            let Ok(message) = serde_json::from_str::<Value>(line.trim()) else {
+                continue;
+            };
+            if let Some(method) = message["method"].as_str() {
+                if let Some(request) = message.get("id") {
+                    let answer = match method {
+                        "ping" => json!({ "jsonrpc": "2.0", "id": request, "result": {} }),
+                        _ => json!({
+                            "jsonrpc": "2.0",
+                            "id": request,
+                            "error": { "code": METHOD_NOT_FOUND, "message": "not supported" },
+                        }),
+                    };
+                    self.send(&answer).await?;
+                }
+                continue;
+            }
+            if message["id"].as_u64() == Some(id) {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+/// A server's tool as mounted in the tool registry
+#[derive(Clone)]
+pub struct McpTool {
+    pub server: String,
+    pub info: McpToolInfo,
+    client: Arc<McpClient>,
+}
+
+impl McpTool {
+    /// Name in the registry, e.g. `mcp__github__search_issues`
+    pub fn qualified_name(&self) -> String {
+        format!("{}{}__{}", TOOL_PREFIX, self.server, self.info.name)
+    }
+
+    /// Arguments the input schema requires
+    pub fn required(&self) -> Vec<String> {
+        self.info.input_schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|name| name.as_str().map(str::to_string))
+            .collect()
+    }
+
+    /// Arguments of the input schema with their JSON type and description
+    pub fn parameters(&self) -> Vec<(String, String, String)> {
+        let Some(properties) = self.info.input_schema["properties"].as_object() else {
+            return Vec::new();
+        };
+        properties
+            .iter()
+            .map(|(name, property)| {
+                (
+                    name.clone(),
+                    property["type"].as_str().unwrap_or("string").to_string(),
+                    property["description"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                )
+            })
+            .collect()
+    }
+
+    pub fn validate_args(
+        &self,
+        parameters: &HashMap<String, String>,
+    ) -> std::result::Result<(), ToolError> {
+        let missing: Vec<String> = self
+            .required()
+            .into_iter()
+            .filter(|name| !parameters.contains_key(name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(ToolError::ValidationError(format!(
+                "Missing {} for {}",
+                missing.join(", "),
+                self.qualified_name()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Call the tool with the registry's string parameters
+    pub async fn call(
+        &self,
+        parameters: &HashMap<String, String>,
+        timeout: Option<Duration>,
+    ) -> std::result::Result<ToolOutput, ToolError> {
+        let started = Instant::now();
+        let arguments = arguments(&self.info.input_schema, parameters);
+        let call = self.client.call_tool(&self.info.name, arguments);
+        let (text, failed) = match timeout {
+            Some(limit) => tokio::time::timeout(limit, call)
+                .await
+                .map_err(|_| ToolError::TimeoutError)?,
+            None => call.await,
+        }
+        .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+        Ok(ToolOutput {
+            success: !failed,
+            stdout: if failed { String::new() } else { text.clone() },
+            stderr: if failed { text.clone() } else { String::new() },
+            exit_code: Some(if failed { 1 } else { 0 }),
+            execution_time: started.elapsed(),
+            resources_used: ResourceUsage {
+                memory_used_mb: 0,
+                cpu_time_seconds: 0.0,
+                processes_created: 0,
+                output_size: text.len(),
+            },
+        })
+    }
+}
+
+/// JSON arguments for `schema` from string parameters: values the schema
+/// types as numbers, booleans, arrays or objects are parsed, the rest are
+/// passed as strings
+pub fn arguments(schema: &Value, parameters: &HashMap<String, String>) -> Value {
+    let arguments = parameters
+        .iter()
+        .map(|(name, value)| {
+            let typed = match schema["properties"][name]["type"].as_str() {
+                Some("number" | "integer" | "boolean" | "array" | "object") => {
+                    serde_json::from_str(value).ok()
+                }
+                _ => None,
+            };
+            (
+                name.clone(),
+                typed.unwrap_or_else(|| Value::String(value.clone())),
+            )
+        })
+        .collect();
+    Value::Object(arguments)
+}
+
+/// Tools mounted from MCP servers; none before the servers are started
+pub fn mounted() -> &'static [McpTool] {
+    MOUNTED.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// The tools `client` lists that `config` allows
+pub async fn server_tools(
+    server: &str,
+    client: McpClient,
+    config: &McpServerConfig,
+) -> Result<Vec<McpTool>> {
+    let client = Arc::new(client);
+    Ok(client
+        .list_tools()
+        .await?
+        .into_iter()
+        .filter(|info| {
+            config
+                .tools
+                .as_ref()
+                .map_or(true, |allowed| allowed.contains(&info.name))
+        })
+        .map(|info| McpTool {
+            server: server.to_string(),
+            info,
+            client: Arc::clone(&client),
+        })
+        .collect())
+}
+
+/// Start the configured servers the sandbox allows and mount their tools,
+/// once per process
+pub async fn mount_configured(servers: &BTreeMap<String, McpServerConfig>, sandbox: &Sandbox) {
+    CONNECTED
+        .get_or_init(|| async {
+            let mut mounted = Vec::new();
+            for (name, config) in servers {
+                let tools = match McpClient::spawn(name, config, sandbox).await {
+                    Ok(client) => server_tools(name, client, config).await,
+                    Err(e) => Err(e),
+                };
+                match tools {
+                    Ok(tools) => {
+                        eprintln!("🔌 MCP server '{}': {} tool(s)", name, tools.len());
+                        mounted.extend(tools);
+                    }
+                    Err(e) => eprintln!("⚠️  MCP server '{}' unavailable: {}", name, e),
+                }
+            }
+            let _ = MOUNTED.set(mounted);
+        })
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers like a server with one `echo` tool
+    async fn fake_server(stream: tokio::io::DuplexStream) {
+        let (read, mut write) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let Some(id) = request.get("id").cloned() else {
+                continue;
+            };
+            let result = match request["method"].as_str().unwrap() {
+                "initialize" => json!({ "protocolVersion": PROTOCOL_VERSION }),
+                "tools/list" => json!({ "tools": [{
+                    "name": "echo",
+                    "description": "Repeat a message",
+                    "inputSchema": {
+                        "type": "object",
+                        "properties": {
+                            "message": { "type": "string" },
+                            "times": { "type": "integer" }
+                        },
+                        "required": ["message"]
+                    }
+                }] }),
+                "tools/call" => {
+                    let args = &request["params"]["arguments"];
+                    let times = args["times"].as_u64().unwrap_or(1) as usize;
+                    json!({
+                        "content": [{
+                            "type": "text",
+                            "text": args["message"].as_str().unwrap().repeat(times)
+                        }],
+                        "isError": false
+                    })
+                }
+                _ => unreachable!(),
+            };
+            // A notification before the reply must be skipped
+            let messages = format!(
+                "{}\n{}\n",
+                json!({ "jsonrpc": "2.0", "method": "notifications/message" }),
+                json!({ "jsonrpc": "2.0", "id": id, "result": result })
+            );
+            write.write_all(messages.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mounts_and_calls_server_tools() {
+        let (client_end, server_end) = tokio::io::duplex(4096);
+        tokio::spawn(fake_server(server_end));
+        let (read, write) = tokio::io::split(client_end);
+        let client = McpClient::over("fake", BufReader::new(read), write);
+        client.initialize().await.unwrap();
+
+        let mut tools = server_tools("fake", client, &McpServerConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(tools.len(), 1);
+        let tool = tools.remove(0);
+        assert_eq!(tool.qualified_name(), "mcp__fake__echo");
+        assert_eq!(tool.required(), ["message"]);
+        assert!(tool.validate_args(&HashMap::new()).is_err());
+
+        let parameters = HashMap::from([
+            ("message".to_string(), "hi".to_string()),
+            ("times".to_string(), "3".to_string()),
+        ]);
+        assert_eq!(
+            arguments(&tool.info.input_schema, &parameters),
+            json!({ "message": "hi", "times": 3 })
+        );
+        let output = tool.call(&parameters, None).await.unwrap();
+        assert!(output.success);
+        assert_eq!(output.stdout, "hihihi");
+    }
+
+    fn server_env(name: &str, value: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([(name.to_string(), value.to_string())])
+    }
+
+    #[test]
+    fn test_servers_see_only_their_configured_environment() {
+        let mut sandbox = Sandbox::new();
+        sandbox.allow_command("env".to_string());
+        let output = sandbox
+            .server_command("env", &[], &server_env("MCP_TOKEN", "t"))
+            .unwrap()
+            .output()
+            .unwrap();
+        let mut names: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once('=').map(|(name, _)| name.to_string()))
+            .collect();
+        names.sort();
+        assert_eq!(names, ["MCP_TOKEN", "PATH"]);
+    }
+
+    #[test]
+    fn test_servers_are_held_to_the_egress_policy() {
+        use crate::config::{NetworkSecurityConfig, SecurityConfig};
+
+        let sandbox = Sandbox::for_security(&SecurityConfig {
+            network_security: NetworkSecurityConfig {
+                allowed_domains: vec!["api.github.com".to_string()],
+                enforce_egress: true,
+                ..NetworkSecurityConfig::default()
+            },
+            ..SecurityConfig::default()
+        });
+        let allowed = server_env("API_URL", "https://api.github.com");
+        assert!(sandbox.server_command("node", &[], &allowed).is_ok());
+        assert!(sandbox
+            .server_command(
+                "node",
+                &[],
+                &server_env("API_URL", "https://evil.example.com")
+            )
+            .is_err());
+        let upstream = [
+            "--upstream".to_string(),
+            "https://evil.example.com".to_string(),
+        ];
+        assert!(sandbox.server_command("node", &upstream, &allowed).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_cursor_ends_the_listing() {
+        let (client_end, server_end) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let (read, mut write) = tokio::io::split(server_end);
+            let mut lines = BufReader::new(read).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let page = json!({ "tools": [], "nextCursor": "same" });
+                let reply = json!({ "jsonrpc": "2.0", "id": request["id"], "result": page });
+                write
+                    .write_all(format!("{}\n", reply).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        let (read, write) = tokio::io::split(client_end);
+        let client = McpClient::over("looping", BufReader::new(read), write);
+
+        let error = client.list_tools().await.unwrap_err();
+        assert!(error.to_string().contains("repeated the tools/list cursor"));
+    }
+}
@@ -0,0 +1,206 @@
+//! Deterministic inference backend for tests
+//!
+//! Replies are canned: registered in code with `with_response` (exact prompt)
+//! or `with_response_containing` (any prompt containing a snippet), or read
+//! from a fixture directory holding one `<hash>.txt` file per prompt, where the hash
+//! is [`MockInference::prompt_hash`] of the exact prompt. A prompt without a
+//! fixture fails with the file name to create, so recording a new fixture is a
+//! matter of copying the reply into place.
+//!
+//! Embeddings are hashed bags of words: texts sharing words point the same
+//! way, which is enough for retrieval tests to rank the right chunk first.
+
+use crate::token_usage::UsageTracker;
+use shared::types::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Vector size of mock embeddings unless configured otherwise
+pub const DEFAULT_MOCK_DIMENSIONS: usize = 64;
+
+#[derive(Clone)]
+pub struct MockInference {
+    fixtures_dir: Option<PathBuf>,
+    /// Replies registered in code, by prompt hash
+    responses: Arc<RwLock<HashMap<String, String>>>,
+    /// Replies for prompts containing a snippet, tried in registration order
+    snippet_responses: Arc<RwLock<Vec<(String, String)>>>,
+    dimensions: usize,
+    usage: UsageTracker,
+}
+
+impl Default for MockInference {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockInference {
+    /// Backend that only knows the replies registered with `with_response`
+    pub fn new() -> Self {
+        Self {
+            fixtures_dir: None,
+            responses: Arc::new(RwLock::new(HashMap::new())),
+            snippet_responses: Arc::new(RwLock::new(Vec::new())),
+            dimensions: DEFAULT_MOCK_DIMENSIONS,
+            usage: UsageTracker::new(),
+        }
+    }
+
+    /// Backend replying from `<dir>/<prompt hash>.txt`
+    pub fn from_fixtures(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            fixtures_dir: Some(dir.into()),
+            ..Self::new()
+        }
+    }
+
+    pub fn with_response(self, prompt: &str, response: impl Into<String>) -> Self {
+        self.responses
+            .write()
+            .unwrap()
+            .insert(Self::prompt_hash(prompt), response.into());
+        self
+    }
+
+    /// Reply to any prompt containing `snippet` that has no exact reply
+    pub fn with_response_containing(self, snippet: &str, response: impl Into<String>) -> Self {
+        self.snippet_responses
+            .write()
+            .unwrap()
+            .push((snippet.to_string(), response.into()));
+        self
+    }
+
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = dimensions.max(1);
+        self
+    }
+
+    /// Key a prompt's fixture is stored under
+    pub fn prompt_hash(prompt: &str) -> String {
+        format!("{:x}", md5::compute(prompt.as_bytes()))
+    }
+
+    pub fn model(&self) -> &str {
+        "mock"
+    }
+
+    pub fn usage_tracker(&self) -> &UsageTracker {
+        &self.usage
+    }
+
+    pub fn fixtures_dir(&self) -> Option<&Path> {
+        self.fixtures_dir.as_deref()
+    }
+
+    /// Canned reply for `prompt`
+    pub fn generate(&self, prompt: &str) -> Result<String> {
+        let hash = Self::prompt_hash(prompt);
+        let exact = self.responses.read().unwrap().get(&hash).cloned();
+        let reply = match exact.or_else(|| self.snippet_reply(prompt)) {
+            Some(reply) => reply,
+            None => self.read_fixture(&hash, prompt)?,
+        };
+        // Whitespace-separated words stand in for tokens
+        self.usage.record(
+            prompt.split_whitespace().count() as u64,
+            reply.split_whitespace().count() as u64,
+        );
+        Ok(reply)
+    }
+
+    fn snippet_reply(&self, prompt: &str) -> Option<String> {
+        self.snippet_responses
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(snippet, _)| prompt.contains(snippet.as_str()))
+            .map(|(_, reply)| reply.clone())
+    }
+
+    fn read_fixture(&self, hash: &str, prompt: &str) -> Result<String> {
+        let first_line = prompt.lines().next().unwrap_or_default();
+        let Some(dir) = &self.fixtures_dir else {
+            return Err(anyhow::anyhow!(
+                "No mock response for prompt {} ({})",
+                hash,
+                first_line
+            ));
+        };
+        let path = dir.join(format!("{}.txt", hash));
+        std::fs::read_to_string(&path).map_err(|_| {
+            anyhow::anyhow!(
+                "No mock response for prompt ({}); record it in {}",
+                first_line,
+                path.display()
+            )
+        })
+    }
+
+    /// Hashed bag-of-words embedding, normalized to unit length
+    pub fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimensions];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|word| !word.is_empty())
+        {
+            let digest = md5::compute(word.to_lowercase().as_bytes());
+            let bucket = u64::from_le_bytes(digest.0[..8].try_into().expect("md5 is 16 bytes"));
+            vector[(bucket % self.dimensions as u64) as usize] += 1.0;
+        }
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        } else {
+            // Empty text still needs a usable direction
+            vector[0] = 1.0;
+        }
+        vector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_replies_from_code_then_fixtures() {
+        let dir = std::env::temp_dir().join(format!("bro-mock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(format!("{}.txt", MockInference::prompt_hash("from file"))),
+            "file reply",
+        )
+        .unwrap();
+
+        let mock = MockInference::from_fixtures(&dir)
+            .with_response("in code", "code reply")
+            .with_response_containing("code", "snippet reply");
+        assert_eq!(mock.generate("in code").unwrap(), "code reply");
+        assert_eq!(mock.generate("some code here").unwrap(), "snippet reply");
+        assert_eq!(mock.generate("from file").unwrap(), "file reply");
+        let err = mock.generate("unknown").unwrap_err().to_string();
+        assert!(err.contains(&MockInference::prompt_hash("unknown")));
+        assert_eq!(mock.usage_tracker().snapshot().requests, 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_embeddings_are_deterministic_and_word_based() {
+        let mock = MockInference::new();
+        let query = mock.embed("where is auth handled");
+        assert_eq!(query, mock.embed("where is auth handled"));
+        assert_eq!(query.len(), DEFAULT_MOCK_DIMENSIONS);
+
+        let related = mock.embed("fn auth handled here");
+        let unrelated = mock.embed("render the sidebar menu");
+        assert!(cosine(&query, &related) > cosine(&query, &unrelated));
+    }
+}
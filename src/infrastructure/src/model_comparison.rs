@@ -0,0 +1,134 @@
+//! One prompt, several models, for `bro --compare`
+//!
+//! Each model gets the same prompt on its own client and token counter, so
+//! the latency and token figures of one run are not mixed with another's.
+//! The counts still go into the global usage totals afterwards.
+
+use crate::ollama_client::OllamaClient;
+use crate::token_usage::{TokenUsage, UsageTracker};
+use shared::cancellation::CancellationToken;
+use std::time::{Duration, Instant};
+
+/// One model's answer and how long it took
+#[derive(Debug, Clone, Default)]
+pub struct ModelRun {
+    pub model: String,
+    pub output: String,
+    /// Time until the first content arrived
+    pub first_token: Option<Duration>,
+    pub elapsed: Duration,
+    pub usage: TokenUsage,
+    pub error: Option<String>,
+    pub done: bool,
+}
+
+impl ModelRun {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Completion tokens per second once the first token arrived
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let generating = self.elapsed.checked_sub(self.first_token?)?;
+        let tokens = self.usage.completion_tokens;
+        (tokens > 0 && !generating.is_zero()).then(|| tokens as f64 / generating.as_secs_f64())
+    }
+
+    /// `1.2s to first token, 8.4s total, 312 tokens (41.5 tok/s)`
+    pub fn stats(&self) -> String {
+        let mut stats = match self.first_token {
+            Some(first) => format!("{:.1}s to first token, ", first.as_secs_f64()),
+            None => String::new(),
+        };
+        stats.push_str(&format!(
+            "{:.1}s total, {} tokens",
+            self.elapsed.as_secs_f64(),
+            self.usage.completion_tokens
+        ));
+        if let Some(rate) = self.tokens_per_second() {
+            stats.push_str(&format!(" ({:.1} tok/s)", rate));
+        }
+        stats
+    }
+}
+
+/// Model names from `--models`, in order and without repeats
+pub fn model_list(values: &[String]) -> Vec<String> {
+    let mut models: Vec<String> = Vec::new();
+    for model in values.iter().flat_map(|value| value.split(',')) {
+        let model = model.trim();
+        if !model.is_empty() && !models.iter().any(|m| m == model) {
+            models.push(model.to_string());
+        }
+    }
+    models
+}
+
+/// Stream `prompt` from `model`, handing each chunk to `on_chunk` as it arrives
+pub async fn run_model<F>(
+    client: &OllamaClient,
+    model: &str,
+    prompt: &str,
+    cancel: &CancellationToken,
+    mut on_chunk: F,
+) -> ModelRun
+where
+    F: FnMut(&str) + Send,
+{
+    let usage = UsageTracker::new();
+    let client = client
+        .clone()
+        .with_model(model)
+        .with_usage_tracker(usage.clone());
+    let mut run = ModelRun::new(model);
+    let started = Instant::now();
+    let mut first_token = None;
+    let result = client
+        .generate_response_streaming_cancellable(
+            prompt,
+            |chunk| {
+                first_token.get_or_insert_with(|| started.elapsed());
+                on_chunk(chunk);
+            },
+            cancel,
+        )
+        .await;
+
+    run.elapsed = started.elapsed();
+    run.first_token = first_token;
+    run.usage = usage.snapshot();
+    if run.usage.requests > 0 {
+        UsageTracker::global().record(run.usage.prompt_tokens, run.usage.completion_tokens);
+    }
+    match result {
+        Ok(output) => run.output = output,
+        Err(e) => run.error = Some(e.to_string()),
+    }
+    run.done = true;
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_list_and_rates() {
+        let values = vec!["llama3, qwen2.5".to_string(), "llama3,,phi3".to_string()];
+        assert_eq!(model_list(&values), vec!["llama3", "qwen2.5", "phi3"]);
+
+        let mut run = ModelRun::new("llama3");
+        assert_eq!(run.tokens_per_second(), None);
+        run.first_token = Some(Duration::from_secs(1));
+        run.elapsed = Duration::from_secs(5);
+        run.usage.completion_tokens = 200;
+        assert_eq!(run.tokens_per_second(), Some(50.0));
+        assert_eq!(
+            run.stats(),
+            "1.0s to first token, 5.0s total, 200 tokens (50.0 tok/s)"
+        );
+    }
+}
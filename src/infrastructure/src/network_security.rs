@@ -1,6 +1,37 @@
+//! Network access policy
+//!
+//! [`NetworkSecurity`] decides which hosts may be reached. Built from
+//! `security.network_security` it also polices the traffic the agent starts:
+//! tool HTTP requests and the network clients (curl, wget, pip, npm, git) in
+//! the commands it runs, whose target hosts are found by [`commands`].
+
+use crate::config::NetworkSecurityConfig;
 use std::collections::HashSet;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::{OnceLock, RwLock};
 use url::Url;
 
+#[path = "network_security/commands.rs"]
+pub mod commands;
+
+/// Hosts the user let through for the rest of the session after a denial
+static SESSION_HOSTS: OnceLock<RwLock<HashSet<String>>> = OnceLock::new();
+
+/// Let commands and tools reach `host` until the process exits
+pub fn allow_for_session(host: &str) {
+    SESSION_HOSTS
+        .get_or_init(Default::default)
+        .write()
+        .unwrap()
+        .insert(host.to_ascii_lowercase());
+}
+
+fn allowed_for_session(host: &str) -> bool {
+    SESSION_HOSTS
+        .get()
+        .is_some_and(|hosts| hosts.read().unwrap().contains(host))
+}
+
 /// Network security manager with default-deny policy
 pub struct NetworkSecurity {
     allowed_domains: HashSet<String>,
@@ -41,6 +72,30 @@ impl NetworkSecurity {
         }
     }
 
+    /// Policy from the configured allow and block lists, which may use
+    /// `*.example.com` for every subdomain of example.com
+    pub fn from_config(config: &NetworkSecurityConfig) -> Self {
+        let hosts = |domains: &[String]| {
+            domains
+                .iter()
+                .map(|domain| domain.trim().to_ascii_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect()
+        };
+        Self {
+            allowed_domains: hosts(&config.allowed_domains),
+            blocked_domains: hosts(&config.blocked_domains),
+            max_request_size: config.max_request_size_kb as usize * 1024,
+            request_timeout: std::time::Duration::from_secs(config.timeout_seconds),
+            ..Self::new()
+        }
+    }
+
+    /// Policy for traffic the agent starts, unless enforcement is turned off
+    pub fn for_egress(config: &NetworkSecurityConfig) -> Option<Self> {
+        config.enforce_egress.then(|| Self::from_config(config))
+    }
+
     /// Check if a URL is allowed for access
     pub fn is_url_allowed(&self, url_str: &str) -> Result<(), NetworkSecurityError> {
         let url = Url::parse(url_str)
@@ -54,23 +109,84 @@ impl NetworkSecurity {
         }
 
         // Check domain
-        if let Some(domain) = url.host_str() {
-            // Check blocklist first
-            if self.blocked_domains.contains(domain) {
-                return Err(NetworkSecurityError::BlockedDomain(domain.to_string()));
-            }
+        match url.host_str() {
+            Some(domain) => self.is_host_allowed(domain),
+            None => Err(NetworkSecurityError::NoHostInUrl),
+        }
+    }
 
-            // Check allowlist
-            if !self.allowed_domains.contains(domain) {
-                return Err(NetworkSecurityError::DomainNotAllowed(domain.to_string()));
-            }
-        } else {
-            return Err(NetworkSecurityError::NoHostInUrl);
+    /// Check a host against the block and allow lists, then make sure an
+    /// allowed name does not resolve to an address on this machine or network
+    pub fn is_host_allowed(&self, host: &str) -> Result<(), NetworkSecurityError> {
+        let host = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+
+        // Check blocklist first
+        if matches_any(&self.blocked_domains, &host) {
+            return Err(NetworkSecurityError::BlockedDomain(host));
+        }
+        if allowed_for_session(&host) {
+            return Ok(());
         }
 
+        // Check allowlist
+        if !matches_any(&self.allowed_domains, &host) {
+            return Err(NetworkSecurityError::DomainNotAllowed(host));
+        }
+        if host == "localhost" || host.parse::<IpAddr>().is_ok() {
+            // Listed explicitly, so the address is meant
+            return Ok(());
+        }
+        // A host that does not resolve cannot be reached either
+        let Ok(addresses) = (host.as_str(), 0).to_socket_addrs() else {
+            return Ok(());
+        };
+        for address in addresses {
+            let ip = address.ip();
+            if is_internal(ip) && !self.allowed_domains.contains(&ip.to_string()) {
+                return Err(NetworkSecurityError::InternalAddress(host, ip));
+            }
+        }
         Ok(())
     }
 
+    /// Check every host a command would connect to
+    pub fn check_command(&self, program: &str, args: &[String]) -> Result<(), EgressDenied> {
+        match self
+            .denials(commands::targets(program, args))
+            .into_iter()
+            .next()
+        {
+            Some(denied) => Err(denied),
+            None => Ok(()),
+        }
+    }
+
+    /// Every host outside the policy that a shell script would connect to
+    pub fn script_denials(&self, script: &str) -> Vec<EgressDenied> {
+        self.denials(commands::script_targets(script))
+    }
+
+    fn denials(&self, targets: Vec<commands::Target>) -> Vec<EgressDenied> {
+        let mut seen = HashSet::new();
+        targets
+            .into_iter()
+            .filter(|target| seen.insert(target.host.clone()))
+            .filter_map(|target| {
+                self.is_host_allowed(&target.host)
+                    .err()
+                    .map(|reason| EgressDenied {
+                        program: target.program,
+                        host: target.host,
+                        reason,
+                    })
+            })
+            .collect()
+    }
+
     /// Validate request size
     pub fn validate_request_size(&self, size: usize) -> Result<(), NetworkSecurityError> {
         if size > self.max_request_size {
@@ -114,12 +230,83 @@ impl NetworkSecurity {
     }
 }
 
+/// `pattern` is `host`, or `*.domain` and host is a subdomain of domain
+fn domain_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+        None => pattern == "*" || pattern == host,
+    }
+}
+
+fn matches_any(patterns: &HashSet<String>, host: &str) -> bool {
+    patterns.iter().any(|pattern| domain_matches(pattern, host))
+}
+
+/// Loopback, private and link-local addresses
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(|v4| is_internal(IpAddr::V4(v4)))
+        }
+    }
+}
+
+/// A command would connect to a host the policy does not allow
+#[derive(Debug, Clone)]
+pub struct EgressDenied {
+    pub program: String,
+    pub host: String,
+    pub reason: NetworkSecurityError,
+}
+
+impl EgressDenied {
+    /// Whether the user may let it through; blocked domains stay blocked
+    pub fn can_override(&self) -> bool {
+        !matches!(self.reason, NetworkSecurityError::BlockedDomain(_))
+    }
+}
+
+impl std::fmt::Display for EgressDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Network access denied: {} would connect to {}",
+            self.program, self.host
+        )?;
+        match &self.reason {
+            NetworkSecurityError::DomainNotAllowed(_) => write!(
+                f,
+                ", which is not in security.network_security.allowed_domains (VIBE_ALLOWED_DOMAINS)"
+            ),
+            NetworkSecurityError::BlockedDomain(_) => {
+                write!(f, ", which is in security.network_security.blocked_domains")
+            }
+            reason => write!(f, ": {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EgressDenied {}
+
 #[derive(Debug, Clone)]
 pub enum NetworkSecurityError {
     InvalidUrl(String),
     ForbiddenScheme(String),
     BlockedDomain(String),
     DomainNotAllowed(String),
+    /// An allowed name resolved to a loopback, private or link-local address
+    InternalAddress(String, IpAddr),
     NoHostInUrl,
     RequestTooLarge(usize, usize),
     ResponseTooLarge(usize, usize),
@@ -137,6 +324,9 @@ impl std::fmt::Display for NetworkSecurityError {
             NetworkSecurityError::DomainNotAllowed(domain) => {
                 write!(f, "Domain not in allowlist: {}", domain)
             }
+            NetworkSecurityError::InternalAddress(domain, ip) => {
+                write!(f, "Domain {} resolves to internal address {}", domain, ip)
+            }
             NetworkSecurityError::NoHostInUrl => write!(f, "URL has no host"),
             NetworkSecurityError::RequestTooLarge(size, limit) => {
                 write!(f, "Request too large: {} > {} bytes", size, limit)
@@ -260,4 +450,56 @@ mod tests {
         security.deny_domain("example.com");
         assert!(security.is_url_allowed("https://example.com/").is_err());
     }
+
+    #[test]
+    fn test_configured_egress_policy() {
+        let security = NetworkSecurity::from_config(&NetworkSecurityConfig {
+            allowed_domains: vec!["*.example.com".to_string(), "localhost".to_string()],
+            blocked_domains: vec!["bad.example.com".to_string()],
+            ..NetworkSecurityConfig::default()
+        });
+        assert!(security.is_host_allowed("api.example.com").is_ok());
+        assert!(security.is_host_allowed("example.com").is_err());
+        assert!(security.is_host_allowed("notexample.com").is_err());
+        assert!(security.is_host_allowed("localhost").is_ok());
+
+        let args = |script: &str| vec!["-c".to_string(), script.to_string()];
+        assert!(security
+            .check_command("bash", &args("curl https://api.example.com/v1"))
+            .is_ok());
+        let denied = security
+            .check_command("bash", &args("cd x && wget https://elsewhere.org/f"))
+            .unwrap_err();
+        assert_eq!(denied.program, "wget");
+        assert_eq!(denied.host, "elsewhere.org");
+        assert!(denied.can_override());
+        assert!(denied.to_string().contains("VIBE_ALLOWED_DOMAINS"));
+
+        let blocked = security.script_denials("curl bad.example.com");
+        assert!(!blocked[0].can_override());
+
+        allow_for_session("elsewhere.org");
+        assert!(security
+            .script_denials("wget https://elsewhere.org/f")
+            .is_empty());
+        allow_for_session("bad.example.com");
+        assert_eq!(security.script_denials("curl bad.example.com").len(), 1);
+    }
+
+    #[test]
+    fn test_internal_addresses() {
+        for internal in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "169.254.169.254",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+        ] {
+            assert!(is_internal(internal.parse().unwrap()), "{}", internal);
+        }
+        for public in ["93.184.216.34", "2606:4700::1111"] {
+            assert!(!is_internal(public.parse().unwrap()), "{}", public);
+        }
+    }
 }
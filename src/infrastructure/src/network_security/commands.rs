@@ -0,0 +1,411 @@
+//! Hosts that shell commands would connect to
+//!
+//! Only the network clients the agent commonly runs are understood: curl,
+//! wget, pip, the npm family and git, also when wrapped in `sudo`/`env` or
+//! inside an `sh -c` script. Package managers go to their default registry
+//! unless the command names another one.
+
+use std::path::Path;
+use std::process::Command;
+use url::Url;
+
+/// A host one program in a command would connect to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub program: String,
+    pub host: String,
+}
+
+/// curl options whose value is the next argument
+const CURL_VALUE_FLAGS: &str =
+    "-A -b -c -C -d -e -E -F -H -K -m -o -r -T -u -w -X --cert --config \
+     --connect-timeout --cookie --cookie-jar --data --data-binary --data-raw \
+     --data-urlencode --form --header --max-time --output --range --referer --request \
+     --retry --upload-file --user --user-agent --write-out";
+
+/// wget options whose value is the next argument
+const WGET_VALUE_FLAGS: &str = "-a -i -o -O -P -t -T -U --directory-prefix --header --input-file \
+     --output-document --output-file --post-data --timeout --tries --user-agent";
+
+/// Options naming a proxy, which is connected to instead
+const CURL_PROXY_FLAGS: &[&str] = &["-x", "--proxy"];
+/// wget's `-e` runs a wgetrc command, which may set a proxy
+const WGET_PROXY_FLAGS: &[&str] = &["-e"];
+
+const PIP_NETWORK_COMMANDS: &[&str] = &["install", "download", "wheel", "index", "search"];
+const PIP_DEFAULT_HOSTS: &[&str] = &["pypi.org", "files.pythonhosted.org"];
+
+const NPM_NETWORK_COMMANDS: &[&str] = &[
+    "install", "i", "ci", "add", "update", "up", "upgrade", "publish", "view", "info", "show",
+    "outdated", "audit", "exec", "x", "dlx", "create",
+];
+
+const GIT_NETWORK_COMMANDS: &[&str] = &["clone", "fetch", "pull", "push", "ls-remote"];
+
+/// Hosts `program` run with `args` would connect to
+pub fn targets(program: &str, args: &[String]) -> Vec<Target> {
+    let name = Path::new(program)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let hosts = match name.as_str() {
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "powershell" | "pwsh" => {
+            return args
+                .iter()
+                .position(|arg| matches!(arg.as_str(), "-c" | "-Command"))
+                .and_then(|i| args.get(i + 1))
+                .map(|script| script_targets(script))
+                .unwrap_or_default();
+        }
+        "sudo" | "env" | "nohup" | "time" | "exec" | "command" => {
+            let rest: Vec<&String> = args
+                .iter()
+                .skip_while(|arg| arg.starts_with('-') || arg.contains('='))
+                .collect();
+            return match rest.split_first() {
+                Some((program, args)) => {
+                    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                    targets(program, &args)
+                }
+                None => Vec::new(),
+            };
+        }
+        "curl" => client_hosts(args, CURL_VALUE_FLAGS, CURL_PROXY_FLAGS),
+        "wget" => client_hosts(args, WGET_VALUE_FLAGS, WGET_PROXY_FLAGS),
+        "pip" | "pip3" => pip_hosts(args),
+        "python" | "python3" if args.len() > 2 && args[0] == "-m" && args[1].starts_with("pip") => {
+            pip_hosts(&args[2..])
+        }
+        "npm" | "pnpm" | "npx" | "yarn" => npm_hosts(&name, args),
+        "git" => git_hosts(args),
+        _ => Vec::new(),
+    };
+    hosts
+        .into_iter()
+        .map(|host| Target {
+            program: name.clone(),
+            host,
+        })
+        .collect()
+}
+
+/// Hosts the commands in a shell script would connect to
+pub fn script_targets(script: &str) -> Vec<Target> {
+    split_commands(script)
+        .into_iter()
+        .flat_map(|words| {
+            // Leading `VAR=value` assignments only set the environment
+            let words: Vec<String> = words
+                .into_iter()
+                .skip_while(|word| is_assignment(word))
+                .collect();
+            match words.split_first() {
+                Some((program, args)) => targets(program, args),
+                None => Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// Simple commands of a script as words, with quotes and redirections removed
+fn split_commands(script: &str) -> Vec<Vec<String>> {
+    let mut commands = vec![Vec::new()];
+    let mut word = String::new();
+    let mut quote = None;
+    // The next word names the file of a redirection
+    let mut redirect = false;
+
+    for ch in script.chars().chain(['\n']) {
+        match (quote, ch) {
+            (Some(open), _) if ch == open => quote = None,
+            (Some(_), _) => word.push(ch),
+            (None, '"' | '\'') => quote = Some(ch),
+            (None, _) if !" \t\n;|&()`<>".contains(ch) => word.push(ch),
+            (None, _) => {
+                if !word.is_empty() {
+                    let word = std::mem::take(&mut word);
+                    if !std::mem::take(&mut redirect) {
+                        commands.last_mut().unwrap().push(word);
+                    }
+                }
+                match ch {
+                    '<' | '>' => redirect = true,
+                    ' ' | '\t' => {}
+                    // Separators, pipes and subshells all start a new command
+                    _ => {
+                        redirect = false;
+                        commands.push(Vec::new());
+                    }
+                }
+            }
+        }
+    }
+    commands.retain(|command| !command.is_empty());
+    commands
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Hosts of the URLs a curl- or wget-style client is given
+fn client_hosts(args: &[String], value_flags: &str, proxy_flags: &[&str]) -> Vec<String> {
+    let mut hosts = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with('-') && arg.len() > 1 {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value)),
+                _ => (arg.as_str(), None),
+            };
+            if proxy_flags.contains(&flag) {
+                let value = inline.map(str::to_string).or_else(|| args.next().cloned());
+                let value = match (flag, value) {
+                    ("-e", Some(command)) => command
+                        .split_once("proxy")
+                        .map(|(_, rest)| rest.trim_start_matches([' ', '=']).to_string()),
+                    (_, value) => value,
+                };
+                hosts
+                    .extend(value.and_then(|value| url_host(&value).or_else(|| bare_host(&value))));
+            } else if inline.is_none() && takes_value(flag, value_flags) {
+                args.next();
+            }
+            continue;
+        }
+        hosts.extend(url_host(arg).or_else(|| bare_host(arg)));
+    }
+    hosts
+}
+
+/// `flag`, or the last of a cluster of short flags like `-sSo`, takes a value
+fn takes_value(flag: &str, value_flags: &str) -> bool {
+    let listed = |flag: &str| value_flags.split_whitespace().any(|listed| listed == flag);
+    match flag.strip_prefix('-') {
+        _ if listed(flag) => true,
+        Some(cluster) if !cluster.starts_with('-') && cluster.len() > 1 => cluster
+            .chars()
+            .last()
+            .is_some_and(|last| listed(&format!("-{}", last))),
+        _ => false,
+    }
+}
+
+fn pip_hosts(args: &[String]) -> Vec<String> {
+    let positional: Vec<&String> = args.iter().filter(|arg| !arg.starts_with('-')).collect();
+    if !positional
+        .first()
+        .is_some_and(|command| PIP_NETWORK_COMMANDS.contains(&command.as_str()))
+    {
+        return Vec::new();
+    }
+
+    let mut index = Some(PIP_DEFAULT_HOSTS.iter().map(|h| h.to_string()).collect());
+    let mut hosts = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || inline.clone().or_else(|| args.next().cloned());
+        match flag {
+            "-i" | "--index-url" => index = value().and_then(|url| url_host(&url)).map(|h| vec![h]),
+            "--extra-index-url" | "-f" | "--find-links" | "--proxy" => {
+                hosts.extend(value().and_then(|url| url_host(&url)))
+            }
+            "--no-index" => index = None,
+            "-r" | "--requirement" | "-c" | "--constraint" | "-t" | "--target" | "--prefix"
+            | "--root" | "--trusted-host" | "--cache-dir" => {
+                value();
+            }
+            // Packages can be given as URLs, including `git+https://...`
+            _ => hosts.extend(url_host(arg)),
+        }
+    }
+    index.unwrap_or_default().into_iter().chain(hosts).collect()
+}
+
+fn npm_hosts(tool: &str, args: &[String]) -> Vec<String> {
+    let command = args.iter().find(|arg| !arg.starts_with('-'));
+    let fetches = match command {
+        // `npx` fetches packages it does not have; bare `yarn` installs
+        _ if tool == "npx" => true,
+        None => tool == "yarn",
+        Some(command) => NPM_NETWORK_COMMANDS.contains(&command.as_str()),
+    };
+    if !fetches {
+        return Vec::new();
+    }
+
+    let mut registry = None;
+    let mut hosts = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(url) = arg.strip_prefix("--registry=") {
+            registry = url_host(url);
+        } else if arg == "--registry" {
+            registry = args.next().and_then(|url| url_host(url));
+        } else {
+            // Packages can be given as tarball or git URLs
+            hosts.extend(url_host(arg).or_else(|| scp_host(arg)));
+        }
+    }
+    let default = if tool == "yarn" {
+        "registry.yarnpkg.com"
+    } else {
+        "registry.npmjs.org"
+    };
+    hosts.insert(0, registry.unwrap_or_else(|| default.to_string()));
+    hosts
+}
+
+fn git_hosts(args: &[String]) -> Vec<String> {
+    let positional: Vec<&String> = args.iter().filter(|arg| !arg.starts_with('-')).collect();
+    let Some((command, rest)) = positional.split_first() else {
+        return Vec::new();
+    };
+    if !GIT_NETWORK_COMMANDS.contains(&command.as_str()) {
+        return Vec::new();
+    }
+    let hosts: Vec<String> = rest
+        .iter()
+        .filter_map(|arg| url_host(arg).or_else(|| scp_host(arg)))
+        .collect();
+    if !hosts.is_empty() || command.as_str() == "clone" {
+        return hosts;
+    }
+    // `git fetch`/`pull`/`push [remote]` go to the remote's URL
+    let remote = rest.first().map_or("origin", |remote| remote.as_str());
+    Command::new("git")
+        .args(["remote", "get-url", remote])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            url_host(&url).or_else(|| scp_host(&url))
+        })
+        .into_iter()
+        .collect()
+}
+
+/// Host of `scheme://host/...`
+fn url_host(value: &str) -> Option<String> {
+    if !value.contains("://") {
+        return None;
+    }
+    let url = Url::parse(value).ok()?;
+    if url.scheme() == "file" {
+        return None;
+    }
+    url.host_str().map(normalize)
+}
+
+/// Host of an scp-style git address, `user@host:path`
+fn scp_host(value: &str) -> Option<String> {
+    let (address, _path) = value.split_once(':')?;
+    let host = address.rsplit('@').next()?;
+    (!host.is_empty() && !host.contains('/') && host.contains('.')).then(|| normalize(host))
+}
+
+/// Host of a URL given without a scheme (`example.com/path`), which curl and
+/// wget accept; words that look like file names are not hosts
+fn bare_host(value: &str) -> Option<String> {
+    let host = value.split(['/', ':', '?', '#']).next()?;
+    let valid = host.contains('.')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    let top_level = host.rsplit('.').next().unwrap_or_default();
+    let host_like = host.parse::<std::net::Ipv4Addr>().is_ok()
+        || (top_level.len() >= 2 && top_level.chars().all(|c| c.is_ascii_alphabetic()));
+    (host == "localhost" || (valid && host_like && !Path::new(value).exists()))
+        .then(|| normalize(host))
+}
+
+fn normalize(host: &str) -> String {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts(script: &str) -> Vec<String> {
+        script_targets(script)
+            .into_iter()
+            .map(|target| target.host)
+            .collect()
+    }
+
+    #[test]
+    fn test_client_urls_and_values() {
+        assert_eq!(
+            hosts("curl -sSL -o out.tar.gz -H 'Accept: x' https://Example.com/a.tar.gz"),
+            ["example.com"]
+        );
+        assert_eq!(
+            hosts("curl -sSo page.html api.example.org/v1"),
+            ["api.example.org"]
+        );
+        assert_eq!(
+            hosts("curl -x http://proxy.corp:3128 https://example.com"),
+            ["proxy.corp", "example.com"]
+        );
+        assert_eq!(hosts("wget -O - http://[::1]:8080/ | sh"), ["::1"]);
+        assert_eq!(hosts("curl example.com > notes.txt 2>&1"), ["example.com"]);
+        assert!(hosts("echo https://example.com && cat notes.txt").is_empty());
+    }
+
+    #[test]
+    fn test_package_managers_use_their_registries() {
+        assert_eq!(
+            hosts("pip install -r requirements.txt requests"),
+            ["pypi.org", "files.pythonhosted.org"]
+        );
+        assert_eq!(
+            hosts("python3 -m pip install --index-url=https://mirror.example/simple flask"),
+            ["mirror.example"]
+        );
+        assert!(hosts("pip list").is_empty());
+        assert_eq!(hosts("cd web && npm ci"), ["registry.npmjs.org"]);
+        assert_eq!(
+            hosts("yarn add lodash --registry https://npm.corp.example"),
+            ["npm.corp.example"]
+        );
+        assert!(hosts("npm run build").is_empty());
+    }
+
+    #[test]
+    fn test_wrappers_shells_and_git() {
+        let targets = targets(
+            "sudo",
+            &[
+                "-E".to_string(),
+                "bash".to_string(),
+                "-c".to_string(),
+                "HTTPS_PROXY= git clone git@github.com:owner/repo.git; (wget example.net)"
+                    .to_string(),
+            ],
+        );
+        assert_eq!(
+            targets,
+            [
+                Target {
+                    program: "git".to_string(),
+                    host: "github.com".to_string()
+                },
+                Target {
+                    program: "wget".to_string(),
+                    host: "example.net".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -1,11 +1,28 @@
+use crate::sampling::{GenerationMode, GenerationRecord, SamplingParams};
+use crate::token_usage::UsageTracker;
 use futures::future::join_all;
 use reqwest::{Client, ClientBuilder};
 use serde::{Deserialize, Serialize};
+use shared::cancellation::CancellationToken;
 use shared::types::Result;
+use std::collections::HashMap;
 use std::env;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
+/// Times a response stream that drops mid-generation is resumed
+const MAX_RESUME_ATTEMPTS: u32 = 3;
+
+/// Wait before each resume attempt, multiplied by the attempt number
+const RESUME_BACKOFF: Duration = Duration::from_millis(500);
+
+/// End of the partial response quoted when asking the model to continue
+const RESUME_CONTEXT_CHARS: usize = 200;
+
+/// Shortest repeated text dropped when stitching a continuation on; shorter
+/// overlaps are as likely to be coincidence
+const MIN_REPEAT_CHARS: usize = 8;
+
 #[derive(Serialize)]
 struct EmbeddingRequest {
     model: String,
@@ -17,6 +34,17 @@ struct EmbeddingResponse {
     embedding: Vec<f32>,
 }
 
+#[derive(Serialize)]
+struct BatchEmbeddingRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct BatchEmbeddingResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct Message {
     role: String,
@@ -28,12 +56,76 @@ struct ChatRequest {
     model: String,
     messages: Vec<Message>,
     stream: bool,
+    /// JSON Schema the reply is constrained to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "ChatOptions::is_empty")]
+    options: ChatOptions,
+}
+
+/// Ollama's names for [`SamplingParams`]
+#[derive(Serialize, Default)]
+struct ChatOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+}
+
+impl ChatOptions {
+    fn is_empty(&self) -> bool {
+        self.temperature.is_none()
+            && self.top_p.is_none()
+            && self.seed.is_none()
+            && self.num_predict.is_none()
+    }
+}
+
+impl From<SamplingParams> for ChatOptions {
+    fn from(params: SamplingParams) -> Self {
+        Self {
+            temperature: params.temperature,
+            top_p: params.top_p,
+            seed: params.seed,
+            num_predict: params.max_tokens,
+        }
+    }
 }
 
 #[derive(Deserialize)]
 struct ChatResponse {
     message: Message,
     done: bool,
+    /// Token counts are only reported on the final (`done`) message
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<TagsModel>,
+}
+
+#[derive(Deserialize)]
+struct TagsModel {
+    name: String,
+    #[serde(default)]
+    digest: String,
+}
+
+#[derive(Deserialize)]
+struct ShowResponse {
+    #[serde(default)]
+    parameters: String,
+    #[serde(default)]
+    model_info: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone)]
@@ -41,6 +133,12 @@ pub struct OllamaClient {
     client: Arc<Client>,
     base_url: String,
     model: String,
+    /// Model used for `/api/embeddings`; `EMBEDDING_MODEL`, else the chat model
+    embedding_model: String,
+    usage: UsageTracker,
+    /// JSON Schema replies are constrained to, see [`Self::with_format`]
+    format: Option<serde_json::Value>,
+    sampling: SamplingParams,
 }
 
 impl OllamaClient {
@@ -48,6 +146,10 @@ impl OllamaClient {
         let base_url =
             env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| "http://localhost:11434".to_string());
         let model = env::var("BASE_MODEL").unwrap_or_else(|_| "qwen2.5:1.5b-instruct".to_string());
+        let embedding_model = env::var("EMBEDDING_MODEL")
+            .ok()
+            .filter(|m| !m.trim().is_empty())
+            .unwrap_or_else(|| model.clone());
 
         // High-performance HTTP client with connection pooling
         let client = ClientBuilder::new()
@@ -61,6 +163,11 @@ impl OllamaClient {
             client: Arc::new(client),
             base_url,
             model,
+            embedding_model,
+            usage: UsageTracker::global().clone(),
+            format: None,
+            // Command line overrides apply even before a mode is chosen
+            sampling: crate::sampling::overrides(),
         })
     }
 
@@ -68,6 +175,148 @@ impl OllamaClient {
         &self.model
     }
 
+    pub fn embedding_model(&self) -> &str {
+        &self.embedding_model
+    }
+
+    /// Client talking to the Ollama server at `base_url`
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Client generating with `model`; the embedding model is unchanged
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    pub fn with_embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.embedding_model = model.into();
+        self
+    }
+
+    /// Client whose replies are constrained to JSON matching `schema`; Ollama
+    /// only lets the model produce tokens that keep the output valid
+    pub fn with_format(mut self, schema: serde_json::Value) -> Self {
+        self.format = Some(schema);
+        self
+    }
+
+    /// Client sampling as configured for `mode`
+    pub fn with_mode(self, mode: GenerationMode) -> Self {
+        self.with_sampling(crate::sampling::params_for(mode))
+    }
+
+    pub fn with_sampling(mut self, sampling: SamplingParams) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    pub fn sampling(&self) -> SamplingParams {
+        self.sampling
+    }
+
+    /// Tracker receiving prompt/completion token counts for chat requests
+    pub fn usage_tracker(&self) -> &UsageTracker {
+        &self.usage
+    }
+
+    /// Route token accounting to a dedicated tracker instead of the global one
+    pub fn with_usage_tracker(mut self, usage: UsageTracker) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    fn record_usage(&self, chat_resp: &ChatResponse) {
+        if chat_resp.done {
+            self.usage.record(
+                chat_resp.prompt_eval_count.unwrap_or(0),
+                chat_resp.eval_count.unwrap_or(0),
+            );
+        }
+    }
+
+    /// Model, weights digest and sampling behind this client's replies
+    pub async fn generation_record(&self) -> GenerationRecord {
+        GenerationRecord {
+            model: self.model.clone(),
+            model_digest: self.model_digest().await,
+            sampling: self.sampling,
+        }
+    }
+
+    /// Digest of the selected model from `/api/tags`, looked up once per run
+    pub async fn model_digest(&self) -> Option<String> {
+        static DIGESTS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+        let digests = DIGESTS.get_or_init(Default::default);
+        let key = format!("{}/{}", self.base_url, self.model);
+        if let Some(digest) = digests.lock().unwrap().get(&key) {
+            return Some(digest.clone());
+        }
+
+        let url = format!("{}/api/tags", self.base_url);
+        let tags: TagsResponse = self.client.get(&url).send().await.ok()?.json().await.ok()?;
+        // A model pulled without a tag is listed as `<name>:latest`
+        let digest = tags
+            .models
+            .into_iter()
+            .find(|m| m.name == self.model || m.name == format!("{}:latest", self.model))
+            .map(|m| m.digest)
+            .filter(|digest| !digest.is_empty())?;
+        digests.lock().unwrap().insert(key, digest.clone());
+        Some(digest)
+    }
+
+    /// Names of the models installed in Ollama
+    pub async fn installed_models(&self) -> Result<Vec<String>> {
+        let url = format!("{}/api/tags", self.base_url);
+        let tags: TagsResponse = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+
+    /// Context window of the selected model: an explicit `num_ctx` parameter wins,
+    /// otherwise the architecture's `*.context_length` from `/api/show`
+    pub async fn fetch_context_length(&self) -> Result<Option<usize>> {
+        let url = format!("{}/api/show", self.base_url);
+        let response = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({ "model": self.model }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+        let info: ShowResponse = response.json().await?;
+
+        let num_ctx = info.parameters.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("num_ctx"), Some(value)) => value.parse().ok(),
+                _ => None,
+            }
+        });
+        if num_ctx.is_some() {
+            return Ok(num_ctx);
+        }
+
+        Ok(info.model_info.iter().find_map(|(key, value)| {
+            if key.ends_with(".context_length") {
+                value.as_u64().map(|v| v as usize)
+            } else {
+                None
+            }
+        }))
+    }
+
     /// Pre-warm the model by sending a minimal request to ensure it's loaded
     pub async fn prewarm_model(&self) -> Result<()> {
         // Send a minimal request to load the model into memory
@@ -78,7 +327,7 @@ impl OllamaClient {
     pub async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
         let url = format!("{}/api/embeddings", self.base_url);
         let request = EmbeddingRequest {
-            model: self.model.clone(),
+            model: self.embedding_model.clone(),
             prompt: text.to_string(),
         };
         let response = self.client.post(&url).json(&request).send().await?;
@@ -108,101 +357,232 @@ impl OllamaClient {
         prompt: &str,
         system: &str,
     ) -> Result<String> {
-        let url = format!("{}/api/chat", self.base_url);
-        let mut messages = Vec::new();
-        if !system.is_empty() {
-            messages.push(Message {
-                role: "system".to_string(),
-                content: system.to_string(),
-            });
-        }
-        messages.push(Message {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        });
-        let request = ChatRequest {
-            model: self.model.clone(),
-            messages,
-            stream: false,
-        };
-        let response = self.client.post(&url).json(&request).send().await?;
-        let status = response.status();
-        let text = response.text().await?;
-        if !status.is_success() {
-            return Err(anyhow::anyhow!("Ollama API error: {}", text));
-        }
-
-        // Ultra-fast response parsing with minimal allocations
-        let mut full_content = String::with_capacity(4096); // Pre-allocate for performance
-        for line in text.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            if let Ok(chat_resp) = serde_json::from_str::<ChatResponse>(line) {
-                full_content.push_str(&chat_resp.message.content);
-                if chat_resp.done {
-                    break;
-                }
-            }
-        }
-        Ok(full_content)
+        // Streamed even without a consumer so a dropped connection keeps the
+        // partial response and can be resumed
+        self.generate_response_with_system_streaming(prompt, system, |_| {})
+            .await
     }
 
     /// Generate response with system message and streaming support
     pub async fn generate_response_with_system_streaming<F>(
+        &self,
+        prompt: &str,
+        system: &str,
+        on_chunk: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.generate_response_with_system_streaming_cancellable(
+            prompt,
+            system,
+            on_chunk,
+            &CancellationToken::new(),
+        )
+        .await
+    }
+
+    /// Streaming generation that stops as soon as `cancel` fires.
+    ///
+    /// Cancelling drops the HTTP response, which closes the connection and makes
+    /// Ollama stop generating; the content received so far is returned as `Ok`.
+    pub async fn generate_response_streaming_cancellable<F>(
+        &self,
+        prompt: &str,
+        on_chunk: F,
+        cancel: &CancellationToken,
+    ) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.generate_response_with_system_streaming_cancellable(prompt, "", on_chunk, cancel)
+            .await
+    }
+
+    /// If the stream drops before the model is done, the request is repeated
+    /// with the partial response and an instruction to carry on, and the
+    /// continuation is stitched on; only when that keeps failing, or nothing
+    /// was received at all, is the error returned. Replies are recorded in the
+    /// prompt archive when one is configured.
+    pub async fn generate_response_with_system_streaming_cancellable<F>(
+        &self,
+        prompt: &str,
+        system: &str,
+        on_chunk: F,
+        cancel: &CancellationToken,
+    ) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let reply = self
+            .stream_with_resume(prompt, system, on_chunk, cancel)
+            .await?;
+        crate::prompt_archive::record(&self.model, system, prompt, &reply);
+        Ok(reply)
+    }
+
+    async fn stream_with_resume<F>(
         &self,
         prompt: &str,
         system: &str,
         mut on_chunk: F,
+        cancel: &CancellationToken,
     ) -> Result<String>
     where
         F: FnMut(&str) + Send,
     {
-        let url = format!("{}/api/chat", self.base_url);
-        let mut messages = Vec::new();
-        if !system.is_empty() {
-            messages.push(Message {
-                role: "system".to_string(),
-                content: system.to_string(),
-            });
+        let mut full_content = String::with_capacity(4096); // Pre-allocate for performance
+        let mut resumes = 0;
+        loop {
+            let messages = chat_messages(prompt, system, &full_content);
+            let mut stitcher = Stitcher::new(&full_content);
+            let end = self
+                .stream_chat(
+                    messages,
+                    &mut |chunk: &str| stitcher.push(chunk, &mut on_chunk),
+                    cancel,
+                )
+                .await;
+            full_content.push_str(&stitcher.finish(&mut on_chunk));
+
+            let error = match end? {
+                StreamEnd::Done | StreamEnd::Cancelled => return Ok(full_content),
+                StreamEnd::Dropped(error) => error,
+            };
+            if full_content.is_empty() {
+                return Err(error);
+            }
+            if resumes == MAX_RESUME_ATTEMPTS {
+                return Err(error.context(format!(
+                    "Ollama response stream dropped {} times",
+                    resumes + 1
+                )));
+            }
+            resumes += 1;
+            eprintln!(
+                "Warning: Ollama stream dropped after {} characters ({}); resuming",
+                full_content.len(),
+                error
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(RESUME_BACKOFF * resumes) => {}
+                _ = cancel.cancelled() => return Ok(full_content),
+            }
         }
-        messages.push(Message {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        });
+    }
 
-        // Enable streaming for real-time feedback
+    /// Send one streaming chat request, passing content to `on_chunk` as it
+    /// arrives. Connection failures are reported as `Dropped` so the caller can
+    /// resume; an error status from Ollama is an error.
+    async fn stream_chat<F>(
+        &self,
+        messages: Vec<Message>,
+        on_chunk: &mut F,
+        cancel: &CancellationToken,
+    ) -> Result<StreamEnd>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let url = format!("{}/api/chat", self.base_url);
         let request = ChatRequest {
             model: self.model.clone(),
             messages,
-            stream: true, // Enable streaming
+            stream: true,
+            format: self.format.clone(),
+            options: self.sampling.into(),
         };
 
-        let response = self.client.post(&url).json(&request).send().await?;
+        let sent = tokio::select! {
+            response = self.client.post(&url).json(&request).send() => response,
+            _ = cancel.cancelled() => return Ok(StreamEnd::Cancelled),
+        };
+        let mut response = match sent {
+            Ok(response) => response,
+            Err(e) => return Ok(StreamEnd::Dropped(e.into())),
+        };
         let status = response.status();
-        let text = response.text().await?;
         if !status.is_success() {
+            let text = response.text().await?;
             return Err(anyhow::anyhow!("Ollama API error: {}", text));
         }
 
-        let mut full_content = String::with_capacity(4096); // Pre-allocate for performance
-        for line in text.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            if let Ok(chat_resp) = serde_json::from_str::<ChatResponse>(line) {
-                let chunk = &chat_resp.message.content;
-                if !chunk.is_empty() {
-                    // Call the callback with each chunk for real-time display
-                    on_chunk(chunk);
-                    full_content.push_str(chunk);
-                }
-                if chat_resp.done {
-                    break;
+        let mut pending = Vec::new(); // NDJSON lines can be split across network chunks
+        loop {
+            let bytes = tokio::select! {
+                chunk = response.chunk() => match chunk {
+                    Ok(Some(bytes)) => bytes,
+                    Ok(None) => break,
+                    Err(e) => return Ok(StreamEnd::Dropped(e.into())),
+                },
+                _ = cancel.cancelled() => return Ok(StreamEnd::Cancelled),
+            };
+            pending.extend_from_slice(&bytes);
+
+            while let Some(newline) = pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=newline).collect();
+                if self.handle_stream_line(&line, on_chunk) {
+                    return Ok(StreamEnd::Done);
                 }
             }
         }
-        Ok(full_content)
+
+        // Final line may arrive without a trailing newline
+        if !pending.is_empty() && self.handle_stream_line(&pending, on_chunk) {
+            return Ok(StreamEnd::Done);
+        }
+        Ok(StreamEnd::Dropped(anyhow::anyhow!(
+            "stream ended before the response was complete"
+        )))
+    }
+
+    /// Process one NDJSON line of a streaming chat response; returns true when done
+    fn handle_stream_line<F>(&self, line: &[u8], on_chunk: &mut F) -> bool
+    where
+        F: FnMut(&str),
+    {
+        let line = String::from_utf8_lossy(line);
+        if line.trim().is_empty() {
+            return false;
+        }
+        let Ok(chat_resp) = serde_json::from_str::<ChatResponse>(line.trim()) else {
+            return false;
+        };
+
+        let chunk = &chat_resp.message.content;
+        if !chunk.is_empty() {
+            // Call the callback with each chunk for real-time display
+            on_chunk(chunk);
+        }
+        self.record_usage(&chat_resp);
+        chat_resp.done
+    }
+
+    /// Embed several texts in one request, in order
+    pub async fn generate_embeddings_batch(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let url = format!("{}/api/embed", self.base_url);
+        let request = BatchEmbeddingRequest {
+            model: self.embedding_model.clone(),
+            input: texts,
+        };
+        let response = self.client.post(&url).json(&request).send().await?;
+        // Ollama before 0.3 only has the single-prompt endpoint
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return self.generate_embeddings_pipelined(request.input).await;
+        }
+        let response: BatchEmbeddingResponse = response.error_for_status()?.json().await?;
+        if response.embeddings.len() != request.input.len() {
+            return Err(anyhow::anyhow!(
+                "Expected {} embeddings from {}, got {}",
+                request.input.len(),
+                self.embedding_model,
+                response.embeddings.len()
+            ));
+        }
+        Ok(response.embeddings)
     }
 
     /// Generate multiple embeddings concurrently with HTTP/2 pipelining
@@ -217,7 +597,7 @@ impl OllamaClient {
             .map(|text| {
                 let client = Arc::clone(&self.client);
                 let base_url = self.base_url.clone();
-                let model = self.model.clone();
+                let model = self.embedding_model.clone();
 
                 async move {
                     let url = format!("{}/api/embeddings", base_url);
@@ -284,9 +664,207 @@ impl OllamaClient {
     }
 }
 
+/// How a streaming chat request ended
+enum StreamEnd {
+    Done,
+    Cancelled,
+    /// The connection failed or closed before the model was done
+    Dropped(anyhow::Error),
+}
+
+/// Messages for a chat request. When resuming, the partial response is
+/// replayed as the assistant's turn, followed by a request to carry on.
+fn chat_messages(prompt: &str, system: &str, partial: &str) -> Vec<Message> {
+    let mut messages = Vec::new();
+    if !system.is_empty() {
+        messages.push(Message {
+            role: "system".to_string(),
+            content: system.to_string(),
+        });
+    }
+    messages.push(Message {
+        role: "user".to_string(),
+        content: prompt.to_string(),
+    });
+    if !partial.is_empty() {
+        messages.push(Message {
+            role: "assistant".to_string(),
+            content: partial.to_string(),
+        });
+        messages.push(Message {
+            role: "user".to_string(),
+            content: format!(
+                "Your reply was cut off. Continue from: \"{}\"\nOutput only the rest of the reply, without repeating anything.",
+                tail(partial, RESUME_CONTEXT_CHARS)
+            ),
+        });
+    }
+    messages
+}
+
+/// The last `max_chars` characters of `text`
+fn tail(text: &str, max_chars: usize) -> &str {
+    let start = text
+        .char_indices()
+        .rev()
+        .nth(max_chars.saturating_sub(1))
+        .map_or(0, |(i, _)| i);
+    &text[start..]
+}
+
+/// Length of the longest start of `next` that `previous` already ends with
+fn repeated_prefix(previous: &str, next: &str) -> usize {
+    (MIN_REPEAT_CHARS..=previous.len().min(next.len()))
+        .rev()
+        .find(|&n| next.is_char_boundary(n) && previous.ends_with(&next[..n]))
+        .unwrap_or(0)
+}
+
+/// Forwards the chunks of a resumed stream, holding back its start until it
+/// is clear how much of it repeats the end of the partial response
+struct Stitcher {
+    tail: String,
+    pending: String,
+    resolved: bool,
+    received: String,
+}
+
+impl Stitcher {
+    fn new(partial: &str) -> Self {
+        Self {
+            tail: tail(partial, RESUME_CONTEXT_CHARS).to_string(),
+            pending: String::new(),
+            resolved: partial.is_empty(),
+            received: String::new(),
+        }
+    }
+
+    fn push<F: FnMut(&str)>(&mut self, chunk: &str, on_chunk: &mut F) {
+        if self.resolved {
+            on_chunk(chunk);
+            self.received.push_str(chunk);
+            return;
+        }
+        self.pending.push_str(chunk);
+        if self.pending.len() >= self.tail.len() {
+            self.resolve(on_chunk);
+        }
+    }
+
+    fn resolve<F: FnMut(&str)>(&mut self, on_chunk: &mut F) {
+        self.resolved = true;
+        let skip = repeated_prefix(&self.tail, &self.pending);
+        let rest = &self.pending[skip..];
+        if !rest.is_empty() {
+            on_chunk(rest);
+            self.received.push_str(rest);
+        }
+    }
+
+    /// Text added by this stream, without any repeated start
+    fn finish<F: FnMut(&str)>(mut self, on_chunk: &mut F) -> String {
+        if !self.resolved {
+            self.resolve(on_chunk);
+        }
+        self.received
+    }
+}
+
 /// Request types for pipelined inference
 #[derive(Clone)]
 pub enum InferenceRequest {
     Embedding { text: String },
     Chat { prompt: String, system: String },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, routing::post, Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn line(content: &str, done: bool) -> String {
+        format!(
+            "{}\n",
+            serde_json::json!({ "message": { "role": "assistant", "content": content }, "done": done })
+        )
+    }
+
+    #[test]
+    fn test_repeated_prefix_and_tail() {
+        assert_eq!(
+            repeated_prefix("fn main() {\n    let greeting", "    let greeting = 1;"),
+            16
+        );
+        assert_eq!(repeated_prefix("abc", "c and more"), 0);
+        assert_eq!(tail("héllo wörld", 5), "wörld");
+        assert_eq!(tail("short", 50), "short");
+    }
+
+    #[test]
+    fn test_sampling_params_are_sent_as_options() {
+        let request = |sampling: SamplingParams| {
+            serde_json::to_value(ChatRequest {
+                model: "m".to_string(),
+                messages: Vec::new(),
+                stream: true,
+                format: None,
+                options: sampling.into(),
+            })
+            .unwrap()
+        };
+        let sent = request(SamplingParams {
+            temperature: Some(0.0),
+            seed: Some(7),
+            max_tokens: Some(64),
+            ..SamplingParams::default()
+        });
+        assert_eq!(
+            sent["options"],
+            serde_json::json!({ "temperature": 0.0, "seed": 7, "num_predict": 64 })
+        );
+        assert!(request(SamplingParams::default()).get("options").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream_is_resumed_and_stitched() {
+        // First request ends without `done`; the second must replay the partial reply
+        async fn chat(
+            State(calls): State<Arc<AtomicUsize>>,
+            Json(request): Json<serde_json::Value>,
+        ) -> String {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                return line("fn main() {\n", false) + &line("    let greeting", false);
+            }
+            let messages = request["messages"].as_array().unwrap();
+            assert_eq!(messages[1]["role"], "assistant");
+            assert!(messages[2]["content"]
+                .as_str()
+                .unwrap()
+                .contains("let greeting"));
+            line("    let greeting = \"hi\";\n}", true)
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = Router::new()
+            .route("/api/chat", post(chat))
+            .with_state(Arc::clone(&calls));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        let client = OllamaClient::new().unwrap().with_base_url(url);
+        let mut streamed = String::new();
+        let reply = client
+            .generate_response_streaming("write main", |chunk| streamed.push_str(chunk))
+            .await
+            .unwrap();
+        server.abort();
+
+        assert_eq!(reply, "fn main() {\n    let greeting = \"hi\";\n}");
+        assert_eq!(streamed, reply);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,202 @@
+//! Retained command outputs
+//!
+//! The last few command outputs are kept in `<data dir>/outputs.json` so a
+//! later query can refer to them: `explain @out:1` attaches the most recent
+//! output, `@out:2` the one before it. Every `bro` invocation is its own
+//! process, so the history lives on disk rather than in the session.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shared::platform;
+use shared::types::Result;
+use std::path::PathBuf;
+
+/// Outputs kept when `OUTPUT_HISTORY_LIMIT` is not set
+pub const DEFAULT_LIMIT: usize = 20;
+
+/// Longest output attached to a query; longer ones keep their tail, where
+/// errors and summaries usually are
+const MAX_ATTACHED_CHARS: usize = 8000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetainedOutput {
+    pub command: String,
+    pub output: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputHistory {
+    path: PathBuf,
+    limit: usize,
+}
+
+impl OutputHistory {
+    pub fn new(path: impl Into<PathBuf>, limit: usize) -> Self {
+        Self {
+            path: path.into(),
+            limit,
+        }
+    }
+
+    /// History shared by all local `bro` processes
+    pub fn open_default(limit: usize) -> Self {
+        Self::new(platform::app_data_dir().join("outputs.json"), limit)
+    }
+
+    /// Retained outputs, most recent first
+    pub fn list(&self) -> Vec<RetainedOutput> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Output `n`, counting from 1 for the most recent
+    pub fn get(&self, n: usize) -> Option<RetainedOutput> {
+        n.checked_sub(1)
+            .and_then(|i| self.list().into_iter().nth(i))
+    }
+
+    pub fn record(&self, command: &str, output: &str) -> Result<()> {
+        if self.limit == 0 || output.trim().is_empty() {
+            return Ok(());
+        }
+        let mut outputs = self.list();
+        outputs.insert(
+            0,
+            RetainedOutput {
+                command: command.to_string(),
+                output: output.to_string(),
+                created_at: Utc::now(),
+            },
+        );
+        outputs.truncate(self.limit);
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write-then-rename so a concurrent reader never sees a partial file
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string(&outputs)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Replace `@out:N` references in `query` with the retained outputs they
+    /// name, attached after the query. Fails on a reference to an output that
+    /// is not retained.
+    pub fn expand_references(&self, query: &str) -> Result<String> {
+        let references = output_references(query);
+        if references.is_empty() {
+            return Ok(query.to_string());
+        }
+
+        let mut attachments = String::new();
+        for n in references {
+            let retained = self.get(n).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "@out:{} does not exist ({} output(s) retained)",
+                    n,
+                    self.list().len()
+                )
+            })?;
+            attachments.push_str(&format!(
+                "\n\nOUTPUT {} (from `{}`):\n```\n{}\n```",
+                n,
+                retained.command,
+                tail(&retained.output, MAX_ATTACHED_CHARS)
+            ));
+        }
+        let mut expanded = String::new();
+        let mut rest = query;
+        while let Some((before, n, after)) = next_reference(rest) {
+            expanded.push_str(before);
+            expanded.push_str(&format!("output {}", n));
+            rest = after;
+        }
+        expanded.push_str(rest);
+        Ok(expanded + &attachments)
+    }
+}
+
+/// Distinct `@out:N` references in order of first appearance
+pub fn output_references(query: &str) -> Vec<usize> {
+    let mut references = Vec::new();
+    let mut rest = query;
+    while let Some((_, n, after)) = next_reference(rest) {
+        if !references.contains(&n) {
+            references.push(n);
+        }
+        rest = after;
+    }
+    references
+}
+
+/// Split `text` around its first `@out:N`: (text before, N, text after)
+fn next_reference(text: &str) -> Option<(&str, usize, &str)> {
+    let mut offset = 0;
+    while let Some(i) = text[offset..].find("@out:") {
+        let start = offset + i;
+        let digits_start = start + "@out:".len();
+        let digits_len = text[digits_start..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len() - digits_start);
+        if let Ok(n) = text[digits_start..digits_start + digits_len].parse() {
+            return Some((&text[..start], n, &text[digits_start + digits_len..]));
+        }
+        offset = digits_start;
+    }
+    None
+}
+
+fn tail(text: &str, max_chars: usize) -> String {
+    let count = text.chars().count();
+    if count <= max_chars {
+        return text.to_string();
+    }
+    let kept: String = text.chars().skip(count - max_chars).collect();
+    format!(
+        "[... {} earlier characters omitted]\n{}",
+        count - max_chars,
+        kept
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_keeps_most_recent_outputs() {
+        let path = std::env::temp_dir().join(format!("bro-outputs-{}.json", std::process::id()));
+        let history = OutputHistory::new(&path, 2);
+
+        history.record("ls", "Cargo.toml\nsrc").unwrap();
+        history.record("pwd", "/home/me").unwrap();
+        history.record("true", "  ").unwrap();
+        history.record("whoami", "me").unwrap();
+
+        let outputs = history.list();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(history.get(1).unwrap().command, "whoami");
+        assert_eq!(history.get(2).unwrap().command, "pwd");
+        assert!(history.get(0).is_none());
+
+        let query = history.expand_references("explain @out:2").unwrap();
+        assert!(query.starts_with("explain output 2\n\nOUTPUT 2 (from `pwd`):"));
+        assert!(query.contains("/home/me"));
+        assert!(history.expand_references("explain @out:3").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_output_references_are_parsed_once() {
+        assert_eq!(
+            output_references("compare @out:1 with @out:12 and @out:1"),
+            vec![1, 12]
+        );
+        assert!(output_references("mail me@out:x").is_empty());
+        assert_eq!(output_references("@out:x @out:3"), vec![3]);
+    }
+}
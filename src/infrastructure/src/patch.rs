@@ -0,0 +1,376 @@
+//! Hunk-based edits for `FileOperation::Patch`
+//!
+//! A model can describe an edit as SEARCH/REPLACE blocks or as a unified
+//! diff instead of rewriting the whole file; both become [`PatchHunk`]s.
+//! Each hunk is applied to the file as it is on disk, so unrelated edits
+//! survive: first by exact text, then line by line ignoring indentation
+//! (re-indenting the replacement), then by the most similar block of lines.
+//! A hunk that matches nowhere, or in more than one place, fails with the
+//! closest candidate named. Diff hunks are placed by their text alone, so one
+//! without context or removed lines (`diff -U0` insertions) is refused.
+
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+
+/// Similarity a block of lines needs to stand in for a hunk's search text
+const FUZZY_THRESHOLD: f32 = 0.85;
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER_MARKER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+/// Replace `search` with `replace`; an empty `search` appends to the file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchHunk {
+    pub search: String,
+    pub replace: String,
+}
+
+/// Hunks from SEARCH/REPLACE blocks or a unified diff
+pub fn parse(text: &str) -> Result<Vec<PatchHunk>> {
+    let hunks = if text.contains(SEARCH_MARKER) {
+        parse_search_replace(text)?
+    } else {
+        parse_unified_diff(text)?
+    };
+    if hunks.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No SEARCH/REPLACE blocks or diff hunks found in the patch"
+        ));
+    }
+    Ok(hunks)
+}
+
+/// SEARCH/REPLACE blocks for `hunks`, which [`parse`] reads back
+pub fn render(hunks: &[PatchHunk]) -> String {
+    hunks
+        .iter()
+        .map(|hunk| {
+            format!(
+                "{}\n{}{}\n{}{}\n",
+                SEARCH_MARKER,
+                with_newline(&hunk.search),
+                DIVIDER_MARKER,
+                with_newline(&hunk.replace),
+                REPLACE_MARKER
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn with_newline(text: &str) -> String {
+    if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{}\n", text)
+    }
+}
+
+fn parse_search_replace(text: &str) -> Result<Vec<PatchHunk>> {
+    let mut hunks = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        if line.trim_end() != SEARCH_MARKER {
+            continue;
+        }
+        let number = hunks.len() + 1;
+        let search = collect_until(&mut lines, DIVIDER_MARKER)
+            .ok_or_else(|| anyhow::anyhow!("Block {} has no {} line", number, DIVIDER_MARKER))?;
+        let replace = collect_until(&mut lines, REPLACE_MARKER)
+            .ok_or_else(|| anyhow::anyhow!("Block {} has no {} line", number, REPLACE_MARKER))?;
+        hunks.push(PatchHunk { search, replace });
+    }
+    Ok(hunks)
+}
+
+/// Lines up to `marker`, each ending in a newline; None without the marker
+fn collect_until<'a>(lines: &mut impl Iterator<Item = &'a str>, marker: &str) -> Option<String> {
+    let mut block = String::new();
+    for line in lines {
+        if line.trim_end() == marker {
+            return Some(block);
+        }
+        block.push_str(line);
+        block.push('\n');
+    }
+    None
+}
+
+fn parse_unified_diff(text: &str) -> Result<Vec<PatchHunk>> {
+    let mut hunks = Vec::new();
+    let mut current: Option<PatchHunk> = None;
+    for line in text.lines() {
+        if line.starts_with("@@") {
+            hunks.extend(current.take());
+            current = Some(PatchHunk {
+                search: String::new(),
+                replace: String::new(),
+            });
+            continue;
+        }
+        let Some(hunk) = current.as_mut() else {
+            continue;
+        };
+        if let Some(rest) = line.strip_prefix('-') {
+            if !line.starts_with("--- ") {
+                hunk.search.push_str(rest);
+                hunk.search.push('\n');
+            }
+        } else if let Some(rest) = line.strip_prefix('+') {
+            if !line.starts_with("+++ ") {
+                hunk.replace.push_str(rest);
+                hunk.replace.push('\n');
+            }
+        } else if line.starts_with('\\') {
+            // "\ No newline at end of file"
+        } else {
+            let context = line.strip_prefix(' ').unwrap_or(line);
+            for side in [&mut hunk.search, &mut hunk.replace] {
+                side.push_str(context);
+                side.push('\n');
+            }
+        }
+    }
+    hunks.extend(current);
+    // An empty search text would append the lines to the end of the file
+    if let Some(index) = hunks.iter().position(|hunk| hunk.search.trim().is_empty()) {
+        return Err(anyhow::anyhow!(
+            "Diff hunk {} only adds lines and has no context to place them; \
+             include context lines or use a SEARCH/REPLACE block",
+            index + 1
+        ));
+    }
+    Ok(hunks)
+}
+
+/// `content` with every hunk applied in order
+pub fn apply(content: &str, hunks: &[PatchHunk]) -> Result<String> {
+    let mut patched = content.to_string();
+    for (index, hunk) in hunks.iter().enumerate() {
+        patched = apply_hunk(&patched, hunk).map_err(|reason| {
+            anyhow::anyhow!(
+                "Hunk {}/{} does not apply: {}",
+                index + 1,
+                hunks.len(),
+                reason
+            )
+        })?;
+    }
+    Ok(patched)
+}
+
+fn apply_hunk(content: &str, hunk: &PatchHunk) -> std::result::Result<String, String> {
+    if hunk.search.trim().is_empty() {
+        let mut appended = content.to_string();
+        if !appended.is_empty() && !appended.ends_with('\n') {
+            appended.push('\n');
+        }
+        appended.push_str(&hunk.replace);
+        return Ok(appended);
+    }
+
+    match content.matches(hunk.search.as_str()).count() {
+        1 => return Ok(content.replacen(hunk.search.as_str(), &hunk.replace, 1)),
+        0 => {}
+        count => {
+            return Err(format!(
+                "its search text occurs {} times; add context to pick one",
+                count
+            ))
+        }
+    }
+
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let search: Vec<&str> = hunk.search.lines().collect();
+    let search = trim_blank_ends(&search);
+    let width = search.len();
+    if width == 0 || width > lines.len() {
+        return Err("its search text is longer than the file".to_string());
+    }
+
+    let same_ignoring_indent: Vec<usize> = (0..=lines.len() - width)
+        .filter(|&start| {
+            lines[start..start + width]
+                .iter()
+                .zip(search)
+                .all(|(line, wanted)| line.trim() == wanted.trim())
+        })
+        .collect();
+    match same_ignoring_indent.as_slice() {
+        [start] => return Ok(splice(&lines, *start, width, search, &hunk.replace)),
+        [] => {}
+        starts => {
+            return Err(format!(
+                "it matches {} places (lines {}); add context to pick one",
+                starts.len(),
+                starts
+                    .iter()
+                    .map(|start| (start + 1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        }
+    }
+
+    let wanted = search.join("\n");
+    let mut scored: Vec<(f32, usize)> = (0..=lines.len() - width)
+        .map(|start| {
+            let block: Vec<&str> = lines[start..start + width]
+                .iter()
+                .map(|line| line.trim_end_matches(['\n', '\r']))
+                .collect();
+            let ratio =
+                similar::TextDiff::from_chars(block.join("\n").as_str(), wanted.as_str()).ratio();
+            (ratio, start)
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let (best, start) = scored[0];
+    let runner_up = scored.get(1).map_or(0.0, |(ratio, _)| *ratio);
+    if best >= FUZZY_THRESHOLD && best > runner_up {
+        // A close block that already reads like the replacement means the
+        // hunk was applied before; applying it again would edit the edit
+        let replace: Vec<&str> = hunk.replace.lines().collect();
+        let replace = trim_blank_ends(&replace);
+        let already_applied = replace.len() == width
+            && lines[start..start + width]
+                .iter()
+                .zip(replace)
+                .all(|(line, applied)| line.trim() == applied.trim());
+        if already_applied {
+            return Err(format!("it is already applied at line {}", start + 1));
+        }
+        return Ok(splice(&lines, start, width, search, &hunk.replace));
+    }
+    Err(format!(
+        "no lines match `{}`; the closest are at line {} ({:.0}% similar)",
+        search[0].trim(),
+        start + 1,
+        best * 100.0
+    ))
+}
+
+fn trim_blank_ends<'a, 'b>(lines: &'b [&'a str]) -> &'b [&'a str] {
+    let start = lines
+        .iter()
+        .position(|line| !line.trim().is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(start, |last| last + 1);
+    &lines[start..end]
+}
+
+/// Replace `width` lines from `start` with `replace`, moved from the search
+/// text's indentation to the file's
+fn splice(lines: &[&str], start: usize, width: usize, search: &[&str], replace: &str) -> String {
+    let file_indent = indent(lines[start]);
+    let search_indent = indent(search[0]);
+    let mut text: String = lines[..start].concat();
+    for line in replace.lines() {
+        match line.strip_prefix(search_indent) {
+            Some(rest) if !line.trim().is_empty() => {
+                text.push_str(file_indent);
+                text.push_str(rest);
+            }
+            _ => text.push_str(line),
+        }
+        text.push('\n');
+    }
+    let replaced_last_line = lines[start + width - 1];
+    if !replaced_last_line.ends_with('\n') && text.ends_with('\n') {
+        text.pop();
+    }
+    text.push_str(&lines[start + width..].concat());
+    text
+}
+
+fn indent(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_apply_hunks() {
+        let file = "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n\nfn other() {}\n";
+        let blocks =
+            "Edit:\n<<<<<<< SEARCH\n    let x = 1;\n=======\n    let x = 2;\n>>>>>>> REPLACE\n";
+        let hunks = parse(blocks).unwrap();
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(parse(&render(&hunks)).unwrap(), hunks);
+        assert_eq!(
+            apply(file, &hunks).unwrap(),
+            file.replace("let x = 1", "let x = 2")
+        );
+
+        let diff = "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -5,2 +5,2 @@\n \n-fn other() {}\n+fn other() -> u8 { 0 }\n";
+        let patched = apply(file, &parse(diff).unwrap()).unwrap();
+        assert!(patched.ends_with("\nfn other() -> u8 { 0 }\n"));
+
+        // Indentation differs from the file: matched anyway, re-indented
+        let unindented = PatchHunk {
+            search: "let x = 1;\nprintln!(\"{}\", x);\n".to_string(),
+            replace: "let x = 3;\nprintln!(\"{}\", x);\n".to_string(),
+        };
+        let patched = apply(file, &[unindented]).unwrap();
+        assert!(patched.contains("\n    let x = 3;\n    println!"));
+
+        // A typo in the search text still finds the block
+        let typo = PatchHunk {
+            search: "    let x = 1;\n    printn!(\"{}\", x);\n".to_string(),
+            replace: "    dbg!(1);\n".to_string(),
+        };
+        assert_eq!(
+            apply(file, &[typo]).unwrap(),
+            "fn main() {\n    dbg!(1);\n}\n\nfn other() {}\n"
+        );
+
+        let missing = PatchHunk {
+            search: "fn absent_function_name(arguments: Vec<String>) {}\n".to_string(),
+            replace: String::new(),
+        };
+        let error = apply(file, &[missing]).unwrap_err().to_string();
+        assert!(
+            error.starts_with("Hunk 1/1 does not apply: no lines match"),
+            "{}",
+            error
+        );
+        let twice = PatchHunk {
+            search: "}\n".to_string(),
+            replace: String::new(),
+        };
+        assert!(apply(file, &[twice])
+            .unwrap_err()
+            .to_string()
+            .contains("2 times"));
+        // Applying an edit twice is refused, not fuzzily redone
+        let edit = PatchHunk {
+            search: "fn other() {}\n".to_string(),
+            replace: "fn other() { 1 }\n".to_string(),
+        };
+        let patched = apply(file, std::slice::from_ref(&edit)).unwrap();
+        let error = apply(&patched, &[edit]).unwrap_err().to_string();
+        assert!(error.contains("already applied at line 6"), "{}", error);
+        assert!(parse("just prose").is_err());
+    }
+
+    #[test]
+    fn test_insertion_without_context_is_refused() {
+        let diff = "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -2,0 +3 @@\n+    let y = 2;\n";
+        let error = parse(diff).unwrap_err().to_string();
+        assert!(error.contains("Diff hunk 1 only adds lines"), "{}", error);
+
+        // The same insertion with a line of context lands in the middle
+        let file = "fn main() {\n    let x = 1;\n}\n";
+        let diff = "@@ -2,1 +2,2 @@\n     let x = 1;\n+    let y = 2;\n";
+        assert_eq!(
+            apply(file, &parse(diff).unwrap()).unwrap(),
+            "fn main() {\n    let x = 1;\n    let y = 2;\n}\n"
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! Plugin marketplace
+//!
+//! Script plugins can be installed from an index instead of dropping
+//! `.plugin.toml` files into the plugin directory by hand. The index is a JSON
+//! file served over HTTP or read from a local path (a git checkout works),
+//! signed with Ed25519: `<index>.sig` holds the base64 signature of the index
+//! bytes, checked against `plugins.index_public_key`. Every entry pins the
+//! SHA-256 of its manifest and script and lists the permissions it needs, so
+//! they can be reviewed before the plugin is enabled.
+
+use crate::config::PluginConfig;
+use base64::Engine as _;
+use chrono::{DateTime, Utc};
+use ring::{digest, signature};
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Record of installed plugins, kept in the plugin directory
+const INSTALLED_FILE: &str = "installed.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+    /// Manifest location, absolute or relative to the index
+    pub manifest: String,
+    pub manifest_sha256: String,
+    /// Script location, absolute or relative to the index
+    pub script: String,
+    pub script_sha256: String,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginIndex {
+    pub plugins: Vec<IndexEntry>,
+}
+
+impl PluginIndex {
+    /// Entries whose name or description contains `query`; all for an empty query
+    pub fn search(&self, query: &str) -> Vec<&IndexEntry> {
+        let query = query.trim().to_lowercase();
+        self.plugins
+            .iter()
+            .filter(|entry| {
+                entry.name.to_lowercase().contains(&query)
+                    || entry.description.to_lowercase().contains(&query)
+            })
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&IndexEntry> {
+        self.plugins.iter().find(|entry| entry.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledPlugin {
+    pub version: String,
+    pub permissions: Vec<String>,
+    pub manifest_sha256: String,
+    pub script_sha256: String,
+    pub installed_at: DateTime<Utc>,
+}
+
+/// Plugin downloaded and verified, waiting for its permissions to be approved
+pub struct PendingInstall {
+    pub entry: IndexEntry,
+    /// Installed version being replaced, if any
+    pub previous: Option<InstalledPlugin>,
+    manifest: toml::Table,
+    script: Vec<u8>,
+}
+
+impl PendingInstall {
+    /// Permissions the installed version did not already have
+    pub fn new_permissions(&self) -> Vec<&str> {
+        self.entry
+            .permissions
+            .iter()
+            .filter(|p| {
+                self.previous
+                    .as_ref()
+                    .map_or(true, |previous| !previous.permissions.contains(p))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+pub struct Marketplace {
+    index_url: String,
+    public_key: Vec<u8>,
+    plugin_dir: PathBuf,
+    client: reqwest::Client,
+}
+
+impl Marketplace {
+    pub fn new(index_url: &str, public_key: &str, plugin_dir: impl Into<PathBuf>) -> Result<Self> {
+        let public_key = base64::engine::general_purpose::STANDARD
+            .decode(public_key.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid plugin index public key: {}", e))?;
+        Ok(Self {
+            index_url: index_url.to_string(),
+            public_key,
+            plugin_dir: plugin_dir.into(),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Marketplace for the configured index, installing into the first plugin path
+    pub fn from_config(config: &PluginConfig) -> Result<Self> {
+        let index_url = config.index_url.as_deref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "No plugin index configured; set plugins.index_url or VIBE_PLUGIN_INDEX"
+            )
+        })?;
+        // An unsigned index would let anyone who can serve it run code here
+        let public_key = config.index_public_key.as_deref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "No key to verify the plugin index; set plugins.index_public_key or VIBE_PLUGIN_INDEX_KEY"
+            )
+        })?;
+        let plugin_dir = config
+            .paths
+            .first()
+            .map(|path| PathBuf::from(shellexpand::tilde(path).to_string()))
+            .ok_or_else(|| anyhow::anyhow!("No plugin path configured"))?;
+        Self::new(index_url, public_key, plugin_dir)
+    }
+
+    pub fn plugin_dir(&self) -> &Path {
+        &self.plugin_dir
+    }
+
+    /// Download the index and check its signature
+    pub async fn fetch_index(&self) -> Result<PluginIndex> {
+        let index = self.fetch(&self.index_url).await?;
+        let signature = self.fetch(&format!("{}.sig", self.index_url)).await?;
+        verify_signature(&self.public_key, &index, &signature)
+            .map_err(|e| anyhow::anyhow!("Plugin index {}: {}", self.index_url, e))?;
+        Ok(serde_json::from_slice(&index)?)
+    }
+
+    /// Plugins installed from the marketplace, by name
+    pub fn installed(&self) -> BTreeMap<String, InstalledPlugin> {
+        std::fs::read_to_string(self.plugin_dir.join(INSTALLED_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Download `entry` and verify it against the index without enabling it
+    pub async fn prepare(&self, entry: &IndexEntry) -> Result<PendingInstall> {
+        if !is_safe_name(&entry.name) {
+            return Err(anyhow::anyhow!("Invalid plugin name '{}'", entry.name));
+        }
+        let manifest = self
+            .download_verified(&entry.manifest, &entry.manifest_sha256)
+            .await?;
+        let script = self
+            .download_verified(&entry.script, &entry.script_sha256)
+            .await?;
+
+        let manifest: toml::Table = toml::from_str(&String::from_utf8(manifest)?)?;
+        if manifest.get("name").and_then(|v| v.as_str()) != Some(entry.name.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Manifest for '{}' declares a different plugin name",
+                entry.name
+            ));
+        }
+        if manifest.get("type").and_then(|v| v.as_str()) != Some("script") {
+            return Err(anyhow::anyhow!(
+                "'{}' is not a script plugin; only script plugins can be installed",
+                entry.name
+            ));
+        }
+
+        Ok(PendingInstall {
+            entry: entry.clone(),
+            previous: self.installed().remove(&entry.name),
+            manifest,
+            script,
+        })
+    }
+
+    /// Write an approved plugin into the plugin directory, where it is loaded
+    /// on the next start; returns the manifest path
+    pub fn install(&self, mut pending: PendingInstall) -> Result<PathBuf> {
+        let entry = &pending.entry;
+        let script_dir = self.plugin_dir.join(&entry.name);
+        std::fs::create_dir_all(&script_dir)?;
+
+        let script_name = entry
+            .script
+            .rsplit('/')
+            .next()
+            .filter(|name| is_safe_name(name))
+            .unwrap_or("plugin");
+        let script_path = script_dir.join(script_name);
+        std::fs::write(&script_path, &pending.script)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script_path, std::fs::Permissions::from_mode(0o755))?;
+        }
+
+        // Manifests point at their script; make it the installed copy
+        pending.manifest.insert(
+            "script".to_string(),
+            toml::Value::String(script_path.to_string_lossy().to_string()),
+        );
+        let manifest_path = self.plugin_dir.join(format!("{}.plugin.toml", entry.name));
+        std::fs::write(&manifest_path, toml::to_string(&pending.manifest)?)?;
+
+        let mut installed = self.installed();
+        installed.insert(
+            entry.name.clone(),
+            InstalledPlugin {
+                version: entry.version.clone(),
+                permissions: entry.permissions.clone(),
+                manifest_sha256: entry.manifest_sha256.clone(),
+                script_sha256: entry.script_sha256.clone(),
+                installed_at: Utc::now(),
+            },
+        );
+        let record = self.plugin_dir.join(INSTALLED_FILE);
+        let tmp = record.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&installed)?)?;
+        std::fs::rename(tmp, record)?;
+        Ok(manifest_path)
+    }
+
+    async fn download_verified(&self, location: &str, expected_sha256: &str) -> Result<Vec<u8>> {
+        let location = resolve(&self.index_url, location);
+        let bytes = self.fetch(&location).await?;
+        let actual = sha256_hex(&bytes);
+        if !actual.eq_ignore_ascii_case(expected_sha256.trim()) {
+            return Err(anyhow::anyhow!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                location,
+                expected_sha256,
+                actual
+            ));
+        }
+        Ok(bytes)
+    }
+
+    async fn fetch(&self, location: &str) -> Result<Vec<u8>> {
+        if location.starts_with("http://") || location.starts_with("https://") {
+            let response = self.client.get(location).send().await?.error_for_status()?;
+            return Ok(response.bytes().await?.to_vec());
+        }
+        let path = location.strip_prefix("file://").unwrap_or(location);
+        std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))
+    }
+}
+
+/// What a permission lets a plugin do, for review before enabling it
+pub fn describe_permission(permission: &str) -> &'static str {
+    match permission {
+        "network" => "make network requests",
+        "filesystem:read" => "read files outside its own directory",
+        "filesystem:write" => "create, modify or delete files",
+        "exec" => "run other programs",
+        "env" => "read environment variables, which may hold credentials",
+        "clipboard" => "read and write the clipboard",
+        _ => "unrecognised permission; ask the author what it grants",
+    }
+}
+
+/// Whether dotted version `candidate` is newer than `installed`
+pub fn is_newer(candidate: &str, installed: &str) -> bool {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    parse(candidate) > parse(installed)
+}
+
+fn verify_signature(public_key: &[u8], data: &[u8], signature_b64: &[u8]) -> Result<()> {
+    let signature = base64::engine::general_purpose::STANDARD
+        .decode(String::from_utf8_lossy(signature_b64).trim())
+        .map_err(|e| anyhow::anyhow!("malformed signature: {}", e))?;
+    signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+        .verify(data, &signature)
+        .map_err(|_| anyhow::anyhow!("signature does not match the configured key"))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    digest::digest(&digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Resolve `location` against the index it was listed in
+fn resolve(index_url: &str, location: &str) -> String {
+    if location.contains("://") || Path::new(location).is_absolute() {
+        return location.to_string();
+    }
+    if index_url.starts_with("http://") || index_url.starts_with("https://") {
+        return url::Url::parse(index_url)
+            .and_then(|base| base.join(location))
+            .map(|url| url.to_string())
+            .unwrap_or_else(|_| location.to_string());
+    }
+    let index_path = index_url.strip_prefix("file://").unwrap_or(index_url);
+    Path::new(index_path)
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(location)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Names that are safe to use as file names
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn test_version_ordering() {
+        assert!(is_newer("1.10.0", "1.9.3"));
+        assert!(is_newer("v2.0", "1.99.99"));
+        assert!(!is_newer("1.0.0", "1.0.0"));
+        assert!(!is_newer("0.9.0-beta", "0.9.0"));
+    }
+
+    #[tokio::test]
+    async fn test_signed_index_install() {
+        let dir = std::env::temp_dir().join(format!("bro-marketplace-{}", std::process::id()));
+        let index_dir = dir.join("index");
+        std::fs::create_dir_all(&index_dir).unwrap();
+
+        let manifest =
+            "name = \"hello\"\ntype = \"script\"\nscript = \"hello.sh\"\ncommands = [\"hello\"]\n";
+        let script = "#!/bin/sh\necho hello\n";
+        std::fs::write(index_dir.join("hello.plugin.toml"), manifest).unwrap();
+        std::fs::write(index_dir.join("hello.sh"), script).unwrap();
+        let index = serde_json::json!({ "plugins": [{
+            "name": "hello",
+            "version": "1.0.0",
+            "description": "Says hello",
+            "manifest": "hello.plugin.toml",
+            "manifest_sha256": sha256_hex(manifest.as_bytes()),
+            "script": "hello.sh",
+            "script_sha256": sha256_hex(script.as_bytes()),
+            "permissions": ["exec"],
+        }]})
+        .to_string();
+        let index_path = index_dir.join("index.json");
+        std::fs::write(&index_path, &index).unwrap();
+
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let b64 = &base64::engine::general_purpose::STANDARD;
+        std::fs::write(
+            index_dir.join("index.json.sig"),
+            b64.encode(key.sign(index.as_bytes())),
+        )
+        .unwrap();
+
+        let marketplace = Marketplace::new(
+            &index_path.to_string_lossy(),
+            &b64.encode(key.public_key()),
+            dir.join("plugins"),
+        )
+        .unwrap();
+        let index = marketplace.fetch_index().await.unwrap();
+        assert_eq!(index.search("HELLO").len(), 1);
+
+        let pending = marketplace
+            .prepare(index.get("hello").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(pending.new_permissions(), vec!["exec"]);
+        let manifest_path = marketplace.install(pending).unwrap();
+        let installed: toml::Table =
+            toml::from_str(&std::fs::read_to_string(manifest_path).unwrap()).unwrap();
+        assert!(installed["script"]
+            .as_str()
+            .unwrap()
+            .ends_with("hello/hello.sh"));
+        assert_eq!(marketplace.installed()["hello"].version, "1.0.0");
+
+        // A tampered index no longer verifies
+        std::fs::write(&index_path, index_path.to_string_lossy().as_bytes()).unwrap();
+        assert!(marketplace.fetch_index().await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,9 +3,18 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+#[path = "policy_engine/bundle.rs"]
+pub mod bundle;
+#[path = "policy_engine/language.rs"]
+pub mod language;
+
+use language::{PolicyContext, PolicySet, Verdict};
+
 /// Central policy engine for security decisions
 pub struct PolicyEngine {
     policies: Arc<RwLock<Vec<SecurityPolicy>>>,
+    /// The user's declarative rules, checked before the built-in policies
+    rules: PolicySet,
     audit_logger: PolicyAuditLogger,
 }
 
@@ -52,9 +61,11 @@ pub struct PolicyRequest {
     pub network_access: bool,
     pub file_paths: Vec<String>,
     pub risk_assessment: RiskLevel,
+    #[serde(default)]
+    pub session: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RiskLevel {
     Low,
     Medium,
@@ -62,6 +73,27 @@ pub enum RiskLevel {
     Critical,
 }
 
+impl RiskLevel {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "low" => Some(Self::Low),
+            "medium" => Some(Self::Medium),
+            "high" => Some(Self::High),
+            "critical" => Some(Self::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceLimits {
     pub max_memory_mb: u64,
@@ -77,9 +109,25 @@ pub struct PolicyDecision {
     pub reason: String,
     pub applied_policies: Vec<String>,
     pub audit_id: String,
+    /// The user rule that matched, as `source: rule`
+    #[serde(default)]
+    pub rule: Option<String>,
 }
 
 impl PolicyEngine {
+    /// Name and description of each enabled policy, highest priority first
+    pub fn describe_policies(&self) -> Vec<(String, String)> {
+        let Ok(policies) = self.policies.try_read() else {
+            return Vec::new();
+        };
+        let mut enabled: Vec<_> = policies.iter().filter(|p| p.enabled).collect();
+        enabled.sort_by_key(|p| -p.priority);
+        enabled
+            .into_iter()
+            .map(|p| (p.name.clone(), p.description.clone()))
+            .collect()
+    }
+
     pub fn new() -> Self {
         let mut policies = Vec::new();
 
@@ -167,10 +215,25 @@ impl PolicyEngine {
 
         Self {
             policies: Arc::new(RwLock::new(policies)),
+            rules: PolicySet::default(),
             audit_logger: PolicyAuditLogger::new(),
         }
     }
 
+    /// Built-in policies preceded by the user's and the project's rules
+    pub fn from_config() -> Self {
+        Self::new().with_rules(PolicySet::load_configured())
+    }
+
+    pub fn with_rules(mut self, rules: PolicySet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn rules(&self) -> &PolicySet {
+        &self.rules
+    }
+
     /// Evaluate a policy request and return a decision
     pub async fn evaluate_request(
         &self,
@@ -183,8 +246,38 @@ impl PolicyEngine {
             reason: "Request allowed by default policy".to_string(),
             applied_policies: vec![],
             audit_id: self.audit_logger.log_request(&request),
+            rule: None,
         };
 
+        let context = PolicyContext {
+            command: request.parameters.get("command").map(String::as_str),
+            paths: &request.file_paths,
+            tool: Some(request.tool_name.as_str()),
+            session: request.session.as_deref(),
+            risk: Some(request.risk_assessment),
+            time: chrono::Local::now().time(),
+        };
+        if let Some(rule) = self.rules.evaluate(&context) {
+            let reason = rule.reason.clone().unwrap_or_else(|| rule.text.clone());
+            decision.rule = Some(format!("{}: {}", rule.source, rule.text));
+            match rule.verdict {
+                Verdict::Deny => {
+                    decision.action = PolicyAction::Deny(reason);
+                    decision.reason = format!("Denied by policy rule {}", rule.describe());
+                    decision.applied_policies = vec![rule.source.clone()];
+                    self.audit_logger.log_decision(&decision);
+                    return Ok(decision);
+                }
+                Verdict::Ask => {
+                    decision.action = PolicyAction::RequireApproval(reason);
+                    decision.reason = format!("Policy rule {} requires approval", rule.describe());
+                    applied_policies.push(rule.source.clone());
+                }
+                // Only ends the user's rules; the built-in policies still apply
+                Verdict::Allow => applied_policies.push(rule.source.clone()),
+            }
+        }
+
         // Evaluate each policy in priority order
         for policy in policies.iter().filter(|p| p.enabled) {
             if self.policy_matches(&request, policy) {
@@ -263,15 +356,7 @@ impl PolicyEngine {
                 request.file_paths.iter().any(|fp| fp.starts_with(path))
             }
             PolicyCondition::ContainsSecrets(required) => request.contains_secrets == *required,
-            PolicyCondition::RiskLevel(level) => {
-                let request_level = match request.risk_assessment {
-                    RiskLevel::Low => "low",
-                    RiskLevel::Medium => "medium",
-                    RiskLevel::High => "high",
-                    RiskLevel::Critical => "critical",
-                };
-                request_level == level
-            }
+            PolicyCondition::RiskLevel(level) => request.risk_assessment.name() == level,
         }
     }
 
@@ -429,6 +514,8 @@ impl std::error::Error for PolicyError {}
 
 /// Integration helper for tool execution
 pub async fn evaluate_tool_request(
+    engine: &PolicyEngine,
+    session: Option<String>,
     tool_name: &str,
     parameters: &HashMap<String, String>,
     resource_limits: &ResourceLimits,
@@ -436,8 +523,6 @@ pub async fn evaluate_tool_request(
     network_access: bool,
     file_paths: &[String],
 ) -> Result<PolicyDecision, PolicyError> {
-    let engine = PolicyEngine::new();
-
     // Assess risk level based on tool and parameters
     let risk_assessment = assess_risk_level(tool_name, parameters);
 
@@ -450,6 +535,7 @@ pub async fn evaluate_tool_request(
         network_access,
         file_paths: file_paths.to_vec(),
         risk_assessment,
+        session,
     };
 
     engine.evaluate_request(request).await
@@ -499,6 +585,7 @@ mod tests {
             network_access: false,
             file_paths: vec![],
             risk_assessment: RiskLevel::High,
+            session: None,
         };
 
         let decision = engine.evaluate_request(request).await.unwrap();
@@ -510,6 +597,51 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_user_rules_come_before_builtin_policies() {
+        let rules = PolicySet::parse(
+            "deny tool == file_read and path ~ \"**/.env\" because \"secrets\"\nallow tool == file_write",
+            "policy.rules",
+        )
+        .unwrap();
+        let engine = PolicyEngine::new().with_rules(rules);
+        let request = |tool: &str, path: &str| PolicyRequest {
+            user_id: None,
+            tool_name: tool.to_string(),
+            parameters: HashMap::from([("path".to_string(), path.to_string())]),
+            resource_limits: ResourceLimits {
+                max_memory_mb: 100,
+                max_cpu_percent: 50.0,
+                max_execution_time: 30,
+                max_output_size: 1024,
+                max_processes: 10,
+            },
+            contains_secrets: false,
+            network_access: false,
+            file_paths: vec![path.to_string()],
+            risk_assessment: RiskLevel::Low,
+            session: None,
+        };
+
+        let decision = engine
+            .evaluate_request(request("file_read", "app/.env"))
+            .await
+            .unwrap();
+        assert!(matches!(decision.action, PolicyAction::Deny(ref reason) if reason == "secrets"));
+        assert_eq!(decision.applied_policies, ["policy.rules:1"]);
+
+        // An allow rule does not lift the built-in protection of system paths
+        let decision = engine
+            .evaluate_request(request("file_write", "/etc/hosts"))
+            .await
+            .unwrap();
+        assert!(matches!(decision.action, PolicyAction::Deny(_)));
+        assert_eq!(
+            decision.rule.as_deref(),
+            Some("policy.rules:2: allow tool == file_write")
+        );
+    }
+
     #[test]
     fn test_resource_limit_check() {
         let engine = PolicyEngine::new();
@@ -528,6 +660,7 @@ mod tests {
             network_access: false,
             file_paths: vec![],
             risk_assessment: RiskLevel::Low,
+            session: None,
         };
 
         // Test the internal method
@@ -0,0 +1,274 @@
+//! Team policy bundles
+//!
+//! A security team publishes policy rules once and every bro installation
+//! enforces them. A bundle is a JSON file holding the rules, a version and an
+//! Ed25519 signature over both, served over https or kept in a git
+//! repository:
+//!
+//! ```json
+//! {"version": 7, "rules": "deny command ~ \"curl * | sh\"\n", "signature": "<base64>"}
+//! ```
+//!
+//! [`refresh`] runs at startup: when the cached copy is older than
+//! `refresh_hours` it fetches the bundle, checks the signature against the
+//! configured public key and caches it. [`cached_rules`] checks the cached
+//! copy again and parses it, so evaluating the policy never waits on the
+//! network. A bundle older than the cached one is refused, so a stale mirror
+//! cannot roll the rules back.
+
+use super::language::PolicySet;
+use base64::Engine;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use shared::platform;
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::process::Command;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where the team bundle comes from, `policy_bundle` in the config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolicyBundleSource {
+    /// URL of the bundle, or `git+<repository>` for one kept in git
+    pub url: String,
+    /// Bundle file within the repository, for git sources
+    #[serde(default = "default_path")]
+    pub path: String,
+    /// Base64 Ed25519 public key the bundle must be signed with
+    pub public_key: String,
+    /// Hours before the cached bundle is fetched again
+    #[serde(default = "default_refresh_hours")]
+    pub refresh_hours: u64,
+    /// Deny every request while no verified bundle is cached
+    #[serde(default)]
+    pub required: bool,
+}
+
+fn default_path() -> String {
+    "policy.bundle.json".to_string()
+}
+
+fn default_refresh_hours() -> u64 {
+    24
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolicyBundle {
+    pub version: u64,
+    pub rules: String,
+    /// Base64 Ed25519 signature over [`PolicyBundle::signed_message`]
+    pub signature: String,
+}
+
+/// What [`refresh`] did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshOutcome {
+    /// The cached bundle is recent enough
+    Fresh,
+    /// Fetched the version already cached
+    Unchanged(u64),
+    /// Fetched and cached a new version
+    Updated(u64),
+}
+
+impl PolicyBundle {
+    /// What the signature covers: the version and the rules
+    pub fn signed_message(version: u64, rules: &str) -> Vec<u8> {
+        format!("bro-policy-bundle v{}\n{}", version, rules).into_bytes()
+    }
+
+    /// Check the signature against a base64 public key
+    pub fn verify(&self, public_key: &str) -> Result<()> {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let key = engine
+            .decode(public_key.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid policy bundle public key: {}", e))?;
+        let signature = engine
+            .decode(self.signature.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid policy bundle signature: {}", e))?;
+        UnparsedPublicKey::new(&ED25519, key)
+            .verify(&Self::signed_message(self.version, &self.rules), &signature)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Policy bundle v{} is not signed by the configured key",
+                    self.version
+                )
+            })
+    }
+
+    /// The bundle's rules, once its signature checks out
+    pub fn policy(&self, public_key: &str) -> Result<PolicySet> {
+        self.verify(public_key)?;
+        PolicySet::parse(&self.rules, &format!("bundle v{}", self.version))
+    }
+}
+
+/// The cached bundle from `source`, if any, verified or not
+pub fn cached(source: &PolicyBundleSource) -> Option<PolicyBundle> {
+    let text = std::fs::read_to_string(cache_path(source)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Rules of the cached bundle; none while nothing is cached, unless the
+/// bundle is required
+pub fn cached_rules(source: &PolicyBundleSource) -> Result<PolicySet> {
+    match cached(source) {
+        Some(bundle) => bundle.policy(&source.public_key),
+        None if source.required => Err(anyhow::anyhow!(
+            "the required team policy bundle from {} has not been fetched",
+            source.url
+        )),
+        None => Ok(PolicySet::default()),
+    }
+}
+
+/// Fetch, verify and cache the bundle unless the cached copy is recent
+/// enough. On failure the cached copy stays in force.
+pub async fn refresh(source: &PolicyBundleSource) -> Result<RefreshOutcome> {
+    let path = cache_path(source);
+    let cached = cached(source).filter(|bundle| bundle.verify(&source.public_key).is_ok());
+    if cached.is_some() && !is_stale(&path, source.refresh_hours) {
+        return Ok(RefreshOutcome::Fresh);
+    }
+
+    let bytes = tokio::time::timeout(FETCH_TIMEOUT, fetch(source))
+        .await
+        .map_err(|_| anyhow::anyhow!("Timed out fetching {}", source.url))??;
+    let bundle: PolicyBundle = serde_json::from_slice(&bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid policy bundle from {}: {}", source.url, e))?;
+    bundle.policy(&source.public_key)?;
+
+    let cached_version = cached.map(|cached| cached.version);
+    if let Some(version) = cached_version.filter(|version| bundle.version < *version) {
+        return Err(anyhow::anyhow!(
+            "Refusing policy bundle v{} from {}; v{} is already in force",
+            bundle.version,
+            source.url,
+            version
+        ));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Rewritten even when unchanged, so the next refresh waits again
+    std::fs::write(&path, serde_json::to_string_pretty(&bundle)?)?;
+    Ok(if cached_version == Some(bundle.version) {
+        RefreshOutcome::Unchanged(bundle.version)
+    } else {
+        RefreshOutcome::Updated(bundle.version)
+    })
+}
+
+/// Cache file for `source`; one per URL, so switching sources never mixes
+/// bundles
+fn cache_path(source: &PolicyBundleSource) -> PathBuf {
+    platform::app_data_dir()
+        .join("policy-bundles")
+        .join(format!("{}.json", source_id(source)))
+}
+
+fn source_id(source: &PolicyBundleSource) -> String {
+    blake3::hash(format!("{}#{}", source.url, source.path).as_bytes()).to_hex()[..16].to_string()
+}
+
+fn is_stale(path: &Path, refresh_hours: u64) -> bool {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map_or(true, |age| age >= Duration::from_secs(refresh_hours * 3600))
+}
+
+async fn fetch(source: &PolicyBundleSource) -> Result<Vec<u8>> {
+    let Some(repository) = source.url.strip_prefix("git+") else {
+        let response = reqwest::get(&source.url).await?.error_for_status()?;
+        return Ok(response.bytes().await?.to_vec());
+    };
+
+    // A shallow checkout, reset to the remote's head on every fetch
+    let checkout = platform::app_data_dir()
+        .join("policy-bundles")
+        .join(source_id(source));
+    if checkout.join(".git").exists() {
+        git(&checkout, &["fetch", "--quiet", "--depth", "1", "origin"]).await?;
+        git(&checkout, &["reset", "--quiet", "--hard", "FETCH_HEAD"]).await?;
+    } else {
+        if let Some(parent) = checkout.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let target = checkout.to_string_lossy();
+        git(
+            Path::new("."),
+            &["clone", "--quiet", "--depth", "1", repository, &target],
+        )
+        .await?;
+    }
+    let file = checkout.join(&source.path);
+    std::fs::read(&file).map_err(|e| anyhow::anyhow!("No {} in {}: {}", source.path, repository, e))
+}
+
+async fn git(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn signed(key: &Ed25519KeyPair, version: u64, rules: &str) -> PolicyBundle {
+        let signature = key.sign(&PolicyBundle::signed_message(version, rules));
+        PolicyBundle {
+            version,
+            rules: rules.to_string(),
+            signature: base64::engine::general_purpose::STANDARD.encode(signature.as_ref()),
+        }
+    }
+
+    fn key_pair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    #[test]
+    fn test_only_bundles_signed_by_the_key_are_accepted() {
+        let team = key_pair();
+        let public_key =
+            base64::engine::general_purpose::STANDARD.encode(team.public_key().as_ref());
+        let bundle = signed(
+            &team,
+            3,
+            "deny command ~ \"curl *\" because \"no downloads\"\n",
+        );
+
+        let rules = bundle.policy(&public_key).unwrap();
+        assert_eq!(rules.rules().len(), 1);
+        assert_eq!(rules.rules()[0].source, "bundle v3:1");
+
+        // Edited rules, a replayed signature on another version, another key
+        let mut edited = bundle.clone();
+        edited.rules = "allow command ~ \"curl *\"\n".to_string();
+        assert!(edited.verify(&public_key).is_err());
+        let mut replayed = bundle.clone();
+        replayed.version = 4;
+        assert!(replayed.verify(&public_key).is_err());
+        let forged = signed(&key_pair(), 3, &bundle.rules);
+        assert!(forged.policy(&public_key).is_err());
+    }
+}
@@ -0,0 +1,568 @@
+//! Declarative policy rules
+//!
+//! Rules live in `policy.rules` in the config directory (or the file named by
+//! `VIBE_POLICY_FILE`) and in `.bro/policy.rules` of the project, project
+//! rules first. One rule per line, `#` starts a comment:
+//!
+//! ```text
+//! deny  command ~ "git push --force*"            because "force pushes rewrite history"
+//! ask   risk >= high and time in 22:00-07:00     because "risky changes at night"
+//! allow session == ops and command ~ "systemctl *"
+//! deny  path ~ "**/.env*"
+//! deny  tool == curl_fetch and not session == research
+//! ```
+//!
+//! A rule is `allow`, `deny` or `ask` followed by conditions joined with
+//! `and`, and an optional `because` reason; a rule without conditions matches
+//! everything. Conditions compare a field with `~` (glob), `==`, `!=`, or for
+//! `risk`, `<`, `<=`, `>` and `>=`; `time in HH:MM-HH:MM` may wrap midnight.
+//! Fields: `command`, `path` (any of the paths involved), `tool`, `session`,
+//! `risk` (low, medium, high, critical) and `time`. A condition on a field
+//! the request does not have never holds, with or without `not`.
+//!
+//! The first matching rule decides. `allow` only ends the user's rules; the
+//! built-in policies still apply after it.
+
+use super::RiskLevel;
+use crate::sandbox::confirmation_rules::glob_match;
+use chrono::NaiveTime;
+use shared::platform;
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+
+const RULES_FILE: &str = "policy.rules";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Deny,
+    Ask,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Command,
+    Path,
+    Tool,
+    Session,
+    Risk,
+    Time,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Test {
+    Glob(String),
+    Equals(String),
+    NotEquals(String),
+    Risk(String, RiskLevel),
+    Between(NaiveTime, NaiveTime),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    negated: bool,
+    field: Field,
+    test: Test,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyRule {
+    pub verdict: Verdict,
+    conditions: Vec<Condition>,
+    pub reason: Option<String>,
+    /// `file:line` the rule was read from
+    pub source: String,
+    /// The rule as written
+    pub text: String,
+}
+
+/// What a request is checked against
+#[derive(Debug, Clone)]
+pub struct PolicyContext<'a> {
+    pub command: Option<&'a str>,
+    pub paths: &'a [String],
+    pub tool: Option<&'a str>,
+    pub session: Option<&'a str>,
+    pub risk: Option<RiskLevel>,
+    pub time: NaiveTime,
+}
+
+impl<'a> PolicyContext<'a> {
+    /// A command about to run now in `session`
+    pub fn for_command(command: &'a str, session: Option<&'a str>) -> Self {
+        Self {
+            command: Some(command),
+            paths: &[],
+            tool: None,
+            session,
+            risk: Some(command_risk(command)),
+            time: chrono::Local::now().time(),
+        }
+    }
+}
+
+/// How one rule fared against a request, for `--policy explain`
+#[derive(Debug, Clone)]
+pub struct RuleTrace<'a> {
+    pub rule: &'a PolicyRule,
+    /// None when the rule matched, otherwise the condition that did not hold
+    pub failed: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolicySet {
+    rules: Vec<PolicyRule>,
+}
+
+impl PolicySet {
+    /// Parse rules; `origin` names the file in errors and rule sources
+    pub fn parse(text: &str, origin: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let source = format!("{}:{}", origin, i + 1);
+            let tokens = tokenize(line).map_err(|e| anyhow::anyhow!("{}: {}", source, e))?;
+            if tokens.is_empty() {
+                continue;
+            }
+            let rule = parse_rule(&tokens, source.clone(), line.trim())
+                .map_err(|e| anyhow::anyhow!("{}: {}", source, e))?;
+            rules.push(rule);
+        }
+        Ok(Self { rules })
+    }
+
+    /// Rules of the team policy bundle, then of the project at
+    /// `project_root`, then the user's
+    pub fn load(project_root: Option<&Path>) -> Result<Self> {
+        let mut rules = match crate::config::PowerUserConfig::load().policy_bundle {
+            Some(source) => super::bundle::cached_rules(&source)?.rules,
+            None => Vec::new(),
+        };
+        for path in Self::files(project_root) {
+            if let Ok(text) = std::fs::read_to_string(&path) {
+                rules.extend(Self::parse(&text, &path.display().to_string())?.rules);
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    /// Rules of the team, current project and user; a file that does not
+    /// parse or a bundle that does not verify denies everything, so a typo
+    /// never silently disables a deny rule
+    pub fn load_configured() -> Self {
+        let root = crate::config::find_project_root().map(PathBuf::from);
+        Self::load(root.as_deref()).unwrap_or_else(|e| {
+            eprintln!(
+                "Warning: policy rules cannot be loaded, denying all requests: {}",
+                e
+            );
+            Self::deny_all(&e.to_string())
+        })
+    }
+
+    /// Files rules are read from, in evaluation order
+    pub fn files(project_root: Option<&Path>) -> Vec<PathBuf> {
+        let user = std::env::var_os("VIBE_POLICY_FILE")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| platform::app_config_dir().join(RULES_FILE));
+        project_root
+            .map(|root| root.join(".bro").join(RULES_FILE))
+            .into_iter()
+            .chain(std::iter::once(user))
+            .collect()
+    }
+
+    fn deny_all(error: &str) -> Self {
+        Self {
+            rules: vec![PolicyRule {
+                verdict: Verdict::Deny,
+                conditions: Vec::new(),
+                reason: Some(format!(
+                    "the policy rules cannot be loaded ({}); check them with bro --policy check",
+                    error
+                )),
+                source: "policy".to_string(),
+                text: "deny".to_string(),
+            }],
+        }
+    }
+
+    pub fn rules(&self) -> &[PolicyRule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The first rule matching `context`
+    pub fn evaluate(&self, context: &PolicyContext) -> Option<&PolicyRule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.first_failure(context).is_none())
+    }
+
+    /// Every rule up to and including the one that decides
+    pub fn explain(&self, context: &PolicyContext) -> Vec<RuleTrace<'_>> {
+        let mut traces = Vec::new();
+        for rule in &self.rules {
+            let failed = rule.first_failure(context);
+            let decided = failed.is_none();
+            traces.push(RuleTrace { rule, failed });
+            if decided {
+                break;
+            }
+        }
+        traces
+    }
+}
+
+impl PolicyRule {
+    /// Why the rule does not match `context`, if it does not
+    fn first_failure(&self, context: &PolicyContext) -> Option<String> {
+        self.conditions
+            .iter()
+            .find(|condition| !condition.holds(context))
+            .map(|condition| condition.describe_failure(context))
+    }
+
+    /// `source: reason (rule text)`, for messages
+    pub fn describe(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!("{}: {} ({})", self.source, reason, self.text),
+            None => format!("{}: {}", self.source, self.text),
+        }
+    }
+}
+
+impl Condition {
+    fn holds(&self, context: &PolicyContext) -> bool {
+        let Some(held) = self.test_value(context) else {
+            return false;
+        };
+        held != self.negated
+    }
+
+    fn test_value(&self, context: &PolicyContext) -> Option<bool> {
+        let matches = |value: &str| match &self.test {
+            // In commands a `*` may span `/`, as in `rm -rf build/*`
+            Test::Glob(pattern) if self.field == Field::Command => {
+                glob_match(&pattern.replace('*', "**"), value)
+            }
+            Test::Glob(pattern) => glob_match(pattern, value),
+            Test::Equals(expected) => value.eq_ignore_ascii_case(expected),
+            Test::NotEquals(expected) => !value.eq_ignore_ascii_case(expected),
+            _ => false,
+        };
+        match self.field {
+            Field::Command => context.command.map(matches),
+            Field::Tool => context.tool.map(matches),
+            Field::Session => context.session.map(matches),
+            Field::Path if context.paths.is_empty() => None,
+            Field::Path => Some(context.paths.iter().any(|path| matches(path))),
+            Field::Risk => match (&self.test, context.risk) {
+                (Test::Risk(op, level), Some(risk)) => Some(compare(op, risk, *level)),
+                _ => None,
+            },
+            Field::Time => match self.test {
+                Test::Between(start, end) if start <= end => {
+                    Some(context.time >= start && context.time < end)
+                }
+                Test::Between(start, end) => Some(context.time >= start || context.time < end),
+                _ => None,
+            },
+        }
+    }
+
+    fn describe_failure(&self, context: &PolicyContext) -> String {
+        let actual = match self.field {
+            Field::Command => context.command.map(str::to_string),
+            Field::Tool => context.tool.map(str::to_string),
+            Field::Session => context.session.map(str::to_string),
+            Field::Path => (!context.paths.is_empty()).then(|| context.paths.join(", ")),
+            Field::Risk => context.risk.map(|risk| risk.name().to_string()),
+            Field::Time => Some(context.time.format("%H:%M").to_string()),
+        };
+        let field = format!("{:?}", self.field).to_lowercase();
+        match actual {
+            None => format!("the request has no {}", field),
+            Some(actual) => format!(
+                "{}{} {} does not hold for {}",
+                if self.negated { "not " } else { "" },
+                field,
+                self.test.describe(),
+                actual
+            ),
+        }
+    }
+}
+
+impl Test {
+    fn describe(&self) -> String {
+        match self {
+            Test::Glob(pattern) => format!("~ \"{}\"", pattern),
+            Test::Equals(value) => format!("== {}", value),
+            Test::NotEquals(value) => format!("!= {}", value),
+            Test::Risk(op, level) => format!("{} {}", op, level.name()),
+            Test::Between(start, end) => {
+                format!("in {}-{}", start.format("%H:%M"), end.format("%H:%M"))
+            }
+        }
+    }
+}
+
+fn compare(op: &str, actual: RiskLevel, expected: RiskLevel) -> bool {
+    match op {
+        "<" => actual < expected,
+        "<=" => actual <= expected,
+        ">" => actual > expected,
+        ">=" => actual >= expected,
+        "!=" => actual != expected,
+        _ => actual == expected,
+    }
+}
+
+/// Rough risk of a shell command, for rules on `risk`
+pub fn command_risk(command: &str) -> RiskLevel {
+    let command = command.trim().to_lowercase();
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let program = match words.as_slice() {
+        ["sudo", program, ..] => *program,
+        [program, ..] => *program,
+        [] => return RiskLevel::Low,
+    };
+    if command.contains("rm -rf /")
+        || command.contains("mkfs")
+        || (command.contains("dd ") && command.contains("of=/dev/"))
+    {
+        RiskLevel::Critical
+    } else if words.first() == Some(&"sudo")
+        || matches!(
+            program,
+            "rm" | "chmod" | "chown" | "systemctl" | "kill" | "pkill" | "shred" | "truncate"
+        )
+        || command.contains("push --force")
+        || command.contains("reset --hard")
+    {
+        RiskLevel::High
+    } else if matches!(
+        program,
+        "mv" | "cp" | "curl" | "wget" | "git" | "npm" | "pip"
+    ) || command.contains(" install")
+        || command.contains('>')
+    {
+        RiskLevel::Medium
+    } else {
+        RiskLevel::Low
+    }
+}
+
+/// Words, quoted strings and operators of a rule line, without comments
+fn tokenize(line: &str) -> std::result::Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            break;
+        } else if c == '"' {
+            chars.next();
+            let mut quoted = String::from("\"");
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => quoted.extend(chars.next()),
+                    Some(c) => quoted.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push(quoted);
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' || c == '#' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(word);
+        }
+    }
+    Ok(tokens)
+}
+
+/// A token's text, without the quote marker of quoted strings
+fn unquote(token: &str) -> &str {
+    token.strip_prefix('"').unwrap_or(token)
+}
+
+fn parse_rule(
+    tokens: &[String],
+    source: String,
+    text: &str,
+) -> std::result::Result<PolicyRule, String> {
+    let verdict = match tokens[0].as_str() {
+        "allow" => Verdict::Allow,
+        "deny" => Verdict::Deny,
+        "ask" => Verdict::Ask,
+        other => return Err(format!("expected allow, deny or ask, found '{}'", other)),
+    };
+    let mut rest = &tokens[1..];
+    let mut reason = None;
+    if let Some(at) = rest.iter().position(|token| token == "because") {
+        match &rest[at + 1..] {
+            [text] => reason = Some(unquote(text).to_string()),
+            _ => return Err("'because' takes exactly one quoted reason".to_string()),
+        }
+        rest = &rest[..at];
+    }
+
+    let mut conditions = Vec::new();
+    if !rest.is_empty() {
+        for clause in rest.split(|token| token == "and") {
+            if clause.is_empty() {
+                return Err("'and' needs a condition on both sides".to_string());
+            }
+            conditions.push(parse_condition(clause)?);
+        }
+    }
+    Ok(PolicyRule {
+        verdict,
+        conditions,
+        reason,
+        source,
+        text: text.to_string(),
+    })
+}
+
+fn parse_condition(tokens: &[String]) -> std::result::Result<Condition, String> {
+    let (negated, tokens) = match tokens {
+        [not, rest @ ..] if not == "not" => (true, rest),
+        _ => (false, tokens),
+    };
+    let [field, op, value] = tokens else {
+        return Err(format!(
+            "expected '<field> <operator> <value>', found '{}'",
+            tokens.join(" ")
+        ));
+    };
+    let field = match field.as_str() {
+        "command" => Field::Command,
+        "path" => Field::Path,
+        "tool" => Field::Tool,
+        "session" => Field::Session,
+        "risk" => Field::Risk,
+        "time" => Field::Time,
+        other => return Err(format!("unknown field '{}'", other)),
+    };
+    let value = unquote(value).to_string();
+    let test = match (field, op.as_str()) {
+        (Field::Time, "in") => parse_time_range(&value)?,
+        (Field::Time, other) => return Err(format!("time only supports 'in', not '{}'", other)),
+        (Field::Risk, "<" | "<=" | ">" | ">=" | "==" | "!=") => {
+            let level = RiskLevel::parse(&value)
+                .ok_or_else(|| format!("unknown risk level '{}'", value))?;
+            Test::Risk(op.clone(), level)
+        }
+        (_, "~") => Test::Glob(value),
+        (_, "==") => Test::Equals(value),
+        (_, "!=") => Test::NotEquals(value),
+        (_, other) => return Err(format!("operator '{}' does not apply here", other)),
+    };
+    Ok(Condition {
+        negated,
+        field,
+        test,
+    })
+}
+
+fn parse_time_range(value: &str) -> std::result::Result<Test, String> {
+    let parse = |time: &str| {
+        NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| format!("'{}' is not a time of day (HH:MM)", time))
+    };
+    let (start, end) = value
+        .split_once('-')
+        .ok_or_else(|| format!("expected a range like 22:00-07:00, found '{}'", value))?;
+    Ok(Test::Between(parse(start)?, parse(end)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"
+# Project rules
+deny  command ~ "git push --force*"   because "force pushes rewrite history"
+allow session == ops and command ~ "systemctl *"
+ask   risk >= high and time in 22:00-07:00
+deny  path ~ "**/.env*"
+deny  tool == curl_fetch and not session == research
+"#;
+
+    fn at(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let rules = PolicySet::parse(RULES, "policy.rules").unwrap();
+        assert_eq!(rules.rules().len(), 5);
+
+        let mut context = PolicyContext::for_command("git push --force origin main", None);
+        let rule = rules.evaluate(&context).unwrap();
+        assert_eq!(rule.verdict, Verdict::Deny);
+        assert_eq!(rule.source, "policy.rules:3");
+        assert_eq!(rule.reason.as_deref(), Some("force pushes rewrite history"));
+
+        context = PolicyContext::for_command("systemctl restart nginx", Some("ops"));
+        context.time = at("23:30");
+        assert_eq!(rules.evaluate(&context).unwrap().verdict, Verdict::Allow);
+        context.session = Some("dev");
+        assert_eq!(rules.evaluate(&context).unwrap().verdict, Verdict::Ask);
+        context.time = at("12:00");
+        assert!(rules.evaluate(&context).is_none());
+
+        let paths = ["/work/app/.env.local".to_string()];
+        let context = PolicyContext {
+            command: None,
+            paths: &paths,
+            tool: Some("file_read"),
+            session: None,
+            risk: Some(RiskLevel::Low),
+            time: at("12:00"),
+        };
+        assert_eq!(rules.evaluate(&context).unwrap().source, "policy.rules:6");
+
+        // No session: `not session == research` does not hold either
+        let context = PolicyContext {
+            paths: &[],
+            tool: Some("curl_fetch"),
+            ..context
+        };
+        assert!(rules.evaluate(&context).is_none());
+        let traces = rules.explain(&context);
+        assert_eq!(traces.len(), 5);
+        assert_eq!(
+            traces[4].failed.as_deref(),
+            Some("the request has no session")
+        );
+        assert!(traces[0].failed.as_deref().unwrap().contains("no command"));
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let error = PolicySet::parse("allow\ndeny risk >= severe", "p").unwrap_err();
+        assert_eq!(error.to_string(), "p:2: unknown risk level 'severe'");
+        assert!(PolicySet::parse("deny command ~ \"rm", "p").is_err());
+        assert!(PolicySet::parse("permit command ~ x", "p").is_err());
+        assert!(PolicySet::parse("deny command ~ x and", "p").is_err());
+
+        let catch_all = PolicySet::parse("ask because \"review everything\"", "p").unwrap();
+        let context = PolicyContext::for_command("ls", None);
+        assert_eq!(catch_all.evaluate(&context).unwrap().verdict, Verdict::Ask);
+    }
+}
@@ -3,6 +3,7 @@ use aes_gcm::{Aes256Gcm, Nonce};
 use anyhow::Result;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use shared::pii_detector::{PiiDetector, PiiKind, PiiMode};
 /// Privacy controls and verification system
 /// Ensures zero external data transmission and maintains user privacy
 /// Implements comprehensive monitoring, validation, and audit trails
@@ -726,3 +727,137 @@ impl SecureCache {
         Ok(removed)
     }
 }
+
+/// Personal data handling for prompts, `pii` in the power user config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PiiSettings {
+    /// Mask, hash or block personal data
+    #[serde(default)]
+    pub mode: PiiMode,
+    /// Kinds of personal data looked for
+    #[serde(default = "all_pii_kinds")]
+    pub kinds: Vec<PiiKind>,
+    /// Filter prompts for a model on this machine too
+    #[serde(default)]
+    pub local_models: bool,
+}
+
+fn all_pii_kinds() -> Vec<PiiKind> {
+    PiiKind::ALL.to_vec()
+}
+
+impl Default for PiiSettings {
+    fn default() -> Self {
+        Self {
+            mode: PiiMode::default(),
+            kinds: all_pii_kinds(),
+            local_models: false,
+        }
+    }
+}
+
+impl PiiSettings {
+    /// Detector for prompts sent to the model at `model_url`; None when
+    /// personal data may go there unchanged
+    pub fn detector_for(&self, model_url: &str) -> Option<PiiDetector> {
+        (self.local_models || !is_local_url(model_url))
+            .then(|| PiiDetector::new(&self.kinds, self.mode))
+    }
+}
+
+/// Archive of every prompt and response for audits, `prompt_archive` in the
+/// power user config. Off unless enabled; kept apart from the caches.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Archive file; `archive/prompts.jsonl` in the data directory by default
+    #[serde(default)]
+    pub path: Option<std::path::PathBuf>,
+    /// Days records are kept; forever when unset
+    #[serde(default)]
+    pub retention_days: Option<u32>,
+    /// Applied to each record before it is written
+    #[serde(default)]
+    pub redactions: Vec<FieldRedaction>,
+}
+
+/// Replace matches of `pattern` in `field` with `replacement`; without a
+/// pattern the whole field is replaced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldRedaction {
+    pub field: ArchiveField,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default = "redacted_placeholder")]
+    pub replacement: String,
+}
+
+fn redacted_placeholder() -> String {
+    "[REDACTED]".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveField {
+    Model,
+    System,
+    Prompt,
+    Response,
+}
+
+impl ArchiveSettings {
+    /// The archive these settings describe, with expired records dropped;
+    /// None when archiving is off
+    pub fn open(&self) -> Result<Option<crate::prompt_archive::PromptArchive>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        let path = self.path.clone().unwrap_or_else(|| {
+            shared::platform::app_data_dir()
+                .join("archive")
+                .join("prompts.jsonl")
+        });
+        let archive = crate::prompt_archive::PromptArchive::new(
+            path,
+            self.retention_days
+                .map(|days| chrono::Duration::days(days.into())),
+            &self.redactions,
+        )?;
+        archive.prune()?;
+        Ok(Some(archive))
+    }
+}
+
+/// Whether `url` points at this machine
+pub fn is_local_url(url: &str) -> bool {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .is_some_and(|host| matches!(host.as_str(), "localhost" | "127.0.0.1" | "[::1]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pii_filtering_only_for_remote_models() {
+        let settings = PiiSettings::default();
+        assert!(settings.detector_for("http://localhost:11434").is_none());
+        assert!(settings.detector_for("http://[::1]:11434").is_none());
+        let detector = settings
+            .detector_for("https://gpu.example.com:11434")
+            .unwrap();
+        assert_eq!(
+            detector.apply("mail ops@example.com").unwrap(),
+            "mail [EMAIL]"
+        );
+
+        let everywhere = PiiSettings {
+            local_models: true,
+            ..PiiSettings::default()
+        };
+        assert!(everywhere.detector_for("http://127.0.0.1:11434").is_some());
+    }
+}
@@ -0,0 +1,450 @@
+//! Append-only archive of prompts and responses for audits
+//!
+//! With `prompt_archive.enabled` in the power user config, every completed
+//! generation is appended to a JSONL file with the model, system prompt,
+//! prompt and response, after the configured field redactions. Records are
+//! hash-chained like the command audit log. The retention window only ever
+//! drops the oldest records, so the chain is checked from whichever record
+//! comes first. The archive lives in the data directory, apart from the
+//! caches, and nothing reads it back to answer a prompt.
+
+use crate::command_audit::ChainBreak;
+use crate::file_lock::FileLock;
+use crate::privacy_controls::{ArchiveField, FieldRedaction};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+/// `prev_hash` of the first record ever written
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// End of the archive read first when looking for the last record; doubled
+/// until a whole record fits
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// Archive generations are recorded to, set from the config at startup
+static ACTIVE: RwLock<Option<PromptArchive>> = RwLock::new(None);
+
+/// Serializes appends and pruning within this process
+static APPEND: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveRecord {
+    /// Position in the archive, counting from 1
+    pub seq: u64,
+    /// RFC 3339, UTC
+    pub timestamp: String,
+    pub session: Option<String>,
+    pub model: String,
+    pub system: String,
+    pub prompt: String,
+    pub response: String,
+    /// Fields a redaction rule changed
+    pub redacted: Vec<ArchiveField>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl ArchiveRecord {
+    /// Hash over every field but `hash` itself
+    fn digest(&self) -> String {
+        let unsigned = ArchiveRecord {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let bytes = serde_json::to_vec(&unsigned).unwrap_or_default();
+        blake3::hash(&bytes).to_hex().to_string()
+    }
+
+    fn field_mut(&mut self, field: ArchiveField) -> &mut String {
+        match field {
+            ArchiveField::Model => &mut self.model,
+            ArchiveField::System => &mut self.system,
+            ArchiveField::Prompt => &mut self.prompt,
+            ArchiveField::Response => &mut self.response,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Redaction {
+    field: ArchiveField,
+    pattern: Option<Regex>,
+    replacement: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PromptArchive {
+    path: PathBuf,
+    retention: Option<chrono::Duration>,
+    redactions: Vec<Redaction>,
+}
+
+/// Record generations in `archive` from now on, or stop recording with None
+pub fn install(archive: Option<PromptArchive>) {
+    *ACTIVE.write().unwrap() = archive;
+}
+
+/// Archive one generation when archiving is on; failures are reported, never
+/// fatal to the generation
+pub fn record(model: &str, system: &str, prompt: &str, response: &str) {
+    let active = ACTIVE.read().unwrap();
+    let Some(archive) = active.as_ref() else {
+        return;
+    };
+    if let Err(e) = archive.append(model, system, prompt, response) {
+        eprintln!("Warning: could not write the prompt archive: {}", e);
+    }
+}
+
+impl PromptArchive {
+    pub fn new(
+        path: PathBuf,
+        retention: Option<chrono::Duration>,
+        redactions: &[FieldRedaction],
+    ) -> Result<Self> {
+        let redactions = redactions
+            .iter()
+            .map(|rule| {
+                let pattern = rule
+                    .pattern
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("Invalid archive redaction pattern: {}", e))?;
+                Ok(Redaction {
+                    field: rule.field,
+                    pattern,
+                    replacement: rule.replacement.clone(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            path,
+            retention,
+            redactions,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Redact and append one generation
+    pub fn append(
+        &self,
+        model: &str,
+        system: &str,
+        prompt: &str,
+        response: &str,
+    ) -> Result<ArchiveRecord> {
+        let _guard = APPEND.lock().unwrap_or_else(|e| e.into_inner());
+        // Opened only once locked, so never the file a prune just replaced
+        let _lock = self.lock()?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let previous = last_record(&mut file)?;
+        let mut record = ArchiveRecord {
+            seq: previous.as_ref().map_or(1, |p| p.seq + 1),
+            timestamp: Utc::now().to_rfc3339(),
+            session: crate::command_audit::current_session(),
+            model: model.to_string(),
+            system: system.to_string(),
+            prompt: prompt.to_string(),
+            response: response.to_string(),
+            redacted: Vec::new(),
+            prev_hash: previous.map_or_else(|| GENESIS_HASH.to_string(), |p| p.hash),
+            hash: String::new(),
+        };
+        self.redact(&mut record);
+        record.hash = record.digest();
+
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(record)
+    }
+
+    /// Lock shared with other `bro` processes, on a file beside the archive
+    /// because pruning replaces the archive itself
+    fn lock(&self) -> Result<FileLock> {
+        FileLock::exclusive(&self.path.with_extension("jsonl.lock"))
+    }
+
+    fn redact(&self, record: &mut ArchiveRecord) {
+        for rule in &self.redactions {
+            let value = record.field_mut(rule.field);
+            let redacted = match &rule.pattern {
+                Some(pattern) => pattern
+                    .replace_all(value, rule.replacement.as_str())
+                    .into_owned(),
+                None if value.is_empty() => continue,
+                None => rule.replacement.clone(),
+            };
+            if redacted != *value {
+                *value = redacted;
+                if !record.redacted.contains(&rule.field) {
+                    record.redacted.push(rule.field);
+                }
+            }
+        }
+    }
+
+    /// Every record, oldest first; unreadable lines are skipped
+    pub fn records(&self) -> Vec<ArchiveRecord> {
+        std::fs::read_to_string(&self.path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// Drop the records older than the retention window, returning how many
+    pub fn prune(&self) -> Result<usize> {
+        let Some(retention) = self.retention else {
+            return Ok(0);
+        };
+        let _guard = APPEND.lock().unwrap_or_else(|e| e.into_inner());
+        // Held until the rename, so no other process appends in between
+        let _lock = self.lock()?;
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let cutoff = Utc::now() - retention;
+        let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+        // Only a prefix goes, so what is left still chains
+        let expired = lines
+            .iter()
+            .take_while(|line| {
+                serde_json::from_str::<ArchiveRecord>(line)
+                    .ok()
+                    .and_then(|record| DateTime::parse_from_rfc3339(&record.timestamp).ok())
+                    .is_some_and(|at| at < cutoff)
+            })
+            .count();
+        if expired == 0 {
+            return Ok(0);
+        }
+
+        let kept: String = lines[expired..]
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let temporary = self.path.with_extension("jsonl.tmp");
+        std::fs::write(&temporary, kept)?;
+        std::fs::rename(&temporary, &self.path)?;
+        Ok(expired)
+    }
+
+    /// Check hashes, links and sequence numbers from the first record on.
+    /// Returns the number of records when the chain is intact.
+    pub fn verify(&self) -> Result<std::result::Result<usize, ChainBreak>> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Ok(0)),
+            Err(e) => return Err(e.into()),
+        };
+        let mut previous: Option<ArchiveRecord> = None;
+        let mut count = 0;
+        for (i, line) in content.lines().enumerate() {
+            let broken = |reason: String| {
+                Ok(Err(ChainBreak {
+                    line: i + 1,
+                    reason,
+                }))
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: ArchiveRecord = match serde_json::from_str(line) {
+                Ok(record) => record,
+                Err(e) => return broken(format!("not a valid record ({})", e)),
+            };
+            if let Some(previous) = &previous {
+                if record.seq != previous.seq + 1 {
+                    return broken(format!(
+                        "sequence number {} where {} was expected; records were removed or reordered",
+                        record.seq,
+                        previous.seq + 1
+                    ));
+                }
+                if record.prev_hash != previous.hash {
+                    return broken("does not link to the record before it".to_string());
+                }
+            }
+            if record.digest() != record.hash {
+                return broken("contents do not match its hash; the record was edited".to_string());
+            }
+            previous = Some(record);
+            count += 1;
+        }
+        Ok(Ok(count))
+    }
+
+    /// Write the records since `since` to `out` as one JSON document for an
+    /// auditor, after checking the chain. Returns the number exported.
+    pub fn export(&self, out: &Path, since: Option<DateTime<Utc>>) -> Result<usize> {
+        if let Err(broken) = self.verify()? {
+            return Err(anyhow::anyhow!(
+                "The prompt archive {} has been tampered with at {}; not exporting",
+                self.path.display(),
+                broken
+            ));
+        }
+        let records: Vec<ArchiveRecord> = self
+            .records()
+            .into_iter()
+            .filter(|record| {
+                since.map_or(true, |since| {
+                    DateTime::parse_from_rfc3339(&record.timestamp).is_ok_and(|at| at >= since)
+                })
+            })
+            .collect();
+        let document = serde_json::json!({
+            "exported_at": Utc::now().to_rfc3339(),
+            "archive": self.path.display().to_string(),
+            "since": since.map(|since| since.to_rfc3339()),
+            "retention_days": self.retention.map(|retention| retention.num_days()),
+            "records": records,
+        });
+        std::fs::write(out, serde_json::to_string_pretty(&document)?)?;
+        Ok(records.len())
+    }
+}
+
+/// Last complete record, read from the end of the file
+fn last_record(file: &mut std::fs::File) -> Result<Option<ArchiveRecord>> {
+    let len = file.metadata()?.len();
+    let mut window = TAIL_BYTES;
+    loop {
+        let start = len.saturating_sub(window);
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail)?;
+        let tail = String::from_utf8_lossy(&tail);
+        let tail = tail.trim_end();
+        let line = match tail.rfind('\n') {
+            Some(newline) => &tail[newline + 1..],
+            None if start == 0 => tail,
+            None => {
+                window *= 2;
+                continue;
+            }
+        };
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        return serde_json::from_str(line).map(Some).map_err(|e| {
+            anyhow::anyhow!(
+                "the last archive record is unreadable ({}); run --archive verify",
+                e
+            )
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_prunes_and_exports() {
+        let dir = std::env::temp_dir().join(format!("bro-archive-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("prompts.jsonl");
+        let archive = PromptArchive::new(
+            path.clone(),
+            Some(chrono::Duration::days(30)),
+            &[
+                FieldRedaction {
+                    field: ArchiveField::Prompt,
+                    pattern: Some(r"\d{3}-\d{2}-\d{4}".to_string()),
+                    replacement: "[SSN]".to_string(),
+                },
+                FieldRedaction {
+                    field: ArchiveField::System,
+                    pattern: None,
+                    replacement: "[REDACTED]".to_string(),
+                },
+            ],
+        )
+        .unwrap();
+
+        let first = archive
+            .append("qwen", "You are terse.", "patient 123-45-6789", "ok")
+            .unwrap();
+        assert_eq!(first.prompt, "patient [SSN]");
+        assert_eq!(first.system, "[REDACTED]");
+        assert_eq!(
+            first.redacted,
+            vec![ArchiveField::Prompt, ArchiveField::System]
+        );
+        let second = archive
+            .append("qwen", "", "hello", &"x".repeat(100_000))
+            .unwrap();
+        let third = archive.append("qwen", "", "again", "done").unwrap();
+        assert!(second.redacted.is_empty());
+        assert_eq!(
+            (third.seq, third.prev_hash.as_str()),
+            (3, second.hash.as_str())
+        );
+        assert_eq!(archive.verify().unwrap(), Ok(3));
+
+        // Age the first record past the retention window
+        let content = std::fs::read_to_string(&path).unwrap();
+        let aged = content.replacen(
+            &first.timestamp,
+            &(Utc::now() - chrono::Duration::days(31)).to_rfc3339(),
+            1,
+        );
+        std::fs::write(&path, aged).unwrap();
+        assert_eq!(archive.verify().unwrap().unwrap_err().line, 1);
+        let mut aged_first = archive.records()[0].clone();
+        aged_first.hash = aged_first.digest();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+        lines[0] = serde_json::to_string(&aged_first).unwrap();
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        assert_eq!(archive.prune().unwrap(), 1);
+        assert_eq!(archive.verify().unwrap(), Ok(2));
+        assert_eq!(archive.records()[0].seq, 2);
+        // Appends after a prune land in the replaced file and keep the chain
+        let fourth = archive.append("qwen", "", "later", "done").unwrap();
+        assert_eq!(fourth.seq, 4);
+        assert_eq!(archive.verify().unwrap(), Ok(3));
+
+        let out = dir.join("export.json");
+        assert_eq!(archive.export(&out, None).unwrap(), 3);
+        let exported: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(exported["records"][1]["prompt"], "again");
+        assert_eq!(exported["retention_days"], 30);
+        assert_eq!(
+            archive
+                .export(&out, Some(Utc::now() + chrono::Duration::days(1)))
+                .unwrap(),
+            0
+        );
+
+        std::fs::write(
+            &path,
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .replace("again", "edited"),
+        )
+        .unwrap();
+        assert!(archive.export(&out, None).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,178 @@
+//! Prompt templates with user overrides
+//!
+//! Built-in templates are compiled into the binary. Dropping a file with the same
+//! name into `~/.config/bro/prompts/` (e.g. `command.j2`) replaces the built-in
+//! one at startup, so prompts can be tuned without recompiling. Templates use
+//! Jinja syntax via minijinja.
+
+use minijinja::{AutoEscape, Environment};
+use serde::Serialize;
+use shared::platform;
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Prompts that can be overridden by the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptTemplate {
+    /// Natural-language request to a single shell command
+    Command,
+    /// Task to a JSON execution plan
+    Plan,
+    /// First step of incremental agent planning
+    PlanAnalysis,
+    /// Instructions that frame every RAG answer
+    Rag,
+    /// RAG question to alternative search queries
+    QueryExpansion,
+}
+
+impl PromptTemplate {
+    pub const ALL: [PromptTemplate; 5] = [
+        PromptTemplate::Command,
+        PromptTemplate::Plan,
+        PromptTemplate::PlanAnalysis,
+        PromptTemplate::Rag,
+        PromptTemplate::QueryExpansion,
+    ];
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            PromptTemplate::Command => "command.j2",
+            PromptTemplate::Plan => "plan.j2",
+            PromptTemplate::PlanAnalysis => "plan_analysis.j2",
+            PromptTemplate::Rag => "rag.j2",
+            PromptTemplate::QueryExpansion => "query_expansion.j2",
+        }
+    }
+
+    fn builtin(&self) -> &'static str {
+        match self {
+            PromptTemplate::Command => include_str!("prompt_templates/command.j2"),
+            PromptTemplate::Plan => include_str!("prompt_templates/plan.j2"),
+            PromptTemplate::PlanAnalysis => include_str!("prompt_templates/plan_analysis.j2"),
+            PromptTemplate::Rag => include_str!("prompt_templates/rag.j2"),
+            PromptTemplate::QueryExpansion => include_str!("prompt_templates/query_expansion.j2"),
+        }
+    }
+}
+
+pub struct PromptTemplates {
+    env: Environment<'static>,
+    overridden: Vec<PromptTemplate>,
+}
+
+impl PromptTemplates {
+    /// Built-in templates only
+    pub fn builtin() -> Self {
+        let mut env = Environment::new();
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        for template in PromptTemplate::ALL {
+            env.add_template(template.file_name(), template.builtin())
+                .expect("built-in prompt templates must parse");
+        }
+        Self {
+            env,
+            overridden: Vec::new(),
+        }
+    }
+
+    /// Built-in templates, replaced by any valid overrides found in `dir`
+    pub fn with_overrides(dir: &Path) -> Self {
+        let mut templates = Self::builtin();
+        for template in PromptTemplate::ALL {
+            let path = dir.join(template.file_name());
+            let Ok(source) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            match templates
+                .env
+                .add_template_owned(template.file_name(), source)
+            {
+                Ok(()) => templates.overridden.push(template),
+                Err(e) => {
+                    eprintln!("Ignoring invalid prompt template {}: {}", path.display(), e);
+                    // minijinja drops the existing entry when a replacement fails to compile
+                    templates
+                        .env
+                        .add_template(template.file_name(), template.builtin())
+                        .expect("built-in prompt templates must parse");
+                }
+            }
+        }
+        templates
+    }
+
+    /// Process-wide templates loaded from the default override directory
+    pub fn global() -> &'static PromptTemplates {
+        static GLOBAL: OnceLock<PromptTemplates> = OnceLock::new();
+        GLOBAL.get_or_init(|| Self::with_overrides(&Self::override_dir()))
+    }
+
+    /// Directory searched for user templates: `~/.config/bro/prompts`
+    pub fn override_dir() -> PathBuf {
+        platform::config_home().join("bro").join("prompts")
+    }
+
+    pub fn is_overridden(&self, template: PromptTemplate) -> bool {
+        self.overridden.contains(&template)
+    }
+
+    pub fn render<S: Serialize>(&self, template: PromptTemplate, context: S) -> Result<String> {
+        let rendered = self
+            .env
+            .get_template(template.file_name())?
+            .render(context)?;
+        Ok(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minijinja::context;
+
+    #[test]
+    fn test_builtin_templates_render() {
+        let templates = PromptTemplates::builtin();
+        let prompt = templates
+            .render(
+                PromptTemplate::Command,
+                context! {
+                    request => "list files",
+                    distro => "Arch Linux",
+                    package_manager => "pacman",
+                    services => "",
+                    directory => "Cargo.toml",
+                },
+            )
+            .unwrap();
+
+        assert!(prompt.contains("REQUEST: list files"));
+        assert!(prompt.contains("\n\nCURRENT DIRECTORY:\nCargo.toml\nCOMMAND GENERATION RULES:"));
+        assert!(!prompt.contains("AVAILABLE SERVICES:\n"));
+        assert!(prompt.ends_with("OUTPUT ONLY THE COMMAND:"));
+    }
+
+    #[test]
+    fn test_override_replaces_builtin_and_invalid_falls_back() {
+        let dir = std::env::temp_dir().join(format!("bro-prompts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("rag.j2"), "Custom: {{ 1 + 1 }}").unwrap();
+        std::fs::write(dir.join("command.j2"), "{% if %}").unwrap();
+
+        let templates = PromptTemplates::with_overrides(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            templates.render(PromptTemplate::Rag, ()).unwrap(),
+            "Custom: 2"
+        );
+        assert!(templates.is_overridden(PromptTemplate::Rag));
+        assert!(!templates.is_overridden(PromptTemplate::Command));
+        assert!(templates
+            .render(PromptTemplate::Command, context! { request => "x" })
+            .unwrap()
+            .contains("REQUEST: x"));
+    }
+}
@@ -0,0 +1,318 @@
+//! Where indexed code comes from and under which license
+//!
+//! Every indexed chunk carries its file's license, detected from an
+//! `SPDX-License-Identifier` or a well-known license header, else from the
+//! nearest `LICENSE`/`COPYING` file above it, and its origin: the repository,
+//! or the vendored package for code under `vendor/`, `third_party/` and the
+//! like. The `context_policy` in the config can then keep code under some
+//! licenses, or vendored code, out of generation prompts:
+//!
+//! ```yaml
+//! context_policy:
+//!   exclude_licenses: ["GPL-*", "AGPL-*"]
+//!   exclude_vendored: true
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Directories whose subdirectories hold code copied from elsewhere
+const VENDOR_DIRS: &[&str] = &[
+    "vendor",
+    "vendored",
+    "third_party",
+    "third-party",
+    "thirdparty",
+    "external",
+    "node_modules",
+];
+
+/// License files looked for in each directory, most specific first
+const LICENSE_FILES: &[&str] = &[
+    "LICENSE",
+    "LICENSE.md",
+    "LICENSE.txt",
+    "LICENCE",
+    "COPYING",
+    "COPYING.md",
+];
+
+/// Lines of a file searched for a license header
+const HEADER_LINES: usize = 40;
+
+/// License and origin of an indexed chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// SPDX identifier, when one could be determined
+    pub license: Option<String>,
+    /// Repository name, or the vendored package directory
+    pub origin: String,
+    pub vendored: bool,
+}
+
+impl Provenance {
+    /// Header lines stored ahead of the chunk text
+    pub fn header(&self) -> String {
+        let mut header = String::new();
+        if let Some(license) = &self.license {
+            header.push_str(&format!("LICENSE: {}\n", license));
+        }
+        header.push_str(&format!("ORIGIN: {}\n", self.origin));
+        if self.vendored {
+            header.push_str("VENDORED: true\n");
+        }
+        header
+    }
+
+    /// Provenance from the headers of a stored chunk; None for chunks indexed
+    /// before provenance was recorded
+    pub fn from_chunk(text: &str) -> Option<Self> {
+        let mut license = None;
+        let mut origin = None;
+        let mut vendored = false;
+        // Headers are the leading `KEY: value` lines
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once(": ") else {
+                break;
+            };
+            match key {
+                "LICENSE" => license = Some(value.trim().to_string()),
+                "ORIGIN" => origin = Some(value.trim().to_string()),
+                "VENDORED" => vendored = value.trim() == "true",
+                "REPO" | "FILE" | "OFFSET" | "LINES" | "SYMBOL" => {}
+                _ => break,
+            }
+        }
+        Some(Self {
+            license,
+            origin: origin?,
+            vendored,
+        })
+    }
+}
+
+/// The SPDX identifier declared or recognisable in `text`
+pub fn detect_license(text: &str) -> Option<String> {
+    let head: String = text
+        .lines()
+        .take(HEADER_LINES)
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Some(position) = head.find("SPDX-License-Identifier:") {
+        let rest = &head[position + "SPDX-License-Identifier:".len()..];
+        let identifier = rest
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .trim_end_matches("*/")
+            .trim();
+        if !identifier.is_empty() {
+            return Some(identifier.to_string());
+        }
+    }
+
+    let head = head.to_lowercase();
+    let gpl_version = if head.contains("version 3") {
+        "3.0"
+    } else {
+        "2.0"
+    };
+    let license = if head.contains("gnu affero general public license") {
+        format!("AGPL-{}", gpl_version)
+    } else if head.contains("gnu lesser general public license") {
+        if gpl_version == "3.0" {
+            "LGPL-3.0".to_string()
+        } else {
+            "LGPL-2.1".to_string()
+        }
+    } else if head.contains("gnu general public license") {
+        format!("GPL-{}", gpl_version)
+    } else if head.contains("apache license") && head.contains("version 2.0") {
+        "Apache-2.0".to_string()
+    } else if head.contains("mozilla public license") {
+        "MPL-2.0".to_string()
+    } else if head.contains("mit license")
+        || head.contains("permission is hereby granted, free of charge")
+    {
+        "MIT".to_string()
+    } else if head.contains("redistribution and use in source and binary forms") {
+        if head.contains("neither the name") {
+            "BSD-3-Clause".to_string()
+        } else {
+            "BSD-2-Clause".to_string()
+        }
+    } else if head.contains("this is free and unencumbered software") {
+        "Unlicense".to_string()
+    } else {
+        return None;
+    };
+    Some(license)
+}
+
+/// The vendored package directory `relative` is in, e.g. `vendor/zlib`
+pub fn vendored_package(relative: &Path) -> Option<PathBuf> {
+    let components: Vec<_> = relative.components().collect();
+    let at = components.iter().position(|component| {
+        VENDOR_DIRS.contains(&component.as_os_str().to_string_lossy().as_ref())
+    })?;
+    // The package directory itself, when the file is inside one
+    let package = components.get(at + 1).filter(|_| at + 2 < components.len());
+    Some(
+        components[..=at]
+            .iter()
+            .chain(package)
+            .map(|component| component.as_os_str())
+            .collect(),
+    )
+}
+
+/// Finds licenses for files of one repository, remembering the license file
+/// found for each directory
+pub struct LicenseResolver<F: Fn(&Path) -> Option<String>> {
+    root: PathBuf,
+    read: F,
+    by_dir: HashMap<PathBuf, Option<String>>,
+}
+
+impl<F: Fn(&Path) -> Option<String>> LicenseResolver<F> {
+    /// Resolver for the repository at `root`; `read` returns the content of a
+    /// path relative to the root
+    pub fn new(root: impl Into<PathBuf>, read: F) -> Self {
+        Self {
+            root: root.into(),
+            read,
+            by_dir: HashMap::new(),
+        }
+    }
+
+    /// Provenance of the file at `path` whose content starts with `head`
+    pub fn provenance(&mut self, path: &Path, head: &str, repo: &str) -> Provenance {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path).to_path_buf();
+        let package = vendored_package(&relative);
+        let license = detect_license(head).or_else(|| self.license_above(&relative));
+        Provenance {
+            license,
+            origin: package.as_ref().map_or(repo.to_string(), |package| {
+                package.to_string_lossy().replace('\\', "/")
+            }),
+            vendored: package.is_some(),
+        }
+    }
+
+    /// License of the nearest license file in a directory containing `relative`
+    fn license_above(&mut self, relative: &Path) -> Option<String> {
+        for dir in relative.ancestors().skip(1) {
+            if let Some(license) = self.license_in(dir) {
+                return Some(license);
+            }
+        }
+        None
+    }
+
+    fn license_in(&mut self, dir: &Path) -> Option<String> {
+        if let Some(cached) = self.by_dir.get(dir) {
+            return cached.clone();
+        }
+        let license = LICENSE_FILES
+            .iter()
+            .find_map(|name| (self.read)(&dir.join(name)))
+            .and_then(|text| detect_license(&text));
+        self.by_dir.insert(dir.to_path_buf(), license.clone());
+        license
+    }
+}
+
+/// Which indexed code may be used as generation context, `context_policy`
+/// in the config
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextPolicy {
+    /// SPDX identifiers to leave out; a trailing `*` matches any suffix
+    #[serde(default)]
+    pub exclude_licenses: Vec<String>,
+    /// Leave out code vendored from third parties
+    #[serde(default)]
+    pub exclude_vendored: bool,
+}
+
+impl ContextPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.exclude_licenses.is_empty() && !self.exclude_vendored
+    }
+
+    /// Why a chunk with `provenance` may not be used, if it may not
+    pub fn exclusion(&self, provenance: &Provenance) -> Option<String> {
+        if self.exclude_vendored && provenance.vendored {
+            return Some(format!("vendored ({})", provenance.origin));
+        }
+        let license = provenance.license.as_deref()?;
+        self.exclude_licenses
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => license.to_lowercase().starts_with(&prefix.to_lowercase()),
+                None => license.eq_ignore_ascii_case(pattern),
+            })
+            .then(|| format!("licensed {}", license))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_license_and_origin_of_vendored_and_own_files() {
+        assert_eq!(
+            detect_license("// SPDX-License-Identifier: Apache-2.0 OR MIT\nfn main() {}"),
+            Some("Apache-2.0 OR MIT".to_string())
+        );
+        assert_eq!(
+            detect_license("/* This program is free software; you can redistribute it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License */"),
+            Some("GPL-3.0".to_string())
+        );
+        assert_eq!(detect_license("fn main() {}"), None);
+
+        let files: HashMap<PathBuf, &str> = [
+            (
+                PathBuf::from("LICENSE"),
+                "MIT License\n\nCopyright (c) 2024",
+            ),
+            (
+                PathBuf::from("vendor/zlib/LICENSE"),
+                "GNU GENERAL PUBLIC LICENSE\nVersion 2",
+            ),
+        ]
+        .into();
+        let mut resolver = LicenseResolver::new("/repo", |path: &Path| {
+            files.get(path).map(|text| text.to_string())
+        });
+
+        let own = resolver.provenance(Path::new("/repo/src/main.rs"), "fn main() {}", "bro");
+        assert_eq!(own.license.as_deref(), Some("MIT"));
+        assert_eq!(own.origin, "bro");
+        let vendored = resolver.provenance(Path::new("/repo/vendor/zlib/src/inflate.c"), "", "bro");
+        assert_eq!(vendored.license.as_deref(), Some("GPL-2.0"));
+        assert_eq!(vendored.origin, "vendor/zlib");
+
+        let chunk = format!(
+            "FILE: vendor/zlib/src/inflate.c\nOFFSET: 0\n{}int x;",
+            vendored.header()
+        );
+        assert_eq!(Provenance::from_chunk(&chunk), Some(vendored.clone()));
+        assert_eq!(
+            Provenance::from_chunk("FILE: a.rs\nOFFSET: 0\nfn a() {}"),
+            None
+        );
+
+        let policy = ContextPolicy {
+            exclude_licenses: vec!["GPL-*".to_string()],
+            exclude_vendored: false,
+        };
+        assert_eq!(
+            policy.exclusion(&vendored).as_deref(),
+            Some("licensed GPL-2.0")
+        );
+        assert_eq!(policy.exclusion(&own), None);
+    }
+}
@@ -0,0 +1,232 @@
+//! Timeouts, cancellation and circuit breaking for Qdrant calls
+//!
+//! A hung Qdrant must never hang indexing or `--rag`. Every call made through
+//! a [`QdrantGuard`] is cut off at the configured timeout or when its
+//! cancellation token fires. Repeated failures trip a breaker shared by all
+//! guards for the same server: while it is open, calls fail at once with
+//! [`QdrantUnavailable`], and callers that keep a local index use SQLite
+//! instead. After the cool-down one call is let through to probe the server.
+
+use serde::{Deserialize, Serialize};
+use shared::cancellation::CancellationToken;
+use shared::types::Result;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Breakers by server URL, so every storage talking to one server trips together
+static BREAKERS: OnceLock<Mutex<HashMap<String, Arc<Mutex<Breaker>>>>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QdrantLimits {
+    /// Longest a single call may take
+    pub timeout_seconds: u64,
+    /// Longest creating, downloading or restoring a snapshot may take
+    pub snapshot_timeout_seconds: u64,
+    /// Consecutive failures after which Qdrant is skipped
+    pub failure_threshold: u32,
+    /// How long Qdrant is skipped before it is tried again
+    pub cooldown_seconds: u64,
+}
+
+impl Default for QdrantLimits {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 10,
+            snapshot_timeout_seconds: 600,
+            failure_threshold: 3,
+            cooldown_seconds: 30,
+        }
+    }
+}
+
+/// Calls to a server are being skipped after repeated failures
+#[derive(Debug, Clone)]
+pub struct QdrantUnavailable {
+    pub url: String,
+    pub failures: u32,
+}
+
+impl Display for QdrantUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Qdrant at {} is unavailable after {} failed calls",
+            self.url, self.failures
+        )
+    }
+}
+
+impl std::error::Error for QdrantUnavailable {}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QdrantGuard {
+    url: String,
+    limits: QdrantLimits,
+    breaker: Arc<Mutex<Breaker>>,
+    cancel: CancellationToken,
+}
+
+impl QdrantGuard {
+    /// Guard for calls to `url`, sharing its breaker with every other guard
+    /// for the same server
+    pub fn for_url(url: &str, limits: QdrantLimits) -> Self {
+        let breakers = BREAKERS.get_or_init(|| Mutex::new(HashMap::new()));
+        let breaker = breakers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(url.to_string())
+            .or_default()
+            .clone();
+        Self {
+            url: url.to_string(),
+            limits,
+            breaker,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Abort calls in flight, and fail later ones, once `cancel` fires
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Whether calls are currently skipped
+    pub fn is_open(&self) -> bool {
+        let breaker = self.breaker.lock().unwrap_or_else(|e| e.into_inner());
+        breaker
+            .open_until
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    pub async fn call<T, E: Display>(
+        &self,
+        call: impl Future<Output = std::result::Result<T, E>>,
+    ) -> Result<T> {
+        self.call_within(Duration::from_secs(self.limits.timeout_seconds), call)
+            .await
+    }
+
+    /// `call` for snapshot transfers, which may take far longer
+    pub async fn call_slow<T, E: Display>(
+        &self,
+        call: impl Future<Output = std::result::Result<T, E>>,
+    ) -> Result<T> {
+        let timeout = Duration::from_secs(self.limits.snapshot_timeout_seconds);
+        self.call_within(timeout, call).await
+    }
+
+    async fn call_within<T, E: Display>(
+        &self,
+        timeout: Duration,
+        call: impl Future<Output = std::result::Result<T, E>>,
+    ) -> Result<T> {
+        if self.is_open() {
+            let failures = self
+                .breaker
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .failures;
+            return Err(QdrantUnavailable {
+                url: self.url.clone(),
+                failures,
+            }
+            .into());
+        }
+        if self.cancel.is_cancelled() {
+            return Err(anyhow::anyhow!("Qdrant call cancelled"));
+        }
+
+        let result = tokio::select! {
+            // A cancelled call says nothing about the server's health
+            _ = self.cancel.cancelled() => return Err(anyhow::anyhow!("Qdrant call cancelled")),
+            result = tokio::time::timeout(timeout, call) => result,
+        };
+        match result {
+            Ok(Ok(value)) => {
+                self.record(true);
+                Ok(value)
+            }
+            Ok(Err(e)) => {
+                self.record(false);
+                Err(anyhow::anyhow!("{}", e))
+            }
+            Err(_) => {
+                self.record(false);
+                Err(anyhow::anyhow!(
+                    "Qdrant at {} did not answer within {}s",
+                    self.url,
+                    timeout.as_secs()
+                ))
+            }
+        }
+    }
+
+    fn record(&self, success: bool) {
+        let mut breaker = self.breaker.lock().unwrap_or_else(|e| e.into_inner());
+        if success {
+            *breaker = Breaker::default();
+            return;
+        }
+        breaker.failures += 1;
+        if breaker.failures >= self.limits.failure_threshold.max(1) {
+            if breaker.open_until.is_none() {
+                eprintln!(
+                    "Warning: Qdrant at {} failed {} times in a row; skipping it for {}s",
+                    self.url, breaker.failures, self.limits.cooldown_seconds
+                );
+            }
+            breaker.open_until =
+                Some(Instant::now() + Duration::from_secs(self.limits.cooldown_seconds));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_breaker_trips_after_repeated_failures() {
+        let limits = QdrantLimits {
+            timeout_seconds: 0,
+            failure_threshold: 2,
+            cooldown_seconds: 60,
+            ..QdrantLimits::default()
+        };
+        let guard = QdrantGuard::for_url("http://guard-test:6334", limits);
+        let hung = std::future::pending::<std::result::Result<(), String>>();
+        assert!(guard
+            .call(hung)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("did not answer"));
+        assert!(!guard.is_open());
+        assert!(guard.call(async { Err::<(), _>("refused") }).await.is_err());
+        assert!(guard.is_open());
+
+        // Other guards for the server skip it too, without making the call
+        let other = QdrantGuard::for_url("http://guard-test:6334", limits);
+        let err = other.call(async { Ok::<_, String>(1) }).await.unwrap_err();
+        assert!(err.downcast_ref::<QdrantUnavailable>().is_some());
+
+        let cancel = CancellationToken::new();
+        let guard = QdrantGuard::for_url("http://guard-other:6334", QdrantLimits::default())
+            .with_cancellation(cancel.clone());
+        cancel.cancel();
+        let hung = std::future::pending::<std::result::Result<(), String>>();
+        assert!(guard.call(hung).await.is_err());
+        assert!(!guard.is_open());
+    }
+}
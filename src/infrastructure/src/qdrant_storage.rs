@@ -1,17 +1,58 @@
-use domain::models::Embedding;
+use crate::embedding_storage::{content_hash, CompactionReport};
+use crate::qdrant_guard::QdrantGuard;
+use crate::schema_migrations::{self, Migration, PayloadMigration, PAYLOAD_VERSION_KEY};
+use domain::models::{CodeSymbol, Embedding};
 use qdrant_client::qdrant::{
-    point_id, value, vectors, vectors_output, CollectionStatus, DeletePointsBuilder, PointId,
-    PointStruct, ScrollPoints, SearchPoints, UpsertPointsBuilder, Value, Vectors,
+    point_id, points_selector::PointsSelectorOneOf, value, vectors, vectors_output,
+    CollectionStatus, Condition, CountPointsBuilder, DeletePointsBuilder, Filter, PointId,
+    PointStruct, PointsIdsList, Range, ScrollPoints, SearchPoints, SetPayloadPointsBuilder,
+    UpsertPointsBuilder, Value, Vectors,
 };
 use qdrant_client::Qdrant;
+use shared::cancellation::CancellationToken;
 use shared::types::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+/// Schema history of point payloads. Points written before versioning have no
+/// `schema_version` and are treated as version 0.
+const PAYLOAD_MIGRATIONS: &[PayloadMigration] = &[Migration {
+    version: 1,
+    description: "text and path keys on every point",
+    apply: |payload| {
+        for key in ["text", "path"] {
+            payload
+                .entry(key.to_string())
+                .or_insert_with(|| Value::from(""));
+        }
+    },
+}];
+
+/// An existing collection stores vectors of a different size than requested
+#[derive(Debug, Clone)]
+pub struct DimensionMismatch {
+    pub collection: String,
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl std::fmt::Display for DimensionMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Vector dimension mismatch in '{}': expected {}, got {}",
+            self.collection, self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for DimensionMismatch {}
+
 /// Qdrant vector storage implementation with full API integration
 #[derive(Clone)]
 pub struct QdrantStorage {
     client: Arc<Qdrant>,
+    guard: QdrantGuard,
     collection_name: String,
     vector_dim: usize,
 }
@@ -23,22 +64,10 @@ impl QdrantStorage {
         collection_name: String,
         vector_dim: usize,
     ) -> Result<Self> {
-        // Create Qdrant client
-        let client = if let Some(url) = qdrant_url {
-            Qdrant::from_url(&url)
-                .build()
-                .map_err(|e| anyhow::anyhow!("Failed to connect to Qdrant at {}: {}", url, e))?
-        } else {
-            // Default to localhost
-            Qdrant::from_url("http://localhost:6334")
-                .build()
-                .map_err(|e| anyhow::anyhow!("Failed to connect to Qdrant at localhost: {}", e))?
-        };
-
-        let client = Arc::new(client);
-
+        let (client, guard) = Self::connect(qdrant_url)?;
         let storage = Self {
-            client: client.clone(),
+            client: Arc::new(client),
+            guard,
             collection_name: collection_name.clone(),
             vector_dim,
         };
@@ -54,13 +83,91 @@ impl QdrantStorage {
         Ok(storage)
     }
 
+    /// Replace the collection with an empty one of `vector_dim` dimensions,
+    /// after taking a server-side snapshot of the old one
+    pub async fn recreate(
+        qdrant_url: Option<String>,
+        collection_name: String,
+        vector_dim: usize,
+    ) -> Result<Self> {
+        let (client, guard) = Self::connect(qdrant_url)?;
+        let storage = Self {
+            client: Arc::new(client),
+            guard,
+            collection_name,
+            vector_dim,
+        };
+        let name = storage.collection_name.as_str();
+
+        let exists = storage
+            .guard
+            .call(storage.client.collection_exists(name))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to check collection '{}': {}", name, e))?;
+        if exists {
+            let snapshot = storage
+                .guard
+                .call_slow(storage.client.create_snapshot(name))
+                .await
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "Refusing to recreate '{}' without a backup; snapshot failed: {}",
+                        name,
+                        e
+                    )
+                })?;
+            eprintln!(
+                "Recreating '{}' with {} dimensions (backup snapshot: {})",
+                name,
+                vector_dim,
+                snapshot
+                    .snapshot_description
+                    .map(|s| s.name)
+                    .unwrap_or_default()
+            );
+            storage
+                .guard
+                .call(storage.client.delete_collection(name))
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to delete collection '{}': {}", name, e))?;
+        }
+        storage.create_collection().await?;
+        Ok(storage)
+    }
+
+    fn connect(qdrant_url: Option<String>) -> Result<(Qdrant, QdrantGuard)> {
+        let url = qdrant_url.unwrap_or_else(|| "http://localhost:6334".to_string());
+        let client = Qdrant::from_url(&url)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to connect to Qdrant at {}: {}", url, e))?;
+        let limits = crate::config::PowerUserConfig::load().qdrant;
+        Ok((client, QdrantGuard::for_url(&url, limits)))
+    }
+
+    /// This storage with calls aborted once `cancel` fires
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.guard = self.guard.with_cancellation(cancel);
+        self
+    }
+
+    /// Whether calls are skipped after repeated failures, so a local index
+    /// should be used instead
+    pub fn is_unavailable(&self) -> bool {
+        self.guard.is_open()
+    }
+
     /// Ensure the collection exists, create it if it doesn't
     async fn ensure_collection(&self) -> Result<()> {
         // Check if collection exists
-        match self.client.collection_info(&self.collection_name).await {
+        match self
+            .guard
+            .call(self.client.collection_info(&self.collection_name))
+            .await
+        {
             Ok(_) => {
-                // Collection exists, verify configuration
+                // Collection exists, verify configuration and upgrade old points
                 self.verify_collection_config().await?;
+                self.migrate_payloads().await?;
                 return Ok(());
             }
             Err(_) => {
@@ -73,20 +180,23 @@ impl QdrantStorage {
 
     /// Create the collection with proper configuration
     async fn create_collection(&self) -> Result<()> {
-        self.client
-            .create_collection(qdrant_client::qdrant::CreateCollection {
-                collection_name: self.collection_name.clone(),
-                vectors_config: Some(qdrant_client::qdrant::VectorsConfig {
-                    config: Some(qdrant_client::qdrant::vectors_config::Config::Params(
-                        qdrant_client::qdrant::VectorParams {
-                            size: self.vector_dim as u64,
-                            distance: qdrant_client::qdrant::Distance::Cosine.into(),
-                            ..Default::default()
-                        },
-                    )),
-                }),
-                ..Default::default()
-            })
+        self.guard
+            .call(
+                self.client
+                    .create_collection(qdrant_client::qdrant::CreateCollection {
+                        collection_name: self.collection_name.clone(),
+                        vectors_config: Some(qdrant_client::qdrant::VectorsConfig {
+                            config: Some(qdrant_client::qdrant::vectors_config::Config::Params(
+                                qdrant_client::qdrant::VectorParams {
+                                    size: self.vector_dim as u64,
+                                    distance: qdrant_client::qdrant::Distance::Cosine.into(),
+                                    ..Default::default()
+                                },
+                            )),
+                        }),
+                        ..Default::default()
+                    }),
+            )
             .await
             .map_err(|e| {
                 anyhow::anyhow!(
@@ -103,8 +213,8 @@ impl QdrantStorage {
     /// Verify collection configuration matches expected parameters
     async fn verify_collection_config(&self) -> Result<()> {
         let info = self
-            .client
-            .collection_info(&self.collection_name)
+            .guard
+            .call(self.client.collection_info(&self.collection_name))
             .await
             .map_err(|e| anyhow::anyhow!("Failed to get collection info: {}", e))?;
 
@@ -116,11 +226,12 @@ impl QdrantStorage {
                         match &vectors_config.config {
                             Some(qdrant_client::qdrant::vectors_config::Config::Params(params)) => {
                                 if params.size != self.vector_dim as u64 {
-                                    return Err(anyhow::anyhow!(
-                                        "Vector dimension mismatch: expected {}, got {}",
-                                        self.vector_dim,
-                                        params.size
-                                    ));
+                                    return Err(DimensionMismatch {
+                                        collection: self.collection_name.clone(),
+                                        expected: self.vector_dim,
+                                        found: params.size as usize,
+                                    }
+                                    .into());
                                 }
                             }
                             _ => return Err(anyhow::anyhow!("Invalid vector configuration")),
@@ -133,6 +244,105 @@ impl QdrantStorage {
         Ok(())
     }
 
+    /// Rewrite payloads of points stored under an older schema, after taking a
+    /// server-side snapshot of the collection
+    async fn migrate_payloads(&self) -> Result<()> {
+        let latest = schema_migrations::latest_version(PAYLOAD_MIGRATIONS);
+        let outdated = Filter::should([
+            Condition::is_empty(PAYLOAD_VERSION_KEY),
+            Condition::range(
+                PAYLOAD_VERSION_KEY,
+                Range {
+                    lt: Some(latest as f64),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let count = self
+            .guard
+            .call(
+                self.client.count(
+                    CountPointsBuilder::new(&self.collection_name)
+                        .filter(outdated.clone())
+                        .exact(true),
+                ),
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to count outdated points: {}", e))?
+            .result
+            .map_or(0, |r| r.count);
+        if count == 0 {
+            return Ok(());
+        }
+
+        let snapshot = self
+            .guard
+            .call_slow(self.client.create_snapshot(self.collection_name.as_str()))
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Refusing to migrate '{}' without a backup; snapshot failed: {}",
+                    self.collection_name,
+                    e
+                )
+            })?;
+        eprintln!(
+            "Migrating {} points in '{}' to payload schema v{} (backup snapshot: {})",
+            count,
+            self.collection_name,
+            latest,
+            snapshot
+                .snapshot_description
+                .map(|s| s.name)
+                .unwrap_or_default()
+        );
+
+        let mut offset = None;
+        loop {
+            let page = self
+                .guard
+                .call(self.client.scroll(ScrollPoints {
+                    collection_name: self.collection_name.clone(),
+                    limit: Some(256),
+                    offset,
+                    filter: Some(outdated.clone()),
+                    with_payload: Some(true.into()),
+                    with_vectors: Some(false.into()),
+                    ..Default::default()
+                }))
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to scroll points in Qdrant: {}", e))?;
+
+            for point in page.result {
+                let (Some(id), mut payload) = (point.id, point.payload) else {
+                    continue;
+                };
+                if !schema_migrations::migrate_payload(&mut payload, PAYLOAD_MIGRATIONS) {
+                    continue;
+                }
+                self.guard
+                    .call(
+                        self.client.overwrite_payload(
+                            SetPayloadPointsBuilder::new(&self.collection_name, payload)
+                                .points_selector(PointsSelectorOneOf::Points(PointsIdsList {
+                                    ids: vec![id],
+                                }))
+                                .wait(true),
+                        ),
+                    )
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to migrate point payload: {}", e))?;
+            }
+
+            if page.next_page_offset.is_none() {
+                break;
+            }
+            offset = page.next_page_offset;
+        }
+        Ok(())
+    }
+
     /// Insert embeddings into Qdrant with batch operations
     pub async fn insert_embeddings(&self, embeddings: Vec<Embedding>) -> Result<()> {
         if embeddings.is_empty() {
@@ -159,12 +369,38 @@ impl QdrantStorage {
                     kind: Some(value::Kind::StringValue(embedding.path.clone())),
                 },
             );
+            if let Some(symbol) = &embedding.symbol {
+                payload.insert(
+                    "symbol_name".to_string(),
+                    Value {
+                        kind: Some(value::Kind::StringValue(symbol.name.clone())),
+                    },
+                );
+                payload.insert(
+                    "symbol_kind".to_string(),
+                    Value {
+                        kind: Some(value::Kind::StringValue(symbol.kind.clone())),
+                    },
+                );
+            }
+            if let Some(commit) = &embedding.commit {
+                payload.insert(
+                    "commit".to_string(),
+                    Value {
+                        kind: Some(value::Kind::StringValue(commit.clone())),
+                    },
+                );
+            }
+            payload.insert(
+                PAYLOAD_VERSION_KEY.to_string(),
+                Value::from(schema_migrations::latest_version(PAYLOAD_MIGRATIONS) as i64),
+            );
 
             let point = PointStruct {
                 id: Some(PointId {
-                    point_id_options: Some(point_id::PointIdOptions::Num(
-                        embedding.id.parse().unwrap_or(0),
-                    )),
+                    point_id_options: Some(point_id::PointIdOptions::Num(point_number(
+                        &embedding.id,
+                    ))),
                 }),
                 vectors: Some(Vectors {
                     vectors_options: Some(vectors::VectorsOptions::Vector(
@@ -182,8 +418,11 @@ impl QdrantStorage {
 
         // Use the new upsert API
         let result = self
-            .client
-            .upsert_points(UpsertPointsBuilder::new(&self.collection_name, points))
+            .guard
+            .call(
+                self.client
+                    .upsert_points(UpsertPointsBuilder::new(&self.collection_name, points)),
+            )
             .await;
 
         match result {
@@ -208,15 +447,15 @@ impl QdrantStorage {
         limit: usize,
     ) -> Result<Vec<Embedding>> {
         let search_result = self
-            .client
-            .search_points(SearchPoints {
+            .guard
+            .call(self.client.search_points(SearchPoints {
                 collection_name: self.collection_name.clone(),
                 vector: query_vector.to_vec(),
                 limit: limit as u64,
                 with_payload: Some(true.into()),
                 with_vectors: Some(true.into()),
                 ..Default::default()
-            })
+            }))
             .await
             .map_err(|e| anyhow::anyhow!("Failed to search points in Qdrant: {}", e))?;
 
@@ -265,6 +504,8 @@ impl QdrantStorage {
                 vector,
                 text,
                 path,
+                symbol: payload_symbol(&point.payload),
+                commit: payload_string(&point.payload, "commit"),
             });
         }
 
@@ -304,8 +545,8 @@ impl QdrantStorage {
         };
 
         let search_result = self
-            .client
-            .search_points(SearchPoints {
+            .guard
+            .call(self.client.search_points(SearchPoints {
                 collection_name: self.collection_name.clone(),
                 vector: query_vector.to_vec(),
                 limit: limit as u64,
@@ -313,7 +554,7 @@ impl QdrantStorage {
                 with_payload: Some(true.into()),
                 with_vectors: Some(true.into()),
                 ..Default::default()
-            })
+            }))
             .await
             .map_err(|e| anyhow::anyhow!("Failed to search points with filter in Qdrant: {}", e))?;
 
@@ -362,6 +603,8 @@ impl QdrantStorage {
                 vector,
                 text,
                 path,
+                symbol: payload_symbol(&point.payload),
+                commit: payload_string(&point.payload, "commit"),
             });
         }
 
@@ -402,8 +645,8 @@ impl QdrantStorage {
         // Use scroll API with filter to get matching points in batches
         loop {
             let scroll_result = self
-                .client
-                .scroll(ScrollPoints {
+                .guard
+                .call(self.client.scroll(ScrollPoints {
                     collection_name: self.collection_name.clone(),
                     limit: Some(limit.min(1000) as u32),
                     offset,
@@ -411,7 +654,7 @@ impl QdrantStorage {
                     with_payload: Some(true.into()),
                     with_vectors: Some(true.into()),
                     ..Default::default()
-                })
+                }))
                 .await
                 .map_err(|e| {
                     anyhow::anyhow!("Failed to scroll points with filter in Qdrant: {}", e)
@@ -463,6 +706,8 @@ impl QdrantStorage {
                         vector,
                         text,
                         path,
+                        symbol: payload_symbol(&point.payload),
+                        commit: payload_string(&point.payload, "commit"),
                     });
                 }
 
@@ -509,15 +754,15 @@ impl QdrantStorage {
         // Use scroll API to get all points in batches
         loop {
             let scroll_result = self
-                .client
-                .scroll(ScrollPoints {
+                .guard
+                .call(self.client.scroll(ScrollPoints {
                     collection_name: self.collection_name.clone(),
                     limit: Some(1000),
                     offset,
                     with_payload: Some(true.into()),
                     with_vectors: Some(true.into()),
                     ..Default::default()
-                })
+                }))
                 .await
                 .map_err(|e| anyhow::anyhow!("Failed to scroll points in Qdrant: {}", e))?;
 
@@ -565,6 +810,8 @@ impl QdrantStorage {
                     vector,
                     text,
                     path,
+                    symbol: payload_symbol(&point.payload),
+                    commit: payload_string(&point.payload, "commit"),
                 });
             }
 
@@ -584,6 +831,49 @@ impl QdrantStorage {
         Ok(all_embeddings)
     }
 
+    /// Delete points for paths not in `live_paths` and all but the first copy
+    /// of identical chunks. Qdrant frees the space when it next optimizes the
+    /// collection, so the reclaimed size is estimated from the removed points.
+    pub async fn compact(&self, live_paths: &HashSet<String>) -> Result<CompactionReport> {
+        let mut report = CompactionReport::default();
+        let mut seen = HashSet::new();
+        let mut doomed = Vec::new();
+        for embedding in self.get_all_embeddings().await? {
+            let stale = !live_paths.contains(&embedding.path);
+            let duplicate = !stale && !seen.insert(content_hash(&embedding.text));
+            if !stale && !duplicate {
+                continue;
+            }
+            if stale {
+                report.stale += 1;
+            } else {
+                report.duplicates += 1;
+            }
+            report.reclaimed_bytes +=
+                (embedding.vector.len() * std::mem::size_of::<f32>() + embedding.text.len()) as u64;
+            let Ok(id) = embedding.id.parse() else {
+                continue;
+            };
+            doomed.push(PointId {
+                point_id_options: Some(point_id::PointIdOptions::Num(id)),
+            });
+        }
+
+        for ids in doomed.chunks(1000) {
+            self.guard
+                .call(
+                    self.client.delete_points(
+                        DeletePointsBuilder::new(&self.collection_name)
+                            .points(PointsIdsList { ids: ids.to_vec() })
+                            .wait(true),
+                    ),
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to delete points from Qdrant: {}", e))?;
+        }
+        Ok(report)
+    }
+
     /// Delete embeddings for a specific path using filter
     pub async fn delete_embeddings_for_path(&self, path: &str) -> Result<()> {
         use qdrant_client::qdrant::{r#match, Condition, FieldCondition, Filter, Match};
@@ -606,8 +896,11 @@ impl QdrantStorage {
 
         // Use delete by filter
         let result = self
-            .client
-            .delete_points(DeletePointsBuilder::new(&self.collection_name).points(filter))
+            .guard
+            .call(
+                self.client
+                    .delete_points(DeletePointsBuilder::new(&self.collection_name).points(filter)),
+            )
             .await;
 
         match result {
@@ -635,7 +928,11 @@ impl QdrantStorage {
         stats.insert("vector_dimension".to_string(), self.vector_dim.to_string());
 
         // Try to get collection info
-        match self.client.collection_info(&self.collection_name).await {
+        match self
+            .guard
+            .call(self.client.collection_info(&self.collection_name))
+            .await
+        {
             Ok(info) => {
                 if let Some(result) = info.result {
                     let point_count = result.points_count.unwrap_or(0);
@@ -673,3 +970,27 @@ impl QdrantStorage {
         Ok(stats)
     }
 }
+
+/// Stable numeric point id for an embedding id such as `src/lib.rs:120`, so
+/// re-indexing unchanged content overwrites its points instead of adding more
+fn point_number(id: &str) -> u64 {
+    id.parse().unwrap_or_else(|_| {
+        let digest = md5::compute(id.as_bytes());
+        u64::from_le_bytes(digest.0[..8].try_into().expect("md5 digest is 16 bytes"))
+    })
+}
+
+/// Symbol metadata stored alongside syntax-aware chunks
+fn payload_symbol(payload: &HashMap<String, Value>) -> Option<CodeSymbol> {
+    Some(CodeSymbol {
+        name: payload_string(payload, "symbol_name")?,
+        kind: payload_string(payload, "symbol_kind")?,
+    })
+}
+
+fn payload_string(payload: &HashMap<String, Value>, key: &str) -> Option<String> {
+    match payload.get(key)?.kind.as_ref()? {
+        value::Kind::StringValue(s) => Some(s.clone()),
+        _ => None,
+    }
+}
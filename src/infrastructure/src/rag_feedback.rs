@@ -0,0 +1,197 @@
+//! Corrections learned from RAG feedback
+//!
+//! When an answer is rejected with feedback and a later one accepted, the
+//! question, the rejected answer, the feedback and the accepted answer are
+//! kept in `.bro/rag-feedback.json` of the project. Questions similar to an
+//! earlier one get its corrections as examples in the prompt, so the same
+//! mistake is not made twice.
+
+use crate::search::SearchEngine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+
+pub const FEEDBACK_FILE: &str = "rag-feedback.json";
+
+/// Corrections kept per project; the oldest are dropped beyond this
+const MAX_CORRECTIONS: usize = 200;
+
+/// Similarity a question needs to an earlier one for its correction to apply
+const MIN_SIMILARITY: f32 = 0.8;
+
+/// Longest answer kept in a prompt example
+const MAX_EXAMPLE_CHARS: usize = 1500;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RagCorrection {
+    pub question: String,
+    pub rejected_answer: String,
+    pub feedback: String,
+    pub accepted_answer: String,
+    pub recorded_at: DateTime<Utc>,
+    /// Embedding of the question, compared with new questions
+    pub embedding: Vec<f32>,
+}
+
+impl RagCorrection {
+    /// Short description for the prompt context report
+    pub fn label(&self) -> String {
+        let question: String = self.question.chars().take(40).collect();
+        let ellipsis = if question.len() < self.question.len() {
+            "..."
+        } else {
+            ""
+        };
+        format!(
+            "correction of \"{}{}\" ({})",
+            question,
+            ellipsis,
+            self.recorded_at.format("%Y-%m-%d")
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RagFeedbackStore {
+    path: PathBuf,
+}
+
+impl RagFeedbackStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Corrections of the project at `root`
+    pub fn for_project(root: &Path) -> Self {
+        Self::new(root.join(".bro").join(FEEDBACK_FILE))
+    }
+
+    pub fn list(&self) -> Vec<RagCorrection> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.path.exists() || self.list().is_empty()
+    }
+
+    pub fn record(&self, correction: RagCorrection) -> Result<()> {
+        let mut corrections = self.list();
+        corrections.push(correction);
+        let excess = corrections.len().saturating_sub(MAX_CORRECTIONS);
+        corrections.drain(..excess);
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&corrections)?)?;
+        std::fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Up to `limit` corrections for questions like the one embedded as
+    /// `embedding`, most similar first
+    pub fn similar(&self, embedding: &[f32], limit: usize) -> Vec<RagCorrection> {
+        let mut scored: Vec<(f32, RagCorrection)> = self
+            .list()
+            .into_iter()
+            .filter(|correction| correction.embedding.len() == embedding.len())
+            .map(|correction| {
+                (
+                    SearchEngine::cosine_similarity(&correction.embedding, embedding),
+                    correction,
+                )
+            })
+            .filter(|(score, _)| *score >= MIN_SIMILARITY)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(_, correction)| correction)
+            .collect()
+    }
+}
+
+/// Prompt section presenting `corrections` as examples to follow
+pub fn format_corrections(corrections: &[RagCorrection]) -> String {
+    let mut section =
+        String::from("Earlier answers to similar questions in this project were corrected:\n");
+    for correction in corrections {
+        section.push_str(&format!(
+            "\nQuestion: {}\nRejected answer: {}\nUser feedback: {}\nAccepted answer: {}\n",
+            correction.question,
+            truncate(&correction.rejected_answer),
+            correction.feedback,
+            truncate(&correction.accepted_answer)
+        ));
+    }
+    section.push_str("\nApply the same corrections where they are relevant.");
+    section
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_EXAMPLE_CHARS) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn correction(question: &str, embedding: Vec<f32>) -> RagCorrection {
+        RagCorrection {
+            question: question.to_string(),
+            rejected_answer: "It uses Redis".to_string(),
+            feedback: "We use sled, not Redis".to_string(),
+            accepted_answer: "Sessions are stored in sled".to_string(),
+            recorded_at: Utc::now(),
+            embedding,
+        }
+    }
+
+    #[test]
+    fn test_similar_questions_get_their_corrections() {
+        let dir = std::env::temp_dir().join(format!("rag-feedback-{}", uuid::Uuid::new_v4()));
+        let store = RagFeedbackStore::for_project(&dir);
+        assert!(store.is_empty());
+        store
+            .record(correction("Where are sessions stored?", vec![1.0, 0.0]))
+            .unwrap();
+        store
+            .record(correction("How is auth done?", vec![0.0, 1.0]))
+            .unwrap();
+
+        let found = store.similar(&[0.95, 0.1], 3);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].question, "Where are sessions stored?");
+        assert!(store.similar(&[0.7, 0.7, 0.0], 3).is_empty());
+
+        let section = format_corrections(&found);
+        assert!(section.contains("User feedback: We use sled, not Redis"));
+        assert!(found[0]
+            .label()
+            .starts_with("correction of \"Where are sessions stored?\" ("));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrections_stay_in_their_project() {
+        let dir = std::env::temp_dir().join(format!("rag-feedback-{}", uuid::Uuid::new_v4()));
+        let here = RagFeedbackStore::for_project(&dir.join("here"));
+        let elsewhere = RagFeedbackStore::for_project(&dir.join("elsewhere"));
+        here.record(correction("Where are sessions stored?", vec![1.0, 0.0]))
+            .unwrap();
+
+        assert_eq!(here.similar(&[1.0, 0.0], 3).len(), 1);
+        assert!(elsewhere.is_empty());
+        assert!(elsewhere.similar(&[1.0, 0.0], 3).is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
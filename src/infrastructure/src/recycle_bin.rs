@@ -0,0 +1,264 @@
+//! Recoverable deletions
+//!
+//! Files a build deletes are moved to `<project>/.bro/trash/<timestamp>/`
+//! under their path relative to the project, one timestamped batch per build,
+//! instead of being removed. `bro --trash` lists the batches and
+//! `bro --trash restore` moves files back where they were.
+
+use chrono::Local;
+use shared::types::Result;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Location of the trash relative to the project root
+pub const TRASH_DIR: &str = ".bro/trash";
+
+/// Files deleted together, kept under one timestamped directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashBatch {
+    /// Directory name, e.g. `20261017-142501`
+    pub id: String,
+    /// Paths relative to the project root
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct RecycleBin {
+    root: PathBuf,
+    /// Batch this instance moves files into, created on first use
+    batch: OnceLock<String>,
+}
+
+impl RecycleBin {
+    /// Trash of the project at `root`
+    pub fn for_project(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            batch: OnceLock::new(),
+        }
+    }
+
+    /// Trash of the project containing the working directory
+    pub fn for_current_project() -> Self {
+        let root = crate::config::find_project_root()
+            .map(PathBuf::from)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        Self::for_project(&root)
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.root.join(TRASH_DIR)
+    }
+
+    /// Move `path` into this instance's batch; returns where it went
+    pub fn trash(&self, path: &Path) -> Result<PathBuf> {
+        let relative = self.relative(path)?;
+        let batch = self.batch.get_or_init(|| self.new_batch_id());
+        let destination = self.dir().join(batch).join(&relative);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        move_file(path, &destination)?;
+        Ok(destination)
+    }
+
+    /// Every batch, oldest first
+    pub fn batches(&self) -> Vec<TrashBatch> {
+        let Ok(entries) = std::fs::read_dir(self.dir()) else {
+            return Vec::new();
+        };
+        let mut batches: Vec<TrashBatch> = entries
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| {
+                let dir = entry.path();
+                let mut files = Vec::new();
+                collect_files(&dir, &dir, &mut files);
+                files.sort();
+                TrashBatch {
+                    id: entry.file_name().to_string_lossy().to_string(),
+                    files,
+                }
+            })
+            .filter(|batch| !batch.files.is_empty())
+            .collect();
+        batches.sort_by(|a, b| a.id.cmp(&b.id));
+        batches
+    }
+
+    /// Move the files of batch `id` (the newest when None) back to where
+    /// they were deleted from, or only `only` when given. Files that have
+    /// been recreated since are left in the trash and reported as an error.
+    pub fn restore(&self, id: Option<&str>, only: Option<&Path>) -> Result<Vec<PathBuf>> {
+        let batches = self.batches();
+        let batch = match id {
+            Some(id) => batches.iter().find(|batch| batch.id == id),
+            None => batches.last(),
+        }
+        .ok_or_else(|| match id {
+            Some(id) => anyhow::anyhow!("No trash batch '{}' in {}", id, self.dir().display()),
+            None => anyhow::anyhow!("The trash in {} is empty", self.dir().display()),
+        })?;
+
+        let only = only.map(|path| self.relative(path)).transpose()?;
+        let selected: Vec<&PathBuf> = batch
+            .files
+            .iter()
+            .filter(|file| only.as_ref().map_or(true, |only| file.starts_with(only)))
+            .collect();
+        if selected.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Batch {} has no file {}",
+                batch.id,
+                only.unwrap_or_default().display()
+            ));
+        }
+
+        let batch_dir = self.dir().join(&batch.id);
+        let mut restored = Vec::new();
+        let mut conflicts = Vec::new();
+        for file in selected {
+            let target = self.root.join(file);
+            if target.exists() {
+                conflicts.push(file.display().to_string());
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            move_file(&batch_dir.join(file), &target)?;
+            remove_empty_dirs(batch_dir.join(file).parent(), &self.dir());
+            restored.push(target);
+        }
+        if !conflicts.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Restored {} file(s), but {} exist again and stayed in the trash: {}",
+                restored.len(),
+                conflicts.len(),
+                conflicts.join(", ")
+            ));
+        }
+        Ok(restored)
+    }
+
+    /// `path` relative to the project root, refusing paths outside it
+    fn relative(&self, path: &Path) -> Result<PathBuf> {
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()?.join(path)
+        };
+        let relative = absolute
+            .strip_prefix(&self.root)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "{} is outside the project at {}",
+                    path.display(),
+                    self.root.display()
+                )
+            })?
+            .to_path_buf();
+        if relative
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(anyhow::anyhow!(
+                "{} is not a plain project path",
+                path.display()
+            ));
+        }
+        Ok(relative)
+    }
+
+    fn new_batch_id(&self) -> String {
+        let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        (0..)
+            .map(|n| match n {
+                0 => stamp.clone(),
+                n => format!("{}-{}", stamp, n),
+            })
+            .find(|id| !self.dir().join(id).exists())
+            .unwrap_or(stamp)
+    }
+}
+
+/// Rename, or copy and remove when `to` is on another filesystem
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn collect_files(base: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(base, &path, files);
+        } else if let Ok(relative) = path.strip_prefix(base) {
+            files.push(relative.to_path_buf());
+        }
+    }
+}
+
+/// Remove `dir` and its parents while they are empty, stopping at `stop`
+fn remove_empty_dirs(dir: Option<&Path>, stop: &Path) {
+    let mut current = dir;
+    while let Some(dir) = current {
+        if dir == stop || std::fs::remove_dir(dir).is_err() {
+            return;
+        }
+        current = dir.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deleted_files_keep_their_structure_and_come_back() {
+        let root = std::env::temp_dir().join(format!("bro-trash-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src/parser")).unwrap();
+        std::fs::write(root.join("src/parser/lexer.rs"), "lexer").unwrap();
+        std::fs::write(root.join("README.md"), "readme").unwrap();
+
+        let bin = RecycleBin::for_project(&root);
+        bin.trash(&root.join("src/parser/lexer.rs")).unwrap();
+        bin.trash(&root.join("README.md")).unwrap();
+        assert!(!root.join("README.md").exists());
+        assert!(bin.trash(Path::new("/etc/hosts")).is_err());
+
+        let batches = bin.batches();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(
+            batches[0].files,
+            [
+                PathBuf::from("README.md"),
+                PathBuf::from("src/parser/lexer.rs")
+            ]
+        );
+
+        std::fs::write(root.join("README.md"), "new readme").unwrap();
+        let conflict = bin.restore(None, None).unwrap_err();
+        assert!(conflict.to_string().contains("README.md"));
+        assert_eq!(
+            std::fs::read_to_string(root.join("src/parser/lexer.rs")).unwrap(),
+            "lexer"
+        );
+        assert_eq!(bin.batches()[0].files, [PathBuf::from("README.md")]);
+
+        std::fs::remove_file(root.join("README.md")).unwrap();
+        let restored = bin
+            .restore(Some(&batches[0].id), Some(&root.join("README.md")))
+            .unwrap();
+        assert_eq!(restored, [root.join("README.md")]);
+        assert!(bin.batches().is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
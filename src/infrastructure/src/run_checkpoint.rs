@@ -0,0 +1,199 @@
+//! Progress of `--run` and `--ai-agent` executions, kept to resume them
+//!
+//! A run saves its checkpoint to the session after every completed plan step,
+//! agent iteration or sub-agent. Finishing clears it; a failure, an exhausted budget or
+//! Ctrl+C records why it stopped, and a crash leaves the last one saved.
+//! `bro --resume` picks the run up after its last completed step.
+
+use chrono::{DateTime, Utc};
+use domain::models::ToolResult;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunKind {
+    /// A `--run` command plan
+    Run,
+    /// An `--ai-agent` loop
+    AiAgent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunCheckpoint {
+    pub kind: RunKind,
+    pub goal: String,
+    /// The `--run` plan as generated
+    #[serde(default)]
+    pub plan: Option<serde_json::Value>,
+    /// Ids of the plan steps that finished
+    #[serde(default)]
+    pub completed_steps: Vec<String>,
+    /// Agent iterations that finished
+    #[serde(default)]
+    pub completed_iterations: usize,
+    /// The `--ai-agent` run splits its goal among sub-agents
+    #[serde(default)]
+    pub sub_agents: bool,
+    /// Reports of the sub-agents that finished, in the order they ran
+    #[serde(default)]
+    pub subtask_reports: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub reasoning: Vec<String>,
+    #[serde(default)]
+    pub tool_results: Vec<ToolResult>,
+    /// Why the run stopped; None when it was cut off without saying
+    #[serde(default)]
+    pub stopped: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RunCheckpoint {
+    pub fn new(kind: RunKind, goal: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            kind,
+            goal: goal.to_string(),
+            plan: None,
+            completed_steps: Vec::new(),
+            completed_iterations: 0,
+            sub_agents: false,
+            subtask_reports: Vec::new(),
+            reasoning: Vec::new(),
+            tool_results: Vec::new(),
+            stopped: None,
+            started_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn with_plan(mut self, plan: serde_json::Value) -> Self {
+        self.plan = Some(plan);
+        self
+    }
+
+    pub fn with_sub_agents(mut self) -> Self {
+        self.sub_agents = true;
+        self
+    }
+
+    pub fn is_step_done(&self, id: &str) -> bool {
+        self.completed_steps.iter().any(|done| done == id)
+    }
+
+    pub fn complete_step(&mut self, id: &str) {
+        if !self.is_step_done(id) {
+            self.completed_steps.push(id.to_string());
+        }
+        self.updated_at = Utc::now();
+    }
+
+    /// Record one finished agent iteration
+    pub fn complete_iteration(&mut self, reasoning: &[String], tool_results: &[ToolResult]) {
+        self.completed_iterations += 1;
+        self.reasoning.extend_from_slice(reasoning);
+        self.tool_results.extend_from_slice(tool_results);
+        self.updated_at = Utc::now();
+    }
+
+    /// Record one finished sub-agent
+    pub fn complete_subtask(&mut self, report: serde_json::Value, tool_results: &[ToolResult]) {
+        self.subtask_reports.push(report);
+        self.tool_results.extend_from_slice(tool_results);
+        self.updated_at = Utc::now();
+    }
+
+    pub fn stop(&mut self, reason: impl Into<String>) {
+        self.stopped = Some(reason.into());
+        self.updated_at = Utc::now();
+    }
+
+    /// One line saying what is resumed and why it stopped
+    pub fn summary(&self) -> String {
+        let progress = match self.kind {
+            RunKind::Run => format!("{} step(s) done", self.completed_steps.len()),
+            RunKind::AiAgent if self.sub_agents => {
+                format!("{} subtask(s) done", self.subtask_reports.len())
+            }
+            RunKind::AiAgent => format!("{} iteration(s) done", self.completed_iterations),
+        };
+        format!(
+            "{} \"{}\": {}, stopped {} ({})",
+            match self.kind {
+                RunKind::Run => "--run",
+                RunKind::AiAgent => "--ai-agent",
+            },
+            self.goal,
+            progress,
+            self.updated_at.format("%Y-%m-%d %H:%M:%S"),
+            self.stopped.as_deref().unwrap_or("interrupted")
+        )
+    }
+
+    /// What the resumed agent is told about the iterations already done
+    pub fn resume_context(&self) -> Option<String> {
+        if self.completed_iterations == 0 {
+            return None;
+        }
+        let results = self
+            .tool_results
+            .iter()
+            .map(|result| match &result.error {
+                Some(error) => format!("- {}: failed: {}", result.tool_call_id, error),
+                None => format!("- {}: {}", result.tool_call_id, result.result),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Some(format!(
+            "This run resumes after {} completed iteration(s). Tool results so far:\n{}",
+            self.completed_iterations, results
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks_progress_to_resume_from() {
+        let mut run = RunCheckpoint::new(RunKind::Run, "set up nginx")
+            .with_plan(serde_json::json!({"steps": []}));
+        run.complete_step("install");
+        run.complete_step("install");
+        assert!(run.is_step_done("install") && !run.is_step_done("configure"));
+        assert_eq!(run.completed_steps.len(), 1);
+        assert!(run
+            .summary()
+            .starts_with("--run \"set up nginx\": 1 step(s) done"));
+        assert!(run.summary().ends_with("(interrupted)"));
+        run.stop("token budget exceeded");
+        assert!(run.summary().ends_with("(token budget exceeded)"));
+
+        let mut agent = RunCheckpoint::new(RunKind::AiAgent, "why is the build slow");
+        assert_eq!(agent.resume_context(), None);
+        agent.complete_iteration(
+            &["Look at the build profile".to_string()],
+            &[ToolResult {
+                tool_call_id: "file_read-1".to_string(),
+                success: true,
+                result: serde_json::json!("opt-level = 3"),
+                error: None,
+            }],
+        );
+        assert_eq!(
+            agent.resume_context().unwrap(),
+            "This run resumes after 1 completed iteration(s). Tool results so far:\n- file_read-1: \"opt-level = 3\""
+        );
+
+        let saved: RunCheckpoint =
+            serde_json::from_str(&serde_json::to_string(&agent).unwrap()).unwrap();
+        assert_eq!(saved, agent);
+
+        let mut split = RunCheckpoint::new(RunKind::AiAgent, "add auth").with_sub_agents();
+        split.complete_subtask(serde_json::json!({"task_id": "analysis"}), &[]);
+        assert!(split
+            .summary()
+            .starts_with("--ai-agent \"add auth\": 1 subtask(s) done"));
+    }
+}
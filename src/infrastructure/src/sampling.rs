@@ -0,0 +1,275 @@
+//! Generation parameters per mode
+//!
+//! Shell commands and agent plans should come out near-deterministic, while
+//! chat answers benefit from some variety. Each [`GenerationMode`] has its own
+//! [`SamplingParams`] in the `sampling` section of the power user config (or
+//! `VIBE_SAMPLING` as JSON); `--temperature`, `--top-p` and `--max-tokens`
+//! override them for one run.
+//!
+//! Every run samples with a known seed: `--seed`, the configured one, or one
+//! picked at random for the run. Generated commands, plans and cached answers
+//! carry a [`GenerationRecord`] so a reply can be reproduced exactly.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{OnceLock, RwLock};
+
+/// Parameters given on the command line, applied on top of every mode's
+static OVERRIDES: RwLock<SamplingParams> = RwLock::new(SamplingParams::UNSET);
+
+/// Configuration chosen for this run, e.g. with `--config`; loaded when unset
+static CONFIG: RwLock<Option<SamplingConfig>> = RwLock::new(None);
+
+/// What the model is generating, which decides how it samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationMode {
+    /// Shell commands to run
+    Command,
+    /// Agent plans, tool steps and file contents
+    Agent,
+    /// Answers, explanations and open-ended conversation
+    Chat,
+}
+
+impl GenerationMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            GenerationMode::Command => "command",
+            GenerationMode::Agent => "agent",
+            GenerationMode::Chat => "chat",
+        }
+    }
+}
+
+/// Sampling options sent to the model; unset ones use the model's defaults
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub seed: Option<u64>,
+    /// Longest reply, in tokens
+    pub max_tokens: Option<u32>,
+}
+
+impl SamplingParams {
+    const UNSET: Self = Self {
+        temperature: None,
+        top_p: None,
+        seed: None,
+        max_tokens: None,
+    };
+
+    /// These parameters, taking unset ones from `fallback`
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            temperature: self.temperature.or(fallback.temperature),
+            top_p: self.top_p.or(fallback.top_p),
+            seed: self.seed.or(fallback.seed),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+        }
+    }
+
+    /// Reject values the model would refuse or silently clamp
+    pub fn validate(&self) -> shared::types::Result<()> {
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(anyhow::anyhow!(
+                    "Temperature must be between 0 and 2, got {}",
+                    temperature
+                ));
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !(top_p > 0.0 && top_p <= 1.0) {
+                return Err(anyhow::anyhow!(
+                    "top_p must be greater than 0 and at most 1, got {}",
+                    top_p
+                ));
+            }
+        }
+        if self.seed.is_some_and(|seed| seed > i64::MAX as u64) {
+            return Err(anyhow::anyhow!("The seed must be at most {}", i64::MAX));
+        }
+        if self.max_tokens == Some(0) {
+            return Err(anyhow::anyhow!("max_tokens must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingConfig {
+    pub command: SamplingParams,
+    pub agent: SamplingParams,
+    pub chat: SamplingParams,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            command: SamplingParams {
+                temperature: Some(0.1),
+                top_p: Some(0.9),
+                ..SamplingParams::UNSET
+            },
+            agent: SamplingParams {
+                temperature: Some(0.2),
+                top_p: Some(0.9),
+                ..SamplingParams::UNSET
+            },
+            chat: SamplingParams {
+                temperature: Some(0.7),
+                top_p: Some(0.95),
+                ..SamplingParams::UNSET
+            },
+        }
+    }
+}
+
+impl SamplingConfig {
+    pub fn for_mode(&self, mode: GenerationMode) -> SamplingParams {
+        match mode {
+            GenerationMode::Command => self.command,
+            GenerationMode::Agent => self.agent,
+            GenerationMode::Chat => self.chat,
+        }
+    }
+}
+
+/// Apply `params` to every generation in this process, whatever its mode
+pub fn set_overrides(params: SamplingParams) {
+    *OVERRIDES.write().unwrap() = params;
+}
+
+pub fn overrides() -> SamplingParams {
+    *OVERRIDES.read().unwrap()
+}
+
+/// Use `config` instead of the power user configuration found on disk
+pub fn set_config(config: SamplingConfig) {
+    *CONFIG.write().unwrap() = Some(config);
+}
+
+/// Parameters for `mode`: command line overrides, then the configuration,
+/// then this run's seed
+pub fn params_for(mode: GenerationMode) -> SamplingParams {
+    let configured = CONFIG.read().unwrap().clone();
+    let configured = configured
+        .unwrap_or_else(|| crate::config::PowerUserConfig::load().sampling)
+        .for_mode(mode);
+    let seeded = SamplingParams {
+        seed: Some(run_seed()),
+        ..SamplingParams::UNSET
+    };
+    overrides().or(configured).or(seeded)
+}
+
+/// Seed for generations nothing else pins one for, the same for the whole run
+pub fn run_seed() -> u64 {
+    static SEED: OnceLock<u64> = OnceLock::new();
+    // Short enough to type, and a valid seed for Ollama's signed integer
+    *SEED.get_or_init(|| uuid::Uuid::new_v4().as_u64_pair().0 >> 33)
+}
+
+/// How a reply was generated, enough to generate it again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationRecord {
+    pub model: String,
+    /// Digest of the model weights; pulling a newer model under the same tag
+    /// changes it, and with it the replies
+    pub model_digest: Option<String>,
+    pub sampling: SamplingParams,
+}
+
+impl GenerationRecord {
+    /// Flags that repeat this generation
+    pub fn reproduce_flags(&self) -> String {
+        let mut flags = Vec::new();
+        if let Some(seed) = self.sampling.seed {
+            flags.push(format!("--seed {}", seed));
+        }
+        if let Some(temperature) = self.sampling.temperature {
+            flags.push(format!("--temperature {}", temperature));
+        }
+        if let Some(top_p) = self.sampling.top_p {
+            flags.push(format!("--top-p {}", top_p));
+        }
+        if let Some(max_tokens) = self.sampling.max_tokens {
+            flags.push(format!("--max-tokens {}", max_tokens));
+        }
+        flags.join(" ")
+    }
+}
+
+impl std::fmt::Display for GenerationRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.model)?;
+        if let Some(digest) = &self.model_digest {
+            write!(f, " ({})", &digest[..digest.len().min(12)])?;
+        }
+        match self.sampling.seed {
+            Some(seed) => write!(f, ", seed {}", seed),
+            None => write!(f, ", unseeded"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_win_and_unset_fields_fall_back() {
+        let configured = SamplingConfig::default().for_mode(GenerationMode::Command);
+        let params = SamplingParams {
+            temperature: Some(1.2),
+            max_tokens: Some(256),
+            ..SamplingParams::default()
+        }
+        .or(configured);
+
+        assert_eq!(params.temperature, Some(1.2));
+        assert_eq!(params.top_p, configured.top_p);
+        assert_eq!(params.max_tokens, Some(256));
+        assert_eq!(params.seed, None);
+        assert!(params.validate().is_ok());
+
+        let config: SamplingConfig =
+            serde_json::from_str(r#"{"chat": {"temperature": 1.0}}"#).unwrap();
+        assert_eq!(config.chat.temperature, Some(1.0));
+        assert_eq!(config.chat.top_p, None);
+        assert_eq!(config.command, SamplingConfig::default().command);
+
+        assert!(SamplingParams {
+            top_p: Some(0.0),
+            ..SamplingParams::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_every_mode_is_seeded_and_the_seed_is_recorded() {
+        set_config(SamplingConfig::default());
+        let command = params_for(GenerationMode::Command);
+        assert_eq!(command.seed, Some(run_seed()));
+        assert_eq!(params_for(GenerationMode::Chat).seed, command.seed);
+        assert!(run_seed() <= i64::MAX as u64);
+
+        let record = GenerationRecord {
+            model: "qwen2.5:1.5b-instruct".to_string(),
+            model_digest: Some("2bada8a745910e8a0b4f1c3e".to_string()),
+            sampling: SamplingParams {
+                temperature: Some(0.1),
+                seed: Some(42),
+                ..SamplingParams::default()
+            },
+        };
+        assert_eq!(
+            record.to_string(),
+            "qwen2.5:1.5b-instruct (2bada8a74591), seed 42"
+        );
+        assert_eq!(record.reproduce_flags(), "--seed 42 --temperature 0.1");
+    }
+}
@@ -1,11 +1,107 @@
+use crate::command_audit;
+use crate::config::{ResourceLimitsConfig, SecurityConfig};
+use crate::network_security::{EgressDenied, NetworkSecurity};
+use crate::policy_engine::language::{PolicyContext, PolicySet, Verdict};
+use serde::{Deserialize, Serialize};
+use shared::platform::Shell;
 use shared::types::Result;
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use tokio::time::{timeout, Duration};
 
+#[cfg_attr(
+    all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ),
+    path = "sandbox/linux.rs"
+)]
+#[cfg_attr(
+    not(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )),
+    path = "sandbox/unsupported.rs"
+)]
+mod isolation;
+
+#[cfg_attr(target_os = "linux", path = "sandbox/limits.rs")]
+#[cfg_attr(not(target_os = "linux"), path = "sandbox/no_limits.rs")]
+mod limits;
+
+#[path = "sandbox/container.rs"]
+mod container;
+
+#[path = "sandbox/approvals.rs"]
+pub mod approvals;
+
+#[path = "sandbox/confirmation_rules.rs"]
+pub mod confirmation_rules;
+
+use approvals::ApprovalQueue;
+use confirmation_rules::{Confirmation, ConfirmationGrant, ConfirmationRules, OperationRisk};
+pub use container::ContainerConfig;
+use container::ContainerRun;
+
+/// How commands that pass validation are run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxBackend {
+    /// Command and path allowlists only; the command runs with full access
+    #[default]
+    Checks,
+    /// Linux user, mount and network namespaces with a seccomp filter and
+    /// read-only system directories, on top of the checks. Falls back to the
+    /// checks alone where namespaces are unavailable.
+    Namespaces,
+    /// A disposable Docker or Podman container with only the project mounted,
+    /// on top of the checks. Commands fail when no runtime is running.
+    Container,
+}
+
+impl SandboxBackend {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "checks" | "none" => Some(Self::Checks),
+            "namespaces" | "linux" => Some(Self::Namespaces),
+            "container" | "docker" | "podman" => Some(Self::Container),
+            _ => None,
+        }
+    }
+}
+
+/// Memory, CPU and process caps for sandboxed commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessLimits {
+    pub memory_mb: u64,
+    /// Share of all cores, in percent
+    pub cpu_percent: u32,
+    pub max_processes: u32,
+    /// Use a cgroup v2 where the hierarchy allows it, rather than `setrlimit`
+    pub cgroups: bool,
+}
+
+impl ProcessLimits {
+    pub fn from_config(limits: &ResourceLimitsConfig) -> Self {
+        Self {
+            memory_mb: limits.max_memory_mb,
+            cpu_percent: limits.max_cpu_percentage,
+            max_processes: limits.max_processes,
+            cgroups: limits.cgroups_enabled,
+        }
+    }
+}
+
 /// Sandbox environment for safe command execution
 pub struct Sandbox {
+    backend: SandboxBackend,
+    container: ContainerRun,
+    process_limits: Option<ProcessLimits>,
+    /// Hosts commands may connect to; unchecked when unset
+    egress: Option<NetworkSecurity>,
+    /// The user's policy rules
+    policy: PolicySet,
     allowed_commands: HashSet<String>,
     blocked_commands: HashSet<String>,
     allowed_paths: HashSet<String>,
@@ -30,6 +126,8 @@ impl Sandbox {
         ] {
             allowed_commands.insert(cmd.to_string());
         }
+        // Whatever shell `execute_shell` uses on this platform
+        allowed_commands.insert(Shell::detect().program().to_string());
 
         // Programming/development commands
         for cmd in &[
@@ -119,6 +217,11 @@ impl Sandbox {
         }
 
         Self {
+            backend: SandboxBackend::Checks,
+            container: ContainerRun::new(&ResourceLimitsConfig::default()),
+            process_limits: None,
+            egress: None,
+            policy: PolicySet::default(),
             allowed_commands,
             blocked_commands,
             allowed_paths,
@@ -129,6 +232,60 @@ impl Sandbox {
         }
     }
 
+    /// Sandbox using the backend chosen in the resource limits
+    pub fn for_limits(limits: &ResourceLimitsConfig) -> Self {
+        Self {
+            container: ContainerRun::new(limits),
+            process_limits: Some(ProcessLimits::from_config(limits)),
+            ..Self::new()
+        }
+        .with_backend(limits.sandbox_backend.clone())
+    }
+
+    /// Sandbox for the resource limits that also keeps commands to the
+    /// network allowlist and the user's policy rules
+    pub fn for_security(security: &SecurityConfig) -> Self {
+        Self {
+            egress: NetworkSecurity::for_egress(&security.network_security),
+            policy: PolicySet::load_configured(),
+            ..Self::for_limits(&security.resource_limits)
+        }
+    }
+
+    pub fn with_policy(mut self, policy: PolicySet) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_backend(mut self, backend: SandboxBackend) -> Self {
+        self.backend = backend;
+        match self.backend {
+            SandboxBackend::Namespaces if !self.isolated() => eprintln!(
+                "Warning: namespace sandbox backend is unavailable here; commands are only checked, not isolated"
+            ),
+            SandboxBackend::Container if !self.isolated() => eprintln!(
+                "Warning: container sandbox backend selected but no container runtime is running; commands will fail"
+            ),
+            _ => {}
+        }
+        self
+    }
+
+    /// Whether commands are actually isolated on this machine
+    pub fn isolated(&self) -> bool {
+        match self.backend {
+            SandboxBackend::Checks => false,
+            SandboxBackend::Namespaces => isolation::supported(),
+            SandboxBackend::Container => {
+                container::runtime(self.container.config.runtime.as_deref()).is_some()
+            }
+        }
+    }
+
+    pub fn backend(&self) -> &SandboxBackend {
+        &self.backend
+    }
+
     /// Get dangerous command patterns
     fn get_dangerous_patterns() -> Vec<String> {
         vec![
@@ -157,23 +314,63 @@ impl Sandbox {
     /// Execute command safely in sandbox
     pub async fn execute_safe(&self, command: &str, args: Vec<String>) -> Result<String> {
         // Pre-execution validation
+        self.check_policy(command, &args)?;
         self.validate_command(command, &args)?;
+        if let Some(egress) = &self.egress {
+            egress.check_command(command, &args)?;
+        }
 
         // Execute with timeout and output limits
-        let command = command.to_string();
-        let args = args.to_owned();
-        let output = timeout(
-            self.max_execution_time,
-            tokio::task::spawn_blocking(move || {
-                let mut cmd = Command::new(&command);
+        // Containers enforce the limits themselves
+        let (mut cmd, running, enforcement) = match self.backend {
+            SandboxBackend::Container => {
+                let (cmd, running) = self.container.command(command, &args)?;
+                (cmd, Some(running), None)
+            }
+            _ => {
+                let mut cmd = Command::new(command);
                 cmd.args(&args);
-                cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
-                cmd.output()
-            }),
+                // The limits hook must run before isolation drops privileges
+                let enforcement = self
+                    .process_limits
+                    .map(|limits| limits::apply(&mut cmd, &limits, self.max_execution_time));
+                self.isolate(&mut cmd);
+                (cmd, None, enforcement)
+            }
+        };
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let audited = std::iter::once(command)
+            .chain(args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let output = match timeout(
+            self.max_execution_time,
+            tokio::task::spawn_blocking(move || cmd.output()),
         )
-        .await???;
+        .await
+        {
+            Ok(output) => {
+                let output = output?;
+                command_audit::record(&audited, "sandbox", output.as_ref().ok().map(|o| &o.status));
+                output?
+            }
+            Err(elapsed) => {
+                command_audit::record(&audited, "sandbox", None);
+                // Abandoning the runtime client does not stop the container
+                if let Some(running) = running {
+                    tokio::task::spawn_blocking(move || running.remove());
+                }
+                return Err(elapsed.into());
+            }
+        };
 
         if !output.status.success() {
+            if let Some(limit) = enforcement.and_then(|e| e.exceeded(&output.status)) {
+                return Err(anyhow::anyhow!(
+                    "Command was stopped for exceeding {}",
+                    limit
+                ));
+            }
             return Err(anyhow::anyhow!(
                 "Command failed with exit code: {}",
                 output.status
@@ -197,6 +394,132 @@ impl Sandbox {
         Ok(combined_output)
     }
 
+    /// Apply the backend's isolation to a command about to be spawned
+    fn isolate(&self, cmd: &mut Command) {
+        if self.backend == SandboxBackend::Namespaces && isolation::supported() {
+            isolation::isolate(cmd);
+        }
+    }
+
+    /// Command starting a long-lived server that talks over stdio, such as an
+    /// MCP server. It passes the same checks as `execute_safe` and runs under
+    /// the backend's isolation, but without the time and process limits meant
+    /// for one-off commands. The server sees only `env`, plus `PATH` when `env`
+    /// does not set it, so its interpreter can be found.
+    pub fn server_command(
+        &self,
+        command: &str,
+        args: &[String],
+        env: &BTreeMap<String, String>,
+    ) -> Result<Command> {
+        self.check_policy(command, args)?;
+        self.validate_command(command, args)?;
+        self.check_server_egress(command, args, env)?;
+
+        if self.backend == SandboxBackend::Container {
+            // The container's own environment is empty; the runtime client
+            // keeps ours so it can reach its daemon
+            let (cmd, _running) = self.container.server_command(command, args, env)?;
+            return Ok(cmd);
+        }
+        let mut cmd = Command::new(command);
+        cmd.args(args).env_clear().envs(env);
+        if !env.contains_key("PATH") {
+            if let Some(path) = std::env::var_os("PATH") {
+                cmd.env("PATH", path);
+            }
+        }
+        self.isolate(&mut cmd);
+        Ok(cmd)
+    }
+
+    /// Check the hosts a server would reach: those its program is known to
+    /// connect to, and any URL it is given in an argument or variable
+    fn check_server_egress(
+        &self,
+        command: &str,
+        args: &[String],
+        env: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let Some(egress) = &self.egress else {
+            return Ok(());
+        };
+        egress.check_command(command, args)?;
+        for value in args.iter().chain(env.values()) {
+            let Some(host) = url::Url::parse(value)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+            else {
+                continue;
+            };
+            if let Err(reason) = egress.is_host_allowed(&host) {
+                return Err(EgressDenied {
+                    program: command.to_string(),
+                    host,
+                    reason,
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the user's policy rules to the command, or to the script when
+    /// it is run through the shell
+    fn check_policy(&self, command: &str, args: &[String]) -> Result<()> {
+        if self.policy.is_empty() {
+            return Ok(());
+        }
+        let shell = Shell::detect();
+        // `args("")` is the shell's flags followed by an empty script
+        let mut shell_flags = shell.args("");
+        shell_flags.pop();
+        let subject = match args.split_last() {
+            Some((script, flags))
+                if (command == shell.program() && flags == shell_flags.as_slice())
+                    || (matches!(command, "bash" | "sh") && flags == ["-c"]) =>
+            {
+                script.clone()
+            }
+            _ => std::iter::once(command)
+                .chain(args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        let session = command_audit::current_session();
+        let context = PolicyContext::for_command(&subject, session.as_deref());
+        let Some(rule) = self.policy.evaluate(&context) else {
+            return Ok(());
+        };
+        tracing::debug!("Policy rule {} decided on '{}'", rule.describe(), subject);
+        match rule.verdict {
+            Verdict::Allow => Ok(()),
+            Verdict::Ask if std::io::IsTerminal::is_terminal(&std::io::stdin()) => {
+                let prompt = format!(
+                    "Policy rule {} asks before running '{}'. Run it?",
+                    rule.describe(),
+                    subject
+                );
+                if shared::confirmation::ask_confirmation(&prompt, false)? {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!(
+                        "Command declined under policy rule {}",
+                        rule.describe()
+                    ))
+                }
+            }
+            Verdict::Ask => Err(anyhow::anyhow!(
+                "Policy rule {} requires approval and no terminal is attached",
+                rule.describe()
+            )),
+            Verdict::Deny => Err(anyhow::anyhow!(
+                "Command blocked by policy rule {}",
+                rule.describe()
+            )),
+        }
+    }
+
     /// Validate command for safety
     fn validate_command(&self, command: &str, args: &[String]) -> Result<()> {
         // Check if command is explicitly blocked
@@ -339,6 +662,17 @@ impl Sandbox {
         self.max_output_size = max_output;
     }
 
+    /// Cap the memory, CPU and processes of each command
+    pub fn limit_resources(&mut self, limits: ProcessLimits) {
+        self.process_limits = Some(limits);
+    }
+
+    /// Run a command string through the platform shell (`bash -c`, `powershell -Command`, ...)
+    pub async fn execute_shell(&self, script: &str) -> Result<String> {
+        let shell = Shell::detect();
+        self.execute_safe(shell.program(), shell.args(script)).await
+    }
+
     /// Parse and execute a shell command string directly (avoiding bash -c)
     pub async fn execute_command_string(&self, command_string: &str) -> Result<String> {
         // Parse the command string into program and arguments
@@ -421,6 +755,8 @@ impl Sandbox {
     pub fn get_stats(&self) -> std::collections::HashMap<String, String> {
         let mut stats = std::collections::HashMap::new();
 
+        stats.insert("isolated".to_string(), self.isolated().to_string());
+
         stats.insert(
             "allowed_commands".to_string(),
             self.allowed_commands.len().to_string(),
@@ -458,6 +794,14 @@ impl Sandbox {
 pub struct ConfirmationManager {
     dangerous_operations: HashSet<String>,
     require_confirmation: bool,
+    /// Where operations are approved when no terminal is attached
+    approvals: ApprovalQueue,
+    approval_timeout: Duration,
+    rules: ConfirmationRules,
+    /// Project root for the outside-workspace rule
+    workspace: Option<PathBuf>,
+    /// Answers remembered in the current session
+    grants: Vec<ConfirmationGrant>,
 }
 
 impl ConfirmationManager {
@@ -490,15 +834,74 @@ impl ConfirmationManager {
         Self {
             dangerous_operations,
             require_confirmation: true,
+            approvals: ApprovalQueue::open_default(),
+            approval_timeout: Duration::from_secs(
+                crate::config::AgentExecutionConfig::default().approval_timeout_seconds,
+            ),
+            rules: ConfirmationRules::default(),
+            workspace: None,
+            grants: Vec::new(),
         }
     }
 
+    /// Decide with the user's `rules`, treating `workspace` as the project
+    pub fn with_rules(mut self, rules: ConfirmationRules, workspace: Option<PathBuf>) -> Self {
+        self.rules = rules;
+        self.workspace = workspace;
+        self
+    }
+
+    /// Honour answers remembered earlier in the session
+    pub fn with_grants(mut self, grants: Vec<ConfirmationGrant>) -> Self {
+        self.grants = grants;
+        self
+    }
+
+    /// Use `queue` for remote approvals instead of the shared one
+    pub fn with_approval_queue(mut self, queue: ApprovalQueue) -> Self {
+        self.approvals = queue;
+        self
+    }
+
+    pub fn set_approval_timeout(&mut self, timeout: Duration) {
+        self.approval_timeout = timeout;
+    }
+
     /// Check if operation requires confirmation
     pub fn requires_confirmation(&self, operation: &str, target: &str) -> bool {
+        !matches!(
+            self.decide(operation, target, None),
+            Confirmation::NotNeeded | Confirmation::Remembered(true)
+        )
+    }
+
+    /// Whether `operation` on `target`, of `risk` when known, needs the user
+    /// to confirm it, going by the configured rules and remembered answers
+    pub fn decide(
+        &self,
+        operation: &str,
+        target: &str,
+        risk: Option<OperationRisk>,
+    ) -> Confirmation {
         if !self.require_confirmation {
-            return false;
+            return Confirmation::NotNeeded;
         }
+        let decided = self.rules.decide(
+            operation,
+            target,
+            risk,
+            self.workspace.as_deref(),
+            &self.grants,
+        );
+        match decided {
+            Some(confirmation) => confirmation,
+            None if self.builtin_requires_confirmation(operation, target) => Confirmation::Ask,
+            None => Confirmation::NotNeeded,
+        }
+    }
 
+    /// Keyword and sensitive path checks used when no rule decides
+    fn builtin_requires_confirmation(&self, operation: &str, target: &str) -> bool {
         let operation_lower = operation.to_lowercase();
         let target_lower = target.to_lowercase();
 
@@ -552,4 +955,23 @@ impl ConfirmationManager {
     pub fn set_require_confirmation(&mut self, require: bool) {
         self.require_confirmation = require;
     }
+
+    /// Put `operation` on `target` in the approval queue, where the web UI
+    /// picks it up, and wait until it is approved, rejected or times out,
+    /// which counts as rejected
+    pub async fn request_approval(&self, operation: &str, target: &str) -> Result<bool> {
+        if !self.require_confirmation {
+            return Ok(true);
+        }
+        let pending = self
+            .approvals
+            .submit(operation, target, self.approval_timeout)?;
+        tracing::info!(
+            "Waiting up to {}s for approval {} of '{}'",
+            self.approval_timeout.as_secs(),
+            pending.id,
+            target
+        );
+        self.approvals.wait(&pending.id).await
+    }
 }
@@ -0,0 +1,196 @@
+//! Approvals of destructive operations requested without a terminal
+//!
+//! When nobody is at a terminal to confirm a destructive step (the agent run
+//! from the web UI or by voice), the request is written to
+//! `<data dir>/approvals/<id>.json` and the agent waits. The web server lists
+//! the same files and records the decision made from the browser or phone;
+//! requests nobody answers in time count as rejected.
+
+use crate::file_queue::{FileQueue, QueueEntry};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shared::platform;
+use shared::types::Result;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingApproval {
+    pub id: String,
+    /// What the agent is about to do, e.g. the step description
+    pub operation: String,
+    /// The command or path it applies to
+    pub target: String,
+    pub created_at: DateTime<Utc>,
+    /// Rejected automatically after this
+    pub expires_at: DateTime<Utc>,
+    pub approved: Option<bool>,
+    /// Who decided, e.g. `web`
+    pub decided_by: Option<String>,
+}
+
+impl PendingApproval {
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+}
+
+impl QueueEntry for PendingApproval {
+    const KIND: &'static str = "approval";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+/// Approval requests shared by all local bro processes
+#[derive(Debug, Clone)]
+pub struct ApprovalQueue {
+    queue: FileQueue<PendingApproval>,
+}
+
+impl ApprovalQueue {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            queue: FileQueue::new(dir),
+        }
+    }
+
+    pub fn open_default() -> Self {
+        Self::new(platform::app_data_dir().join("approvals"))
+    }
+
+    /// Ask for approval of `operation` on `target`, valid for `timeout`
+    pub fn submit(
+        &self,
+        operation: &str,
+        target: &str,
+        timeout: Duration,
+    ) -> Result<PendingApproval> {
+        let created_at = Utc::now();
+        let pending = PendingApproval {
+            id: uuid::Uuid::new_v4().to_string(),
+            operation: operation.to_string(),
+            target: target.to_string(),
+            created_at,
+            expires_at: created_at
+                + chrono::Duration::from_std(timeout).unwrap_or(chrono::Duration::hours(1)),
+            approved: None,
+            decided_by: None,
+        };
+        self.queue.insert(&pending)?;
+        Ok(pending)
+    }
+
+    /// Requests still waiting for a decision, oldest first; expired and
+    /// decided ones that were never collected are discarded
+    pub fn list(&self) -> Result<Vec<PendingApproval>> {
+        let mut pending = self.queue.list(PendingApproval::is_expired)?;
+        pending.retain(|approval| approval.approved.is_none());
+        Ok(pending)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<PendingApproval>> {
+        self.queue.get(id)
+    }
+
+    /// Approve or reject a request that is still open
+    pub fn decide(&self, id: &str, approve: bool, source: &str) -> Result<PendingApproval> {
+        self.queue.modify(id, |pending| {
+            if pending.is_expired() {
+                return Err(anyhow::anyhow!("Approval {} has expired", id));
+            }
+            if pending.approved.is_some() {
+                return Err(anyhow::anyhow!("Approval {} was already decided", id));
+            }
+            pending.approved = Some(approve);
+            pending.decided_by = Some(source.to_string());
+            Ok(())
+        })
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        self.queue.remove(id)
+    }
+
+    /// Wait for the decision on `id`; false when it is rejected, expires or
+    /// disappears. The request is removed either way.
+    pub async fn wait(&self, id: &str) -> Result<bool> {
+        let decided = self
+            .queue
+            .wait(
+                id,
+                |pending| pending.approved.is_some(),
+                PendingApproval::is_expired,
+            )
+            .await?;
+        Ok(decided.is_some_and(|pending| pending.approved == Some(true)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_waiting_request_sees_remote_decision_or_expires() {
+        let dir = std::env::temp_dir().join(format!("bro-approvals-{}", uuid::Uuid::new_v4()));
+        let queue = ApprovalQueue::new(&dir);
+
+        let pending = queue
+            .submit(
+                "Remove build cache",
+                "rm -rf target",
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        assert_eq!(queue.list().unwrap()[0].id, pending.id);
+        let remote = queue.clone();
+        let id = pending.id.clone();
+        tokio::spawn(async move { remote.decide(&id, true, "web").unwrap() });
+        assert!(queue.wait(&pending.id).await.unwrap());
+        assert!(queue.get(&pending.id).unwrap().is_none());
+
+        let expired = queue
+            .submit("Wipe disk", "dd if=/dev/zero of=/dev/sda", Duration::ZERO)
+            .unwrap();
+        assert!(queue.decide(&expired.id, true, "web").is_err());
+        assert!(!queue.wait(&expired.id).await.unwrap());
+        assert!(queue.list().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_only_the_first_decision_wins() {
+        let dir = std::env::temp_dir().join(format!("bro-approvals-{}", uuid::Uuid::new_v4()));
+        let queue = ApprovalQueue::new(&dir);
+        let pending = queue
+            .submit(
+                "Remove build cache",
+                "rm -rf target",
+                Duration::from_secs(60),
+            )
+            .unwrap();
+
+        let accepted = std::thread::scope(|scope| {
+            let decisions: Vec<_> = [true, false, true, false]
+                .into_iter()
+                .map(|approve| {
+                    let (queue, id) = (&queue, &pending.id);
+                    scope.spawn(move || queue.decide(id, approve, "web").is_ok())
+                })
+                .collect();
+            decisions
+                .into_iter()
+                .map(|decision| decision.join().unwrap())
+                .filter(|&accepted| accepted)
+                .count()
+        });
+        assert_eq!(accepted, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,301 @@
+//! User-defined rules for when to ask before an operation
+//!
+//! Configured under `confirmations` in the power user config:
+//!
+//! ```json
+//! "confirmations": {
+//!   "rules": [
+//!     { "operation": "cargo *", "action": "allow" },
+//!     { "target": "**/migrations/**", "action": "ask" },
+//!     { "operation": "*", "min_risk": "high", "action": "ask" }
+//!   ],
+//!   "risk_threshold": "critical",
+//!   "outside_workspace": true
+//! }
+//! ```
+//!
+//! Rules are tried in order and the first match decides. Answers the user
+//! asks to remember are kept with the session as [`ConfirmationGrant`]s and
+//! take precedence over the rules, except for paths outside the workspace
+//! when `outside_workspace` is set.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationRisk {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    Ask,
+    Allow,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfirmationRule {
+    /// Glob over the operation, e.g. the command or step description
+    #[serde(default = "match_all")]
+    pub operation: String,
+    /// Glob over the target path or command
+    #[serde(default = "match_all")]
+    pub target: String,
+    /// Only operations at least this risky match
+    #[serde(default)]
+    pub min_risk: Option<OperationRisk>,
+    pub action: RuleAction,
+}
+
+fn match_all() -> String {
+    "*".to_string()
+}
+
+impl ConfirmationRule {
+    fn matches(&self, operation: &str, target: &str, risk: Option<OperationRisk>) -> bool {
+        let risky_enough = match (self.min_risk, risk) {
+            (Some(min), Some(risk)) => risk >= min,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        risky_enough && glob_match(&self.operation, operation) && glob_match(&self.target, target)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfirmationRules {
+    pub rules: Vec<ConfirmationRule>,
+    /// Ask for operations at least this risky when no rule matches
+    pub risk_threshold: Option<OperationRisk>,
+    /// Always ask when the target names a path outside the workspace
+    pub outside_workspace: bool,
+    /// Fall back to the built-in keyword and system path checks for
+    /// operations of unknown risk
+    pub builtin: bool,
+}
+
+impl Default for ConfirmationRules {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            risk_threshold: Some(OperationRisk::Critical),
+            outside_workspace: false,
+            builtin: true,
+        }
+    }
+}
+
+/// An answer remembered for the rest of a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfirmationGrant {
+    pub operation: String,
+    pub target: String,
+    pub approved: bool,
+    pub granted_at: DateTime<Utc>,
+}
+
+impl ConfirmationGrant {
+    pub fn new(operation: &str, target: &str, approved: bool) -> Self {
+        Self {
+            operation: operation.to_string(),
+            target: target.to_string(),
+            approved,
+            granted_at: Utc::now(),
+        }
+    }
+}
+
+/// Outcome of checking an operation against the rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    /// The user has to confirm
+    Ask,
+    NotNeeded,
+    /// Answered earlier in the session
+    Remembered(bool),
+}
+
+impl ConfirmationRules {
+    /// Decide for `operation` on `target`; None for the built-in checks,
+    /// which the caller owns
+    pub fn decide(
+        &self,
+        operation: &str,
+        target: &str,
+        risk: Option<OperationRisk>,
+        workspace: Option<&Path>,
+        grants: &[ConfirmationGrant],
+    ) -> Option<Confirmation> {
+        if self.outside_workspace
+            && workspace.is_some_and(|workspace| names_outside_path(target, workspace))
+        {
+            return Some(Confirmation::Ask);
+        }
+        if let Some(grant) = grants
+            .iter()
+            .rev()
+            .find(|grant| grant.operation == operation && grant.target == target)
+        {
+            return Some(Confirmation::Remembered(grant.approved));
+        }
+        if let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(operation, target, risk))
+        {
+            return Some(match rule.action {
+                RuleAction::Ask => Confirmation::Ask,
+                RuleAction::Allow => Confirmation::NotNeeded,
+            });
+        }
+        match (risk, self.risk_threshold) {
+            (Some(risk), Some(threshold)) if risk >= threshold => Some(Confirmation::Ask),
+            (Some(_), _) => Some(Confirmation::NotNeeded),
+            (None, _) if self.builtin => None,
+            (None, _) => Some(Confirmation::NotNeeded),
+        }
+    }
+}
+
+/// Shell-style glob: `*` stays within a path segment, `**` crosses them and
+/// `?` is any one character. Matching ignores case.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    glob_at(&pattern, &text)
+}
+
+fn glob_at(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| glob_at(rest, &text[i..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_at(rest, &text[i..])),
+        ['?', rest @ ..] => !text.is_empty() && glob_at(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && glob_at(rest, &text[1..]),
+    }
+}
+
+/// Whether any path-like word of `target` resolves outside `workspace`
+fn names_outside_path(target: &str, workspace: &Path) -> bool {
+    target
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c| c == '"' || c == '\'' || c == ';'))
+        .map(|word| word.rsplit('=').next().unwrap_or(word))
+        .filter(|word| word.starts_with('/') || word.starts_with('~') || word.contains(".."))
+        .any(|word| !normalize(&expand_home(word), workspace).starts_with(workspace))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) => shared::platform::home_dir().join(rest.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
+
+/// `path` made absolute against `base`, with `.` and `..` resolved lexically
+fn normalize(path: &Path, base: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_matching_rule_wins_after_grants_and_workspace() {
+        let rules = ConfirmationRules {
+            rules: vec![
+                ConfirmationRule {
+                    operation: "cargo *".to_string(),
+                    target: match_all(),
+                    min_risk: None,
+                    action: RuleAction::Allow,
+                },
+                ConfirmationRule {
+                    operation: match_all(),
+                    target: "**/migrations/**".to_string(),
+                    min_risk: None,
+                    action: RuleAction::Ask,
+                },
+                ConfirmationRule {
+                    operation: match_all(),
+                    target: match_all(),
+                    min_risk: Some(OperationRisk::High),
+                    action: RuleAction::Ask,
+                },
+            ],
+            outside_workspace: true,
+            ..ConfirmationRules::default()
+        };
+        let workspace = Path::new("/work/app");
+        let decide = |op: &str, target: &str, risk, grants: &[ConfirmationGrant]| {
+            rules.decide(op, target, risk, Some(workspace), grants)
+        };
+        let low = Some(OperationRisk::Low);
+
+        assert_eq!(
+            decide("cargo clean", "target", Some(OperationRisk::Critical), &[]),
+            Some(Confirmation::NotNeeded)
+        );
+        assert_eq!(
+            decide("edit", "db/migrations/001.sql", low, &[]),
+            Some(Confirmation::Ask)
+        );
+        assert_eq!(
+            decide("rm", "rm -rf build", Some(OperationRisk::High), &[]),
+            Some(Confirmation::Ask)
+        );
+        assert_eq!(
+            decide("cat", "cat src/main.rs", low, &[]),
+            Some(Confirmation::NotNeeded)
+        );
+        assert_eq!(decide("cat", "cat src/main.rs", None, &[]), None);
+
+        let grants = [ConfirmationGrant::new("rm", "rm -rf build", true)];
+        assert_eq!(
+            decide("rm", "rm -rf build", Some(OperationRisk::High), &grants),
+            Some(Confirmation::Remembered(true))
+        );
+        // Paths outside the workspace are asked about even with an allow rule
+        // or a remembered answer
+        let grants = [ConfirmationGrant::new("cargo run", "cp a ../other/b", true)];
+        assert_eq!(
+            decide("cargo run", "cp a ../other/b", low, &grants),
+            Some(Confirmation::Ask)
+        );
+        assert_eq!(
+            decide("cargo run", "cp a ./src/../b", low, &[]),
+            Some(Confirmation::NotNeeded)
+        );
+    }
+
+    #[test]
+    fn test_glob_segments() {
+        assert!(glob_match("src/*.rs", "src/main.rs"));
+        assert!(!glob_match("src/*.rs", "src/cli/mod.rs"));
+        assert!(glob_match("src/**.rs", "src/cli/mod.rs"));
+        assert!(glob_match("**/.env", "/work/app/.env"));
+        assert!(glob_match("git push*", "Git Push --force"));
+        assert!(glob_match("?m", "rm"));
+        assert!(!glob_match("rm", "rmdir"));
+    }
+}
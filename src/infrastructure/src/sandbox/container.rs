@@ -0,0 +1,281 @@
+//! Container execution for sandboxed commands
+//!
+//! Each command runs in a fresh, disposable Docker or Podman container from a
+//! configurable image. Only the project directory is mounted, at its host
+//! path so arguments naming project files keep working. The root filesystem is
+//! read-only, capabilities are dropped, the network is off unless enabled, and
+//! memory, CPU and process counts are capped.
+
+use crate::config::ResourceLimitsConfig;
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Runtimes tried, in order, when none is configured
+const RUNTIMES: [&str; 2] = ["podman", "docker"];
+
+/// Distinguishes containers started by one process
+static NEXT_CONTAINER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContainerConfig {
+    /// `docker` or `podman`; the first one that is running when unset
+    pub runtime: Option<String>,
+    /// Image commands run in; it must provide the tools the agent calls
+    pub image: String,
+    /// Give the container network access
+    pub network: bool,
+}
+
+impl Default for ContainerConfig {
+    fn default() -> Self {
+        Self {
+            runtime: None,
+            image: "docker.io/library/debian:stable-slim".to_string(),
+            network: false,
+        }
+    }
+}
+
+/// How the sandbox runs commands in containers
+#[derive(Debug, Clone)]
+pub struct ContainerRun {
+    pub config: ContainerConfig,
+    pub memory_mb: u64,
+    /// CPU share, in cores
+    pub cpus: f64,
+    pub max_processes: u32,
+}
+
+/// A started container, removed if its command outlives the timeout
+pub struct RunningContainer {
+    runtime: String,
+    name: String,
+}
+
+impl RunningContainer {
+    pub fn remove(&self) {
+        let _ = Command::new(&self.runtime)
+            .args(["rm", "--force", &self.name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+impl ContainerRun {
+    /// Containers capped at the memory and share of the machine's cores in `limits`
+    pub fn new(limits: &ResourceLimitsConfig) -> Self {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let share = limits.max_cpu_percentage.clamp(1, 100) as f64 / 100.0;
+        Self {
+            config: limits.container.clone(),
+            memory_mb: limits.max_memory_mb,
+            cpus: (cores as f64 * share).max(0.1),
+            max_processes: limits.max_processes,
+        }
+    }
+
+    /// Command running `program` in a new container
+    pub fn command(&self, program: &str, args: &[String]) -> Result<(Command, RunningContainer)> {
+        self.command_with(program, args, &[])
+    }
+
+    /// Command running a long-lived server over stdio in a new container,
+    /// which ends when its stdin closes. Only the variables in `env` are
+    /// passed in, their values through the runtime's environment rather than
+    /// its arguments.
+    pub fn server_command(
+        &self,
+        program: &str,
+        args: &[String],
+        env: &BTreeMap<String, String>,
+    ) -> Result<(Command, RunningContainer)> {
+        let extra: Vec<String> = std::iter::once("--interactive".to_string())
+            .chain(env.keys().map(|name| format!("--env={}", name)))
+            .collect();
+        let (mut cmd, running) = self.command_with(program, args, &extra)?;
+        cmd.envs(env);
+        Ok((cmd, running))
+    }
+
+    fn command_with(
+        &self,
+        program: &str,
+        args: &[String],
+        extra: &[String],
+    ) -> Result<(Command, RunningContainer)> {
+        let runtime = runtime(self.config.runtime.as_deref()).ok_or_else(|| {
+            anyhow::anyhow!(
+                "The container sandbox backend needs a running {}",
+                self.config.runtime.as_deref().unwrap_or("podman or docker")
+            )
+        })?;
+        let workdir = std::env::current_dir()?;
+        let mount = crate::config::find_project_root()
+            .map(PathBuf::from)
+            .filter(|root| workdir.starts_with(root))
+            .unwrap_or_else(|| workdir.clone());
+        let name = format!(
+            "bro-sandbox-{}-{}",
+            std::process::id(),
+            NEXT_CONTAINER.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let mut cmd = Command::new(&runtime);
+        cmd.args(self.run_args(&runtime, &name, &mount, &workdir, extra))
+            .arg(program)
+            .args(args);
+        Ok((cmd, RunningContainer { runtime, name }))
+    }
+
+    /// `<runtime> run` arguments up to the image name, with `extra` options
+    /// last
+    fn run_args(
+        &self,
+        runtime: &str,
+        name: &str,
+        mount: &Path,
+        workdir: &Path,
+        extra: &[String],
+    ) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "run",
+            "--rm",
+            "--name",
+            name,
+            "--read-only",
+            "--tmpfs",
+            "/tmp",
+            "--cap-drop",
+            "ALL",
+            "--security-opt",
+            "no-new-privileges",
+            "--env",
+            "HOME=/tmp",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+
+        args.push(format!("--memory={}m", self.memory_mb));
+        args.push(format!("--cpus={:.2}", self.cpus));
+        args.push(format!("--pids-limit={}", self.max_processes));
+        if !self.config.network {
+            args.push("--network=none".to_string());
+        }
+        args.extend(owner_args(runtime, mount));
+        args.push(format!("--volume={}:{}", mount.display(), mount.display()));
+        args.push(format!("--workdir={}", workdir.display()));
+        args.extend_from_slice(extra);
+        args.push(self.config.image.clone());
+        args
+    }
+}
+
+/// Run as the owner of the project so files the command creates stay theirs
+#[cfg(unix)]
+fn owner_args(runtime: &str, mount: &Path) -> Vec<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    if Path::new(runtime).ends_with("podman") {
+        // Rootless Podman maps the caller's own id into the container
+        return vec!["--userns=keep-id".to_string()];
+    }
+    match std::fs::metadata(mount) {
+        Ok(metadata) => vec![format!("--user={}:{}", metadata.uid(), metadata.gid())],
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(not(unix))]
+fn owner_args(_runtime: &str, _mount: &Path) -> Vec<String> {
+    Vec::new()
+}
+
+/// `preferred`, or the first runtime that answers, if it is running
+pub fn runtime(preferred: Option<&str>) -> Option<String> {
+    type Checked = Mutex<HashMap<Option<String>, Option<String>>>;
+    static CHECKED: OnceLock<Checked> = OnceLock::new();
+    let mut checked = CHECKED.get_or_init(Default::default).lock().unwrap();
+    checked
+        .entry(preferred.map(str::to_string))
+        .or_insert_with(|| match preferred {
+            Some(runtime) => responds(runtime).then(|| runtime.to_string()),
+            None => RUNTIMES
+                .into_iter()
+                .find(|runtime| responds(runtime))
+                .map(str::to_string),
+        })
+        .clone()
+}
+
+/// Whether the runtime is installed and its daemon or service is reachable
+fn responds(runtime: &str) -> bool {
+    Command::new(runtime)
+        .arg("info")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_args_mount_project_and_apply_limits() {
+        let run = ContainerRun {
+            config: ContainerConfig {
+                image: "alpine:3".to_string(),
+                ..ContainerConfig::default()
+            },
+            memory_mb: 512,
+            cpus: 1.5,
+            max_processes: 256,
+        };
+        let args = run.run_args(
+            "docker",
+            "bro-sandbox-1-0",
+            Path::new("/work/project"),
+            Path::new("/work/project/src"),
+            &["--env=TOKEN".to_string()],
+        );
+
+        assert_eq!(&args[..2], ["run", "--rm"]);
+        for expected in [
+            "--memory=512m",
+            "--cpus=1.50",
+            "--pids-limit=256",
+            "--network=none",
+            "--volume=/work/project:/work/project",
+            "--workdir=/work/project/src",
+        ] {
+            assert!(args.contains(&expected.to_string()), "missing {}", expected);
+        }
+        assert_eq!(args[args.len() - 2..], ["--env=TOKEN", "alpine:3"]);
+
+        let networked = ContainerRun {
+            config: ContainerConfig {
+                network: true,
+                ..run.config.clone()
+            },
+            ..run
+        };
+        let args = networked.run_args(
+            "podman",
+            "bro-sandbox-1-1",
+            Path::new("/p"),
+            Path::new("/p"),
+            &[],
+        );
+        assert!(!args.iter().any(|arg| arg.starts_with("--network")));
+        assert!(args.contains(&"--userns=keep-id".to_string()));
+    }
+}
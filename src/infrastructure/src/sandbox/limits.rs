@@ -0,0 +1,261 @@
+//! Memory, CPU and process limits for sandboxed commands on Linux
+//!
+//! When cgroups are enabled and a cgroup v2 hierarchy is writable, each
+//! command gets its own cgroup next to ours with `memory.max`, `cpu.max` and
+//! `pids.max` set; the child moves itself in before exec, and everything left
+//! in the cgroup is killed when the command finishes or times out. Otherwise
+//! `setrlimit` caps the data segment, CPU seconds and process count instead.
+
+use super::ProcessLimits;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// `cpu.max` accounting period, in microseconds
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Distinguishes cgroups created by one process
+static NEXT_CGROUP: AtomicUsize = AtomicUsize::new(0);
+
+/// Limits in force for one command; tears its cgroup down when dropped
+pub struct Enforcement {
+    cgroup: Option<PathBuf>,
+    limits: ProcessLimits,
+}
+
+impl Enforcement {
+    /// Which limit, if any, a failed command ran into
+    pub fn exceeded(&self, status: &ExitStatus) -> Option<String> {
+        let memory = format!("the {} MB memory limit", self.limits.memory_mb);
+        let oom_killed = self.cgroup.as_ref().is_some_and(|cgroup| {
+            std::fs::read_to_string(cgroup.join("memory.events"))
+                .unwrap_or_default()
+                .lines()
+                .any(|line| line.strip_prefix("oom_kill ").is_some_and(|n| n != "0"))
+        });
+        match status.signal() {
+            _ if oom_killed => Some(memory),
+            Some(libc::SIGXCPU) => Some("its CPU time limit".to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Enforcement {
+    fn drop(&mut self) {
+        let Some(cgroup) = &self.cgroup else {
+            return;
+        };
+        // Background processes the command left behind go with it
+        let _ = std::fs::write(cgroup.join("cgroup.kill"), "1");
+        for _ in 0..50 {
+            if std::fs::remove_dir(cgroup).is_ok() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Limit `command`, which may run for up to `timeout`, when it is spawned
+pub fn apply(command: &mut Command, limits: &ProcessLimits, timeout: Duration) -> Enforcement {
+    let cgroup = limits.cgroups.then(|| create_cgroup(limits)).flatten();
+    let procs = cgroup
+        .as_ref()
+        .and_then(|cgroup| CString::new(cgroup.join("cgroup.procs").as_os_str().as_bytes()).ok());
+    let rlimits = rlimits(limits, timeout);
+    // SAFETY: the hook only makes async-signal-safe syscalls on prepared data
+    unsafe {
+        command.pre_exec(move || match &procs {
+            Some(procs) => join_cgroup(procs),
+            None => set_rlimits(&rlimits),
+        });
+    }
+    Enforcement {
+        cgroup,
+        limits: *limits,
+    }
+}
+
+/// A new cgroup beside ours with the limits set, if the hierarchy allows it
+fn create_cgroup(limits: &ProcessLimits) -> Option<PathBuf> {
+    let own = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let own = own.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let root = Path::new(CGROUP_ROOT);
+    if !root.join("cgroup.controllers").exists() {
+        // cgroup v1 or hybrid hierarchy
+        return None;
+    }
+    let own = root.join(own.trim_start_matches('/'));
+    let parent = if own == root { root } else { own.parent()? };
+
+    let cgroup = parent.join(format!(
+        "bro-sandbox-{}-{}",
+        std::process::id(),
+        NEXT_CGROUP.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir(&cgroup).ok()?;
+    let controllers =
+        std::fs::read_to_string(cgroup.join("cgroup.controllers")).unwrap_or_default();
+    let available = |name: &str| controllers.split_whitespace().any(|c| c == name);
+    let configured = available("memory")
+        && available("pids")
+        && std::fs::write(
+            cgroup.join("memory.max"),
+            (limits.memory_mb * 1024 * 1024).to_string(),
+        )
+        .is_ok()
+        && std::fs::write(cgroup.join("pids.max"), limits.max_processes.to_string()).is_ok();
+    if !configured {
+        let _ = std::fs::remove_dir(&cgroup);
+        return None;
+    }
+    // Swap would let the command get around the memory limit
+    let _ = std::fs::write(cgroup.join("memory.swap.max"), "0");
+    if available("cpu") {
+        let _ = std::fs::write(cgroup.join("cpu.max"), cpu_max(limits.cpu_percent));
+    }
+    Some(cgroup)
+}
+
+/// `cpu.max` value granting `percent` of the machine's cores
+fn cpu_max(percent: u32) -> String {
+    let quota = CPU_PERIOD_US as f64 * cores() * percent.clamp(1, 100) as f64 / 100.0;
+    format!("{} {}", (quota as u64).max(1000), CPU_PERIOD_US)
+}
+
+fn cores() -> f64 {
+    std::thread::available_parallelism().map_or(1, |n| n.get()) as f64
+}
+
+/// `setrlimit` values used when no cgroup is available
+struct Rlimits {
+    data_bytes: u64,
+    cpu_seconds: u64,
+    processes: u64,
+}
+
+fn rlimits(limits: &ProcessLimits, timeout: Duration) -> Rlimits {
+    let share = limits.cpu_percent.clamp(1, 100) as f64 / 100.0;
+    let cpu_seconds = timeout.as_secs_f64() * cores() * share;
+    Rlimits {
+        data_bytes: limits.memory_mb * 1024 * 1024,
+        cpu_seconds: (cpu_seconds.ceil() as u64).max(1),
+        // The process limit counts every task of the user, not just this command's
+        processes: user_tasks() + limits.max_processes as u64,
+    }
+}
+
+/// Tasks (processes and threads) currently running as our user
+fn user_tasks() -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    // SAFETY: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().parse::<u32>().is_ok())
+        .filter(|entry| entry.metadata().is_ok_and(|meta| meta.uid() == uid))
+        .map(|entry| {
+            std::fs::read_dir(entry.path().join("task")).map_or(1, |tasks| tasks.count() as u64)
+        })
+        .sum()
+}
+
+/// Runs in the forked child: moves it into the prepared cgroup
+fn join_cgroup(procs: &CString) -> io::Result<()> {
+    // SAFETY: only async-signal-safe calls on a prepared path; "0" means the caller
+    unsafe {
+        let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+        let error = io::Error::last_os_error();
+        libc::close(fd);
+        if written != 1 {
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Runs in the forked child. Limits never rise above the current hard
+/// limit, which an unprivileged process could not do.
+fn set_rlimits(rlimits: &Rlimits) -> io::Result<()> {
+    macro_rules! lower {
+        ($resource:expr, $value:expr) => {{
+            let mut current: libc::rlimit = std::mem::zeroed();
+            libc::getrlimit($resource, &mut current);
+            let value = ($value as libc::rlim_t).min(current.rlim_max);
+            libc::setrlimit(
+                $resource,
+                &libc::rlimit {
+                    rlim_cur: value,
+                    rlim_max: value,
+                },
+            )
+        }};
+    }
+    // SAFETY: getrlimit and setrlimit only touch the structs passed to them
+    let results = unsafe {
+        [
+            lower!(libc::RLIMIT_DATA, rlimits.data_bytes),
+            lower!(libc::RLIMIT_CPU, rlimits.cpu_seconds),
+            lower!(libc::RLIMIT_NPROC, rlimits.processes),
+        ]
+    };
+    if results.iter().any(|result| *result != 0) {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_max_is_a_share_of_all_cores() {
+        let (quota, period) = cpu_max(50)
+            .split_once(' ')
+            .map(|(q, p)| (q.parse::<f64>().unwrap(), p.parse::<f64>().unwrap()))
+            .unwrap();
+        assert_eq!(period, CPU_PERIOD_US as f64);
+        assert!((quota / period - cores() / 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_rlimits_apply_to_the_command() {
+        let limits = ProcessLimits {
+            memory_mb: 256,
+            cpu_percent: 100,
+            max_processes: 64,
+            cgroups: false,
+        };
+        let mut command = Command::new("sh");
+        command.args(["-c", "ulimit -d; ulimit -t"]);
+        let rlimits = rlimits(&limits, Duration::from_secs(10));
+        // SAFETY: set_rlimits only makes async-signal-safe syscalls
+        unsafe {
+            command.pre_exec(move || set_rlimits(&rlimits));
+        }
+        let output = command.output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = stdout.lines();
+        // `ulimit -d` reports KiB
+        assert_eq!(lines.next(), Some("262144"));
+        let cpu_seconds: f64 = lines.next().unwrap().parse().unwrap();
+        assert_eq!(cpu_seconds, (10.0 * cores()).ceil());
+    }
+}
@@ -0,0 +1,383 @@
+//! Namespace and seccomp isolation for sandboxed commands on Linux
+//!
+//! Before exec, the child moves into new user, mount and network namespaces.
+//! It keeps the caller's uid and gid (mapped one-to-one), sees system
+//! directories through read-only bind mounts, has no network beyond a downed
+//! loopback interface, and runs under a seccomp filter refusing syscalls that
+//! could reconfigure or escape the host, including the new mount API and
+//! `clone` into fresh user or mount namespaces. Everything the child needs is
+//! prepared before fork, so the hook itself only makes syscalls.
+
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// Directories the command can read but not modify
+const READ_ONLY_PATHS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc", "/opt", "/boot",
+];
+
+/// Syscalls refused with `EPERM`
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    // The new mount API could remount the read-only binds writable
+    libc::SYS_open_tree,
+    libc::SYS_move_mount,
+    libc::SYS_fsopen,
+    libc::SYS_fsconfig,
+    libc::SYS_fsmount,
+    libc::SYS_fspick,
+    libc::SYS_mount_setattr,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_kexec_load,
+    libc::SYS_kexec_file_load,
+    libc::SYS_reboot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_userfaultfd,
+    libc::SYS_acct,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+];
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+/// Kernel ABI value; libc does not export it for every target
+const ST_RELATIME: libc::c_ulong = 4096;
+
+/// `clone` flags refused with `EPERM`: a fresh user or mount namespace would
+/// own its mounts again
+const DENIED_CLONE_FLAGS: u32 = (libc::CLONE_NEWUSER | libc::CLONE_NEWNS) as u32;
+
+/// Offsets into `struct seccomp_data`
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+/// Low half of the first argument, on little-endian targets
+const SECCOMP_DATA_ARG0: u32 = 16;
+
+/// State computed in the parent for the pre-exec hook
+struct Isolation {
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    /// Paths to remount read-only, with the mount flags they must keep
+    read_only: Vec<(CString, libc::c_ulong)>,
+    filter: Vec<libc::sock_filter>,
+}
+
+impl Isolation {
+    fn prepare() -> Self {
+        // SAFETY: getuid and getgid cannot fail
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let read_only = READ_ONLY_PATHS
+            .iter()
+            // Symlinks such as /bin -> usr/bin are covered by their target
+            .filter(|path| std::fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir()))
+            .filter_map(|path| CString::new(*path).ok())
+            .map(|path| {
+                let flags = locked_flags(&path);
+                (path, flags)
+            })
+            .collect();
+        Self {
+            uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+            gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+            read_only,
+            filter: seccomp_filter(),
+        }
+    }
+
+    /// Runs in the forked child: no allocation, only syscalls
+    fn enter(&self) -> io::Result<()> {
+        let null = std::ptr::null();
+        // SAFETY: every pointer refers to data owned by `self` or a static C string
+        unsafe {
+            check(libc::unshare(
+                libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET,
+            ))?;
+            write_file(c"/proc/self/setgroups", b"deny")?;
+            write_file(c"/proc/self/uid_map", &self.uid_map)?;
+            write_file(c"/proc/self/gid_map", &self.gid_map)?;
+
+            // Keep the mounts below from propagating back to the host
+            check(libc::mount(
+                null,
+                c"/".as_ptr(),
+                null,
+                libc::MS_REC | libc::MS_PRIVATE,
+                null.cast(),
+            ))?;
+            for (path, locked) in &self.read_only {
+                check(libc::mount(
+                    path.as_ptr(),
+                    path.as_ptr(),
+                    null,
+                    libc::MS_BIND | libc::MS_REC,
+                    null.cast(),
+                ))?;
+                check(libc::mount(
+                    null,
+                    path.as_ptr(),
+                    null,
+                    libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | locked,
+                    null.cast(),
+                ))?;
+            }
+
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+            let program = libc::sock_fprog {
+                len: self.filter.len() as libc::c_ushort,
+                filter: self.filter.as_ptr() as *mut libc::sock_filter,
+            };
+            check(libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER as libc::c_ulong,
+                &program as *const libc::sock_fprog,
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// Make `command` enter the isolation when it is spawned
+pub fn isolate(command: &mut Command) {
+    let isolation = Isolation::prepare();
+    // SAFETY: the hook only makes async-signal-safe syscalls on prepared data
+    unsafe {
+        command.pre_exec(move || isolation.enter());
+    }
+}
+
+/// Whether isolation works here. User namespaces can be disabled by the
+/// kernel or restricted by security modules, so this is probed once by
+/// running `true` isolated.
+pub fn supported() -> bool {
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+    *SUPPORTED.get_or_init(|| {
+        let mut probe = Command::new("true");
+        probe.stdout(Stdio::null()).stderr(Stdio::null());
+        isolate(&mut probe);
+        probe.status().is_ok_and(|status| status.success())
+    })
+}
+
+/// Flags of the mount holding `path` that a remount inside a user namespace
+/// must keep, or the kernel refuses it
+fn locked_flags(path: &CStr) -> libc::c_ulong {
+    // SAFETY: statvfs only writes into the zeroed struct
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return 0;
+    }
+    [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (ST_RELATIME, libc::MS_RELATIME),
+    ]
+    .iter()
+    .filter(|(st, _)| stat.f_flag & st != 0)
+    .fold(0, |flags, (_, ms)| flags | ms)
+}
+
+/// BPF program refusing `DENIED_SYSCALLS` and `DENIED_CLONE_FLAGS`, and
+/// killing processes that use a foreign syscall ABI to get around the list.
+/// `clone3` passes its flags in memory the filter cannot read, so it reports
+/// `ENOSYS` and libc falls back to `clone`.
+fn seccomp_filter() -> Vec<libc::sock_filter> {
+    let stmt = |code: u32, k: u32| libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    let jump = |op: u32, k: u32, jt: u8, jf: u8| libc::sock_filter {
+        code: (libc::BPF_JMP | op | libc::BPF_K) as u16,
+        jt,
+        jf,
+        k,
+    };
+    let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+    let ret = libc::BPF_RET | libc::BPF_K;
+
+    let mut filter = vec![
+        stmt(load, SECCOMP_DATA_ARCH),
+        jump(libc::BPF_JEQ, AUDIT_ARCH, 1, 0),
+        stmt(ret, libc::SECCOMP_RET_KILL_PROCESS),
+        stmt(load, SECCOMP_DATA_NR),
+    ];
+    // x32 syscalls share the x86_64 arch value
+    #[cfg(target_arch = "x86_64")]
+    filter.extend([
+        jump(libc::BPF_JGE, 0x4000_0000, 0, 1),
+        stmt(ret, libc::SECCOMP_RET_KILL_PROCESS),
+    ]);
+    for nr in DENIED_SYSCALLS {
+        filter.push(jump(libc::BPF_JEQ, *nr as u32, 0, 1));
+        filter.push(stmt(ret, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32));
+    }
+    filter.extend([
+        jump(libc::BPF_JEQ, libc::SYS_clone3 as u32, 0, 1),
+        stmt(ret, libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+        // Anything but `clone` skips to the final allow
+        jump(libc::BPF_JEQ, libc::SYS_clone as u32, 0, 4),
+        stmt(load, SECCOMP_DATA_ARG0),
+        stmt(
+            libc::BPF_ALU | libc::BPF_AND | libc::BPF_K,
+            DENIED_CLONE_FLAGS,
+        ),
+        jump(libc::BPF_JEQ, 0, 1, 0),
+        stmt(ret, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32),
+    ]);
+    filter.push(stmt(ret, libc::SECCOMP_RET_ALLOW));
+    filter
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// # Safety
+/// Only async-signal-safe calls, so it may run between fork and exec
+unsafe fn write_file(path: &CStr, data: &[u8]) -> io::Result<()> {
+    let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let written = libc::write(fd, data.as_ptr().cast(), data.len());
+    let error = io::Error::last_os_error();
+    libc::close(fd);
+    match written {
+        n if n < 0 => Err(error),
+        n if n as usize != data.len() => Err(io::Error::from_raw_os_error(libc::EIO)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_isolated(script: &str) -> std::process::Output {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        isolate(&mut command);
+        command.output().unwrap()
+    }
+
+    #[test]
+    fn test_filter_ends_by_allowing() {
+        let filter = seccomp_filter();
+        let last = filter.last().unwrap();
+        assert_eq!(last.k, libc::SECCOMP_RET_ALLOW);
+        assert!(filter.len() > DENIED_SYSCALLS.len() * 2);
+    }
+
+    /// What `filter` returns for syscall `nr` with first argument `arg0`
+    fn verdict(filter: &[libc::sock_filter], nr: libc::c_long, arg0: u32) -> u32 {
+        let (mut accumulator, mut pc) = (0u32, 0);
+        loop {
+            let instruction = filter[pc];
+            let code = instruction.code as u32;
+            pc += 1;
+            match code & 0x07 {
+                libc::BPF_LD => {
+                    accumulator = match instruction.k {
+                        SECCOMP_DATA_NR => nr as u32,
+                        SECCOMP_DATA_ARCH => AUDIT_ARCH,
+                        SECCOMP_DATA_ARG0 => arg0,
+                        offset => panic!("unexpected load at {}", offset),
+                    }
+                }
+                libc::BPF_ALU => accumulator &= instruction.k,
+                libc::BPF_JMP => {
+                    let taken = match code & 0xf0 {
+                        libc::BPF_JEQ => accumulator == instruction.k,
+                        libc::BPF_JGE => accumulator >= instruction.k,
+                        op => panic!("unexpected jump {}", op),
+                    };
+                    pc += if taken {
+                        instruction.jt
+                    } else {
+                        instruction.jf
+                    } as usize;
+                }
+                libc::BPF_RET => return instruction.k,
+                class => panic!("unexpected instruction class {}", class),
+            }
+        }
+    }
+
+    #[test]
+    fn test_filter_refuses_new_mount_api_and_namespace_clones() {
+        let filter = seccomp_filter();
+        let eperm = libc::SECCOMP_RET_ERRNO | libc::EPERM as u32;
+        assert_eq!(verdict(&filter, libc::SYS_mount_setattr, 0), eperm);
+        assert_eq!(verdict(&filter, libc::SYS_open_tree, 0), eperm);
+        assert_eq!(
+            verdict(&filter, libc::SYS_clone, libc::CLONE_NEWUSER as u32),
+            eperm
+        );
+        assert_eq!(
+            verdict(&filter, libc::SYS_clone, libc::CLONE_NEWNS as u32),
+            eperm
+        );
+        assert_eq!(
+            verdict(&filter, libc::SYS_clone3, 0),
+            libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32
+        );
+
+        // Plain forks and threads still work
+        let thread = (libc::CLONE_VM | libc::CLONE_FS | libc::CLONE_THREAD) as u32;
+        assert_eq!(
+            verdict(&filter, libc::SYS_clone, thread),
+            libc::SECCOMP_RET_ALLOW
+        );
+        assert_eq!(verdict(&filter, libc::SYS_read, 0), libc::SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn test_isolated_command_cannot_touch_system_or_network() {
+        if !supported() {
+            eprintln!("user namespaces unavailable; skipping");
+            return;
+        }
+        let output = run_isolated("id -u; touch /usr/.bro-sandbox-probe");
+        assert!(!output.status.success());
+        // SAFETY: getuid cannot fail
+        let uid = unsafe { libc::getuid() };
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            uid.to_string()
+        );
+
+        // Only loopback exists in the new network namespace
+        let interfaces = run_isolated("tail -n +3 /proc/net/dev | cut -d: -f1 | tr -d ' '");
+        assert_eq!(String::from_utf8_lossy(&interfaces.stdout).trim(), "lo");
+    }
+}
@@ -0,0 +1,18 @@
+//! Platforms without cgroups or `setrlimit` support here: only the timeout
+//! and output size are enforced
+
+use super::ProcessLimits;
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+pub struct Enforcement;
+
+impl Enforcement {
+    pub fn exceeded(&self, _status: &ExitStatus) -> Option<String> {
+        None
+    }
+}
+
+pub fn apply(_command: &mut Command, _limits: &ProcessLimits, _timeout: Duration) -> Enforcement {
+    Enforcement
+}
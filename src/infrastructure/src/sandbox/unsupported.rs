@@ -0,0 +1,9 @@
+//! Platforms without namespace isolation: commands are only checked
+
+use std::process::Command;
+
+pub fn isolate(_command: &mut Command) {}
+
+pub fn supported() -> bool {
+    false
+}
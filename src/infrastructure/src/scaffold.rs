@@ -0,0 +1,319 @@
+//! Project skeletons for `bro --new <template> <name>`
+//!
+//! Templates are either built in (`rust-cli`, `axum-api`, `react-app`) or a
+//! git repository (`gh:owner/repo`, or any URL git can clone). Placeholders
+//! such as `{{project_name}}` are filled in file contents and paths; anything
+//! else in braces, like JSX `style={{ ... }}`, is left alone. Every path must
+//! stay inside the new project directory, which must not exist yet.
+
+use chrono::Datelike;
+use shared::types::Result;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+/// Built-in templates and what they create
+pub const BUILTIN_TEMPLATES: [(&str, &str); 3] = [
+    ("rust-cli", "Rust command line tool with clap"),
+    ("axum-api", "Rust HTTP API with axum and tokio"),
+    ("react-app", "React app with Vite"),
+];
+
+/// Files of a template: path relative to the project and raw content
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl Template {
+    /// Built-in template, or a git repository for `gh:owner/repo` and URLs
+    pub fn resolve(spec: &str) -> Result<Self> {
+        if let Some(template) = Self::builtin(spec) {
+            return Ok(template);
+        }
+        let url = match spec.strip_prefix("gh:") {
+            Some(repo) => format!("https://github.com/{}.git", repo),
+            None if spec.contains("://") || spec.starts_with("git@") => spec.to_string(),
+            None => {
+                let known: Vec<&str> = BUILTIN_TEMPLATES.iter().map(|(name, _)| *name).collect();
+                return Err(anyhow::anyhow!(
+                    "Unknown template '{}'; use one of {} or a git repository (gh:owner/repo or a URL)",
+                    spec,
+                    known.join(", ")
+                ));
+            }
+        };
+        Self::from_git(&url)
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        let files: &[(&str, &str)] = match name {
+            "rust-cli" => &[
+                (
+                    "Cargo.toml",
+                    include_str!("scaffold_templates/rust-cli/Cargo.toml.tmpl"),
+                ),
+                (
+                    "src/main.rs",
+                    include_str!("scaffold_templates/rust-cli/main.rs.tmpl"),
+                ),
+                (
+                    ".gitignore",
+                    include_str!("scaffold_templates/rust-cli/gitignore.tmpl"),
+                ),
+                (
+                    "README.md",
+                    include_str!("scaffold_templates/rust-cli/README.md.tmpl"),
+                ),
+            ],
+            "axum-api" => &[
+                (
+                    "Cargo.toml",
+                    include_str!("scaffold_templates/axum-api/Cargo.toml.tmpl"),
+                ),
+                (
+                    "src/main.rs",
+                    include_str!("scaffold_templates/axum-api/main.rs.tmpl"),
+                ),
+                (
+                    ".gitignore",
+                    include_str!("scaffold_templates/axum-api/gitignore.tmpl"),
+                ),
+                (
+                    "README.md",
+                    include_str!("scaffold_templates/axum-api/README.md.tmpl"),
+                ),
+            ],
+            "react-app" => &[
+                (
+                    "package.json",
+                    include_str!("scaffold_templates/react-app/package.json.tmpl"),
+                ),
+                (
+                    "index.html",
+                    include_str!("scaffold_templates/react-app/index.html.tmpl"),
+                ),
+                (
+                    "vite.config.js",
+                    include_str!("scaffold_templates/react-app/vite.config.js.tmpl"),
+                ),
+                (
+                    "src/main.jsx",
+                    include_str!("scaffold_templates/react-app/main.jsx.tmpl"),
+                ),
+                (
+                    "src/App.jsx",
+                    include_str!("scaffold_templates/react-app/App.jsx.tmpl"),
+                ),
+                (
+                    ".gitignore",
+                    include_str!("scaffold_templates/react-app/gitignore.tmpl"),
+                ),
+                (
+                    "README.md",
+                    include_str!("scaffold_templates/react-app/README.md.tmpl"),
+                ),
+            ],
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            files: files
+                .iter()
+                .map(|(path, content)| (PathBuf::from(path), content.as_bytes().to_vec()))
+                .collect(),
+        })
+    }
+
+    /// Shallow clone of `url`, without its git history
+    pub fn from_git(url: &str) -> Result<Self> {
+        let checkout = std::env::temp_dir().join(format!(
+            "bro-template-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let output = Command::new("git")
+            .args(["clone", "-q", "--depth", "1", url])
+            .arg(&checkout)
+            .output()?;
+        if !output.status.success() {
+            let _ = std::fs::remove_dir_all(&checkout);
+            return Err(anyhow::anyhow!(
+                "Could not clone template {}: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let mut files = Vec::new();
+        let collected = collect_files(&checkout, &checkout, &mut files);
+        let _ = std::fs::remove_dir_all(&checkout);
+        collected?;
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(Self {
+            name: url.to_string(),
+            files,
+        })
+    }
+}
+
+/// Regular files below `dir`, skipping `.git` and symlinks, which could point
+/// outside the project
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(PathBuf, Vec<u8>)>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() && entry.file_name() != ".git" {
+            collect_files(root, &path, files)?;
+        } else if file_type.is_file() {
+            let relative = path.strip_prefix(root)?.to_path_buf();
+            files.push((relative, std::fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
+
+/// Placeholder values for a project called `name`; `extra` (`KEY=VALUE`)
+/// adds or overrides variables
+pub fn variables(name: &str, extra: &[String]) -> Result<BTreeMap<String, String>> {
+    let author = Command::new("git")
+        .args(["config", "user.name"])
+        .output()
+        .ok()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .filter(|author| !author.is_empty())
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_default();
+    let mut vars = BTreeMap::from([
+        ("project_name".to_string(), name.to_string()),
+        ("crate_name".to_string(), name.replace('-', "_")),
+        ("author".to_string(), author),
+        ("year".to_string(), chrono::Local::now().year().to_string()),
+    ]);
+    for assignment in extra {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid --var '{}'; expected KEY=VALUE", assignment))?;
+        vars.insert(key.trim().to_string(), value.to_string());
+    }
+    Ok(vars)
+}
+
+/// Fill `{{key}}` and `{{ key }}` for every known variable
+pub fn substitute(text: &str, vars: &BTreeMap<String, String>) -> String {
+    let mut text = text.to_string();
+    for (key, value) in vars {
+        for placeholder in [format!("{{{{{}}}}}", key), format!("{{{{ {} }}}}", key)] {
+            text = text.replace(&placeholder, value);
+        }
+    }
+    text
+}
+
+/// Write `template` into the new directory `target`; on failure nothing is
+/// left behind. Returns the files created.
+pub fn scaffold(
+    template: &Template,
+    target: &Path,
+    vars: &BTreeMap<String, String>,
+) -> Result<Vec<PathBuf>> {
+    if target.exists() {
+        return Err(anyhow::anyhow!("{} already exists", target.display()));
+    }
+    let mut planned = Vec::with_capacity(template.files.len());
+    for (path, content) in &template.files {
+        let path = PathBuf::from(substitute(&path.to_string_lossy(), vars));
+        if path.as_os_str().is_empty()
+            || path
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(anyhow::anyhow!(
+                "Template {} writes outside the project: {}",
+                template.name,
+                path.display()
+            ));
+        }
+        let content = match std::str::from_utf8(content) {
+            Ok(text) => substitute(text, vars).into_bytes(),
+            Err(_) => content.clone(),
+        };
+        planned.push((target.join(path), content));
+    }
+
+    std::fs::create_dir_all(target)?;
+    let written = planned.iter().try_for_each(|(path, content)| {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)
+    });
+    if let Err(e) = written {
+        let _ = std::fs::remove_dir_all(target);
+        return Err(e.into());
+    }
+    Ok(planned.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Where `bro --new` puts a project called `name`: a plain relative path
+/// below `parent`
+pub fn project_dir(parent: &Path, name: &str) -> Result<PathBuf> {
+    let relative = Path::new(name);
+    let plain = !name.trim().is_empty()
+        && relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if !plain {
+        return Err(anyhow::anyhow!(
+            "Project name '{}' must be a directory below {}",
+            name,
+            parent.display()
+        ));
+    }
+    Ok(parent.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaffold_fills_placeholders_inside_the_project() {
+        let parent = std::env::temp_dir().join(format!("bro-scaffold-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&parent);
+        std::fs::create_dir_all(&parent).unwrap();
+        assert!(project_dir(&parent, "../escape").is_err());
+        assert!(project_dir(&parent, "/abs").is_err());
+        let target = project_dir(&parent, "my-tool").unwrap();
+
+        let vars = variables("my-tool", &["license=MIT".to_string()]).unwrap();
+        assert_eq!(vars["crate_name"], "my_tool");
+        assert!(variables("x", &["nope".to_string()]).is_err());
+
+        let template = Template::builtin("react-app").unwrap();
+        let files = scaffold(&template, &target, &vars).unwrap();
+        assert_eq!(files.len(), template.files.len());
+        let app = std::fs::read_to_string(target.join("src/App.jsx")).unwrap();
+        assert!(app.contains("<h1>my-tool</h1>"));
+        assert!(app.contains("style={{ fontFamily"));
+        assert!(scaffold(&template, &target, &vars).is_err());
+
+        let escaping = Template {
+            name: "bad".to_string(),
+            files: vec![
+                (PathBuf::from("ok.txt"), b"{{ license }}".to_vec()),
+                (PathBuf::from("../{{project_name}}.txt"), Vec::new()),
+            ],
+        };
+        let other = parent.join("other");
+        assert!(scaffold(&escaping, &other, &vars).is_err());
+        assert!(!other.exists());
+        assert!(Template::resolve("no-such-template").is_err());
+
+        for (name, _) in BUILTIN_TEMPLATES {
+            assert!(Template::builtin(name).is_some());
+        }
+        std::fs::remove_dir_all(&parent).unwrap();
+    }
+}
@@ -0,0 +1,295 @@
+//! Versioned schema migrations for persistent storage
+//!
+//! Each store declares an ordered list of migrations and records the version it
+//! has reached: SQLite in `PRAGMA user_version`, sled in a metadata key and
+//! Qdrant in a `schema_version` field on every point. Pending migrations run at
+//! startup after a backup of the existing data is taken, so a release that
+//! changes a table or payload upgrades old data instead of misreading it.
+
+use chrono::Utc;
+use qdrant_client::qdrant::Value;
+use rusqlite::{Connection, Result as SqlResult};
+use shared::types::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// One step in a store's schema history
+pub struct Migration<F> {
+    /// Version the store is at after this step; versions start at 1
+    pub version: u32,
+    pub description: &'static str,
+    pub apply: F,
+}
+
+pub type SqliteMigration = Migration<fn(&Connection) -> SqlResult<()>>;
+pub type SledMigration = Migration<fn(&sled::Db) -> Result<()>>;
+/// Rewrites the payload of one point written under an older schema
+pub type PayloadMigration = Migration<fn(&mut HashMap<String, Value>)>;
+
+/// Payload key holding the schema version a Qdrant point was written with
+pub const PAYLOAD_VERSION_KEY: &str = "schema_version";
+
+/// Sled key (in the `metadata` tree) holding the session store version
+pub const SLED_VERSION_KEY: &str = "__schema_version";
+
+/// Version reached once every migration has run
+pub fn latest_version<F>(migrations: &[Migration<F>]) -> u32 {
+    migrations.iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+fn pending<F>(migrations: &[Migration<F>], current: u32) -> Vec<&Migration<F>> {
+    let mut pending: Vec<_> = migrations.iter().filter(|m| m.version > current).collect();
+    pending.sort_by_key(|m| m.version);
+    pending
+}
+
+/// Backup location next to `path`, tagged with the version being migrated from
+pub fn backup_path(path: &Path, from_version: u32) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "store".to_string());
+    path.with_file_name(format!(
+        "{}.v{}-{}.bak",
+        name,
+        from_version,
+        Utc::now().format("%Y%m%dT%H%M%S")
+    ))
+}
+
+/// Copy the SQLite database at `db_path` next to it, tagged with `version`
+pub fn backup_sqlite(conn: &Connection, db_path: &Path, version: u32) -> Result<PathBuf> {
+    let backup = backup_path(db_path, version);
+    conn.execute("VACUUM INTO ?1", [backup.to_string_lossy().to_string()])?;
+    Ok(backup)
+}
+
+pub fn has_column(conn: &Connection, table: &str, column: &str) -> SqlResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let col_name: String = row.get(1)?;
+        if col_name == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Bring the SQLite database at `db_path` up to date, returning the backup taken
+/// (if any). Databases created before versioning report version 0, so early
+/// migrations must tolerate tables and columns that already exist.
+pub fn migrate_sqlite(
+    conn: &Connection,
+    db_path: &Path,
+    migrations: &[SqliteMigration],
+) -> Result<Option<PathBuf>> {
+    let current: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let pending = pending(migrations, current);
+    if pending.is_empty() {
+        return Ok(None);
+    }
+
+    let has_tables: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table')",
+        [],
+        |row| row.get(0),
+    )?;
+    let backup = if has_tables {
+        Some(backup_sqlite(conn, db_path, current)?)
+    } else {
+        None
+    };
+
+    for migration in pending {
+        let tx = conn.unchecked_transaction()?;
+        (migration.apply)(&tx).map_err(|e| {
+            anyhow::anyhow!(
+                "Migration {} ({}) of {} failed: {}",
+                migration.version,
+                migration.description,
+                db_path.display(),
+                e
+            )
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        if has_tables {
+            eprintln!(
+                "Migrated {} to schema v{}: {}",
+                db_path.display(),
+                migration.version,
+                migration.description
+            );
+        }
+    }
+    Ok(backup)
+}
+
+/// Bring the sled database at `db_path` up to date, returning the backup taken
+/// (if any)
+pub fn migrate_sled(
+    db: &sled::Db,
+    db_path: &Path,
+    migrations: &[SledMigration],
+) -> Result<Option<PathBuf>> {
+    let metadata = db.open_tree("metadata")?;
+    let current = metadata
+        .get(SLED_VERSION_KEY)?
+        .and_then(|v| std::str::from_utf8(&v).ok()?.parse().ok())
+        .unwrap_or(0);
+    let pending = pending(migrations, current);
+    if pending.is_empty() {
+        return Ok(None);
+    }
+
+    let has_data = db
+        .tree_names()
+        .iter()
+        .filter_map(|name| db.open_tree(name).ok())
+        .any(|tree| !tree.is_empty());
+    let backup = if has_data {
+        db.flush()?;
+        let backup = backup_path(db_path, current);
+        copy_dir(db_path, &backup)?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    for migration in pending {
+        (migration.apply)(db).map_err(|e| {
+            anyhow::anyhow!(
+                "Migration {} ({}) of {} failed: {}",
+                migration.version,
+                migration.description,
+                db_path.display(),
+                e
+            )
+        })?;
+        metadata.insert(SLED_VERSION_KEY, migration.version.to_string().as_bytes())?;
+        db.flush()?;
+        if has_data {
+            eprintln!(
+                "Migrated {} to schema v{}: {}",
+                db_path.display(),
+                migration.version,
+                migration.description
+            );
+        }
+    }
+    Ok(backup)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Schema version recorded on a Qdrant point, 0 for points written before versioning
+pub fn payload_version(payload: &HashMap<String, Value>) -> u32 {
+    use qdrant_client::qdrant::value::Kind;
+    match payload
+        .get(PAYLOAD_VERSION_KEY)
+        .and_then(|v| v.kind.as_ref())
+    {
+        Some(Kind::IntegerValue(v)) => *v as u32,
+        Some(Kind::DoubleValue(v)) => *v as u32,
+        _ => 0,
+    }
+}
+
+/// Apply pending payload migrations to one point and stamp the new version.
+/// Returns false if the payload was already current.
+pub fn migrate_payload(
+    payload: &mut HashMap<String, Value>,
+    migrations: &[PayloadMigration],
+) -> bool {
+    let pending = pending(migrations, payload_version(payload));
+    let Some(last) = pending.last().map(|m| m.version) else {
+        return false;
+    };
+    for migration in pending {
+        (migration.apply)(payload);
+    }
+    payload.insert(PAYLOAD_VERSION_KEY.to_string(), Value::from(last as i64));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQLITE: &[SqliteMigration] = &[
+        Migration {
+            version: 1,
+            description: "notes table",
+            apply: |conn| conn.execute_batch("CREATE TABLE IF NOT EXISTS notes (id TEXT)"),
+        },
+        Migration {
+            version: 2,
+            description: "note body",
+            apply: |conn| {
+                if !has_column(conn, "notes", "body")? {
+                    conn.execute("ALTER TABLE notes ADD COLUMN body TEXT", [])?;
+                }
+                Ok(())
+            },
+        },
+    ];
+
+    #[test]
+    fn test_sqlite_migrations_upgrade_with_backup() {
+        let dir = std::env::temp_dir().join(format!("bro-migrations-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("store.db");
+        let conn = Connection::open(&db_path).unwrap();
+
+        // Fresh databases are created without a backup
+        assert!(migrate_sqlite(&conn, &db_path, &SQLITE[..1])
+            .unwrap()
+            .is_none());
+        conn.execute("INSERT INTO notes (id) VALUES ('a')", [])
+            .unwrap();
+
+        let backup = migrate_sqlite(&conn, &db_path, SQLITE).unwrap().unwrap();
+        assert!(backup.exists());
+        assert!(has_column(&conn, "notes", "body").unwrap());
+        let version: u32 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, latest_version(SQLITE));
+
+        // Nothing left to do on the next startup
+        assert!(migrate_sqlite(&conn, &db_path, SQLITE).unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_payload_migrations_stamp_version() {
+        const PAYLOAD: &[PayloadMigration] = &[Migration {
+            version: 1,
+            description: "default path",
+            apply: |payload| {
+                payload
+                    .entry("path".to_string())
+                    .or_insert_with(|| Value::from(""));
+            },
+        }];
+
+        let mut payload = HashMap::from([("text".to_string(), Value::from("fn main() {}"))]);
+        assert!(migrate_payload(&mut payload, PAYLOAD));
+        assert_eq!(payload_version(&payload), 1);
+        assert!(payload.contains_key("path"));
+        assert!(!migrate_payload(&mut payload, PAYLOAD));
+    }
+}
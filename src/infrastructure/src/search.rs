@@ -1,3 +1,4 @@
+use crate::context_window::ScoredChunk;
 use domain::models::Embedding;
 use std::cmp::Ordering;
 
@@ -16,6 +17,20 @@ impl SearchEngine {
         embeddings: &[Embedding],
         top_k: usize,
     ) -> Vec<String> {
+        Self::find_relevant_scored(query_embedding, embeddings, top_k)
+            .into_iter()
+            .map(|chunk| chunk.text)
+            .collect()
+    }
+
+    /// The `top_k` chunks most similar to the query, best first, with their
+    /// cosine similarity
+    pub fn find_relevant_scored(
+        query_embedding: &[f32],
+        embeddings: &[Embedding],
+        top_k: usize,
+    ) -> Vec<ScoredChunk> {
+        use std::cmp::Reverse;
         use std::collections::BinaryHeap;
 
         #[derive(Debug)]
@@ -32,34 +47,69 @@ impl SearchEngine {
         impl<'a> Eq for Scored<'a> {}
         impl<'a> PartialOrd for Scored<'a> {
             fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                self.score.partial_cmp(&other.score)
+                Some(self.cmp(other))
             }
         }
         impl<'a> Ord for Scored<'a> {
             fn cmp(&self, other: &Self) -> Ordering {
-                self.partial_cmp(other).unwrap_or(Ordering::Equal)
+                self.score
+                    .partial_cmp(&other.score)
+                    .unwrap_or(Ordering::Equal)
             }
         }
 
-        let mut heap: BinaryHeap<Scored> =
-            BinaryHeap::with_capacity(top_k.saturating_mul(2).max(8));
+        // Min-heap of the best candidates so far; the weakest is evicted first
+        let mut heap: BinaryHeap<Reverse<Scored>> = BinaryHeap::with_capacity(top_k + 1);
         for emb in embeddings {
             let score = Self::cosine_similarity(query_embedding, &emb.vector);
-            heap.push(Scored {
+            if score.is_nan() {
+                continue;
+            }
+            heap.push(Reverse(Scored {
                 score,
                 text: emb.text.as_str(),
-            });
-            if heap.len() > top_k * 3 {
+            }));
+            if heap.len() > top_k {
                 heap.pop();
             }
         }
 
-        let mut results: Vec<Scored> = heap.into_iter().collect();
-        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
-        results
+        heap.into_sorted_vec()
             .into_iter()
-            .take(top_k)
-            .map(|s| s.text.to_string())
+            .map(|Reverse(s)| ScoredChunk {
+                text: s.text.to_string(),
+                score: s.score,
+            })
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedding(text: &str, vector: Vec<f32>) -> Embedding {
+        Embedding {
+            id: text.to_string(),
+            vector,
+            text: text.to_string(),
+            path: String::new(),
+            symbol: None,
+            commit: None,
+        }
+    }
+
+    #[test]
+    fn test_scored_search_keeps_best_matches() {
+        let embeddings = vec![
+            embedding("far", vec![0.0, 1.0]),
+            embedding("close", vec![1.0, 0.1]),
+            embedding("exact", vec![1.0, 0.0]),
+            embedding("empty", vec![0.0, 0.0]),
+        ];
+        let results = SearchEngine::find_relevant_scored(&[1.0, 0.0], &embeddings, 2);
+        let texts: Vec<&str> = results.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["exact", "close"]);
+        assert!((results[0].score - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,302 @@
+//! Health checks for the background supervisor's services
+//!
+//! Watcher tasks are meant to run until shutdown, so one that returns or
+//! panics has crashed. It is restarted after an exponential backoff and given
+//! up on after too many crashes in a row; a service that stayed up for
+//! `healthy_after` starts counting afresh. CPU and memory of bro itself, where
+//! the watcher tasks run, and of the processes it spawned are sampled from
+//! /proc, and sustained use over the limits is reported.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Wait before the first restart; doubled for each further one
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Crashes in a row after which a service stays down
+    pub max_restarts: u32,
+    /// Uptime after which a crash no longer counts as "in a row"
+    pub healthy_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_restarts: 5,
+            healthy_after: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Wait before restarting a service that already restarted `restarts` times
+    pub fn backoff(&self, restarts: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(restarts))
+            .min(self.max_backoff)
+    }
+}
+
+/// What to do with a service that just exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashAction {
+    Restart { after: Duration, attempt: u32 },
+    GiveUp { restarts: u32 },
+}
+
+/// Restarts of one service
+#[derive(Debug, Clone, Default)]
+pub struct RestartState {
+    pub restarts: u32,
+}
+
+impl RestartState {
+    /// Record a crash after `uptime` and decide what happens next
+    pub fn on_crash(&mut self, policy: &RestartPolicy, uptime: Duration) -> CrashAction {
+        if uptime >= policy.healthy_after {
+            self.restarts = 0;
+        }
+        if self.restarts >= policy.max_restarts {
+            return CrashAction::GiveUp {
+                restarts: self.restarts,
+            };
+        }
+        let after = policy.backoff(self.restarts);
+        self.restarts += 1;
+        CrashAction::Restart {
+            after,
+            attempt: self.restarts,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResourceLimits {
+    /// Percent of one core
+    pub max_cpu_percent: f64,
+    pub max_memory_bytes: u64,
+    /// Consecutive samples over a limit before it is reported
+    pub sustained_samples: u32,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            max_cpu_percent: 90.0,
+            max_memory_bytes: 2 * 1024 * 1024 * 1024,
+            sustained_samples: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    pub name: String,
+    /// Percent of one core since the previous sample
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+}
+
+/// Fields of /proc/<pid>/stat the watchdog uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProcStat {
+    name: String,
+    ppid: u32,
+    /// User plus system time, in clock ticks
+    cpu_ticks: u64,
+    rss_pages: u64,
+}
+
+/// Samples bro and its descendant processes, remembering CPU times between
+/// samples and how long each process has been over the limits
+#[derive(Debug, Default)]
+pub struct ResourceSampler {
+    previous: HashMap<u32, (u64, Instant)>,
+    over_limit: HashMap<u32, u32>,
+}
+
+impl ResourceSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current usage of this process and every process it spawned; empty
+    /// where /proc is unavailable
+    pub fn sample(&mut self) -> Vec<ProcessUsage> {
+        let Ok(entries) = std::fs::read_dir("/proc") else {
+            return Vec::new();
+        };
+        let stats: HashMap<u32, ProcStat> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let pid = entry.file_name().to_string_lossy().parse::<u32>().ok()?;
+                let text = std::fs::read_to_string(entry.path().join("stat")).ok()?;
+                Some((pid, parse_stat(&text)?))
+            })
+            .collect();
+
+        let mut tree = vec![std::process::id()];
+        let mut index = 0;
+        while let Some(&pid) = tree.get(index) {
+            tree.extend(
+                stats
+                    .iter()
+                    .filter(|(_, stat)| stat.ppid == pid)
+                    .map(|(&child, _)| child),
+            );
+            index += 1;
+        }
+
+        let now = Instant::now();
+        let (tick_rate, page_size) = clock_and_page();
+        let usage: Vec<ProcessUsage> = tree
+            .iter()
+            .filter_map(|pid| {
+                let stat = stats.get(pid)?;
+                let cpu_percent = match self.previous.get(pid) {
+                    Some(&(ticks, at)) if now > at => {
+                        let seconds = stat.cpu_ticks.saturating_sub(ticks) as f64 / tick_rate;
+                        seconds / now.duration_since(at).as_secs_f64() * 100.0
+                    }
+                    _ => 0.0,
+                };
+                Some(ProcessUsage {
+                    pid: *pid,
+                    name: stat.name.clone(),
+                    cpu_percent,
+                    memory_bytes: stat.rss_pages * page_size,
+                })
+            })
+            .collect();
+        self.previous = usage
+            .iter()
+            .map(|process| (process.pid, (stats[&process.pid].cpu_ticks, now)))
+            .collect();
+        usage
+    }
+
+    /// Processes that just stayed over `limits` for the sustained number of
+    /// samples; each stretch over a limit is reported once
+    pub fn over_limits(&mut self, usage: &[ProcessUsage], limits: &ResourceLimits) -> Vec<String> {
+        let mut reports = Vec::new();
+        let live: HashSet<u32> = usage.iter().map(|process| process.pid).collect();
+        self.over_limit.retain(|pid, _| live.contains(pid));
+        for process in usage {
+            let over = process.cpu_percent > limits.max_cpu_percent
+                || process.memory_bytes > limits.max_memory_bytes;
+            if !over {
+                self.over_limit.remove(&process.pid);
+                continue;
+            }
+            let samples = self.over_limit.entry(process.pid).or_insert(0);
+            *samples += 1;
+            if *samples == limits.sustained_samples {
+                reports.push(format!(
+                    "{} (pid {}) is using {:.0}% CPU and {} MiB of memory",
+                    process.name,
+                    process.pid,
+                    process.cpu_percent,
+                    process.memory_bytes / (1024 * 1024)
+                ));
+            }
+        }
+        reports
+    }
+}
+
+/// Parse /proc/<pid>/stat; the name may itself contain spaces and parentheses
+fn parse_stat(text: &str) -> Option<ProcStat> {
+    let open = text.find('(')?;
+    let close = text.rfind(')')?;
+    let name = text.get(open + 1..close)?.to_string();
+    // Fields after the name start with the state, field 3 in proc(5)
+    let fields: Vec<&str> = text.get(close + 1..)?.split_whitespace().collect();
+    let field = |number: usize| fields.get(number - 3)?.parse::<u64>().ok();
+    Some(ProcStat {
+        name,
+        ppid: field(4)? as u32,
+        cpu_ticks: field(14)? + field(15)?,
+        rss_pages: field(24)?,
+    })
+}
+
+fn clock_and_page() -> (f64, u64) {
+    // SAFETY: sysconf only reads configuration values
+    let (ticks, page) = unsafe {
+        (
+            libc::sysconf(libc::_SC_CLK_TCK),
+            libc::sysconf(libc::_SC_PAGESIZE),
+        )
+    };
+    (
+        if ticks > 0 { ticks as f64 } else { 100.0 },
+        if page > 0 { page as u64 } else { 4096 },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restarts_back_off_then_give_up() {
+        let policy = RestartPolicy {
+            max_restarts: 3,
+            ..RestartPolicy::default()
+        };
+        let mut state = RestartState::default();
+        let quick = Duration::from_secs(1);
+        let waits: Vec<CrashAction> = (0..4).map(|_| state.on_crash(&policy, quick)).collect();
+        assert_eq!(
+            waits,
+            vec![
+                CrashAction::Restart {
+                    after: Duration::from_secs(1),
+                    attempt: 1
+                },
+                CrashAction::Restart {
+                    after: Duration::from_secs(2),
+                    attempt: 2
+                },
+                CrashAction::Restart {
+                    after: Duration::from_secs(4),
+                    attempt: 3
+                },
+                CrashAction::GiveUp { restarts: 3 },
+            ]
+        );
+        assert!(matches!(
+            state.on_crash(&policy, policy.healthy_after),
+            CrashAction::Restart { attempt: 1, .. }
+        ));
+        assert_eq!(policy.backoff(20), policy.max_backoff);
+
+        let stat = parse_stat(
+            "4242 (cargo (test)) S 17 4242 17 0 -1 4194304 900 0 0 0 120 30 0 0 20 0 1 0 500 80000000 2048 18446744073709551615",
+        )
+        .unwrap();
+        assert_eq!(stat.name, "cargo (test)");
+        assert_eq!((stat.ppid, stat.cpu_ticks, stat.rss_pages), (17, 150, 2048));
+
+        let limits = ResourceLimits {
+            sustained_samples: 2,
+            ..ResourceLimits::default()
+        };
+        let hot = vec![ProcessUsage {
+            pid: 1,
+            name: "cargo".to_string(),
+            cpu_percent: 99.0,
+            memory_bytes: 0,
+        }];
+        let mut sampler = ResourceSampler::new();
+        assert!(sampler.over_limits(&hot, &limits).is_empty());
+        assert_eq!(sampler.over_limits(&hot, &limits).len(), 1);
+        assert!(sampler.over_limits(&hot, &limits).is_empty());
+        assert!(sampler.sample().iter().any(|p| p.pid == std::process::id()));
+    }
+}
@@ -0,0 +1,333 @@
+//! Merging a forked session back into another
+//!
+//! A fork shares the history of the session it was forked from up to the
+//! fork. Merging appends the fork's later conversation and applied changes to
+//! the target session and, when the two are pinned to different workspaces
+//! such as git worktrees, copies the files those changes touched. A file the
+//! target changed as well since the fork is a conflict, resolved by keeping
+//! ours, taking theirs or an edited version before anything is written.
+
+use crate::recycle_bin::RecycleBin;
+use crate::session_store::{AppliedChange, ConversationMessage, Session};
+use chrono::Utc;
+use shared::types::Result;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    KeepOurs,
+    KeepTheirs,
+    Edited(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct FileMerge {
+    /// Path relative to the workspace roots
+    pub path: PathBuf,
+    /// Content in the target's workspace; None when the file does not exist
+    pub ours: Option<String>,
+    /// Content in the fork's workspace
+    pub theirs: Option<String>,
+    /// Both sessions changed the file since the fork
+    pub conflict: bool,
+    pub resolution: Option<Resolution>,
+}
+
+impl FileMerge {
+    /// Content the target ends up with, `Some(None)` for a deleted file;
+    /// None while a conflict is unresolved
+    fn merged(&self) -> Option<Option<&str>> {
+        match (&self.resolution, self.conflict) {
+            (Some(Resolution::KeepOurs), _) => Some(self.ours.as_deref()),
+            (Some(Resolution::KeepTheirs), _) | (None, false) => Some(self.theirs.as_deref()),
+            (Some(Resolution::Edited(content)), _) => Some(Some(content)),
+            (None, true) => None,
+        }
+    }
+}
+
+/// What merging a fork into a target session would change
+#[derive(Debug, Clone)]
+pub struct SessionMerge {
+    pub source: String,
+    pub target: String,
+    /// The fork's messages after the histories diverged
+    pub messages: Vec<ConversationMessage>,
+    /// The fork's changes the target does not have
+    pub changes: Vec<AppliedChange>,
+    /// Files differing between the workspaces that the fork's changes touched
+    pub files: Vec<FileMerge>,
+    /// Both sessions work in the same directory, so their files are shared
+    pub shared_workspace: bool,
+    target_root: PathBuf,
+}
+
+impl SessionMerge {
+    pub fn plan(
+        source: &Session,
+        target: &Session,
+        source_root: &Path,
+        target_root: &Path,
+    ) -> Self {
+        let common = source
+            .conversation_history
+            .iter()
+            .zip(&target.conversation_history)
+            .take_while(|(theirs, ours)| {
+                theirs.role == ours.role
+                    && theirs.content == ours.content
+                    && theirs.timestamp == ours.timestamp
+            })
+            .count();
+        let source_ids: HashSet<&str> = ids(source);
+        let target_ids: HashSet<&str> = ids(target);
+        let changes: Vec<AppliedChange> = source
+            .applied_changes
+            .iter()
+            .filter(|change| !target_ids.contains(change.id.as_str()))
+            .cloned()
+            .collect();
+        let ours_touched: BTreeSet<PathBuf> = target
+            .applied_changes
+            .iter()
+            .filter(|change| !source_ids.contains(change.id.as_str()))
+            .flat_map(|change| &change.files_affected)
+            .map(|path| relative(path, target_root))
+            .collect();
+
+        let shared_workspace = same_dir(source_root, target_root);
+        let files = if shared_workspace {
+            Vec::new()
+        } else {
+            changes
+                .iter()
+                .flat_map(|change| &change.files_affected)
+                .map(|path| relative(path, source_root))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .filter_map(|path| {
+                    let theirs = std::fs::read_to_string(source_root.join(&path)).ok();
+                    let ours = std::fs::read_to_string(target_root.join(&path)).ok();
+                    (theirs != ours).then(|| FileMerge {
+                        conflict: ours_touched.contains(&path),
+                        path,
+                        ours,
+                        theirs,
+                        resolution: None,
+                    })
+                })
+                .collect()
+        };
+
+        Self {
+            source: source.metadata.name.clone(),
+            target: target.metadata.name.clone(),
+            messages: source.conversation_history[common..].to_vec(),
+            changes,
+            files,
+            shared_workspace,
+            target_root: target_root.to_path_buf(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty() && self.changes.is_empty() && self.files.is_empty()
+    }
+
+    pub fn conflicts(&self) -> usize {
+        self.files.iter().filter(|file| file.conflict).count()
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.files.iter().all(|file| file.merged().is_some())
+    }
+
+    /// Write the merged files, trashing deleted ones, and append the fork's
+    /// history to `target`, which the caller saves
+    pub fn commit(&self, target: &mut Session) -> Result<()> {
+        if !self.is_resolved() {
+            return Err(anyhow::anyhow!(
+                "Resolve every conflict before committing the merge"
+            ));
+        }
+        let trash = RecycleBin::for_project(&self.target_root);
+        for file in &self.files {
+            let path = self.target_root.join(&file.path);
+            match file.merged().expect("checked above") {
+                Some(content) if file.ours.as_deref() != Some(content) => {
+                    if let Some(parent) = path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&path, content)?;
+                }
+                Some(_) => {}
+                None if path.exists() => {
+                    trash.trash(&path)?;
+                }
+                None => {}
+            }
+        }
+
+        target
+            .conversation_history
+            .extend(self.messages.iter().cloned());
+        // Keep the conversation in the order it happened across both sessions
+        target
+            .conversation_history
+            .sort_by_key(|message| message.timestamp);
+        target.applied_changes.extend(self.changes.iter().cloned());
+        target.metadata.change_count += self.changes.len() as u32;
+        target.metadata.last_used = Utc::now();
+        Ok(())
+    }
+}
+
+/// `ours` and `theirs` with conflict markers around the lines that differ
+pub fn conflict_markers(ours: &str, theirs: &str, our_name: &str, their_name: &str) -> String {
+    let diff = similar::TextDiff::from_lines(ours, theirs);
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+    let mut merged = String::new();
+    for op in diff.ops() {
+        if op.tag() == similar::DiffTag::Equal {
+            merged.extend(old[op.old_range()].iter().copied());
+            continue;
+        }
+        merged.push_str(&format!("<<<<<<< {}\n", our_name));
+        merged.extend(old[op.old_range()].iter().copied());
+        ensure_newline(&mut merged);
+        merged.push_str("=======\n");
+        merged.extend(new[op.new_range()].iter().copied());
+        ensure_newline(&mut merged);
+        merged.push_str(&format!(">>>>>>> {}\n", their_name));
+    }
+    merged
+}
+
+fn ensure_newline(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+fn ids(session: &Session) -> HashSet<&str> {
+    session
+        .applied_changes
+        .iter()
+        .map(|change| change.id.as_str())
+        .collect()
+}
+
+/// `path` relative to `root`; recorded paths may be either
+fn relative(path: &str, root: &Path) -> PathBuf {
+    let path = Path::new(path);
+    path.strip_prefix(root).unwrap_or(path).to_path_buf()
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::SessionMetadata;
+
+    fn session(name: &str, messages: &[&str], changes: &[(&str, &str)]) -> Session {
+        let start = chrono::DateTime::<Utc>::UNIX_EPOCH;
+        Session {
+            metadata: SessionMetadata {
+                name: name.to_string(),
+                created_at: start,
+                last_used: start,
+                goal_summary: String::new(),
+                change_count: changes.len() as u32,
+                is_active: true,
+                token_usage: Default::default(),
+            },
+            conversation_history: messages
+                .iter()
+                .enumerate()
+                .map(|(i, content)| ConversationMessage {
+                    role: "user".to_string(),
+                    content: content.to_string(),
+                    timestamp: start + chrono::Duration::seconds(i as i64),
+                })
+                .collect(),
+            applied_changes: changes
+                .iter()
+                .map(|(id, file)| AppliedChange {
+                    id: id.to_string(),
+                    description: format!("change {}", id),
+                    timestamp: start,
+                    files_affected: vec![file.to_string()],
+                })
+                .collect(),
+            undo_stack: Vec::new(),
+            background_state: None,
+            workspace: None,
+            confirmation_grants: Vec::new(),
+            environment_snapshots: Vec::new(),
+            timeline: Vec::new(),
+            forked_from: None,
+            version: 0,
+            run_checkpoint: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_replays_fork_and_stops_at_conflicts() {
+        let dir = std::env::temp_dir().join(format!("bro-merge-{}", uuid::Uuid::new_v4()));
+        let (ours_root, theirs_root) = (dir.join("main"), dir.join("experiment"));
+        std::fs::create_dir_all(&ours_root).unwrap();
+        std::fs::create_dir_all(&theirs_root).unwrap();
+        std::fs::write(ours_root.join("a.rs"), "fn a() {}\nfn ours() {}\n").unwrap();
+        std::fs::write(theirs_root.join("a.rs"), "fn a() {}\nfn theirs() {}\n").unwrap();
+        std::fs::write(theirs_root.join("b.rs"), "fn b() {}\n").unwrap();
+
+        let mut main = session(
+            "main",
+            &["plan", "our step"],
+            &[("1", "x.rs"), ("2", "a.rs")],
+        );
+        let experiment = session(
+            "experiment",
+            &["plan", "their step"],
+            &[("1", "x.rs"), ("3", "a.rs"), ("4", "b.rs")],
+        );
+        let mut merge = SessionMerge::plan(&experiment, &main, &theirs_root, &ours_root);
+        assert_eq!(merge.messages.len(), 1);
+        assert_eq!(merge.changes.len(), 2);
+        assert_eq!(merge.files.len(), 2);
+        assert_eq!(merge.conflicts(), 1);
+        assert!(merge.commit(&mut main.clone()).is_err());
+
+        let conflict = merge.files.iter_mut().find(|file| file.conflict).unwrap();
+        assert_eq!(
+            conflict_markers(
+                conflict.ours.as_deref().unwrap(),
+                conflict.theirs.as_deref().unwrap(),
+                "main",
+                "experiment"
+            ),
+            "fn a() {}\n<<<<<<< main\nfn ours() {}\n=======\nfn theirs() {}\n>>>>>>> experiment\n"
+        );
+        conflict.resolution = Some(Resolution::KeepOurs);
+        merge.commit(&mut main).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(ours_root.join("a.rs")).unwrap(),
+            "fn a() {}\nfn ours() {}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(ours_root.join("b.rs")).unwrap(),
+            "fn b() {}\n"
+        );
+        assert_eq!(main.conversation_history.len(), 3);
+        assert_eq!(main.metadata.change_count, 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
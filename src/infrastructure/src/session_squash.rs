@@ -0,0 +1,183 @@
+//! One reviewable commit per build session, for `/finish`
+//!
+//! Build mode commits after every operation. The commit checked out when the
+//! operations started is remembered, and `/finish` folds everything since
+//! then into a single commit, optionally pushing the branch afterwards.
+//! Changes that were never committed, and staged work of the user's own,
+//! are left alone.
+
+use crate::git_repo::GitRepo;
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone)]
+pub struct SessionCommits {
+    root: PathBuf,
+    base: String,
+}
+
+impl SessionCommits {
+    /// Remember HEAD of the repository containing `path`; None outside a
+    /// repository or before its first commit
+    pub fn start(path: &Path) -> Option<Self> {
+        let repo = GitRepo::discover(path)?;
+        let base = repo.head()?;
+        Some(Self {
+            root: repo.root().to_path_buf(),
+            base,
+        })
+    }
+
+    /// Commit checked out when the session started
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Subjects of the commits made since the session started, oldest first
+    pub fn subjects(&self) -> Result<Vec<String>> {
+        let range = format!("{}..HEAD", self.base);
+        Ok(
+            git(&self.root, &["log", "--reverse", "--format=%s", &range])?
+                .lines()
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+
+    /// `git diff --stat` of the whole session
+    pub fn diff_stat(&self) -> Result<String> {
+        git(&self.root, &["diff", "--stat", &self.base, "HEAD"])
+    }
+
+    /// Cumulative diff of the whole session
+    pub fn diff(&self) -> Result<String> {
+        git(&self.root, &["diff", &self.base, "HEAD"])
+    }
+
+    /// Replace the session's commits with one commit; returns its hash
+    pub fn squash(&self, message: &str) -> Result<String> {
+        if git(
+            &self.root,
+            &["merge-base", "--is-ancestor", &self.base, "HEAD"],
+        )
+        .is_err()
+        {
+            return Err(anyhow::anyhow!(
+                "HEAD no longer builds on {}; squash the commits by hand",
+                short(&self.base)
+            ));
+        }
+        if self.subjects()?.is_empty() {
+            return Err(anyhow::anyhow!("No commits since the build started"));
+        }
+        let staged = git(&self.root, &["diff", "--cached", "--name-only"])?;
+        if !staged.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "Staged changes would end up in the squashed commit; commit or unstage them first"
+            ));
+        }
+
+        let head = git(&self.root, &["rev-parse", "HEAD"])?;
+        git(&self.root, &["reset", "-q", "--soft", &self.base])?;
+        if let Err(e) = git(&self.root, &["commit", "-q", "-m", message]) {
+            let _ = git(&self.root, &["reset", "-q", "--soft", head.trim()]);
+            return Err(e);
+        }
+        Ok(git(&self.root, &["rev-parse", "HEAD"])?.trim().to_string())
+    }
+
+    /// Push the current branch to its upstream, or to `origin` as its new
+    /// upstream; returns the branch name
+    pub fn push(&self) -> Result<String> {
+        let branch = git(&self.root, &["symbolic-ref", "--short", "-q", "HEAD"])
+            .map_err(|_| anyhow::anyhow!("Cannot push from a detached HEAD"))?
+            .trim()
+            .to_string();
+        let upstream = git(
+            &self.root,
+            &["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{u}"],
+        );
+        match upstream {
+            Ok(_) => git(&self.root, &["push", "-q"])?,
+            Err(_) => git(&self.root, &["push", "-q", "-u", "origin", &branch])?,
+        };
+        Ok(branch)
+    }
+}
+
+/// Squash message used when no model can write one: the goal as subject and
+/// the files changed as body
+pub fn fallback_message(goal: &str, diff_stat: &str) -> String {
+    let subject = goal.lines().next().unwrap_or("").trim();
+    let stat = diff_stat.trim_end();
+    if stat.is_empty() {
+        format!("feat: {}", subject)
+    } else {
+        format!("feat: {}\n\nFiles changed:\n{}", subject, stat)
+    }
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(8)]
+}
+
+fn git(root: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_commits_squash_into_one() {
+        let dir = std::env::temp_dir().join(format!("bro-squash-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let run = |args: &[&str]| git(&dir, args).unwrap();
+        run(&["init", "-q", "-b", "main"]);
+        run(&["config", "user.name", "t"]);
+        run(&["config", "user.email", "t@t"]);
+        std::fs::write(dir.join("a.txt"), "one\n").unwrap();
+        run(&["add", "-A"]);
+        run(&["commit", "-q", "-m", "initial"]);
+
+        let session = SessionCommits::start(&dir).unwrap();
+        assert!(session.squash("nothing").is_err());
+        for step in 1..=3 {
+            std::fs::write(dir.join(format!("step{}.txt", step)), "x\n").unwrap();
+            run(&["add", "-A"]);
+            run(&[
+                "commit",
+                "-q",
+                "-m",
+                &format!("feat: steps (step {}/3)", step),
+            ]);
+        }
+        assert_eq!(session.subjects().unwrap().len(), 3);
+        assert!(session.diff().unwrap().contains("step2.txt"));
+
+        let message = fallback_message("add steps", &session.diff_stat().unwrap());
+        assert!(message.starts_with("feat: add steps\n\nFiles changed:\n"));
+        let head = run(&["rev-parse", "HEAD"]);
+        let squashed = session.squash(&message).unwrap();
+        assert_ne!(squashed, head.trim());
+        assert_eq!(session.subjects().unwrap(), vec!["feat: add steps"]);
+        assert_eq!(run(&["rev-parse", "HEAD~1"]).trim(), session.base());
+        assert!(dir.join("step3.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,24 @@
+use crate::config::PowerUserConfig;
+use crate::credentials::{CredentialStore, SESSION_KEY};
+use crate::environment_snapshot::EnvironmentSnapshot;
+use crate::file_lock::FileLock;
+use crate::run_checkpoint::RunCheckpoint;
+use crate::sandbox::confirmation_rules::ConfirmationGrant;
+use crate::schema_migrations::{self, Migration, SledMigration};
+use crate::session_timeline::TimelineEvent;
+use crate::token_usage::TokenUsage;
+use crate::write_conflicts::{ConflictChoice, ConflictPolicy, ConflictResolver, WriteConflict};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use shared::platform;
 use sled::{Db, Tree};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 /// Session metadata for listing and management
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +29,8 @@ pub struct SessionMetadata {
     pub goal_summary: String,
     pub change_count: u32,
     pub is_active: bool,
+    #[serde(default)]
+    pub token_usage: TokenUsage,
 }
 
 /// Complete session state
@@ -23,6 +41,172 @@ pub struct Session {
     pub applied_changes: Vec<AppliedChange>,
     pub undo_stack: Vec<UndoEntry>,
     pub background_state: Option<serde_json::Value>,
+    /// Where the session runs, restored when it is continued
+    #[serde(default)]
+    pub workspace: Option<SessionWorkspace>,
+    /// Confirmation answers the user asked to remember
+    #[serde(default)]
+    pub confirmation_grants: Vec<ConfirmationGrant>,
+    /// Environment of each build run, oldest first
+    #[serde(default)]
+    pub environment_snapshots: Vec<EnvironmentSnapshot>,
+    /// Queries, plans, operations, commits and test runs, oldest first
+    #[serde(default)]
+    pub timeline: Vec<TimelineEvent>,
+    /// Session this one was forked from, the default merge target
+    #[serde(default)]
+    pub forked_from: Option<String>,
+    /// Bumped on every save, so a write based on an older copy is noticed
+    #[serde(default)]
+    pub version: u64,
+    /// The last `--run` or `--ai-agent` execution, while it is unfinished
+    #[serde(default)]
+    pub run_checkpoint: Option<RunCheckpoint>,
+}
+
+impl Session {
+    /// Both copies of a session written concurrently combined: the union of
+    /// their conversations, changes and remembered answers
+    pub fn merge_concurrent(&self, theirs: &Session) -> Session {
+        let mut merged = self.clone();
+        for message in &theirs.conversation_history {
+            let known = merged.conversation_history.iter().any(|m| {
+                m.timestamp == message.timestamp
+                    && m.role == message.role
+                    && m.content == message.content
+            });
+            if !known {
+                merged.conversation_history.push(message.clone());
+            }
+        }
+        merged
+            .conversation_history
+            .sort_by_key(|message| message.timestamp);
+
+        for change in &theirs.applied_changes {
+            if !merged.applied_changes.iter().any(|c| c.id == change.id) {
+                merged.applied_changes.push(change.clone());
+            }
+        }
+        merged
+            .applied_changes
+            .sort_by_key(|change| change.timestamp);
+        for entry in &theirs.undo_stack {
+            if !merged
+                .undo_stack
+                .iter()
+                .any(|e| e.change_id == entry.change_id)
+            {
+                merged.undo_stack.push(entry.clone());
+            }
+        }
+        merged.undo_stack.sort_by_key(|entry| entry.timestamp);
+        for grant in &theirs.confirmation_grants {
+            if !merged.confirmation_grants.contains(grant) {
+                merged.confirmation_grants.push(grant.clone());
+            }
+        }
+        for snapshot in &theirs.environment_snapshots {
+            if !merged.environment_snapshots.contains(snapshot) {
+                merged.environment_snapshots.push(snapshot.clone());
+            }
+        }
+        for event in &theirs.timeline {
+            if !merged.timeline.contains(event) {
+                merged.timeline.push(event.clone());
+            }
+        }
+        merged.timeline.sort_by_key(|event| event.at);
+        if let Some(theirs) = &theirs.run_checkpoint {
+            let newer = merged
+                .run_checkpoint
+                .as_ref()
+                .map_or(true, |ours| ours.updated_at < theirs.updated_at);
+            if newer {
+                merged.run_checkpoint = Some(theirs.clone());
+            }
+        }
+
+        merged.metadata.last_used = self.metadata.last_used.max(theirs.metadata.last_used);
+        merged.metadata.change_count = merged.applied_changes.len() as u32;
+        if theirs.metadata.token_usage.total() > self.metadata.token_usage.total() {
+            merged.metadata.token_usage = theirs.metadata.token_usage;
+        }
+        if merged.metadata.goal_summary.is_empty() {
+            merged.metadata.goal_summary = theirs.metadata.goal_summary.clone();
+        }
+        merged
+    }
+
+    /// One-line description used when asking about a conflict
+    fn summary(&self) -> String {
+        format!(
+            "{} message(s), {} change(s), last used {}",
+            self.conversation_history.len(),
+            self.applied_changes.len(),
+            self.metadata.last_used.format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+}
+
+/// Build environments kept per session
+const MAX_ENVIRONMENT_SNAPSHOTS: usize = 50;
+
+/// Timeline events kept per session
+const MAX_TIMELINE_EVENTS: usize = 2000;
+
+/// Workspace a session is pinned to, so continuing it from another directory
+/// operates on the same project with the same environment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionWorkspace {
+    pub root: PathBuf,
+    /// Variables set before the session's commands run
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Branch the session's changes are meant for
+    #[serde(default)]
+    pub branch: Option<String>,
+}
+
+impl SessionWorkspace {
+    /// Pin `root` on the branch currently checked out there
+    pub fn capture(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            env: BTreeMap::new(),
+            branch: crate::git_repo::GitRepo::discover(root).and_then(|repo| repo.branch()),
+        }
+    }
+
+    /// Enter the workspace: change to its root and set its environment.
+    /// Returns a warning when a different branch is checked out.
+    pub fn enter(&self) -> Result<Option<String>> {
+        std::env::set_current_dir(&self.root).with_context(|| {
+            format!("Pinned workspace {} is not accessible", self.root.display())
+        })?;
+        for (key, value) in &self.env {
+            std::env::set_var(key, value);
+        }
+        let Some(branch) = &self.branch else {
+            return Ok(None);
+        };
+        let current = crate::git_repo::GitRepo::discover(&self.root).and_then(|repo| repo.branch());
+        Ok((current.as_ref() != Some(branch)).then(|| {
+            format!(
+                "session is pinned to branch '{}' but '{}' is checked out",
+                branch,
+                current.as_deref().unwrap_or("a detached HEAD")
+            )
+        }))
+    }
+}
+
+/// Pinned session found in the cross-project index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PinnedSession {
+    pub name: String,
+    pub root: PathBuf,
+    pub last_used: DateTime<Utc>,
 }
 
 /// Conversation message
@@ -50,12 +234,106 @@ pub struct UndoEntry {
     pub timestamp: DateTime<Utc>,
 }
 
+/// Schema history of the session database
+const MIGRATIONS: &[SledMigration] = &[Migration {
+    version: 1,
+    description: "rewrite sessions and session list with token usage totals",
+    apply: |db| {
+        let sessions_tree = db.open_tree("sessions")?;
+        let mut list = Vec::new();
+        for entry in sessions_tree.scan_prefix("session:") {
+            let (key, data) = entry?;
+            let session: Session = serde_json::from_slice(&data)
+                .with_context(|| format!("Unreadable session {}", String::from_utf8_lossy(&key)))?;
+            sessions_tree.insert(key, serde_json::to_vec(&session)?)?;
+            list.push(session.metadata);
+        }
+        db.open_tree("metadata")?
+            .insert("session:list", serde_json::to_vec(&list)?)?;
+        Ok(())
+    },
+}];
+
+/// Prefix of encrypted values, followed by the nonce and the ciphertext
+const ENCRYPTED_MAGIC: &[u8] = b"bro-enc1";
+
+/// AES-256-GCM over stored sessions and the session list, with a key per
+/// project derived from the `session-key` credential
+struct SessionCipher(Aes256Gcm);
+
+impl SessionCipher {
+    /// The cipher for `project_hash`, creating the master key in the
+    /// credential store on first use
+    fn load(credentials: &CredentialStore, project_hash: &str) -> Result<Self> {
+        if let Some(cipher) = Self::existing(credentials, project_hash)? {
+            return Ok(cipher);
+        }
+        let key = Aes256Gcm::generate_key(OsRng).to_vec();
+        let engine = base64::engine::general_purpose::STANDARD;
+        credentials.set_blocking(SESSION_KEY, &engine.encode(&key))?;
+        Ok(Self::derive(&key, project_hash))
+    }
+
+    /// The cipher for `project_hash` if the master key exists
+    fn existing(credentials: &CredentialStore, project_hash: &str) -> Result<Option<Self>> {
+        let Some(encoded) = credentials.get_blocking(SESSION_KEY)? else {
+            return Ok(None);
+        };
+        let master = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .context("The session-key credential is not base64")?;
+        Ok(Some(Self::derive(&master, project_hash)))
+    }
+
+    fn derive(master: &[u8], project_hash: &str) -> Self {
+        let key = blake3::derive_key(
+            "bro session store v1",
+            &[master, project_hash.as_bytes()].concat(),
+        );
+        Self(Aes256Gcm::new(&key.into()))
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt session"))?;
+        Ok([ENCRYPTED_MAGIC, nonce.as_slice(), &ciphertext].concat())
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        let body = &sealed[ENCRYPTED_MAGIC.len()..];
+        if body.len() < 12 {
+            return Err(anyhow::anyhow!("Truncated encrypted session"));
+        }
+        let (nonce, ciphertext) = body.split_at(12);
+        self.0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Cannot decrypt session; the session key has changed"))
+    }
+}
+
 /// Session store using sled for persistent storage
+///
+/// With `encrypt_sessions` in the config, sessions and the session list are
+/// encrypted at rest. Values written before the setting changed are read
+/// either way, and [`SessionStore::migrate_encryption`] rewrites them.
 pub struct SessionStore {
     db: Db,
     sessions_tree: Tree,
     metadata_tree: Tree,
     project_hash: String,
+    /// Encrypts what is written; None when encryption is off
+    cipher: Option<SessionCipher>,
+    /// Opens values encrypted before encryption was turned off, loaded from
+    /// the credential store on first need
+    reader: OnceLock<SessionCipher>,
+    credentials: CredentialStore,
+    conflict_policy: ConflictPolicy,
+    resolver: Option<ConflictResolver>,
+    /// Per session, each version this process wrote and the one it replaced
+    own_writes: Mutex<HashMap<String, HashMap<u64, u64>>>,
 }
 
 impl SessionStore {
@@ -65,13 +343,18 @@ impl SessionStore {
         let project_hash = blake3::hash(project_path.as_bytes()).to_hex().to_string();
 
         // Create data directory
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let data_dir = PathBuf::from(home).join(".ai-agent").join("data");
+        let data_dir = platform::home_dir().join(".ai-agent").join("data");
         std::fs::create_dir_all(&data_dir).context("Failed to create data directory")?;
 
-        // Open sled database
         let db_path = data_dir.join(format!("{}.sled", project_hash));
-        let db = sled::open(&db_path).context("Failed to open sled database")?;
+        Self::open(&db_path, project_hash, PowerUserConfig::load())
+    }
+
+    fn open(db_path: &Path, project_hash: String, power_user: PowerUserConfig) -> Result<Self> {
+        // Open sled database
+        let db = sled::open(db_path).context("Failed to open sled database")?;
+        schema_migrations::migrate_sled(&db, db_path, MIGRATIONS)
+            .context("Failed to migrate session database")?;
 
         // Get trees
         let sessions_tree = db
@@ -81,14 +364,98 @@ impl SessionStore {
             .open_tree("metadata")
             .context("Failed to open metadata tree")?;
 
+        let credentials = CredentialStore::new(power_user.credentials);
+        let cipher = if power_user.encrypt_sessions {
+            Some(
+                SessionCipher::load(&credentials, &project_hash)
+                    .context("Session encryption is on but the session key is unavailable")?,
+            )
+        } else {
+            None
+        };
+
         Ok(Self {
             db,
             sessions_tree,
             metadata_tree,
             project_hash,
+            cipher,
+            reader: OnceLock::new(),
+            credentials,
+            conflict_policy: power_user.write_conflict_policy,
+            resolver: None,
+            own_writes: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Ask `resolver` how to settle sessions changed by another process,
+    /// under the interactive conflict policy
+    pub fn with_conflict_resolver(mut self, resolver: ConflictResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Stored bytes for `data`, encrypted when encryption is on
+    fn encode(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        match &self.cipher {
+            Some(cipher) => cipher.seal(&data),
+            None => Ok(data),
+        }
+    }
+
+    /// Plaintext of stored bytes, encrypted or not
+    fn decode(&self, stored: &[u8]) -> Result<Vec<u8>> {
+        if !stored.starts_with(ENCRYPTED_MAGIC) {
+            return Ok(stored.to_vec());
+        }
+        match &self.cipher {
+            Some(cipher) => cipher.open(stored),
+            None => self.reader()?.open(stored),
+        }
+    }
+
+    fn reader(&self) -> Result<&SessionCipher> {
+        if let Some(cipher) = self.reader.get() {
+            return Ok(cipher);
+        }
+        let cipher =
+            SessionCipher::existing(&self.credentials, &self.project_hash)?.ok_or_else(|| {
+                anyhow::anyhow!("Sessions are encrypted but the session-key credential is missing")
+            })?;
+        Ok(self.reader.get_or_init(|| cipher))
+    }
+
+    /// Rewrite every session and the session list encrypted, or as plaintext
+    /// when encryption is off; returns how many values changed form
+    pub fn migrate_encryption(&self) -> Result<usize> {
+        let mut rewritten = 0;
+        let entries = self
+            .sessions_tree
+            .scan_prefix("session:")
+            .chain(self.metadata_tree.scan_prefix("session:list"))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        for (key, stored) in entries {
+            let encrypted = stored.starts_with(ENCRYPTED_MAGIC);
+            if encrypted == self.cipher.is_some() {
+                continue;
+            }
+            let data = self.encode(self.decode(&stored)?)?;
+            let tree = if key.as_ref() == b"session:list" {
+                &self.metadata_tree
+            } else {
+                &self.sessions_tree
+            };
+            tree.insert(key, data)?;
+            rewritten += 1;
+        }
+        self.db.flush()?;
+        Ok(rewritten)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
     /// Create or load a session
     pub fn get_or_create_session(&self, session_name: &str) -> Result<Session> {
         // Try to load existing session
@@ -105,6 +472,7 @@ impl SessionStore {
             goal_summary: "".to_string(),
             change_count: 0,
             is_active: true,
+            token_usage: TokenUsage::default(),
         };
 
         let session = Session {
@@ -113,6 +481,13 @@ impl SessionStore {
             applied_changes: Vec::new(),
             undo_stack: Vec::new(),
             background_state: None,
+            workspace: None,
+            confirmation_grants: Vec::new(),
+            environment_snapshots: Vec::new(),
+            timeline: Vec::new(),
+            forked_from: None,
+            version: 0,
+            run_checkpoint: None,
         };
 
         // Save the new session
@@ -127,7 +502,7 @@ impl SessionStore {
 
         match self.sessions_tree.get(key.as_bytes())? {
             Some(data) => {
-                let session: Session = serde_json::from_slice(data.as_ref())
+                let session: Session = serde_json::from_slice(&self.decode(&data)?)
                     .context("Failed to deserialize session")?;
                 Ok(Some(session))
             }
@@ -135,28 +510,97 @@ impl SessionStore {
         }
     }
 
-    /// Save a session to storage
+    /// Save a session to storage. When another process saved it since
+    /// `session` was read, the conflict policy decides between merging,
+    /// overwriting and dropping this write.
     pub fn save_session(&self, session: &Session) -> Result<()> {
-        let key = format!("session:{}", session.metadata.name);
-        let data = serde_json::to_vec(session).context("Failed to serialize session")?;
+        let name = session.metadata.name.as_str();
+        let key = format!("session:{}", name);
+        let mut session = session.clone();
+        loop {
+            let stored = self.sessions_tree.get(key.as_bytes())?;
+            let theirs: Option<Session> = match &stored {
+                Some(data) => Some(
+                    serde_json::from_slice(&self.decode(data)?)
+                        .context("Failed to deserialize session")?,
+                ),
+                None => None,
+            };
+            let found = theirs.as_ref().map_or(0, |theirs| theirs.version);
+            if let Some(theirs) = theirs
+                .as_ref()
+                .filter(|_| !self.descends_from(name, session.version, found))
+            {
+                let conflict = WriteConflict {
+                    what: format!("session '{}'", name),
+                    expected: session.version,
+                    found,
+                    ours: session.summary(),
+                    theirs: theirs.summary(),
+                };
+                match self
+                    .conflict_policy
+                    .resolve(&conflict, self.resolver.as_ref())
+                {
+                    ConflictChoice::Merge => session = session.merge_concurrent(theirs),
+                    ConflictChoice::KeepOurs => {}
+                    ConflictChoice::KeepTheirs => return Ok(()),
+                }
+            }
 
-        self.sessions_tree.insert(key.as_bytes(), data.as_slice())?;
+            let base = session.version;
+            session.version = found + 1;
+            let data = serde_json::to_vec(&session).context("Failed to serialize session")?;
+            let data = self.encode(data)?;
+            // Another write between reading and writing starts the check over
+            if self
+                .sessions_tree
+                .compare_and_swap(key.as_bytes(), stored, Some(data))?
+                .is_ok()
+            {
+                let mut own_writes = self.own_writes.lock().unwrap_or_else(|e| e.into_inner());
+                let writes = own_writes.entry(name.to_string()).or_default();
+                writes.insert(session.version, found);
+                if found != base {
+                    // What was merged or overwritten is settled for this copy
+                    writes.entry(found).or_insert(base);
+                }
+                break;
+            }
+            session.version = base;
+        }
         self.sessions_tree.flush()?;
 
         // Update session list
-        self.update_session_list(session)?;
+        self.update_session_list(&session)?;
 
         Ok(())
     }
 
+    /// Whether stored version `found` came from `base` through saves of this
+    /// process alone, so writing over it loses nothing
+    fn descends_from(&self, name: &str, base: u64, found: u64) -> bool {
+        let own_writes = self.own_writes.lock().unwrap_or_else(|e| e.into_inner());
+        let writes = own_writes.get(name);
+        let mut version = found;
+        while version != base {
+            match writes.and_then(|writes| writes.get(&version)) {
+                Some(&replaced) if replaced < version => version = replaced,
+                _ => return false,
+            }
+        }
+        true
+    }
+
     /// List all sessions
     pub fn list_sessions(&self) -> Result<Vec<SessionMetadata>> {
         let list_key = "session:list";
 
         match self.metadata_tree.get(list_key.as_bytes())? {
             Some(data) => {
-                let sessions: Vec<SessionMetadata> = serde_json::from_slice(data.as_ref())
-                    .context("Failed to deserialize session list")?;
+                let sessions: Vec<SessionMetadata> =
+                    serde_json::from_slice(&self.decode(&data)?)
+                        .context("Failed to deserialize session list")?;
                 Ok(sessions)
             }
             None => Ok(Vec::new()),
@@ -174,13 +618,41 @@ impl SessionStore {
         let mut sessions = self.list_sessions()?;
         sessions.retain(|s| s.name != session_name);
 
-        let data =
-            serde_json::to_vec(&sessions).context("Failed to serialize updated session list")?;
+        let data = self.encode(
+            serde_json::to_vec(&sessions).context("Failed to serialize updated session list")?,
+        )?;
         self.metadata_tree
             .insert("session:list".as_bytes(), data.as_slice())?;
         self.metadata_tree.flush()?;
 
-        Ok(())
+        update_pins(&Self::pins_path(), |pins| {
+            pins.retain(|pin| {
+                pin.name != session_name
+                    || blake3::hash(pin.root.to_string_lossy().as_bytes())
+                        .to_hex()
+                        .as_str()
+                        != self.project_hash
+            })
+        })
+    }
+
+    /// Copy `source` into a new session `name` whose changes and conversation
+    /// can later be merged back
+    pub fn fork_session(&self, source: &str, name: &str) -> Result<Session> {
+        if self.load_session(name)?.is_some() {
+            return Err(anyhow::anyhow!("Session '{}' already exists", name));
+        }
+        let mut session = self
+            .load_session(source)?
+            .with_context(|| format!("Session '{}' not found", source))?;
+        let now = Utc::now();
+        session.metadata.name = name.to_string();
+        session.metadata.created_at = now;
+        session.metadata.last_used = now;
+        session.metadata.token_usage = TokenUsage::default();
+        session.forked_from = Some(source.to_string());
+        self.save_session(&session)?;
+        Ok(session)
     }
 
     /// Get the default session (creates "main" if none exists)
@@ -198,7 +670,8 @@ impl SessionStore {
         // Add updated metadata
         sessions.push(session.metadata.clone());
 
-        let data = serde_json::to_vec(&sessions).context("Failed to serialize session list")?;
+        let data = self
+            .encode(serde_json::to_vec(&sessions).context("Failed to serialize session list")?)?;
         self.metadata_tree
             .insert("session:list".as_bytes(), data.as_slice())?;
         self.metadata_tree.flush()?;
@@ -206,14 +679,144 @@ impl SessionStore {
         Ok(())
     }
 
+    /// Add token usage to a session's running total and to today's project total
+    pub fn record_token_usage(&self, session_name: &str, usage: &TokenUsage) -> Result<()> {
+        if usage.requests == 0 {
+            return Ok(());
+        }
+
+        let mut session = self.get_or_create_session(session_name)?;
+        session.metadata.token_usage.add(usage);
+        session.metadata.last_used = Utc::now();
+        self.save_session(&session)?;
+
+        let key = Self::daily_usage_key(Utc::now().date_naive());
+        let mut daily = self.daily_usage(Utc::now().date_naive())?;
+        daily.add(usage);
+        let data = serde_json::to_vec(&daily).context("Failed to serialize daily usage")?;
+        self.metadata_tree.insert(key.as_bytes(), data.as_slice())?;
+        self.metadata_tree.flush()?;
+
+        Ok(())
+    }
+
+    /// Confirmation answers remembered in a session
+    pub fn confirmation_grants(&self, session_name: &str) -> Result<Vec<ConfirmationGrant>> {
+        Ok(self
+            .load_session(session_name)?
+            .map(|session| session.confirmation_grants)
+            .unwrap_or_default())
+    }
+
+    /// Remember an answer for the rest of the session, replacing an earlier
+    /// one for the same operation and target
+    pub fn remember_confirmation(
+        &self,
+        session_name: &str,
+        grant: ConfirmationGrant,
+    ) -> Result<()> {
+        let mut session = self.get_or_create_session(session_name)?;
+        session
+            .confirmation_grants
+            .retain(|g| g.operation != grant.operation || g.target != grant.target);
+        session.confirmation_grants.push(grant);
+        session.metadata.last_used = Utc::now();
+        self.save_session(&session)
+    }
+
+    /// Store the environment a build run starts in, dropping the oldest
+    /// snapshots beyond the limit
+    pub fn record_environment(
+        &self,
+        session_name: &str,
+        snapshot: EnvironmentSnapshot,
+    ) -> Result<()> {
+        let mut session = self.get_or_create_session(session_name)?;
+        session.environment_snapshots.push(snapshot);
+        let excess = session
+            .environment_snapshots
+            .len()
+            .saturating_sub(MAX_ENVIRONMENT_SNAPSHOTS);
+        session.environment_snapshots.drain(..excess);
+        session.metadata.last_used = Utc::now();
+        self.save_session(&session)
+    }
+
+    /// Append `event` to the session's timeline, dropping the oldest events
+    /// past [`MAX_TIMELINE_EVENTS`]
+    pub fn record_timeline(&self, session_name: &str, event: TimelineEvent) -> Result<()> {
+        let mut session = self.get_or_create_session(session_name)?;
+        session.timeline.push(event);
+        let excess = session.timeline.len().saturating_sub(MAX_TIMELINE_EVENTS);
+        session.timeline.drain(..excess);
+        session.metadata.last_used = Utc::now();
+        self.save_session(&session)
+    }
+
+    /// Unfinished run of a session, to resume
+    pub fn run_checkpoint(&self, session_name: &str) -> Result<Option<RunCheckpoint>> {
+        Ok(self
+            .load_session(session_name)?
+            .and_then(|session| session.run_checkpoint))
+    }
+
+    /// Save the progress of the session's run; None once it finished
+    pub fn save_run_checkpoint(
+        &self,
+        session_name: &str,
+        checkpoint: Option<&RunCheckpoint>,
+    ) -> Result<()> {
+        let mut session = self.get_or_create_session(session_name)?;
+        session.run_checkpoint = checkpoint.cloned();
+        session.metadata.last_used = Utc::now();
+        self.save_session(&session)
+    }
+
+    /// Token usage recorded for this project on the given day
+    pub fn daily_usage(&self, date: NaiveDate) -> Result<TokenUsage> {
+        let key = Self::daily_usage_key(date);
+
+        match self.metadata_tree.get(key.as_bytes())? {
+            Some(data) => {
+                serde_json::from_slice(data.as_ref()).context("Failed to deserialize daily usage")
+            }
+            None => Ok(TokenUsage::default()),
+        }
+    }
+
+    /// All recorded daily totals, oldest first
+    pub fn usage_history(&self) -> Result<Vec<(NaiveDate, TokenUsage)>> {
+        let mut history = Vec::new();
+
+        for entry in self.metadata_tree.scan_prefix(b"usage:daily:") {
+            let (key, data) = entry?;
+            let key = String::from_utf8_lossy(key.as_ref()).to_string();
+            let Some(date) = key
+                .strip_prefix("usage:daily:")
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            else {
+                continue;
+            };
+            let usage: TokenUsage = serde_json::from_slice(data.as_ref())
+                .context("Failed to deserialize daily usage")?;
+            history.push((date, usage));
+        }
+
+        history.sort_by_key(|(date, _)| *date);
+        Ok(history)
+    }
+
+    fn daily_usage_key(date: NaiveDate) -> String {
+        format!("usage:daily:{}", date.format("%Y-%m-%d"))
+    }
+
     /// Export session to JSON file for backup
     pub fn export_session(&self, session_name: &str) -> Result<PathBuf> {
         let session = self
             .load_session(session_name)?
             .context("Session not found")?;
 
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let export_dir = PathBuf::from(home).join(".ai-agent").join("sessions");
+        let export_dir = platform::home_dir().join(".ai-agent").join("sessions");
         std::fs::create_dir_all(&export_dir).context("Failed to create export directory")?;
 
         let filename = format!("{}-{}.json", self.project_hash, session_name);
@@ -226,14 +829,291 @@ impl SessionStore {
         Ok(export_path)
     }
 
+    /// Pin a session to `workspace`, recording it in the cross-project index
+    /// so it can be continued from any directory
+    pub fn pin_workspace(&self, session_name: &str, workspace: SessionWorkspace) -> Result<()> {
+        let mut session = self.get_or_create_session(session_name)?;
+        session.workspace = Some(workspace.clone());
+        self.save_session(&session)?;
+
+        update_pins(&Self::pins_path(), |pins| {
+            add_pin(pins, session_name, workspace.root)
+        })
+    }
+
+    /// Pinned sessions of every project, most recently pinned last
+    pub fn pinned_sessions() -> Result<Vec<PinnedSession>> {
+        read_pins(&Self::pins_path())
+    }
+
+    /// Most recently used pinned session named `name`, or of any name
+    pub fn find_pinned(name: Option<&str>) -> Result<Option<PinnedSession>> {
+        Ok(latest_pin(Self::pinned_sessions()?, name))
+    }
+
+    fn pins_path() -> PathBuf {
+        platform::home_dir()
+            .join(".ai-agent")
+            .join("data")
+            .join("pinned_sessions.json")
+    }
+
     /// Get project hash
     pub fn project_hash(&self) -> &str {
         &self.project_hash
     }
 }
 
+/// Pins in the index at `path`. An unreadable index is only a shortcut to
+/// sessions that still exist, so it counts as empty and is rewritten by the
+/// next pin.
+fn read_pins(path: &Path) -> Result<Vec<PinnedSession>> {
+    match std::fs::read(path) {
+        Ok(data) => Ok(serde_json::from_slice(&data).unwrap_or_else(|e| {
+            tracing::warn!(
+                "Ignoring unreadable session index {}: {}",
+                path.display(),
+                e
+            );
+            Vec::new()
+        })),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Change the index at `path` under a lock shared by every `bro` process,
+/// replacing it atomically so a crash never leaves it half written
+fn update_pins(path: &Path, change: impl FnOnce(&mut Vec<PinnedSession>)) -> Result<()> {
+    let _lock = FileLock::exclusive(&path.with_extension("json.lock"))?;
+    let mut pins = read_pins(path)?;
+    let before = pins.clone();
+    change(&mut pins);
+    if pins == before {
+        return Ok(());
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(&pins)?)
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+/// Record `name` as pinned to `root` just now, replacing an older pin of the
+/// same session
+fn add_pin(pins: &mut Vec<PinnedSession>, name: &str, root: PathBuf) {
+    pins.retain(|pin| !(pin.name == name && pin.root == root));
+    pins.push(PinnedSession {
+        name: name.to_string(),
+        root,
+        last_used: Utc::now(),
+    });
+}
+
+/// Most recently used pin named `name`, or of any name
+fn latest_pin(pins: Vec<PinnedSession>, name: Option<&str>) -> Option<PinnedSession> {
+    pins.into_iter()
+        .filter(|pin| name.map_or(true, |name| pin.name == name))
+        .max_by_key(|pin| pin.last_used)
+}
+
 impl Drop for SessionStore {
     fn drop(&mut self) {
         let _ = self.db.flush();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_sessions_open_only_with_the_project_key() {
+        let cipher = SessionCipher::derive(b"master key", "project-a");
+        let sealed = cipher.seal(b"{\"goal\":\"secret\"}").unwrap();
+        assert!(sealed.starts_with(ENCRYPTED_MAGIC));
+        assert!(!sealed.windows(6).any(|w| w == b"secret"));
+        assert_eq!(cipher.open(&sealed).unwrap(), b"{\"goal\":\"secret\"}");
+
+        let other_project = SessionCipher::derive(b"master key", "project-b");
+        assert!(other_project.open(&sealed).is_err());
+        assert!(cipher.open(ENCRYPTED_MAGIC).is_err());
+    }
+
+    #[test]
+    fn test_migration_decrypts_sessions_once_encryption_is_off() {
+        let engine = base64::engine::general_purpose::STANDARD;
+        std::env::set_var(
+            crate::credentials::env_var(SESSION_KEY),
+            engine.encode([7u8; 32]),
+        );
+        let db_path =
+            std::env::temp_dir().join(format!("bro-sessions-{}.sled", uuid::Uuid::new_v4()));
+        let open = |encrypt_sessions| {
+            let power_user = PowerUserConfig {
+                encrypt_sessions,
+                ..Default::default()
+            };
+            SessionStore::open(&db_path, "project".to_string(), power_user).unwrap()
+        };
+
+        let encrypted = open(true);
+        let mut session = encrypted.get_or_create_session("main").unwrap();
+        session.metadata.goal_summary = "secret goal".to_string();
+        encrypted.save_session(&session).unwrap();
+        drop(encrypted);
+
+        let plain = open(false);
+        assert_eq!(
+            plain
+                .load_session("main")
+                .unwrap()
+                .unwrap()
+                .metadata
+                .goal_summary,
+            "secret goal"
+        );
+        assert_eq!(plain.migrate_encryption().unwrap(), 2);
+        let stored = plain.sessions_tree.get("session:main").unwrap().unwrap();
+        assert!(!stored.starts_with(ENCRYPTED_MAGIC));
+        assert_eq!(plain.migrate_encryption().unwrap(), 0);
+        drop(plain);
+        let _ = std::fs::remove_dir_all(&db_path);
+    }
+
+    #[test]
+    fn test_concurrent_copies_merge_without_losing_history() {
+        let at = |secs: i64| DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::seconds(secs);
+        let message = |content: &str, secs: i64| ConversationMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+            timestamp: at(secs),
+        };
+        let change = |id: &str, secs: i64| AppliedChange {
+            id: id.to_string(),
+            description: format!("change {}", id),
+            timestamp: at(secs),
+            files_affected: Vec::new(),
+        };
+        let session = |messages: Vec<ConversationMessage>, changes: Vec<AppliedChange>| Session {
+            metadata: SessionMetadata {
+                name: "main".to_string(),
+                created_at: at(0),
+                last_used: messages.last().map_or(at(0), |m| m.timestamp),
+                goal_summary: String::new(),
+                change_count: changes.len() as u32,
+                is_active: true,
+                token_usage: TokenUsage::default(),
+            },
+            conversation_history: messages,
+            applied_changes: changes,
+            undo_stack: Vec::new(),
+            background_state: None,
+            workspace: None,
+            confirmation_grants: Vec::new(),
+            environment_snapshots: Vec::new(),
+            timeline: Vec::new(),
+            forked_from: None,
+            version: 3,
+            run_checkpoint: None,
+        };
+
+        let cli = session(
+            vec![message("plan", 1), message("from the cli", 3)],
+            vec![change("a", 1), change("b", 3)],
+        );
+        let daemon = session(
+            vec![message("plan", 1), message("from the daemon", 2)],
+            vec![change("a", 1), change("c", 2)],
+        );
+        let merged = cli.merge_concurrent(&daemon);
+        let contents: Vec<&str> = merged
+            .conversation_history
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents, ["plan", "from the daemon", "from the cli"]);
+        let ids: Vec<&str> = merged
+            .applied_changes
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(ids, ["a", "c", "b"]);
+        assert_eq!(merged.metadata.change_count, 3);
+        assert_eq!(merged.metadata.last_used, at(3));
+    }
+
+    #[test]
+    fn test_pins_round_trip_and_unpin() {
+        let dir = std::env::temp_dir().join(format!("bro-pins-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("pinned_sessions.json");
+        assert!(read_pins(&path).unwrap().is_empty());
+
+        update_pins(&path, |pins| {
+            add_pin(pins, "api", PathBuf::from("/work/api"))
+        })
+        .unwrap();
+        update_pins(&path, |pins| {
+            add_pin(pins, "web", PathBuf::from("/work/web"))
+        })
+        .unwrap();
+        update_pins(&path, |pins| {
+            add_pin(pins, "api", PathBuf::from("/work/api"))
+        })
+        .unwrap();
+        let pins = read_pins(&path).unwrap();
+        assert_eq!(pins.len(), 2);
+        assert_eq!(
+            latest_pin(pins.clone(), Some("web")).unwrap().root,
+            PathBuf::from("/work/web")
+        );
+        assert_eq!(latest_pin(pins.clone(), None).unwrap().name, "api");
+        assert!(latest_pin(pins, Some("docs")).is_none());
+
+        update_pins(&path, |pins| pins.retain(|pin| pin.name != "api")).unwrap();
+        let pins = read_pins(&path).unwrap();
+        assert_eq!(latest_pin(pins.clone(), None).unwrap().name, "web");
+        assert!(latest_pin(pins, Some("api")).is_none());
+        assert!(!path.with_extension("json.tmp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_corrupt_pins_are_replaced_by_the_next_pin() {
+        let dir = std::env::temp_dir().join(format!("bro-pins-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("pinned_sessions.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "[{\"name\": \"api\", \"ro").unwrap();
+        assert!(read_pins(&path).unwrap().is_empty());
+
+        // Nothing to drop leaves the file alone
+        update_pins(&path, |pins| pins.retain(|pin| pin.name != "api")).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().ends_with("\"ro"));
+
+        update_pins(&path, |pins| {
+            add_pin(pins, "api", PathBuf::from("/work/api"))
+        })
+        .unwrap();
+        assert_eq!(read_pins(&path).unwrap()[0].name, "api");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_concurrent_pins_are_all_kept() {
+        let dir = std::env::temp_dir().join(format!("bro-pins-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("pinned_sessions.json");
+        std::thread::scope(|scope| {
+            for i in 0..16 {
+                let path = &path;
+                scope.spawn(move || {
+                    update_pins(path, |pins| {
+                        add_pin(pins, &format!("session-{}", i), PathBuf::from("/work"))
+                    })
+                    .unwrap()
+                });
+            }
+        });
+        assert_eq!(read_pins(&path).unwrap().len(), 16);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,398 @@
+//! Replicating sessions and caches between devices on a tailnet
+//!
+//! Each device serves a [`SyncBundle`] of a project's sessions and the shared
+//! caches from its web server. Syncing fetches the peer's bundle, applies it
+//! locally and sends the result back, so both devices end up with the same
+//! state. A session is taken whole from whichever device used it last; the
+//! applied changes only the replaced copy had are written to the project's
+//! conflict journal rather than dropped silently. Caches are whole files,
+//! likewise taken from whichever device wrote them last.
+
+use crate::session_store::{AppliedChange, Session, SessionStore};
+use crate::workspace_lock::hostname;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shared::platform;
+use shared::types::Result;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// Cache files under the app data directory that are synced
+pub const SYNCED_CACHES: [&str; 5] = [
+    "commands_cache.bin",
+    "commands_semantic_cache.bin",
+    "explain_cache.bin",
+    "rag_cache.bin",
+    "rag_semantic_cache.bin",
+];
+
+/// Port of a peer's web server when none is given
+pub const DEFAULT_SYNC_PORT: u16 = 8080;
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheFile {
+    pub name: String,
+    pub modified: DateTime<Utc>,
+    /// Base64 of the file
+    pub content: String,
+}
+
+/// A project's sessions and the caches as one device has them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncBundle {
+    pub device: String,
+    /// Project root relative to the home directory, see [`project_key`]
+    pub project: String,
+    pub sessions: Vec<Session>,
+    pub caches: Vec<CacheFile>,
+}
+
+/// An applied change that was only in a session copy sync replaced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub session: String,
+    /// Device whose copy of the session was replaced
+    pub device: String,
+    pub change: AppliedChange,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// What applying a bundle changed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub sessions: Vec<String>,
+    pub caches: Vec<String>,
+    pub conflicts: usize,
+}
+
+impl SyncBundle {
+    pub fn collect(store: &SessionStore, project: &str, cache_dir: &Path) -> Result<Self> {
+        let mut sessions = Vec::new();
+        for metadata in store.list_sessions()? {
+            if let Some(session) = store.load_session(&metadata.name)? {
+                sessions.push(session);
+            }
+        }
+
+        let mut caches = Vec::new();
+        for name in SYNCED_CACHES {
+            let path = cache_dir.join(name);
+            let Ok(modified) = std::fs::metadata(&path).and_then(|meta| meta.modified()) else {
+                continue;
+            };
+            caches.push(CacheFile {
+                name: name.to_string(),
+                modified: modified.into(),
+                content: base64::engine::general_purpose::STANDARD.encode(std::fs::read(&path)?),
+            });
+        }
+
+        Ok(Self {
+            device: hostname(),
+            project: project.to_string(),
+            sessions,
+            caches,
+        })
+    }
+
+    /// Take the sessions and caches this bundle has newer copies of,
+    /// journaling the applied changes that only replaced sessions had
+    pub fn apply(
+        &self,
+        store: &SessionStore,
+        cache_dir: &Path,
+        journal: &SyncJournal,
+    ) -> Result<SyncReport> {
+        let device = hostname();
+        let mut report = SyncReport::default();
+        let mut conflicts = Vec::new();
+        for remote in &self.sessions {
+            let local = store.load_session(&remote.metadata.name)?;
+            let (winner, lost) = reconcile(local.as_ref(), &device, remote, &self.device);
+            if let Some(mut session) = winner {
+                // Version counters are per device; this one replaces the local copy
+                session.version = local.as_ref().map_or(0, |local| local.version);
+                store.save_session(&session)?;
+                report.sessions.push(session.metadata.name);
+            }
+            conflicts.extend(lost);
+        }
+        journal.record(&conflicts)?;
+        report.conflicts = conflicts.len();
+        report.caches = self.apply_caches(cache_dir)?;
+        Ok(report)
+    }
+
+    fn apply_caches(&self, cache_dir: &Path) -> Result<Vec<String>> {
+        let mut updated = Vec::new();
+        for cache in &self.caches {
+            // Only known cache names, so a peer cannot write elsewhere
+            if !SYNCED_CACHES.contains(&cache.name.as_str()) {
+                continue;
+            }
+            let path = cache_dir.join(&cache.name);
+            let local: Option<DateTime<Utc>> = std::fs::metadata(&path)
+                .and_then(|meta| meta.modified())
+                .ok()
+                .map(Into::into);
+            if local.is_some_and(|local| local >= cache.modified) {
+                continue;
+            }
+            let content = base64::engine::general_purpose::STANDARD.decode(&cache.content)?;
+            std::fs::create_dir_all(cache_dir)?;
+            std::fs::write(&path, content)?;
+            // Keep the writer's time so the copy does not look newer
+            filetime::set_file_mtime(
+                &path,
+                filetime::FileTime::from_system_time(cache.modified.into()),
+            )?;
+            updated.push(cache.name.clone());
+        }
+        Ok(updated)
+    }
+}
+
+/// Which copy of a session wins, None when the local one does, and the
+/// applied changes only the losing copy had
+pub fn reconcile(
+    local: Option<&Session>,
+    local_device: &str,
+    remote: &Session,
+    remote_device: &str,
+) -> (Option<Session>, Vec<SyncConflict>) {
+    let Some(local) = local else {
+        return (Some(remote.clone()), Vec::new());
+    };
+    let (local_used, remote_used) = (local.metadata.last_used, remote.metadata.last_used);
+    if local_used == remote_used {
+        return (None, Vec::new());
+    }
+    let remote_newer = remote_used > local_used;
+    let (winner, loser, loser_device) = if remote_newer {
+        (remote, local, local_device)
+    } else {
+        (local, remote, remote_device)
+    };
+
+    let kept: HashSet<&str> = winner
+        .applied_changes
+        .iter()
+        .map(|change| change.id.as_str())
+        .collect();
+    let now = Utc::now();
+    let conflicts = loser
+        .applied_changes
+        .iter()
+        .filter(|change| !kept.contains(change.id.as_str()))
+        .map(|change| SyncConflict {
+            session: loser.metadata.name.clone(),
+            device: loser_device.to_string(),
+            change: change.clone(),
+            recorded_at: now,
+        })
+        .collect();
+    (remote_newer.then(|| remote.clone()), conflicts)
+}
+
+/// Applied changes lost to last-writer-wins, one JSON object per line in
+/// `.bro/sync-conflicts.jsonl`
+pub struct SyncJournal {
+    path: PathBuf,
+}
+
+impl SyncJournal {
+    pub fn for_project(root: &Path) -> Self {
+        Self {
+            path: root.join(".bro").join("sync-conflicts.jsonl"),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, conflicts: &[SyncConflict]) -> Result<()> {
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        for conflict in conflicts {
+            writeln!(file, "{}", serde_json::to_string(conflict)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// `root` relative to the home directory, which names the project the same
+/// way on every device that keeps it at the same place
+pub fn project_key(root: &Path) -> Result<String> {
+    let home = platform::home_dir();
+    let relative = root.strip_prefix(&home).map_err(|_| {
+        anyhow::anyhow!(
+            "Only projects under {} can be synced, not {}",
+            home.display(),
+            root.display()
+        )
+    })?;
+    Ok(relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// Project root on this device for a key from [`project_key`]
+pub fn project_root(key: &str) -> Result<PathBuf> {
+    let relative = Path::new(key);
+    if key.is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(anyhow::anyhow!("Invalid project '{}'", key));
+    }
+    Ok(platform::home_dir().join(relative))
+}
+
+/// Base URL of a peer's web server; a bare tailnet host name gets the
+/// default port
+pub fn peer_url(peer: &str, port: u16) -> String {
+    let peer = peer.trim_end_matches('/');
+    if peer.contains("://") {
+        peer.to_string()
+    } else if peer.contains(':') {
+        format!("http://{}", peer)
+    } else {
+        format!("http://{}:{}", peer, port)
+    }
+}
+
+/// Exchange bundles with the peer at `base_url`, returning what changed here
+/// and what changed on the peer. `token` is the peer's web token, which is
+/// the same on every device of one user.
+pub async fn sync_with_peer(
+    base_url: &str,
+    token: Option<&str>,
+    store: &SessionStore,
+    root: &Path,
+) -> Result<(SyncReport, SyncReport)> {
+    let project = project_key(root)?;
+    let cache_dir = platform::app_data_dir();
+    let client = reqwest::Client::builder().timeout(SYNC_TIMEOUT).build()?;
+    let url = format!("{}/api/sync/bundle", base_url);
+    let authorize = |request: reqwest::RequestBuilder| match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+
+    let remote: SyncBundle = authorize(client.get(&url).query(&[("project", &project)]))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let local = remote.apply(store, &cache_dir, &SyncJournal::for_project(root))?;
+
+    let merged = SyncBundle::collect(store, &project, &cache_dir)?;
+    let peer: SyncReport = authorize(client.post(&url).json(&merged))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok((local, peer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::SessionMetadata;
+
+    fn session(last_used: i64, changes: &[&str]) -> Session {
+        let at = DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::seconds(last_used);
+        Session {
+            metadata: SessionMetadata {
+                name: "main".to_string(),
+                created_at: DateTime::<Utc>::UNIX_EPOCH,
+                last_used: at,
+                goal_summary: String::new(),
+                change_count: changes.len() as u32,
+                is_active: true,
+                token_usage: Default::default(),
+            },
+            conversation_history: Vec::new(),
+            applied_changes: changes
+                .iter()
+                .map(|id| AppliedChange {
+                    id: id.to_string(),
+                    description: format!("change {}", id),
+                    timestamp: at,
+                    files_affected: Vec::new(),
+                })
+                .collect(),
+            undo_stack: Vec::new(),
+            background_state: None,
+            workspace: None,
+            confirmation_grants: Vec::new(),
+            environment_snapshots: Vec::new(),
+            timeline: Vec::new(),
+            forked_from: None,
+            version: 0,
+            run_checkpoint: None,
+        }
+    }
+
+    #[test]
+    fn test_last_writer_wins_and_journals_lost_changes() {
+        let desktop = session(10, &["1", "2"]);
+        let laptop = session(20, &["1", "3"]);
+
+        let (winner, lost) = reconcile(Some(&desktop), "desktop", &laptop, "laptop");
+        assert_eq!(winner.unwrap().applied_changes[1].id, "3");
+        assert_eq!(lost.len(), 1);
+        assert_eq!(
+            (lost[0].device.as_str(), lost[0].change.id.as_str()),
+            ("desktop", "2")
+        );
+
+        let (winner, lost) = reconcile(Some(&laptop), "laptop", &desktop, "desktop");
+        assert!(winner.is_none());
+        assert_eq!(lost[0].device, "desktop");
+        assert!(reconcile(Some(&laptop), "laptop", &laptop, "desktop")
+            .1
+            .is_empty());
+        assert!(reconcile(None, "laptop", &desktop, "desktop").0.is_some());
+
+        let dir = std::env::temp_dir().join(format!("bro-sync-{}", uuid::Uuid::new_v4()));
+        let newer = |name: &str, secs: i64, content: &str| CacheFile {
+            name: name.to_string(),
+            modified: Utc::now() + chrono::Duration::seconds(secs),
+            content: base64::engine::general_purpose::STANDARD.encode(content),
+        };
+        let bundle = SyncBundle {
+            device: "laptop".to_string(),
+            project: "code/bro".to_string(),
+            sessions: Vec::new(),
+            caches: vec![
+                newer("rag_cache.bin", -60, "old"),
+                newer("../escape.bin", 60, "x"),
+            ],
+        };
+        assert_eq!(bundle.apply_caches(&dir).unwrap(), vec!["rag_cache.bin"]);
+        // A second apply finds the copy as new as the bundle's
+        assert!(bundle.apply_caches(&dir).unwrap().is_empty());
+        assert!(!dir.join("../escape.bin").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(project_root("../etc").is_err());
+        assert!(project_root("/etc").is_err());
+        assert_eq!(peer_url("laptop", 8080), "http://laptop:8080");
+    }
+}
@@ -0,0 +1,232 @@
+//! Chronological record of what happened in a session
+//!
+//! Queries, build plans, applied operations, commits and test runs are
+//! appended to the session as they happen, so `bro --timeline <name>` can
+//! reconstruct what the agent did long after the terminal output is gone.
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Characters of a query or message kept in an event's summary
+const MAX_SUMMARY_CHARS: usize = 120;
+
+/// Characters of test output kept in an event's detail, from the end
+const MAX_DETAIL_CHARS: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineKind {
+    Query,
+    Plan,
+    Operation,
+    Commit,
+    Test,
+}
+
+impl TimelineKind {
+    pub const ALL: [TimelineKind; 5] = [
+        TimelineKind::Query,
+        TimelineKind::Plan,
+        TimelineKind::Operation,
+        TimelineKind::Commit,
+        TimelineKind::Test,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimelineKind::Query => "query",
+            TimelineKind::Plan => "plan",
+            TimelineKind::Operation => "operation",
+            TimelineKind::Commit => "commit",
+            TimelineKind::Test => "test",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    pub at: DateTime<Utc>,
+    pub kind: TimelineKind,
+    /// One line, e.g. `Update src/lib.rs`
+    pub summary: String,
+    /// Whether it succeeded, for operations and test runs
+    #[serde(default)]
+    pub ok: Option<bool>,
+    /// Shown when the event is expanded: a plan's operations, an error or
+    /// the end of the test output
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+impl TimelineEvent {
+    fn new(kind: TimelineKind, summary: String) -> Self {
+        Self {
+            at: Utc::now(),
+            kind,
+            summary,
+            ok: None,
+            detail: None,
+        }
+    }
+
+    /// A request made in `mode`, e.g. `build`; plain queries are not prefixed
+    pub fn query(mode: &str, text: &str) -> Self {
+        let text = text.trim();
+        let mut event = Self::new(
+            TimelineKind::Query,
+            match mode {
+                "query" => first_line(text),
+                mode => format!("{}: {}", mode, first_line(text)),
+            },
+        );
+        if text.lines().count() > 1 || text.chars().count() > MAX_SUMMARY_CHARS {
+            event.detail = Some(text.to_string());
+        }
+        event
+    }
+
+    /// A plan for `goal` made of `operations`, one summary each
+    pub fn plan(goal: &str, operations: &[String]) -> Self {
+        let mut event = Self::new(
+            TimelineKind::Plan,
+            format!(
+                "{} operation{} for {}",
+                operations.len(),
+                if operations.len() == 1 { "" } else { "s" },
+                first_line(goal)
+            ),
+        );
+        event.detail = Some(operations.join("\n")).filter(|detail| !detail.is_empty());
+        event
+    }
+
+    /// An operation or group applied, or rolled back with `error`
+    pub fn operation(summary: &str, error: Option<&str>) -> Self {
+        let mut event = Self::new(TimelineKind::Operation, summary.to_string());
+        event.ok = Some(error.is_none());
+        event.detail = error.map(str::to_string);
+        event
+    }
+
+    pub fn commit(hash: &str, message: &str) -> Self {
+        Self::new(
+            TimelineKind::Commit,
+            format!("{} {}", hash, first_line(message)),
+        )
+    }
+
+    /// A run of `command`, taking `elapsed` when known; the output is kept
+    /// only when it failed
+    pub fn test(command: &str, passed: bool, elapsed: Option<Duration>, output: &str) -> Self {
+        let mut event = Self::new(
+            TimelineKind::Test,
+            match elapsed {
+                Some(elapsed) => format!("{} ({:.1}s)", command, elapsed.as_secs_f64()),
+                None => command.to_string(),
+            },
+        );
+        event.ok = Some(passed);
+        if !passed && !output.trim().is_empty() {
+            let output = output.trim_end();
+            let skip = output.chars().count().saturating_sub(MAX_DETAIL_CHARS);
+            event.detail = Some(output.chars().skip(skip).collect());
+        }
+        event
+    }
+
+    /// `ok`, `failed`, or empty when the event has no outcome
+    pub fn status(&self) -> &'static str {
+        match self.ok {
+            Some(true) => "ok",
+            Some(false) => "failed",
+            None => "",
+        }
+    }
+
+    /// Time of day in the local time zone
+    pub fn local_time(&self) -> String {
+        self.at.with_timezone(&Local).format("%H:%M:%S").to_string()
+    }
+}
+
+/// `events` in order, grouped by local calendar day
+pub fn by_day(events: &[TimelineEvent]) -> Vec<(NaiveDate, Vec<&TimelineEvent>)> {
+    let mut sorted: Vec<&TimelineEvent> = events.iter().collect();
+    sorted.sort_by_key(|event| event.at);
+    let mut days: Vec<(NaiveDate, Vec<&TimelineEvent>)> = Vec::new();
+    for event in sorted {
+        let day = event.at.with_timezone(&Local).date_naive();
+        match days.last_mut() {
+            Some((last, events)) if *last == day => events.push(event),
+            _ => days.push((day, vec![event])),
+        }
+    }
+    days
+}
+
+/// First line of `text`, cut to [`MAX_SUMMARY_CHARS`]
+fn first_line(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or_default();
+    if line.chars().count() <= MAX_SUMMARY_CHARS {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(MAX_SUMMARY_CHARS - 3).collect();
+    format!("{}...", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_summarize_and_group_by_day() {
+        let query = TimelineEvent::query("build", "add retries\nto the http client");
+        assert_eq!(query.summary, "build: add retries");
+        assert_eq!(
+            query.detail.as_deref(),
+            Some("add retries\nto the http client")
+        );
+        assert_eq!(TimelineEvent::query("query", "why?").detail, None);
+        assert!(TimelineEvent::query("query", &"x".repeat(500))
+            .summary
+            .ends_with("..."));
+
+        let mut plan = TimelineEvent::plan(
+            "add retries",
+            &[
+                "Update src/http.rs".to_string(),
+                "Create src/retry.rs".to_string(),
+            ],
+        );
+        assert_eq!(plan.summary, "2 operations for add retries");
+        let test = TimelineEvent::test(
+            "cargo test",
+            false,
+            Some(Duration::from_millis(2500)),
+            "boom\n",
+        );
+        assert_eq!(
+            (test.summary.as_str(), test.status(), test.detail.as_deref()),
+            ("cargo test (2.5s)", "failed", Some("boom"))
+        );
+        assert_eq!(TimelineKind::parse("commit"), Some(TimelineKind::Commit));
+
+        let mut commit = TimelineEvent::commit("abc1234", "feat: retries\n\nbody");
+        assert_eq!(commit.summary, "abc1234 feat: retries");
+        let mut earlier = TimelineEvent::operation("Update src/http.rs", None);
+        let noon = "2026-10-10T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        earlier.at = noon;
+        plan.at = noon + chrono::Duration::minutes(1);
+        commit.at = noon + chrono::Duration::days(3);
+        let events = [commit.clone(), plan.clone(), earlier.clone()];
+        let days = by_day(&events);
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].1, vec![&earlier, &plan]);
+        assert_eq!(days[1].1, vec![&commit]);
+    }
+}
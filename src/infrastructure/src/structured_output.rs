@@ -0,0 +1,352 @@
+//! Schema-checked JSON replies from the model
+//!
+//! Types the model is asked to produce implement [`OutputSchema`]: a JSON
+//! Schema that Ollama constrains decoding to, and checks serde cannot
+//! express. [`generate`] takes the first JSON value in the reply, validates it
+//! against the schema and, on a violation, asks again with a repair prompt
+//! listing exactly what was wrong, up to [`MAX_REPAIRS`] times.
+
+use crate::InferenceEngine;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use shared::types::Result;
+use std::future::Future;
+
+/// Repair prompts sent before giving up on a reply
+pub const MAX_REPAIRS: usize = 2;
+
+/// Previous reply quoted in a repair prompt, in characters
+const MAX_QUOTED_REPLY: usize = 4000;
+
+pub trait OutputSchema: DeserializeOwned {
+    /// What the value is, for messages: "agent plan"
+    const NAME: &'static str;
+
+    /// JSON Schema of the value; `type`, `properties`, `required`, `items`,
+    /// `enum` and `minItems` are checked
+    fn json_schema() -> Value;
+
+    /// Problems the schema cannot describe, one message each
+    fn check(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Why a reply was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    pub errors: Vec<String>,
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.errors.join("; "))
+    }
+}
+
+impl std::error::Error for SchemaViolation {}
+
+/// Generate a `T`, constraining decoding to its schema and repairing
+/// replies that still do not match
+pub async fn generate<T: OutputSchema>(engine: &InferenceEngine, prompt: &str) -> Result<T> {
+    let engine = engine.with_output_format(T::json_schema());
+    generate_with(prompt, |prompt| {
+        let engine = engine.clone();
+        async move { engine.generate(&prompt).await }
+    })
+    .await
+}
+
+/// [`generate`] for any way of sending a prompt to a model
+pub async fn generate_with<T, F, Fut>(prompt: &str, mut send: F) -> Result<T>
+where
+    T: OutputSchema,
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let mut request = prompt.to_string();
+    let mut repairs = 0;
+    loop {
+        let reply = send(request).await?;
+        match parse::<T>(&reply) {
+            Ok(value) => return Ok(value),
+            Err(violation) if repairs < MAX_REPAIRS => {
+                repairs += 1;
+                request = repair_prompt::<T>(prompt, &reply, &violation);
+            }
+            Err(violation) => {
+                return Err(anyhow::anyhow!(
+                    "The model's {} is still invalid after {} repair attempts: {}",
+                    T::NAME,
+                    MAX_REPAIRS,
+                    violation
+                ))
+            }
+        }
+    }
+}
+
+/// The first JSON value in `reply` as a `T`, if it matches the schema
+pub fn parse<T: OutputSchema>(reply: &str) -> std::result::Result<T, SchemaViolation> {
+    let schema = T::json_schema();
+    let violation = |error: String| SchemaViolation {
+        errors: vec![error],
+    };
+    let value = first_json_value(reply, schema.get("type").and_then(Value::as_str))
+        .ok_or_else(|| violation(format!("the reply contains no JSON {}", T::NAME)))?;
+
+    let mut errors = Vec::new();
+    check_value(&schema, &value, "", &mut errors);
+    if !errors.is_empty() {
+        return Err(SchemaViolation { errors });
+    }
+    // The schema may be looser than the type; serde has the last word
+    let parsed: T = serde_json::from_value(value).map_err(|e| violation(e.to_string()))?;
+    let errors = parsed.check();
+    if !errors.is_empty() {
+        return Err(SchemaViolation { errors });
+    }
+    Ok(parsed)
+}
+
+/// Ask for the reply again, saying what was wrong with it
+pub fn repair_prompt<T: OutputSchema>(
+    prompt: &str,
+    reply: &str,
+    violation: &SchemaViolation,
+) -> String {
+    let mut quoted: String = reply.chars().take(MAX_QUOTED_REPLY).collect();
+    if quoted.len() < reply.len() {
+        quoted.push_str("\n...");
+    }
+    let errors: Vec<String> = violation
+        .errors
+        .iter()
+        .map(|error| format!("- {}", error))
+        .collect();
+    format!(
+        "{}\n\nYour previous reply was not a valid {}:\n{}\n\nPrevious reply:\n{}\n\n\
+         Reply again with only the corrected JSON, matching this JSON Schema:\n{}",
+        prompt,
+        T::NAME,
+        errors.join("\n"),
+        quoted,
+        serde_json::to_string_pretty(&T::json_schema()).unwrap_or_default()
+    )
+}
+
+/// First complete JSON object or array in `text`, skipping prose and code
+/// fences around it; only objects when `root` is "object"
+fn first_json_value(text: &str, root: Option<&str>) -> Option<Value> {
+    let opening: &[char] = match root {
+        Some("object") => &['{'],
+        Some("array") => &['['],
+        _ => &['{', '['],
+    };
+    text.char_indices()
+        .filter(|(_, c)| opening.contains(c))
+        .find_map(|(start, _)| {
+            serde_json::Deserializer::from_str(&text[start..])
+                .into_iter::<Value>()
+                .next()
+                .and_then(|value| value.ok())
+        })
+}
+
+/// Collect the ways `value` differs from `schema`, with paths like `steps[0].id`
+fn check_value(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let at = if path.is_empty() { "the reply" } else { path };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            other => other.as_str().into_iter().collect(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| has_type(value, t)) {
+            errors.push(format!(
+                "{} should be {} but is {}",
+                at,
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(choices) = schema.get("enum").and_then(Value::as_array) {
+        if !choices.contains(value) {
+            let choices: Vec<String> = choices.iter().map(Value::to_string).collect();
+            errors.push(format!(
+                "{} is {} but must be one of {}",
+                at,
+                value,
+                choices.join(", ")
+            ));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            for field in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !object.contains_key(field) {
+                    errors.push(format!("{} is missing \"{}\"", at, field));
+                }
+            }
+            if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                for (name, property) in properties {
+                    if let Some(field) = object.get(name) {
+                        let path = if path.is_empty() {
+                            name.clone()
+                        } else {
+                            format!("{}.{}", path, name)
+                        };
+                        check_value(property, field, &path, errors);
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            let min = schema.get("minItems").and_then(Value::as_u64).unwrap_or(0);
+            if (items.len() as u64) < min {
+                errors.push(format!("{} needs at least {} item(s)", at, min));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check_value(item_schema, item, &format!("{}[{}]", path, i), errors);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Files {
+        files: Vec<File>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct File {
+        path: String,
+        action: String,
+    }
+
+    impl OutputSchema for Files {
+        const NAME: &'static str = "file list";
+
+        fn json_schema() -> Value {
+            json!({
+                "type": "object",
+                "required": ["files"],
+                "properties": {
+                    "files": {
+                        "type": "array",
+                        "minItems": 1,
+                        "items": {
+                            "type": "object",
+                            "required": ["path", "action"],
+                            "properties": {
+                                "path": {"type": "string"},
+                                "action": {"type": "string", "enum": ["create", "update"]}
+                            }
+                        }
+                    }
+                }
+            })
+        }
+
+        fn check(&self) -> Vec<String> {
+            self.files
+                .iter()
+                .filter(|file| file.path.starts_with('/'))
+                .map(|file| format!("{} must be relative", file.path))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_parse_skips_prose_and_reports_every_violation() {
+        let reply = "Sure! {not json}\n```json\n{\"files\": [{\"path\": \"a.rs\", \"action\": \"create\"}]}\n```\nDone {}";
+        let files: Files = parse(reply).unwrap();
+        assert_eq!(files.files[0].path, "a.rs");
+
+        let violation =
+            parse::<Files>(r#"{"files": [{"action": "delete"}, {"path": 3, "action": "update"}]}"#)
+                .unwrap_err();
+        assert_eq!(
+            violation.errors,
+            [
+                "files[0] is missing \"path\"",
+                "files[0].action is \"delete\" but must be one of \"create\", \"update\"",
+                "files[1].path should be string but is number",
+            ]
+        );
+        assert_eq!(
+            parse::<Files>(r#"{"files": [{"path": "/etc/x", "action": "update"}]}"#)
+                .unwrap_err()
+                .errors,
+            ["/etc/x must be relative"]
+        );
+        assert!(parse::<Files>("no json here").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_repairs_invalid_replies() {
+        let mut prompts = Vec::new();
+        let mut replies = vec![
+            r#"{"files": []}"#.to_string(),
+            r#"{"files": [{"path": "main.rs", "action": "create"}]}"#.to_string(),
+        ]
+        .into_iter();
+        let files: Files = generate_with("List the files", |prompt| {
+            prompts.push(prompt);
+            let reply = replies.next().unwrap();
+            async move { Ok(reply) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(files.files.len(), 1);
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].starts_with("List the files"));
+        assert!(prompts[1].contains("- files needs at least 1 item(s)"));
+
+        let result: Result<Files> =
+            generate_with("List the files", |_| async { Ok("{}".to_string()) }).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("missing \"files\""));
+    }
+}
@@ -0,0 +1,277 @@
+//! Fast syntax checks of generated files
+//!
+//! Before a generated file is queued for writing it is parsed with the
+//! cheapest checker for its language: `rustfmt` (which parses without
+//! resolving crates, unlike `rustc`), `node --check`, `python -m py_compile`,
+//! or built-in parsers for HTML, JSON and TOML. Languages without a checker,
+//! or whose tool is not installed, are skipped rather than failed.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Longest checker report kept, in characters
+const MAX_REPORT_CHARS: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxCheck {
+    Passed,
+    /// What the checker reported
+    Failed(String),
+    /// No checker for the language, or it is not installed
+    Skipped,
+}
+
+/// Check `content` as the file at `path` would be parsed
+pub async fn check(path: &Path, content: &str) -> SyntaxCheck {
+    let path = path.to_path_buf();
+    let content = content.to_string();
+    tokio::task::spawn_blocking(move || check_blocking(&path, &content))
+        .await
+        .unwrap_or(SyntaxCheck::Skipped)
+}
+
+pub fn check_blocking(path: &Path, content: &str) -> SyntaxCheck {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match extension.as_str() {
+        "rs" => run_checker(
+            &["rustfmt"],
+            &["--edition", "2021", "--emit", "stdout"],
+            path,
+            content,
+        ),
+        "js" | "mjs" | "cjs" => run_checker(&["node"], &["--check"], path, content),
+        "py" => run_checker(&["python3", "python"], &["-m", "py_compile"], path, content),
+        "html" | "htm" => verdict(check_html(content)),
+        "json" => verdict(
+            serde_json::from_str::<serde_json::Value>(content)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        ),
+        "toml" => verdict(
+            content
+                .parse::<toml::Table>()
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        ),
+        _ => SyntaxCheck::Skipped,
+    }
+}
+
+fn verdict(result: Result<(), String>) -> SyntaxCheck {
+    match result {
+        Ok(()) => SyntaxCheck::Passed,
+        Err(report) => SyntaxCheck::Failed(report),
+    }
+}
+
+/// Run the first of `programs` that is installed on a copy of the file,
+/// named like the original so the checker treats it the same way
+fn run_checker(programs: &[&str], args: &[&str], path: &Path, content: &str) -> SyntaxCheck {
+    let dir = std::env::temp_dir().join(format!("bro-syntax-{}", uuid::Uuid::new_v4()));
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    let copy = dir.join(name);
+    if std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&copy, content))
+        .is_err()
+    {
+        return SyntaxCheck::Skipped;
+    }
+
+    let mut result = SyntaxCheck::Skipped;
+    for program in programs {
+        let output = match Command::new(program).args(args).arg(&copy).output() {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(_) => break,
+        };
+        result = if output.status.success() {
+            SyntaxCheck::Passed
+        } else {
+            let report = String::from_utf8_lossy(&output.stderr)
+                .replace(&copy.display().to_string(), &path.display().to_string());
+            SyntaxCheck::Failed(report.trim().chars().take(MAX_REPORT_CHARS).collect())
+        };
+        break;
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+/// Elements that never have a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements whose closing tag may be left out
+const OPTIONAL_CLOSE: &[&str] = &[
+    "body", "caption", "colgroup", "dd", "dt", "head", "html", "li", "optgroup", "option", "p",
+    "rp", "rt", "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+/// Elements whose content is raw text up to their closing tag
+const RAW_TEXT: &[&str] = &["script", "style", "textarea", "title"];
+
+/// Tag balance, comments and raw text elements; lenient about what HTML
+/// lets authors leave out
+fn check_html(html: &str) -> Result<(), String> {
+    let line_at = |offset: usize| html[..offset].matches('\n').count() + 1;
+    // ASCII only, so offsets match `html`
+    let lower = html.to_ascii_lowercase();
+    let mut open: Vec<(String, usize)> = Vec::new();
+    let mut at = 0;
+
+    while let Some(found) = html[at..].find('<') {
+        let start = at + found;
+        let rest = &html[start..];
+        if rest.starts_with("<!--") {
+            let end = rest
+                .find("-->")
+                .ok_or_else(|| format!("line {}: comment is never closed", line_at(start)))?;
+            at = start + end + 3;
+            continue;
+        }
+        let closing = rest.starts_with("</");
+        let name_start = start + if closing { 2 } else { 1 };
+        let name: String = html[name_start..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect::<String>()
+            .to_lowercase();
+        let declaration = rest.starts_with("<!") || rest.starts_with("<?");
+        if name.is_empty() && !declaration {
+            // A `<` in text, such as `a < b`
+            at = start + 1;
+            continue;
+        }
+        let end = tag_end(html, start)
+            .ok_or_else(|| format!("line {}: tag is never closed with '>'", line_at(start)))?;
+        at = end + 1;
+        if declaration {
+            continue;
+        }
+
+        if closing {
+            match open.iter().rposition(|(tag, _)| *tag == name) {
+                Some(index) => {
+                    if let Some((tag, line)) = open[index + 1..]
+                        .iter()
+                        .find(|(tag, _)| !OPTIONAL_CLOSE.contains(&tag.as_str()))
+                    {
+                        return Err(format!(
+                            "line {}: </{}> closes <{}> from line {} before it was closed",
+                            line_at(start),
+                            name,
+                            tag,
+                            line
+                        ));
+                    }
+                    open.truncate(index);
+                }
+                None if OPTIONAL_CLOSE.contains(&name.as_str()) => {}
+                None => {
+                    return Err(format!(
+                        "line {}: </{}> has no matching <{}>",
+                        line_at(start),
+                        name,
+                        name
+                    ))
+                }
+            }
+        } else if RAW_TEXT.contains(&name.as_str()) {
+            let close = format!("</{}", name);
+            let body_end = lower[at..]
+                .find(&close)
+                .ok_or_else(|| format!("line {}: <{}> is never closed", line_at(start), name))?;
+            // Skip the closing tag as well
+            at = tag_end(html, at + body_end).map_or(html.len(), |end| end + 1);
+        } else if !VOID_ELEMENTS.contains(&name.as_str()) && !html[start..end].ends_with('/') {
+            open.push((name, line_at(start)));
+        }
+    }
+
+    match open
+        .iter()
+        .find(|(tag, _)| !OPTIONAL_CLOSE.contains(&tag.as_str()))
+    {
+        Some((tag, line)) => Err(format!("line {}: <{}> is never closed", line, tag)),
+        None => Ok(()),
+    }
+}
+
+/// Offset of the `>` ending the tag at `start`, skipping quoted attributes
+fn tag_end(html: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in html[start..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(start + i),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_structure() {
+        let page = r#"<!DOCTYPE html>
+<html>
+<head><title>a < b</title><meta charset="utf-8"></head>
+<body>
+  <!-- <div> in a comment -->
+  <ul><li>one<li>two</ul>
+  <p>if (a < b) <img src="x.png" alt="1 > 0"><br/>
+  <script>if (a < b && "</div>") {}</script>
+</body>
+</html>"#;
+        assert_eq!(check_html(page), Ok(()));
+        assert_eq!(
+            check_html("<div>\n<span>text</div>"),
+            Err("line 2: </div> closes <span> from line 2 before it was closed".to_string())
+        );
+        assert_eq!(
+            check_html("<main>\n<section></section>"),
+            Err("line 1: <main> is never closed".to_string())
+        );
+        assert!(check_html("</div>").is_err());
+        assert!(check_html("<div class=\"x\"").is_err());
+        assert!(check_html("<style>body {}").is_err());
+    }
+
+    #[test]
+    fn test_checks_by_extension() {
+        let check = |name: &str, content: &str| check_blocking(Path::new(name), content);
+        assert_eq!(check("data.json", "{\"a\": 1}"), SyntaxCheck::Passed);
+        assert!(matches!(
+            check("data.json", "{\"a\": }"),
+            SyntaxCheck::Failed(_)
+        ));
+        assert!(matches!(
+            check("Cargo.toml", "[package"),
+            SyntaxCheck::Failed(_)
+        ));
+        assert_eq!(check("notes.txt", "anything"), SyntaxCheck::Skipped);
+
+        // External checkers may be missing; they must never pass bad code
+        for (name, broken) in [
+            ("main.py", "def f(:\n    pass\n"),
+            ("app.js", "function (\n"),
+            ("main.rs", "fn main() {\n"),
+        ] {
+            match check(name, broken) {
+                SyntaxCheck::Failed(report) => assert!(report.contains(name), "{}", report),
+                other => assert_eq!(other, SyntaxCheck::Skipped),
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! The project's test command, for `bro --build --gate tests`
+//!
+//! The command is picked from the project files: `cargo test` next to a
+//! Cargo.toml, `npm test` when package.json defines a real test script, and
+//! `pytest` for Python projects with a pytest config or a tests directory.
+//! Only the tail of the output is kept; that is where failures end up.
+
+use shared::types::Result;
+use std::fmt;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+/// Characters of test output kept from the end
+const OUTPUT_TAIL_CHARS: usize = 4000;
+
+/// Longest a test run may take before it counts as failed
+const TEST_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Script npm writes into a new package.json
+const NPM_PLACEHOLDER_TEST: &str = "no test specified";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+/// Outcome of one test run
+#[derive(Debug, Clone)]
+pub struct TestRun {
+    pub passed: bool,
+    /// End of stdout and stderr
+    pub output: String,
+    pub elapsed: Duration,
+}
+
+/// Tests failed after an operation, which was rolled back
+#[derive(Debug, Clone)]
+pub struct TestGateFailed {
+    /// The operation, e.g. `Update src/lib.rs`
+    pub operation: String,
+    pub command: TestCommand,
+    pub run: TestRun,
+}
+
+impl fmt::Display for TestGateFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` failed after {}; the operation was rolled back",
+            self.command, self.operation
+        )
+    }
+}
+
+impl std::error::Error for TestGateFailed {}
+
+impl fmt::Display for TestCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+impl TestCommand {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    /// Test command of the project at `root`, if it has one
+    pub fn detect(root: &Path) -> Option<Self> {
+        if root.join("Cargo.toml").is_file() {
+            return Some(Self::new("cargo", &["test", "--quiet"]));
+        }
+        if let Ok(manifest) = std::fs::read_to_string(root.join("package.json")) {
+            let manifest: serde_json::Value = serde_json::from_str(&manifest).ok()?;
+            let script = manifest["scripts"]["test"].as_str()?;
+            return (!script.contains(NPM_PLACEHOLDER_TEST))
+                .then(|| Self::new("npm", &["test", "--silent"]));
+        }
+        let pytest_config = ["pytest.ini", "conftest.py"]
+            .iter()
+            .any(|file| root.join(file).is_file());
+        let python_project = ["pyproject.toml", "setup.py", "requirements.txt"]
+            .iter()
+            .any(|file| root.join(file).is_file());
+        if pytest_config || (python_project && root.join("tests").is_dir()) {
+            return Some(Self::new("python3", &["-m", "pytest", "-q"]));
+        }
+        None
+    }
+
+    /// Run the tests in `root`
+    pub async fn run(&self, root: &Path) -> Result<TestRun> {
+        let started = Instant::now();
+        let child = tokio::process::Command::new(&self.program)
+            .args(&self.args)
+            .current_dir(root)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Could not run `{}`: {}", self, e))?;
+
+        let Ok(output) = tokio::time::timeout(TEST_TIMEOUT, child.wait_with_output()).await else {
+            return Ok(TestRun {
+                passed: false,
+                output: format!("Timed out after {}s", TEST_TIMEOUT.as_secs()),
+                elapsed: started.elapsed(),
+            });
+        };
+        let output = output?;
+        let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(TestRun {
+            passed: output.status.success(),
+            output: tail(text.trim_end(), OUTPUT_TAIL_CHARS),
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+/// Last `max` characters of `text`
+fn tail(text: &str, max: usize) -> String {
+    let skip = text.chars().count().saturating_sub(max);
+    text.chars().skip(skip).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_detects_and_runs_the_project_tests() {
+        let root = std::env::temp_dir().join(format!("bro-test-gate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        assert_eq!(TestCommand::detect(&root), None);
+
+        std::fs::write(
+            root.join("package.json"),
+            r#"{"scripts": {"test": "echo \"Error: no test specified\" && exit 1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(TestCommand::detect(&root), None);
+        std::fs::write(
+            root.join("package.json"),
+            r#"{"scripts": {"test": "jest"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            TestCommand::detect(&root).unwrap().to_string(),
+            "npm test --silent"
+        );
+        std::fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        assert_eq!(
+            TestCommand::detect(&root).unwrap().to_string(),
+            "cargo test --quiet"
+        );
+
+        let failing = TestCommand::new("sh", &["-c", "echo ok; echo broken >&2; exit 1"]);
+        let run = failing.run(&root).await.unwrap();
+        assert!(!run.passed);
+        assert_eq!(run.output, "ok\nbroken");
+        assert!(
+            TestCommand::new("sh", &["-c", "true"])
+                .run(&root)
+                .await
+                .unwrap()
+                .passed
+        );
+        assert_eq!(tail("abcdef", 3), "def");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+//! Line-based three-way merge for files that changed after planning
+//!
+//! A build update is planned against the content the file had then (the
+//! base). When the file changed on disk in the meantime, both edits are
+//! diffed against the base: changes to different regions are combined, and
+//! changes that touch the same or adjacent lines differently are conflicts,
+//! written between `<<<<<<<` / `=======` / `>>>>>>>` markers.
+
+use similar::{Algorithm, DiffTag};
+
+/// Result of a merge; `text` holds conflict markers when `conflicts > 0`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeOutcome {
+    pub text: String,
+    pub conflicts: usize,
+}
+
+/// Hash identifying a file's content, to notice it changed
+pub fn content_hash(text: &str) -> String {
+    blake3::hash(text.as_bytes()).to_hex().to_string()
+}
+
+/// Base lines `start..end` replaced by `lines` on one side
+struct Hunk<'a> {
+    start: usize,
+    end: usize,
+    lines: &'a [&'a str],
+}
+
+/// Merge the changes `ours` and `theirs` made to `base`
+pub fn merge(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    our_name: &str,
+    their_name: &str,
+) -> MergeOutcome {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let (our_hunks, their_hunks) = (hunks(&base, &ours), hunks(&base, &theirs));
+
+    let mut text = String::new();
+    let mut conflicts = 0;
+    let mut pos = 0;
+    let (mut a, mut b) = (our_hunks.iter().peekable(), their_hunks.iter().peekable());
+    loop {
+        let start = match (a.peek(), b.peek()) {
+            (None, None) => break,
+            (Some(h), None) | (None, Some(h)) => h.start,
+            (Some(x), Some(y)) => x.start.min(y.start),
+        };
+        // Group hunks of both sides that overlap or touch
+        let (mut mine, mut yours) = (Vec::new(), Vec::new());
+        let mut end = start;
+        loop {
+            if let Some(h) = a.next_if(|h| h.start <= end) {
+                end = end.max(h.end);
+                mine.push(h);
+            } else if let Some(h) = b.next_if(|h| h.start <= end) {
+                end = end.max(h.end);
+                yours.push(h);
+            } else {
+                break;
+            }
+        }
+
+        text.extend(base[pos..start].iter().copied());
+        let ours_region = apply(&base, &mine, start, end);
+        let theirs_region = apply(&base, &yours, start, end);
+        if yours.is_empty() || ours_region == theirs_region {
+            text.push_str(&ours_region);
+        } else if mine.is_empty() {
+            text.push_str(&theirs_region);
+        } else {
+            conflicts += 1;
+            text.push_str(&format!("<<<<<<< {}\n", our_name));
+            push_line_block(&mut text, &ours_region);
+            text.push_str("=======\n");
+            push_line_block(&mut text, &theirs_region);
+            text.push_str(&format!(">>>>>>> {}\n", their_name));
+        }
+        pos = end;
+    }
+    text.extend(base[pos..].iter().copied());
+    MergeOutcome { text, conflicts }
+}
+
+fn hunks<'a>(base: &[&str], other: &'a [&'a str]) -> Vec<Hunk<'a>> {
+    similar::capture_diff_slices(Algorithm::Myers, base, other)
+        .iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| Hunk {
+            start: op.old_range().start,
+            end: op.old_range().end,
+            lines: &other[op.new_range()],
+        })
+        .collect()
+}
+
+/// Base lines `start..end` with `hunks` applied
+fn apply(base: &[&str], hunks: &[&Hunk], start: usize, end: usize) -> String {
+    let mut region = String::new();
+    let mut pos = start;
+    for hunk in hunks {
+        region.extend(base[pos..hunk.start].iter().copied());
+        region.extend(hunk.lines.iter().copied());
+        pos = hunk.end;
+    }
+    region.extend(base[pos..end].iter().copied());
+    region
+}
+
+fn push_line_block(text: &mut String, block: &str) {
+    text.push_str(block);
+    if !block.is_empty() && !block.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_combines_separate_changes_and_marks_conflicts() {
+        let base = "fn a() {}\n\nfn b() {}\n\nfn c() {}\n";
+        let ours = "fn a() { 1 }\n\nfn b() {}\n\nfn c() {}\n";
+        let theirs = "fn a() {}\n\nfn b() {}\n\nfn c() { 3 }\n";
+        let merged = merge(base, ours, theirs, "plan", "disk");
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(merged.text, "fn a() { 1 }\n\nfn b() {}\n\nfn c() { 3 }\n");
+        assert_eq!(merge(base, ours, ours, "plan", "disk").text, ours);
+
+        let theirs = "fn a() { 2 }\n\nfn b() {}\n\nfn c() {}\n";
+        let merged = merge(base, ours, theirs, "plan", "disk");
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(
+            merged.text,
+            "<<<<<<< plan\nfn a() { 1 }\n=======\nfn a() { 2 }\n>>>>>>> disk\n\nfn b() {}\n\nfn c() {}\n"
+        );
+
+        assert_eq!(content_hash(base), content_hash(base));
+        assert_ne!(content_hash(base), content_hash(ours));
+    }
+}
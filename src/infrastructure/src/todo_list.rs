@@ -0,0 +1,233 @@
+//! Project task list shared between the user and the agent
+//!
+//! Deferred work ("add tests for X") is kept in `<project>/.bro/todo.md` as a
+//! Markdown checklist, so it can be committed with the project and edited by
+//! hand. The agent reads and appends to it through the `todo_list` tool,
+//! `bro --todo` shows it, and open items are part of the build planning
+//! context so deferred work is picked up by later runs. Lines that are not
+//! checklist items are kept as they are.
+
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+
+/// Location of the list relative to the project root
+pub const TODO_FILE: &str = ".bro/todo.md";
+
+/// Open items included in planning context; older ones are still listed by `--todo`
+const MAX_PLANNING_ITEMS: usize = 20;
+
+const OPEN_MARKER: &str = "- [ ] ";
+const DONE_MARKER: &str = "- [x] ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoItem {
+    /// Position in the list, counting from 1, used to complete the item
+    pub number: usize,
+    pub text: String,
+    pub done: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoList {
+    path: PathBuf,
+}
+
+impl TodoList {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn for_project(root: &Path) -> Self {
+        Self::new(root.join(TODO_FILE))
+    }
+
+    /// List of the project containing the working directory
+    pub fn for_current_project() -> Self {
+        let root = crate::config::find_project_root()
+            .map(PathBuf::from)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        Self::for_project(&root)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every checklist item, in file order
+    pub fn items(&self) -> Vec<TodoItem> {
+        let content = std::fs::read_to_string(&self.path).unwrap_or_default();
+        parse_items(&content)
+    }
+
+    pub fn open_items(&self) -> Vec<TodoItem> {
+        self.items().into_iter().filter(|item| !item.done).collect()
+    }
+
+    /// Append an open item, creating the file if needed
+    pub fn add(&self, text: &str) -> Result<TodoItem> {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            return Err(anyhow::anyhow!("A TODO item needs a description"));
+        }
+        let mut content =
+            std::fs::read_to_string(&self.path).unwrap_or_else(|_| "# TODO\n\n".to_string());
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(OPEN_MARKER);
+        content.push_str(&text);
+        content.push('\n');
+        self.write(&content)?;
+
+        Ok(TodoItem {
+            number: parse_items(&content).len(),
+            text,
+            done: false,
+        })
+    }
+
+    /// Mark item `number` as done
+    pub fn complete(&self, number: usize) -> Result<TodoItem> {
+        let content = std::fs::read_to_string(&self.path).unwrap_or_default();
+        let mut seen = 0;
+        let mut completed = None;
+        let lines: Vec<String> = content
+            .lines()
+            .map(|line| match parse_line(line) {
+                Some((text, done)) => {
+                    seen += 1;
+                    if seen == number {
+                        completed = Some(TodoItem {
+                            number,
+                            text: text.to_string(),
+                            done: true,
+                        });
+                        if !done {
+                            let indent = &line[..line.len() - line.trim_start().len()];
+                            return format!("{}{}{}", indent, DONE_MARKER, text);
+                        }
+                    }
+                    line.to_string()
+                }
+                None => line.to_string(),
+            })
+            .collect();
+
+        let item = completed
+            .ok_or_else(|| anyhow::anyhow!("No TODO item {} in {}", number, self.path.display()))?;
+        self.write(&format!("{}\n", lines.join("\n")))?;
+        Ok(item)
+    }
+
+    /// Open items as a planning context section, if there are any
+    pub fn planning_context(&self) -> Option<String> {
+        let open = self.open_items();
+        if open.is_empty() {
+            return None;
+        }
+        let mut section = format!("Deferred work ({}):", TODO_FILE);
+        for item in open.iter().take(MAX_PLANNING_ITEMS) {
+            section.push_str(&format!("\n- {}", item.text));
+        }
+        if open.len() > MAX_PLANNING_ITEMS {
+            section.push_str(&format!(
+                "\n- ... and {} more",
+                open.len() - MAX_PLANNING_ITEMS
+            ));
+        }
+        Some(section)
+    }
+
+    fn write(&self, content: &str) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write-then-rename so a concurrent reader never sees a partial file
+        let tmp = self.path.with_extension("md.tmp");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn parse_items(content: &str) -> Vec<TodoItem> {
+    content
+        .lines()
+        .filter_map(parse_line)
+        .enumerate()
+        .map(|(i, (text, done))| TodoItem {
+            number: i + 1,
+            text: text.to_string(),
+            done,
+        })
+        .collect()
+}
+
+/// Text and state of a `- [ ] text` / `* [x] text` line
+fn parse_line(line: &str) -> Option<(&str, bool)> {
+    let rest = line
+        .trim_start()
+        .strip_prefix("- ")
+        .or_else(|| line.trim_start().strip_prefix("* "))?;
+    let (done, text) = if let Some(text) = rest.strip_prefix("[ ]") {
+        (false, text)
+    } else if let Some(text) = rest
+        .strip_prefix("[x]")
+        .or_else(|| rest.strip_prefix("[X]"))
+    {
+        (true, text)
+    } else {
+        return None;
+    };
+    let text = text.trim();
+    (!text.is_empty()).then_some((text, done))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_complete_and_keep_other_lines() {
+        let dir = std::env::temp_dir().join(format!("bro-todo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let list = TodoList::for_project(&dir);
+        assert!(list.planning_context().is_none());
+
+        list.add("add tests for  the parser").unwrap();
+        std::fs::write(
+            list.path(),
+            format!(
+                "{}Notes kept by hand\n",
+                std::fs::read_to_string(list.path()).unwrap()
+            ),
+        )
+        .unwrap();
+        let second = list.add("document --todo").unwrap();
+        assert_eq!(second.number, 2);
+
+        let done = list.complete(1).unwrap();
+        assert_eq!(done.text, "add tests for the parser");
+        assert!(list.complete(3).is_err());
+
+        let content = std::fs::read_to_string(list.path()).unwrap();
+        assert!(content.contains("- [x] add tests for the parser"));
+        assert!(content.contains("Notes kept by hand"));
+        assert_eq!(list.open_items().len(), 1);
+        assert_eq!(
+            list.planning_context().unwrap(),
+            "Deferred work (.bro/todo.md):\n- document --todo"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_checklist_lines() {
+        assert_eq!(parse_line("- [ ] write docs"), Some(("write docs", false)));
+        assert_eq!(parse_line("  * [X] done"), Some(("done", true)));
+        assert_eq!(parse_line("- plain bullet"), None);
+        assert_eq!(parse_line("- [ ]"), None);
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Prompt/completion token counts reported by the inference backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub requests: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.requests += other.requests;
+    }
+
+    /// Usage accumulated since an earlier snapshot of the same tracker
+    pub fn since(&self, earlier: &TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens.saturating_sub(earlier.prompt_tokens),
+            completion_tokens: self
+                .completion_tokens
+                .saturating_sub(earlier.completion_tokens),
+            requests: self.requests.saturating_sub(earlier.requests),
+        }
+    }
+}
+
+/// Thread-safe token counter shared between clones of an inference client
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker {
+    inner: Arc<Mutex<TokenUsage>>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process-wide tracker used by every `OllamaClient` unless told otherwise
+    pub fn global() -> &'static UsageTracker {
+        static GLOBAL: OnceLock<UsageTracker> = OnceLock::new();
+        GLOBAL.get_or_init(UsageTracker::new)
+    }
+
+    pub fn record(&self, prompt_tokens: u64, completion_tokens: u64) {
+        if let Ok(mut usage) = self.inner.lock() {
+            usage.prompt_tokens += prompt_tokens;
+            usage.completion_tokens += completion_tokens;
+            usage.requests += 1;
+        }
+    }
+
+    pub fn snapshot(&self) -> TokenUsage {
+        self.inner.lock().map(|usage| *usage).unwrap_or_default()
+    }
+}
+
+/// Token limits applied to agent loops; `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenBudget {
+    pub max_session_tokens: Option<u64>,
+    pub max_daily_tokens: Option<u64>,
+}
+
+impl TokenBudget {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_session_tokens.is_none() && self.max_daily_tokens.is_none()
+    }
+
+    /// Check totals against the budget, returning a human-readable reason when exceeded
+    pub fn check(&self, session_tokens: u64, daily_tokens: u64) -> Result<(), String> {
+        if let Some(limit) = self.max_session_tokens {
+            if session_tokens >= limit {
+                return Err(format!(
+                    "session token budget exhausted ({} / {} tokens)",
+                    session_tokens, limit
+                ));
+            }
+        }
+        if let Some(limit) = self.max_daily_tokens {
+            if daily_tokens >= limit {
+                return Err(format!(
+                    "daily token budget exhausted ({} / {} tokens)",
+                    daily_tokens, limit
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_accumulates_across_clones() {
+        let tracker = UsageTracker::new();
+        let clone = tracker.clone();
+
+        tracker.record(10, 5);
+        clone.record(3, 2);
+
+        let usage = tracker.snapshot();
+        assert_eq!(usage.prompt_tokens, 13);
+        assert_eq!(usage.completion_tokens, 7);
+        assert_eq!(usage.requests, 2);
+        assert_eq!(usage.total(), 20);
+    }
+
+    #[test]
+    fn test_usage_since_snapshot() {
+        let tracker = UsageTracker::new();
+        tracker.record(100, 50);
+        let before = tracker.snapshot();
+        tracker.record(20, 10);
+
+        let delta = tracker.snapshot().since(&before);
+        assert_eq!(delta.total(), 30);
+        assert_eq!(delta.requests, 1);
+    }
+
+    #[test]
+    fn test_budget_enforcement() {
+        let budget = TokenBudget {
+            max_session_tokens: Some(1_000),
+            max_daily_tokens: Some(5_000),
+        };
+
+        assert!(budget.check(999, 4_999).is_ok());
+        assert!(budget.check(1_000, 0).is_err());
+        assert!(budget.check(0, 5_000).is_err());
+        assert!(TokenBudget::default().check(u64::MAX, u64::MAX).is_ok());
+    }
+}
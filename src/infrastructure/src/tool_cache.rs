@@ -0,0 +1,246 @@
+//! Cached results of read-only tool calls
+//!
+//! Agent iterations keep re-reading the same files and re-running the same
+//! searches. Results of read-only tools are kept for the run, or for the
+//! whole session when configured, keyed by the tool and its canonicalized
+//! arguments. A search or a git call may depend on any file, so every change
+//! reported by the file watcher and every write through the tool registry
+//! drops all cached results rather than just those naming the file.
+
+use crate::tools::{ToolArgs, ToolOutput};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Tools whose output depends only on their arguments and the files on disk
+const CACHEABLE_TOOLS: &[&str] = &[
+    "file_read",
+    "directory_list",
+    "grep_search",
+    "find_files",
+    "awk_extract",
+    "git_status",
+    "git_diff",
+    "git_log",
+];
+
+/// Results kept per cache; further results are not cached
+const MAX_ENTRIES: usize = 256;
+
+/// Bumped whenever files may have changed; older entries are stale
+static FILES_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+static SESSION: OnceLock<Arc<ToolCache>> = OnceLock::new();
+
+/// How long cached tool results live
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToolCacheScope {
+    /// For one agent run
+    #[default]
+    Run,
+    /// For every run in this process
+    Session,
+    /// Not cached
+    Off,
+}
+
+impl ToolCacheScope {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "run" | "on" => Some(Self::Run),
+            "session" => Some(Self::Session),
+            "off" | "none" => Some(Self::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Key of one tool call, with the file generation it was made at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    text: String,
+    generation: u64,
+}
+
+struct Entry {
+    generation: u64,
+    output: ToolOutput,
+}
+
+#[derive(Default)]
+pub struct ToolCache {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ToolCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cache for `scope`: a fresh one per run, the process-wide one per
+    /// session, none when off
+    pub fn for_scope(scope: ToolCacheScope) -> Option<Arc<Self>> {
+        match scope {
+            ToolCacheScope::Run => Some(Arc::new(Self::new())),
+            ToolCacheScope::Session => Some(SESSION.get_or_init(Default::default).clone()),
+            ToolCacheScope::Off => None,
+        }
+    }
+
+    /// Key of calling `tool_name` with `args`; None when its results are
+    /// not cached
+    pub fn key(tool_name: &str, args: &ToolArgs) -> Option<CacheKey> {
+        if !CACHEABLE_TOOLS.contains(&tool_name) {
+            return None;
+        }
+        let working_directory = args
+            .working_directory
+            .as_deref()
+            .map(|dir| normalize_path(Path::new(dir)));
+        let parameters: BTreeMap<&str, String> = args
+            .parameters
+            .iter()
+            .map(|(name, value)| {
+                let value = match (name.as_str(), &working_directory) {
+                    ("path", Some(dir)) => normalize_path(&Path::new(dir).join(value.trim())),
+                    ("path", None) => normalize_path(Path::new(value.trim())),
+                    _ => value.clone(),
+                };
+                (name.as_str(), value)
+            })
+            .collect();
+        let text = serde_json::to_string(&(tool_name, working_directory, parameters)).ok()?;
+        Some(CacheKey {
+            text,
+            generation: FILES_GENERATION.load(Ordering::SeqCst),
+        })
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<ToolOutput> {
+        let generation = FILES_GENERATION.load(Ordering::SeqCst);
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(&key.text)
+            .filter(|entry| entry.generation == generation)
+            .map(|entry| entry.output.clone())
+    }
+
+    /// Keep `output` for `key` unless files changed since the key was made
+    pub fn insert(&self, key: CacheKey, output: ToolOutput) {
+        let generation = FILES_GENERATION.load(Ordering::SeqCst);
+        if key.generation != generation {
+            return;
+        }
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|_, entry| entry.generation == generation);
+            if entries.len() < MAX_ENTRIES || entries.contains_key(&key.text) {
+                entries.insert(key.text, Entry { generation, output });
+            }
+        }
+    }
+}
+
+/// Drop every cached result; called by the file watcher and after writes
+pub fn files_changed() {
+    FILES_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// `path` without `.` components and with `..` resolved where it can be,
+/// so `./src/../src/main.rs` and `src/main.rs` share a key
+fn normalize_path(path: &Path) -> String {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{ResourceUsage, SafeTool};
+    use std::time::Duration;
+
+    fn args(parameters: &[(&str, &str)], dir: Option<&str>) -> ToolArgs {
+        ToolArgs {
+            parameters: parameters
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            timeout: Some(Duration::from_secs(30)),
+            working_directory: dir.map(str::to_string),
+        }
+    }
+
+    fn output(stdout: &str) -> ToolOutput {
+        ToolOutput {
+            success: true,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: Some(0),
+            execution_time: Duration::from_millis(5),
+            resources_used: ResourceUsage {
+                memory_used_mb: 0,
+                cpu_time_seconds: 0.0,
+                processes_created: 0,
+                output_size: stdout.len(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_caches_read_only_calls_until_files_change() {
+        let key = |parameters: &[(&str, &str)], dir| {
+            ToolCache::key("grep_search", &args(parameters, dir)).unwrap()
+        };
+        assert_eq!(
+            key(
+                &[("pattern", "fn main"), ("path", "./src/../src")],
+                Some("/repo/")
+            ),
+            key(&[("path", " src/"), ("pattern", "fn main")], Some("/repo"))
+        );
+        assert_ne!(
+            key(&[("pattern", "fn main"), ("path", "src")], Some("/repo")),
+            key(&[("pattern", "fn main"), ("path", "src")], Some("/other"))
+        );
+        assert!(ToolCache::key("file_write", &args(&[("path", "a")], None)).is_none());
+        assert!(SafeTool::SedReplace.writes_files() && SafeTool::TodoList.writes_files());
+        assert!(!SafeTool::FileRead.writes_files());
+
+        let cache = ToolCache::new();
+        let read = key(&[("path", "src/main.rs")], None);
+        assert!(cache.get(&read).is_none());
+        cache.insert(read.clone(), output("fn main() {}"));
+        assert_eq!(cache.get(&read).unwrap().stdout, "fn main() {}");
+
+        files_changed();
+        assert!(cache.get(&read).is_none());
+        cache.insert(read.clone(), output("stale"));
+        assert!(cache.get(&key(&[("path", "src/main.rs")], None)).is_none());
+
+        assert_eq!(
+            ToolCacheScope::parse("Session"),
+            Some(ToolCacheScope::Session)
+        );
+        assert!(ToolCache::for_scope(ToolCacheScope::Off).is_none());
+        let session = ToolCache::for_scope(ToolCacheScope::Session).unwrap();
+        assert!(Arc::ptr_eq(
+            &session,
+            &ToolCache::for_scope(ToolCacheScope::Session).unwrap()
+        ));
+    }
+}
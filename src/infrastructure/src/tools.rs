@@ -1,9 +1,15 @@
+use crate::config_edit;
+use crate::mcp_client::McpTool;
+use crate::network_security::NetworkSecurity;
 use crate::observability::OBSERVABILITY;
 use crate::resource_enforcement::{ResourceEnforcer, ResourceLimits};
+use crate::todo_list::TodoList;
+use crate::tool_cache::{self, ToolCache};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 /// Tool execution arguments with validation
@@ -184,6 +190,8 @@ pub enum SafeTool {
     GitStatus,
     GitDiff,
     GitLog,
+    TodoList,
+    ConfigEdit,
 }
 
 impl SafeTool {
@@ -202,6 +210,8 @@ impl SafeTool {
             SafeTool::GitStatus => "git_status",
             SafeTool::GitDiff => "git_diff",
             SafeTool::GitLog => "git_log",
+            SafeTool::TodoList => "todo_list",
+            SafeTool::ConfigEdit => "config_edit",
         }
     }
 
@@ -222,6 +232,10 @@ impl SafeTool {
             SafeTool::GitStatus => "Get git repository status (read-only)",
             SafeTool::GitDiff => "Show git diffs between commits or working directory",
             SafeTool::GitLog => "Show git commit history with filtering options",
+            SafeTool::TodoList => "List, add or complete deferred work in the project TODO list",
+            SafeTool::ConfigEdit => {
+                "Set or delete values in TOML, YAML or JSON config files by path, keeping comments"
+            }
         }
     }
 
@@ -240,6 +254,8 @@ impl SafeTool {
             SafeTool::GitStatus => self.execute_git_status(args).await,
             SafeTool::GitDiff => self.execute_git_diff(args).await,
             SafeTool::GitLog => self.execute_git_log(args).await,
+            SafeTool::TodoList => self.execute_todo_list(args),
+            SafeTool::ConfigEdit => self.execute_config_edit(args),
         }
     }
 
@@ -258,6 +274,8 @@ impl SafeTool {
             SafeTool::GitStatus => self.validate_git_status_args(args),
             SafeTool::GitDiff => self.validate_git_diff_args(args),
             SafeTool::GitLog => self.validate_git_log_args(args),
+            SafeTool::TodoList => self.validate_todo_list_args(args),
+            SafeTool::ConfigEdit => self.validate_config_edit_args(args),
         }
     }
 
@@ -265,6 +283,37 @@ impl SafeTool {
         ResourceLimits::default()
     }
 
+    /// Whether calling the tool may change files; cached tool results are
+    /// dropped after it runs
+    pub fn writes_files(&self) -> bool {
+        match self {
+            SafeTool::FileWrite
+            | SafeTool::SedReplace
+            | SafeTool::TodoList
+            | SafeTool::ConfigEdit => true,
+            SafeTool::FileRead
+            | SafeTool::DirectoryList
+            | SafeTool::ProcessList
+            | SafeTool::GrepSearch
+            | SafeTool::FindFiles
+            | SafeTool::AwkExtract
+            | SafeTool::CurlFetch
+            | SafeTool::WebSearch
+            | SafeTool::GitStatus
+            | SafeTool::GitDiff
+            | SafeTool::GitLog => false,
+        }
+    }
+
+    /// URL the tool would request, checked against the network allowlist
+    fn egress_url(&self, args: &ToolArgs) -> Option<String> {
+        match self {
+            SafeTool::CurlFetch => args.parameters.get("url").cloned(),
+            SafeTool::WebSearch => Some("https://duckduckgo.com/".to_string()),
+            _ => None,
+        }
+    }
+
     // File read implementation
     async fn execute_file_read(&self, args: ToolArgs) -> Result<ToolOutput, ToolError> {
         let start_time = Instant::now();
@@ -625,9 +674,22 @@ impl SafeTool {
         let security_validator = ToolSecurityValidator::new();
         security_validator.validate_path(path)?;
 
-        // Use ripgrep (rg) for fast searching
+        // Use ripgrep (rg) for fast searching, plain grep where it is missing
+        let program = match args.parameters.get("engine").map(String::as_str) {
+            None | Some("rg") => "rg",
+            Some("grep") => "grep",
+            Some(other) => {
+                return Err(ToolError::ValidationError(format!(
+                    "Unknown search engine '{}'",
+                    other
+                )))
+            }
+        };
         let mut cmd_args: Vec<String> =
             vec!["--line-number".to_string(), "--with-filename".to_string()];
+        if program == "grep" {
+            cmd_args.push("--recursive".to_string());
+        }
 
         // Add case insensitive if requested
         if args
@@ -640,10 +702,15 @@ impl SafeTool {
 
         // Add include patterns
         if let Some(include) = args.parameters.get("include") {
-            cmd_args.push("--glob".to_string());
-            cmd_args.push(include.clone());
+            if program == "grep" {
+                cmd_args.push(format!("--include={}", include));
+            } else {
+                cmd_args.push("--glob".to_string());
+                cmd_args.push(include.clone());
+            }
         }
 
+        cmd_args.push("-e".to_string());
         cmd_args.push(pattern.clone());
         cmd_args.push(path.clone());
 
@@ -654,7 +721,7 @@ impl SafeTool {
 
         match enforcer
             .execute_with_limits(
-                "rg",
+                program,
                 &cmd_args_refs,
                 &limits,
                 args.working_directory.as_deref(),
@@ -1230,12 +1297,174 @@ impl SafeTool {
     fn validate_git_log_args(&self, _args: &ToolArgs) -> Result<(), ValidationError> {
         Ok(())
     }
+
+    // Project TODO list implementation
+    fn execute_todo_list(&self, args: ToolArgs) -> Result<ToolOutput, ToolError> {
+        let start_time = Instant::now();
+        let list = match &args.working_directory {
+            Some(dir) => TodoList::for_project(Path::new(dir)),
+            None => TodoList::for_current_project(),
+        };
+
+        let action = args.parameters.get("action").map_or("list", |a| a.as_str());
+        let stdout = match action {
+            "add" => {
+                let text = args.parameters.get("text").map_or("", |t| t.as_str());
+                let item = list
+                    .add(text)
+                    .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+                format!("Added TODO {}: {}", item.number, item.text)
+            }
+            "done" => {
+                let number = args
+                    .parameters
+                    .get("index")
+                    .and_then(|i| i.trim().parse::<usize>().ok())
+                    .unwrap_or_default();
+                let item = list
+                    .complete(number)
+                    .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+                format!("Completed TODO {}: {}", item.number, item.text)
+            }
+            _ => {
+                let open = list.open_items();
+                if open.is_empty() {
+                    "No open TODO items".to_string()
+                } else {
+                    open.iter()
+                        .map(|item| format!("{}. {}", item.number, item.text))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }
+            }
+        };
+
+        let execution_time = start_time.elapsed();
+        Ok(ToolOutput {
+            success: true,
+            resources_used: ResourceUsage {
+                memory_used_mb: 0,
+                cpu_time_seconds: execution_time.as_secs_f64(),
+                processes_created: 0,
+                output_size: stdout.len(),
+            },
+            stdout,
+            stderr: String::new(),
+            exit_code: Some(0),
+            execution_time,
+        })
+    }
+
+    fn validate_todo_list_args(&self, args: &ToolArgs) -> Result<(), ValidationError> {
+        let present = |field: &str| {
+            args.parameters
+                .get(field)
+                .is_some_and(|value| !value.trim().is_empty())
+        };
+        let (field, message) = match args.parameters.get("action").map_or("list", |a| a.as_str()) {
+            "list" => return Ok(()),
+            "add" if present("text") => return Ok(()),
+            "add" => ("text", "Text of the item to add is required"),
+            "done" if present("index") => return Ok(()),
+            "done" => ("index", "Number of the item to complete is required"),
+            _ => ("action", "Action must be list, add or done"),
+        };
+        Err(ValidationError {
+            field: field.to_string(),
+            message: message.to_string(),
+            severity: ValidationSeverity::Error,
+        })
+    }
+
+    // Structured config edit implementation
+    fn execute_config_edit(&self, args: ToolArgs) -> Result<ToolOutput, ToolError> {
+        let start_time = Instant::now();
+        let file_path = args
+            .parameters
+            .get("path")
+            .ok_or_else(|| ToolError::ValidationError("Missing 'path' parameter".to_string()))?;
+        let edits = args
+            .parameters
+            .get("edits")
+            .ok_or_else(|| ToolError::ValidationError("Missing 'edits' parameter".to_string()))?;
+        let dry_run = args
+            .parameters
+            .get("dry_run")
+            .is_some_and(|value| value == "true");
+
+        let security_validator = ToolSecurityValidator::new();
+        security_validator.validate_path(file_path)?;
+
+        let path = Path::new(file_path);
+        let format = config_edit::ConfigFormat::from_path(path)
+            .map_err(|e| ToolError::ValidationError(e.to_string()))?;
+        let changes = config_edit::parse_changes(edits)
+            .map_err(|e| ToolError::ValidationError(e.to_string()))?;
+        let original = fs::read_to_string(path)
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to read file: {}", e)))?;
+        let edited = config_edit::apply_changes(format, &original, &changes)
+            .map_err(|e| ToolError::ExecutionError(format!("Config edit failed: {}", e)))?;
+
+        let stdout = if edited == original {
+            "No changes".to_string()
+        } else {
+            if !dry_run {
+                fs::write(path, &edited).map_err(|e| {
+                    ToolError::ExecutionError(format!("Failed to write file: {}", e))
+                })?;
+            }
+            config_edit::diff(path, &original, &edited)
+        };
+
+        let execution_time = start_time.elapsed();
+        Ok(ToolOutput {
+            success: true,
+            resources_used: ResourceUsage {
+                memory_used_mb: 0,
+                cpu_time_seconds: execution_time.as_secs_f64(),
+                processes_created: 0,
+                output_size: stdout.len(),
+            },
+            stdout,
+            stderr: String::new(),
+            exit_code: Some(0),
+            execution_time,
+        })
+    }
+
+    fn validate_config_edit_args(&self, args: &ToolArgs) -> Result<(), ValidationError> {
+        for field in ["path", "edits"] {
+            if args
+                .parameters
+                .get(field)
+                .map_or(true, |value| value.trim().is_empty())
+            {
+                return Err(ValidationError {
+                    field: field.to_string(),
+                    message: format!("{} parameter is required", field),
+                    severity: ValidationSeverity::Error,
+                });
+            }
+        }
+        if args.parameters["path"].contains("..") {
+            return Err(ValidationError {
+                field: "path".to_string(),
+                message: "Path traversal not allowed".to_string(),
+                severity: ValidationSeverity::Critical,
+            });
+        }
+        Ok(())
+    }
 }
 
 /// Tool registry for managing available tools
 pub struct ToolRegistry {
     tools: HashMap<String, SafeTool>,
+    /// Tools mounted from MCP servers, by qualified name
+    mcp_tools: HashMap<String, McpTool>,
     policy_engine: crate::policy_engine::PolicyEngine,
+    /// Results of read-only calls, shared by the registries of one run
+    cache: Option<Arc<ToolCache>>,
 }
 
 impl ToolRegistry {
@@ -1254,13 +1483,28 @@ impl ToolRegistry {
         tools.insert("git_status".to_string(), SafeTool::GitStatus);
         tools.insert("git_diff".to_string(), SafeTool::GitDiff);
         tools.insert("git_log".to_string(), SafeTool::GitLog);
+        tools.insert("todo_list".to_string(), SafeTool::TodoList);
+        tools.insert("config_edit".to_string(), SafeTool::ConfigEdit);
+
+        let mcp_tools = crate::mcp_client::mounted()
+            .iter()
+            .map(|tool| (tool.qualified_name(), tool.clone()))
+            .collect();
 
         Self {
             tools,
-            policy_engine: crate::policy_engine::PolicyEngine::new(),
+            mcp_tools,
+            policy_engine: crate::policy_engine::PolicyEngine::from_config(),
+            cache: None,
         }
     }
 
+    /// Answer repeated read-only calls from `cache`
+    pub fn with_cache(mut self, cache: Option<Arc<ToolCache>>) -> Self {
+        self.cache = cache;
+        self
+    }
+
     pub async fn execute_tool(
         &self,
         tool_name: &str,
@@ -1272,6 +1516,12 @@ impl ToolRegistry {
         let _trace = OBSERVABILITY.start_request_trace(&format!("tool_{}", tool_name));
 
         let result = async {
+            if let Some(tool) = self.mcp_tools.get(tool_name) {
+                tool.validate_args(&args.parameters)?;
+                self.check_policy(tool_name, &args).await?;
+                return tool.call(&args.parameters, args.timeout).await;
+            }
+
             let tool = self.tools.get(tool_name).ok_or_else(|| {
                 ToolError::ValidationError(format!("Tool '{}' not found", tool_name))
             })?;
@@ -1282,10 +1532,29 @@ impl ToolRegistry {
 
             // Policy check before execution
             self.check_policy(tool_name, &args).await?;
+            if let Some(url) = tool.egress_url(&args) {
+                check_egress(&url)?;
+            }
 
-            tool.execute(args).await
+            let cached = self.cache.as_ref().zip(ToolCache::key(tool_name, &args));
+            if let Some(output) = cached.as_ref().and_then(|(cache, key)| cache.get(key)) {
+                return Ok(output);
+            }
+            let output = tool.execute(args).await?;
+            if let Some((cache, key)) = cached.filter(|_| output.success) {
+                cache.insert(key, output.clone());
+            }
+            Ok(output)
         }
         .await;
+        // MCP tools may write anything
+        let writes_files = self.tools.get(tool_name).map_or(
+            self.mcp_tools.contains_key(tool_name),
+            SafeTool::writes_files,
+        );
+        if writes_files {
+            tool_cache::files_changed();
+        }
 
         let execution_time = start_time.elapsed();
         let success = result.is_ok();
@@ -1340,6 +1609,8 @@ impl ToolRegistry {
             .collect::<Vec<_>>();
 
         match evaluate_tool_request(
+            &self.policy_engine,
+            crate::command_audit::current_session(),
             tool_name,
             &args.parameters,
             &resource_limits,
@@ -1351,9 +1622,12 @@ impl ToolRegistry {
         {
             Ok(decision) => match decision.action {
                 crate::policy_engine::PolicyAction::Allow => Ok(()),
-                crate::policy_engine::PolicyAction::Deny(reason) => Err(
-                    ToolError::SecurityViolation(format!("Policy denied: {}", reason)),
-                ),
+                crate::policy_engine::PolicyAction::Deny(reason) => {
+                    Err(ToolError::SecurityViolation(match decision.rule {
+                        Some(rule) => format!("Policy denied: {} (rule {})", reason, rule),
+                        None => format!("Policy denied: {}", reason),
+                    }))
+                }
                 crate::policy_engine::PolicyAction::RequireApproval(reason) => Err(
                     ToolError::SecurityViolation(format!("Approval required: {}", reason)),
                 ),
@@ -1370,7 +1644,27 @@ impl ToolRegistry {
     }
 
     pub fn list_tools(&self) -> Vec<String> {
-        self.tools.keys().cloned().collect()
+        self.tools
+            .keys()
+            .chain(self.mcp_tools.keys())
+            .cloned()
+            .collect()
+    }
+
+    /// Tools mounted from MCP servers
+    pub fn mcp_tools(&self) -> impl Iterator<Item = &McpTool> {
+        self.mcp_tools.values()
+    }
+}
+
+/// Refuse requests the network policy does not allow; both tools run curl
+fn check_egress(url: &str) -> Result<(), ToolError> {
+    let config = crate::config::Config::load();
+    match NetworkSecurity::for_egress(&config.security.network_security) {
+        Some(egress) => egress
+            .check_command("curl", &[url.to_string()])
+            .map_err(|denied| ToolError::SecurityViolation(denied.to_string())),
+        None => Ok(()),
     }
 }
 
@@ -1396,5 +1690,7 @@ pub fn create_safe_tools() -> Vec<SafeTool> {
         SafeTool::GitStatus,
         SafeTool::GitDiff,
         SafeTool::GitLog,
+        SafeTool::TodoList,
+        SafeTool::ConfigEdit,
     ]
 }
@@ -0,0 +1,306 @@
+//! Snapshot-based undo that works without git
+//!
+//! Every committed build transaction appends an entry to
+//! `<project>/.bro/undo/journal.jsonl` naming the files it touched and the
+//! content they had before, kept once per distinct content under
+//! `.bro/undo/blobs/`. `bro --undo` puts the newest entries' pre-images back
+//! and drops them from the journal. A file edited since the build is moved to
+//! the trash before it is overwritten, so undo never loses work.
+
+use crate::recycle_bin::RecycleBin;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+/// Location of the journal relative to the project root
+pub const UNDO_DIR: &str = ".bro/undo";
+
+/// Entries kept; older ones can no longer be undone
+const MAX_ENTRIES: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalFile {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    /// Blob with the content before the change; None when the file was created
+    pub before: Option<String>,
+    /// Blob hash of the content the change left; None when it deleted the file
+    pub after: Option<String>,
+}
+
+/// Files one committed transaction changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub description: String,
+    pub timestamp: DateTime<Utc>,
+    pub files: Vec<JournalFile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UndoJournal {
+    root: PathBuf,
+}
+
+impl UndoJournal {
+    /// Journal of the project at `root`
+    pub fn for_project(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+
+    /// Journal of the project containing the working directory
+    pub fn for_current_project() -> Self {
+        let root = crate::config::find_project_root()
+            .map(PathBuf::from)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        Self::for_project(&root)
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.root.join(UNDO_DIR)
+    }
+
+    fn journal_path(&self) -> PathBuf {
+        self.dir().join("journal.jsonl")
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.dir().join("blobs").join(hash)
+    }
+
+    /// Record a change; `pre_images` pairs each touched path with its content
+    /// before the change, None when it did not exist
+    pub fn record(
+        &self,
+        description: &str,
+        pre_images: &[(PathBuf, Option<Vec<u8>>)],
+    ) -> Result<JournalEntry> {
+        let mut files = Vec::with_capacity(pre_images.len());
+        for (path, before) in pre_images {
+            let before = match before {
+                Some(content) => Some(self.store_blob(content)?),
+                None => None,
+            };
+            let after = std::fs::read(path)
+                .ok()
+                .map(|content| content_hash(&content));
+            files.push(JournalFile {
+                path: self.relative(path)?,
+                before,
+                after,
+            });
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let entry = JournalEntry {
+            description: description.to_string(),
+            timestamp: Utc::now(),
+            files,
+        };
+
+        let mut entries = self.entries()?;
+        entries.push(entry.clone());
+        if entries.len() > MAX_ENTRIES {
+            entries.drain(..entries.len() - MAX_ENTRIES);
+        }
+        self.write_entries(&entries)?;
+        Ok(entry)
+    }
+
+    /// Every entry, oldest first
+    pub fn entries(&self) -> Result<Vec<JournalEntry>> {
+        let Ok(content) = std::fs::read_to_string(self.journal_path()) else {
+            return Ok(Vec::new());
+        };
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// Put back the pre-images of the newest `steps` entries, newest first,
+    /// and drop them from the journal; returns the entries undone
+    pub fn undo(&self, steps: usize) -> Result<Vec<JournalEntry>> {
+        let mut entries = self.entries()?;
+        if entries.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Nothing to undo in {}",
+                self.dir().display()
+            ));
+        }
+
+        let trash = RecycleBin::for_project(&self.root);
+        let mut undone = Vec::new();
+        while undone.len() < steps {
+            let Some(entry) = entries.pop() else {
+                break;
+            };
+            for file in &entry.files {
+                self.restore(file, &trash)?;
+            }
+            // Written after every entry so a failure leaves the journal in step
+            self.write_entries(&entries)?;
+            undone.push(entry);
+        }
+        Ok(undone)
+    }
+
+    fn restore(&self, file: &JournalFile, trash: &RecycleBin) -> Result<()> {
+        let target = self.root.join(&file.path);
+        let current = std::fs::read(&target)
+            .ok()
+            .map(|content| content_hash(&content));
+        if current.is_some() && current != file.after {
+            let kept = trash.trash(&target)?;
+            eprintln!(
+                "Warning: {} changed since the build; kept your version in {}",
+                file.path.display(),
+                kept.display()
+            );
+        }
+        match &file.before {
+            Some(hash) => {
+                let content = std::fs::read(self.blob_path(hash)).map_err(|e| {
+                    anyhow::anyhow!(
+                        "Snapshot of {} is missing from {}: {}",
+                        file.path.display(),
+                        self.dir().display(),
+                        e
+                    )
+                })?;
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&target, content)?;
+            }
+            None if target.exists() => std::fs::remove_file(&target)?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn store_blob(&self, content: &[u8]) -> Result<String> {
+        let hash = content_hash(content);
+        let path = self.blob_path(&hash);
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, content)?;
+        }
+        Ok(hash)
+    }
+
+    /// Rewrite the journal and remove blobs no entry refers to anymore
+    fn write_entries(&self, entries: &[JournalEntry]) -> Result<()> {
+        std::fs::create_dir_all(self.dir())?;
+        let mut out = std::fs::File::create(self.journal_path())?;
+        for entry in entries {
+            writeln!(out, "{}", serde_json::to_string(entry)?)?;
+        }
+
+        let live: HashSet<&str> = entries
+            .iter()
+            .flat_map(|entry| &entry.files)
+            .filter_map(|file| file.before.as_deref())
+            .collect();
+        if let Ok(blobs) = std::fs::read_dir(self.dir().join("blobs")) {
+            for blob in blobs.flatten() {
+                if !live.contains(blob.file_name().to_string_lossy().as_ref()) {
+                    let _ = std::fs::remove_file(blob.path());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn relative(&self, path: &Path) -> Result<PathBuf> {
+        let absolute = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir()?.join(path)
+        };
+        let relative = absolute
+            .strip_prefix(&self.root)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "{} is outside the project at {}",
+                    path.display(),
+                    self.root.display()
+                )
+            })?
+            .to_path_buf();
+        if relative
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(anyhow::anyhow!(
+                "{} is not a plain path inside the project",
+                path.display()
+            ));
+        }
+        Ok(relative)
+    }
+}
+
+fn content_hash(content: &[u8]) -> String {
+    blake3::hash(content).to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_restores_pre_images_newest_first() {
+        let root = std::env::temp_dir().join(format!("bro-undo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let journal = UndoJournal::for_project(&root);
+        let lib = root.join("src/lib.rs");
+        let new = root.join("src/new.rs");
+
+        std::fs::write(&lib, "v1").unwrap();
+        std::fs::write(&lib, "v2").unwrap();
+        std::fs::write(&new, "created").unwrap();
+        journal
+            .record(
+                "first",
+                &[(lib.clone(), Some(b"v1".to_vec())), (new.clone(), None)],
+            )
+            .unwrap();
+        std::fs::write(&lib, "v3").unwrap();
+        journal
+            .record("second", &[(lib.clone(), Some(b"v2".to_vec()))])
+            .unwrap();
+        assert_eq!(journal.entries().unwrap().len(), 2);
+
+        let undone = journal.undo(1).unwrap();
+        assert_eq!(undone[0].description, "second");
+        assert_eq!(std::fs::read_to_string(&lib).unwrap(), "v2");
+
+        // An edit made since the build goes to the trash instead of being lost
+        std::fs::write(&new, "edited").unwrap();
+        let undone = journal.undo(5).unwrap();
+        assert_eq!(undone.len(), 1);
+        assert_eq!(std::fs::read_to_string(&lib).unwrap(), "v1");
+        assert!(!new.exists());
+        let batches = RecycleBin::for_project(&root).batches();
+        assert_eq!(batches[0].files, vec![PathBuf::from("src/new.rs")]);
+
+        assert!(journal.entries().unwrap().is_empty());
+        assert!(journal.undo(1).is_err());
+        assert_eq!(
+            std::fs::read_dir(journal.dir().join("blobs"))
+                .unwrap()
+                .count(),
+            0
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
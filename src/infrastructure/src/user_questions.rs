@@ -0,0 +1,219 @@
+//! Questions the agent asks the user in the middle of a run
+//!
+//! A question from the `ask_user` tool is written to
+//! `<data dir>/questions/<id>.json` while the agent waits. The web server
+//! lists the same files and voice mode reads new ones out, so the question
+//! can be answered from the terminal, a browser or by voice; the first answer
+//! wins. Questions nobody answers in time are left to the agent's judgement.
+
+use crate::file_queue::{FileQueue, QueueEntry};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shared::platform;
+use shared::types::Result;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingQuestion {
+    pub id: String,
+    pub question: String,
+    /// Answers the agent suggests; any other answer is accepted too
+    #[serde(default)]
+    pub options: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// Left unanswered after this
+    pub expires_at: DateTime<Utc>,
+    pub answer: Option<String>,
+    /// Who answered: `cli`, `web` or `voice`
+    pub answered_by: Option<String>,
+}
+
+impl PendingQuestion {
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+
+    /// The question with its numbered options, for showing or speaking it
+    pub fn prompt(&self) -> String {
+        let mut prompt = self.question.clone();
+        for (i, option) in self.options.iter().enumerate() {
+            prompt.push_str(&format!("\n  {}. {}", i + 1, option));
+        }
+        prompt
+    }
+
+    /// `answer` with an option number replaced by the option it picks
+    pub fn resolve(&self, answer: &str) -> String {
+        let answer = answer.trim();
+        answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| self.options.get(i))
+            .cloned()
+            .unwrap_or_else(|| answer.to_string())
+    }
+}
+
+impl QueueEntry for PendingQuestion {
+    const KIND: &'static str = "question";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+/// Questions shared by all local bro processes
+#[derive(Debug, Clone)]
+pub struct QuestionQueue {
+    queue: FileQueue<PendingQuestion>,
+}
+
+impl QuestionQueue {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            queue: FileQueue::new(dir),
+        }
+    }
+
+    pub fn open_default() -> Self {
+        Self::new(platform::app_data_dir().join("questions"))
+    }
+
+    /// Ask `question`, open for answers during `timeout`
+    pub fn submit(
+        &self,
+        question: &str,
+        options: &[String],
+        timeout: Duration,
+    ) -> Result<PendingQuestion> {
+        let created_at = Utc::now();
+        let pending = PendingQuestion {
+            id: uuid::Uuid::new_v4().to_string(),
+            question: question.to_string(),
+            options: options.to_vec(),
+            created_at,
+            expires_at: created_at
+                + chrono::Duration::from_std(timeout).unwrap_or(chrono::Duration::hours(1)),
+            answer: None,
+            answered_by: None,
+        };
+        self.queue.insert(&pending)?;
+        Ok(pending)
+    }
+
+    /// Questions still waiting for an answer, oldest first; expired ones are
+    /// discarded
+    pub fn list(&self) -> Result<Vec<PendingQuestion>> {
+        let mut pending = self.queue.list(PendingQuestion::is_expired)?;
+        pending.retain(|question| question.answer.is_none());
+        Ok(pending)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<PendingQuestion>> {
+        self.queue.get(id)
+    }
+
+    /// Answer a question that is still open
+    pub fn answer(&self, id: &str, answer: &str, source: &str) -> Result<PendingQuestion> {
+        self.queue.modify(id, |pending| {
+            if pending.is_expired() {
+                return Err(anyhow::anyhow!("Question {} has expired", id));
+            }
+            if pending.answer.is_some() {
+                return Err(anyhow::anyhow!("Question {} was already answered", id));
+            }
+            if answer.trim().is_empty() {
+                return Err(anyhow::anyhow!("The answer to question {} is empty", id));
+            }
+            pending.answer = Some(pending.resolve(answer));
+            pending.answered_by = Some(source.to_string());
+            Ok(())
+        })
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        self.queue.remove(id)
+    }
+
+    /// Wait for the answer to `id`; None when it expires or disappears
+    /// unanswered. The question is removed either way.
+    pub async fn wait(&self, id: &str) -> Result<Option<PendingQuestion>> {
+        self.queue
+            .wait(
+                id,
+                |question| question.answer.is_some(),
+                PendingQuestion::is_expired,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_waiting_question_gets_remote_answer_or_expires() {
+        let dir = std::env::temp_dir().join(format!("bro-questions-{}", uuid::Uuid::new_v4()));
+        let queue = QuestionQueue::new(&dir);
+
+        let pending = queue
+            .submit(
+                "Which database should the service use?",
+                &["PostgreSQL".to_string(), "SQLite".to_string()],
+                Duration::from_secs(60),
+            )
+            .unwrap();
+        assert_eq!(
+            pending.prompt(),
+            "Which database should the service use?\n  1. PostgreSQL\n  2. SQLite"
+        );
+        assert_eq!(queue.list().unwrap()[0].id, pending.id);
+        assert!(queue.answer(&pending.id, "  ", "web").is_err());
+        let remote = queue.clone();
+        let id = pending.id.clone();
+        tokio::spawn(async move { remote.answer(&id, "2", "web").unwrap() });
+        let answered = queue.wait(&pending.id).await.unwrap().unwrap();
+        assert_eq!(answered.answer.as_deref(), Some("SQLite"));
+        assert_eq!(answered.answered_by.as_deref(), Some("web"));
+        assert!(queue.get(&pending.id).unwrap().is_none());
+
+        let expired = queue.submit("Deploy now?", &[], Duration::ZERO).unwrap();
+        assert!(queue.answer(&expired.id, "yes", "voice").is_err());
+        assert!(queue.wait(&expired.id).await.unwrap().is_none());
+        assert!(queue.list().unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_only_the_first_answer_wins() {
+        let dir = std::env::temp_dir().join(format!("bro-questions-{}", uuid::Uuid::new_v4()));
+        let queue = QuestionQueue::new(&dir);
+        let pending = queue
+            .submit("Deploy now?", &[], Duration::from_secs(60))
+            .unwrap();
+
+        let accepted = std::thread::scope(|scope| {
+            let answers: Vec<_> = ["yes", "no", "later", "never"]
+                .into_iter()
+                .map(|answer| {
+                    let (queue, id) = (&queue, &pending.id);
+                    scope.spawn(move || queue.answer(id, answer, "web").is_ok())
+                })
+                .collect();
+            answers
+                .into_iter()
+                .map(|answer| answer.join().unwrap())
+                .filter(|&accepted| accepted)
+                .count()
+        });
+        assert_eq!(accepted, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
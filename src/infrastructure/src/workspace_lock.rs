@@ -0,0 +1,210 @@
+//! One build session per workspace
+//!
+//! A bro process that applies operations holds `<project>/.bro/build.lock`,
+//! which says who owns it. A second process refuses to start instead of
+//! interleaving its writes with the first one's; `--steal-lock` takes over a
+//! lock left behind by a process that crashed or was killed. The lock file is
+//! removed when the [`WorkspaceLock`] is dropped.
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use shared::types::Result;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Location of the lock relative to the project root
+pub const LOCK_FILE: &str = ".bro/build.lock";
+
+/// Who holds a workspace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub host: String,
+    pub user: String,
+    pub started_at: DateTime<Utc>,
+    /// What the owner is doing, e.g. the build goal
+    pub purpose: String,
+}
+
+impl LockOwner {
+    fn current(purpose: &str) -> Self {
+        Self {
+            pid: std::process::id(),
+            host: hostname(),
+            user: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_else(|_| "unknown".to_string()),
+            started_at: Utc::now(),
+            purpose: purpose.to_string(),
+        }
+    }
+
+    /// Whether the owner has certainly exited; only knowable on this host
+    pub fn is_stale(&self) -> bool {
+        self.host == hostname() && !process_alive(self.pid)
+    }
+}
+
+impl std::fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "process {} of {}@{}, since {} ({})",
+            self.pid,
+            self.user,
+            self.host,
+            self.started_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S"),
+            self.purpose
+        )
+    }
+}
+
+/// Exclusive hold on a workspace; released when dropped
+#[derive(Debug)]
+pub struct WorkspaceLock {
+    path: PathBuf,
+    owner: LockOwner,
+}
+
+impl WorkspaceLock {
+    /// Take the lock of the project at `root` for `purpose`. Fails, naming the
+    /// owner, when another process holds it, unless `steal` is set.
+    pub fn acquire(root: &Path, purpose: &str, steal: bool) -> Result<Self> {
+        let path = root.join(LOCK_FILE);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let owner = LockOwner::current(purpose);
+        let content = serde_json::to_string_pretty(&owner)?;
+
+        // Once for a free workspace, and once more after removing a stolen lock
+        for _ in 0..2 {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    file.write_all(content.as_bytes())?;
+                    file.sync_all()?;
+                    return Ok(Self { path, owner });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if !steal {
+                        return Err(held_error(&path));
+                    }
+                    if let Some(previous) = Self::holder(root) {
+                        eprintln!("Warning: taking over the workspace lock from {}", previous);
+                    }
+                    std::fs::remove_file(&path)?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(held_error(&path))
+    }
+
+    /// Current owner of the project's lock, if any
+    pub fn holder(root: &Path) -> Option<LockOwner> {
+        let content = std::fs::read_to_string(root.join(LOCK_FILE)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn owner(&self) -> &LockOwner {
+        &self.owner
+    }
+}
+
+impl Drop for WorkspaceLock {
+    fn drop(&mut self) {
+        // Leave the file alone if someone stole the lock from us meanwhile
+        let ours = std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<LockOwner>(&content).ok())
+            .is_some_and(|owner| owner == self.owner);
+        if ours {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn held_error(path: &Path) -> anyhow::Error {
+    let owner = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<LockOwner>(&content).ok());
+    match owner {
+        Some(owner) if owner.is_stale() => anyhow::anyhow!(
+            "The workspace is locked by {}, which is no longer running. \
+             Rerun with --steal-lock to take the lock over.",
+            owner
+        ),
+        Some(owner) => anyhow::anyhow!(
+            "Another bro build is applying changes in this workspace: {}. \
+             Wait for it to finish, or rerun with --steal-lock if it is stuck.",
+            owner
+        ),
+        None => anyhow::anyhow!(
+            "The workspace is locked ({} exists but names no owner). \
+             Rerun with --steal-lock if no other bro is running.",
+            path.display()
+        ),
+    }
+}
+
+pub(crate) fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Without a cheap check, assume the owner may still be running
+#[cfg(not(target_os = "linux"))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_session_is_refused_until_released_or_stolen() {
+        let root = std::env::temp_dir().join(format!("bro-lock-{}", uuid::Uuid::new_v4()));
+        let first = WorkspaceLock::acquire(&root, "build: add tests", false).unwrap();
+        assert_eq!(WorkspaceLock::holder(&root).as_ref(), Some(first.owner()));
+
+        let refused = WorkspaceLock::acquire(&root, "build: other", false).unwrap_err();
+        assert!(refused.to_string().contains("build: add tests"));
+        assert!(refused.to_string().contains("--steal-lock"));
+
+        let stolen = WorkspaceLock::acquire(&root, "build: other", true).unwrap();
+        // The previous holder must not release a lock it no longer owns
+        drop(first);
+        assert_eq!(WorkspaceLock::holder(&root).as_ref(), Some(stolen.owner()));
+
+        drop(stolen);
+        assert!(WorkspaceLock::holder(&root).is_none());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_lock_of_an_exited_process_is_stale() {
+        let owner = LockOwner {
+            pid: u32::MAX,
+            ..LockOwner::current("build")
+        };
+        assert!(cfg!(not(target_os = "linux")) || owner.is_stale());
+        assert!(!LockOwner::current("build").is_stale());
+    }
+}
@@ -0,0 +1,75 @@
+//! Optimistic concurrency for state several processes write
+//!
+//! The daemon and the CLI may both write a session or a conversation in
+//! semantic memory. Stored values carry a version counter bumped on every
+//! write; a writer that finds a version it has not seen is in conflict and
+//! settles it by the configured policy instead of overwriting silently.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Ask whether to merge or keep one side; merge when nobody can be asked
+    #[default]
+    Interactive,
+    /// Overwrite with the newer write, warning about it
+    LastWriterWins,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictChoice {
+    /// Combine both writes
+    Merge,
+    /// Write ours over theirs
+    KeepOurs,
+    /// Drop our write
+    KeepTheirs,
+}
+
+/// A write that found a value changed by another process since it was read
+#[derive(Debug, Clone)]
+pub struct WriteConflict {
+    /// What is written, e.g. `session 'main'`
+    pub what: String,
+    /// Version the writer last saw
+    pub expected: u64,
+    pub found: u64,
+    /// One-line summaries of both sides
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// Asks the user how to settle a conflict
+pub type ConflictResolver = Arc<dyn Fn(&WriteConflict) -> ConflictChoice + Send + Sync>;
+
+impl ConflictPolicy {
+    pub fn resolve(
+        self,
+        conflict: &WriteConflict,
+        resolver: Option<&ConflictResolver>,
+    ) -> ConflictChoice {
+        match (self, resolver) {
+            (ConflictPolicy::LastWriterWins, _) => {
+                tracing::warn!(
+                    "{} changed in another process (version {} -> {}); overwriting it",
+                    conflict.what,
+                    conflict.expected,
+                    conflict.found
+                );
+                ConflictChoice::KeepOurs
+            }
+            (ConflictPolicy::Interactive, Some(resolver)) => resolver(conflict),
+            (ConflictPolicy::Interactive, None) => {
+                tracing::warn!(
+                    "{} changed in another process (version {} -> {}); merging both writes",
+                    conflict.what,
+                    conflict.expected,
+                    conflict.found
+                );
+                ConflictChoice::Merge
+            }
+        }
+    }
+}
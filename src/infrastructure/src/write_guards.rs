@@ -0,0 +1,108 @@
+//! Guards against generated file content that is probably a model failure
+//!
+//! A reply cut off mid-file, a dump of base64 or binary noise, or an
+//! "update" that replaces a long file with a stub all look like ordinary
+//! Create/Update operations. [`WriteGuards::check`] flags them so the build
+//! can refuse them or ask again with a stronger prompt.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WriteGuards {
+    /// Largest file a generated operation may write, in bytes
+    pub max_file_bytes: usize,
+    /// Allow content with NUL bytes or mostly control characters
+    pub allow_binary: bool,
+    /// Smallest share of an existing file's size an update may leave
+    pub min_kept_ratio: f64,
+    /// Files smaller than this may shrink freely, in bytes
+    pub shrink_floor_bytes: usize,
+}
+
+impl Default for WriteGuards {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 1024 * 1024,
+            allow_binary: false,
+            min_kept_ratio: 0.25,
+            shrink_floor_bytes: 400,
+        }
+    }
+}
+
+/// Share of control characters above which text counts as binary
+const MAX_CONTROL_RATIO: f64 = 0.1;
+
+impl WriteGuards {
+    /// Reasons not to write `new` over `old` (None for a new file); empty
+    /// when the write looks sound
+    pub fn check(&self, old: Option<&str>, new: &str) -> Vec<String> {
+        let mut violations = Vec::new();
+        if new.len() > self.max_file_bytes {
+            violations.push(format!(
+                "{} bytes is over the {} byte limit",
+                new.len(),
+                self.max_file_bytes
+            ));
+        }
+        if !self.allow_binary && looks_binary(new) {
+            violations.push("content looks binary".to_string());
+        }
+        if let Some(old) = old {
+            let kept = new.len() as f64 / old.len().max(1) as f64;
+            if old.len() >= self.shrink_floor_bytes && kept < self.min_kept_ratio {
+                violations.push(format!(
+                    "would truncate the file from {} to {} bytes ({:.0}% kept)",
+                    old.len(),
+                    new.len(),
+                    kept * 100.0
+                ));
+            }
+        }
+        violations
+    }
+}
+
+/// NUL bytes, or more control characters than text ever has
+pub fn looks_binary(content: &str) -> bool {
+    if content.contains('\0') {
+        return true;
+    }
+    let sample: Vec<char> = content.chars().take(8192).collect();
+    let control = sample
+        .iter()
+        .filter(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\u{c}'))
+        .count();
+    !sample.is_empty() && control as f64 / sample.len() as f64 > MAX_CONTROL_RATIO
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_oversized_binary_and_truncating_writes() {
+        let guards = WriteGuards {
+            max_file_bytes: 1000,
+            ..WriteGuards::default()
+        };
+        let source = "fn main() {}\n".repeat(50);
+
+        assert!(guards.check(None, &source).is_empty());
+        assert!(guards.check(Some(&source), &source[..300]).is_empty());
+        assert!(guards.check(Some("tiny"), "").is_empty());
+
+        let violations = guards.check(None, &"x".repeat(1001));
+        assert_eq!(violations, ["1001 bytes is over the 1000 byte limit"]);
+        assert_eq!(
+            guards.check(None, "PNG\0\0\x01\x02"),
+            ["content looks binary"]
+        );
+        assert!(!looks_binary("col1\tcol2\r\n\u{c}"));
+        assert_eq!(
+            guards.check(Some(&source), "// rest unchanged\n"),
+            ["would truncate the file from 650 to 18 bytes (3% kept)"]
+        );
+    }
+}
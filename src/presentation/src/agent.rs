@@ -2,6 +2,8 @@ use crate::analysis::assess_agent_command_risk;
 use crate::types::{AgentCommandRisk, AgentPlan, AgentStep};
 use crate::utils::{clean_command_output, extract_last_json};
 use anyhow::{anyhow, Result};
+use infrastructure::fs_simulation::FsSimulation;
+use infrastructure::structured_output;
 use shared::confirmation;
 
 /// Analyze agent task and generate execution plan
@@ -23,7 +25,8 @@ pub async fn analyze_agent_task(task: &str) -> Result<AgentPlan> {
         .unwrap_or_else(|| String::new());
 
     // Use AI to generate detailed execution plan
-    let client = infrastructure::ollama_client::OllamaClient::new()?;
+    let client = infrastructure::ollama_client::OllamaClient::new()?
+        .with_mode(infrastructure::sampling::GenerationMode::Agent);
 
     let prompt = format!(
         r#"Analyze this task and create a detailed execution plan with individual steps.
@@ -65,21 +68,11 @@ OUTPUT ONLY VALID JSON:"#,
         task, current_dir, ls_output
     );
 
-    let response = client.generate_response(&prompt).await?;
-
-    // Extract JSON from the response (AI might include extra text)
-    let plan: AgentPlan = if let Some(json_start) = response.find('{') {
-        let json_str = &response[json_start..];
-        if let Some(json_end) = json_str.rfind('}') {
-            let json_content = &json_str[..=json_end];
-            serde_json::from_str(json_content)
-                .map_err(|e| anyhow!("Failed to parse agent plan JSON: {}", e))?
-        } else {
-            return Err(anyhow!("No closing brace found in agent plan response"));
-        }
-    } else {
-        return Err(anyhow!("No JSON found in agent plan response"));
-    };
+    // Constrained to the plan schema, with repair prompts for invalid replies
+    let generation = client.generation_record().await;
+    let engine = infrastructure::InferenceEngine::Ollama(client);
+    let plan: AgentPlan = structured_output::generate(&engine, &prompt).await?;
+    println!("Plan generated with {}", generation);
 
     // Enhance plan with additional analysis
     let enhanced_plan = enhance_agent_plan(plan, task);
@@ -315,6 +308,8 @@ pub async fn execute_dry_run(plan: &AgentPlan) -> Result<()> {
     println!("DRY RUN MODE - No commands will be executed");
     println!("========================================");
 
+    let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+    let mut simulation = FsSimulation::new(cwd);
     for (i, step) in plan.steps.iter().enumerate() {
         let step_num = i + 1;
         println!();
@@ -340,8 +335,12 @@ pub async fn execute_dry_run(plan: &AgentPlan) -> Result<()> {
         } else {
             println!("  Safety: Command blocked by policy");
         }
+        simulation.run_command(&step.command);
     }
 
+    println!();
+    println!("PREDICTED FILESYSTEM CHANGES");
+    println!("{}", simulation.diff());
     println!();
     println!("DRY RUN COMPLETE");
     println!("- Total steps: {}", plan.steps.len());
@@ -353,7 +352,8 @@ pub async fn execute_dry_run(plan: &AgentPlan) -> Result<()> {
 
 /// Execute individual agent step
 pub async fn execute_agent_step(step: &AgentStep) -> Result<()> {
-    let power_config = infrastructure::config::Config::load().power_user;
+    let config = infrastructure::config::Config::load();
+    let power_config = &config.power_user;
 
     // Check safety policy - allow user override if they confirmed
     let is_allowed = power_config.is_command_allowed(&step.command);
@@ -365,9 +365,18 @@ pub async fn execute_agent_step(step: &AgentStep) -> Result<()> {
         }
         // User explicitly confirmed override
     }
+    if !crate::confirmation::confirm_network_access(
+        &config.security.network_security,
+        &step.command,
+    )? {
+        return Err(anyhow!(
+            "Command cancelled: network access was not allowed."
+        ));
+    }
+    crate::confirmation::confirm_agent_step(&config, step, None).await?;
 
     // Execute the command
-    let sandbox = infrastructure::sandbox::Sandbox::new();
+    let sandbox = infrastructure::sandbox::Sandbox::for_security(&config.security);
     let output = sandbox
         .execute_safe("bash", vec!["-c".to_string(), step.command.clone()])
         .await?;
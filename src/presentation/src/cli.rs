@@ -1,29 +1,63 @@
 use anyhow::anyhow;
-use application::{agent_service::AgentService, build_service::BuildPlan, rag_service::RagService};
+use application::{
+    agent_service::AgentService,
+    build_service::{operation_summary, print_diff, unit_summary, BuildPlan},
+    confirmation_queue::{ConfirmationQueue, Decision, QueuedPlan},
+    memory_summarizer::{compact_history, SummarizationPolicy},
+    prompts,
+    rag_service::{ChangedPaths, RagService, RagStreamEvent},
+};
 use bincode;
 use chrono::Utc;
 use clap::Parser;
 use colored::Colorize;
 use docx_rs::*;
-use flume::Receiver;
 use infrastructure::{
-    background_supervisor::{
-        BackgroundEvent, BackgroundSupervisor, DiagnosticSeverity, FileChangeType,
-        GitStatus as GitStatusType, LogLevel, TestStatus,
-    },
+    agent_control::AgentExecutionLimits,
+    background_supervisor::BackgroundSupervisor,
+    build_branch::BuildBranch,
+    capabilities::Capabilities,
+    capability_registry::{self, CapabilityRegistry, ProbeTargets},
+    cargo_workspace::CargoWorkspace,
+    command_audit,
     config::Config,
-    input_classifier::{InputClassifier, InputType},
+    context_window::{MemoryScope, PromptBreakdown, TokenCounter},
+    diff_engine::DiffEngine,
+    embedder::EmbeddingMismatch,
+    environment_snapshot::EnvironmentSnapshot,
+    format_hooks::LintFailed,
+    fs_simulation::FsSimulation,
+    input_classifier::{looks_like_code_change, InputClassifier, InputType},
+    logging, model_comparison,
+    network_security::NetworkSecurity,
     ollama_client::OllamaClient,
+    output_history::{output_references, OutputHistory},
+    patch,
+    policy_engine::bundle as policy_bundle,
+    prompt_archive,
+    prompt_templates::{PromptTemplate, PromptTemplates},
+    run_checkpoint::{RunCheckpoint, RunKind},
+    sampling::{self, GenerationMode, GenerationRecord, SamplingParams},
     sandbox::Sandbox,
-    session_store::SessionStore,
+    session_squash::SessionCommits,
+    session_store::{SessionStore, SessionWorkspace},
+    session_timeline::{TimelineEvent, TimelineKind},
+    structured_output,
+    test_gate::TestGateFailed,
+    token_usage::{TokenUsage, UsageTracker},
+    undo_journal::UndoJournal,
+    workspace_lock::WorkspaceLock,
 };
+use shared::cancellation::{cancel_on_interrupt, CancellationToken};
 use shared::confirmation::ask_confirmation;
+use shared::platform::{self, Shell};
+use shared::terminal;
 use shared::types::Result;
-use shared::ultra_fast_cache::UltraFastCache;
+use shared::ultra_fast_cache::{SemanticCache, SemanticHit, UltraFastCache};
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::{oneshot, RwLock};
@@ -41,18 +75,62 @@ use crate::session::*;
 // Import refactored CLI modules from cli/ subdirectory
 #[path = "cli/agent.rs"]
 mod cli_agent;
+#[path = "cli/archive.rs"]
+mod cli_archive;
+#[path = "cli/audit.rs"]
+mod cli_audit;
+#[path = "cli/auth.rs"]
+mod cli_auth;
 #[path = "cli/background.rs"]
 mod cli_background;
+#[path = "cli/batch.rs"]
+mod cli_batch;
+#[path = "cli/bench.rs"]
+mod cli_bench;
 #[path = "cli/build_helpers.rs"]
 mod cli_build_helpers;
 #[path = "cli/cache.rs"]
 mod cli_cache;
 #[path = "cli/chat.rs"]
 mod cli_chat;
+#[path = "cli/checkpoints.rs"]
+mod cli_checkpoints;
+#[path = "cli/compare.rs"]
+mod cli_compare;
+#[path = "cli/doctor.rs"]
+mod cli_doctor;
+#[path = "cli/environment.rs"]
+mod cli_environment;
+#[path = "cli/finish.rs"]
+mod cli_finish;
+#[path = "cli/pager.rs"]
+mod cli_pager;
+#[path = "cli/plugins.rs"]
+mod cli_plugins;
+#[path = "cli/policy.rs"]
+mod cli_policy;
 #[path = "cli/rag.rs"]
 mod cli_rag;
+#[path = "cli/recovery.rs"]
+mod cli_recovery;
+#[path = "cli/research.rs"]
+mod cli_research;
+#[path = "cli/scaffold.rs"]
+mod cli_scaffold;
+#[path = "cli/secrets.rs"]
+mod cli_secrets;
 #[path = "cli/session.rs"]
 mod cli_session;
+#[path = "cli/snapshots.rs"]
+mod cli_snapshots;
+#[path = "cli/sync.rs"]
+mod cli_sync;
+#[path = "cli/todo.rs"]
+mod cli_todo;
+#[path = "cli/trash.rs"]
+mod cli_trash;
+#[path = "cli/usage.rs"]
+mod cli_usage;
 #[path = "cli/utils.rs"]
 mod cli_utils;
 #[path = "cli/voice.rs"]
@@ -63,6 +141,7 @@ use cli_cache::{
     CommandCacheEntry, CommandCacheFile, ExplainCacheEntry, ExplainCacheFile, RagCacheEntry,
     RagCacheFile,
 };
+use cli_checkpoints::CheckpointCommand;
 
 /// Analyze agent task and generate execution plan
 async fn analyze_agent_task(task: &str) -> Result<AgentPlan> {
@@ -74,72 +153,30 @@ async fn analyze_agent_task(task: &str) -> Result<AgentPlan> {
         .and_then(|p| p.to_str().map(|s| s.to_string()))
         .unwrap_or_else(|| ".".to_string());
 
-    let ls_output = std::process::Command::new("sh")
-        .arg("-c")
-        .arg("ls -la 2>/dev/null | head -n 20")
+    let ls_output = Shell::detect()
+        .command(&Shell::detect().directory_listing_script(20))
         .output()
         .ok()
         .and_then(|o| String::from_utf8(o.stdout).ok())
         .unwrap_or_else(|| String::new());
 
     // Use AI to generate detailed execution plan
-    let client = infrastructure::ollama_client::OllamaClient::new()?;
-
-    let prompt = format!(
-        r#"Analyze this task and create a detailed execution plan with individual steps.
-
-TASK: {}
-
-CURRENT DIRECTORY: {}
-DIRECTORY CONTENTS (first 20 entries):
-{}
-
-Generate a JSON object with this structure:
-{{
-  "steps": [
-    {{
-      "id": "step_1",
-      "command": "exact shell command",
-      "description": "what this step does",
-      "risk_level": "InfoOnly|SafeOperations|NetworkAccess|SystemChanges|Destructive",
-      "estimated_duration": "X seconds" or "X minutes",
-      "dependencies": ["step_id1", "step_id2"] (empty array if none)
-    }}
-  ],
-  "estimated_total_time": "X minutes",
-  "disk_impact": "X MB" (if applicable),
-  "network_required": true/false,
-  "safety_concerns": ["concern1", "concern2"] (if any)
-}}
-
-Rules:
-- Commands must be executable shell commands
-- Each step should be atomic and independently verifiable
-- Include realistic time estimates
-- Mark dependencies accurately
-- Flag any safety concerns
-- Use only commands available in the current directory context
-- Prefer safer alternatives when possible
-
-OUTPUT ONLY VALID JSON:"#,
-        task, current_dir, ls_output
-    );
-
-    let response = client.generate_response(&prompt).await?;
-
-    // Extract JSON from the response (AI might include extra text)
-    let plan: AgentPlan = if let Some(json_start) = response.find('{') {
-        let json_str = &response[json_start..];
-        if let Some(json_end) = json_str.rfind('}') {
-            let json_content = &json_str[..=json_end];
-            serde_json::from_str(json_content)
-                .map_err(|e| anyhow!("Failed to parse agent plan JSON: {}", e))?
-        } else {
-            return Err(anyhow!("No closing brace found in agent plan response"));
-        }
-    } else {
-        return Err(anyhow!("No JSON found in agent plan response"));
-    };
+    let client = OllamaClient::new()?.with_mode(GenerationMode::Agent);
+
+    let prompt = PromptTemplates::global().render(
+        PromptTemplate::Plan,
+        serde_json::json!({
+            "task": task,
+            "current_dir": current_dir,
+            "directory": ls_output,
+        }),
+    )?;
+
+    // Constrained to the plan schema, with repair prompts for invalid replies
+    let generation = client.generation_record().await;
+    let engine = infrastructure::InferenceEngine::Ollama(client);
+    let plan: AgentPlan = structured_output::generate(&engine, &prompt).await?;
+    println!("{}", format!("Plan generated with {}", generation).dimmed());
 
     // Enhance plan with additional analysis
     let enhanced_plan = enhance_agent_plan(plan, task);
@@ -282,6 +319,21 @@ fn format_risk_level(risk: &AgentCommandRisk) -> &'static str {
     }
 }
 
+/// Say how a cached reply was generated, so it can be generated again
+fn show_cached_generation(generation: &Option<GenerationRecord>) {
+    if let Some(record) = generation {
+        println!(
+            "{}",
+            format!(
+                "Cached reply generated with {} (reproduce with {})",
+                record,
+                record.reproduce_flags()
+            )
+            .dimmed()
+        );
+    }
+}
+
 /// Validate that a command has basic syntactical correctness
 fn validate_command_syntax(command: &str) -> std::result::Result<(), String> {
     let trimmed = command.trim();
@@ -353,6 +405,18 @@ pub struct Cli {
     #[arg(long)]
     pub ai_agent: bool,
 
+    /// Split the goal and work each part with its own bounded agent
+    #[arg(
+        long,
+        requires = "ai_agent",
+        help = "With --ai-agent, decompose the goal and run each subtask as a sub-agent with its own tools, iteration limit and context window"
+    )]
+    pub sub_agents: bool,
+
+    /// Continue the session's interrupted --run or --ai-agent execution after its last completed step
+    #[arg(long)]
+    pub resume: bool,
+
     /// Create execution plan without running commands
     #[arg(long)]
     pub plan: bool,
@@ -365,9 +429,14 @@ pub struct Cli {
     #[arg(long)]
     pub rag: bool,
 
-    /// Load context from path
-    #[arg(long)]
-    pub context: bool,
+    /// Load context from path, or with --rag, the repositories to search
+    #[arg(
+        long,
+        value_name = "PATH|REPOS",
+        value_delimiter = ',',
+        help = "Chat with context loaded from a path; with --rag, search only these repositories (names or paths, comma-separated)"
+    )]
+    pub context: Option<Vec<String>>,
 
     /// Stream agent execution in real-time
     #[arg(long)]
@@ -423,17 +492,69 @@ pub struct Cli {
     )]
     pub dry_run: bool,
 
-    /// Verbose output: show detailed information
+    /// Take over a workspace lock left by another bro process
     #[arg(
         long,
-        help = "Display retrieved context and detailed operation information"
+        help = "Apply build changes even if another bro process holds the workspace lock (for locks left by a crashed run)"
     )]
-    pub verbose: bool,
+    pub steal_lock: bool,
 
-    /// Show diffs for file operations (reserved for future use)
-    #[arg(long, help = "Show diffs for file modifications (planned feature)")]
+    /// Apply the build on its own `bro/<goal>` branch
+    #[arg(
+        long,
+        requires = "build",
+        help = "Apply and commit the build on a new bro/<goal> branch created from HEAD, then switch back to the current branch"
+    )]
+    pub branch: bool,
+
+    /// Open a pull request for the build branch
+    #[arg(
+        long,
+        requires = "branch",
+        help = "Push the build branch and open a pull request with the plan as its description (uses gh, or GITHUB_TOKEN)"
+    )]
+    pub pr: bool,
+
+    /// Verbose output: show detailed information; repeat for more logging
+    #[arg(
+        short = 'v',
+        long,
+        action = clap::ArgAction::Count,
+        help = "Display retrieved context, a token breakdown of each RAG prompt by part and source file, and detailed operation information; -vv logs debug and -vvv trace output"
+    )]
+    pub verbose: u8,
+
+    /// Debug logging for chosen subsystems only
+    #[arg(
+        long,
+        value_name = "module=NAME[,NAME]",
+        help = "Log debug output of the named modules only, e.g. --debug module=rag_service,sandbox (repeatable)"
+    )]
+    pub debug: Vec<String>,
+
+    /// Show full diffs for file updates instead of a preview
+    #[arg(long, help = "Show the full unified diff of every file update")]
     pub show_diff: bool,
 
+    /// How diffs of file updates are computed
+    #[arg(
+        long,
+        value_name = "ENGINE",
+        default_value = "line",
+        help = "Diff updates by line, word (changed words marked) or semantic (formatting-only changes hidden); /diff <engine> switches while reviewing"
+    )]
+    pub diff_engine: DiffEngine,
+
+    /// Check every build operation before keeping it
+    #[arg(
+        long,
+        value_name = "GATE",
+        value_parser = ["tests"],
+        requires = "build",
+        help = "Run the project's tests (cargo test, npm test or pytest) after each build operation; a failing operation is rolled back and the build replanned with the test output"
+    )]
+    pub gate: Option<String>,
+
     /// Specify which session to use for operations
     #[arg(
         long,
@@ -454,14 +575,401 @@ pub struct Cli {
     )]
     pub delete_session: Option<String>,
 
+    /// Rewrite stored sessions for the current encryption setting
+    #[arg(
+        long,
+        help = "Encrypt this project's plaintext sessions when encrypt_sessions is on, or decrypt them when it is off"
+    )]
+    pub migrate_sessions: bool,
+
+    /// Fork the current session into a new one
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Copy the current or most recently used session into a new session NAME to try an alternative in"
+    )]
+    pub fork_session: Option<String>,
+
+    /// Merge a session into another
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Replay the changes and conversation of session NAME into the session it was forked from, or --into, resolving conflicting files interactively"
+    )]
+    pub merge_session: Option<String>,
+
+    /// Target session of --merge-session
+    #[arg(long, value_name = "NAME", requires = "merge_session")]
+    pub into: Option<String>,
+
+    /// Show what happened in a session
+    #[arg(
+        long,
+        value_name = "NAME",
+        num_args = 0..=1,
+        default_missing_value = "",
+        help = "Show the queries, plans, applied operations, commits and test results of session NAME (the current or last used one by default) in order; arguments keep only the kinds named (query, plan, operation, commit, test), --verbose adds details and --tui opens a scrollable view"
+    )]
+    pub timeline: Option<String>,
+
+    /// Replicate sessions and caches with other devices on the tailnet
+    #[arg(
+        long,
+        help = "Sync this project's sessions and the caches with the tailnet hosts given as arguments, or the configured sync_peers; the newest copy of a session wins and applied changes it lacks go to .bro/sync-conflicts.jsonl"
+    )]
+    pub sync: bool,
+
     /// Continue the current or last active session
-    #[arg(long, help = "Resume the current or most recently used session")]
+    #[arg(
+        long,
+        help = "Resume the current or most recently used session, in the workspace it is pinned to"
+    )]
     pub continue_session: bool,
 
+    /// Pin environment variables to the session
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        help = "Set an environment variable whenever this session runs (repeatable; with --session or --continue-session)"
+    )]
+    pub session_env: Vec<String>,
+
     /// Undo the last operation in the current session
     #[arg(long, help = "Revert the last applied changes in the current session")]
     pub undo: bool,
 
+    /// Applied changes --undo reverts
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "undo")]
+    pub steps: usize,
+
+    /// Show token usage per session and per day
+    #[arg(
+        long,
+        help = "Report prompt/completion token usage per session and per day, with budgets"
+    )]
+    pub usage: bool,
+
+    /// Check that configured backends are installed and working
+    #[arg(
+        long,
+        help = "Run health checks for the configured speech recognition and text-to-speech providers"
+    )]
+    pub doctor: bool,
+
+    /// Skip cached commands and answers
+    #[arg(
+        long,
+        help = "Ignore exact and semantic caches and always query the model"
+    )]
+    pub no_cache: bool,
+
+    /// Leave remembered conversations and learned corrections out of prompts
+    #[arg(
+        long,
+        conflicts_with = "memory",
+        help = "Answer without earlier conversations or feedback corrections (same as --memory off)"
+    )]
+    pub no_memory: bool,
+
+    /// Which memories go into prompts
+    #[arg(
+        long,
+        value_name = "SCOPE",
+        value_parser = ["all", "project-only", "off"],
+        help = "Use memories from all projects, only from this project, or none; --verbose lists the memories each prompt used"
+    )]
+    pub memory: Option<String>,
+
+    /// Answer a RAG query from the repository as of a git revision
+    #[arg(
+        long,
+        value_name = "REV",
+        requires = "rag",
+        help = "With --rag, index and query the repository as of a commit, branch or tag"
+    )]
+    pub at: Option<String>,
+
+    /// Back up Qdrant collections before risky cleanups or migrations
+    #[arg(
+        long,
+        value_name = "COLLECTION",
+        num_args = 0..,
+        help = "Download snapshots of the given Qdrant collections (default: RAG index and semantic memory)"
+    )]
+    pub snapshot: Option<Vec<String>>,
+
+    /// Restore a Qdrant collection from a downloaded snapshot
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Restore a collection from a snapshot (.snapshot or its .json metadata), replacing its contents"
+    )]
+    pub restore: Option<PathBuf>,
+
+    /// List downloaded Qdrant snapshots
+    #[arg(long, help = "List locally stored Qdrant snapshots")]
+    pub list_snapshots: bool,
+
+    /// Search the plugin marketplace
+    #[arg(
+        long,
+        value_name = "QUERY",
+        num_args = 0..=1,
+        default_missing_value = "",
+        help = "Search the plugin marketplace index (all plugins when QUERY is omitted)"
+    )]
+    pub plugin_search: Option<String>,
+
+    /// Install a plugin from the marketplace
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Install a plugin from the signed marketplace index after reviewing its permissions"
+    )]
+    pub plugin_install: Option<String>,
+
+    /// Update marketplace plugins
+    #[arg(
+        long,
+        value_name = "NAME",
+        num_args = 0..=1,
+        default_missing_value = "",
+        help = "Update NAME, or every plugin installed from the marketplace, to the index version"
+    )]
+    pub plugin_update: Option<String>,
+
+    /// Describe this installation for tooling and model grounding
+    #[arg(
+        long,
+        help = "Print the tools, modes, policies and limits of this installation, and which services and devices are available, as JSON"
+    )]
+    pub capabilities: bool,
+
+    /// Back up semantic memory and all RAG indexes for moving to another machine
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Snapshot semantic memory and every RAG index into DIR, with a manifest for --memory-restore"
+    )]
+    pub memory_backup: Option<PathBuf>,
+
+    /// Restore a backup made with --memory-backup
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Restore semantic memory and RAG indexes from a --memory-backup directory, replacing existing collections"
+    )]
+    pub memory_restore: Option<PathBuf>,
+
+    /// Deduplicate and compact the RAG indexes
+    #[arg(
+        long,
+        help = "Remove duplicate and stale vectors from the RAG indexes and report the space reclaimed"
+    )]
+    pub memory_compact: bool,
+
+    /// Rebuild a RAG index made with a different embedding model
+    #[arg(
+        long,
+        help = "Re-embed RAG indexes built with a different embedding model or vector size (the old index is backed up first)"
+    )]
+    pub reembed: bool,
+
+    /// Search with paraphrases of the RAG question too
+    #[arg(
+        long,
+        help = "Expand the RAG question into paraphrases and keyword variants and search with all of them (RAG_QUERY_EXPANSION)"
+    )]
+    pub expand_query: bool,
+
+    /// Sampling temperature for every generation in this run
+    #[arg(
+        long,
+        value_name = "T",
+        help = "Sampling temperature from 0 (near-deterministic) to 2, overriding the per-mode sampling config for this run"
+    )]
+    pub temperature: Option<f32>,
+
+    /// Nucleus sampling threshold for this run
+    #[arg(
+        long,
+        value_name = "P",
+        help = "Sample only from the most likely tokens covering probability P (0 < P <= 1) for this run"
+    )]
+    pub top_p: Option<f32>,
+
+    /// Longest reply for this run
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Stop each reply after N generated tokens"
+    )]
+    pub max_tokens: Option<u32>,
+
+    /// Sampling seed for this run
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Sample with seed N to reproduce a plan, command or answer exactly; the seed of each generation is printed with it and kept in the caches"
+    )]
+    pub seed: Option<u64>,
+
+    /// Show or edit the project TODO list
+    #[arg(
+        long,
+        help = "List deferred work in .bro/todo.md; 'add <text>' appends an item, 'done <N>' checks one off"
+    )]
+    pub todo: bool,
+
+    /// Research a topic within a time budget
+    #[arg(
+        long,
+        help = "Search the index and allowlisted web for the topic given as arguments and compile the findings into a cited Markdown brief in .bro/research"
+    )]
+    pub research: bool,
+
+    /// Time budget of --research
+    #[arg(long, value_name = "N", default_value_t = 10, requires = "research")]
+    pub minutes: u64,
+
+    /// Deleted files kept in the project trash
+    #[arg(
+        long,
+        help = "List files deleted by builds, kept in .bro/trash; 'restore [BATCH] [PATH]' moves them back"
+    )]
+    pub trash: bool,
+
+    /// Create a project from a template
+    #[arg(
+        long,
+        help = "Create a project: --new <template> <name>, from rust-cli, axum-api, react-app or a git repository (gh:owner/repo); without arguments, list templates"
+    )]
+    pub new: bool,
+
+    /// Template variables for --new
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        requires = "new",
+        help = "Set a template placeholder ({{KEY}}) for --new (repeatable)"
+    )]
+    pub var: Vec<String>,
+
+    /// Show or verify the command audit log
+    #[arg(
+        long,
+        help = "List commands run by bro from the tamper-evident audit log; 'show [N]' lists the last N, 'verify' checks the hash chain"
+    )]
+    pub audit: bool,
+
+    /// Show, verify or export the prompt archive
+    #[arg(
+        long,
+        help = "List archived prompts and responses; 'show [N]' lists the last N, 'verify' checks the hash chain, 'export <FILE> [SINCE]' writes them for an audit"
+    )]
+    pub archive: bool,
+
+    /// Show or test the declarative policy rules
+    #[arg(
+        long,
+        help = "List the policy rules in force; 'check' validates them, 'explain <COMMAND>' shows which rule decides on a command"
+    )]
+    pub policy: bool,
+
+    /// Scan for secrets not accepted in the baseline
+    #[arg(
+        long,
+        help = "Scan the repository for secrets not in .bro/secrets.baseline and fail if any are found; 'baseline' accepts the current findings"
+    )]
+    pub secrets: bool,
+
+    /// Manage credentials in the OS keychain or Vault
+    #[arg(
+        long,
+        help = "List API keys and the web server token and whether each is set; 'set <NAME>' stores one from a prompt or stdin, 'get <NAME>' prints it, 'delete <NAME>' removes it, 'token' stores a new web server token"
+    )]
+    pub auth: bool,
+
+    /// Compare the environments of the session's build runs
+    #[arg(
+        long,
+        help = "List the environments recorded for the session's build runs; 'show [N]' prints one, 'diff [A] [B]' compares two (the last two by default)"
+    )]
+    pub environments: bool,
+
+    /// List retained command outputs
+    #[arg(
+        long,
+        help = "List recent command outputs that can be referenced as @out:N in a query"
+    )]
+    pub list_outputs: bool,
+
+    /// Load-test a running web server
+    #[arg(
+        long,
+        value_name = "URL",
+        num_args = 0..=1,
+        default_missing_value = cli_bench::DEFAULT_SERVER_URL,
+        help = "Send concurrent chat, RAG, dictation and config requests to the web server and report latency percentiles and error rates"
+    )]
+    pub bench_server: Option<String>,
+
+    /// Concurrent workers for --bench-server
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 16,
+        requires = "bench_server"
+    )]
+    pub concurrency: usize,
+
+    /// Seconds to run --bench-server for
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 30,
+        requires = "bench_server"
+    )]
+    pub duration: u64,
+
+    /// Answer every job of a file without prompting
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Process each line of FILE, a JSON job like {\"mode\": \"review\", \"file\": \"src/lib.rs\"} (modes: query, explain, review) or a plain question, without prompting, and write one JSON result per line"
+    )]
+    pub batch: Option<String>,
+
+    /// Where --batch writes its results
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "batch",
+        help = "File --batch writes its results to (FILE.results.jsonl next to the input by default)"
+    )]
+    pub batch_output: Option<String>,
+
+    /// Jobs --batch runs at a time
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "batch")]
+    pub jobs: usize,
+
+    /// Run one prompt on several models side by side
+    #[arg(
+        long,
+        requires = "models",
+        help = "Run the prompt on every model in --models and compare the answers, latency and tokens per second; with --tui, in side-by-side columns"
+    )]
+    pub compare: bool,
+
+    /// Models for --compare
+    #[arg(
+        long,
+        value_name = "MODEL,MODEL",
+        value_delimiter = ',',
+        requires = "compare"
+    )]
+    pub models: Vec<String>,
+
     /// The query or file path to process
     #[arg(trailing_var_arg = true)]
     pub args: Vec<String>,
@@ -483,8 +991,21 @@ pub struct Cli {
     pub generate_config: Option<String>,
 }
 
+/// Semantic cache files under the platform data directory
+const COMMAND_SEMANTIC_CACHE: &str = "commands_semantic_cache.bin";
+const RAG_SEMANTIC_CACHE: &str = "rag_semantic_cache.bin";
+
+/// Most search rounds of a `--research` run, whatever its time budget
+const RESEARCH_MAX_ITERATIONS: u32 = 12;
+
+/// Replans after operations rolled back by `--gate tests` or a lint hook
+/// before giving up
+const MAX_GATE_RETRIES: usize = 2;
+
 pub struct CliApp {
-    rag_service: Option<RagService>,
+    rag_service: Option<Arc<RagService>>,
+    /// File changes from the background watcher, consumed by the RAG index once loaded
+    index_changes: Option<Arc<ChangedPaths>>,
     cache_path: PathBuf,
     ultra_fast_cache: Option<UltraFastCache>,
     system_info: String,
@@ -495,6 +1016,28 @@ pub struct CliApp {
     scripted_inputs: Option<std::collections::VecDeque<String>>,
     power_config_override: Option<infrastructure::config::PowerUserConfig>,
     input_classifier: Option<infrastructure::input_classifier::InputClassifier>,
+    usage_baseline: TokenUsage,
+    no_cache: bool,
+    /// Show what went into each prompt (`--verbose`)
+    verbose: bool,
+    /// Git revision RAG queries are answered from (`--at`)
+    rag_revision: Option<String>,
+    /// Repositories RAG queries are scoped to (`--rag --context`)
+    rag_context: Option<Vec<String>>,
+    /// Embedding of the last query looked up in a semantic cache, reused when saving
+    cache_embedding: std::sync::Mutex<Option<(String, Vec<f32>)>>,
+    /// How the last reply was generated, recorded with it when it is cached
+    last_generation: std::sync::Mutex<Option<GenerationRecord>>,
+    /// Recent command outputs, referenced as `@out:N`
+    output_history: OutputHistory,
+    /// Take over the workspace lock from another process (`--steal-lock`)
+    steal_lock: bool,
+    /// Plan the last build applied, described in its pull request (`--pr`)
+    applied_plan: Option<application::build_service::BuildPlan>,
+    /// How update diffs are shown while reviewing a build (`--diff-engine`)
+    diff_engine: DiffEngine,
+    /// Run the tests after each build operation (`--gate tests`)
+    test_gate: bool,
 }
 
 impl CliApp {
@@ -504,18 +1047,16 @@ impl CliApp {
                 return Ok(next);
             }
         }
+        if let Some(answer) = shared::confirmation::scripted_answer("input") {
+            return answer;
+        }
 
         let mut input = String::new();
         std::io::stdin().read_line(&mut input)?;
         Ok(input.trim_end().to_string())
     }
     pub fn new() -> Self {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let mut cache_path = PathBuf::from(home);
-        cache_path.push(".local");
-        cache_path.push("share");
-        cache_path.push("vibe_cli");
-        cache_path.push("commands_cache.bin");
+        let cache_path = platform::app_data_dir().join("commands_cache.bin");
         let system_info_path = Self::default_system_info_path();
         let system_info = Self::load_or_collect_system_info(&system_info_path);
         let config = Config::load();
@@ -523,7 +1064,10 @@ impl CliApp {
         // Initialize session store for current project
         let session_store = if let Some(project_root) = find_project_root() {
             match SessionStore::new(&project_root) {
-                Ok(store) => Some(store),
+                Ok(store) => Some(match cli_session::conflict_resolver() {
+                    Some(resolver) => store.with_conflict_resolver(resolver),
+                    None => store,
+                }),
                 Err(e) => {
                     eprintln!("Warning: Failed to initialize session store: {}", e);
                     None
@@ -534,21 +1078,24 @@ impl CliApp {
         };
 
         // Initialize input classifier
-        let input_classifier = match infrastructure::ollama_client::OllamaClient::new() {
-            Ok(client) => Some(infrastructure::input_classifier::InputClassifier::new(
-                std::sync::Arc::new(client),
-            )),
-            Err(e) => {
-                eprintln!("Warning: Failed to initialize input classifier: {}", e);
-                None
-            }
-        };
+        let input_classifier =
+            match OllamaClient::new().map(|client| client.with_mode(GenerationMode::Command)) {
+                Ok(client) => Some(infrastructure::input_classifier::InputClassifier::new(
+                    std::sync::Arc::new(client),
+                )),
+                Err(e) => {
+                    eprintln!("Warning: Failed to initialize input classifier: {}", e);
+                    None
+                }
+            };
 
         // Ultra-fast cache will be initialized lazily when needed in async context
         let ultra_fast_cache = None;
+        let output_history = OutputHistory::open_default(config.output_history_limit);
 
         Self {
             rag_service: None,
+            index_changes: None,
             cache_path,
             ultra_fast_cache,
             system_info,
@@ -559,16 +1106,107 @@ impl CliApp {
             scripted_inputs: None,
             power_config_override: None,
             input_classifier,
+            usage_baseline: UsageTracker::global().snapshot(),
+            no_cache: false,
+            verbose: false,
+            rag_revision: None,
+            steal_lock: false,
+            applied_plan: None,
+            diff_engine: DiffEngine::default(),
+            test_gate: false,
+            rag_context: None,
+            cache_embedding: std::sync::Mutex::new(None),
+            last_generation: std::sync::Mutex::new(None),
+            output_history,
+        }
+    }
+
+    /// Use `rag_service` for queries and keep its index current as files change
+    fn attach_rag_service(&mut self, rag_service: RagService) {
+        let rag_service = Arc::new(rag_service);
+        if let Some(changes) = self.index_changes.take() {
+            let rag_service = Arc::clone(&rag_service);
+            tokio::spawn(async move { rag_service.reindex_on_change(&changes).await });
+        }
+        self.rag_service = Some(rag_service);
+    }
+
+    /// Open the RAG index, offering to re-embed it when it was built with a
+    /// different embedding model than the configured one
+    async fn open_rag_service(&self, roots: &[&str], db_path: &str) -> Result<RagService> {
+        let config = self.config.clone();
+        match application::create_rag_service_with_config(roots, db_path, None, config.clone())
+            .await
+        {
+            Err(e) if !config.rag_reembed => {
+                let Some(mismatch) = e.downcast_ref::<EmbeddingMismatch>() else {
+                    return Err(e);
+                };
+                eprintln!("{}", mismatch.to_string().yellow());
+                if !ask_confirmation(
+                    "Re-embed the index with the configured model now? (the old index is backed up)",
+                    false,
+                )? {
+                    return Err(e);
+                }
+                let config = Config {
+                    rag_reembed: true,
+                    ..config
+                };
+                application::create_rag_service_with_config(roots, db_path, None, config).await
+            }
+            result => result,
+        }
+    }
+
+    /// Page a command's output and retain it for `@out:N` references
+    fn show_command_output(&self, command: &str, output: &str) {
+        if let Err(e) = self.output_history.record(command, output) {
+            tracing::warn!("Failed to retain command output: {}", e);
         }
+        cli_pager::page(output);
+    }
+
+    /// Answer a question about retained outputs; `question` has them attached
+    async fn handle_output_question(&self, question: &str) -> Result<()> {
+        let client = OllamaClient::new()?.with_mode(GenerationMode::Chat);
+        let prompt = format!(
+            "Answer the question using the command output attached to it. \
+             Point out errors and what caused them when there are any.\n\n{}",
+            question
+        );
+        let response = client.generate_response(&prompt).await?;
+        cli_pager::page(&response);
+        Ok(())
+    }
+
+    fn list_outputs(&self) -> Result<()> {
+        let outputs = self.output_history.list();
+        if outputs.is_empty() {
+            println!("No command outputs retained yet.");
+            return Ok(());
+        }
+        for (i, retained) in outputs.iter().enumerate() {
+            println!(
+                "{} {} {}",
+                format!("@out:{}", i + 1).bright_cyan(),
+                retained.command,
+                format!(
+                    "({} lines, {})",
+                    retained.output.lines().count(),
+                    retained
+                        .created_at
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                )
+                .dimmed()
+            );
+        }
+        Ok(())
     }
 
     fn default_system_info_path() -> PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let mut path = PathBuf::from(home);
-        path.push(".config");
-        path.push("vibe_cli");
-        path.push("system_info.txt");
-        path
+        platform::app_config_dir().join("system_info.txt")
     }
 
     fn load_or_collect_system_info(path: &PathBuf) -> String {
@@ -589,26 +1227,20 @@ impl CliApp {
     }
 
     fn explain_cache_path() -> PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let mut path = PathBuf::from(home);
-        path.push(".local");
-        path.push("share");
-        path.push("vibe_cli");
-        path.push("explain_cache.bin");
-        path
+        platform::app_data_dir().join("explain_cache.bin")
     }
 
     fn rag_cache_path() -> PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        let mut path = PathBuf::from(home);
-        path.push(".local");
-        path.push("share");
-        path.push("vibe_cli");
-        path.push("rag_cache.bin");
-        path
+        platform::app_data_dir().join("rag_cache.bin")
     }
 
-    async fn handle_ai_agent(&mut self, goal: &str) -> Result<()> {
+    /// Run the agent on `goal`, or continue the run `resume` was saved from
+    async fn handle_ai_agent(
+        &mut self,
+        goal: &str,
+        sub_agents: bool,
+        resume: Option<RunCheckpoint>,
+    ) -> Result<()> {
         use domain::models::AgentRequest;
 
         eprintln!("🤖 Enhanced AI Agent processing request...");
@@ -617,7 +1249,9 @@ impl CliApp {
         // Initialize services
         let client = OllamaClient::new()?;
         // Use Ollama by default (now the recommended option)
-        let agent_service = application::create_agent_service().await?;
+        let mut agent_service = application::create_agent_service().await?;
+        self.apply_token_budget(&mut agent_service);
+        agent_service.config.context.memory_scope = self.config.context.memory_scope;
 
         // Create agent request
         let request = AgentRequest {
@@ -626,8 +1260,63 @@ impl CliApp {
             conversation_id: None,
         };
 
+        // Progress is saved after every iteration, so the run can be resumed
+        let checkpoint = resume.unwrap_or_else(|| {
+            let checkpoint = RunCheckpoint::new(RunKind::AiAgent, goal);
+            if sub_agents {
+                checkpoint.with_sub_agents()
+            } else {
+                checkpoint
+            }
+        });
+        self.save_run_checkpoint(Some(&checkpoint));
+        let (progress, mut updates) = tokio::sync::watch::channel(checkpoint);
+        agent_service.run_checkpoint = Some(progress);
+
         // Process with enhanced agent
-        match agent_service.process_request(&request).await {
+        let cancel = CancellationToken::new();
+        let interrupt = cancel_on_interrupt(&cancel);
+        let run = async {
+            if sub_agents {
+                agent_service
+                    .process_request_with_sub_agents(&request)
+                    .await
+            } else {
+                agent_service.process_request(&request).await
+            }
+        };
+        tokio::pin!(run);
+        let response = loop {
+            tokio::select! {
+                response = &mut run => break Some(response),
+                Ok(()) = updates.changed() => {
+                    let checkpoint = updates.borrow_and_update().clone();
+                    self.save_run_checkpoint(Some(&checkpoint));
+                }
+                _ = cancel.cancelled() => break None,
+            }
+        };
+        drop(interrupt);
+
+        let mut checkpoint = updates.borrow().clone();
+        let Some(response) = response else {
+            checkpoint.stop("interrupted");
+            self.save_run_checkpoint(Some(&checkpoint));
+            println!(
+                "\n{}",
+                "Interrupted - `bro --resume` continues after the last completed iteration."
+                    .yellow()
+            );
+            return Ok(());
+        };
+        match &response {
+            Ok(_) => self.save_run_checkpoint(None),
+            Err(e) => {
+                checkpoint.stop(e.to_string());
+                self.save_run_checkpoint(Some(&checkpoint));
+            }
+        }
+        match response {
             Ok(response) => {
                 println!("\n{}", "🧠 Reasoning:".bright_cyan());
                 for (i, step) in response.reasoning.iter().enumerate() {
@@ -641,6 +1330,13 @@ impl CliApp {
                     }
                 }
 
+                if !response.retries.is_empty() {
+                    println!("\n{}", "🔁 Retries:".bright_yellow());
+                    for retry in &response.retries {
+                        println!("  • {}", retry.line());
+                    }
+                }
+
                 println!("\n{}", "💬 Response:".bright_green());
                 println!("{}", response.final_response);
                 println!(
@@ -682,7 +1378,9 @@ impl CliApp {
 
         // Initialize enhanced agent for planning
         let client = OllamaClient::new()?;
-        let agent_service = application::create_agent_service().await?;
+        let mut agent_service = application::create_agent_service().await?;
+        self.apply_token_budget(&mut agent_service);
+        agent_service.config.context.memory_scope = self.config.context.memory_scope;
 
         // Create agent request for planning with full context
         let context_info = format!(
@@ -744,7 +1442,9 @@ impl CliApp {
         show_diff: bool,
     ) -> Result<()> {
         use application::agent_service::IncrementalBuildPlanner;
-        use application::build_service::{BuildPlan, BuildService, ConfirmationMode, RiskLevel};
+        use application::build_service::{
+            BuildPlan, BuildService, ConfirmationMode, FileOperation, RiskLevel,
+        };
         use infrastructure::config::Config;
 
         if goal.trim().is_empty() {
@@ -758,8 +1458,38 @@ impl CliApp {
 
         let workspace_root =
             std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+        // Held until the build finishes so no other bro writes here meanwhile
+        let _workspace_lock = if dry_run {
+            None
+        } else {
+            let project_root = find_project_root()
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(|| workspace_root.clone());
+            Some(WorkspaceLock::acquire(
+                &project_root,
+                &format!("build: {}", goal.trim()),
+                self.steal_lock,
+            )?)
+        };
+        if let Some(store) = &self.session_store {
+            let snapshot = EnvironmentSnapshot::capture(&self.config, goal.trim());
+            if let Err(e) = store.record_environment(&self.active_session_name(), snapshot) {
+                eprintln!("Warning: Failed to record the build environment: {}", e);
+            }
+        }
         let mut current_goal = goal.to_string();
         let mut plan_hints: Option<String> = None;
+        let test_gate = if self.test_gate && !dry_run {
+            let project_root = find_project_root()
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(|| workspace_root.clone());
+            cli_build_helpers::test_gate(&project_root).await
+        } else {
+            None
+        };
+        // Test output of the last operation the gate rolled back
+        let mut gate_feedback: Option<String> = None;
+        let mut gate_retries = 0;
 
         println!(
             "{}",
@@ -782,12 +1512,27 @@ impl CliApp {
             } else {
                 current_goal.clone()
             };
+            let planning_goal = match &gate_feedback {
+                Some(feedback) => format!("{}\n\n{}", planning_goal, feedback),
+                None => planning_goal,
+            };
 
             // Configure build service based on flags
             let mut build_service = BuildService::new(&workspace_root);
             build_service.set_dry_run(dry_run);
             build_service.set_show_diff(show_diff);
+            build_service.set_diff_engine(self.diff_engine);
             build_service.set_verbose(verbose);
+            build_service.set_write_guards(self.get_power_config().write_guards.clone());
+            if let Some(resolver) = cli_build_helpers::drift_resolver() {
+                build_service.set_drift_resolver(resolver);
+            }
+            build_service.set_test_gate(test_gate.clone());
+            build_service.set_format_hooks(Some(self.get_power_config().format_hooks.clone()));
+            // A test gate compiles the crates itself
+            if test_gate.is_none() {
+                build_service.set_cargo_check(CargoWorkspace::detect(&workspace_root));
+            }
 
             if verbose {
                 build_service.set_confirmation_mode(ConfirmationMode::Interactive);
@@ -845,7 +1590,7 @@ impl CliApp {
                                 (&step.code_chunk, &step.file_path, &step.operation_type)
                             {
                                 // Display the incremental changes from AI
-                                self.display_incremental_changes(code, path, op_type);
+                                self.display_incremental_changes(code, path, op_type, show_diff);
 
                                 // Mark code generation as complete to prevent duplicate steps
                                 code_generation_complete = true;
@@ -865,7 +1610,12 @@ impl CliApp {
                         break;
                     }
                     Err(e) => {
-                        println!("[✗] [{}/{}] Planning failed", step_count, total_steps);
+                        println!(
+                            "{} [{}/{}] Planning failed",
+                            terminal::icon("[✗]", "[X]"),
+                            step_count,
+                            total_steps
+                        );
                         eprintln!("Planning error: {}", e);
                         return Ok(());
                     }
@@ -903,12 +1653,33 @@ impl CliApp {
                 operations: build_service.get_buffered_operations().to_vec(),
                 description: "Streaming-generated operations".to_string(),
                 estimated_risk: RiskLevel::Low,
+                groups: planner.operation_groups(),
             };
 
             if build_service.get_buffered_operations().is_empty() {
                 println!("[ERROR] All planned operations were outside the workspace. Please edit the plan to target paths under {}.", workspace_root.display());
                 continue 'planning;
             }
+            self.record_timeline(TimelineEvent::plan(
+                &current_goal,
+                &temp_plan
+                    .operations
+                    .iter()
+                    .map(operation_summary)
+                    .collect::<Vec<_>>(),
+            ));
+            for group in &temp_plan.groups {
+                println!(
+                    "[GROUP] {} (applied all or nothing): {}",
+                    group.name,
+                    group
+                        .paths
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
 
             if let Some(ref hints) = plan_hints {
                 let missing = Self::missing_plan_hints(hints, &temp_plan.operations);
@@ -1002,9 +1773,14 @@ impl CliApp {
                 }
             }
 
+            // Published for web/mobile review until the plan has run or been dropped
+            let mut queued: Option<QueuedPlan> = None;
+
             // Get user confirmation before execution (unless dry-run)
             if !dry_run {
-                use shared::confirmation::{ask_enhanced_confirmation, ConfirmationChoice};
+                use shared::confirmation::{
+                    ask_enhanced_confirmation_with_remote, ConfirmationChoice,
+                };
 
                 let operation_count = build_service.buffered_count();
                 if operation_count == 0 {
@@ -1027,11 +1803,40 @@ impl CliApp {
 
                 let mut restart_planning = false;
 
-                match ask_enhanced_confirmation(&prompt) {
+                match QueuedPlan::publish(ConfirmationQueue::open_default(), &temp_plan, |op| {
+                    build_service.assess_risk(op)
+                }) {
+                    Ok(plan) => {
+                        println!(
+                            "[REMOTE] Awaiting approval here or via the web UI (/api/confirmations/{})",
+                            plan.id()
+                        );
+                        queued = Some(plan);
+                    }
+                    Err(e) => tracing::warn!("Failed to publish plan for remote approval: {}", e),
+                }
+
+                let remote_choice = || {
+                    queued
+                        .as_ref()?
+                        .plan_decision()
+                        .map(|(decision, _)| match decision {
+                            Decision::Approved => ConfirmationChoice::Yes,
+                            Decision::Rejected => ConfirmationChoice::No,
+                        })
+                };
+
+                match ask_enhanced_confirmation_with_remote(&prompt, remote_choice) {
                     Ok(ConfirmationChoice::Yes) => {
+                        if let Some(plan) = &queued {
+                            plan.record_plan(Decision::Approved);
+                        }
                         println!("[EXEC] Proceeding with execution...");
                     }
                     Ok(ConfirmationChoice::No) => {
+                        if let Some(plan) = &queued {
+                            plan.record_plan(Decision::Rejected);
+                        }
                         println!("[CANCEL] Operation cancelled by user.");
                         return Ok(());
                     }
@@ -1101,9 +1906,14 @@ impl CliApp {
             }
 
             // Execute the buffered operations (unless dry-run)
+            let mut session_commits = None;
             if !dry_run {
                 // Final per-operation review/edit/apply loop
-                if !self.apply_operations_interactively(&mut temp_plan, &mut build_service)? {
+                if !self.apply_operations_interactively(
+                    &mut temp_plan,
+                    &mut build_service,
+                    queued.as_ref(),
+                )? {
                     println!("[CANCEL] Execution cancelled by user.");
                     break 'planning;
                 }
@@ -1111,33 +1921,92 @@ impl CliApp {
                 let mut completed = 0usize;
                 let mut failed = 0usize;
                 let mut errors = Vec::new();
-
-                for (idx, operation) in temp_plan.operations.iter().enumerate() {
-                    if let Err(e) = build_service.execute_operation_once(operation).await {
-                        failed += 1;
-                        errors.push(format!("{:?}: {}", operation, e));
-                        eprintln!("{} {}", "Build execution error:".red(), e);
+                session_commits = SessionCommits::start(&workspace_root);
+
+                let units = temp_plan.units();
+                for (idx, unit) in units.iter().enumerate() {
+                    if let Err(e) = build_service.execute_unit(unit).await {
+                        self.record_timeline(TimelineEvent::operation(
+                            &unit_summary(unit),
+                            Some(&format!("{:#}", e)),
+                        ));
+                        let feedback = if let Some(failure) = e.downcast_ref::<TestGateFailed>() {
+                            cli_build_helpers::print_gate_failure(failure);
+                            self.record_timeline(TimelineEvent::test(
+                                &failure.command.to_string(),
+                                false,
+                                Some(failure.run.elapsed),
+                                &failure.run.output,
+                            ));
+                            Some(prompts::test_gate_feedback(
+                                &failure.operation,
+                                &failure.command.to_string(),
+                                &failure.run.output,
+                            ))
+                        } else if let Some(failure) = e.downcast_ref::<LintFailed>() {
+                            cli_build_helpers::print_lint_failure(failure);
+                            Some(prompts::lint_feedback(
+                                &failure.operation,
+                                &failure.command,
+                                &failure.output,
+                            ))
+                        } else {
+                            None
+                        };
+                        if let Some(feedback) = feedback {
+                            if gate_retries < MAX_GATE_RETRIES {
+                                gate_retries += 1;
+                                gate_feedback = Some(feedback);
+                                println!(
+                                    "[REPLAN] Replanning with the check output (retry {}/{})...",
+                                    gate_retries, MAX_GATE_RETRIES
+                                );
+                                continue 'planning;
+                            }
+                        }
+                        failed += unit.operations.len();
+                        errors.push(format!("{}: {:#}", unit_summary(unit), e));
+                        eprintln!("{} {:#}", "Build execution error:".red(), e);
                         break;
                     }
 
-                    completed += 1;
+                    completed += unit.operations.len();
+                    self.record_timeline(TimelineEvent::operation(&unit_summary(unit), None));
+                    if let Some(command) = &test_gate {
+                        self.record_timeline(TimelineEvent::test(
+                            &command.to_string(),
+                            true,
+                            None,
+                            "",
+                        ));
+                    }
                     let commit_msg = format!(
-                        "feat: {} (step {}/{})\n\nOperation:\n- {:?}",
+                        "feat: {} (step {}/{})\n\nOperation{}:\n{}",
                         current_goal,
                         idx + 1,
-                        temp_plan.operations.len(),
-                        operation
+                        units.len(),
+                        if unit.operations.len() == 1 { "" } else { "s" },
+                        unit.operations
+                            .iter()
+                            .map(|operation| format!("- {:?}", operation))
+                            .collect::<Vec<_>>()
+                            .join("\n")
                     );
-                    if let Err(e) = build_service.commit_message(&commit_msg).await {
-                        eprintln!("{} {}", "Warning: Git commit failed:".yellow(), e);
-                    } else {
-                        println!(
-                            "[COMMIT] {}",
-                            commit_msg.lines().next().unwrap_or("Committed")
-                        );
+                    match build_service.commit_message(&commit_msg).await {
+                        Err(e) => eprintln!("{} {}", "Warning: Git commit failed:".yellow(), e),
+                        Ok(hash) => {
+                            println!(
+                                "[COMMIT] {}",
+                                commit_msg.lines().next().unwrap_or("Committed")
+                            );
+                            if let Some(hash) = hash {
+                                self.record_timeline(TimelineEvent::commit(&hash, &commit_msg));
+                            }
+                        }
                     }
                 }
 
+                self.applied_plan = Some(temp_plan.clone());
                 if failed == 0 {
                     println!("\nBuild completed successfully.");
                     println!("{} operations completed", completed);
@@ -1149,13 +2018,28 @@ impl CliApp {
                     }
                 }
             } else {
+                let mut simulation = FsSimulation::new(&workspace_root);
+                for operation in &temp_plan.operations {
+                    match operation {
+                        FileOperation::Create { path, .. }
+                        | FileOperation::Update { path, .. }
+                        | FileOperation::Patch { path, .. } => simulation.write_file(path),
+                        FileOperation::AddDependency { manifest, .. }
+                        | FileOperation::RemoveDependency { manifest, .. } => {
+                            simulation.write_file(manifest)
+                        }
+                        FileOperation::Delete { path } => simulation.delete(path),
+                        FileOperation::Read { .. } => {}
+                    }
+                }
+                println!("\n[PREDICT] {}", simulation.diff());
                 println!("\n[DONE] Dry-run mode: No changes were made.");
             }
 
             // Enhanced final power-user controls with session persistence
             println!("\n[COMPLETE] Task finished successfully");
             println!(
-                "[CONTROLS] Next action? [/suggest /new-task /status /undo /edit-plan /session /q]"
+                "[CONTROLS] Next action? [/finish /suggest /new-task /status /undo /edit-plan /checkpoint /restore /session /q]"
             );
             println!("[TIP] Commands can be abbreviated (e.g., /s for /suggest)");
 
@@ -1164,8 +2048,7 @@ impl CliApp {
             loop {
                 print!("vibe> ");
                 std::io::stdout().flush()?;
-                let mut input = String::new();
-                std::io::stdin().read_line(&mut input)?;
+                let input = self.read_input_line()?;
                 let command = input.trim().to_lowercase();
 
                 if command.is_empty() {
@@ -1174,6 +2057,37 @@ impl CliApp {
 
                 command_history.push(command.clone());
 
+                if let Some(checkpoint_command) = cli_checkpoints::parse(input.trim()) {
+                    match checkpoint_command {
+                        CheckpointCommand::Save(name) => {
+                            cli_checkpoints::save(name, &temp_plan, temp_plan.operations.len())
+                        }
+                        CheckpointCommand::Restore(name) => {
+                            if let Some(checkpoint) = cli_checkpoints::restore(name) {
+                                let pending = checkpoint
+                                    .plan
+                                    .operations
+                                    .len()
+                                    .saturating_sub(checkpoint.next_step);
+                                temp_plan = checkpoint.plan;
+                                if pending > 0 {
+                                    println!(
+                                        "[RESTORE] {} step(s) after the checkpoint were not re-applied; run the build again to redo them",
+                                        pending
+                                    );
+                                }
+                            }
+                        }
+                        CheckpointCommand::List => cli_checkpoints::list(),
+                    }
+                    continue;
+                }
+
+                if let Some(push) = cli_finish::parse(&command) {
+                    cli_finish::finish(session_commits.as_ref(), &current_goal, push).await;
+                    continue;
+                }
+
                 match command.as_str() {
                     "/suggest" | "/s" => {
                         println!("[SUGGEST] Generating improvement suggestions...");
@@ -1278,11 +2192,14 @@ impl CliApp {
                     }
                     "/help" | "/?" => {
                         println!("[HELP] Available commands:");
+                        println!("  /finish [push] - Squash this build's commits into one");
                         println!("  /suggest (/s)  - Show improvement suggestions");
                         println!("  /new-task (/n) - Start new task");
                         println!("  /status (/st)  - Show completion status");
                         println!("  /undo (/u)     - Undo last changes");
                         println!("  /edit-plan (/e)- Review last plan");
+                        println!("  /checkpoint <name> (/cp) - Save files and plan");
+                        println!("  /restore <name> - Go back to a checkpoint");
                         println!("  /session (/ss) - Show session info");
                         println!("  /history (/h)  - Show command history");
                         println!("  /config (/c)   - Show power user configuration");
@@ -1343,8 +2260,107 @@ impl CliApp {
         Ok(())
     }
 
+    /// Offer build mode on a freshly scaffolded project, scoped to its directory
+    async fn customize_new_project(&mut self, project: &Path, dry_run: bool) -> Result<()> {
+        println!("[BUILD] Describe changes to make with build mode (Enter to skip):");
+        let goal = self.read_input_line()?;
+        if goal.trim().is_empty() {
+            println!("{}", format!("Next: cd {}", project.display()).dimmed());
+            return Ok(());
+        }
+        std::env::set_current_dir(project)?;
+        let verbose = self.verbose;
+        self.handle_build(goal.trim(), dry_run, verbose, false)
+            .await
+    }
+
+    /// `--build --branch`: apply the build on a `bro/<goal>` branch, return
+    /// to the current branch and, with `--pr`, open a pull request for it
+    async fn handle_build_on_branch(
+        &mut self,
+        goal: &str,
+        verbose: bool,
+        show_diff: bool,
+        open_pr: bool,
+    ) -> Result<()> {
+        let workspace_root =
+            std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+        let branch = BuildBranch::start(&workspace_root, goal.trim())?;
+        println!(
+            "[BRANCH] Building on {} (from {})",
+            branch.name.bright_cyan(),
+            branch.base.as_deref().unwrap_or("detached HEAD")
+        );
+
+        self.applied_plan = None;
+        let built = self.handle_build(goal, false, verbose, show_diff).await;
+        let commits = branch.finish(&format!("feat: {}", goal.trim()))?;
+        built?;
+        let back_on = branch.base.as_deref().unwrap_or("the starting commit");
+        if commits == 0 {
+            println!(
+                "[BRANCH] Nothing was committed; removed {} and returned to {}",
+                branch.name, back_on
+            );
+            return Ok(());
+        }
+        println!(
+            "{} [BRANCH] {} commit(s) on {}; back on {}",
+            terminal::icon("✓", "OK").green(),
+            commits,
+            branch.name.bright_cyan(),
+            back_on
+        );
+
+        if !open_pr {
+            println!(
+                "{}",
+                format!(
+                    "Review with: git diff {}...{}",
+                    branch.base.as_deref().unwrap_or("HEAD"),
+                    branch.name
+                )
+                .dimmed()
+            );
+            return Ok(());
+        }
+        let body = match &self.applied_plan {
+            Some(plan) => cli_build_helpers::pull_request_body(plan, &workspace_root),
+            None => goal.trim().to_string(),
+        };
+        let url = branch.open_pull_request(goal.trim(), &body).await?;
+        println!("{} [PR] Opened {}", terminal::icon("✓", "OK").green(), url);
+        Ok(())
+    }
+
     pub async fn run(&mut self, cli: Cli) -> Result<()> {
+        terminal::init();
+        // Log lines on stderr would tear through the TUI's screen
+        if !cli.tui {
+            logging::init(cli.verbose, &cli.debug)?;
+        }
+        let mut result = self.dispatch(cli.clone()).await;
+        // A request that failed on a service the user then brought back runs once more
+        if let Err(error) = &result {
+            if cli_recovery::offer_recovery(error, &self.config)
+                .await
+                .unwrap_or(false)
+            {
+                result = self.dispatch(cli).await;
+            }
+        }
+        self.persist_token_usage();
+        result
+    }
+
+    async fn dispatch(&mut self, cli: Cli) -> Result<()> {
+        // Before anything reads the project: a pinned session runs in its workspace
+        self.enter_session_workspace(&cli)?;
+
         let args_str = cli.args.join(" ");
+        // `@out:N` attaches a retained command output to the query
+        let asks_about_output = !output_references(&args_str).is_empty();
+        let args_str = self.output_history.expand_references(&args_str)?;
 
         // Handle configuration file generation
         if let Some(config_path) = &cli.generate_config {
@@ -1382,11 +2398,42 @@ impl CliApp {
             }
         }
 
+        // Per-mode sampling from the chosen config, with this run's flags on top
+        let overrides = SamplingParams {
+            temperature: cli.temperature,
+            top_p: cli.top_p,
+            max_tokens: cli.max_tokens,
+            seed: cli.seed,
+        };
+        overrides.validate()?;
+        sampling::set_config(self.get_power_config().sampling.clone());
+        sampling::set_overrides(overrides);
+
+        // Every generation from here on is archived when the config asks for it
+        match self.get_power_config().prompt_archive.open() {
+            Ok(archive) => prompt_archive::install(archive),
+            Err(e) => eprintln!("Warning: prompt archive disabled: {}", e),
+        }
+
         // Initialize plugins
         if let Err(e) = self.config.initialize_plugins().await {
             eprintln!("Warning: Failed to initialize plugins: {}", e);
         }
 
+        // The team policy bundle is refreshed before anything checks the policy
+        if let Some(source) = &self.get_power_config().policy_bundle {
+            match policy_bundle::refresh(source).await {
+                Ok(policy_bundle::RefreshOutcome::Updated(version)) => {
+                    println!("Team policy bundle updated to v{}", version);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!(
+                    "Warning: Failed to refresh the team policy bundle, keeping the cached one: {}",
+                    e
+                ),
+            }
+        }
+
         // Show initial status
         self.display_background_status();
 
@@ -1398,20 +2445,48 @@ impl CliApp {
                 // Background services disabled - no automatic startup
                 // Event receiver available for explicit manual control
                 if let Some(event_receiver) = supervisor.get_event_receiver() {
-                    tokio::spawn(async move {
-                        Self::handle_background_events(event_receiver).await;
-                    });
+                    let index_changes = Arc::new(ChangedPaths::new());
+                    self.index_changes = Some(Arc::clone(&index_changes));
+                    let events_config = self.get_power_config().events.clone();
+                    tokio::spawn(cli_background::handle_events(
+                        event_receiver,
+                        index_changes,
+                        events_config,
+                    ));
                 }
 
                 // Log status update (services disabled)
                 tokio::spawn(async move {
                     if let Err(e) = supervisor.start(&project_root_path).await {
                         eprintln!("Background services remain disabled: {}", e);
+                        return;
                     }
+                    // Restarts crashed watchers; failures arrive as events
+                    supervisor.run_watchdog().await;
                 });
             }
         }
 
+        self.no_cache = cli.no_cache;
+        self.config.context.memory_scope = MemoryScope::resolve(
+            cli.no_memory,
+            cli.memory.as_deref(),
+            self.config.context.memory_scope,
+        );
+        self.verbose = cli.verbose > 0;
+        self.config.rag_reembed |= cli.reembed;
+        self.config.rag_query_expansion |= cli.expand_query;
+        self.rag_revision = cli.at.clone();
+        self.steal_lock = cli.steal_lock;
+        self.diff_engine = cli.diff_engine;
+        self.test_gate = cli.gate.as_deref() == Some("tests");
+        self.rag_context = cli.context.clone().filter(|_| cli.rag);
+        // Commands run from here on are audited as part of this request
+        command_audit::set_context(
+            cli.session.clone(),
+            Some(args_str.clone()).filter(|request| !request.is_empty()),
+        );
+
         // Handle session commands first
         if cli.list_sessions {
             return self.handle_list_sessions().await;
@@ -1419,11 +2494,154 @@ impl CliApp {
         if let Some(session_name) = &cli.delete_session {
             return self.handle_delete_session(session_name).await;
         }
+        if cli.migrate_sessions {
+            return self.handle_migrate_sessions();
+        }
+        if let Some(name) = &cli.fork_session {
+            return self.handle_fork_session(cli.session.as_ref(), name);
+        }
+        if let Some(source) = &cli.merge_session {
+            return self.handle_merge_session(source, cli.into.as_deref());
+        }
+        if let Some(name) = &cli.timeline {
+            let name = Some(name.clone()).filter(|name| !name.is_empty());
+            let Some((name, events)) =
+                self.session_timeline(name.as_ref().or(cli.session.as_ref()), &cli.args)?
+            else {
+                return Ok(());
+            };
+            #[cfg(feature = "tui")]
+            if cli.tui {
+                return crate::tui::run_timeline_view(&name, &events);
+            }
+            cli_session::display_timeline(&name, &events, cli.verbose > 0);
+            return Ok(());
+        }
+        if cli.sync {
+            return self.handle_sync(&cli.args).await;
+        }
         if cli.continue_session {
             return self.handle_continue_session().await;
         }
         if cli.undo {
-            return self.handle_undo().await;
+            return self.handle_undo(cli.steps).await;
+        }
+        if cli.usage {
+            if let Some(session_name) = &cli.session {
+                self.current_session = Some(session_name.clone());
+            }
+            return self.handle_usage_report();
+        }
+        if cli.doctor {
+            return cli_doctor::run_doctor(&self.config).await;
+        }
+        if let Some(collections) = &cli.snapshot {
+            return cli_snapshots::run_snapshot(collections).await;
+        }
+        if let Some(path) = &cli.restore {
+            return cli_snapshots::run_restore(path).await;
+        }
+        if cli.list_snapshots {
+            return cli_snapshots::list_snapshots();
+        }
+        if let Some(query) = &cli.plugin_search {
+            return cli_plugins::run_search(&self.get_power_config().plugins, query).await;
+        }
+        if let Some(name) = &cli.plugin_install {
+            return cli_plugins::run_install(&self.get_power_config().plugins, name).await;
+        }
+        if let Some(name) = &cli.plugin_update {
+            return cli_plugins::run_update(&self.get_power_config().plugins, name).await;
+        }
+        if cli.capabilities {
+            capability_registry::install(
+                CapabilityRegistry::probe(
+                    &ProbeTargets::from_config(&self.config),
+                    &capability_registry::Capability::ALL,
+                )
+                .await,
+            );
+            println!("{}", Capabilities::gather(&self.config).to_json());
+            return Ok(());
+        }
+        if let Some(dir) = &cli.memory_backup {
+            return cli_snapshots::run_memory_backup(dir).await;
+        }
+        if let Some(dir) = &cli.memory_restore {
+            return cli_snapshots::run_memory_restore(dir).await;
+        }
+        if cli.memory_compact {
+            return self.handle_memory_compact().await;
+        }
+        if cli.list_outputs {
+            return self.list_outputs();
+        }
+        if cli.todo {
+            return cli_todo::run_todo(&cli.args);
+        }
+        if cli.research {
+            return self.handle_research(&cli.args.join(" "), cli.minutes).await;
+        }
+        if cli.audit {
+            return cli_audit::run_audit(&cli.args);
+        }
+        if cli.archive {
+            return cli_archive::run_archive(&self.get_power_config().prompt_archive, &cli.args);
+        }
+        if cli.policy {
+            return cli_policy::run_policy(&cli.args, cli.session.as_deref());
+        }
+        if cli.secrets {
+            return cli_secrets::run_secrets(&cli.args, &self.get_power_config().secret_patterns);
+        }
+        if cli.auth {
+            return cli_auth::run_auth(&cli.args, &self.get_power_config().credentials).await;
+        }
+        if cli.environments {
+            let Some(store) = &self.session_store else {
+                println!(
+                    "{}",
+                    "No project detected - build environments are recorded per project session."
+                        .yellow()
+                );
+                return Ok(());
+            };
+            return cli_environment::run_environments(
+                &cli.args,
+                store,
+                &self.active_session_name(),
+            );
+        }
+        if cli.trash {
+            return cli_trash::run_trash(&cli.args);
+        }
+        if cli.new {
+            let Some(project) = cli_scaffold::run_new(&cli.args, &cli.var)? else {
+                return Ok(());
+            };
+            return self.customize_new_project(&project, cli.dry_run).await;
+        }
+        if let Some(file) = &cli.batch {
+            return cli_batch::run_batch(file, cli.batch_output.as_deref(), cli.jobs, &self.config)
+                .await;
+        }
+        if let Some(url) = &cli.bench_server {
+            return cli_bench::run_bench(
+                url,
+                cli.concurrency,
+                std::time::Duration::from_secs(cli.duration),
+                &self.get_power_config().credentials,
+            )
+            .await;
+        }
+        if cli.compare {
+            let models = model_comparison::model_list(&cli.models);
+            cli_compare::check_request(&args_str, &models)?;
+            #[cfg(feature = "tui")]
+            if cli.tui {
+                return crate::tui::run_compare_view(&args_str, &models).await;
+            }
+            return cli_compare::run_compare(&args_str, &models).await;
         }
 
         // Handle session context for other commands
@@ -1442,6 +2660,7 @@ impl CliApp {
             ("chat", cli.chat),
             ("run", cli.run || cli.agent), // agent is deprecated but still works
             ("ai_agent", cli.ai_agent),
+            ("resume", cli.resume),
             ("plan", cli.plan),
             ("build", cli.build),
             ("test", cli.test),
@@ -1451,7 +2670,7 @@ impl CliApp {
             ("explain", cli.explain),
             ("rag", cli.rag),
             ("stream", cli.stream),
-            ("context", cli.context),
+            ("context", cli.context.is_some() && !cli.rag),
         ];
 
         let active_modes: Vec<&str> = mode_flags
@@ -1472,6 +2691,30 @@ impl CliApp {
             return Ok(());
         }
 
+        // Refuse a mode whose services or devices are missing with one message
+        // saying why and how to fix it, and note what optional ones it lacks
+        let mode = match active_modes.first() {
+            Some(&"build") if cli.branch && !cli.dry_run => Some("build --branch"),
+            Some(mode) => Some(*mode),
+            None if !args_str.trim().is_empty() => Some("query"),
+            None => None,
+        };
+        if let Some(mode) = mode.filter(|_| !cli.tui) {
+            let registry = CapabilityRegistry::probe(
+                &ProbeTargets::from_config(&self.config),
+                &capability_registry::used_by(mode),
+            )
+            .await;
+            let degraded = registry.check(mode);
+            capability_registry::install(registry);
+            for note in degraded? {
+                eprintln!("{} {}", terminal::icon("⚠️", "Warn").yellow(), note);
+            }
+            if !args_str.trim().is_empty() {
+                self.record_timeline(TimelineEvent::query(mode, &args_str));
+            }
+        }
+
         // Handle new TUI mode
         if cli.tui {
             return self.handle_tui_mode(&cli).await;
@@ -1501,23 +2744,30 @@ impl CliApp {
             }
         } else if cli.test {
             self.handle_test_run().await
+        } else if cli.build && cli.branch && !cli.dry_run {
+            self.handle_build_on_branch(&args_str, cli.verbose > 0, cli.show_diff, cli.pr)
+                .await
         } else if cli.build {
-            self.handle_build(&args_str, cli.dry_run, cli.verbose, cli.show_diff)
+            self.handle_build(&args_str, cli.dry_run, cli.verbose > 0, cli.show_diff)
                 .await
         } else if cli.run || cli.agent {
             self.handle_agent(&args_str).await
         } else if cli.ai_agent {
-            self.handle_ai_agent(&args_str).await
+            self.handle_ai_agent(&args_str, cli.sub_agents, None).await
+        } else if cli.resume {
+            self.handle_resume().await
         } else if cli.plan {
             self.handle_plan_mode(&args_str).await
-        } else if cli.explain {
+        } else if cli.explain && !asks_about_output {
             self.handle_explain(&args_str).await
         } else if cli.rag {
             self.handle_rag(&args_str, cli.streaming).await
         } else if cli.stream {
             self.handle_stream_mode(&args_str).await
-        } else if cli.context {
-            self.handle_context(&args_str).await
+        } else if let Some(path) = &cli.context {
+            self.handle_context(&path.join(",")).await
+        } else if asks_about_output {
+            self.handle_output_question(&args_str).await
         } else {
             // Default: general query with ultra-fast processing
             self.handle_query_streaming(&args_str, cli.streaming).await
@@ -1527,11 +2777,11 @@ impl CliApp {
     /// Update progress display with minimal plain text indicators
     fn update_progress_display(&self, current: usize, total: usize, description: &str) {
         let status = match current {
-            1..=2 => "[✓]",
-            3 => "[→]",
-            4 => "[⚡]",
-            5 => "[✓]",
-            _ => "[○]",
+            1..=2 => terminal::icon("[✓]", "[ok]"),
+            3 => terminal::icon("[→]", "[->]"),
+            4 => terminal::icon("[⚡]", "[..]"),
+            5 => terminal::icon("[✓]", "[ok]"),
+            _ => terminal::icon("[○]", "[ ]"),
         };
 
         let session_prefix = if let Some(session) = &self.current_session {
@@ -1542,7 +2792,11 @@ impl CliApp {
 
         println!(
             "{}{} [{}/{}] {}",
-            session_prefix, status, current, total, description
+            session_prefix,
+            status,
+            current,
+            total,
+            terminal::wrap(&terminal::text(description), 4)
         );
     }
 
@@ -1617,8 +2871,11 @@ impl CliApp {
             let risk = match operation {
                 application::build_service::FileOperation::Create { .. }
                 | application::build_service::FileOperation::Read { .. } => "Low",
-                application::build_service::FileOperation::Update { .. } => "Medium",
-                application::build_service::FileOperation::Delete { .. } => "High",
+                application::build_service::FileOperation::Update { .. }
+                | application::build_service::FileOperation::Patch { .. } => "Medium",
+                application::build_service::FileOperation::Delete { .. }
+                | application::build_service::FileOperation::AddDependency { .. }
+                | application::build_service::FileOperation::RemoveDependency { .. } => "High",
             };
             let op_desc = match operation {
                 application::build_service::FileOperation::Create { path, .. } => {
@@ -1630,9 +2887,16 @@ impl CliApp {
                 application::build_service::FileOperation::Delete { path } => {
                     format!("Delete {}", path.display())
                 }
+                application::build_service::FileOperation::Patch { path, .. } => {
+                    format!("Patch {}", path.display())
+                }
                 application::build_service::FileOperation::Read { path } => {
                     format!("Read {}", path.display())
                 }
+                application::build_service::FileOperation::AddDependency { .. }
+                | application::build_service::FileOperation::RemoveDependency { .. } => {
+                    application::build_service::operation_summary(operation)
+                }
             };
             content.push_str(&format!("{}. {} ({})\n", i + 1, op_desc, risk));
         }
@@ -1690,9 +2954,16 @@ impl CliApp {
             application::build_service::FileOperation::Delete { path } => {
                 format!("Delete {}", path.display())
             }
+            application::build_service::FileOperation::Patch { path, .. } => {
+                format!("Patch {}", path.display())
+            }
             application::build_service::FileOperation::Read { path } => {
                 format!("Read {}", path.display())
             }
+            application::build_service::FileOperation::AddDependency { .. }
+            | application::build_service::FileOperation::RemoveDependency { .. } => {
+                application::build_service::operation_summary(operation)
+            }
         }
     }
 
@@ -1717,8 +2988,15 @@ impl CliApp {
             return None;
         }
 
-        if let Some(path_str) = strip_prefix("update ") {
-            if let Some(op) = original_ops.iter().find(|op| matches!(op, FileOperation::Update { path, .. } if path.display().to_string() == path_str)) {
+        if let Some(path_str) = strip_prefix("update ") {
+            if let Some(op) = original_ops.iter().find(|op| matches!(op, FileOperation::Update { path, .. } if path.display().to_string() == path_str)) {
+                return Some(op.clone());
+            }
+            return None;
+        }
+
+        if let Some(path_str) = strip_prefix("patch ") {
+            if let Some(op) = original_ops.iter().find(|op| matches!(op, FileOperation::Patch { path, .. } if path.display().to_string() == path_str)) {
                 return Some(op.clone());
             }
             return None;
@@ -1743,6 +3021,27 @@ impl CliApp {
             });
         }
 
+        // Dependency steps keep their case: `Add dependency serde 1.0 to Cargo.toml`
+        if lower.starts_with("add dependency ") {
+            let (spec, manifest) = line.get("add dependency ".len()..)?.rsplit_once(" to ")?;
+            let mut spec = spec.split_whitespace();
+            return Some(FileOperation::AddDependency {
+                manifest: std::path::PathBuf::from(manifest.trim()),
+                name: spec.next()?.to_string(),
+                version: spec.next().map(str::to_string),
+            });
+        }
+
+        if lower.starts_with("remove dependency ") {
+            let (name, manifest) = line
+                .get("remove dependency ".len()..)?
+                .rsplit_once(" from ")?;
+            return Some(FileOperation::RemoveDependency {
+                manifest: std::path::PathBuf::from(manifest.trim()),
+                name: name.trim().to_string(),
+            });
+        }
+
         None
     }
 
@@ -1794,19 +3093,34 @@ impl CliApp {
             FileOperation::Update {
                 path, new_content, ..
             } => format!("Update {} {}", path.display(), new_content),
+            FileOperation::Patch { path, hunks } => {
+                format!("Patch {} {}", path.display(), patch::render(hunks))
+            }
             FileOperation::Delete { path } => format!("Delete {}", path.display()),
             FileOperation::Read { path } => format!("Read {}", path.display()),
+            FileOperation::AddDependency { .. } | FileOperation::RemoveDependency { .. } => {
+                application::build_service::operation_summary(op)
+            }
         }
     }
 
-    /// Review and apply operations one by one with inline editing/viewing
+    /// Review and apply operations one by one with inline editing/viewing.
+    /// With a `remote` plan, steps already decided in the web UI are applied
+    /// without prompting and terminal decisions are mirrored back to it.
     fn apply_operations_interactively(
         &mut self,
         plan: &mut BuildPlan,
         build_service: &mut application::build_service::BuildService,
+        remote: Option<&QueuedPlan>,
     ) -> Result<bool> {
         use application::build_service::FileOperation;
 
+        // Approved from the web: wait for per-step decisions from there too
+        let remote_review = remote
+            .and_then(|queued| queued.plan_decision())
+            .is_some_and(|(_, source)| source.as_deref() == Some("web"));
+
+        let mut skipped = vec![false; plan.operations.len()];
         let mut idx = 0;
         while idx < plan.operations.len() {
             let total = plan.operations.len();
@@ -1814,22 +3128,98 @@ impl CliApp {
 
             println!("\n[STEP {}/{}]", idx + 1, total);
             build_service.display_operation_detail(&op)?;
+
+            let mut decision = remote.and_then(|queued| queued.operation_decision(idx));
+            if decision.is_none() && remote_review {
+                if let Some(queued) = remote {
+                    decision = shared::confirmation::wait_for_remote(
+                        "[REMOTE] Waiting for a decision on this step from the web UI",
+                        || queued.operation_decision(idx),
+                    )?;
+                }
+            }
+            match decision {
+                Some(Decision::Approved) => {
+                    println!("[REMOTE] Step {} approved", idx + 1);
+                    idx += 1;
+                    continue;
+                }
+                Some(Decision::Rejected) => {
+                    println!("[REMOTE] Step {} rejected - skipping", idx + 1);
+                    skipped[idx] = true;
+                    idx += 1;
+                    continue;
+                }
+                None => {}
+            }
+
             println!(
-                "[PROMPT] Apply? [y/n/e(dit)/v(iew)/r(emove)/q] or /plan /status /undo /suggest"
+                "[PROMPT] Apply? [y/n/e(dit)/v(iew)/r(emove)/q] or /plan /status /undo /suggest /checkpoint /restore /diff"
             );
 
             let input = self.read_input_line()?;
+            if let Some(engine) = input.trim().strip_prefix("/diff") {
+                // Shows the step again with the chosen engine
+                match engine.trim().parse::<DiffEngine>() {
+                    Ok(engine) => {
+                        self.diff_engine = engine;
+                        build_service.set_diff_engine(engine);
+                    }
+                    Err(_) => println!(
+                        "[DIFF] Showing {} diffs; use /diff line, /diff word or /diff semantic",
+                        self.diff_engine.name()
+                    ),
+                }
+                continue;
+            }
+            if let Some(command) = cli_checkpoints::parse(&input) {
+                match command {
+                    CheckpointCommand::Save(name) => {
+                        let (reviewed, next_step) =
+                            cli_checkpoints::reviewed_plan(plan, &skipped, idx);
+                        cli_checkpoints::save(name, &reviewed, next_step);
+                    }
+                    CheckpointCommand::Restore(name) => {
+                        if let Some(checkpoint) = cli_checkpoints::restore(name) {
+                            *plan = checkpoint.plan;
+                            build_service.set_buffered_operations(plan.operations.clone());
+                            skipped = vec![false; plan.operations.len()];
+                            idx = checkpoint.next_step;
+                            if let Some(queued) = remote {
+                                queued.update_operations(&plan.operations, idx, |op| {
+                                    build_service.assess_risk(op)
+                                });
+                            }
+                        }
+                    }
+                    CheckpointCommand::List => cli_checkpoints::list(),
+                }
+                continue;
+            }
             match input.trim().to_lowercase().as_str() {
                 "y" | "yes" => {
+                    if let Some(queued) = remote {
+                        queued.record_operation(idx, Decision::Approved);
+                    }
                     idx += 1;
                 }
                 "n" | "skip" => {
                     println!("[SKIP] Skipping operation {}", idx + 1);
+                    if let Some(queued) = remote {
+                        queued.record_operation(idx, Decision::Rejected);
+                    }
+                    skipped[idx] = true;
                     idx += 1;
                 }
                 "r" | "remove" => {
                     println!("[REMOVE] Removing step {}", idx + 1);
                     plan.operations.remove(idx);
+                    skipped.remove(idx);
+                    if let Some(queued) = remote {
+                        queued.update_operations(&plan.operations, idx, |op| {
+                            build_service.assess_risk(op)
+                        });
+                    }
                     continue;
                 }
                 "v" | "view" => {
@@ -1840,6 +3230,11 @@ impl CliApp {
                     if let Some(edited_op) = Self::edit_operation(op.clone())? {
                         plan.operations[idx] = edited_op;
                         build_service.set_buffered_operations(plan.operations.clone());
+                        if let Some(queued) = remote {
+                            queued.update_operations(&plan.operations, idx, |op| {
+                                build_service.assess_risk(op)
+                            });
+                        }
                         println!("[EDIT] Updated step {}", idx + 1);
                     } else {
                         println!("[EDIT] No changes made");
@@ -1865,6 +3260,12 @@ impl CliApp {
                                 if !updated_ops.is_empty() {
                                     plan.operations = updated_ops;
                                     build_service.set_buffered_operations(plan.operations.clone());
+                                    skipped = vec![false; plan.operations.len()];
+                                    if let Some(queued) = remote {
+                                        queued.update_operations(&plan.operations, 0, |op| {
+                                            build_service.assess_risk(op)
+                                        });
+                                    }
                                     idx = 0;
                                     println!("[EDIT] Plan updated; restarting review");
                                 }
@@ -1897,6 +3298,9 @@ impl CliApp {
             }
         }
 
+        let mut skipped = skipped.into_iter();
+        plan.operations.retain(|_| !skipped.next().unwrap_or(false));
+
         // Refresh buffered operations after interactive edits/removals
         build_service.set_buffered_operations(plan.operations.clone());
         Ok(true)
@@ -1914,8 +3318,14 @@ impl CliApp {
             } => {
                 println!("Update {}:\n{}", path.display(), new_content);
             }
+            FileOperation::Patch { path, hunks } => {
+                println!("Patch {}:\n{}", path.display(), patch::render(hunks));
+            }
             FileOperation::Delete { path } => println!("Delete {}", path.display()),
             FileOperation::Read { path } => println!("Read {}", path.display()),
+            FileOperation::AddDependency { .. } | FileOperation::RemoveDependency { .. } => {
+                println!("{}", application::build_service::operation_summary(op))
+            }
         }
     }
 
@@ -1944,8 +3354,22 @@ impl CliApp {
                     println!("  ... (truncated)");
                 }
             }
+            FileOperation::Patch { path, hunks } => {
+                println!("Patch {} ({} hunk(s))", path.display(), hunks.len());
+                let rendered = patch::render(hunks);
+                let lines: Vec<&str> = rendered.lines().collect();
+                for line in lines.iter().take(10) {
+                    println!("  {}", line);
+                }
+                if lines.len() > 10 {
+                    println!("  ... (truncated)");
+                }
+            }
             FileOperation::Delete { path } => println!("Delete {}", path.display()),
             FileOperation::Read { path } => println!("Read {}", path.display()),
+            FileOperation::AddDependency { .. } | FileOperation::RemoveDependency { .. } => {
+                println!("{}", application::build_service::operation_summary(op))
+            }
         }
     }
 
@@ -1981,8 +3405,22 @@ impl CliApp {
                     new_content: edited,
                 }))
             }
+            FileOperation::Patch { path, hunks } => {
+                let rendered = patch::render(&hunks);
+                let edited = editor::Editor::edit_content(
+                    &rendered,
+                    editor::EditContent::File(rendered.clone()),
+                )?;
+                match patch::parse(&edited) {
+                    Ok(hunks) => Ok(Some(FileOperation::Patch { path, hunks })),
+                    Err(e) => {
+                        println!("[EDIT] {}; keeping the original patch.", e);
+                        Ok(None)
+                    }
+                }
+            }
             _ => {
-                println!("[EDIT] Only create/update steps can be edited.");
+                println!("[EDIT] Only create/update/patch steps can be edited.");
                 Ok(None)
             }
         }
@@ -2021,6 +3459,13 @@ impl CliApp {
                 content.push_str("\nwith:\n");
                 content.push_str(new_content);
             }
+            application::build_service::FileOperation::Patch { path, hunks } => {
+                content.push_str(&format!("# Original: Patch {}\n", path.display()));
+                content.push_str("Patch ");
+                content.push_str(&path.display().to_string());
+                content.push_str(" with:\n");
+                content.push_str(&patch::render(hunks));
+            }
             application::build_service::FileOperation::Delete { path } => {
                 content.push_str(&format!("# Original: Delete {}\n", path.display()));
                 content.push_str("Delete ");
@@ -2031,6 +3476,12 @@ impl CliApp {
                 content.push_str("Read ");
                 content.push_str(&path.display().to_string());
             }
+            application::build_service::FileOperation::AddDependency { .. }
+            | application::build_service::FileOperation::RemoveDependency { .. } => {
+                let summary = application::build_service::operation_summary(operation);
+                content.push_str(&format!("# Original: {}\n", summary));
+                content.push_str(&summary);
+            }
         }
 
         content
@@ -2085,7 +3536,7 @@ impl CliApp {
     }
 
     /// Interactive plan review and editing session
-    fn interactive_plan_review(&self, plan: &mut BuildPlan) -> Result<()> {
+    fn interactive_plan_review(&mut self, plan: &mut BuildPlan) -> Result<()> {
         println!("\n[PLAN REVIEW] Interactive editing session");
         println!("Commands: 'e <step>' to edit step, 'd <step>' to delete step, 'a <desc>' to add step, 'q' to quit");
 
@@ -2102,17 +3553,23 @@ impl CliApp {
                     application::build_service::FileOperation::Delete { path } => {
                         format!("Delete {}", path.display())
                     }
+                    application::build_service::FileOperation::Patch { path, .. } => {
+                        format!("Patch {}", path.display())
+                    }
                     application::build_service::FileOperation::Read { path } => {
                         format!("Read {}", path.display())
                     }
+                    application::build_service::FileOperation::AddDependency { .. }
+                    | application::build_service::FileOperation::RemoveDependency { .. } => {
+                        application::build_service::operation_summary(operation)
+                    }
                 };
                 println!("  {}. {}", i + 1, op_desc);
             }
 
             print!("plan> ");
             std::io::stdout().flush()?;
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input)?;
+            let input = self.read_input_line()?;
             let command = input.trim();
 
             if command.is_empty() {
@@ -2184,7 +3641,7 @@ impl CliApp {
     }
 
     /// Display incremental changes in plain text format
-    fn display_incremental_changes(&self, code: &str, path: &str, op_type: &str) {
+    fn display_incremental_changes(&self, code: &str, path: &str, op_type: &str, full_diff: bool) {
         let session_info = if let Some(session) = &self.current_session {
             format!(" [{}]", session)
         } else {
@@ -2211,17 +3668,17 @@ impl CliApp {
                 );
 
                 if lines.len() <= 15 {
-                    println!("  └─ [full file - {} lines]", lines.len());
+                    println!(
+                        "  {} [full file - {} lines]",
+                        terminal::tree_branch(true),
+                        lines.len()
+                    );
                     Self::display_code_with_syntax(&lines, 0, &ext);
                 } else {
                     // Show in logical chunks for large files
                     let chunks = Self::create_file_chunks(&lines);
                     for (i, (start, end, description)) in chunks.iter().enumerate() {
-                        let chunk_marker = if i == chunks.len() - 1 {
-                            "└─"
-                        } else {
-                            "├─"
-                        };
+                        let chunk_marker = terminal::tree_branch(i == chunks.len() - 1);
                         println!(
                             "  {} Step {}: {}",
                             chunk_marker,
@@ -2258,7 +3715,8 @@ impl CliApp {
                 if code.contains("REPLACE") || code.contains("INSERT") || code.contains("DELETE") {
                     // AI generated targeted changes - display as instructions
                     println!(
-                        "  └─ [targeted changes - {} operations]",
+                        "  {} [targeted changes - {} operations]",
+                        terminal::tree_branch(true),
                         code.lines()
                             .filter(|l| l.starts_with("REPLACE")
                                 || l.starts_with("INSERT")
@@ -2277,10 +3735,29 @@ impl CliApp {
                         }
                     }
                 } else if code.contains("NO CHANGES REQUIRED") {
-                    println!("  └─ [no changes required - file already matches goal]");
+                    println!(
+                        "  {} [no changes required - file already matches goal]",
+                        terminal::tree_branch(true)
+                    );
+                } else if let Some(old_content) = std::env::current_dir()
+                    .ok()
+                    .and_then(|root| std::fs::read_to_string(root.join(path)).ok())
+                {
+                    println!("  {} [unified diff]", terminal::tree_branch(true));
+                    print_diff(
+                        self.diff_engine,
+                        Path::new(path),
+                        &old_content,
+                        &cleaned_code,
+                        full_diff,
+                    );
                 } else {
                     // Full content replacement - show diff preview
-                    println!("  └─ [full replacement - {} lines]", lines.len());
+                    println!(
+                        "  {} [full replacement - {} lines]",
+                        terminal::tree_branch(true),
+                        lines.len()
+                    );
                     if lines.len() <= 10 {
                         Self::display_code_with_syntax(&lines, 0, &ext);
                     } else {
@@ -2289,10 +3766,40 @@ impl CliApp {
                     }
                 }
             }
+            "patch" => {
+                println!(
+                    "{} Patching existing file {}",
+                    "🩹".bright_yellow(),
+                    path.bright_yellow()
+                );
+                let patched = patch::parse(code).and_then(|hunks| {
+                    let old_content = std::fs::read_to_string(path)?;
+                    let new_content = patch::apply(&old_content, &hunks)?;
+                    Ok((old_content, new_content))
+                });
+                match patched {
+                    Ok((old_content, new_content)) => {
+                        println!("  {} [unified diff]", terminal::tree_branch(true));
+                        print_diff(
+                            self.diff_engine,
+                            Path::new(path),
+                            &old_content,
+                            &new_content,
+                            full_diff,
+                        );
+                    }
+                    Err(e) => {
+                        println!("  {} [{}]", terminal::tree_branch(true), e);
+                        for line in &lines {
+                            println!("     {}", line.dimmed());
+                        }
+                    }
+                }
+            }
             _ => {
                 // Unknown operation type - show basic preview
                 println!("{} Processing {} ({})", "⚙️".bright_blue(), path, op_type);
-                println!("  └─ [{} lines]", lines.len());
+                println!("  {} [{} lines]", terminal::tree_branch(true), lines.len());
                 if lines.len() <= 10 {
                     Self::display_code_with_syntax(&lines, 0, &ext);
                 } else {
@@ -2310,6 +3817,13 @@ impl CliApp {
             "update" => {
                 println!("  Files: ~ {} (modified, {} lines)", path, lines.len());
             }
+            "patch" => {
+                println!(
+                    "  Files: ~ {} (patched, {} hunk(s))",
+                    path,
+                    patch::parse(code).map_or(0, |hunks| hunks.len())
+                );
+            }
             _ => {
                 println!("  Files: ? {} ({}, {} lines)", path, op_type, lines.len());
             }
@@ -2480,6 +3994,10 @@ impl CliApp {
                 .collect::<Vec<_>>()
                 .join(", ")
         );
+        // Earlier requests and commands, so follow-ups can refer to them
+        let mut history: Vec<domain::models::ConversationMessage> = Vec::new();
+        let policy = SummarizationPolicy::from_config(&self.config.context);
+        let counter = TokenCounter::from_config(&self.config.context);
 
         loop {
             let input: String = Input::with_theme(&ColorfulTheme::default())
@@ -2488,6 +4006,13 @@ impl CliApp {
             if input.to_lowercase() == "exit" {
                 break;
             }
+            if !output_references(&input).is_empty() {
+                match self.output_history.expand_references(&input) {
+                    Ok(question) => self.handle_output_question(&question).await?,
+                    Err(e) => eprintln!("{}", e.to_string().red()),
+                }
+                continue;
+            }
 
             // Check for shortcuts
             let effective_input =
@@ -2506,9 +4031,11 @@ impl CliApp {
             if effective_input != input {
                 println!("Expanded '{}' to: {}", input, effective_input);
             }
+            command_audit::set_prompt(&effective_input);
+            self.record_timeline(TimelineEvent::query("query", &effective_input));
 
             // Use the same logic as handle_query but with effective_input
-            let client = infrastructure::ollama_client::OllamaClient::new()?;
+            let client = OllamaClient::new()?.with_mode(GenerationMode::Command);
             // Check permissions for the expanded command if it's a direct command
             if !power_config.is_command_allowed(&effective_input) {
                 println!("{}", "Command blocked by sandbox".red());
@@ -2517,33 +4044,54 @@ impl CliApp {
                 }
             }
 
-            let prompt = format!("You are on a system with: {}. Generate a bash command to: {}. Respond with only the exact command to run, without any formatting, backticks, quotes, or explanation. Ensure the command is complete, syntactically correct, and uses standard Unix tools. For size comparisons, use appropriate units like -BG for gigabytes in df.", self.system_info, effective_input);
+            let earlier = if history.is_empty() {
+                String::new()
+            } else {
+                let turns: Vec<String> = history
+                    .iter()
+                    .map(|message| format!("{}: {}", message.role, message.content))
+                    .collect();
+                format!(" Earlier in this chat:\n{}\n", turns.join("\n"))
+            };
+            let prompt = format!("You are on a system with: {}.{} Generate a {} command to: {}. Respond with only the exact command to run, without any formatting, backticks, quotes, or explanation. Ensure the command is complete, syntactically correct, and uses standard tools for that shell. For size comparisons, use appropriate units like -BG for gigabytes in df.", self.system_info, earlier, Shell::detect(), effective_input);
             let response = client.generate_response(&prompt).await?;
             let command = extract_command_from_response(&response);
             println!("{}", format!("Command: {}", command).green());
-            if ask_confirmation("Run this command?", false)? {
-                let sandbox = Sandbox::new();
+            self.note_generation(&client).await;
+
+            for (role, content) in [("user", &effective_input), ("assistant", &command)] {
+                history.push(domain::models::ConversationMessage {
+                    role: role.to_string(),
+                    content: content.clone(),
+                    tool_calls: None,
+                    tool_call_id: None,
+                });
+            }
+            let engine = infrastructure::InferenceEngine::Ollama(client.clone());
+            if let Err(e) = compact_history(&engine, None, &mut history, &policy, &counter).await {
+                eprintln!("Warning: Failed to summarize the chat so far: {}", e);
+            }
+            if ask_confirmation("Run this command?", false)?
+                && confirm_network_access(&self.config.security.network_security, &command)?
+            {
+                let sandbox = Sandbox::for_security(&self.config.security);
                 println!("[EXEC] {}", command);
                 println!("[RUN] Executing command...");
-                match sandbox
-                    .execute_safe("bash", vec!["-c".to_string(), command.clone()])
-                    .await
-                {
+                match sandbox.execute_shell(&command).await {
                     Ok(output) => {
-                        println!("{}", output);
+                        self.show_command_output(&command, &output);
                         println!("[DONE] Command completed");
                     }
                     Err(e) => {
                         eprintln!("[ERROR] Sandbox execution failed: {}", e);
                         // Offer fallback option for debugging
                         if ask_confirmation("Try running without sandboxing?", false)? {
-                            match std::process::Command::new("bash")
-                                .arg("-c")
-                                .arg(&command)
-                                .output()
-                            {
+                            match command_audit::run_direct(&command) {
                                 Ok(output) => {
-                                    println!("{}", String::from_utf8_lossy(&output.stdout));
+                                    self.show_command_output(
+                                        &command,
+                                        &String::from_utf8_lossy(&output.stdout),
+                                    );
                                     if !output.status.success() {
                                         println!(
                                             "[DONE] Command failed: {}",
@@ -2604,8 +4152,14 @@ impl CliApp {
         let choice = input.trim();
 
         match choice {
-            "1" => self.execute_complete_plan(&plan).await?,
-            "2" => self.execute_step_by_step(&plan).await?,
+            "1" => {
+                let checkpoint = self.start_run_checkpoint(task, &plan)?;
+                self.execute_complete_plan(&plan, checkpoint).await?
+            }
+            "2" => {
+                let checkpoint = self.start_run_checkpoint(task, &plan)?;
+                self.execute_step_by_step(&plan, checkpoint).await?
+            }
             "3" => self.execute_dry_run(&plan).await?,
             "cancel" => {
                 println!("Execution cancelled.");
@@ -2620,6 +4174,42 @@ impl CliApp {
         Ok(())
     }
 
+    /// Continue the active session's interrupted run after its last
+    /// completed step
+    async fn handle_resume(&mut self) -> Result<()> {
+        let Some(store) = &self.session_store else {
+            println!(
+                "{}",
+                "No project detected - resuming a run requires a project context.".yellow()
+            );
+            return Ok(());
+        };
+        let session = self.active_session_name();
+        let Some(checkpoint) = store.run_checkpoint(&session)? else {
+            println!("Nothing to resume in session '{}'.", session);
+            return Ok(());
+        };
+        println!("{} {}", "Resuming".bright_cyan(), checkpoint.summary());
+
+        match checkpoint.kind {
+            RunKind::Run => {
+                let plan: AgentPlan = checkpoint
+                    .plan
+                    .clone()
+                    .and_then(|plan| serde_json::from_value(plan).ok())
+                    .ok_or_else(|| anyhow!("The interrupted run has no readable plan."))?;
+                display_agent_plan(&plan);
+                self.execute_complete_plan(&plan, checkpoint).await
+            }
+            RunKind::AiAgent => {
+                let goal = checkpoint.goal.clone();
+                let sub_agents = checkpoint.sub_agents;
+                self.handle_ai_agent(&goal, sub_agents, Some(checkpoint))
+                    .await
+            }
+        }
+    }
+
     async fn handle_explain(&self, file: &str) -> Result<()> {
         let path = std::path::Path::new(file);
         let content = if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
@@ -2691,19 +4281,26 @@ impl CliApp {
             return Ok(());
         }
 
+        // Personal data stays on this machine unless the model does too
+        let content = match self.config.pii_detector() {
+            Some(pii) => pii.apply(&content)?,
+            None => content,
+        };
         let prompt = format!("Explain this content in detail:\n\n{}", content);
 
         // Check cache first
-        if let Some(cached_response) = self.load_cached_explain(&prompt)? {
-            println!("{}", cached_response);
+        if let Some(cached) = self.load_cached_explain(&prompt)? {
+            println!("{}", cached.response);
+            show_cached_generation(&cached.generation);
             if ask_confirmation("Use this cached explanation?", true)? {
                 return Ok(());
             }
         }
 
         eprintln!("Analyzing file content...");
-        let client = infrastructure::ollama_client::OllamaClient::new()?;
+        let client = OllamaClient::new()?.with_mode(GenerationMode::Chat);
         let response = client.generate_response(&prompt).await?;
+        self.note_generation(&client).await;
 
         // Cache the response
         self.save_cached_explain(&prompt, &response)?;
@@ -2712,9 +4309,103 @@ impl CliApp {
         Ok(())
     }
 
+    /// Deduplicate and drop stale vectors from this project's RAG indexes
+    async fn handle_memory_compact(&self) -> Result<()> {
+        let project_root = find_project_root().unwrap_or_else(|| ".".to_string());
+        let (roots, _) =
+            cli_rag::resolve_rag_repositories(&project_root, &self.config.rag_repositories, None);
+        let roots: Vec<&str> = roots.iter().map(String::as_str).collect();
+        let rag_service = self.open_rag_service(&roots, &self.config.db_path).await?;
+
+        for (name, report) in rag_service.compact_index().await? {
+            println!(
+                "{} {}: {} duplicate and {} stale vectors removed, {} KB reclaimed",
+                terminal::icon("✓", "OK").green(),
+                name,
+                report.duplicates,
+                report.stale,
+                report.reclaimed_bytes / 1024
+            );
+        }
+        Ok(())
+    }
+
+    /// Research `topic` for at most `minutes`, then save and print the brief
+    async fn handle_research(&mut self, topic: &str, minutes: u64) -> Result<()> {
+        if topic.trim().is_empty() {
+            return Err(anyhow::anyhow!(
+                "Usage: bro --research <topic> [--minutes N]"
+            ));
+        }
+        let project_root = find_project_root().unwrap_or_else(|| ".".to_string());
+        if self.rag_service.is_none() {
+            let (roots, scope) = cli_rag::resolve_rag_repositories(
+                &project_root,
+                &self.config.rag_repositories,
+                self.rag_context.as_deref(),
+            );
+            let roots: Vec<&str> = roots.iter().map(String::as_str).collect();
+            let mut rag_service = self.open_rag_service(&roots, &self.config.db_path).await?;
+            if let Some(scope) = &scope {
+                rag_service.set_scope(scope)?;
+            }
+            rag_service
+                .build_index_for_keywords(&keywords_from_text(topic))
+                .await?;
+            self.attach_rag_service(rag_service);
+        }
+
+        let limits = AgentExecutionLimits {
+            max_iterations: RESEARCH_MAX_ITERATIONS,
+            max_execution_time_seconds: minutes.max(1) * 60,
+            ..AgentExecutionLimits::default()
+        };
+        let research = application::create_research_service(limits)?
+            .with_index(Arc::clone(self.rag_service.as_ref().unwrap()))
+            .with_web(NetworkSecurity::from_config(
+                &self.config.security.network_security,
+            ));
+        eprintln!("Researching '{}' for up to {} minute(s)...", topic, minutes);
+        let brief = research.research(topic).await?;
+
+        let path = cli_research::save_brief(Path::new(&project_root), topic, &brief.markdown)?;
+        println!("{}", brief.markdown);
+        println!(
+            "{} {} finding(s) over {} iteration(s){}, saved to {}",
+            terminal::icon("✓", "OK").green(),
+            brief.findings.len(),
+            brief.iterations,
+            if brief.timed_out {
+                ", stopped at the time limit"
+            } else {
+                ""
+            },
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// What went into the last RAG prompt
+    pub fn last_prompt_breakdown(&self) -> Option<PromptBreakdown> {
+        self.rag_service.as_ref()?.last_prompt_breakdown()
+    }
+
     pub async fn handle_rag(&mut self, question: &str, enable_streaming: bool) -> Result<()> {
-        if let Some(cached_response) = self.load_cached_rag(question)? {
+        // Cached answers describe the whole working tree, not a past revision
+        // or a subset of repositories
+        let use_cache = !self.no_cache && self.rag_revision.is_none() && self.rag_context.is_none();
+        let cached_response = if !use_cache {
+            None
+        } else if let Some(cached) = self.load_cached_rag(question)? {
+            Some((cached.response, cached.generation))
+        } else {
+            self.lookup_semantic_cache(RAG_SEMANTIC_CACHE, question)
+                .await
+                .map(|hit| (hit.value, None))
+        };
+        if let Some((cached_response, generation)) = cached_response {
             println!("{}", cached_response);
+            show_cached_generation(&generation);
             if ask_confirmation("Use this cached answer?", true)? {
                 return Ok(());
             }
@@ -2724,34 +4415,70 @@ impl CliApp {
             eprintln!("Analyzing query and scanning codebase...");
             let _client = OllamaClient::new()?;
             let project_root = find_project_root().unwrap_or_else(|| ".".to_string());
-            self.rag_service =
-                Some(application::create_rag_service(&project_root, &self.config.db_path).await?);
-            let keywords = keywords_from_text(question);
-            self.rag_service
-                .as_ref()
-                .unwrap()
-                .build_index_for_keywords(&keywords)
-                .await?;
+            let (roots, scope) = cli_rag::resolve_rag_repositories(
+                &project_root,
+                &self.config.rag_repositories,
+                self.rag_context.as_deref(),
+            );
+            let roots: Vec<&str> = roots.iter().map(String::as_str).collect();
+            let mut rag_service = self.open_rag_service(&roots, &self.config.db_path).await?;
+            if let Some(scope) = &scope {
+                rag_service.set_scope(scope)?;
+            }
+            if let Some(rev) = &self.rag_revision {
+                let commit = rag_service.build_index_at(rev).await?;
+                eprintln!(
+                    "Answering from {} ({})",
+                    rev,
+                    &commit[..12.min(commit.len())]
+                );
+                self.attach_rag_service(rag_service);
+            } else {
+                self.attach_rag_service(rag_service);
+                let keywords = keywords_from_text(question);
+                self.rag_service
+                    .as_ref()
+                    .unwrap()
+                    .build_index_for_keywords(&keywords)
+                    .await?;
+            }
         }
 
         let mut feedback = String::new();
+        // Answers rejected with feedback, learned from once one is accepted
+        let mut rejected: Vec<(String, String)> = Vec::new();
         loop {
             eprintln!("Thinking...");
             let response = if enable_streaming {
                 println!("🧠 Analyzing context...");
-                let mut streamed_response = String::new();
+                let cancel = CancellationToken::new();
+                let interrupt = cancel_on_interrupt(&cancel);
                 let result = self
                     .rag_service
                     .as_ref()
                     .unwrap()
-                    .query_with_feedback_streaming(question, &feedback, |chunk| {
-                        // Real-time streaming display
-                        print!("{}", chunk);
-                        std::io::Write::flush(&mut std::io::stdout()).unwrap();
-                        streamed_response.push_str(chunk);
-                    })
+                    .query_with_feedback_streaming_events(
+                        question,
+                        &feedback,
+                        |event| {
+                            // Real-time streaming display
+                            if let RagStreamEvent::Token(chunk) = event {
+                                print!("{}", chunk);
+                                std::io::Write::flush(&mut std::io::stdout()).unwrap();
+                            }
+                        },
+                        &cancel,
+                    )
                     .await?;
+                drop(interrupt);
                 println!(); // New line after streaming
+                if cancel.is_cancelled() {
+                    println!(
+                        "{}",
+                        "Generation interrupted - partial answer not cached.".yellow()
+                    );
+                    return Ok(());
+                }
                 result
             } else {
                 self.rag_service
@@ -2761,10 +4488,13 @@ impl CliApp {
                     .await?
             };
 
-            if response.starts_with("__SECRETS_DETECTED__:") {
+            let response = if response.answer.starts_with("__SECRETS_DETECTED__:") {
                 println!(
                     "{}",
-                    response.trim_start_matches("__SECRETS_DETECTED__:").trim()
+                    response
+                        .answer
+                        .trim_start_matches("__SECRETS_DETECTED__:")
+                        .trim()
                 );
                 if ask_confirmation("Continue with sanitized response?", false)? {
                     // Re-run the query but force it to continue with sanitized content
@@ -2774,17 +4504,51 @@ impl CliApp {
                         .unwrap()
                         .query_with_feedback_force(question, &feedback)
                         .await?;
-                    println!("{}", force_response);
+                    println!("{}", force_response.answer);
+                    force_response
                 } else {
                     println!("Query cancelled by user.");
                     return Ok(());
                 }
             } else {
-                println!("{}", response);
+                println!("{}", response.answer);
+                response
+            };
+
+            // The RAG service generates with the chat model and sampling too
+            self.note_generation(&OllamaClient::new()?.with_mode(GenerationMode::Chat))
+                .await;
+            if self.verbose {
+                if let Some(breakdown) = self.last_prompt_breakdown() {
+                    eprint!("{}", breakdown.to_string().dimmed());
+                }
+            }
+
+            // Footnotes travel with cached answers, so they are plain text
+            let footnotes = cli_rag::format_citations(&response.citations);
+            if !footnotes.is_empty() {
+                println!("\n{}", footnotes);
             }
+            let response = if footnotes.is_empty() {
+                response.answer
+            } else {
+                format!("{}\n\n{}", response.answer, footnotes)
+            };
 
             if ask_confirmation("Satisfied with this response?", true)? {
-                self.save_cached_rag(question, &response)?;
+                if self.rag_revision.is_none() && self.rag_context.is_none() {
+                    self.save_cached_rag(question, &response)?;
+                }
+                let rag_service = self.rag_service.as_ref().unwrap();
+                for (rejected_answer, feedback) in &rejected {
+                    if let Err(e) = rag_service
+                        .record_correction(question, rejected_answer, feedback, &response)
+                        .await
+                    {
+                        eprintln!("{} Feedback not remembered: {}", "Note:".yellow(), e);
+                        break;
+                    }
+                }
                 break;
             } else {
                 feedback.clear();
@@ -2792,6 +4556,9 @@ impl CliApp {
                 io::stdout().flush()?;
                 io::stdin().read_line(&mut feedback)?;
                 feedback = feedback.trim().to_string();
+                if !feedback.is_empty() {
+                    rejected.push((response, feedback.clone()));
+                }
                 eprintln!("Regenerating with feedback...");
             }
         }
@@ -2808,8 +4575,10 @@ impl CliApp {
         let context_db_path = super::utils::project_cache_suffix();
         context_config.db_path = context_db_path;
 
-        self.rag_service =
-            Some(application::create_rag_service(path, &context_config.db_path.clone()).await?);
+        let rag_service = self
+            .open_rag_service(&[path], &context_config.db_path)
+            .await?;
+        self.attach_rag_service(rag_service);
         self.rag_service.as_ref().unwrap().build_index().await?;
         eprintln!("Context loaded from {}", path);
         self.handle_chat().await
@@ -2820,6 +4589,17 @@ impl CliApp {
         self.handle_query_streaming(query, false).await
     }
 
+    /// Offer build mode for a bare query that reads like a code change
+    fn suggest_build_mode(query: &str) -> Result<bool> {
+        if !looks_like_code_change(query) || !std::io::stdin().is_terminal() {
+            return Ok(false);
+        }
+        ask_confirmation(
+            &terminal::text("This looks like a code change — run in build mode?"),
+            true,
+        )
+    }
+
     /// Ultra-fast streaming query handler for real-time feedback
     async fn handle_query_streaming(&mut self, query: &str, enable_streaming: bool) -> Result<()> {
         use shared::performance_monitor::GLOBAL_METRICS;
@@ -2869,7 +4649,26 @@ impl CliApp {
             Self::load_cached(&self.cache_path, &effective_query).is_ok_and(|opt| opt.is_some());
         GLOBAL_METRICS.end_operation("cache_lookup").await;
 
-        if let Ok(Some(cached_command)) = Self::load_cached(&self.cache_path, &effective_query) {
+        let cached_command = if self.no_cache {
+            None
+        } else if let Ok(Some(cached)) = Self::load_cached(&self.cache_path, &effective_query) {
+            show_cached_generation(&cached.generation);
+            Some(cached.command)
+        } else {
+            self.lookup_semantic_cache(COMMAND_SEMANTIC_CACHE, &effective_query)
+                .await
+                .map(|hit| hit.value)
+        };
+
+        // A build goal would only produce a nonsensical shell command
+        if cached_command.is_none() && Self::suggest_build_mode(&effective_query)? {
+            GLOBAL_METRICS.end_operation("query_total").await;
+            return self
+                .handle_build(&effective_query, false, false, false)
+                .await;
+        }
+
+        if let Some(cached_command) = cached_command {
             // Use enhanced confirmation system based on intent
             let confirmed = match query_intent {
                 CommandIntent::Installation => {
@@ -2897,14 +4696,13 @@ impl CliApp {
                 if needs_sudo {
                     // For sudo commands, skip sandbox and execute directly
                     GLOBAL_METRICS.start_operation("command_execution").await;
-                    match std::process::Command::new("bash")
-                        .arg("-c")
-                        .arg(&effective_command)
-                        .output()
-                    {
+                    match command_audit::run_direct(&effective_command) {
                         Ok(output) => {
                             GLOBAL_METRICS.end_operation("command_execution").await;
-                            println!("{}", String::from_utf8_lossy(&output.stdout));
+                            self.show_command_output(
+                                &effective_command,
+                                &String::from_utf8_lossy(&output.stdout),
+                            );
                             if !output.status.success() {
                                 let stderr = String::from_utf8_lossy(&output.stderr);
                                 // Check if this is an expected non-error exit code
@@ -2913,32 +4711,34 @@ impl CliApp {
                                     output.status.code(),
                                     &stderr,
                                 ) {
-                                    let _ = Self::save_cached(
-                                        &self.cache_path,
-                                        &effective_query,
-                                        &effective_command,
-                                    );
+                                    let _ = self
+                                        .save_cached_command(&effective_query, &effective_command);
                                 } else {
                                     println!("{}", format!("Command failed: {}", stderr).red());
                                 }
                             } else {
-                                let _ = Self::save_cached(
-                                    &self.cache_path,
-                                    &effective_query,
-                                    &effective_command,
-                                );
+                                let _ =
+                                    self.save_cached_command(&effective_query, &effective_command);
                             }
                         }
                         Err(e) => {
                             eprintln!("{}", format!("Direct execution failed: {}", e).red());
                         }
                     }
+                } else if !confirm_network_access(
+                    &self.config.security.network_security,
+                    &effective_command,
+                )? {
+                    eprintln!(
+                        "{}",
+                        "Command cancelled: network access was not allowed.".red()
+                    );
                 } else {
                     // For non-sudo commands, try sandbox first
-                    let sandbox = Sandbox::new();
+                    let sandbox = Sandbox::for_security(&self.config.security);
                     match sandbox.execute_command_string(&effective_command).await {
                         Ok(output) => {
-                            println!("{}", output);
+                            self.show_command_output(&effective_command, &output);
                             return Ok(());
                         }
                         Err(e) => {
@@ -2948,13 +4748,12 @@ impl CliApp {
                                 "Try executing directly (bypassing sandbox)?",
                                 false,
                             )? {
-                                match std::process::Command::new("bash")
-                                    .arg("-c")
-                                    .arg(&effective_command)
-                                    .output()
-                                {
+                                match command_audit::run_direct(&effective_command) {
                                     Ok(output) => {
-                                        println!("{}", String::from_utf8_lossy(&output.stdout));
+                                        self.show_command_output(
+                                            &effective_command,
+                                            &String::from_utf8_lossy(&output.stdout),
+                                        );
                                         if !output.status.success() {
                                             let stderr = String::from_utf8_lossy(&output.stderr);
                                             // Check if this is an expected non-error exit code
@@ -2963,8 +4762,7 @@ impl CliApp {
                                                 output.status.code(),
                                                 &stderr,
                                             ) {
-                                                let _ = Self::save_cached(
-                                                    &self.cache_path,
+                                                let _ = self.save_cached_command(
                                                     &effective_query,
                                                     &effective_command,
                                                 );
@@ -2975,8 +4773,7 @@ impl CliApp {
                                                 );
                                             }
                                         } else {
-                                            let _ = Self::save_cached(
-                                                &self.cache_path,
+                                            let _ = self.save_cached_command(
                                                 &effective_query,
                                                 &effective_command,
                                             );
@@ -3009,9 +4806,8 @@ impl CliApp {
         let system_context = infrastructure::config::SystemContext::gather();
 
         // Gather dynamic context based on request type
-        let ls_output = std::process::Command::new("sh")
-            .arg("-c")
-            .arg("ls -la 2>/dev/null | head -n 30")
+        let ls_output = Shell::detect()
+            .command(&Shell::detect().directory_listing_script(30))
             .output()
             .ok()
             .and_then(|o| String::from_utf8(o.stdout).ok())
@@ -3023,9 +4819,7 @@ impl CliApp {
             || query.to_lowercase().contains("ssh")
             || query.to_lowercase().contains("systemctl")
         {
-            std::process::Command::new("sh")
-                .arg("-c")
-                .arg("systemctl list-units --type=service --no-pager 2>/dev/null | grep -E '(running|active)' | awk '{print $1}' | head -n 50 || service --status-all 2>/dev/null | grep '+' | awk '{print $NF}' | head -n 30")
+            Shell::detect().command("systemctl list-units --type=service --no-pager 2>/dev/null | grep -E '(running|active)' | awk '{print $1}' | head -n 50 || service --status-all 2>/dev/null | grep '+' | awk '{print $NF}' | head -n 30")
                 .output()
                 .ok()
                 .and_then(|o| String::from_utf8(o.stdout).ok())
@@ -3034,88 +4828,56 @@ impl CliApp {
             String::new()
         };
 
-        let client = infrastructure::ollama_client::OllamaClient::new()?;
-
-        let prompt = format!(
-            r#"Generate ONE bash command for the user's request. Output ONLY the command, nothing else.
-
-REQUEST: {}
+        let client = OllamaClient::new()?.with_mode(GenerationMode::Command);
 
-SYSTEM: {}
-Package Manager: {}
-{}
-{}
-COMMAND GENERATION RULES:
-1. Output format: ONE line, ONE command, NO markdown, NO explanations, NO backticks
-2. For services: Use "systemctl status SERVICE_NAME" where SERVICE_NAME is from the list above
-3. For files: Use exact names from directory listing
-4. For packages: Use the package manager shown above
-5. Common patterns:
-    - Service status: systemctl status SERVICE_NAME
-    - Install package: sudo PACKAGE_MANAGER install PACKAGE
-    - File operations: Use actual file names from directory
-
-HOW TO FIND THE RIGHT SERVICE NAME:
-- User says "ssh" or "sshd" → Look in AVAILABLE SERVICES for "ssh.service" or "sshd.service"
-- If you see "sshd.service" in the list, use: systemctl status sshd
-- If you see "ssh.service" in the list, use: systemctl status ssh
-- Remove ".service" suffix when using with systemctl
-
-VALID COMMAND EXAMPLES (adjust based on actual context):
-systemctl status nginx
-sudo apt install python3
-zip archive.zip file.txt
-
-OUTPUT ONLY THE COMMAND:"#,
-            effective_query,
-            system_context.distro,
-            system_context.package_manager,
-            if !services_output.is_empty() {
-                format!(
-                    "AVAILABLE SERVICES:\n{}",
-                    services_output
-                        .lines()
-                        .take(20)
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                )
-            } else {
-                String::new()
-            },
-            if !ls_output.is_empty() {
-                format!(
-                    "\nCURRENT DIRECTORY:\n{}",
-                    ls_output.lines().take(15).collect::<Vec<_>>().join("\n")
-                )
-            } else {
-                String::new()
-            }
-        );
+        let prompt = prompts::command_prompt(
+            PromptTemplates::global(),
+            &effective_query,
+            &system_context,
+            &Shell::detect().to_string(),
+            &services_output,
+            &ls_output,
+        )?;
 
         // Use streaming response for real-time feedback if enabled
         let response = if enable_streaming {
             println!("🤖 Generating command...");
             let mut streamed_response = String::new();
+            let cancel = CancellationToken::new();
+            let interrupt = cancel_on_interrupt(&cancel);
             let result = client
-                .generate_response_streaming(&prompt, |chunk| {
-                    // Real-time streaming display
-                    print!("{}", chunk);
-                    let _ = std::io::stdout().flush(); // Ignore flush errors for streaming
-                    streamed_response.push_str(chunk);
-                })
+                .generate_response_streaming_cancellable(
+                    &prompt,
+                    |chunk| {
+                        // Real-time streaming display
+                        print!("{}", chunk);
+                        let _ = std::io::stdout().flush(); // Ignore flush errors for streaming
+                        streamed_response.push_str(chunk);
+                    },
+                    &cancel,
+                )
                 .await?;
+            drop(interrupt);
             println!(); // New line after streaming
+            if cancel.is_cancelled() {
+                println!(
+                    "{}",
+                    "Generation interrupted - no command executed.".yellow()
+                );
+                return Ok(());
+            }
             result
         } else {
             client.generate_response(&prompt).await?
         };
+        self.note_generation(&client).await;
 
         let command = extract_command_from_response(&response);
 
         // Validate command syntax before caching
         match validate_command_syntax(&command) {
             Ok(_) => {
-                let _ = Self::save_cached(&self.cache_path, &effective_query, &command);
+                let _ = self.save_cached_command(&effective_query, &command);
             }
             Err(error_msg) => {
                 eprintln!(
@@ -3146,13 +4908,12 @@ OUTPUT ONLY THE COMMAND:"#,
         if ask_confirmation(&prompt, is_safe)? {
             if needs_sudo {
                 // For sudo commands, skip sandbox and execute directly
-                match std::process::Command::new("bash")
-                    .arg("-c")
-                    .arg(&effective_command)
-                    .output()
-                {
+                match command_audit::run_direct(&effective_command) {
                     Ok(output) => {
-                        println!("{}", String::from_utf8_lossy(&output.stdout));
+                        self.show_command_output(
+                            &effective_command,
+                            &String::from_utf8_lossy(&output.stdout),
+                        );
                         if !output.status.success() {
                             let stderr = String::from_utf8_lossy(&output.stderr);
                             // Check if this is an expected non-error exit code
@@ -3161,44 +4922,44 @@ OUTPUT ONLY THE COMMAND:"#,
                                 output.status.code(),
                                 &stderr,
                             ) {
-                                let _ = Self::save_cached(
-                                    &self.cache_path,
-                                    &effective_query,
-                                    &effective_command,
-                                );
+                                let _ =
+                                    self.save_cached_command(&effective_query, &effective_command);
                             } else {
                                 println!("{}", format!("Command failed: {}", stderr).red());
                             }
                         } else {
-                            let _ = Self::save_cached(
-                                &self.cache_path,
-                                &effective_query,
-                                &effective_command,
-                            );
+                            let _ = self.save_cached_command(&effective_query, &effective_command);
                         }
                     }
                     Err(e) => {
                         eprintln!("{}", format!("Direct execution failed: {}", e).red());
                     }
                 }
+            } else if !confirm_network_access(
+                &self.config.security.network_security,
+                &effective_command,
+            )? {
+                eprintln!(
+                    "{}",
+                    "Command cancelled: network access was not allowed.".red()
+                );
             } else {
                 // For non-sudo commands, try sandbox first
-                let sandbox = Sandbox::new();
+                let sandbox = Sandbox::for_security(&self.config.security);
                 match sandbox.execute_command_string(&effective_command).await {
                     Ok(output) => {
-                        println!("{}", output);
+                        self.show_command_output(&effective_command, &output);
                     }
                     Err(e) => {
                         eprintln!("{}", format!("Command execution failed: {}", e).red());
                         // Offer direct execution as fallback
                         if ask_confirmation("Try executing directly (bypassing sandbox)?", false)? {
-                            match std::process::Command::new("bash")
-                                .arg("-c")
-                                .arg(&effective_command)
-                                .output()
-                            {
+                            match command_audit::run_direct(&effective_command) {
                                 Ok(output) => {
-                                    println!("{}", String::from_utf8_lossy(&output.stdout));
+                                    self.show_command_output(
+                                        &effective_command,
+                                        &String::from_utf8_lossy(&output.stdout),
+                                    );
                                     if !output.status.success() {
                                         let stderr = String::from_utf8_lossy(&output.stderr);
                                         // Check if this is an expected non-error exit code
@@ -3207,8 +4968,7 @@ OUTPUT ONLY THE COMMAND:"#,
                                             output.status.code(),
                                             &stderr,
                                         ) {
-                                            let _ = Self::save_cached(
-                                                &self.cache_path,
+                                            let _ = self.save_cached_command(
                                                 &effective_query,
                                                 &effective_command,
                                             );
@@ -3219,8 +4979,7 @@ OUTPUT ONLY THE COMMAND:"#,
                                             );
                                         }
                                     } else {
-                                        let _ = Self::save_cached(
-                                            &self.cache_path,
+                                        let _ = self.save_cached_command(
                                             &effective_query,
                                             &effective_command,
                                         );
@@ -3252,8 +5011,12 @@ OUTPUT ONLY THE COMMAND:"#,
         command: &str,
         raw_output: &str,
     ) -> Result<()> {
-        let client = infrastructure::ollama_client::OllamaClient::new()?;
+        let client = OllamaClient::new()?.with_mode(GenerationMode::Chat);
         let system_context = infrastructure::config::SystemContext::gather();
+        let redactor = self.config.prompt_redactor();
+        // The model sees placeholders; the user still sees the real output
+        let prompt_command = redactor.redact(command)?;
+        let prompt_output = redactor.redact(raw_output)?;
 
         let prompt = format!(
             r#"Process this command output for the user's query and provide a direct, human-readable answer.
@@ -3298,8 +5061,8 @@ Use the system context to better understand the output format and provide more a
             system_context.gpu_model,
             system_context.package_manager,
             query,
-            command,
-            raw_output
+            prompt_command,
+            prompt_output
         );
 
         match client.generate_response(&prompt).await {
@@ -3468,11 +5231,12 @@ COMMAND:"#,
             query
         );
 
-        let client = infrastructure::ollama_client::OllamaClient::new()?;
+        let client = OllamaClient::new()?.with_mode(GenerationMode::Command);
         let response = client.generate_response(&prompt).await?;
         let command = extract_command_from_response(&response);
 
         println!("{}", format!("Generated command: {}", command).green());
+        self.note_generation(&client).await;
 
         // Validate command syntax
         match validate_command_syntax(&command) {
@@ -3540,11 +5304,17 @@ COMMAND:"#,
                             eprintln!("Installation execution failed: {}", e);
                         }
                     }
+                } else if !confirm_network_access(&self.config.security.network_security, &command)?
+                {
+                    eprintln!(
+                        "{}",
+                        "Command cancelled: network access was not allowed.".red()
+                    );
                 } else {
-                    let sandbox = Sandbox::new();
+                    let sandbox = Sandbox::for_security(&self.config.security);
                     match sandbox.execute_command_string(&command).await {
                         Ok(output) => {
-                            println!("{}", output);
+                            self.show_command_output(&command, &output);
                             println!("Installation completed successfully");
                             self.show_post_installation_steps(&command, query);
                         }
@@ -3555,7 +5325,7 @@ COMMAND:"#,
                 }
 
                 // Cache successful installations
-                let _ = Self::save_cached(&self.cache_path, query, &command);
+                let _ = self.save_cached_command(query, &command);
             }
             Err(error_msg) => {
                 eprintln!("Generated command has syntax issues: {}", error_msg);
@@ -3565,27 +5335,49 @@ COMMAND:"#,
         Ok(())
     }
 
-    async fn execute_complete_plan(&self, plan: &AgentPlan) -> Result<()> {
+    async fn execute_complete_plan(
+        &self,
+        plan: &AgentPlan,
+        mut checkpoint: RunCheckpoint,
+    ) -> Result<()> {
         println!();
         println!("EXECUTING AGENT PLAN...");
 
         let start_time = std::time::Instant::now();
         let mut completed_steps = 0;
         let total_steps = plan.steps.len();
+        let cancel = CancellationToken::new();
+        let _interrupt = cancel_on_interrupt(&cancel);
 
         for (i, step) in plan.steps.iter().enumerate() {
             let step_num = i + 1;
             println!();
+            if checkpoint.is_step_done(&step.id) {
+                completed_steps += 1;
+                println!(
+                    "[{}/{}] {} (completed before)",
+                    step_num, total_steps, step.description
+                );
+                continue;
+            }
             println!("[{}/{}] {}", step_num, total_steps, step.description);
 
             // Execute the step
-            match self.execute_agent_step(step).await {
+            let Some(outcome) = self
+                .run_checkpointed_step(step, step_num, &mut checkpoint, &cancel)
+                .await
+            else {
+                return Ok(());
+            };
+            match outcome {
                 Ok(_) => {
                     completed_steps += 1;
                     println!("Step {}/{}: {}", step_num, total_steps, step.description);
                 }
                 Err(e) => {
                     eprintln!("Step {}/{} failed: {}", step_num, total_steps, e);
+                    checkpoint.stop(format!("step {} failed: {}", step_num, e));
+                    self.save_run_checkpoint(Some(&checkpoint));
                     if ask_confirmation("Continue with remaining steps?", false)? {
                         continue;
                     } else {
@@ -3605,15 +5397,24 @@ COMMAND:"#,
         println!("- Duration: {:.1}s", duration.as_secs_f64());
 
         if completed_steps == total_steps {
+            self.save_run_checkpoint(None);
             self.show_agent_completion_steps(plan);
+        } else {
+            println!("`bro --resume` runs the steps that did not complete.");
         }
 
         Ok(())
     }
 
-    async fn execute_step_by_step(&self, plan: &AgentPlan) -> Result<()> {
+    async fn execute_step_by_step(
+        &self,
+        plan: &AgentPlan,
+        mut checkpoint: RunCheckpoint,
+    ) -> Result<()> {
         println!();
         println!("STEP-BY-STEP EXECUTION MODE");
+        let cancel = CancellationToken::new();
+        let _interrupt = cancel_on_interrupt(&cancel);
 
         for (i, step) in plan.steps.iter().enumerate() {
             let step_num = i + 1;
@@ -3634,10 +5435,18 @@ COMMAND:"#,
                 continue;
             }
 
-            match self.execute_agent_step(step).await {
+            let Some(outcome) = self
+                .run_checkpointed_step(step, step_num, &mut checkpoint, &cancel)
+                .await
+            else {
+                return Ok(());
+            };
+            match outcome {
                 Ok(_) => println!("Step {} completed successfully.", step_num),
                 Err(e) => {
                     eprintln!("Step {} failed: {}", step_num, e);
+                    checkpoint.stop(format!("step {} failed: {}", step_num, e));
+                    self.save_run_checkpoint(Some(&checkpoint));
                     if !ask_confirmation("Continue with next step?", false)? {
                         break;
                     }
@@ -3645,6 +5454,13 @@ COMMAND:"#,
             }
         }
 
+        if plan
+            .steps
+            .iter()
+            .all(|step| checkpoint.is_step_done(&step.id))
+        {
+            self.save_run_checkpoint(None);
+        }
         println!();
         println!("Step-by-step execution complete.");
         Ok(())
@@ -3655,6 +5471,8 @@ COMMAND:"#,
         println!("DRY RUN MODE - No commands will be executed");
         println!("========================================");
 
+        let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+        let mut simulation = FsSimulation::new(cwd);
         for (i, step) in plan.steps.iter().enumerate() {
             let step_num = i + 1;
             println!();
@@ -3680,8 +5498,12 @@ COMMAND:"#,
             } else {
                 println!("  Safety: Command blocked by policy");
             }
+            simulation.run_command(&step.command);
         }
 
+        println!();
+        println!("PREDICTED FILESYSTEM CHANGES");
+        println!("{}", simulation.diff());
         println!();
         println!("DRY RUN COMPLETE");
         println!("- Total steps: {}", plan.steps.len());
@@ -3691,6 +5513,52 @@ COMMAND:"#,
         Ok(())
     }
 
+    /// Run `step` and record it in `checkpoint` once it completes; None
+    /// when Ctrl+C came first, with the checkpoint saved for `--resume`
+    async fn run_checkpointed_step(
+        &self,
+        step: &AgentStep,
+        step_num: usize,
+        checkpoint: &mut RunCheckpoint,
+        cancel: &CancellationToken,
+    ) -> Option<Result<()>> {
+        let outcome = tokio::select! {
+            outcome = self.execute_agent_step(step) => outcome,
+            _ = cancel.cancelled() => {
+                checkpoint.stop(format!("interrupted at step {}", step_num));
+                self.save_run_checkpoint(Some(checkpoint));
+                println!(
+                    "\n{}",
+                    format!("Interrupted - `bro --resume` continues from step {}.", step_num)
+                        .yellow()
+                );
+                return None;
+            }
+        };
+        if outcome.is_ok() {
+            checkpoint.complete_step(&step.id);
+            self.save_run_checkpoint(Some(checkpoint));
+        }
+        Some(outcome)
+    }
+
+    /// Checkpoint of running `plan` for `task`, saved before its first step
+    fn start_run_checkpoint(&self, task: &str, plan: &AgentPlan) -> Result<RunCheckpoint> {
+        let checkpoint =
+            RunCheckpoint::new(RunKind::Run, task).with_plan(serde_json::to_value(plan)?);
+        self.save_run_checkpoint(Some(&checkpoint));
+        Ok(checkpoint)
+    }
+
+    /// Save the progress of the active session's run; None once it finished
+    fn save_run_checkpoint(&self, checkpoint: Option<&RunCheckpoint>) {
+        if let Some(store) = &self.session_store {
+            if let Err(e) = store.save_run_checkpoint(&self.active_session_name(), checkpoint) {
+                eprintln!("Warning: Failed to save the run progress: {}", e);
+            }
+        }
+    }
+
     async fn execute_agent_step(&self, step: &AgentStep) -> Result<()> {
         let power_config = self.get_power_config();
 
@@ -3704,14 +5572,23 @@ COMMAND:"#,
             }
             // User explicitly confirmed override
         }
+        if !confirm_network_access(&self.config.security.network_security, &step.command)? {
+            return Err(anyhow!(
+                "Command cancelled: network access was not allowed."
+            ));
+        }
+        let session_name = self.active_session_name();
+        let session = self
+            .session_store
+            .as_ref()
+            .map(|store| (store, session_name.as_str()));
+        confirm_agent_step(&self.config, step, session).await?;
 
         // Execute the command
-        let sandbox = Sandbox::new();
-        let output = sandbox
-            .execute_safe("bash", vec!["-c".to_string(), step.command.clone()])
-            .await?;
+        let sandbox = Sandbox::for_security(&self.config.security);
+        let output = sandbox.execute_shell(&step.command).await?;
         if !output.trim().is_empty() {
-            println!("{}", output);
+            self.show_command_output(&step.command, &output);
         }
         Ok(())
     }
@@ -3805,7 +5682,7 @@ COMMAND:"#,
         }
     }
 
-    fn load_cached_explain(&self, prompt: &str) -> Result<Option<String>> {
+    fn load_cached_explain(&self, prompt: &str) -> Result<Option<ExplainCacheEntry>> {
         let cache_path = Self::explain_cache_path();
         if !cache_path.exists() {
             return Ok(None);
@@ -3829,12 +5706,10 @@ COMMAND:"#,
         std::fs::write(&cache_path, serialized)?;
 
         // Find exact match
-        for entry in &cache.entries {
-            if entry.prompt == prompt {
-                return Ok(Some(entry.response.clone()));
-            }
-        }
-        Ok(None)
+        Ok(cache
+            .entries
+            .into_iter()
+            .find(|entry| entry.prompt == prompt))
     }
 
     fn save_cached_explain(&self, prompt: &str, response: &str) -> Result<()> {
@@ -3853,6 +5728,7 @@ COMMAND:"#,
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            generation: self.take_generation(),
         });
 
         if let Some(parent) = cache_path.parent() {
@@ -3865,7 +5741,7 @@ COMMAND:"#,
         Ok(())
     }
 
-    fn load_cached_rag(&self, question: &str) -> Result<Option<String>> {
+    fn load_cached_rag(&self, question: &str) -> Result<Option<RagCacheEntry>> {
         let cache_path = Self::rag_cache_path();
         if !cache_path.exists() {
             return Ok(None);
@@ -3882,12 +5758,10 @@ COMMAND:"#,
         cache.entries.retain(|entry| now - entry.timestamp < 604800);
 
         // Find exact match
-        for entry in &cache.entries {
-            if entry.question == question {
-                return Ok(Some(entry.response.clone()));
-            }
-        }
-        Ok(None)
+        Ok(cache
+            .entries
+            .into_iter()
+            .find(|entry| entry.question == question))
     }
 
     fn save_cached_rag(&self, question: &str, response: &str) -> Result<()> {
@@ -3906,6 +5780,7 @@ COMMAND:"#,
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            generation: self.take_generation(),
         });
 
         if let Some(parent) = cache_path.parent() {
@@ -3915,10 +5790,75 @@ COMMAND:"#,
         let serialized = bincode::serialize(&cache)?;
         std::fs::write(&cache_path, serialized)?;
 
-        Ok(())
+        self.remember_semantic_cache(RAG_SEMANTIC_CACHE, question, response)
+    }
+
+    fn save_cached_command(&self, query: &str, command: &str) -> Result<()> {
+        Self::save_cached(&self.cache_path, query, command, self.take_generation())?;
+        self.remember_semantic_cache(COMMAND_SEMANTIC_CACHE, query, command)
+    }
+
+    /// Print how the reply `client` just produced was generated and keep the
+    /// record for the cache entry it may become
+    async fn note_generation(&self, client: &OllamaClient) {
+        let record = client.generation_record().await;
+        println!("{}", format!("Generated with {}", record).dimmed());
+        if let Ok(mut last) = self.last_generation.lock() {
+            *last = Some(record);
+        }
+    }
+
+    /// The record of the last generation, attached to at most one cache entry
+    fn take_generation(&self) -> Option<GenerationRecord> {
+        self.last_generation.lock().ok()?.take()
+    }
+
+    fn semantic_cache(&self, file_name: &str) -> SemanticCache {
+        SemanticCache::load(
+            platform::app_data_dir().join(file_name),
+            self.get_power_config().performance.semantic_cache_threshold,
+            604800,
+        )
+    }
+
+    /// Find a cached value for a reworded query. The query embedding is kept so a
+    /// fresh answer can be stored without embedding the query twice.
+    async fn lookup_semantic_cache(&self, file_name: &str, query: &str) -> Option<SemanticHit> {
+        let client = OllamaClient::new().ok()?;
+        let embedding = client.generate_embedding(query).await.ok()?;
+        let hit = self.semantic_cache(file_name).lookup(&embedding);
+        if let Ok(mut pending) = self.cache_embedding.lock() {
+            *pending = Some((query.to_string(), embedding));
+        }
+
+        if let Some(hit) = &hit {
+            println!(
+                "{}",
+                format!(
+                    "Similar to cached query '{}' ({:.0}% match)",
+                    hit.query,
+                    hit.similarity * 100.0
+                )
+                .dimmed()
+            );
+        }
+        hit
+    }
+
+    fn remember_semantic_cache(&self, file_name: &str, query: &str, value: &str) -> Result<()> {
+        let embedding = match self.cache_embedding.lock() {
+            Ok(pending) => match pending.as_ref() {
+                Some((embedded_query, embedding)) if embedded_query == query => embedding.clone(),
+                _ => return Ok(()),
+            },
+            Err(_) => return Ok(()),
+        };
+        let mut cache = self.semantic_cache(file_name);
+        cache.insert(query, embedding, value);
+        cache.save()
     }
 
-    fn load_cached(cache_path: &PathBuf, query: &str) -> Result<Option<String>> {
+    fn load_cached(cache_path: &PathBuf, query: &str) -> Result<Option<CommandCacheEntry>> {
         if !cache_path.exists() {
             return Ok(None);
         }
@@ -3934,15 +5874,15 @@ COMMAND:"#,
         cache.entries.retain(|entry| now - entry.timestamp < 604800);
 
         // Find exact match
-        for entry in &cache.entries {
-            if entry.query == query {
-                return Ok(Some(entry.command.clone()));
-            }
-        }
-        Ok(None)
+        Ok(cache.entries.into_iter().find(|entry| entry.query == query))
     }
 
-    fn save_cached(cache_path: &PathBuf, query: &str, command: &str) -> Result<()> {
+    fn save_cached(
+        cache_path: &PathBuf,
+        query: &str,
+        command: &str,
+        generation: Option<GenerationRecord>,
+    ) -> Result<()> {
         let mut cache = if cache_path.exists() {
             let data = std::fs::read(cache_path).unwrap_or_default();
             bincode::deserialize::<CommandCacheFile>(&data).unwrap_or_default()
@@ -3957,6 +5897,7 @@ COMMAND:"#,
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            generation,
         });
 
         if let Some(parent) = cache_path.parent() {
@@ -4083,6 +6024,191 @@ COMMAND:"#,
         Ok(())
     }
 
+    /// Session that token usage is attributed to
+    fn active_session_name(&self) -> String {
+        self.current_session
+            .clone()
+            .unwrap_or_else(|| "main".to_string())
+    }
+
+    /// Record tokens consumed since the last call against the active session
+    fn persist_token_usage(&mut self) {
+        let current = UsageTracker::global().snapshot();
+        let delta = current.since(&self.usage_baseline);
+        self.usage_baseline = current;
+
+        if let Some(store) = &self.session_store {
+            if let Err(e) = store.record_token_usage(&self.active_session_name(), &delta) {
+                eprintln!("Warning: Failed to record token usage: {}", e);
+            }
+        }
+    }
+
+    /// Enforce the configured token budget inside the agent's execution loop
+    fn apply_token_budget(&self, agent_service: &mut AgentService) {
+        let budget = self.config.security.agent_execution.token_budget();
+        if budget.is_unlimited() {
+            return;
+        }
+
+        let (session_tokens, daily_tokens) = match &self.session_store {
+            Some(store) => (
+                store
+                    .load_session(&self.active_session_name())
+                    .ok()
+                    .flatten()
+                    .map(|s| s.metadata.token_usage.total())
+                    .unwrap_or(0),
+                store
+                    .daily_usage(Utc::now().date_naive())
+                    .map(|u| u.total())
+                    .unwrap_or(0),
+            ),
+            None => (0, 0),
+        };
+
+        // Tokens used earlier in this process have not been persisted yet
+        let unpersisted = UsageTracker::global()
+            .snapshot()
+            .since(&self.usage_baseline)
+            .total();
+
+        agent_service.agent_controller =
+            infrastructure::agent_control::AgentController::with_config(
+                self.config.security.clone(),
+            )
+            .with_token_accounting(
+                UsageTracker::global().clone(),
+                session_tokens + unpersisted,
+                daily_tokens + unpersisted,
+            );
+    }
+
+    /// Handle the token usage report
+    fn handle_usage_report(&self) -> Result<()> {
+        let Some(store) = &self.session_store else {
+            println!(
+                "{}",
+                "No project detected - usage tracking requires a project context.".yellow()
+            );
+            return Ok(());
+        };
+
+        let budget = self.config.security.agent_execution.token_budget();
+        cli_usage::display_usage_report(store, &budget, Some(&self.active_session_name()))
+    }
+
+    /// Encrypt or decrypt the project's stored sessions to match the config
+    fn handle_migrate_sessions(&self) -> Result<()> {
+        let Some(store) = &self.session_store else {
+            println!(
+                "{}",
+                "No project detected - session management requires a project context.".yellow()
+            );
+            return Ok(());
+        };
+        let rewritten = store.migrate_encryption()?;
+        println!(
+            "{} {} {} stored value(s)",
+            terminal::icon("✓", "OK").green(),
+            if store.is_encrypted() {
+                "Encrypted"
+            } else {
+                "Decrypted"
+            },
+            rewritten
+        );
+        Ok(())
+    }
+
+    fn handle_fork_session(&self, current: Option<&String>, name: &str) -> Result<()> {
+        let Some(store) = &self.session_store else {
+            println!(
+                "{}",
+                "No project detected - session management requires a project context.".yellow()
+            );
+            return Ok(());
+        };
+        let source = cli_session::get_target_session_to_continue(store, current);
+        cli_session::fork_session(store, &source, name)
+    }
+
+    /// Name and timeline of session `name`, or of the current or last used
+    /// one, keeping the kinds named in `args`; None, after saying why, when
+    /// there is no such session
+    fn session_timeline(
+        &self,
+        name: Option<&String>,
+        args: &[String],
+    ) -> Result<Option<(String, Vec<TimelineEvent>)>> {
+        let Some(store) = &self.session_store else {
+            println!(
+                "{}",
+                "No project detected - session management requires a project context.".yellow()
+            );
+            return Ok(None);
+        };
+        let mut kinds = Vec::new();
+        for arg in args {
+            match TimelineKind::parse(arg) {
+                Some(kind) => kinds.push(kind),
+                None => {
+                    return Err(anyhow!(
+                        "Unknown event kind '{}'; expected query, plan, operation, commit or test",
+                        arg
+                    ))
+                }
+            }
+        }
+        let name = match name {
+            Some(name) => name.clone(),
+            None => {
+                cli_session::get_target_session_to_continue(store, self.current_session.as_ref())
+            }
+        };
+        let Some(session) = store.load_session(&name)? else {
+            println!("{} No session named '{}'.", "Warning:".yellow(), name);
+            return Ok(None);
+        };
+        let events = session
+            .timeline
+            .into_iter()
+            .filter(|event| kinds.is_empty() || kinds.contains(&event.kind))
+            .collect();
+        Ok(Some((name, events)))
+    }
+
+    /// Append `event` to the active session's timeline
+    fn record_timeline(&self, event: TimelineEvent) {
+        if let Some(store) = &self.session_store {
+            if let Err(e) = store.record_timeline(&self.active_session_name(), event) {
+                eprintln!("Warning: Failed to record the session timeline: {}", e);
+            }
+        }
+    }
+
+    fn handle_merge_session(&self, source: &str, into: Option<&str>) -> Result<()> {
+        let Some(store) = &self.session_store else {
+            println!(
+                "{}",
+                "No project detected - session management requires a project context.".yellow()
+            );
+            return Ok(());
+        };
+        cli_session::merge_session(store, source, into)
+    }
+
+    async fn handle_sync(&self, peers: &[String]) -> Result<()> {
+        let Some(store) = &self.session_store else {
+            println!(
+                "{}",
+                "No project detected - session management requires a project context.".yellow()
+            );
+            return Ok(());
+        };
+        cli_sync::run_sync(store, &self.config, peers).await
+    }
+
     /// Handle listing all sessions
     async fn handle_list_sessions(&mut self) -> Result<()> {
         let Some(store) = &self.session_store else {
@@ -4097,7 +6223,16 @@ COMMAND:"#,
         let project_hash = store.project_hash();
 
         println!("{}", "Session Management".bright_cyan().bold());
-        println!("Project: {} (hash: {})", project_root, &project_hash[..8]);
+        println!(
+            "Project: {} (hash: {}{})",
+            project_root,
+            &project_hash[..8],
+            if store.is_encrypted() {
+                ", encrypted"
+            } else {
+                ""
+            }
+        );
         println!();
 
         match store.list_sessions() {
@@ -4166,7 +6301,7 @@ COMMAND:"#,
                     Ok(_) => {
                         println!(
                             "{} Session '{}' deleted successfully.",
-                            "✓".green(),
+                            terminal::icon("✓", "OK").green(),
                             session_name
                         );
                         // Export backup before deletion
@@ -4179,7 +6314,11 @@ COMMAND:"#,
                         }
                     }
                     Err(e) => {
-                        eprintln!("{} Failed to delete session: {}", "✗".red(), e);
+                        eprintln!(
+                            "{} Failed to delete session: {}",
+                            terminal::icon("✗", "X").red(),
+                            e
+                        );
                     }
                 }
             }
@@ -4187,13 +6326,106 @@ COMMAND:"#,
                 println!("{}", "Session deletion cancelled.".yellow());
             }
             Err(e) => {
-                eprintln!("{} Confirmation error: {}", "✗".red(), e);
+                eprintln!(
+                    "{} Confirmation error: {}",
+                    terminal::icon("✗", "X").red(),
+                    e
+                );
             }
         }
 
         Ok(())
     }
 
+    /// Move into the workspace the requested session is pinned to, which
+    /// may be another project when it is continued from elsewhere. A session
+    /// used for the first time is pinned to the current project and branch.
+    fn enter_session_workspace(&mut self, cli: &Cli) -> Result<()> {
+        if cli.session.is_none() && !cli.continue_session {
+            return Ok(());
+        }
+        let mut env = std::collections::BTreeMap::new();
+        for pair in &cli.session_env {
+            let (key, value) = pair
+                .split_once('=')
+                .filter(|(key, _)| !key.is_empty())
+                .ok_or_else(|| anyhow!("--session-env expects KEY=VALUE, got '{}'", pair))?;
+            env.insert(key.to_string(), value.to_string());
+        }
+
+        let mut name = cli.session.clone();
+        if name.is_none() {
+            // Most recent session of this project, as `--continue-session` picks
+            name = self
+                .session_store
+                .as_ref()
+                .and_then(|store| store.list_sessions().ok())
+                .and_then(|sessions| sessions.into_iter().max_by_key(|s| s.last_used))
+                .map(|s| s.name);
+        }
+        let local = match (&self.session_store, &name) {
+            (Some(store), Some(name)) => store.load_session(name)?,
+            _ => None,
+        };
+
+        // Outside any project, continuing picks the most recent pinned session
+        let search_pins = name.is_some() || self.session_store.is_none();
+        let (name, mut workspace) = match local {
+            Some(session) => (session.metadata.name, session.workspace),
+            None if !search_pins => return Ok(()),
+            None => match SessionStore::find_pinned(name.as_deref())? {
+                // A session of another project: switch to its store
+                Some(pin) => {
+                    let store = SessionStore::new(&pin.root.to_string_lossy())?;
+                    let workspace = store
+                        .load_session(&pin.name)?
+                        .and_then(|session| session.workspace);
+                    self.session_store = Some(store);
+                    (pin.name, workspace)
+                }
+                None => match name {
+                    Some(name) => (name, None),
+                    None => return Ok(()),
+                },
+            },
+        };
+
+        let first_use = workspace.is_none();
+        if first_use {
+            let Some(root) = find_project_root() else {
+                return Ok(());
+            };
+            workspace = Some(SessionWorkspace::capture(std::path::Path::new(&root)));
+        }
+        let Some(mut workspace) = workspace else {
+            return Ok(());
+        };
+        let changed_env = env.iter().any(|(k, v)| workspace.env.get(k) != Some(v));
+        workspace.env.extend(env);
+
+        let previous_dir = std::env::current_dir().ok();
+        if let Some(warning) = workspace.enter()? {
+            eprintln!("{} {}", "Warning:".yellow(), warning);
+        }
+        if previous_dir.as_deref() != Some(workspace.root.as_path()) {
+            // Index and cache locations follow the project
+            self.config = Config::load();
+            println!(
+                "{} Session '{}' runs in {}",
+                "📌".blue(),
+                name.bright_green(),
+                workspace.root.display()
+            );
+        }
+        if let Some(store) = &self.session_store {
+            if first_use || changed_env || cli.continue_session {
+                store.pin_workspace(&name, workspace)?;
+            }
+        }
+        self.current_session = Some(name);
+        Ok(())
+    }
+
     /// Handle continuing a session
     async fn handle_continue_session(&mut self) -> Result<()> {
         let Some(store) = &self.session_store else {
@@ -4253,17 +6485,25 @@ COMMAND:"#,
                         self.current_session = Some(target_session.clone());
                         println!(
                             "{} Created and activated session '{}'",
-                            "✓".green(),
+                            terminal::icon("✓", "OK").green(),
                             target_session.bright_green()
                         );
                     }
                     Err(e) => {
-                        eprintln!("{} Failed to create session: {}", "✗".red(), e);
+                        eprintln!(
+                            "{} Failed to create session: {}",
+                            terminal::icon("✗", "X").red(),
+                            e
+                        );
                     }
                 }
             }
             Err(e) => {
-                eprintln!("{} Failed to load session: {}", "✗".red(), e);
+                eprintln!(
+                    "{} Failed to load session: {}",
+                    terminal::icon("✗", "X").red(),
+                    e
+                );
             }
         }
 
@@ -4278,76 +6518,6 @@ COMMAND:"#,
         }
     }
 
-    /// Handle background events and display them in the UI
-    async fn handle_background_events(event_receiver: Receiver<BackgroundEvent>) {
-        while let Ok(event) = event_receiver.recv_async().await {
-            match event {
-                BackgroundEvent::FileChanged { path, change_type } => {
-                    let (change_icon, change_str) = match change_type {
-                        FileChangeType::Created => ("🆕", "created"),
-                        FileChangeType::Modified => ("✏️", "modified"),
-                        FileChangeType::Deleted => ("🗑️", "deleted"),
-                        FileChangeType::Renamed => ("📝", "renamed"),
-                    };
-                    println!("{} {} {}", change_icon, change_str, path.display());
-                }
-                BackgroundEvent::TestResult {
-                    session,
-                    status,
-                    output,
-                } => {
-                    let (status_icon, _status_str) = match status {
-                        TestStatus::Started => ("▶️", "started"),
-                        TestStatus::Passed => ("✅", "passed"),
-                        TestStatus::Failed { .. } => ("❌", "failed"),
-                        TestStatus::Completed => ("🏁", "completed"),
-                    };
-                    println!(
-                        "{} Test {}: {}",
-                        status_icon,
-                        session,
-                        output.lines().next().unwrap_or("")
-                    );
-                }
-                BackgroundEvent::LogEntry {
-                    source,
-                    level,
-                    message,
-                } => {
-                    let (level_icon, level_str) = match level {
-                        LogLevel::Debug => ("🐛", "debug"),
-                        LogLevel::Info => ("ℹ️", "info"),
-                        LogLevel::Warn => ("⚠️", "warn"),
-                        LogLevel::Error => ("🚨", "error"),
-                    };
-                    println!("{} [{}] {}: {}", level_icon, source, level_str, message);
-                }
-                BackgroundEvent::LspDiagnostic {
-                    file,
-                    severity,
-                    message,
-                } => {
-                    let severity_icon = match severity {
-                        DiagnosticSeverity::Error => "🚨",
-                        DiagnosticSeverity::Warning => "⚠️",
-                        DiagnosticSeverity::Information => "ℹ️",
-                        DiagnosticSeverity::Hint => "💡",
-                    };
-                    println!("{} {}: {}", severity_icon, file.display(), message);
-                }
-                BackgroundEvent::GitStatus { status } => match status {
-                    GitStatusType::Clean => println!("{} Repository is clean", "✅".green()),
-                    GitStatusType::Dirty { modified_files } => {
-                        println!("{} {} modified files", "📝".yellow(), modified_files.len());
-                    }
-                    GitStatusType::Untracked { files } => {
-                        println!("{} {} untracked files", "📄".yellow(), files.len());
-                    }
-                },
-            }
-        }
-    }
-
     /// Handle test execution with real-time monitoring
     async fn handle_test_run(&mut self) -> Result<()> {
         println!("🧪 Running tests with real-time monitoring...");
@@ -4391,7 +6561,10 @@ COMMAND:"#,
         let _session_status = if self.session_store.is_some() {
             format!("{} Session persistence active", "✅".green())
         } else {
-            format!("{} Session store unavailable", "❌".red())
+            format!(
+                "{} Session store unavailable",
+                terminal::icon("❌", "X").red()
+            )
         };
 
         // Check background services
@@ -4410,66 +6583,87 @@ COMMAND:"#,
                     "Stopped" => icon.yellow(),
                     _ => icon.red(),
                 };
-                println!("  └─ {} {}: {}", color, service_name, status);
+                println!(
+                    "  {} {} {}: {}",
+                    terminal::tree_branch(true),
+                    color,
+                    service_name,
+                    status
+                );
             }
         } else {
-            println!("  └─ {} Background services unavailable", "❌".red());
+            println!(
+                "  {} {} Background services unavailable",
+                terminal::tree_branch(true),
+                terminal::icon("❌", "X").red()
+            );
         }
     }
 
-    /// Handle undo command
-    async fn handle_undo(&mut self) -> Result<()> {
-        let Some(session_name) = &self.current_session.clone() else {
-            println!(
-                "{}",
-                "No active session. Use --session to specify a session first.".yellow()
-            );
+    /// Handle undo command: revert the last `steps` changes from the undo
+    /// journal, or the last agent commit when nothing was journaled
+    async fn handle_undo(&mut self, steps: usize) -> Result<()> {
+        let journal = UndoJournal::for_current_project();
+        if !journal.entries()?.is_empty() {
+            let undone = journal.undo(steps.max(1))?;
+            for entry in &undone {
+                println!(
+                    "{} Undid {} ({} file(s), {})",
+                    terminal::icon("✓", "OK").green(),
+                    entry.description,
+                    entry.files.len(),
+                    entry
+                        .timestamp
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                );
+            }
+            if undone.len() < steps {
+                println!(
+                    "{}",
+                    format!("Only {} change(s) were left to undo", undone.len()).yellow()
+                );
+            }
+            self.note_undone(undone.len() as u32);
             return Ok(());
-        };
+        }
 
-        // Try git undo first (preferred)
+        // Changes applied before the journal existed can still be undone via git
         let repo_path = std::env::current_dir()?;
         if repo_path.join(".git").exists() {
             match self.git_undo_last_commit().await {
                 Ok(true) => {
-                    println!("{} Undid last commit via git", "✓".green());
-
-                    // Update session metadata - borrow store separately to avoid conflict
-                    if let Some(store) = &self.session_store {
-                        if let Ok(mut session) = store.load_session(session_name) {
-                            if let Some(ref mut session) = session {
-                                session.metadata.change_count =
-                                    session.metadata.change_count.saturating_sub(1);
-                                if let Err(e) = store.save_session(session) {
-                                    eprintln!(
-                                        "{} {}",
-                                        "Warning: Failed to update session:".yellow(),
-                                        e
-                                    );
-                                }
-                            }
-                        }
-                    }
+                    println!(
+                        "{} Undid last commit via git",
+                        terminal::icon("✓", "OK").green()
+                    );
+                    self.note_undone(1);
                     return Ok(());
                 }
-                Ok(false) => {
-                    // Git undo not available, fall through to manual undo
-                }
+                Ok(false) => {}
                 Err(e) => {
                     eprintln!("{} {}", "Warning: Git undo failed:".yellow(), e);
-                    // Fall through to manual undo
                 }
             }
         }
 
-        println!("[UNDO] Git undo completed - changes reverted");
-        println!(
-            "{}",
-            "Tip: Use 'git reset --hard HEAD~1' for manual git rollback".bright_black()
-        );
+        println!("{}", "Nothing to undo.".yellow());
         Ok(())
     }
 
+    /// Take undone changes off the active session's change count
+    fn note_undone(&self, count: u32) {
+        let (Some(store), Some(session_name)) = (&self.session_store, &self.current_session) else {
+            return;
+        };
+        if let Ok(Some(mut session)) = store.load_session(session_name) {
+            session.metadata.change_count = session.metadata.change_count.saturating_sub(count);
+            if let Err(e) = store.save_session(&session) {
+                eprintln!("{} {}", "Warning: Failed to update session:".yellow(), e);
+            }
+        }
+    }
+
     /// Attempt to undo the last git commit
     async fn git_undo_last_commit(&mut self) -> Result<bool> {
         let repo_path = std::env::current_dir()?;
@@ -4592,13 +6786,9 @@ COMMAND:"#,
                     Ok(result) if result.success => {
                         println!("");
                         println!("🤖 AI Response:");
-                        println!(
-                            "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
-                        );
+                        println!("{}", terminal::rule(66));
                         println!("{}", result.response);
-                        println!(
-                            "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"
-                        );
+                        println!("{}", terminal::rule(66));
                         println!("");
                         println!("✅ Query completed successfully (zero-cost AI workflow)");
                     }
@@ -4636,7 +6826,7 @@ COMMAND:"#,
         println!("Say 'stop' to exit voice mode");
         println!("");
 
-        let mut voice_handler = cli_voice::VoiceHandler::new().await?;
+        let mut voice_handler = cli_voice::VoiceHandler::new(&self.config.power_user.audio).await?;
         voice_handler.start_voice_mode().await
     }
 
@@ -4731,10 +6921,11 @@ mod tests {
             }],
             description: "test plan".to_string(),
             estimated_risk: application::build_service::RiskLevel::Low,
+            groups: Vec::new(),
         };
 
         let ok = app
-            .apply_operations_interactively(&mut plan, &mut build_service)
+            .apply_operations_interactively(&mut plan, &mut build_service, None)
             .unwrap();
         assert!(ok);
         build_service
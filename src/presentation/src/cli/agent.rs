@@ -2,7 +2,8 @@
 
 use super::super::analysis::assess_agent_command_risk;
 use super::super::types::{AgentCommandRisk, AgentPlan};
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use infrastructure::structured_output;
 
 /// Analyze agent task and generate execution plan
 pub async fn analyze_agent_task(task: &str) -> Result<AgentPlan> {
@@ -23,7 +24,8 @@ pub async fn analyze_agent_task(task: &str) -> Result<AgentPlan> {
         .unwrap_or_else(|| String::new());
 
     // Use AI to generate detailed execution plan
-    let client = infrastructure::ollama_client::OllamaClient::new()?;
+    let client = infrastructure::ollama_client::OllamaClient::new()?
+        .with_mode(infrastructure::sampling::GenerationMode::Agent);
 
     let prompt = format!(
         r#"Analyze this task and create a detailed execution plan with individual steps.
@@ -65,21 +67,11 @@ OUTPUT ONLY VALID JSON:"#,
         task, current_dir, ls_output
     );
 
-    let response = client.generate_response(&prompt).await?;
-
-    // Extract JSON from the response (AI might include extra text)
-    let plan: AgentPlan = if let Some(json_start) = response.find('{') {
-        let json_str = &response[json_start..];
-        if let Some(json_end) = json_str.rfind('}') {
-            let json_content = &json_str[..=json_end];
-            serde_json::from_str(json_content)
-                .map_err(|e| anyhow!("Failed to parse agent plan JSON: {}", e))?
-        } else {
-            return Err(anyhow!("No closing brace found in agent plan response"));
-        }
-    } else {
-        return Err(anyhow!("No JSON found in agent plan response"));
-    };
+    // Constrained to the plan schema, with repair prompts for invalid replies
+    let generation = client.generation_record().await;
+    let engine = infrastructure::InferenceEngine::Ollama(client);
+    let plan: AgentPlan = structured_output::generate(&engine, &prompt).await?;
+    println!("Plan generated with {}", generation);
 
     // Enhance plan with additional analysis
     let enhanced_plan = enhance_agent_plan(plan, task);
@@ -0,0 +1,123 @@
+//! Prompt archive for `bro --archive`
+//!
+//! `bro --archive` (or `show [N]`) lists the last N archived generations,
+//! `verify` checks the hash chain and `export <FILE> [SINCE]` writes records
+//! for an auditor, all of them or those since an RFC 3339 time or date.
+//! Records past the retention window are dropped whenever bro starts.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use colored::Colorize;
+use infrastructure::privacy_controls::ArchiveSettings;
+use infrastructure::prompt_archive::ArchiveRecord;
+use shared::terminal;
+use shared::types::Result;
+use std::path::Path;
+
+/// Records listed when no count is given
+const DEFAULT_SHOWN: usize = 20;
+
+/// Characters of prompt and response shown per record
+const PREVIEW_CHARS: usize = 120;
+
+pub fn run_archive(settings: &ArchiveSettings, args: &[String]) -> Result<()> {
+    let Some(archive) = settings.open()? else {
+        return Err(anyhow::anyhow!(
+            "The prompt archive is off; set prompt_archive.enabled in the power user config"
+        ));
+    };
+    let path = archive.path().display();
+    match args.first().map(String::as_str) {
+        None | Some("show") => {
+            let count = match args.get(1) {
+                Some(n) => n
+                    .parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Usage: bro --archive show [N]"))?,
+                None => DEFAULT_SHOWN,
+            };
+            let records = archive.records();
+            if records.is_empty() {
+                println!("No prompts archived in {}.", path);
+                return Ok(());
+            }
+            for record in &records[records.len().saturating_sub(count)..] {
+                display(record);
+            }
+            Ok(())
+        }
+        Some("verify") => match archive.verify()? {
+            Ok(count) => {
+                println!(
+                    "{} {} records, chain intact ({})",
+                    terminal::icon("✓", "OK").green(),
+                    count,
+                    path
+                );
+                Ok(())
+            }
+            Err(broken) => Err(anyhow::anyhow!(
+                "The prompt archive {} has been tampered with at {}",
+                path,
+                broken
+            )),
+        },
+        Some("export") => {
+            let usage = || anyhow::anyhow!("Usage: bro --archive export <FILE> [SINCE]");
+            let out = args.get(1).ok_or_else(usage)?;
+            let since = args
+                .get(2)
+                .map(|since| parse_since(since).ok_or_else(usage))
+                .transpose()?;
+            let count = archive.export(Path::new(out), since)?;
+            println!(
+                "{} Exported {} records to {}",
+                terminal::icon("✓", "OK").green(),
+                count,
+                out
+            );
+            Ok(())
+        }
+        Some(other) => Err(anyhow::anyhow!(
+            "Unknown archive action '{}'; use show [N], verify or export <FILE> [SINCE]",
+            other
+        )),
+    }
+}
+
+/// An RFC 3339 time, or a date meaning its midnight UTC
+fn parse_since(since: &str) -> Option<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(since) {
+        return Some(at.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(since, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+fn display(record: &ArchiveRecord) {
+    let redacted = if record.redacted.is_empty() {
+        String::new()
+    } else {
+        format!("[{} redacted]", record.redacted.len())
+    };
+    println!(
+        "{} {} {} {} {}",
+        format!("{:>5}", record.seq).bright_cyan(),
+        record.timestamp.dimmed(),
+        record.session.as_deref().unwrap_or("-").dimmed(),
+        record.model,
+        redacted.yellow()
+    );
+    println!("      > {}", preview(&record.prompt));
+    println!(
+        "      {}",
+        format!("< {}", preview(&record.response)).dimmed()
+    );
+}
+
+fn preview(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() > PREVIEW_CHARS {
+        format!("{}…", line.chars().take(PREVIEW_CHARS).collect::<String>())
+    } else {
+        line
+    }
+}
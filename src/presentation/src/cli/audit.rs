@@ -0,0 +1,75 @@
+//! Command audit log for `bro --audit`
+//!
+//! `bro --audit` (or `show [N]`) lists the last N commands bro ran, with the
+//! request behind each; `bro --audit verify` checks the log's hash chain.
+
+use colored::Colorize;
+use infrastructure::command_audit::{self, AuditEntry};
+use shared::terminal;
+use shared::types::Result;
+
+/// Entries listed when no count is given
+const DEFAULT_SHOWN: usize = 20;
+
+pub fn run_audit(args: &[String]) -> Result<()> {
+    let path = command_audit::log_path();
+    match args.first().map(String::as_str) {
+        None | Some("show") => {
+            let count = match args.get(1) {
+                Some(n) => n
+                    .parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Usage: bro --audit show [N]"))?,
+                None => DEFAULT_SHOWN,
+            };
+            let entries = command_audit::entries(&path);
+            if entries.is_empty() {
+                println!("No commands recorded in {}.", path.display());
+                return Ok(());
+            }
+            for entry in &entries[entries.len().saturating_sub(count)..] {
+                display(entry);
+            }
+            Ok(())
+        }
+        Some("verify") => match command_audit::verify(&path)? {
+            Ok(count) => {
+                println!(
+                    "{} {} entries, chain intact ({})",
+                    terminal::icon("✓", "OK").green(),
+                    count,
+                    path.display()
+                );
+                Ok(())
+            }
+            Err(broken) => Err(anyhow::anyhow!(
+                "The audit log {} has been tampered with at {}",
+                path.display(),
+                broken
+            )),
+        },
+        Some(other) => Err(anyhow::anyhow!(
+            "Unknown audit action '{}'; use show [N] or verify",
+            other
+        )),
+    }
+}
+
+fn display(entry: &AuditEntry) {
+    let status = match entry.exit_status {
+        Some(0) => "exit 0".green(),
+        Some(code) => format!("exit {}", code).red(),
+        None => "no status".yellow(),
+    };
+    println!(
+        "{} {} {} {} {}",
+        format!("{:>5}", entry.seq).bright_cyan(),
+        entry.timestamp.dimmed(),
+        entry.session.as_deref().unwrap_or("-").dimmed(),
+        status,
+        format!("[{}]", entry.via).dimmed()
+    );
+    println!("      {}", entry.command);
+    if let Some(prompt) = &entry.prompt {
+        println!("      {}", format!("for: {}", prompt).dimmed());
+    }
+}
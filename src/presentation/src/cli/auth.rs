@@ -0,0 +1,109 @@
+//! Credential management for `bro --auth`
+//!
+//! `bro --auth` lists the credentials bro reads and whether each is set.
+//! `set <NAME>` stores one read from a hidden prompt or stdin, `get <NAME>`
+//! prints it for scripts, `delete <NAME>` removes it and `token` stores a new
+//! random web server token and prints it once.
+
+use colored::Colorize;
+use infrastructure::credentials::{self, CredentialSettings, CredentialStore, KNOWN_CREDENTIALS};
+use shared::terminal;
+use shared::types::Result;
+use std::io::{BufRead, IsTerminal};
+
+pub async fn run_auth(args: &[String], settings: &CredentialSettings) -> Result<()> {
+    let store = CredentialStore::new(settings.clone());
+    let name = || {
+        args.get(1).map(String::as_str).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Name the credential, e.g. `bro --auth {} web-token`",
+                args[0]
+            )
+        })
+    };
+
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            println!("Credentials in the {}:", store.settings().describe());
+            for (name, purpose) in KNOWN_CREDENTIALS {
+                let state = if std::env::var(credentials::env_var(name)).is_ok() {
+                    format!("set by {}", credentials::env_var(name)).cyan()
+                } else {
+                    match store.get(name).await {
+                        Ok(Some(_)) => "set".green(),
+                        Ok(None) => "not set".dimmed(),
+                        Err(e) => e.to_string().red(),
+                    }
+                };
+                println!("  {:<20} {} ({})", name, state, purpose.dimmed());
+            }
+            Ok(())
+        }
+        Some("set") => {
+            let name = name()?;
+            credentials::validate_name(name)?;
+            let value = read_secret(name)?;
+            if value.is_empty() {
+                return Err(anyhow::anyhow!("No value given for {}", name));
+            }
+            store.set(name, &value).await?;
+            println!(
+                "{} Stored {} in the {}",
+                terminal::icon("✓", "OK").green(),
+                name,
+                store.settings().describe()
+            );
+            Ok(())
+        }
+        Some("get") => {
+            let name = name()?;
+            let value = store
+                .get(name)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("{} is not set", name))?;
+            println!("{}", value);
+            Ok(())
+        }
+        Some("delete") => {
+            let name = name()?;
+            if store.delete(name).await? {
+                println!("{} Deleted {}", terminal::icon("✓", "OK").green(), name);
+            } else {
+                println!("{} was not stored", name);
+            }
+            Ok(())
+        }
+        Some("token") => {
+            let token = format!(
+                "{}{}",
+                uuid::Uuid::new_v4().simple(),
+                uuid::Uuid::new_v4().simple()
+            );
+            store.set(credentials::WEB_TOKEN, &token).await?;
+            println!(
+                "{} New web server token stored; clients send it as `Authorization: Bearer <token>`:",
+                terminal::icon("✓", "OK").green()
+            );
+            println!("{}", token);
+            Ok(())
+        }
+        Some(other) => Err(anyhow::anyhow!(
+            "Unknown auth action '{}'; use list, set, get, delete or token",
+            other
+        )),
+    }
+}
+
+/// Read the value from a hidden prompt, or the first line of stdin when
+/// piped, so it never lands in the shell history
+fn read_secret(name: &str) -> Result<String> {
+    if std::io::stdin().is_terminal() {
+        let value = dialoguer::Password::new()
+            .with_prompt(format!("Value for {}", name))
+            .interact()?;
+        return Ok(value.trim().to_string());
+    }
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
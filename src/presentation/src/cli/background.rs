@@ -1,82 +1,151 @@
 //! Background event handling and monitoring
 
+use application::rag_service::ChangedPaths;
 use colored::Colorize;
 use flume::Receiver;
 use infrastructure::background_supervisor::{
     BackgroundEvent, DiagnosticSeverity, FileChangeType, GitStatus, LogLevel, TestStatus,
 };
+use infrastructure::config::EventStreamConfig;
+use infrastructure::event_stream::{EventBatch, EventThrottle};
+use shared::terminal;
+use std::sync::Arc;
+use std::time::Instant;
 
-/// Handle background events from the supervisor
-pub async fn handle_events(event_receiver: Receiver<BackgroundEvent>) {
-    while let Ok(event) = event_receiver.recv_async().await {
-        match event {
-            BackgroundEvent::FileChanged { path, change_type } => {
-                let (change_icon, change_str) = match change_type {
-                    FileChangeType::Created => ("New", "created"),
-                    FileChangeType::Modified => ("Edit", "modified"),
-                    FileChangeType::Deleted => ("Del", "deleted"),
-                    FileChangeType::Renamed => ("Ren", "renamed"),
-                };
-                println!("{} {} {}", change_icon, change_str, path.display());
+/// Changed files named in a file-storm summary
+const STORM_SAMPLE: usize = 3;
+
+/// Handle background events from the supervisor, printing them in throttled
+/// batches. Every changed path is still forwarded to `index_changes`.
+pub async fn handle_events(
+    event_receiver: Receiver<BackgroundEvent>,
+    index_changes: Arc<ChangedPaths>,
+    config: EventStreamConfig,
+) {
+    let mut throttle = EventThrottle::new(config);
+    let mut ticker = tokio::time::interval(throttle.batch_window());
+    loop {
+        tokio::select! {
+            event = event_receiver.recv_async() => {
+                let Ok(event) = event else { break };
+                if let BackgroundEvent::FileChanged { path, .. } = &event {
+                    // Held until a RAG index is loaded and takes them
+                    index_changes.push(path.clone());
+                }
+                throttle.push(event, Instant::now());
             }
-            BackgroundEvent::TestResult {
-                session,
-                status,
-                output,
-            } => {
-                let (status_icon, _status_str) = match status {
-                    TestStatus::Started => ("Run", "started"),
-                    TestStatus::Passed => ("Pass", "passed"),
-                    TestStatus::Failed { .. } => ("Fail", "failed"),
-                    TestStatus::Completed => ("Done", "completed"),
-                };
-                println!(
-                    "{} Test {}: {}",
-                    status_icon,
-                    session,
-                    output.lines().next().unwrap_or("")
-                );
+            _ = ticker.tick() => {
+                print_batch(throttle.flush(), throttle.file_storm_threshold());
             }
-            BackgroundEvent::LogEntry {
-                source,
-                level,
-                message,
-            } => {
-                let (level_icon, level_str) = match level {
-                    LogLevel::Debug => ("Debug", "debug"),
-                    LogLevel::Info => ("Info", "info"),
-                    LogLevel::Warn => ("Warn", "warn"),
-                    LogLevel::Error => ("Error", "error"),
-                };
-                println!("{} [{}] {}: {}", level_icon, source, level_str, message);
+        }
+    }
+    index_changes.close();
+    print_batch(throttle.flush(), throttle.file_storm_threshold());
+}
+
+fn print_batch(batch: EventBatch, storm_threshold: usize) {
+    if batch.file_changes.len() > storm_threshold {
+        let sample: Vec<String> = batch
+            .file_changes
+            .iter()
+            .take(STORM_SAMPLE)
+            .map(|(path, _)| path.display().to_string())
+            .collect();
+        println!(
+            "{} {} files changed ({}, ...)",
+            terminal::icon("📝", "Edit"),
+            batch.file_changes.len(),
+            sample.join(", ")
+        );
+    } else {
+        for (path, change_type) in &batch.file_changes {
+            let (change_icon, change_str) = match change_type {
+                FileChangeType::Created => (terminal::icon("🆕", "New"), "created"),
+                FileChangeType::Modified => (terminal::icon("✏️", "Edit"), "modified"),
+                FileChangeType::Deleted => (terminal::icon("🗑️", "Del"), "deleted"),
+                FileChangeType::Renamed => (terminal::icon("📝", "Ren"), "renamed"),
+            };
+            println!("{} {} {}", change_icon, change_str, path.display());
+        }
+    }
+
+    for (event, count) in &batch.events {
+        let line = format_event(event);
+        if *count > 1 {
+            println!("{} {}", line, format!("(x{})", count).dimmed());
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    for (source, count) in &batch.suppressed {
+        println!(
+            "{}",
+            format!("... {} more event(s) from {} suppressed", count, source).dimmed()
+        );
+    }
+}
+
+fn format_event(event: &BackgroundEvent) -> String {
+    match event {
+        BackgroundEvent::FileChanged { path, .. } => format!("changed {}", path.display()),
+        BackgroundEvent::TestResult {
+            session,
+            status,
+            output,
+        } => {
+            let status_icon = match status {
+                TestStatus::Started => terminal::icon("▶️", "Run"),
+                TestStatus::Passed => terminal::icon("✅", "Pass"),
+                TestStatus::Failed { .. } => terminal::icon("❌", "Fail"),
+                TestStatus::Completed => terminal::icon("🏁", "Done"),
+            };
+            format!(
+                "{} Test {}: {}",
+                status_icon,
+                session,
+                output.lines().next().unwrap_or("")
+            )
+        }
+        BackgroundEvent::LogEntry {
+            source,
+            level,
+            message,
+        } => {
+            let (level_icon, level_str) = match level {
+                LogLevel::Debug => (terminal::icon("🐛", "Debug"), "debug"),
+                LogLevel::Info => (terminal::icon("ℹ️", "Info"), "info"),
+                LogLevel::Warn => (terminal::icon("⚠️", "Warn"), "warn"),
+                LogLevel::Error => (terminal::icon("🚨", "Error"), "error"),
+            };
+            format!("{} [{}] {}: {}", level_icon, source, level_str, message)
+        }
+        BackgroundEvent::LspDiagnostic {
+            file,
+            severity,
+            message,
+        } => {
+            let severity_icon = match severity {
+                DiagnosticSeverity::Error => terminal::icon("🚨", "Error"),
+                DiagnosticSeverity::Warning => terminal::icon("⚠️", "Warn"),
+                DiagnosticSeverity::Information => terminal::icon("ℹ️", "Info"),
+                DiagnosticSeverity::Hint => terminal::icon("💡", "Hint"),
+            };
+            format!("{} {}: {}", severity_icon, file.display(), message)
+        }
+        BackgroundEvent::GitStatus { status } => match status {
+            GitStatus::Clean => format!("{} Repository is clean", "Clean".green()),
+            GitStatus::Dirty { modified_files } => {
+                format!(
+                    "{} {} modified files",
+                    "Dirty".yellow(),
+                    modified_files.len()
+                )
             }
-            BackgroundEvent::LspDiagnostic {
-                file,
-                severity,
-                message,
-            } => {
-                let severity_icon = match severity {
-                    DiagnosticSeverity::Error => "Error",
-                    DiagnosticSeverity::Warning => "Warn",
-                    DiagnosticSeverity::Information => "Info",
-                    DiagnosticSeverity::Hint => "Hint",
-                };
-                println!("{} {}: {}", severity_icon, file.display(), message);
+            GitStatus::Untracked { files } => {
+                format!("{} {} untracked files", "Untracked".yellow(), files.len())
             }
-            BackgroundEvent::GitStatus { status } => match status {
-                GitStatus::Clean => println!("{} Repository is clean", "Clean".green()),
-                GitStatus::Dirty { modified_files } => {
-                    println!(
-                        "{} {} modified files",
-                        "Dirty".yellow(),
-                        modified_files.len()
-                    );
-                }
-                GitStatus::Untracked { files } => {
-                    println!("{} {} untracked files", "Untracked".yellow(), files.len());
-                }
-            },
-        }
+        },
     }
 }
 
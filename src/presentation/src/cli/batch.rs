@@ -0,0 +1,121 @@
+//! Non-interactive runs over a file of jobs (`bro --batch <file.jsonl>`)
+//!
+//! Useful for bulk explain and review jobs across many files: nothing asks
+//! for confirmation, cached answers are not offered, and every result lands
+//! in a JSONL file next to the input unless `--batch-output` says otherwise.
+
+use application::prompts;
+use colored::Colorize;
+use infrastructure::batch_jobs::{self, BatchJob, BatchMode};
+use infrastructure::config::Config;
+use infrastructure::ollama_client::OllamaClient;
+use infrastructure::sampling::GenerationMode;
+use shared::pii_detector::PiiDetector;
+use shared::terminal;
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+
+/// Run the jobs in `path` with `jobs` at a time and write the results to
+/// `output`. Fails when any job failed, so it can gate CI.
+pub async fn run_batch(
+    path: &str,
+    output: Option<&str>,
+    jobs: usize,
+    config: &Config,
+) -> Result<()> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Cannot read batch file {}: {}", path, e))?;
+    let batch = batch_jobs::parse_jobs(&text)?;
+    if batch.is_empty() {
+        println!("No jobs in {}.", path);
+        return Ok(());
+    }
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| results_path(Path::new(path)));
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&output)?);
+
+    let client = OllamaClient::new()?.with_mode(GenerationMode::Chat);
+    let pii = config.pii_detector();
+    let total = batch.len();
+    eprintln!("Running {} job(s), {} at a time...", total, jobs.max(1));
+    let mut done = 0usize;
+    let summary = batch_jobs::run(
+        batch,
+        jobs,
+        &mut out,
+        |job| answer(&client, pii.as_ref(), job),
+        |result| {
+            done += 1;
+            let status = if result.ok {
+                terminal::icon("✓", "OK").green()
+            } else {
+                terminal::icon("✗", "X").red()
+            };
+            eprintln!(
+                "[{}/{}] {} {} ({:.1}s){}",
+                done,
+                total,
+                status,
+                result.id,
+                result.elapsed_ms as f64 / 1000.0,
+                result
+                    .error
+                    .as_ref()
+                    .map(|e| format!(": {}", e))
+                    .unwrap_or_default()
+            );
+        },
+    )
+    .await?;
+
+    println!(
+        "{} succeeded, {} failed; results in {}",
+        summary.succeeded,
+        summary.failed,
+        output.display()
+    );
+    if summary.failed > 0 {
+        anyhow::bail!("{} of {} batch jobs failed", summary.failed, total);
+    }
+    Ok(())
+}
+
+/// `notes.jsonl` -> `notes.results.jsonl`
+fn results_path(input: &Path) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "batch".to_string());
+    input.with_file_name(format!("{}.results.jsonl", stem))
+}
+
+async fn answer(client: &OllamaClient, pii: Option<&PiiDetector>, job: BatchJob) -> Result<String> {
+    let prompt = match (&job.mode, &job.file) {
+        (BatchMode::Query, _) => job.input.clone(),
+        (mode, Some(file)) => {
+            let content = std::fs::read_to_string(file)
+                .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", file.display(), e))?;
+            // Personal data stays on this machine unless the model does too
+            let content = match pii {
+                Some(pii) => pii.apply(&content)?,
+                None => content,
+            };
+            match mode {
+                BatchMode::Review => {
+                    prompts::review_prompt(&file.to_string_lossy(), &content, &job.input)
+                }
+                _ if job.input.trim().is_empty() => {
+                    format!("Explain this content in detail:\n\n{}", content)
+                }
+                _ => format!(
+                    "Explain this content in detail, focusing on: {}\n\n{}",
+                    job.input.trim(),
+                    content
+                ),
+            }
+        }
+        (mode, None) => anyhow::bail!("{:?} needs a file", mode),
+    };
+    client.generate_response(&prompt).await
+}
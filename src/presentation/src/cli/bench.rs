@@ -0,0 +1,426 @@
+//! Load generator for the web server (`bro --bench-server`)
+//!
+//! Workers hit the chat, RAG, dictation and config endpoints concurrently, the
+//! way a phone and a CLI session do at the same time, and report latency
+//! percentiles and error rates per endpoint. Config reads go through the
+//! `AppState` lock, so contention there shows up as tail latency. Requests
+//! carry the `web-token` credential when one is set, as the server requires.
+
+use colored::Colorize;
+use infrastructure::credentials::{CredentialSettings, CredentialStore, WEB_TOKEN};
+use serde_json::{json, Value};
+use shared::terminal;
+use shared::types::Result;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Address the mobile web interface listens on
+pub const DEFAULT_SERVER_URL: &str = "http://localhost:8080";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Scenario {
+    Chat,
+    Rag,
+    Dictation,
+    Config,
+}
+
+impl Scenario {
+    const ALL: [Scenario; 4] = [
+        Scenario::Chat,
+        Scenario::Rag,
+        Scenario::Dictation,
+        Scenario::Config,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Scenario::Chat => "chat (POST /voice/process)",
+            Scenario::Rag => "rag (POST /rag/query)",
+            Scenario::Dictation => "dictation (start/insert/stop)",
+            Scenario::Config => "config (GET /config)",
+        }
+    }
+
+    /// Endless scenario mix for one worker. Workers start at different
+    /// scenarios so every endpoint is under load at once.
+    fn mix(worker: usize) -> impl Iterator<Item = Scenario> {
+        Scenario::ALL
+            .into_iter()
+            .cycle()
+            .skip(worker % Scenario::ALL.len())
+    }
+}
+
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: usize,
+    last_error: Option<String>,
+}
+
+impl Samples {
+    fn record(&mut self, elapsed: Duration, outcome: Result<()>) {
+        self.latencies.push(elapsed);
+        if let Err(e) = outcome {
+            self.errors += 1;
+            self.last_error = Some(e.to_string());
+        }
+    }
+
+    fn summary(&self) -> Summary {
+        let mut latencies = self.latencies.clone();
+        latencies.sort();
+        Summary {
+            requests: latencies.len(),
+            errors: self.errors,
+            p50: percentile(&latencies, 50.0),
+            p90: percentile(&latencies, 90.0),
+            p99: percentile(&latencies, 99.0),
+            max: latencies.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// One report row
+#[derive(Debug, PartialEq)]
+struct Summary {
+    requests: usize,
+    errors: usize,
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+impl Summary {
+    fn error_rate(&self) -> f64 {
+        self.errors as f64 / self.requests.max(1) as f64 * 100.0
+    }
+}
+
+/// Run `concurrency` workers against `base_url` for `duration` and print a report.
+/// Fails if any request failed, so it can gate CI.
+pub async fn run_bench(
+    base_url: &str,
+    concurrency: usize,
+    duration: Duration,
+    credentials: &CredentialSettings,
+) -> Result<()> {
+    let base_url = base_url.trim_end_matches('/').to_string();
+    let token = CredentialStore::new(credentials.clone())
+        .get(WEB_TOKEN)
+        .await?;
+    let client = bench_client(token.as_deref())?;
+
+    // Fail fast rather than reporting a wall of connection errors
+    client
+        .get(format!("{}/api/health", base_url))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| anyhow::anyhow!("Server at {} is not reachable: {}", base_url, e))?;
+
+    println!(
+        "{} {} workers against {} for {}s",
+        "bench-server:".bright_cyan().bold(),
+        concurrency,
+        base_url,
+        duration.as_secs()
+    );
+
+    let samples: Arc<Mutex<BTreeMap<Scenario, Samples>>> = Arc::default();
+    let deadline = Instant::now() + duration;
+    let started = Instant::now();
+
+    let workers: Vec<_> = (0..concurrency.max(1))
+        .map(|worker| {
+            let client = client.clone();
+            let base_url = base_url.clone();
+            let samples = Arc::clone(&samples);
+            tokio::spawn(async move {
+                for scenario in Scenario::mix(worker) {
+                    if Instant::now() >= deadline {
+                        break;
+                    }
+                    let start = Instant::now();
+                    let outcome = run_scenario(&client, &base_url, scenario).await;
+                    let elapsed = start.elapsed();
+
+                    let mut samples = samples.lock().unwrap();
+                    samples
+                        .entry(scenario)
+                        .or_default()
+                        .record(elapsed, outcome);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.await?;
+    }
+    let elapsed = started.elapsed();
+
+    let samples = std::mem::take(&mut *samples.lock().unwrap());
+    let total_errors = print_report(&samples, elapsed);
+    if total_errors > 0 {
+        return Err(anyhow::anyhow!("{} request(s) failed", total_errors));
+    }
+    Ok(())
+}
+
+/// HTTP client sending `token` as a bearer token with every request
+fn bench_client(token: Option<&str>) -> Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = token {
+        let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))?;
+        value.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+    Ok(reqwest::Client::builder()
+        .timeout(Duration::from_secs(120))
+        .default_headers(headers)
+        .build()?)
+}
+
+async fn run_scenario(client: &reqwest::Client, base_url: &str, scenario: Scenario) -> Result<()> {
+    let api = |path: &str| format!("{}/api{}", base_url, path);
+    match scenario {
+        Scenario::Chat => {
+            let body = post_json(
+                client,
+                &api("/voice/process"),
+                json!({ "text": "what time is it", "confidence": 0.9 }),
+            )
+            .await?;
+            if body["status"] == "error" {
+                return Err(anyhow::anyhow!(
+                    "{}",
+                    body["error"].as_str().unwrap_or("chat failed")
+                ));
+            }
+        }
+        Scenario::Rag => {
+            let mut response = client
+                .post(api("/rag/query"))
+                .header("content-type", "application/json")
+                .body(json!({ "question": "How is the web server started?" }).to_string())
+                .send()
+                .await?
+                .error_for_status()?;
+            // The answer arrives as Server-Sent Events; read until the final event
+            let mut stream = String::new();
+            while let Some(chunk) = response.chunk().await? {
+                stream.push_str(&String::from_utf8_lossy(&chunk));
+            }
+            if stream.contains("event: error") || !stream.contains("event: done") {
+                return Err(anyhow::anyhow!("RAG stream ended without an answer"));
+            }
+        }
+        Scenario::Dictation => {
+            // `type` and `backspace` drive the real keyboard, so they are left out
+            post_json(
+                client,
+                &api("/dictation/start"),
+                json!({ "inputType": "text", "url": "bench" }),
+            )
+            .await?;
+            post_json(
+                client,
+                &api("/dictation/insert"),
+                json!({ "text": "load test dictation", "inputType": "text" }),
+            )
+            .await?;
+            post_json(client, &api("/dictation/stop"), json!({})).await?;
+        }
+        Scenario::Config => {
+            client
+                .get(api("/config"))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+    }
+    Ok(())
+}
+
+async fn post_json(client: &reqwest::Client, url: &str, body: Value) -> Result<Value> {
+    let response = client
+        .post(url)
+        .header("content-type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+    let bytes = response.bytes().await?;
+    Ok(serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+/// Print one row per scenario and return the total number of errors
+fn print_report(samples: &BTreeMap<Scenario, Samples>, elapsed: Duration) -> usize {
+    println!();
+    println!(
+        "{:<32} {:>7} {:>7} {:>9} {:>9} {:>9} {:>9}",
+        "endpoint", "reqs", "errors", "p50 ms", "p90 ms", "p99 ms", "max ms"
+    );
+    println!("{}", terminal::rule(88));
+
+    let mut total_requests = 0;
+    let mut total_errors = 0;
+    for (scenario, s) in samples {
+        let summary = s.summary();
+        total_requests += summary.requests;
+        total_errors += summary.errors;
+
+        println!("{}", report_row(*scenario, &summary));
+        if let Some(error) = &s.last_error {
+            println!("  {} {}", "last error:".dimmed(), terminal::wrap(error, 4));
+        }
+    }
+
+    println!("{}", terminal::rule(88));
+    println!(
+        "{} requests in {:.1}s ({:.1} req/s), {} failed",
+        total_requests,
+        elapsed.as_secs_f64(),
+        total_requests as f64 / elapsed.as_secs_f64().max(0.001),
+        total_errors
+    );
+    total_errors
+}
+
+fn report_row(scenario: Scenario, summary: &Summary) -> String {
+    // Pad before colouring so escape codes don't break the alignment
+    let errors = format!("{:>7}", format!("{:.1}%", summary.error_rate()));
+    format!(
+        "{:<32} {:>7} {} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+        scenario.label(),
+        summary.requests,
+        if summary.errors > 0 {
+            errors.red().to_string()
+        } else {
+            errors
+        },
+        millis(summary.p50),
+        millis(summary.p90),
+        millis(summary.p99),
+        millis(summary.max),
+    )
+}
+
+/// Nearest-rank percentile of sorted samples
+fn percentile(sorted: &[Duration], pct: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_workers_start_on_different_scenarios_and_cycle_through_all() {
+        let first: Vec<Scenario> = (0..4)
+            .map(|worker| Scenario::mix(worker).next().unwrap())
+            .collect();
+        assert_eq!(first, Scenario::ALL);
+
+        let mut round: Vec<Scenario> = Scenario::mix(6).take(4).collect();
+        assert_eq!(round[0], Scenario::Dictation);
+        round.sort();
+        assert_eq!(round, Scenario::ALL);
+    }
+
+    #[test]
+    fn test_summary_aggregates_latencies_and_errors() {
+        let mut samples = Samples::default();
+        for millis in (1..=10).rev() {
+            samples.record(ms(millis * 10), Ok(()));
+        }
+        samples.record(ms(5), Err(anyhow::anyhow!("first")));
+        samples.record(ms(200), Err(anyhow::anyhow!("503 Service Unavailable")));
+
+        let summary = samples.summary();
+        assert_eq!(
+            summary,
+            Summary {
+                requests: 12,
+                errors: 2,
+                p50: ms(50),
+                p90: ms(100),
+                p99: ms(200),
+                max: ms(200),
+            }
+        );
+        assert_eq!(
+            samples.last_error.as_deref(),
+            Some("503 Service Unavailable")
+        );
+    }
+
+    #[test]
+    fn test_empty_samples_report_zeros() {
+        let summary = Samples::default().summary();
+        assert_eq!(summary.requests, 0);
+        assert_eq!(summary.max, Duration::ZERO);
+        assert_eq!(summary.error_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_report_row_columns() {
+        let summary = Summary {
+            requests: 40,
+            errors: 0,
+            p50: ms(12),
+            p90: Duration::from_micros(30_400),
+            p99: ms(90),
+            max: ms(1500),
+        };
+        assert_eq!(
+            report_row(Scenario::Config, &summary),
+            format!(
+                "{:<32} {:>7} {:>7} {:>9} {:>9} {:>9} {:>9}",
+                "config (GET /config)", "40", "0.0%", "12.0", "30.4", "90.0", "1500.0"
+            )
+        );
+
+        let failing = Summary {
+            errors: 10,
+            ..summary
+        };
+        assert!(report_row(Scenario::Config, &failing).contains("25.0%"));
+    }
+
+    #[tokio::test]
+    async fn test_scenarios_pass_the_token_check() {
+        use crate::web::{routes::create_router, state::AppState};
+        use infrastructure::config::Config;
+
+        let mut state = AppState::minimal(Config::load());
+        state.auth_token = Some(std::sync::Arc::new("secret".to_string()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, create_router(state)).await });
+
+        let anonymous = bench_client(None).unwrap();
+        assert!(run_scenario(&anonymous, &base_url, Scenario::Config)
+            .await
+            .is_err());
+        let authorized = bench_client(Some("secret")).unwrap();
+        run_scenario(&authorized, &base_url, Scenario::Config)
+            .await
+            .unwrap();
+    }
+}
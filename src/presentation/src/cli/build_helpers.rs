@@ -1,6 +1,15 @@
 //! Build display and formatting helpers
 
+use application::build_service::{BuildPlan, DriftChoice, DriftResolver, FileOperation};
 use colored::Colorize;
+use infrastructure::format_hooks::LintFailed;
+use infrastructure::test_gate::{TestCommand, TestGateFailed};
+use infrastructure::three_way_merge::MergeOutcome;
+use shared::confirmation::scripted_answer;
+use shared::terminal;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::Arc;
 
 /// Create logical chunks from file lines for display
 pub fn create_file_chunks(lines: &[&str]) -> Vec<(usize, usize, &'static str)> {
@@ -147,25 +156,6 @@ pub fn display_code_with_syntax(lines: &[&str], start_line: usize, ext: &str) {
     }
 }
 
-/// Strip code fences from code blocks
-pub fn strip_code_fences(code: &str) -> String {
-    let lines: Vec<&str> = code.lines().collect();
-    let mut result = Vec::new();
-    let mut in_fence = false;
-
-    for line in lines {
-        if line.trim().starts_with("```") {
-            in_fence = !in_fence;
-            continue;
-        }
-        if !in_fence || !line.trim().starts_with("```") {
-            result.push(line);
-        }
-    }
-
-    result.join("\n")
-}
-
 /// Display chain of thought in tree format
 pub fn display_chain_of_thought(reasoning: &str) {
     println!("\nChain of Thought:");
@@ -178,12 +168,12 @@ pub fn display_chain_of_thought(reasoning: &str) {
         .collect();
 
     for (i, line) in lines.iter().enumerate() {
-        let marker = if i == lines.len() - 1 {
-            "└─"
-        } else {
-            "├─"
-        };
-        println!("  {} {}", marker, line.trim().bright_white());
+        let marker = terminal::tree_branch(i == lines.len() - 1);
+        println!(
+            "  {} {}",
+            marker,
+            terminal::text(line.trim()).bright_white()
+        );
     }
 }
 
@@ -205,6 +195,155 @@ pub fn display_progress(current: usize, total: usize, description: &str, session
 
     println!(
         "{}{} [{}/{}] {}",
-        session_prefix, status, current, total, description
+        session_prefix,
+        status,
+        current,
+        total,
+        terminal::wrap(&terminal::text(description), 4)
+    );
+}
+
+/// Pull request description for a build branch: the goal and each step of
+/// the plan, with paths shown relative to `root`
+pub fn pull_request_body(plan: &BuildPlan, root: &Path) -> String {
+    let mut body = format!("## Goal\n\n{}\n", plan.goal);
+    if !plan.description.trim().is_empty() && plan.description.trim() != plan.goal.trim() {
+        body.push_str(&format!("\n{}\n", plan.description.trim()));
+    }
+    body.push_str("\n## Plan\n\n");
+    for (i, operation) in plan.operations.iter().enumerate() {
+        let (verb, path) = match operation {
+            FileOperation::Create { path, .. } => ("Create", path),
+            FileOperation::Update { path, .. } => ("Update", path),
+            FileOperation::Delete { path } => ("Delete", path),
+            FileOperation::Patch { path, .. } => ("Patch", path),
+            FileOperation::Read { path } => ("Read", path),
+            FileOperation::AddDependency { manifest, .. } => ("Add dependency", manifest),
+            FileOperation::RemoveDependency { manifest, .. } => ("Remove dependency", manifest),
+        };
+        let path = path.strip_prefix(root).unwrap_or(path);
+        match operation {
+            FileOperation::AddDependency { name, .. }
+            | FileOperation::RemoveDependency { name, .. } => body.push_str(&format!(
+                "{}. {} `{}` in `{}`\n",
+                i + 1,
+                verb,
+                name,
+                path.display()
+            )),
+            _ => body.push_str(&format!("{}. {} `{}`\n", i + 1, verb, path.display())),
+        }
+    }
+    body.push_str(&format!(
+        "\nRisk: {:?}. Generated with `bro --build --branch --pr`.\n",
+        plan.estimated_risk
+    ));
+    body
+}
+
+/// Asks on the terminal how to settle an update that conflicts with edits
+/// made on disk since planning; None when there is no terminal to ask on
+pub fn drift_resolver() -> Option<DriftResolver> {
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    Some(Arc::new(|path: &Path, merged: &MergeOutcome| {
+        println!(
+            "{} {} changed on disk since planning; {} change(s) conflict with the update:",
+            terminal::icon("⚠️", "Warn").yellow(),
+            path.display(),
+            merged.conflicts
+        );
+        let mut in_conflict = false;
+        for line in merged.text.lines() {
+            in_conflict |= line.starts_with("<<<<<<< ");
+            if in_conflict {
+                println!("  {}", line.yellow());
+            }
+            in_conflict &= !line.starts_with(">>>>>>> ");
+        }
+        loop {
+            let prompt = "Write [m]arkers, keep [p]lan, keep [d]isk or [a]bort?";
+            print!("{} ", prompt);
+            let _ = std::io::stdout().flush();
+            let answer = match scripted_answer(prompt) {
+                Some(answer) => answer.unwrap_or_default(),
+                None => {
+                    let mut line = String::new();
+                    if std::io::stdin().read_line(&mut line).is_err() {
+                        return DriftChoice::Abort;
+                    }
+                    line
+                }
+            };
+            match answer.trim().to_lowercase().as_str() {
+                "m" | "markers" => return DriftChoice::Markers,
+                "p" | "plan" => return DriftChoice::KeepPlan,
+                "d" | "disk" => return DriftChoice::KeepDisk,
+                "" | "a" | "abort" => return DriftChoice::Abort,
+                _ => println!("Answer m, p, d or a"),
+            }
+        }
+    }))
+}
+
+/// Test command for `--gate tests`, if the project has one whose tests pass
+/// before the build starts; a gate on failing tests would reject everything
+pub async fn test_gate(project_root: &Path) -> Option<TestCommand> {
+    let Some(command) = TestCommand::detect(project_root) else {
+        println!(
+            "{} --gate tests: no test command found (cargo, npm or pytest); the gate is off",
+            "[GATE]".yellow()
+        );
+        return None;
+    };
+    println!(
+        "{} Running {} before the build...",
+        "[GATE]".cyan(),
+        command
+    );
+    match command.run(project_root).await {
+        Ok(run) if run.passed => {
+            println!(
+                "{} Tests pass ({:.1}s); each operation must keep them passing",
+                "[GATE]".green(),
+                run.elapsed.as_secs_f64()
+            );
+            Some(command)
+        }
+        Ok(run) => {
+            println!(
+                "{} `{}` already fails; the gate is off:\n{}",
+                "[GATE]".yellow(),
+                command,
+                run.output.dimmed()
+            );
+            None
+        }
+        Err(e) => {
+            println!("{} {}; the gate is off", "[GATE]".yellow(), e);
+            None
+        }
+    }
+}
+
+/// Show why the gate rolled an operation back
+pub fn print_gate_failure(failure: &TestGateFailed) {
+    println!(
+        "{} {} ({:.1}s):",
+        "[GATE]".red(),
+        failure,
+        failure.run.elapsed.as_secs_f64()
     );
+    for line in failure.run.output.lines() {
+        println!("  {}", line.dimmed());
+    }
+}
+
+/// Show why a formatter or linter rolled an operation back
+pub fn print_lint_failure(failure: &LintFailed) {
+    println!("{} {}:", "[LINT]".red(), failure);
+    for line in failure.output.lines() {
+        println!("  {}", line.dimmed());
+    }
 }
@@ -6,6 +6,7 @@
 //! - CommandCache: Caches command suggestions
 
 use anyhow::Result;
+use infrastructure::sampling::GenerationRecord;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -21,6 +22,8 @@ pub struct ExplainCacheEntry {
     pub prompt: String,
     pub response: String,
     pub timestamp: u64,
+    /// Model and seed that produced the response
+    pub generation: Option<GenerationRecord>,
 }
 
 /// Cache file structure for RAG operations
@@ -35,6 +38,7 @@ pub struct RagCacheEntry {
     pub question: String,
     pub response: String,
     pub timestamp: u64,
+    pub generation: Option<GenerationRecord>,
 }
 
 /// Cache file structure for command operations
@@ -49,6 +53,7 @@ pub struct CommandCacheEntry {
     pub query: String,
     pub command: String,
     pub timestamp: u64,
+    pub generation: Option<GenerationRecord>,
 }
 
 /// Load cached command from file
@@ -77,7 +82,12 @@ pub fn load_cached_command(cache_path: &PathBuf, query: &str) -> Result<Option<S
 }
 
 /// Save command to cache file
-pub fn save_cached_command(cache_path: &PathBuf, query: &str, command: &str) -> Result<()> {
+pub fn save_cached_command(
+    cache_path: &PathBuf,
+    query: &str,
+    command: &str,
+    generation: Option<GenerationRecord>,
+) -> Result<()> {
     let mut cache = if cache_path.exists() {
         let data = std::fs::read(cache_path).unwrap_or_default();
         bincode::deserialize::<CommandCacheFile>(&data).unwrap_or_default()
@@ -92,6 +102,7 @@ pub fn save_cached_command(cache_path: &PathBuf, query: &str, command: &str) ->
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs(),
+        generation,
     });
 
     if let Some(parent) = cache_path.parent() {
@@ -0,0 +1,106 @@
+//! Named build checkpoints at the build-mode prompts
+//!
+//! `/checkpoint <name>` saves the plan and the files it touches,
+//! `/restore <name>` puts both back, and either command without a name lists
+//! the checkpoints of the project.
+
+use crate::utils::find_project_root;
+use application::build_service::BuildPlan;
+use application::transaction::{Checkpoint, Checkpoints};
+use colored::Colorize;
+use shared::terminal;
+use std::path::PathBuf;
+
+pub enum CheckpointCommand<'a> {
+    Save(&'a str),
+    Restore(&'a str),
+    List,
+}
+
+/// Recognise `/checkpoint [name]` and `/restore [name]`
+pub fn parse(input: &str) -> Option<CheckpointCommand<'_>> {
+    let mut words = input.split_whitespace();
+    let command = words.next()?;
+    let name = words.next();
+    match (command, name) {
+        ("/checkpoint" | "/cp", Some(name)) => Some(CheckpointCommand::Save(name)),
+        ("/restore", Some(name)) => Some(CheckpointCommand::Restore(name)),
+        ("/checkpoint" | "/cp" | "/restore", None) => Some(CheckpointCommand::List),
+        _ => None,
+    }
+}
+
+fn store() -> Checkpoints {
+    let root = find_project_root()
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+    Checkpoints::for_project(&root)
+}
+
+/// `plan` without the steps skipped during review, and where review resumes in it
+pub fn reviewed_plan(plan: &BuildPlan, skipped: &[bool], next_step: usize) -> (BuildPlan, usize) {
+    let kept = |end: usize| (0..end).filter(|&i| !skipped.get(i).copied().unwrap_or(false));
+    let mut reviewed = plan.clone();
+    reviewed.operations = kept(plan.operations.len())
+        .map(|i| plan.operations[i].clone())
+        .collect();
+    (reviewed, kept(next_step.min(plan.operations.len())).count())
+}
+
+pub fn save(name: &str, plan: &BuildPlan, next_step: usize) {
+    match store().save(name, plan, next_step) {
+        Ok(checkpoint) => println!(
+            "{} [CHECKPOINT] '{}' saved: {} file(s), {} of {} step(s) reviewed",
+            terminal::icon("✓", "OK").green(),
+            checkpoint.name,
+            checkpoint.paths().count(),
+            checkpoint.next_step,
+            checkpoint.plan.operations.len()
+        ),
+        Err(e) => println!("[ERROR] Checkpoint failed: {}", e),
+    }
+}
+
+/// Put the files of a checkpoint back; the caller resumes its plan
+pub fn restore(name: &str) -> Option<Checkpoint> {
+    match store().restore(name) {
+        Ok(checkpoint) => {
+            println!(
+                "{} [RESTORE] '{}' restored: {} file(s), {} of {} step(s) reviewed",
+                terminal::icon("✓", "OK").green(),
+                checkpoint.name,
+                checkpoint.paths().count(),
+                checkpoint.next_step,
+                checkpoint.plan.operations.len()
+            );
+            Some(checkpoint)
+        }
+        Err(e) => {
+            println!("[ERROR] Restore failed: {}", e);
+            None
+        }
+    }
+}
+
+pub fn list() {
+    let store = store();
+    match store.list() {
+        Ok(checkpoints) if checkpoints.is_empty() => {
+            println!("[CHECKPOINT] None yet. Save one with /checkpoint <name>");
+        }
+        Ok(checkpoints) => {
+            println!("[CHECKPOINT] In {}:", store.dir().display());
+            for checkpoint in checkpoints {
+                println!(
+                    "  {:<20} {}  {} ({} file(s))",
+                    checkpoint.name.bright_cyan(),
+                    checkpoint.created_at.format("%Y-%m-%d %H:%M"),
+                    checkpoint.plan.goal,
+                    checkpoint.paths().count()
+                );
+            }
+        }
+        Err(e) => println!("[ERROR] Could not list checkpoints: {}", e),
+    }
+}
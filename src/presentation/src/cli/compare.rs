@@ -0,0 +1,119 @@
+//! `bro --compare "prompt" --models a,b` in the terminal
+//!
+//! Models answer one after another, streaming as they go, so each run has
+//! the machine to itself and the timings compare fairly. A table at the end
+//! lines up latency and throughput.
+
+use colored::Colorize;
+use infrastructure::model_comparison::{self, ModelRun};
+use infrastructure::ollama_client::OllamaClient;
+use shared::cancellation::{cancel_on_interrupt, CancellationToken};
+use shared::terminal;
+use shared::types::Result;
+use std::io::Write;
+
+/// Reject a comparison that has nothing to compare
+pub fn check_request(prompt: &str, models: &[String]) -> Result<()> {
+    if prompt.trim().is_empty() {
+        return Err(anyhow::anyhow!(
+            "--compare needs a prompt, e.g. bro --compare \"explain lifetimes\" --models llama3,qwen2.5"
+        ));
+    }
+    if models.len() < 2 {
+        return Err(anyhow::anyhow!(
+            "--compare needs at least two models, e.g. --models llama3,qwen2.5"
+        ));
+    }
+    Ok(())
+}
+
+pub async fn run_compare(prompt: &str, models: &[String]) -> Result<()> {
+    let client = OllamaClient::new()?;
+    if let Ok(installed) = client.installed_models().await {
+        for model in models {
+            let tagged = format!("{}:latest", model);
+            if !installed.iter().any(|m| m == model || *m == tagged) {
+                println!(
+                    "{} {} is not installed; run: ollama pull {}",
+                    terminal::icon("⚠", "!").yellow(),
+                    model,
+                    model
+                );
+            }
+        }
+    }
+
+    let cancel = CancellationToken::new();
+    let _interrupt = cancel_on_interrupt(&cancel);
+    let mut runs = Vec::new();
+    for (i, model) in models.iter().enumerate() {
+        println!(
+            "\n{}",
+            format!("── {} ({}/{}) ──", model, i + 1, models.len())
+                .bright_cyan()
+                .bold()
+        );
+        let run = model_comparison::run_model(&client, model, prompt, &cancel, |chunk| {
+            print!("{}", chunk);
+            let _ = std::io::stdout().flush();
+        })
+        .await;
+        println!();
+        match &run.error {
+            Some(e) => println!("{} {}", terminal::icon("✗", "X").red(), e),
+            None => println!("{}", run.stats().dimmed()),
+        }
+        runs.push(run);
+        if cancel.is_cancelled() {
+            println!("{}", "Comparison interrupted".yellow());
+            break;
+        }
+    }
+
+    print_summary(&runs);
+    Ok(())
+}
+
+fn print_summary(runs: &[ModelRun]) {
+    println!("\n{}", "Summary".bright_cyan().bold());
+    println!(
+        "  {:<24} {:>12} {:>9} {:>8} {:>9}",
+        "model", "first token", "total", "tokens", "tok/s"
+    );
+    for run in runs {
+        if let Some(e) = &run.error {
+            println!("  {:<24} {}", run.model, format!("failed: {}", e).red());
+            continue;
+        }
+        let first = run
+            .first_token
+            .map(|first| format!("{:.1}s", first.as_secs_f64()))
+            .unwrap_or_else(|| "-".to_string());
+        let rate = run
+            .tokens_per_second()
+            .map(|rate| format!("{:.1}", rate))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "  {:<24} {:>12} {:>8.1}s {:>8} {:>9}",
+            run.model,
+            first,
+            run.elapsed.as_secs_f64(),
+            run.usage.completion_tokens,
+            rate
+        );
+    }
+
+    let fastest = runs
+        .iter()
+        .filter(|run| run.error.is_none())
+        .filter_map(|run| Some((run, run.tokens_per_second()?)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((run, rate)) = fastest {
+        println!(
+            "\n{} Fastest here: {} at {:.1} tok/s",
+            terminal::icon("✓", "OK").green(),
+            run.model.bold(),
+            rate
+        );
+    }
+}
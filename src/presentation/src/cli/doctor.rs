@@ -0,0 +1,51 @@
+//! Environment health checks for `bro --doctor`
+
+use colored::Colorize;
+use infrastructure::adapters::speech_providers::SpeechProviderRegistry;
+use infrastructure::config::Config;
+use shared::terminal;
+use shared::types::Result;
+
+/// Check every configured audio provider, failing if any is unusable
+pub async fn run_doctor(config: &Config) -> Result<()> {
+    println!("{}", "bro doctor".bright_cyan().bold());
+    println!();
+
+    let registry = SpeechProviderRegistry::with_builtin();
+    let audio = &config.power_user.audio;
+    println!(
+        "Audio providers (speech: {}; tts: {})",
+        registry.stt_providers().join(", "),
+        registry.tts_providers().join(", ")
+    );
+
+    let report = registry.check_health(audio).await;
+    for health in &report {
+        let status = if health.healthy {
+            terminal::icon("✓", "OK").green()
+        } else {
+            terminal::icon("✗", "X").red()
+        };
+        println!(
+            "  {} {} [{}]: {}",
+            status,
+            health.kind.label(),
+            health.provider,
+            terminal::wrap(&health.message, 6)
+        );
+    }
+
+    let failures = report.iter().filter(|health| !health.healthy).count();
+    println!();
+    if failures == 0 {
+        println!("{}", "All checks passed.".green());
+        Ok(())
+    } else {
+        println!(
+            "{}",
+            "Set providers with VIBE_STT_PROVIDER / VIBE_TTS_PROVIDER or the `audio` config section."
+                .dimmed()
+        );
+        Err(anyhow::anyhow!("{} health check(s) failed", failures))
+    }
+}
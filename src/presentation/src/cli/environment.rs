@@ -0,0 +1,89 @@
+//! Build environments for `bro --environments`
+//!
+//! `bro --environments` lists the environment snapshots taken at the start
+//! of the session's build runs, `bro --environments show [N]` prints one
+//! (the latest by default), and `bro --environments diff [A] [B]` shows what
+//! changed between two runs, the last two by default.
+
+use colored::Colorize;
+use infrastructure::environment_snapshot::EnvironmentSnapshot;
+use infrastructure::session_store::SessionStore;
+use shared::types::Result;
+
+pub fn run_environments(args: &[String], store: &SessionStore, session: &str) -> Result<()> {
+    let snapshots = store
+        .load_session(session)?
+        .map(|session| session.environment_snapshots)
+        .unwrap_or_default();
+    if snapshots.is_empty() {
+        println!("No build runs recorded in session '{}' yet.", session);
+        return Ok(());
+    }
+    let pick = |arg: Option<&String>, default: usize| -> Result<&EnvironmentSnapshot> {
+        let number = match arg {
+            Some(arg) => arg
+                .parse::<usize>()
+                .map_err(|_| anyhow::anyhow!("Expected a run number, got '{}'", arg))?,
+            None => default,
+        };
+        number
+            .checked_sub(1)
+            .and_then(|index| snapshots.get(index))
+            .ok_or_else(|| anyhow::anyhow!("No run {}; there are {}", number, snapshots.len()))
+    };
+
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            for (index, snapshot) in snapshots.iter().enumerate() {
+                println!(
+                    "{:>3}  {}  {}",
+                    index + 1,
+                    snapshot.taken_at.format("%Y-%m-%d %H:%M"),
+                    snapshot.goal.dimmed()
+                );
+            }
+            Ok(())
+        }
+        Some("show") => {
+            let snapshot = pick(args.get(1), snapshots.len())?;
+            println!(
+                "{} bro {} on {} {}",
+                snapshot.taken_at.format("%Y-%m-%d %H:%M:%S"),
+                snapshot.bro_version,
+                snapshot.os,
+                snapshot.arch
+            );
+            println!("Goal: {}", snapshot.goal);
+            for (tool, version) in &snapshot.tools {
+                let version = version.as_deref().unwrap_or("not installed");
+                println!("  {:<8} {}", tool, version);
+            }
+            for (name, value) in &snapshot.config {
+                println!("  {} = {}", name.dimmed(), value);
+            }
+            Ok(())
+        }
+        Some("diff") => {
+            let latest = snapshots.len();
+            let before = pick(args.get(1), latest.saturating_sub(1).max(1))?;
+            let after = pick(args.get(2), latest)?;
+            let changes = before.diff(after);
+            if changes.is_empty() {
+                println!("No environment changes between the two runs.");
+            }
+            for change in changes {
+                println!(
+                    "{}: {} -> {}",
+                    change.name,
+                    change.before.as_deref().unwrap_or("(none)").red(),
+                    change.after.as_deref().unwrap_or("(none)").green()
+                );
+            }
+            Ok(())
+        }
+        Some(other) => Err(anyhow::anyhow!(
+            "Unknown environments action '{}'; use list, show [N] or diff [A] [B]",
+            other
+        )),
+    }
+}
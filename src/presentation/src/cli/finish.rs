@@ -0,0 +1,83 @@
+//! `/finish` after a build: one commit for the whole session
+//!
+//! The per-operation commits are squashed into one whose message the model
+//! writes from the cumulative diff, falling back to the goal and the files
+//! changed. `/finish push` pushes the branch afterwards.
+
+use application::prompts;
+use colored::Colorize;
+use infrastructure::ollama_client::OllamaClient;
+use infrastructure::sampling::GenerationMode;
+use infrastructure::session_squash::{self, SessionCommits};
+use shared::confirmation::ask_confirmation;
+use shared::output_cleanup::strip_code_fences;
+use shared::types::Result;
+
+/// Recognise `/finish` and `/finish push`; Some(push) when it matches
+pub fn parse(input: &str) -> Option<bool> {
+    let mut words = input.split_whitespace();
+    if words.next()? != "/finish" {
+        return None;
+    }
+    Some(matches!(words.next(), Some("push" | "--push")))
+}
+
+pub async fn finish(session: Option<&SessionCommits>, goal: &str, push: bool) {
+    let Some(session) = session else {
+        println!("[FINISH] No git repository, or it had no commits when the build started");
+        return;
+    };
+    if let Err(e) = squash(session, goal, push).await {
+        println!("{} {}", "[FINISH]".red(), e);
+    }
+}
+
+async fn squash(session: &SessionCommits, goal: &str, push: bool) -> Result<()> {
+    let commits = session.subjects()?.len();
+    if commits == 0 {
+        println!("[FINISH] No commits since the build started");
+    } else {
+        println!("[FINISH] Writing a message for {} commit(s)...", commits);
+        let message = message(session, goal).await;
+        println!("{}", message.dimmed());
+        if !ask_confirmation(
+            &format!("Squash {} commit(s) into one with this message?", commits),
+            true,
+        )? {
+            println!("[FINISH] Left the commits as they are");
+            return Ok(());
+        }
+        let hash = session.squash(&message)?;
+        println!(
+            "[FINISH] Squashed {} commit(s) into {}",
+            commits,
+            &hash[..hash.len().min(8)]
+        );
+    }
+
+    if push {
+        let branch = session.push()?;
+        println!("[FINISH] Pushed {}", branch);
+    } else if commits > 0 {
+        println!("{}", "Push it with: /finish push".dimmed());
+    }
+    Ok(())
+}
+
+/// Commit message from the model, or the goal and diff stat without one
+async fn message(session: &SessionCommits, goal: &str) -> String {
+    let generated = match (session.diff(), OllamaClient::new()) {
+        (Ok(diff), Ok(client)) => client
+            .with_mode(GenerationMode::Chat)
+            .generate_response(&prompts::squash_message_prompt(goal, &diff))
+            .await
+            .ok(),
+        _ => None,
+    };
+    generated
+        .map(|message| strip_code_fences(&message).trim().to_string())
+        .filter(|message| !message.is_empty())
+        .unwrap_or_else(|| {
+            session_squash::fallback_message(goal, &session.diff_stat().unwrap_or_default())
+        })
+}
@@ -0,0 +1,214 @@
+//! Paging for long command output
+//!
+//! Output taller than the terminal is piped to `$PAGER` when set, otherwise to a
+//! small built-in pager with less-style keys and `/` search. Output that fits,
+//! or that isn't going to a terminal, is printed as-is.
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use shared::platform::Shell;
+use std::io::{IsTerminal, Write};
+
+/// Show `text`, paging it if it does not fit on screen
+pub fn page(text: &str) {
+    let text = text.trim_end_matches('\n');
+    let rows = terminal::size()
+        .map(|(_, rows)| rows as usize)
+        .unwrap_or(24);
+    let interactive = std::io::stdout().is_terminal() && std::io::stdin().is_terminal();
+    if !interactive || text.lines().count() < rows {
+        println!("{}", text);
+        return;
+    }
+
+    if let Some(pager) = std::env::var("PAGER").ok().filter(|p| !p.trim().is_empty()) {
+        match run_external(&pager, text) {
+            Ok(()) => return,
+            Err(e) => eprintln!("Failed to run $PAGER ({}): {}", pager, e),
+        }
+    }
+    if let Err(e) = run_internal(text) {
+        let _ = terminal::disable_raw_mode();
+        eprintln!("Pager unavailable ({}), printing output", e);
+        println!("{}", text);
+    }
+}
+
+fn run_external(pager: &str, text: &str) -> std::io::Result<()> {
+    let mut child = Shell::detect()
+        .command(pager)
+        .stdin(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user may quit before reading everything; a broken pipe is fine
+        let _ = stdin.write_all(text.as_bytes());
+        let _ = stdin.write_all(b"\n");
+    }
+    child.wait()?;
+    Ok(())
+}
+
+struct Pager<'a> {
+    lines: Vec<&'a str>,
+    top: usize,
+    height: usize,
+    search: Option<String>,
+    /// Line of the last match, where `n`/`N` continue from
+    last_match: Option<usize>,
+    status: String,
+}
+
+impl Pager<'_> {
+    fn max_top(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    fn scroll(&mut self, delta: isize) {
+        self.top = self.top.saturating_add_signed(delta).min(self.max_top());
+    }
+
+    /// Jump to the next line matching the search, wrapping around
+    fn find(&mut self, forward: bool) {
+        let Some(pattern) = self.search.as_deref().map(str::to_lowercase) else {
+            self.status = "No previous search".to_string();
+            return;
+        };
+        let count = self.lines.len();
+        let from = self.last_match.unwrap_or(self.top);
+        let found = (1..=count)
+            .map(|step| {
+                if forward {
+                    (from + step) % count
+                } else {
+                    (from + count - step % count) % count
+                }
+            })
+            .find(|&i| self.lines[i].to_lowercase().contains(&pattern));
+        match found {
+            Some(i) => {
+                self.last_match = Some(i);
+                self.top = i.min(self.max_top());
+                self.status.clear();
+            }
+            None => self.status = format!("Pattern not found: {}", pattern),
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write) -> std::io::Result<()> {
+        queue!(out, cursor::MoveTo(0, 0), terminal::Clear(ClearType::All))?;
+        let pattern = self.search.as_deref().map(str::to_lowercase);
+        for (row, line) in self.lines[self.top..].iter().take(self.height).enumerate() {
+            queue!(out, cursor::MoveTo(0, row as u16))?;
+            let highlighted = pattern
+                .as_deref()
+                .is_some_and(|p| !p.is_empty() && line.to_lowercase().contains(p));
+            if highlighted {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(out, Print(line), SetAttribute(Attribute::Reset))?;
+        }
+
+        let last = (self.top + self.height).min(self.lines.len());
+        let status = if self.status.is_empty() {
+            format!(
+                "lines {}-{} of {} (space/b page, / search, n/N next/prev, q quit)",
+                self.top + 1,
+                last,
+                self.lines.len()
+            )
+        } else {
+            self.status.clone()
+        };
+        queue!(
+            out,
+            cursor::MoveTo(0, self.height as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(status),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()
+    }
+}
+
+fn run_internal(text: &str) -> std::io::Result<()> {
+    let (_, rows) = terminal::size()?;
+    let mut pager = Pager {
+        lines: text.lines().collect(),
+        top: 0,
+        height: (rows as usize).saturating_sub(1).max(1),
+        search: None,
+        last_match: None,
+        status: String::new(),
+    };
+
+    let mut out = std::io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = event_loop(&mut pager, &mut out);
+    execute!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn event_loop(pager: &mut Pager, out: &mut impl Write) -> std::io::Result<()> {
+    loop {
+        pager.draw(out)?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        let page = pager.height as isize;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Char('j') | KeyCode::Down | KeyCode::Enter => pager.scroll(1),
+            KeyCode::Char('k') | KeyCode::Up => pager.scroll(-1),
+            KeyCode::Char(' ') | KeyCode::Char('f') | KeyCode::PageDown => pager.scroll(page),
+            KeyCode::Char('b') | KeyCode::PageUp => pager.scroll(-page),
+            KeyCode::Char('g') | KeyCode::Home => pager.top = 0,
+            KeyCode::Char('G') | KeyCode::End => pager.top = pager.max_top(),
+            KeyCode::Char('/') => {
+                if let Some(pattern) = read_pattern(pager, out)? {
+                    pager.search = Some(pattern);
+                    // A new search starts from the top of the screen
+                    let count = pager.lines.len();
+                    pager.last_match = Some((pager.top + count - 1) % count);
+                    pager.find(true);
+                }
+            }
+            KeyCode::Char('n') => pager.find(true),
+            KeyCode::Char('N') => pager.find(false),
+            _ => {}
+        }
+    }
+}
+
+/// Read a search pattern on the status line; `None` if cancelled
+fn read_pattern(pager: &mut Pager, out: &mut impl Write) -> std::io::Result<Option<String>> {
+    let mut pattern = String::new();
+    loop {
+        pager.status = format!("/{}", pattern);
+        pager.draw(out)?;
+        if let Event::Key(KeyEvent { code, .. }) = event::read()? {
+            match code {
+                KeyCode::Enter => {
+                    pager.status.clear();
+                    return Ok(Some(pattern).filter(|p| !p.is_empty()));
+                }
+                KeyCode::Esc => {
+                    pager.status.clear();
+                    return Ok(None);
+                }
+                KeyCode::Backspace => {
+                    pattern.pop();
+                }
+                KeyCode::Char(c) => pattern.push(c),
+                _ => {}
+            }
+        }
+    }
+}
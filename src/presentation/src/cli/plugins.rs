@@ -0,0 +1,171 @@
+//! Plugin marketplace commands (`--plugin-search`, `--plugin-install`,
+//! `--plugin-update`)
+//!
+//! Nothing is written to the plugin directory until the user has reviewed the
+//! permissions a plugin asks for; updates only highlight what is new.
+
+use colored::Colorize;
+use infrastructure::config::PluginConfig;
+use infrastructure::plugin_marketplace::{
+    describe_permission, is_newer, IndexEntry, Marketplace, PendingInstall,
+};
+use shared::confirmation::ask_confirmation;
+use shared::terminal;
+use shared::types::Result;
+
+/// List index entries matching `query`, marking installed ones
+pub async fn run_search(config: &PluginConfig, query: &str) -> Result<()> {
+    let marketplace = Marketplace::from_config(config)?;
+    let index = marketplace.fetch_index().await?;
+    let installed = marketplace.installed();
+    let matches = index.search(query);
+    if matches.is_empty() {
+        println!("No plugins matching '{}'", query);
+        return Ok(());
+    }
+
+    for entry in matches {
+        let status = match installed.get(&entry.name) {
+            Some(current) if is_newer(&entry.version, &current.version) => {
+                format!("installed {}, update available", current.version).yellow()
+            }
+            Some(_) => "installed".green(),
+            None => "".normal(),
+        };
+        println!(
+            "{} {} {}",
+            entry.name.bright_cyan().bold(),
+            entry.version,
+            status
+        );
+        println!("    {}", terminal::wrap(&entry.description, 4));
+        if !entry.permissions.is_empty() {
+            println!(
+                "    {} {}",
+                "permissions:".dimmed(),
+                entry.permissions.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Install `name` from the index after the user approves its permissions
+pub async fn run_install(config: &PluginConfig, name: &str) -> Result<()> {
+    let marketplace = Marketplace::from_config(config)?;
+    let index = marketplace.fetch_index().await?;
+    let entry = index
+        .get(name)
+        .ok_or_else(|| anyhow::anyhow!("No plugin named '{}' in the index", name))?;
+    if let Some(current) = marketplace.installed().get(name) {
+        if !is_newer(&entry.version, &current.version) {
+            println!("{} {} is already installed", name, current.version);
+            return Ok(());
+        }
+    }
+    install_reviewed(&marketplace, entry).await
+}
+
+/// Update `name`, or every installed plugin when empty, to the index version
+pub async fn run_update(config: &PluginConfig, name: &str) -> Result<()> {
+    let marketplace = Marketplace::from_config(config)?;
+    let index = marketplace.fetch_index().await?;
+    let installed = marketplace.installed();
+    if installed.is_empty() {
+        println!("No plugins installed from the marketplace");
+        return Ok(());
+    }
+    if !name.is_empty() && !installed.contains_key(name) {
+        return Err(anyhow::anyhow!(
+            "'{}' was not installed from the marketplace; use --plugin-install",
+            name
+        ));
+    }
+
+    for (plugin, current) in installed
+        .iter()
+        .filter(|(plugin, _)| name.is_empty() || plugin.as_str() == name)
+    {
+        let Some(entry) = index.get(plugin) else {
+            println!(
+                "{} {} is no longer in the index",
+                terminal::icon("○", "-"),
+                plugin
+            );
+            continue;
+        };
+        if !is_newer(&entry.version, &current.version) {
+            println!(
+                "{} {} {} is up to date",
+                terminal::icon("✓", "OK"),
+                plugin,
+                current.version
+            );
+            continue;
+        }
+        install_reviewed(&marketplace, entry).await?;
+    }
+    Ok(())
+}
+
+async fn install_reviewed(marketplace: &Marketplace, entry: &IndexEntry) -> Result<()> {
+    println!("Verifying {} {}...", entry.name, entry.version);
+    let pending = marketplace.prepare(entry).await?;
+    if !review(&pending)? {
+        println!("{} not installed", entry.name);
+        return Ok(());
+    }
+    let manifest = marketplace.install(pending)?;
+    println!(
+        "{} Installed {} {} ({})",
+        terminal::icon("✓", "OK").green(),
+        entry.name,
+        entry.version,
+        manifest.display()
+    );
+    Ok(())
+}
+
+/// Show what the plugin may do and ask before enabling it
+fn review(pending: &PendingInstall) -> Result<bool> {
+    let entry = &pending.entry;
+    println!();
+    match &pending.previous {
+        Some(previous) => println!(
+            "{} {} → {}",
+            entry.name.bright_cyan().bold(),
+            previous.version,
+            entry.version
+        ),
+        None => println!("{} {}", entry.name.bright_cyan().bold(), entry.version),
+    }
+    if !entry.author.is_empty() {
+        println!("  by {}", entry.author);
+    }
+    println!("  {}", terminal::wrap(&entry.description, 2));
+    println!("  {}", "checksums verified, index signature valid".dimmed());
+
+    let new_permissions = pending.new_permissions();
+    if entry.permissions.is_empty() {
+        println!("  Requests no permissions");
+    } else {
+        println!("  May:");
+        for permission in &entry.permissions {
+            let line = format!("{} ({})", describe_permission(permission), permission);
+            if pending.previous.is_some() && new_permissions.contains(&permission.as_str()) {
+                println!("    {} {}", "NEW".yellow().bold(), line.yellow());
+            } else {
+                println!("    - {}", line);
+            }
+        }
+    }
+    println!();
+
+    // Updates that ask for nothing new only need a plain confirmation
+    let prompt = if pending.previous.is_some() && new_permissions.is_empty() {
+        format!("Update {}?", entry.name)
+    } else {
+        format!("Enable {} with these permissions?", entry.name)
+    };
+    ask_confirmation(&prompt, false)
+}
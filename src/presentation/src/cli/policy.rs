@@ -0,0 +1,98 @@
+//! Policy rules for `bro --policy`
+//!
+//! `bro --policy` lists the rules in force and the team bundle and files
+//! they come from,
+//! `bro --policy check` only reports whether they parse, and
+//! `bro --policy explain <COMMAND>` shows how the rules decide on a command
+//! run now in the current session.
+
+use crate::utils::find_project_root;
+use colored::Colorize;
+use infrastructure::config::PowerUserConfig;
+use infrastructure::policy_engine::bundle;
+use infrastructure::policy_engine::language::{PolicyContext, PolicyRule, PolicySet, Verdict};
+use shared::terminal;
+use shared::types::Result;
+use std::path::PathBuf;
+
+pub fn run_policy(args: &[String], session: Option<&str>) -> Result<()> {
+    let root = find_project_root().map(PathBuf::from);
+    let rules = PolicySet::load(root.as_deref())?;
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            if let Some(source) = PowerUserConfig::load().policy_bundle {
+                let state = match bundle::cached(&source) {
+                    Some(bundle) => format!(" (v{})", bundle.version),
+                    None => " (not fetched yet)".to_string(),
+                };
+                println!("{}{}", source.url, state.dimmed());
+            }
+            for file in PolicySet::files(root.as_deref()) {
+                let state = if file.exists() { "" } else { " (not present)" };
+                println!("{}{}", file.display(), state.dimmed());
+            }
+            if rules.is_empty() {
+                println!("No policy rules; only the built-in checks apply.");
+            }
+            for rule in rules.rules() {
+                println!("  {} {}", rule.source.dimmed(), colored_rule(rule));
+            }
+            Ok(())
+        }
+        Some("check") => {
+            println!(
+                "{} {} rule(s) parse",
+                terminal::icon("✓", "OK").green(),
+                rules.rules().len()
+            );
+            Ok(())
+        }
+        Some("explain") => {
+            let command = args[1..].join(" ");
+            if command.is_empty() {
+                return Err(anyhow::anyhow!("Usage: bro --policy explain <COMMAND>"));
+            }
+            let context = PolicyContext::for_command(&command, session);
+            println!(
+                "{} risk {}, session {}, time {}",
+                command.bold(),
+                context.risk.map_or("unknown", |risk| risk.name()),
+                session.unwrap_or("-"),
+                context.time.format("%H:%M")
+            );
+            for trace in rules.explain(&context) {
+                match &trace.failed {
+                    Some(failed) => println!(
+                        "  {} {} {}",
+                        terminal::icon("·", "-").dimmed(),
+                        format!("{} {}", trace.rule.source, trace.rule.text).dimmed(),
+                        format!("({})", failed).dimmed()
+                    ),
+                    None => println!(
+                        "  {} {} {}",
+                        terminal::icon("→", ">").bright_cyan(),
+                        trace.rule.source,
+                        colored_rule(trace.rule)
+                    ),
+                }
+            }
+            match rules.evaluate(&context) {
+                Some(rule) => println!("Decided by {}", rule.describe()),
+                None => println!("No rule matched; the built-in checks decide."),
+            }
+            Ok(())
+        }
+        Some(other) => Err(anyhow::anyhow!(
+            "Unknown policy action '{}'; use list, check or explain <COMMAND>",
+            other
+        )),
+    }
+}
+
+fn colored_rule(rule: &PolicyRule) -> colored::ColoredString {
+    match rule.verdict {
+        Verdict::Allow => rule.text.green(),
+        Verdict::Deny => rule.text.red(),
+        Verdict::Ask => rule.text.yellow(),
+    }
+}
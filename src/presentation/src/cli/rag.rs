@@ -1,9 +1,75 @@
 //! RAG and file explanation functionality
 
 use anyhow::Result;
+use application::rag_service::RagCitation;
 use docx_rs::{read_docx, DocumentChild};
 use std::path::Path;
 
+/// Repository roots to index and the repository names a query is scoped to.
+///
+/// The project root comes first, followed by `RAG_REPOSITORIES`. `--context`
+/// entries name repositories; entries that are directories are added as
+/// repositories for this run.
+pub fn resolve_rag_repositories(
+    project_root: &str,
+    configured: &[String],
+    context: Option<&[String]>,
+) -> (Vec<String>, Option<Vec<String>>) {
+    let mut roots = vec![project_root.to_string()];
+    roots.extend(configured.iter().cloned());
+
+    let scope = context.map(|entries| {
+        entries
+            .iter()
+            .map(|entry| {
+                if Path::new(entry).is_dir() {
+                    if !roots.contains(entry) {
+                        roots.push(entry.clone());
+                    }
+                    application::rag_service::repo_name(entry)
+                } else {
+                    entry.clone()
+                }
+            })
+            .collect()
+    });
+    (roots, scope)
+}
+
+/// Footnote list of the sources behind a RAG answer, e.g.
+/// `[1] api/src/lib.rs:7-19 (function parse_args) - similarity 0.82`
+pub fn format_citations(citations: &[RagCitation]) -> String {
+    if citations.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("Sources:");
+    for (i, citation) in citations.iter().enumerate() {
+        let mut location = match &citation.repo {
+            Some(repo) => format!("{}/{}", repo, citation.path),
+            None => citation.path.clone(),
+        };
+        match (citation.start_line, citation.end_line) {
+            (Some(start), Some(end)) if start != end => {
+                location.push_str(&format!(":{}-{}", start, end))
+            }
+            (Some(start), _) => location.push_str(&format!(":{}", start)),
+            _ => {}
+        }
+        out.push_str(&format!("\n  [{}] {}", i + 1, location));
+        if let Some(symbol) = &citation.symbol {
+            out.push_str(&format!(" ({})", symbol));
+        }
+        if let Some(license) = &citation.license {
+            out.push_str(&format!(" [{}]", license));
+        }
+        if let Some(score) = citation.score {
+            out.push_str(&format!(" - similarity {:.2}", score));
+        }
+    }
+    out
+}
+
 /// Read file content with support for multiple formats (text, PDF, DOCX)
 pub fn read_file_content(file: &str) -> Result<String> {
     let path = Path::new(file);
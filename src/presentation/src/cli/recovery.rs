@@ -0,0 +1,64 @@
+//! Guided recovery when a service bro needs has failed
+//!
+//! When a request fails because Ollama is not running, the model is not
+//! installed or Qdrant is unreachable, the failure is explained and the
+//! recovery actions from `error_analyzer` are offered one at a time. Without
+//! a terminal to ask on, they are only listed.
+
+use colored::Colorize;
+use infrastructure::config::Config;
+use infrastructure::error_analyzer::ServiceFailure;
+use infrastructure::ollama_client::OllamaClient;
+use shared::confirmation::ask_confirmation;
+use shared::terminal;
+use shared::types::Result;
+use std::io::IsTerminal;
+
+/// Offer to fix the service failure behind `error`; true when an action
+/// succeeded and the request is worth running again
+pub async fn offer_recovery(error: &anyhow::Error, config: &Config) -> Result<bool> {
+    let Some(failure) = ServiceFailure::classify(error, &config.ollama_base_url) else {
+        return Ok(false);
+    };
+    eprintln!(
+        "{} {}",
+        terminal::icon("✗", "X").red(),
+        failure.describe().bold()
+    );
+
+    let installed = match failure {
+        ServiceFailure::ModelMissing { .. } => match OllamaClient::new() {
+            Ok(client) => client.installed_models().await.unwrap_or_default(),
+            Err(_) => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    let actions = failure.recovery_actions(&installed);
+    if actions.is_empty() {
+        return Ok(false);
+    }
+    if !std::io::stdin().is_terminal() {
+        eprintln!("To recover:");
+        for action in &actions {
+            eprintln!("  - {}", action.describe());
+        }
+        return Ok(false);
+    }
+
+    for action in &actions {
+        if !ask_confirmation(&format!("{}?", action.describe()), true)? {
+            continue;
+        }
+        match action.run().await {
+            Ok(()) => {
+                println!(
+                    "{} Done; running the request again",
+                    terminal::icon("✓", "OK").green()
+                );
+                return Ok(true);
+            }
+            Err(e) => eprintln!("{} {}", terminal::icon("⚠️", "Warn").yellow(), e),
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,29 @@
+//! Research briefs for `bro --research <topic>`
+//!
+//! Briefs are saved as Markdown under `.bro/research`, named after the topic.
+
+use std::path::{Path, PathBuf};
+
+/// Write `markdown` to `.bro/research/<topic slug>.md` below `project_root`,
+/// replacing an earlier brief on the same topic
+pub fn save_brief(project_root: &Path, topic: &str, markdown: &str) -> anyhow::Result<PathBuf> {
+    let dir = project_root.join(".bro").join("research");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.md", slug(topic)));
+    std::fs::write(&path, markdown)?;
+    Ok(path)
+}
+
+fn slug(topic: &str) -> String {
+    let slug = topic
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    match slug.char_indices().nth(60) {
+        Some((end, _)) => slug[..end].trim_end_matches('-').to_string(),
+        None if slug.is_empty() => "brief".to_string(),
+        None => slug,
+    }
+}
@@ -0,0 +1,56 @@
+//! New projects for `bro --new <template> <name>`
+//!
+//! Without arguments the built-in templates are listed. The project is
+//! created below the working directory; build mode can then customise it.
+
+use colored::Colorize;
+use infrastructure::scaffold::{self, Template, BUILTIN_TEMPLATES};
+use shared::terminal;
+use shared::types::Result;
+use std::path::PathBuf;
+
+/// Create the project; returns its directory, or None when only listing
+pub fn run_new(args: &[String], vars: &[String]) -> Result<Option<PathBuf>> {
+    let (spec, name) = match args {
+        [] => {
+            println!("{}", "Templates".bright_cyan());
+            for (name, description) in BUILTIN_TEMPLATES {
+                println!("  {:<12} {}", name, description);
+            }
+            println!("  gh:OWNER/REPO a GitHub repository, or any git URL");
+            println!(
+                "{}",
+                "Create one with: bro --new <template> <name> [--var KEY=VALUE]".dimmed()
+            );
+            return Ok(None);
+        }
+        [spec, name] => (spec, name),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Usage: bro --new <template> <name>, e.g. bro --new rust-cli my-tool"
+            ))
+        }
+    };
+
+    let target = scaffold::project_dir(&std::env::current_dir()?, name)?;
+    let project_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| name.clone());
+    let vars = scaffold::variables(&project_name, vars)?;
+    let template = Template::resolve(spec)?;
+    let files = scaffold::scaffold(&template, &target, &vars)?;
+
+    for file in &files {
+        let shown = file.strip_prefix(&target).unwrap_or(file);
+        println!("  {} {}", "+".green(), shown.display());
+    }
+    println!(
+        "{} Created {} from {} ({} files)",
+        terminal::icon("✓", "OK").green(),
+        target.display(),
+        template.name,
+        files.len()
+    );
+    Ok(Some(target))
+}
@@ -0,0 +1,114 @@
+//! Secret scanning for `bro --secrets`
+//!
+//! `bro --secrets` scans the files of the repository and fails when it finds
+//! secrets that the baseline does not list, so it can gate CI.
+//! `bro --secrets baseline` accepts everything found now by writing the
+//! baseline to `.bro/secrets.baseline`. Low severity findings such as email
+//! addresses are informational and never reported.
+
+use crate::utils::find_project_root;
+use colored::Colorize;
+use infrastructure::git_repo::GitRepo;
+use shared::secrets_baseline::{SecretsBaseline, BASELINE_FILE};
+use shared::secrets_detector::{SecretFinding, SecretPattern, SecretSeverity, SecretsDetector};
+use shared::terminal;
+use shared::types::Result;
+use std::path::{Path, PathBuf};
+
+pub fn run_secrets(args: &[String], patterns: &[SecretPattern]) -> Result<()> {
+    let root = find_project_root()
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())
+        .ok_or_else(|| anyhow::anyhow!("Cannot determine the project root"))?;
+    let baseline_path = root.join(BASELINE_FILE);
+    let detector = SecretsDetector::with_patterns(patterns)?;
+    let findings = scan_repository(&root, &detector)?;
+
+    match args.first().map(String::as_str) {
+        Some("baseline") => {
+            let mut baseline = SecretsBaseline::default();
+            for (file, finding) in &findings {
+                baseline.add(file, finding);
+            }
+            baseline.save(&baseline_path)?;
+            println!(
+                "{} Recorded {} finding(s) in {} file(s) to {}",
+                terminal::icon("✓", "OK").green(),
+                baseline.len(),
+                baseline.results.len(),
+                baseline_path.display()
+            );
+            Ok(())
+        }
+        None | Some("scan") => {
+            let baseline = SecretsBaseline::load(&baseline_path)?;
+            let new: Vec<_> = findings
+                .iter()
+                .filter(|(file, finding)| !baseline.contains(file, finding))
+                .collect();
+            for (file, finding) in &new {
+                let severity = match finding.severity {
+                    SecretSeverity::High => "high".red(),
+                    _ => "medium".yellow(),
+                };
+                println!(
+                    "{}:{} [{}] {} {}",
+                    file,
+                    finding.line_number.unwrap_or(0),
+                    severity,
+                    finding.pattern_name,
+                    finding.masked_value.dimmed()
+                );
+            }
+            if !new.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "{} new secret(s) found; remove them, or accept them with `bro --secrets baseline`",
+                    new.len()
+                ));
+            }
+            println!(
+                "{} No new secrets ({} accepted in the baseline)",
+                terminal::icon("✓", "OK").green(),
+                baseline.len()
+            );
+            Ok(())
+        }
+        Some(other) => Err(anyhow::anyhow!(
+            "Unknown secrets action '{}'; use scan or baseline",
+            other
+        )),
+    }
+}
+
+/// Findings worth reporting in the repository's tracked and unignored files,
+/// by path relative to `root`
+fn scan_repository(
+    root: &Path,
+    detector: &SecretsDetector,
+) -> Result<Vec<(String, SecretFinding)>> {
+    let repo = GitRepo::discover(root)
+        .ok_or_else(|| anyhow::anyhow!("{} is not a git repository", root.display()))?;
+    let mut findings = Vec::new();
+    for path in repo.working_tree_files()? {
+        let Ok(relative) = path.strip_prefix(root) else {
+            continue;
+        };
+        let file = relative.to_string_lossy().replace('\\', "/");
+        if file == BASELINE_FILE {
+            continue;
+        }
+        // Binary and unreadable files are skipped
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        findings.extend(
+            detector
+                .scan_content(&content)
+                .findings
+                .into_iter()
+                .filter(|finding| finding.severity != SecretSeverity::Low)
+                .map(|finding| (file.clone(), finding)),
+        );
+    }
+    Ok(findings)
+}
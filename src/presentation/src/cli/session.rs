@@ -1,10 +1,19 @@
 //! Session management functionality for CLI operations
 
+use crate::editor::{EditContent, Editor};
 use crate::utils::find_project_root;
 use colored::Colorize;
-use infrastructure::session_store::{SessionMetadata, SessionStore};
-use shared::confirmation::ask_confirmation;
+use infrastructure::session_merge::{conflict_markers, Resolution, SessionMerge};
+use infrastructure::session_store::{Session, SessionMetadata, SessionStore};
+use infrastructure::session_timeline::{self, TimelineEvent, TimelineKind};
+use infrastructure::write_conflicts::{ConflictChoice, ConflictResolver};
+use shared::confirmation::{ask_confirmation, scripted_answer};
+use shared::terminal;
 use shared::types::Result;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 /// Display all sessions for the current project
 pub fn display_sessions(store: &SessionStore, current_session: Option<&String>) -> Result<()> {
@@ -142,3 +151,211 @@ pub fn display_session_created(session_name: &str) {
         session_name.bright_green()
     );
 }
+
+/// Fork `source` into a new session `name`
+pub fn fork_session(store: &SessionStore, source: &str, name: &str) -> Result<()> {
+    store.fork_session(source, name)?;
+    println!(
+        "{} Forked session '{}' from '{}'; merge it back with: bro --merge-session {}",
+        terminal::icon("✓", "OK").green(),
+        name.bright_green(),
+        source,
+        name
+    );
+    Ok(())
+}
+
+/// Print `events` of session `name` as a table grouped by day; `verbose`
+/// adds each event's details under it
+pub fn display_timeline(name: &str, events: &[TimelineEvent], verbose: bool) {
+    println!("{} {}", "Session timeline:".bright_cyan().bold(), name);
+    if events.is_empty() {
+        println!(
+            "{}",
+            "Nothing recorded yet - queries, plans, operations, commits and test runs are added as they happen."
+                .dimmed()
+        );
+        return;
+    }
+    for (day, events) in session_timeline::by_day(events) {
+        println!();
+        println!("{}", day.format("%A %Y-%m-%d").to_string().bold());
+        for event in events {
+            let kind = format!("{:<9}", event.kind.name());
+            let kind = match event.kind {
+                TimelineKind::Query => kind.bright_cyan(),
+                TimelineKind::Plan => kind.bright_blue(),
+                TimelineKind::Operation => kind.normal(),
+                TimelineKind::Commit => kind.bright_magenta(),
+                TimelineKind::Test => kind.bright_yellow(),
+            };
+            let status = format!("{:<6}", event.status());
+            let status = match event.ok {
+                Some(true) => status.green(),
+                Some(false) => status.red(),
+                None => status.normal(),
+            };
+            println!(
+                "  {}  {}  {}  {}",
+                event.local_time().dimmed(),
+                kind,
+                status,
+                event.summary
+            );
+            if let Some(detail) = event.detail.as_ref().filter(|_| verbose) {
+                for line in detail.lines() {
+                    println!("{}{}", " ".repeat(31), line.dimmed());
+                }
+            }
+        }
+    }
+}
+
+/// Merge session `source` into `into`, or into the session it was forked
+/// from, resolving conflicting files interactively first
+pub fn merge_session(store: &SessionStore, source: &str, into: Option<&str>) -> Result<()> {
+    let theirs = store
+        .load_session(source)?
+        .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", source))?;
+    let into = into
+        .or(theirs.forked_from.as_deref())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Session '{}' was not forked from another; name the target with --into",
+                source
+            )
+        })?
+        .to_string();
+    if into == source {
+        return Err(anyhow::anyhow!(
+            "Cannot merge session '{}' into itself",
+            source
+        ));
+    }
+    let mut ours = store
+        .load_session(&into)?
+        .ok_or_else(|| anyhow::anyhow!("Session '{}' not found", into))?;
+
+    let mut merge = SessionMerge::plan(
+        &theirs,
+        &ours,
+        &workspace_root(&theirs),
+        &workspace_root(&ours),
+    );
+    if merge.is_empty() {
+        println!("Session '{}' has nothing to merge into '{}'.", source, into);
+        return Ok(());
+    }
+    println!(
+        "Merging '{}' into '{}': {} message(s), {} change(s), {} file(s), {} conflict(s)",
+        source.bright_green(),
+        into.bright_green(),
+        merge.messages.len(),
+        merge.changes.len(),
+        merge.files.len(),
+        merge.conflicts()
+    );
+    for change in &merge.changes {
+        println!("  {} {}", "+".green(), change.description);
+    }
+
+    for file in merge.files.iter_mut().filter(|file| file.conflict) {
+        println!(
+            "{} {} was changed in both sessions",
+            terminal::icon("⚠️", "Warn").yellow(),
+            file.path.display()
+        );
+        loop {
+            let answer = ask_line("Keep [o]urs, take [t]heirs, [e]dit or [a]bort the merge?")?;
+            let resolution = match answer.trim().to_lowercase().as_str() {
+                "o" | "ours" => Resolution::KeepOurs,
+                "t" | "theirs" => Resolution::KeepTheirs,
+                "e" | "edit" => {
+                    let marked = conflict_markers(
+                        file.ours.as_deref().unwrap_or_default(),
+                        file.theirs.as_deref().unwrap_or_default(),
+                        &into,
+                        source,
+                    );
+                    match Editor::edit_content(&marked, EditContent::File(marked.clone())) {
+                        Ok(edited) => Resolution::Edited(format!("{}\n", edited)),
+                        Err(e) => {
+                            eprintln!("{} {}", terminal::icon("✗", "X").red(), e);
+                            continue;
+                        }
+                    }
+                }
+                "a" | "abort" => {
+                    println!("{}", "Merge aborted; nothing was changed.".yellow());
+                    return Ok(());
+                }
+                _ => continue,
+            };
+            file.resolution = Some(resolution);
+            break;
+        }
+    }
+
+    merge.commit(&mut ours)?;
+    store.save_session(&ours)?;
+    println!(
+        "{} Merged '{}' into '{}'",
+        terminal::icon("✓", "OK").green(),
+        source,
+        into.bright_green()
+    );
+    Ok(())
+}
+
+/// Directory a session works in: its pinned workspace, else the project
+fn workspace_root(session: &Session) -> PathBuf {
+    session
+        .workspace
+        .as_ref()
+        .map(|workspace| workspace.root.clone())
+        .or_else(|| find_project_root().map(PathBuf::from))
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default()
+}
+
+/// Asks on the terminal how to settle a write that raced another process;
+/// None when there is no terminal to ask on
+pub fn conflict_resolver() -> Option<ConflictResolver> {
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    Some(Arc::new(|conflict| {
+        println!(
+            "{} {} was changed by another process since it was read",
+            terminal::icon("⚠️", "Warn").yellow(),
+            conflict.what
+        );
+        println!("  ours:   {}", conflict.ours);
+        println!("  theirs: {}", conflict.theirs);
+        loop {
+            let answer = match ask_line("[m]erge both, keep [o]urs or keep [t]heirs?") {
+                Ok(answer) => answer,
+                Err(_) => return ConflictChoice::Merge,
+            };
+            match answer.trim().to_lowercase().as_str() {
+                "" | "m" | "merge" => return ConflictChoice::Merge,
+                "o" | "ours" => return ConflictChoice::KeepOurs,
+                "t" | "theirs" => return ConflictChoice::KeepTheirs,
+                _ => println!("Answer m, o or t"),
+            }
+        }
+    }))
+}
+
+fn ask_line(prompt: &str) -> Result<String> {
+    print!("{} ", prompt);
+    std::io::stdout().flush()?;
+    if let Some(answer) = scripted_answer(prompt) {
+        let answer = answer?;
+        println!("{}", answer);
+        return Ok(answer);
+    }
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line)
+}
@@ -0,0 +1,147 @@
+//! Qdrant collection backups for `bro --snapshot` / `--restore`, and whole
+//! memory backups for `--memory-backup` / `--memory-restore`
+
+use application::advanced_qdrant::{self, AdvancedQdrantManager};
+use colored::Colorize;
+use infrastructure::capability_registry::qdrant_url;
+use shared::cancellation::{cancel_on_interrupt, CancellationToken, InterruptGuard};
+use shared::terminal;
+use shared::types::Result;
+use std::path::Path;
+
+/// Collections backed up when `--snapshot` is given no names
+const DEFAULT_COLLECTIONS: [&str; 2] = ["vibe_rag", "conversation_memory"];
+
+/// Manager whose requests Ctrl+C aborts while the guard is held
+fn interruptible_manager() -> (AdvancedQdrantManager, InterruptGuard) {
+    let cancel = CancellationToken::new();
+    let interrupt = cancel_on_interrupt(&cancel);
+    let manager = AdvancedQdrantManager::new(&qdrant_url()).with_cancellation(cancel);
+    (manager, interrupt)
+}
+
+/// Download snapshots of `collections` (or the defaults) into the backup directory
+pub async fn run_snapshot(collections: &[String]) -> Result<()> {
+    let (manager, _interrupt) = interruptible_manager();
+    let dir = advanced_qdrant::default_backup_dir();
+    let explicit = !collections.is_empty();
+    let names: Vec<String> = if explicit {
+        collections.to_vec()
+    } else {
+        DEFAULT_COLLECTIONS.iter().map(|c| c.to_string()).collect()
+    };
+
+    let mut failures = 0;
+    for name in &names {
+        // Default collections that were never created are not an error
+        if !explicit && manager.get_performance_metrics(name).await.is_err() {
+            println!("{} {} (not found, skipped)", terminal::icon("○", "-"), name);
+            continue;
+        }
+        match manager.backup_collection(name, &dir).await {
+            Ok((metadata, path)) => println!(
+                "{} {}: {} points, {} KB → {}",
+                terminal::icon("✓", "OK").green(),
+                name,
+                metadata.points_count,
+                metadata.size_bytes / 1024,
+                path.display()
+            ),
+            Err(e) => {
+                failures += 1;
+                println!("{} {}: {}", terminal::icon("✗", "X").red(), name, e);
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(anyhow::anyhow!("{} snapshot(s) failed", failures));
+    }
+    Ok(())
+}
+
+/// Restore a snapshot downloaded by `run_snapshot`, replacing the collection
+pub async fn run_restore(path: &Path) -> Result<()> {
+    let (manager, _interrupt) = interruptible_manager();
+    let metadata = manager.restore_collection(path, None).await?;
+    println!(
+        "{} Restored '{}' ({} points) from snapshot taken {}",
+        terminal::icon("✓", "OK").green(),
+        metadata.collection,
+        metadata.points_count,
+        metadata.created_at.format("%Y-%m-%d %H:%M UTC")
+    );
+    Ok(())
+}
+
+/// List local snapshots, newest first
+pub fn list_snapshots() -> Result<()> {
+    let dir = advanced_qdrant::default_backup_dir();
+    let backups = advanced_qdrant::list_backups(&dir)?;
+    if backups.is_empty() {
+        println!("No snapshots in {}", dir.display());
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        format!("Snapshots in {}", dir.display()).bright_cyan()
+    );
+    for (metadata, path) in backups {
+        println!(
+            "  {}  {:<22} {:>8} points  {:>8} KB  {}",
+            metadata.created_at.format("%Y-%m-%d %H:%M"),
+            metadata.collection,
+            metadata.points_count,
+            metadata.size_bytes / 1024,
+            path.display().to_string().dimmed()
+        );
+    }
+    Ok(())
+}
+
+/// Back up semantic memory and every RAG index into `dir`
+pub async fn run_memory_backup(dir: &Path) -> Result<()> {
+    let (manager, _interrupt) = interruptible_manager();
+    let backup = manager.backup_memory(dir).await?;
+    for metadata in &backup.snapshots {
+        println!(
+            "{} {}: {} points, {} KB",
+            terminal::icon("✓", "OK").green(),
+            metadata.collection,
+            metadata.points_count,
+            metadata.size_bytes / 1024
+        );
+    }
+    println!(
+        "Backed up {} collection(s), {} points, to {}",
+        backup.snapshots.len(),
+        backup.points_count(),
+        dir.display()
+    );
+    println!(
+        "{}",
+        format!("Restore with: bro --memory-restore {}", dir.display()).dimmed()
+    );
+    Ok(())
+}
+
+/// Restore a backup made by `run_memory_backup`
+pub async fn run_memory_restore(path: &Path) -> Result<()> {
+    let (manager, _interrupt) = interruptible_manager();
+    let backup = manager.restore_memory(path).await?;
+    for metadata in &backup.snapshots {
+        println!(
+            "{} Restored '{}' ({} points)",
+            terminal::icon("✓", "OK").green(),
+            metadata.collection,
+            metadata.points_count
+        );
+    }
+    println!(
+        "Memory backup from {} restored into {}",
+        backup.created_at.format("%Y-%m-%d %H:%M UTC"),
+        qdrant_url()
+    );
+    Ok(())
+}
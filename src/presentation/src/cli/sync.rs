@@ -0,0 +1,108 @@
+//! Session sync with other devices for `bro --sync [PEER...]`
+//!
+//! Peers are tailnet hosts running `bro --serve` with the same web token.
+//! Without arguments the `sync_peers` of the tailscale plugin settings are
+//! used, and the port is the configured web server port.
+
+use crate::utils::find_project_root;
+use colored::Colorize;
+use infrastructure::config::Config;
+use infrastructure::credentials::{CredentialStore, WEB_TOKEN};
+use infrastructure::session_store::SessionStore;
+use infrastructure::session_sync::{
+    peer_url, sync_with_peer, SyncJournal, SyncReport, DEFAULT_SYNC_PORT,
+};
+use shared::terminal;
+use shared::types::Result;
+use std::path::Path;
+
+pub async fn run_sync(store: &SessionStore, config: &Config, peers: &[String]) -> Result<()> {
+    let settings = &config.power_user.plugins.settings;
+    let setting = |plugin: &str, key: &str| settings.get(plugin).and_then(|s| s.get(key)).cloned();
+    let peers: Vec<String> = if peers.is_empty() {
+        setting("tailscale", "sync_peers")
+            .unwrap_or_default()
+            .split(',')
+            .map(|peer| peer.trim().to_string())
+            .filter(|peer| !peer.is_empty())
+            .collect()
+    } else {
+        peers.to_vec()
+    };
+    if peers.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No peers to sync with; pass tailnet hosts or set sync_peers in the tailscale settings"
+        ));
+    }
+    let port = setting("web", "server_port")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_SYNC_PORT);
+    let token = CredentialStore::new(config.power_user.credentials.clone())
+        .get(WEB_TOKEN)
+        .await?;
+    let root = find_project_root()
+        .ok_or_else(|| anyhow::anyhow!("Sync runs inside a project directory"))?;
+    let root = Path::new(&root);
+
+    let mut failed = 0;
+    for peer in &peers {
+        match sync_with_peer(&peer_url(peer, port), token.as_deref(), store, root).await {
+            Ok((local, remote)) => {
+                println!("{} Synced with {}", terminal::icon("✓", "OK").green(), peer);
+                print_report("  here", &local);
+                print_report(&format!("  on {}", peer), &remote);
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("{} {}: {}", terminal::icon("✗", "X").red(), peer, e);
+            }
+        }
+    }
+    let journal = SyncJournal::for_project(root);
+    if journal.path().exists() {
+        println!(
+            "{}",
+            format!(
+                "Applied changes replaced by a newer copy are journaled in {}",
+                journal.path().display()
+            )
+            .dimmed()
+        );
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "Sync failed with {} of {} peer(s)",
+            failed,
+            peers.len()
+        ));
+    }
+    Ok(())
+}
+
+fn print_report(place: &str, report: &SyncReport) {
+    if report.sessions.is_empty() && report.caches.is_empty() && report.conflicts == 0 {
+        println!("{}: up to date", place);
+        return;
+    }
+    println!(
+        "{}: {} session(s) updated{}, {} cache(s) updated{}",
+        place,
+        report.sessions.len(),
+        if report.sessions.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", report.sessions.join(", "))
+        },
+        report.caches.len(),
+        if report.conflicts > 0 {
+            format!(
+                ", {} applied change(s) journaled as conflicts",
+                report.conflicts
+            )
+            .yellow()
+            .to_string()
+        } else {
+            String::new()
+        }
+    );
+}
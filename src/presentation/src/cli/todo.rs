@@ -0,0 +1,66 @@
+//! Project TODO list for `bro --todo`
+//!
+//! `bro --todo` lists the items in `.bro/todo.md`, `bro --todo add <text>`
+//! appends one and `bro --todo done <N>` checks item N off.
+
+use colored::Colorize;
+use infrastructure::todo_list::TodoList;
+use shared::terminal;
+use shared::types::Result;
+
+pub fn run_todo(args: &[String]) -> Result<()> {
+    let list = TodoList::for_current_project();
+    match args.first().map(String::as_str) {
+        None | Some("list") => display(&list),
+        Some("add") => {
+            let item = list.add(&args[1..].join(" "))?;
+            println!(
+                "{} Added {}: {}",
+                terminal::icon("✓", "OK").green(),
+                item.number,
+                item.text
+            );
+            Ok(())
+        }
+        Some("done") => {
+            let number = args
+                .get(1)
+                .and_then(|n| n.parse::<usize>().ok())
+                .ok_or_else(|| anyhow::anyhow!("Usage: bro --todo done <N>"))?;
+            let item = list.complete(number)?;
+            println!(
+                "{} Done {}: {}",
+                terminal::icon("✓", "OK").green(),
+                item.number,
+                item.text
+            );
+            Ok(())
+        }
+        Some(other) => Err(anyhow::anyhow!(
+            "Unknown TODO action '{}'; use add <text>, done <N> or no action to list",
+            other
+        )),
+    }
+}
+
+fn display(list: &TodoList) -> Result<()> {
+    let items = list.items();
+    if items.is_empty() {
+        println!("No TODO items in {}.", list.path().display());
+        return Ok(());
+    }
+    for item in &items {
+        let number = format!("{:>3}.", item.number);
+        if item.done {
+            println!("{} {}", number.dimmed(), item.text.dimmed().strikethrough());
+        } else {
+            println!("{} {}", number.bright_cyan(), item.text);
+        }
+    }
+    let open = items.iter().filter(|item| !item.done).count();
+    println!(
+        "{}",
+        format!("{} open, {} done", open, items.len() - open).dimmed()
+    );
+    Ok(())
+}
@@ -0,0 +1,51 @@
+//! Project trash for `bro --trash`
+//!
+//! `bro --trash` lists the batches of files deleted by builds,
+//! `bro --trash restore` moves the newest batch back, and
+//! `bro --trash restore <BATCH> [PATH]` one batch or one file of it.
+
+use colored::Colorize;
+use infrastructure::recycle_bin::RecycleBin;
+use shared::terminal;
+use shared::types::Result;
+use std::path::Path;
+
+pub fn run_trash(args: &[String]) -> Result<()> {
+    let bin = RecycleBin::for_current_project();
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            let batches = bin.batches();
+            if batches.is_empty() {
+                println!("The trash in {} is empty.", bin.dir().display());
+                return Ok(());
+            }
+            for batch in &batches {
+                println!(
+                    "{} {}",
+                    batch.id.bright_cyan(),
+                    format!("({} file(s))", batch.files.len()).dimmed()
+                );
+                for file in &batch.files {
+                    println!("    {}", file.display());
+                }
+            }
+            Ok(())
+        }
+        Some("restore") => {
+            let batch = args.get(1).map(String::as_str);
+            let only = args.get(2).map(Path::new);
+            for path in bin.restore(batch, only)? {
+                println!(
+                    "{} Restored {}",
+                    terminal::icon("✓", "OK").green(),
+                    path.display()
+                );
+            }
+            Ok(())
+        }
+        Some(other) => Err(anyhow::anyhow!(
+            "Unknown trash action '{}'; use restore [BATCH] [PATH] or no action to list",
+            other
+        )),
+    }
+}
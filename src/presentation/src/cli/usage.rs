@@ -0,0 +1,100 @@
+//! Token usage reporting for CLI operations
+
+use chrono::Utc;
+use colored::Colorize;
+use infrastructure::session_store::SessionStore;
+use infrastructure::token_usage::{TokenBudget, TokenUsage};
+use shared::terminal;
+use shared::types::Result;
+
+/// Number of days shown in the daily usage table
+const HISTORY_DAYS: usize = 7;
+
+/// Display per-session and per-day token usage for the current project
+pub fn display_usage_report(
+    store: &SessionStore,
+    budget: &TokenBudget,
+    current_session: Option<&String>,
+) -> Result<()> {
+    println!("{}", "Token Usage".bright_cyan().bold());
+    println!();
+
+    let narrow = terminal::capabilities().is_narrow();
+    let mut sessions = store.list_sessions()?;
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.token_usage.total()));
+
+    println!("Sessions:");
+    if sessions.iter().all(|s| s.token_usage.requests == 0) {
+        println!("  {}", "No token usage recorded yet.".dimmed());
+    } else {
+        for session in sessions.iter().filter(|s| s.token_usage.requests > 0) {
+            let active_marker = if Some(&session.name) == current_session {
+                "[active] "
+            } else {
+                "         "
+            };
+            if narrow {
+                // Keep the numbers on their own line instead of letting them wrap mid-column
+                println!(
+                    "  {}{}",
+                    active_marker.trim_end(),
+                    session.name.bright_green()
+                );
+                println!("    {}", format_usage(&session.token_usage).trim_start());
+            } else {
+                println!(
+                    "  {} {:<15} {}",
+                    active_marker,
+                    session.name.bright_green(),
+                    format_usage(&session.token_usage)
+                );
+            }
+        }
+    }
+
+    println!();
+    println!("Last {} days:", HISTORY_DAYS);
+    let history = store.usage_history()?;
+    if history.is_empty() {
+        println!("  {}", "No token usage recorded yet.".dimmed());
+    } else {
+        for (date, usage) in history.iter().rev().take(HISTORY_DAYS) {
+            println!("  {}  {}", date.format("%Y-%m-%d"), format_usage(usage));
+        }
+    }
+
+    println!();
+    println!("Budgets:");
+    let session_total = current_session
+        .and_then(|name| sessions.iter().find(|s| &s.name == name))
+        .map(|s| s.token_usage.total())
+        .unwrap_or(0);
+    let today_total = store.daily_usage(Utc::now().date_naive())?.total();
+    println!(
+        "  Session: {}",
+        format_budget(session_total, budget.max_session_tokens)
+    );
+    println!(
+        "  Today:   {}",
+        format_budget(today_total, budget.max_daily_tokens)
+    );
+
+    Ok(())
+}
+
+fn format_usage(usage: &TokenUsage) -> String {
+    format!(
+        "{:>9} tokens ({} prompt / {} completion, {} requests)",
+        usage.total(),
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        usage.requests
+    )
+}
+
+fn format_budget(used: u64, limit: Option<u64>) -> String {
+    match limit {
+        Some(limit) => format!("{} / {} tokens", used, limit),
+        None => format!("{} tokens (unlimited)", used),
+    }
+}
@@ -1,97 +1,83 @@
 //! Voice input handler for CLI voice mode
 //!
 //! Provides voice-activated command execution using:
-//! - Vosk for speech recognition
-//! - Piper for text-to-speech feedback
+//! - A configurable speech recognizer (Vosk by default)
+//! - A configurable text-to-speech engine (Piper by default)
 //! - CPAL for microphone input
+//!
+//! Questions an agent run waits on are read out, and the next thing said
+//! answers them.
 
+use domain::services::{SpeechRecognitionService, TextToSpeechService};
 use infrastructure::adapters::{
     microphone::{MicrophoneCapture, MicrophoneConfig},
-    tts_adapter::TtsAdapter,
-    vosk_adapter::VoskAdapter,
+    speech_providers::SpeechProviderRegistry,
 };
+use infrastructure::config::AudioConfig;
 use infrastructure::ollama_client::OllamaClient;
+use infrastructure::user_questions::{PendingQuestion, QuestionQueue};
+use shared::terminal;
 use shared::types::Result;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
+/// How often voice mode looks for questions from agent runs
+const QUESTION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Recorded as the source of answers given by voice
+const VOICE_SOURCE: &str = "voice";
+
 /// Voice input handler for CLI voice mode
 pub struct VoiceHandler {
     microphone: MicrophoneCapture,
-    speech_recognizer: Arc<VoskAdapter>,
-    tts_engine: Option<TtsAdapter>,
+    speech_recognizer: Arc<dyn SpeechRecognitionService>,
+    tts_engine: Option<Arc<dyn TextToSpeechService>>,
     ollama_client: OllamaClient,
     wake_words: Vec<String>,
     is_listening: bool,
+    questions: QuestionQueue,
+    /// The question read out last, answered by the next utterance
+    asking: Option<PendingQuestion>,
+    last_question_check: Instant,
 }
 
 impl VoiceHandler {
-    /// Create a new voice handler
-    pub async fn new() -> Result<Self> {
+    /// Create a new voice handler using the configured speech providers
+    pub async fn new(audio: &AudioConfig) -> Result<Self> {
         println!("🎤 Initializing voice recognition system...");
 
         // Initialize microphone with voice command config
         let microphone = MicrophoneCapture::with_config(MicrophoneConfig::for_voice_commands())
             .map_err(|e| anyhow::anyhow!("Failed to initialize microphone: {}", e))?;
 
-        println!("  ✓ Microphone initialized");
+        println!("  {} Microphone initialized", terminal::icon("✓", "OK"));
 
-        // Try to find Vosk model
-        let home_model_path = format!(
-            "{}/.local/share/vosk/model",
-            std::env::var("HOME").unwrap_or_default()
+        // Build the configured speech backends; a broken one is an error, not a silent downgrade
+        let registry = SpeechProviderRegistry::with_builtin();
+        let (speech_recognizer, tts_engine) = registry.create_checked(audio).await?;
+        println!(
+            "  {} Speech recognition ready ({})",
+            terminal::icon("✓", "OK"),
+            audio.stt_provider
         );
-        let model_paths = vec![
-            "model/vosk-model-en-us-0.22",
-            "model/vosk-model-small-en-us-0.15",
-            "models/vosk-model-en-us-0.22",
-            "models/vosk-model-small-en-us-0.15",
-            "models/vosk-model-en-us-0.22-lgraph",
-            "/usr/share/vosk/model",
-            &home_model_path,
-        ];
-
-        let mut speech_recognizer = None;
-        for path in &model_paths {
-            if std::path::Path::new(path).exists() {
-                match VoskAdapter::new(path, 16000.0) {
-                    Ok(adapter) => {
-                        println!("  ✓ Speech recognition loaded from {}", path);
-                        speech_recognizer = Some(Arc::new(adapter));
-                        break;
-                    }
-                    Err(e) => {
-                        tracing::debug!("Failed to load Vosk model from {}: {}", path, e);
-                    }
-                }
-            }
+        match &tts_engine {
+            Some(_) => println!(
+                "  {} Text-to-speech ready ({})",
+                terminal::icon("✓", "OK"),
+                audio.tts_provider
+            ),
+            None => println!("  Text-to-speech disabled"),
         }
 
-        let speech_recognizer = speech_recognizer.ok_or_else(|| {
-            anyhow::anyhow!(
-                "Vosk model not found. Please download a model from https://alphacephei.com/vosk/models\n\
-                 and place it in one of: {:?}",
-                model_paths
-            )
-        })?;
-
-        // Try to initialize TTS (optional - will work without it)
-        let tts_engine = match TtsAdapter::new() {
-            Ok(tts) => {
-                println!("  ✓ Text-to-speech initialized");
-                Some(tts)
-            }
-            Err(e) => {
-                println!("  ⚠ Text-to-speech unavailable: {}", e);
-                None
-            }
-        };
-
         // Initialize Ollama client for command interpretation
         let ollama_client = OllamaClient::new()
             .map_err(|e| anyhow::anyhow!("Failed to initialize Ollama client: {}", e))?;
 
-        println!("  ✓ AI command interpreter ready");
+        println!(
+            "  {} AI command interpreter ready",
+            terminal::icon("✓", "OK")
+        );
 
         Ok(Self {
             microphone,
@@ -100,6 +86,9 @@ impl VoiceHandler {
             ollama_client,
             wake_words: vec!["bro".to_string(), "hey bro".to_string()],
             is_listening: false,
+            questions: QuestionQueue::open_default(),
+            asking: None,
+            last_question_check: Instant::now(),
         })
     }
 
@@ -107,10 +96,10 @@ impl VoiceHandler {
     pub async fn start_voice_mode(&mut self) -> Result<()> {
         println!();
         println!("🎤 Voice Mode Active");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        println!("{}", terminal::rule(51));
         println!("Say 'bro' followed by your command");
         println!("Say 'stop', 'exit', or 'quit' to end voice mode");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        println!("{}", terminal::rule(51));
         println!();
 
         // Speak welcome message if TTS available
@@ -134,6 +123,8 @@ impl VoiceHandler {
 
         // Main voice processing loop
         while self.is_listening {
+            self.announce_question().await;
+
             // Wait for audio with timeout
             match tokio::time::timeout(tokio::time::Duration::from_secs(30), rx.recv()).await {
                 Ok(Some(audio_chunk)) => {
@@ -164,7 +155,6 @@ impl VoiceHandler {
 
     /// Process an audio chunk for voice commands
     async fn process_audio_chunk(&mut self, audio_chunk: Vec<i16>) -> Result<bool> {
-        use domain::services::SpeechRecognitionService;
         use shared::types::AudioSample;
 
         // Skip very short audio chunks
@@ -198,6 +188,24 @@ impl VoiceHandler {
             return Ok(false);
         }
 
+        // Whatever follows a question read out answers it
+        if let Some(question) = self.asking.take() {
+            let reply = match self.questions.answer(&question.id, &text, VOICE_SOURCE) {
+                Ok(answered) => {
+                    println!("  Answered: {}", answered.answer.unwrap_or_default());
+                    "Got it.".to_string()
+                }
+                Err(e) => {
+                    println!("  Could not answer the question: {}", e);
+                    "That question is no longer open.".to_string()
+                }
+            };
+            if let Some(ref tts) = self.tts_engine {
+                let _ = self.speak(tts, &reply).await;
+            }
+            return Ok(true);
+        }
+
         // Check for wake word and extract command
         if let Some(command) = self.extract_command(&text) {
             println!("  Command: \"{}\"", command);
@@ -224,6 +232,36 @@ impl VoiceHandler {
         Ok(true) // Continue listening
     }
 
+    /// Read out the oldest question an agent run waits on, once
+    async fn announce_question(&mut self) {
+        if self.last_question_check.elapsed() < QUESTION_POLL_INTERVAL {
+            return;
+        }
+        self.last_question_check = Instant::now();
+        let pending = self.questions.list().unwrap_or_default();
+        if let Some(asking) = &self.asking {
+            if pending.iter().any(|question| question.id == asking.id) {
+                return;
+            }
+            // Answered elsewhere or expired
+            self.asking = None;
+        }
+        let Some(question) = pending.into_iter().next() else {
+            return;
+        };
+
+        println!();
+        println!("❓ The agent asks: {}", question.prompt());
+        if let Some(ref tts) = self.tts_engine {
+            let mut speech = format!("The agent asks: {}", question.question);
+            if !question.options.is_empty() {
+                speech.push_str(&format!(" Options: {}.", question.options.join(", or ")));
+            }
+            let _ = self.speak(tts, &speech).await;
+        }
+        self.asking = Some(question);
+    }
+
     /// Extract command from recognized text (looking for wake word)
     fn extract_command(&self, text: &str) -> Option<String> {
         let text_lower = text.to_lowercase();
@@ -260,8 +298,7 @@ Response:"#,
     }
 
     /// Speak text using TTS
-    async fn speak(&self, tts: &TtsAdapter, text: &str) -> Result<()> {
-        use domain::services::TextToSpeechService;
+    async fn speak(&self, tts: &Arc<dyn TextToSpeechService>, text: &str) -> Result<()> {
         use infrastructure::adapters::audio_player::AudioPlayer;
 
         let samples = tts.synthesize(text, None).await?;
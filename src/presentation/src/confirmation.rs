@@ -1,6 +1,14 @@
 use crate::analysis::assess_command_risk;
-use crate::types::{CommandIntent, CommandRisk, InstallationOption};
+use crate::types::{AgentCommandRisk, AgentStep, CommandIntent, CommandRisk, InstallationOption};
 use colored::Colorize;
+use infrastructure::config::{Config, NetworkSecurityConfig, SecurityConfig};
+use infrastructure::network_security::{self, NetworkSecurity};
+use infrastructure::sandbox::confirmation_rules::{Confirmation, ConfirmationGrant, OperationRisk};
+use infrastructure::sandbox::ConfirmationManager;
+use infrastructure::session_store::SessionStore;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// Present confirmation dialog for data collection commands
 pub fn prompt_data_collection_confirmation(
@@ -47,6 +55,118 @@ pub fn prompt_data_collection_confirmation(
     )
 }
 
+/// Ask before a command connects to hosts outside the network allowlist.
+/// Hosts the user lets through stay allowed for the session; false when the
+/// user declines or a host is blocked outright.
+pub fn confirm_network_access(
+    config: &NetworkSecurityConfig,
+    command: &str,
+) -> anyhow::Result<bool> {
+    let Some(egress) = NetworkSecurity::for_egress(config) else {
+        return Ok(true);
+    };
+    for denied in egress.script_denials(command) {
+        eprintln!("{}", denied.to_string().yellow());
+        if !denied.can_override() {
+            return Ok(false);
+        }
+        let prompt = format!("Allow connections to {} for this session?", denied.host);
+        if !shared::confirmation::ask_confirmation(&prompt, false)? {
+            return Ok(false);
+        }
+        network_security::allow_for_session(&denied.host);
+    }
+    Ok(true)
+}
+
+/// Apply the user's confirmation rules to an agent step, whose command is
+/// both the operation and the target. Asks at the terminal, offering to
+/// remember the answer in `session`, or in the web UI when there is no
+/// terminal; an error when the step is declined.
+pub async fn confirm_agent_step(
+    config: &Config,
+    step: &AgentStep,
+    session: Option<(&SessionStore, &str)>,
+) -> anyhow::Result<()> {
+    let workspace = crate::utils::find_project_root()
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok());
+    let grants = session
+        .map(|(store, name)| store.confirmation_grants(name).unwrap_or_default())
+        .unwrap_or_default();
+    let manager = ConfirmationManager::new()
+        .with_rules(config.power_user.confirmations.clone(), workspace)
+        .with_grants(grants);
+
+    let risk = match step.risk_level {
+        AgentCommandRisk::InfoOnly | AgentCommandRisk::SafeOperations => Some(OperationRisk::Low),
+        AgentCommandRisk::NetworkAccess => Some(OperationRisk::Medium),
+        AgentCommandRisk::SystemChanges => Some(OperationRisk::High),
+        AgentCommandRisk::Destructive => Some(OperationRisk::Critical),
+        AgentCommandRisk::Unknown => None,
+    };
+    match manager.decide(&step.command, &step.command, risk) {
+        Confirmation::NotNeeded | Confirmation::Remembered(true) => Ok(()),
+        Confirmation::Remembered(false) => Err(anyhow::anyhow!(
+            "Command cancelled: '{}' was declined earlier in this session",
+            step.command
+        )),
+        Confirmation::Ask if !std::io::stdin().is_terminal() => {
+            approve_remotely(&config.security, step).await
+        }
+        Confirmation::Ask => {
+            println!("{}", format!("Command: {}", step.command).yellow());
+            let approved = shared::confirmation::ask_confirmation("Run this step?", false)?;
+            if let Some((store, name)) = session {
+                if shared::confirmation::ask_confirmation(
+                    "Remember this answer for the session?",
+                    false,
+                )? {
+                    let grant = ConfirmationGrant::new(&step.command, &step.command, approved);
+                    store.remember_confirmation(name, grant)?;
+                }
+            }
+            if approved {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!(
+                    "Command cancelled: '{}' was declined",
+                    step.command
+                ))
+            }
+        }
+    }
+}
+
+/// Hold an agent step until it is approved in the web UI, for runs with no
+/// terminal to confirm it at; an error when it is rejected or nobody answers
+/// in time
+pub async fn approve_remotely(config: &SecurityConfig, step: &AgentStep) -> anyhow::Result<()> {
+    let mut manager = ConfirmationManager::new();
+    manager.set_approval_timeout(Duration::from_secs(
+        config.agent_execution.approval_timeout_seconds,
+    ));
+    eprintln!(
+        "{}",
+        format!(
+            "Step '{}' is waiting for approval in the web UI (up to {}s)",
+            step.description, config.agent_execution.approval_timeout_seconds
+        )
+        .yellow()
+    );
+    if manager
+        .request_approval(&step.description, &step.command)
+        .await?
+    {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Command cancelled: '{}' was not approved",
+            step.command
+        ))
+    }
+}
+
 /// Present confirmation dialog for installation commands
 pub fn prompt_installation_confirmation(
     command: &str,
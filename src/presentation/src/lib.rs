@@ -4,6 +4,7 @@ pub mod analysis;
 pub mod cli;
 pub mod confirmation;
 pub mod editor;
+pub mod scenario;
 pub mod session;
 pub mod types;
 pub mod utils;
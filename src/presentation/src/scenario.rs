@@ -0,0 +1,382 @@
+//! End-to-end CLI scenarios
+//!
+//! A scenario is a YAML file describing one CLI invocation: the arguments, the
+//! files the workspace starts with, the answers typed at each prompt, the
+//! model's recorded replies and what the workspace must look like afterwards.
+//! Scenarios run the real `CliApp` in a scratch directory against a stub
+//! Ollama server, so interactive flows such as build review can be covered by
+//! `cargo test` without a model or a terminal. They run from their own test
+//! binary (`tests/scenarios.rs`) because they change the process's working
+//! directory and environment.
+//!
+//! ```yaml
+//! args: ["--build", "create hello.sh"]
+//! files:
+//!   README.md: "demo"
+//! inputs: ["", "y", "y", "/q"]
+//! responses:
+//!   - match: "FILE TO CREATE: hello.sh"
+//!     reply: "echo hello"
+//!   - match: "FILE CONTEXT (from filesystem scan)"
+//!     reply: '{"files": [{"path": "hello.sh", "action": "create", "reason": "entrypoint"}]}'
+//! expect:
+//!   files:
+//!     - path: hello.sh
+//!       contains: ["echo hello"]
+//!   absent: ["notes.txt"]
+//! ```
+//!
+//! Replies are served in order; one with `match` is only used for a prompt
+//! containing that text. Replies to prompts that ask for JSON, such as the
+//! file discovery above, must be valid JSON of the requested shape.
+
+use crate::cli::{Cli, CliApp};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use clap::Parser;
+use serde::Deserialize;
+use shared::types::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Width of the embeddings the stub server returns
+const STUB_EMBEDDING_DIMENSION: usize = 8;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    /// Defaults to the file name
+    #[serde(default)]
+    pub name: String,
+    /// Arguments after the program name
+    pub args: Vec<String>,
+    /// Files to create in the workspace before running, by relative path
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// Answers to prompts, in the order they are asked
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// Recorded model replies
+    #[serde(default)]
+    pub responses: Vec<RecordedResponse>,
+    /// Extra environment, e.g. `EDITOR` for flows that open an editor
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub expect: Expectations,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordedResponse {
+    /// Only reply to prompts containing this text
+    #[serde(default, rename = "match")]
+    pub matches: Option<String>,
+    pub reply: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Expectations {
+    #[serde(default)]
+    pub files: Vec<FileExpectation>,
+    /// Paths that must not exist afterwards
+    #[serde(default)]
+    pub absent: Vec<String>,
+    /// Whether the command itself should fail
+    #[serde(default)]
+    pub fails: bool,
+    /// Allow answers or replies to be left over
+    #[serde(default)]
+    pub allow_unused: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileExpectation {
+    pub path: String,
+    /// Exact content, ignoring surrounding whitespace
+    #[serde(default)]
+    pub equals: Option<String>,
+    #[serde(default)]
+    pub contains: Vec<String>,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut scenario: Scenario = serde_yaml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid scenario {}: {}", path.display(), e))?;
+        if scenario.name.is_empty() {
+            scenario.name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+        }
+        Ok(scenario)
+    }
+
+    /// Run the scenario and check its expectations, describing every mismatch
+    ///
+    /// Scenarios change the working directory and environment of the whole
+    /// process, so they must not run concurrently with each other.
+    pub async fn run(&self) -> Result<()> {
+        let workspace =
+            std::env::temp_dir().join(format!("bro-scenario-{}-{}", std::process::id(), self.name));
+        let _ = std::fs::remove_dir_all(&workspace);
+        let home = workspace.join(".home");
+        let project = workspace.join("project");
+        std::fs::create_dir_all(&home)?;
+        std::fs::create_dir_all(&project)?;
+        for (path, content) in &self.files {
+            let path = project.join(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, content)?;
+        }
+
+        let model = StubModel::start(self.responses.clone()).await?;
+        let mut env = vec![
+            ("OLLAMA_BASE_URL".to_string(), model.url.clone()),
+            ("HOME".to_string(), home.to_string_lossy().to_string()),
+            (
+                "XDG_CONFIG_HOME".to_string(),
+                home.join(".config").to_string_lossy().to_string(),
+            ),
+            (
+                "XDG_DATA_HOME".to_string(),
+                home.join(".local/share").to_string_lossy().to_string(),
+            ),
+        ];
+        env.extend(self.env.clone());
+        let saved_env = override_env(&env);
+        let original_dir = std::env::current_dir()?;
+        std::env::set_current_dir(&project)?;
+        shared::confirmation::script_answers(self.inputs.clone());
+
+        let outcome = match Cli::try_parse_from(
+            std::iter::once("bro".to_string()).chain(self.args.clone()),
+        ) {
+            Ok(cli) => CliApp::new().run(cli).await,
+            Err(e) => Err(anyhow::anyhow!("Invalid scenario arguments: {}", e)),
+        };
+
+        let unused_inputs = shared::confirmation::end_script();
+        std::env::set_current_dir(original_dir)?;
+        override_env(&saved_env);
+        model.stop();
+
+        let mut failures = Vec::new();
+        match (&outcome, self.expect.fails) {
+            (Err(e), false) => failures.push(format!("command failed: {}", e)),
+            (Ok(()), true) => {
+                failures.push("command succeeded but was expected to fail".to_string())
+            }
+            _ => {}
+        }
+        failures.extend(self.check_files(&project));
+        for prompt in model.unanswered() {
+            failures.push(format!(
+                "no recorded reply for prompt: {}",
+                prompt.lines().next().unwrap_or_default()
+            ));
+        }
+        if !self.expect.allow_unused {
+            if !unused_inputs.is_empty() {
+                failures.push(format!("inputs never asked for: {:?}", unused_inputs));
+            }
+            let unused_replies = model.unused();
+            if unused_replies > 0 {
+                failures.push(format!(
+                    "{} recorded replies never requested",
+                    unused_replies
+                ));
+            }
+        }
+
+        let _ = std::fs::remove_dir_all(&workspace);
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Scenario '{}' failed:\n  {}",
+                self.name,
+                failures.join("\n  ")
+            ))
+        }
+    }
+
+    fn check_files(&self, project: &Path) -> Vec<String> {
+        let mut failures = Vec::new();
+        for expected in &self.expect.files {
+            let Ok(content) = std::fs::read_to_string(project.join(&expected.path)) else {
+                failures.push(format!("{} was not created", expected.path));
+                continue;
+            };
+            if let Some(equals) = &expected.equals {
+                if content.trim() != equals.trim() {
+                    failures.push(format!(
+                        "{} has unexpected content:\n{}",
+                        expected.path, content
+                    ));
+                }
+            }
+            for needle in &expected.contains {
+                if !content.contains(needle.as_str()) {
+                    failures.push(format!("{} does not contain {:?}", expected.path, needle));
+                }
+            }
+        }
+        for path in &self.expect.absent {
+            if project.join(path).exists() {
+                failures.push(format!("{} should not exist", path));
+            }
+        }
+        failures
+    }
+}
+
+/// Load every `.yaml` scenario in `dir`, sorted by file name
+pub fn load_dir(dir: &Path) -> Result<Vec<Scenario>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml")
+        })
+        .collect();
+    paths.sort();
+    paths.iter().map(|path| Scenario::load(path)).collect()
+}
+
+/// Set each variable, returning the previous values so they can be restored
+fn override_env(vars: &[(String, String)]) -> Vec<(String, String)> {
+    vars.iter()
+        .map(|(key, value)| {
+            let previous = std::env::var(key).unwrap_or_default();
+            if value.is_empty() {
+                std::env::remove_var(key);
+            } else {
+                std::env::set_var(key, value);
+            }
+            (key.clone(), previous)
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct StubState {
+    replies: Vec<(RecordedResponse, bool)>,
+    unanswered: Vec<String>,
+}
+
+/// Ollama-compatible server replaying recorded responses
+struct StubModel {
+    url: String,
+    state: Arc<Mutex<StubState>>,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl StubModel {
+    async fn start(replies: Vec<RecordedResponse>) -> Result<Self> {
+        let state = Arc::new(Mutex::new(StubState {
+            replies: replies.into_iter().map(|reply| (reply, false)).collect(),
+            unanswered: Vec::new(),
+        }));
+        let app = Router::new()
+            .route("/api/chat", post(stub_chat))
+            .route("/api/embeddings", post(stub_embedding))
+            .route("/api/embed", post(stub_embed_batch))
+            .route("/api/show", post(stub_show))
+            .route("/api/tags", get(stub_tags))
+            .with_state(Arc::clone(&state));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Ok(Self { url, state, server })
+    }
+
+    fn unanswered(&self) -> Vec<String> {
+        self.state.lock().unwrap().unanswered.clone()
+    }
+
+    fn unused(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.replies.iter().filter(|(_, used)| !used).count()
+    }
+
+    fn stop(&self) {
+        self.server.abort();
+    }
+}
+
+async fn stub_chat(
+    State(state): State<Arc<Mutex<StubState>>>,
+    Json(request): Json<serde_json::Value>,
+) -> (StatusCode, String) {
+    let prompt = request["messages"]
+        .as_array()
+        .and_then(|messages| messages.last())
+        .and_then(|message| message["content"].as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let mut state = state.lock().unwrap();
+    let reply = state
+        .replies
+        .iter_mut()
+        .find(|(reply, used)| {
+            !used
+                && reply
+                    .matches
+                    .as_deref()
+                    .map_or(true, |needle| prompt.contains(needle))
+        })
+        .map(|(reply, used)| {
+            *used = true;
+            reply.reply.clone()
+        });
+    match reply {
+        Some(content) => {
+            let line = serde_json::json!({
+                "message": { "role": "assistant", "content": content },
+                "done": true,
+                "prompt_eval_count": 0,
+                "eval_count": 0,
+            });
+            (StatusCode::OK, format!("{}\n", line))
+        }
+        None => {
+            state.unanswered.push(prompt);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "no recorded reply for this prompt".to_string(),
+            )
+        }
+    }
+}
+
+async fn stub_embedding() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "embedding": vec![0.1f32; STUB_EMBEDDING_DIMENSION] }))
+}
+
+async fn stub_embed_batch(Json(request): Json<serde_json::Value>) -> Json<serde_json::Value> {
+    let count = request["input"].as_array().map_or(0, Vec::len);
+    Json(serde_json::json!({
+        "embeddings": vec![vec![0.1f32; STUB_EMBEDDING_DIMENSION]; count]
+    }))
+}
+
+async fn stub_show() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "parameters": "num_ctx 8192", "model_info": {} }))
+}
+
+/// Answers the `--build` capability probe
+async fn stub_tags() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "models": [] }))
+}
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use colored::Colorize;
+use shared::terminal;
 
 /// Handle deleting a session
 pub async fn handle_delete_session(
@@ -26,7 +27,7 @@ pub async fn handle_delete_session(
                 Ok(_) => {
                     println!(
                         "{} Session '{}' deleted successfully.",
-                        "✓".green(),
+                        terminal::icon("✓", "OK").green(),
                         session_name
                     );
                     // Export backup before deletion
@@ -39,7 +40,11 @@ pub async fn handle_delete_session(
                     }
                 }
                 Err(e) => {
-                    eprintln!("{} Failed to delete session: {}", "✗".red(), e);
+                    eprintln!(
+                        "{} Failed to delete session: {}",
+                        terminal::icon("✗", "X").red(),
+                        e
+                    );
                 }
             }
         }
@@ -47,7 +52,11 @@ pub async fn handle_delete_session(
             println!("{}", "Session deletion cancelled.".yellow());
         }
         Err(e) => {
-            eprintln!("{} Confirmation error: {}", "✗".red(), e);
+            eprintln!(
+                "{} Confirmation error: {}",
+                terminal::icon("✗", "X").red(),
+                e
+            );
         }
     }
 
@@ -116,17 +125,25 @@ pub async fn handle_continue_session(
                     *current_session = Some(target_session.clone());
                     println!(
                         "{} Created and activated session '{}'",
-                        "✓".green(),
+                        terminal::icon("✓", "OK").green(),
                         target_session.bright_green()
                     );
                 }
                 Err(e) => {
-                    eprintln!("{} Failed to create session: {}", "✗".red(), e);
+                    eprintln!(
+                        "{} Failed to create session: {}",
+                        terminal::icon("✗", "X").red(),
+                        e
+                    );
                 }
             }
         }
         Err(e) => {
-            eprintln!("{} Failed to load session: {}", "✗".red(), e);
+            eprintln!(
+                "{} Failed to load session: {}",
+                terminal::icon("✗", "X").red(),
+                e
+            );
         }
     }
 
@@ -159,7 +176,10 @@ pub async fn handle_undo(
     if repo_path.join(".git").exists() {
         match git_undo_last_commit().await {
             Ok(true) => {
-                println!("{} Undid last commit via git", "✓".green());
+                println!(
+                    "{} Undid last commit via git",
+                    terminal::icon("✓", "OK").green()
+                );
 
                 // Update session metadata - borrow store separately to avoid conflict
                 if let Some(store) = session_store {
@@ -20,8 +20,19 @@ use ratatui::{
 use crate::cli::{Cli, CliApp};
 use clap::Parser;
 use infrastructure::config::Config;
+use infrastructure::context_window::PromptBreakdown;
 use serde::{Deserialize, Serialize};
 
+#[path = "tui/compare.rs"]
+mod compare;
+#[path = "tui/palette.rs"]
+mod palette;
+#[path = "tui/timeline.rs"]
+mod timeline;
+pub use compare::run_compare_view;
+use palette::{Palette, PaletteAction};
+pub use timeline::run_timeline_view;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum IntentType {
@@ -187,6 +198,7 @@ pub struct TuiApp {
     history_index: Option<usize>,
     tui_mode: Option<String>, // Current TUI mode (plan, build, run, chat, etc.)
     pending_action: Option<PendingAction>, // Action confirmed but not yet executed
+    palette: Palette,
 
     // Agent loop state
     agent_status: AgentStatus,
@@ -300,6 +312,7 @@ impl TuiApp {
             tool_results: vec![],
             final_response: "Plan created successfully".to_string(),
             confidence: 0.87,
+            retries: Vec::new(),
         };
         self.current_plan = Some(mock_plan);
 
@@ -369,6 +382,7 @@ impl TuiApp {
             history_index: None,
             tui_mode: None,
             pending_action: None,
+            palette: Palette::default(),
             // Initialize agent state
             agent_status: AgentStatus {
                 phase: AgentPhase::Idle,
@@ -412,6 +426,13 @@ impl TuiRunner {
             // Handle events
             if event::poll(Duration::from_millis(100))? {
                 if let Event::Key(key) = event::read()? {
+                    // The open palette takes every key, whatever the mode
+                    if matches!(self.app.show_overlay, Some(Overlay::Palette)) {
+                        if self.handle_palette_key(key).await? {
+                            break;
+                        }
+                        continue;
+                    }
                     match self.app.current_mode {
                         TuiMode::Normal => {
                             if self.handle_normal_mode(key).await? {
@@ -670,8 +691,7 @@ impl TuiRunner {
             }
             KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 // Ctrl+P: Show command palette
-                self.app.show_overlay = Some(Overlay::Palette);
-                self.app.status_message = "PALETTE".to_string();
+                self.open_palette();
             }
             KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 // Ctrl+K: Show tools overlay
@@ -725,7 +745,7 @@ impl TuiRunner {
                             self.handle_context_overlay_key(c);
                         }
                         Overlay::Palette => {
-                            self.handle_palette_overlay_key(c);
+                            // Handled by handle_palette_key before mode keys
                         }
                         Overlay::Confirmation { .. } => {
                             self.handle_confirmation_overlay_key(c);
@@ -870,64 +890,77 @@ impl TuiRunner {
         }
     }
 
-    /// Handle palette overlay key events
-    fn handle_palette_overlay_key(&mut self, key: char) {
-        match key {
-            '1' => {
+    fn open_palette(&mut self) {
+        self.app.palette = Palette::default();
+        self.app.show_overlay = Some(Overlay::Palette);
+        self.app.status_message = "PALETTE".to_string();
+    }
+
+    /// Handle keys while the command palette is open: typing filters,
+    /// arrows move the selection and Enter runs it
+    async fn handle_palette_key(&mut self, key: event::KeyEvent) -> Result<bool> {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => {
                 self.app.show_overlay = None;
-                self.app.status_message = "Quit command executed".to_string();
-                // In a real implementation, this would trigger quit
+                self.app.status_message = "Ready".to_string();
             }
-            '2' => {
-                self.app.show_overlay = None;
-                self.app.status_message =
-                    "Help: i=insert, :q=quit, hjkl=navigate, Ctrl+P/N=history".to_string();
+            KeyCode::Up => self.app.palette.move_selection(false),
+            KeyCode::Char('p') if control => self.app.palette.move_selection(false),
+            KeyCode::Down | KeyCode::Tab => self.app.palette.move_selection(true),
+            KeyCode::Char('n') if control => self.app.palette.move_selection(true),
+            KeyCode::Backspace => self.app.palette.pop(),
+            KeyCode::Enter => {
+                if let Some(entry) = self.app.palette.chosen() {
+                    self.app.show_overlay = None;
+                    self.app.status_message = "Ready".to_string();
+                    return self.run_palette_action(entry.action).await;
+                }
             }
-            '3' => {
-                self.app.show_overlay = None;
-                self.app.status_message =
-                    "Session switch: use :session <name>. Current: default_session ".to_string();
+            KeyCode::Char(c) => self.app.palette.push(c),
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    /// Run an action chosen in the palette; true when it quits
+    async fn run_palette_action(&mut self, action: PaletteAction) -> Result<bool> {
+        match action {
+            PaletteAction::SwitchMode(mode) => {
+                self.switch_tui_mode(mode)?;
             }
-            '4' => {
-                self.app.show_overlay = None;
-                self.app.input_buffer.clear();
-                self.app.cursor_position = 0;
-                self.app.status_message = "Buffer cleared".to_string();
+            PaletteAction::VimCommand(command) => return self.execute_vim_command(command).await,
+            PaletteAction::ShowSessions => {
+                self.app.show_overlay = Some(Overlay::Sessions);
+                self.app.status_message = "SESSIONS".to_string();
             }
-            '5' => {
-                self.app.show_overlay = None;
-                self.app.status_message = format!(
-                    "Status: Mode={:?}, Session={:?}, History={} cmds",
-                    self.app.current_mode,
-                    self.app.current_session,
-                    self.app.command_history.len()
-                );
+            PaletteAction::ShowTools => {
+                self.app.show_overlay = Some(Overlay::Tools);
+                self.app.status_message = "TOOLS".to_string();
             }
-            '6' => {
-                self.app.show_overlay = None;
-                self.app.status_message = "Session saved. Goodbye!".to_string();
-                // In a real implementation, this would save and quit
+            PaletteAction::ShowContext => {
+                self.app.show_overlay = Some(Overlay::Context);
+                self.app.status_message = "CONTEXT".to_string();
             }
-            '7' => {
-                self.app.show_overlay = None;
-                self.app.status_message =
-                    "Mode switch: use :mode <plan|build|run|chat>. Current: normal ".to_string();
+            PaletteAction::InsertMode => {
+                self.app.current_mode = TuiMode::Insert;
+                self.app.status_message = "INSERT".to_string();
             }
-            '8' => {
-                self.app.show_overlay = None;
-                let history_preview: Vec<String> = self
-                    .app
-                    .command_history
-                    .iter()
-                    .rev()
-                    .take(5)
-                    .map(|cmd| format!("  {}", cmd))
-                    .collect();
-                self.app.status_message =
-                    format!("Recent history:\n{}", history_preview.join("\n"));
+            PaletteAction::CommandMode => {
+                self.app.current_mode = TuiMode::Command;
+                self.app.input_buffer.clear();
+                self.app.cursor_position = 0;
+                self.app.status_message = "COMMAND".to_string();
+            }
+            PaletteAction::PreviousCommand => self.navigate_history(true),
+            PaletteAction::Prefill(text) => {
+                self.app.input_buffer = text.to_string();
+                self.app.cursor_position = text.len();
+                self.app.current_mode = TuiMode::Insert;
+                self.app.status_message = "INSERT".to_string();
             }
-            _ => {}
         }
+        Ok(false)
     }
 
     /// Handle confirmation overlay key events
@@ -1075,6 +1108,9 @@ impl TuiRunner {
                     self.app.cursor_position += 1;
                 }
             }
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.open_palette();
+            }
             KeyCode::Char(c) => {
                 self.app.input_buffer.insert(self.app.cursor_position, c);
                 self.app.cursor_position += 1;
@@ -3099,13 +3135,18 @@ Output ONLY valid JSON, no other text."#;
     }
 
     /// Draw context overlay
-    fn draw_context_overlay(f: &mut Frame, area: Rect, _app: &TuiApp) {
+    fn draw_context_overlay(f: &mut Frame, area: Rect, app: &TuiApp) {
+        let breakdown = app.cli_app.last_prompt_breakdown();
+        let breakdown_height = breakdown
+            .as_ref()
+            .map_or(0, |breakdown| breakdown.rows().len() as u16 + 3);
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(2), // Header
-                Constraint::Min(1),    // Context list
-                Constraint::Length(2), // Footer
+                Constraint::Length(2),                // Header
+                Constraint::Min(1),                   // Context list
+                Constraint::Length(breakdown_height), // Last prompt
+                Constraint::Length(2),                // Footer
             ])
             .split(area);
 
@@ -3149,71 +3190,128 @@ Output ONLY valid JSON, no other text."#;
         let list = List::new(items).block(list_block);
         f.render_widget(list, chunks[1]);
 
+        if let Some(breakdown) = &breakdown {
+            Self::draw_prompt_breakdown(f, chunks[2], breakdown);
+        }
+
         // Footer
         let footer = Paragraph::new("Press number key to select, Esc to cancel")
             .alignment(Alignment::Center)
             .style(Style::default().fg(Color::Gray));
-        f.render_widget(footer, chunks[2]);
+        f.render_widget(footer, chunks[3]);
+    }
+
+    /// Tokens per part of the last prompt, each with a bar against the budget
+    fn draw_prompt_breakdown(f: &mut Frame, area: Rect, breakdown: &PromptBreakdown) {
+        let budget = breakdown.budget_tokens.max(1);
+        let bar_width = (area.width as usize).saturating_sub(50).clamp(4, 30);
+        let mut lines: Vec<Line> = breakdown
+            .rows()
+            .into_iter()
+            .map(|row| {
+                let filled = ((row.tokens * bar_width + budget - 1) / budget).min(bar_width);
+                let indent = if row.indented { "    " } else { "  " };
+                let width = 34 - indent.len();
+                let label: String = row.label.chars().take(width).collect();
+                Line::from(vec![
+                    Span::raw(format!("{}{:<width$}", indent, label, width = width)),
+                    Span::styled(
+                        format!("{:>7} ", row.tokens),
+                        Style::default().fg(Color::White),
+                    ),
+                    Span::styled(
+                        "█".repeat(filled),
+                        Style::default().fg(if row.indented {
+                            Color::Blue
+                        } else {
+                            Color::Cyan
+                        }),
+                    ),
+                    Span::styled(
+                        "░".repeat(bar_width - filled),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+            })
+            .collect();
+        if breakdown.dropped_chunks > 0 || breakdown.trimmed_chunks > 0 {
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "  {} chunk(s) left out, {} trimmed to fit",
+                    breakdown.dropped_chunks, breakdown.trimmed_chunks
+                ),
+                Style::default().fg(Color::Yellow),
+            )));
+        }
+        let title = format!(
+            "Last prompt: {} of {} tokens",
+            breakdown.total_tokens(),
+            breakdown.budget_tokens
+        );
+        let panel =
+            Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL));
+        f.render_widget(panel, area);
     }
 
     /// Draw command palette overlay
-    fn draw_palette_overlay(f: &mut Frame, area: Rect, _app: &TuiApp) {
+    fn draw_palette_overlay(f: &mut Frame, area: Rect, app: &TuiApp) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(2), // Header
-                Constraint::Min(1),    // Commands list
-                Constraint::Length(2), // Footer
+                Constraint::Length(3), // Query
+                Constraint::Min(1),    // Matching actions
+                Constraint::Length(1), // Footer
             ])
             .split(area);
 
-        // Header
-        let header_block = Block::default()
-            .title("Command Palette")
-            .borders(Borders::ALL);
-
-        let header = Paragraph::new("Quick commands and actions:")
-            .block(header_block)
-            .alignment(Alignment::Center);
-        f.render_widget(header, chunks[0]);
-
-        // Commands list
-        let list_block = Block::default().borders(Borders::ALL);
-
-        let commands = vec![
-            ("1", ":quit", "Exit the application"),
-            ("2", ":help", "Show help and keybindings"),
-            ("3", ":session <name>", "Switch to named session"),
-            ("4", ":clear", "Clear command buffer"),
-            ("5", ":status", "Show current status"),
-            ("6", ":wq", "Save session and quit"),
-            ("7", ":mode <type>", "Switch mode (plan/build/run/chat)"),
-            ("8", ":history", "Show full command history"),
-        ];
+        let query = Paragraph::new(Line::from(vec![
+            Span::styled("> ", Style::default().fg(Color::Cyan)),
+            Span::raw(app.palette.query.as_str()),
+        ]))
+        .block(
+            Block::default()
+                .title("Command Palette")
+                .borders(Borders::ALL),
+        );
+        f.render_widget(query, chunks[0]);
 
-        let items: Vec<ListItem> = commands
+        let matches = app.palette.matches();
+        let width = chunks[1].width.saturating_sub(2) as usize;
+        let items: Vec<ListItem> = matches
             .iter()
-            .map(|(num, cmd, desc)| {
+            .enumerate()
+            .map(|(i, entry)| {
+                let label = format!("{} ({})", entry.label, entry.category);
+                let padding = width.saturating_sub(label.chars().count() + entry.keys.len() + 2);
+                let style = if i == app.palette.selected {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::White)
+                };
                 ListItem::new(Line::from(vec![
-                    Span::styled(
-                        format!("{} {}", num, cmd),
-                        Style::default()
-                            .fg(Color::Cyan)
-                            .add_modifier(Modifier::BOLD),
-                    ),
-                    Span::styled(" - ", Style::default().fg(Color::Gray)),
-                    Span::styled(*desc, Style::default().fg(Color::White)),
+                    Span::styled(format!(" {}", label), style),
+                    Span::styled(" ".repeat(padding), style),
+                    Span::styled(format!("{} ", entry.keys), style.fg(Color::Gray)),
                 ]))
             })
             .collect();
+        let list_block = Block::default().borders(Borders::ALL);
+        if items.is_empty() {
+            let none = Paragraph::new("No matching actions")
+                .block(list_block)
+                .style(Style::default().fg(Color::Gray));
+            f.render_widget(none, chunks[1]);
+        } else {
+            f.render_widget(List::new(items).block(list_block), chunks[1]);
+        }
 
-        let list = List::new(items).block(list_block);
-        f.render_widget(list, chunks[1]);
-
-        // Footer
-        let footer = Paragraph::new("Press number key to execute, Esc to cancel")
-            .alignment(Alignment::Center)
-            .style(Style::default().fg(Color::Gray));
+        let footer =
+            Paragraph::new("Type to filter, Up/Down to select, Enter to run, Esc to close")
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(Color::Gray));
         f.render_widget(footer, chunks[2]);
     }
 
@@ -0,0 +1,159 @@
+//! Side-by-side model comparison for `bro --compare --tui`
+//!
+//! Every model streams into its own column at the same time, so the columns
+//! show how the answers differ while they are being written. Models share
+//! the machine while they run, so the timings favour the plain `--compare`,
+//! which runs them one after another, when exact numbers matter.
+
+use std::io::stdout;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use crossterm::{
+    cursor::Show,
+    event::{self, Event, KeyCode},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame, Terminal,
+};
+
+use infrastructure::model_comparison::{self, ModelRun};
+use infrastructure::ollama_client::OllamaClient;
+use shared::cancellation::CancellationToken;
+
+/// Stream `prompt` from every model in its own column until the user quits
+pub async fn run_compare_view(prompt: &str, models: &[String]) -> Result<()> {
+    let client = OllamaClient::new()?;
+    let cancel = CancellationToken::new();
+    let runs: Arc<Mutex<Vec<ModelRun>>> = Arc::new(Mutex::new(
+        models.iter().map(|m| ModelRun::new(m)).collect(),
+    ));
+
+    let tasks: Vec<_> = models
+        .iter()
+        .enumerate()
+        .map(|(column, model)| {
+            let (client, model, prompt) = (client.clone(), model.clone(), prompt.to_string());
+            let (cancel, runs) = (cancel.clone(), Arc::clone(&runs));
+            tokio::spawn(async move {
+                let streamed = Arc::clone(&runs);
+                let run = model_comparison::run_model(&client, &model, &prompt, &cancel, |chunk| {
+                    streamed.lock().unwrap()[column].output.push_str(chunk);
+                })
+                .await;
+                runs.lock().unwrap()[column] = run;
+            })
+        })
+        .collect();
+
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, Show)?;
+    terminal.clear()?;
+    let drawn = draw_until_quit(&mut terminal, prompt, &runs);
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, Show)?;
+    drawn?;
+
+    cancel.cancel();
+    for task in tasks {
+        let _ = task.await;
+    }
+    for run in runs.lock().unwrap().iter() {
+        match &run.error {
+            Some(e) => println!("{}: failed: {}", run.model, e),
+            None => println!("{}: {}", run.model, run.stats()),
+        }
+    }
+    Ok(())
+}
+
+fn draw_until_quit(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    prompt: &str,
+    runs: &Mutex<Vec<ModelRun>>,
+) -> Result<()> {
+    loop {
+        let snapshot = runs.lock().unwrap().clone();
+        terminal.draw(|f| draw(f, prompt, &snapshot))?;
+        if event::poll(Duration::from_millis(50))? {
+            if let Event::Key(key) = event::read()? {
+                if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn draw(f: &mut Frame, prompt: &str, runs: &[ModelRun]) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(3)])
+        .split(f.size());
+    let finished = runs.iter().filter(|run| run.done).count();
+    let status = if finished == runs.len() {
+        "done - q to quit".to_string()
+    } else {
+        format!("{}/{} finished - q to stop", finished, runs.len())
+    };
+    let header = Paragraph::new(Line::from(vec![
+        Span::styled(prompt, Style::default().add_modifier(Modifier::BOLD)),
+        Span::styled(
+            format!("  ({})", status),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ]))
+    .block(Block::default().borders(Borders::ALL).title("compare"));
+    f.render_widget(header, rows[0]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Ratio(1, runs.len().max(1) as u32);
+            runs.len()
+        ])
+        .split(rows[1]);
+    for (run, area) in runs.iter().zip(columns.iter()) {
+        draw_column(f, *area, run);
+    }
+}
+
+fn draw_column(f: &mut Frame, area: Rect, run: &ModelRun) {
+    let (footer, color) = match (&run.error, run.done) {
+        (Some(e), _) => (format!(" failed: {} ", e), Color::Red),
+        (None, true) => (format!(" {} ", run.stats()), Color::Green),
+        (None, false) => (" streaming... ".to_string(), Color::Yellow),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(Span::styled(
+            run.model.as_str(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ))
+        .title_bottom(Span::styled(footer, Style::default().fg(color)));
+
+    // Keep the newest text in view
+    let width = area.width.saturating_sub(2).max(1) as usize;
+    let height = area.height.saturating_sub(2) as usize;
+    let wrapped: usize = run
+        .output
+        .lines()
+        .map(|line| ((line.chars().count() + width - 1) / width).max(1))
+        .sum();
+    let scroll = wrapped.saturating_sub(height).min(u16::MAX as usize) as u16;
+
+    let body = Paragraph::new(run.output.as_str())
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0));
+    f.render_widget(body, area);
+}
@@ -0,0 +1,239 @@
+//! Command palette for the TUI
+//!
+//! Ctrl+P lists every action with the key that runs it directly. Typing
+//! narrows the list with fuzzy matching: the query's characters must appear
+//! in order, and runs of consecutive characters and word starts rank higher,
+//! so `sv` finds "Save session" and `bm` "Build mode".
+
+/// What choosing a palette entry does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteAction {
+    SwitchMode(&'static str),
+    /// A `:` command, without the colon
+    VimCommand(&'static str),
+    ShowSessions,
+    ShowTools,
+    ShowContext,
+    InsertMode,
+    CommandMode,
+    PreviousCommand,
+    /// Start a request with this text in insert mode
+    Prefill(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteEntry {
+    pub label: &'static str,
+    pub category: &'static str,
+    /// Keybinding or command running the action without the palette
+    pub keys: &'static str,
+    pub action: PaletteAction,
+}
+
+const fn entry(
+    label: &'static str,
+    category: &'static str,
+    keys: &'static str,
+    action: PaletteAction,
+) -> PaletteEntry {
+    PaletteEntry {
+        label,
+        category,
+        keys,
+        action,
+    }
+}
+
+/// Every action, in the order shown for an empty query
+pub const ENTRIES: &[PaletteEntry] = &[
+    entry(
+        "Plan mode",
+        "mode",
+        ":mode plan",
+        PaletteAction::SwitchMode("plan"),
+    ),
+    entry(
+        "Build mode",
+        "mode",
+        ":mode build",
+        PaletteAction::SwitchMode("build"),
+    ),
+    entry(
+        "Run mode",
+        "mode",
+        ":mode run",
+        PaletteAction::SwitchMode("run"),
+    ),
+    entry(
+        "Chat mode",
+        "mode",
+        ":mode chat",
+        PaletteAction::SwitchMode("chat"),
+    ),
+    entry(
+        "RAG mode",
+        "mode",
+        "Ctrl+K 5",
+        PaletteAction::SwitchMode("rag"),
+    ),
+    entry(
+        "Switch session",
+        "session",
+        "Ctrl+S",
+        PaletteAction::ShowSessions,
+    ),
+    entry(
+        "Save session",
+        "session",
+        ":w",
+        PaletteAction::VimCommand("w"),
+    ),
+    entry(
+        "Save session and quit",
+        "session",
+        ":wq",
+        PaletteAction::VimCommand("wq"),
+    ),
+    entry("Show context", "view", "Ctrl+O", PaletteAction::ShowContext),
+    entry("Show tools", "view", "Ctrl+K", PaletteAction::ShowTools),
+    entry(
+        "Show status",
+        "view",
+        ":status",
+        PaletteAction::VimCommand("status"),
+    ),
+    entry("Insert mode", "edit", "i", PaletteAction::InsertMode),
+    entry("Command line", "edit", ":", PaletteAction::CommandMode),
+    entry(
+        "Previous command",
+        "edit",
+        "Up",
+        PaletteAction::PreviousCommand,
+    ),
+    entry(
+        "Clear input",
+        "edit",
+        ":clear",
+        PaletteAction::VimCommand("clear"),
+    ),
+    entry("Read a file", "tool", "", PaletteAction::Prefill("read ")),
+    entry(
+        "Search files",
+        "tool",
+        "",
+        PaletteAction::Prefill("search for "),
+    ),
+    entry("Edit a file", "tool", "", PaletteAction::Prefill("edit ")),
+    entry("Help", "help", ":help", PaletteAction::VimCommand("help")),
+    entry("Quit", "app", ":q", PaletteAction::VimCommand("q")),
+];
+
+/// How well `query` matches `text`, or None when its characters do not all
+/// appear in order; case and spaces in the query are ignored
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut at = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = at + text[at..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 3;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 2;
+        }
+        previous = Some(found);
+        at = found + 1;
+    }
+    Some(score)
+}
+
+#[derive(Debug, Default)]
+pub struct Palette {
+    pub query: String,
+    pub selected: usize,
+}
+
+impl Palette {
+    /// Entries matching the query, best first; of equal matches the shorter
+    /// label comes first, then the list order
+    pub fn matches(&self) -> Vec<&'static PaletteEntry> {
+        if self.query.trim().is_empty() {
+            return ENTRIES.iter().collect();
+        }
+        let mut scored: Vec<_> = ENTRIES
+            .iter()
+            .filter_map(|entry| {
+                let text = format!("{} {} {}", entry.label, entry.category, entry.keys);
+                fuzzy_score(&self.query, &text).map(|score| (score, entry))
+            })
+            .collect();
+        scored.sort_by_key(|(score, entry)| (std::cmp::Reverse(*score), entry.label.len()));
+        scored.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    pub fn chosen(&self) -> Option<&'static PaletteEntry> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Move the selection, wrapping around the matches
+    pub fn move_selection(&mut self, down: bool) {
+        let count = self.matches().len();
+        if count == 0 {
+            return;
+        }
+        self.selected = if down {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(query: &str) -> Vec<&'static str> {
+        let palette = Palette {
+            query: query.to_string(),
+            selected: 0,
+        };
+        palette.matches().iter().map(|entry| entry.label).collect()
+    }
+
+    #[test]
+    fn test_fuzzy_matching_ranks_word_starts_and_runs() {
+        assert_eq!(fuzzy_score("bm", "Build mode"), Some(6));
+        assert_eq!(fuzzy_score("xyz", "Build mode"), None);
+        assert_eq!(labels("bm")[0], "Build mode");
+        assert_eq!(
+            labels("save")[..2],
+            ["Save session", "Save session and quit"]
+        );
+        assert_eq!(labels(":wq")[0], "Save session and quit");
+        assert_eq!(labels("").len(), ENTRIES.len());
+    }
+
+    #[test]
+    fn test_selection_wraps_and_resets_on_typing() {
+        let mut palette = Palette::default();
+        palette.move_selection(false);
+        assert_eq!(palette.chosen(), ENTRIES.last());
+        palette.push('q');
+        assert_eq!(palette.selected, 0);
+        assert_eq!(palette.chosen().unwrap().label, "Quit");
+    }
+}
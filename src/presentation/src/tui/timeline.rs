@@ -0,0 +1,151 @@
+//! Scrollable session timeline for `bro --timeline --tui`
+//!
+//! Events are listed oldest first under a header for each day; the details
+//! of the selected one (a plan's operations, an error, failing test output)
+//! are shown below the list.
+
+use std::io::stdout;
+
+use anyhow::Result;
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{self, Event, KeyCode},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame, Terminal,
+};
+
+use infrastructure::session_timeline::{self, TimelineEvent, TimelineKind};
+
+/// A line of the list: a day header or an event
+enum Row<'a> {
+    Day(String),
+    Event(&'a TimelineEvent),
+}
+
+/// Show the timeline of session `name` until the user quits
+pub fn run_timeline_view(name: &str, events: &[TimelineEvent]) -> Result<()> {
+    let rows: Vec<Row> = session_timeline::by_day(events)
+        .into_iter()
+        .flat_map(|(day, events)| {
+            std::iter::once(Row::Day(day.format("%A %Y-%m-%d").to_string()))
+                .chain(events.into_iter().map(Row::Event))
+        })
+        .collect();
+
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, Hide)?;
+    terminal.clear()?;
+    let drawn = browse(&mut terminal, name, &rows);
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, Show)?;
+    drawn
+}
+
+fn browse(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    name: &str,
+    rows: &[Row],
+) -> Result<()> {
+    let events: Vec<usize> = rows
+        .iter()
+        .enumerate()
+        .filter(|(_, row)| matches!(row, Row::Event(_)))
+        .map(|(i, _)| i)
+        .collect();
+    // Start at the newest event, the one usually looked for
+    let mut selected = events.len().saturating_sub(1);
+    let mut state = ListState::default();
+    loop {
+        state.select(events.get(selected).copied());
+        terminal.draw(|f| draw(f, name, rows, &mut state))?;
+        if let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => {
+                    selected = (selected + 1).min(events.len().saturating_sub(1))
+                }
+                KeyCode::PageUp => selected = selected.saturating_sub(10),
+                KeyCode::PageDown => selected = (selected + 10).min(events.len().saturating_sub(1)),
+                KeyCode::Home | KeyCode::Char('g') => selected = 0,
+                KeyCode::End | KeyCode::Char('G') => selected = events.len().saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn draw(f: &mut Frame, name: &str, rows: &[Row], state: &mut ListState) {
+    let areas = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(5), Constraint::Length(10)])
+        .split(f.size());
+
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|row| match row {
+            Row::Day(day) => ListItem::new(Line::from(Span::styled(
+                day.as_str(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ))),
+            Row::Event(event) => {
+                let kind_color = match event.kind {
+                    TimelineKind::Query => Color::Cyan,
+                    TimelineKind::Plan => Color::Blue,
+                    TimelineKind::Operation => Color::White,
+                    TimelineKind::Commit => Color::Magenta,
+                    TimelineKind::Test => Color::Yellow,
+                };
+                let status_color = match event.ok {
+                    Some(false) => Color::Red,
+                    _ => Color::Green,
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("  {}  ", event.local_time()),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(
+                        format!("{:<9}  ", event.kind.name()),
+                        Style::default().fg(kind_color),
+                    ),
+                    Span::styled(
+                        format!("{:<6}  ", event.status()),
+                        Style::default().fg(status_color),
+                    ),
+                    Span::raw(event.summary.as_str()),
+                ]))
+            }
+        })
+        .collect();
+    let title = format!("timeline: {} - j/k to move, q to quit", name);
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(list, areas[0], state);
+
+    let selected = state.selected().and_then(|i| match rows.get(i) {
+        Some(Row::Event(event)) => Some(*event),
+        _ => None,
+    });
+    let detail = match selected {
+        Some(event) => event
+            .detail
+            .clone()
+            .unwrap_or_else(|| event.summary.clone()),
+        None => "Nothing recorded yet".to_string(),
+    };
+    let details = Paragraph::new(detail)
+        .block(Block::default().borders(Borders::ALL).title("details"))
+        .wrap(Wrap { trim: false });
+    f.render_widget(details, areas[1]);
+}
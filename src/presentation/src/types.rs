@@ -1,3 +1,4 @@
+use infrastructure::structured_output::OutputSchema;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -50,7 +51,7 @@ pub enum CommandIntent {
 }
 
 /// Risk assessment for commands in agent execution
-#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum AgentCommandRisk {
     InfoOnly,       // Read-only queries (ls, pwd, cat)
     SafeOperations, // Safe operations (mkdir, echo, cp)
@@ -61,27 +62,99 @@ pub enum AgentCommandRisk {
 }
 
 /// Individual step in an agent execution plan
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct AgentStep {
     pub id: String,
     pub command: String,
     pub description: String,
     pub risk_level: AgentCommandRisk,
     pub estimated_duration: Option<String>,
+    #[serde(default)]
     pub dependencies: Vec<String>,
     pub rollback_command: Option<String>,
 }
 
 /// Complete agent execution plan
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct AgentPlan {
     pub steps: Vec<AgentStep>,
+    // The plan prompt asks for the shorter names
+    #[serde(alias = "estimated_total_time")]
     pub total_estimated_time: Option<String>,
+    #[serde(alias = "disk_impact")]
     pub total_disk_impact: Option<String>,
+    #[serde(default)]
     pub network_required: bool,
+    #[serde(default)]
     pub safety_concerns: Vec<String>,
 }
 
+impl OutputSchema for AgentPlan {
+    const NAME: &'static str = "agent plan";
+
+    fn json_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "required": ["steps"],
+            "properties": {
+                "steps": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "command", "description", "risk_level"],
+                        "properties": {
+                            "id": {"type": "string"},
+                            "command": {"type": "string"},
+                            "description": {"type": "string"},
+                            "risk_level": {
+                                "type": "string",
+                                "enum": [
+                                    "InfoOnly",
+                                    "SafeOperations",
+                                    "NetworkAccess",
+                                    "SystemChanges",
+                                    "Destructive"
+                                ]
+                            },
+                            "estimated_duration": {"type": ["string", "null"]},
+                            "dependencies": {"type": "array", "items": {"type": "string"}}
+                        }
+                    }
+                },
+                "estimated_total_time": {"type": ["string", "null"]},
+                "disk_impact": {"type": ["string", "null"]},
+                "network_required": {"type": "boolean"},
+                "safety_concerns": {"type": "array", "items": {"type": "string"}}
+            }
+        })
+    }
+
+    fn check(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut ids = std::collections::HashSet::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            if step.command.trim().is_empty() {
+                errors.push(format!("steps[{}].command is empty", i));
+            }
+            if !ids.insert(step.id.as_str()) {
+                errors.push(format!("steps[{}].id \"{}\" is used twice", i, step.id));
+            }
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            for dependency in &step.dependencies {
+                if !ids.contains(dependency.as_str()) {
+                    errors.push(format!(
+                        "steps[{}] depends on \"{}\", which is not a step id",
+                        i, dependency
+                    ));
+                }
+            }
+        }
+        errors
+    }
+}
+
 /// Risk assessment for commands
 #[derive(Debug, Clone, PartialEq)]
 pub enum CommandRisk {
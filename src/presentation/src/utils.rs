@@ -1,39 +1,7 @@
 use std::collections::HashSet;
 use std::hash::{DefaultHasher, Hash, Hasher};
 
-/// Find the project root by looking for common project files
-pub fn find_project_root() -> Option<String> {
-    let mut current = std::env::current_dir().ok()?;
-    loop {
-        // Check for various project indicators
-        let project_files = [
-            "Cargo.toml",       // Rust
-            "package.json",     // Node.js
-            "requirements.txt", // Python
-            "Pipfile",          // Python
-            "pyproject.toml",   // Python
-            "setup.py",         // Python
-            "Makefile",         // C/C++
-            "CMakeLists.txt",   // C/C++
-            "configure.ac",     // C/C++
-            "go.mod",           // Go
-            "Gemfile",          // Ruby
-            "composer.json",    // PHP
-            ".git",             // Git repo as fallback
-        ];
-
-        for file in &project_files {
-            if current.join(file).exists() {
-                return Some(current.display().to_string());
-            }
-        }
-
-        if !current.pop() {
-            break;
-        }
-    }
-    None
-}
+pub use infrastructure::config::find_project_root;
 
 /// Generate a cache suffix based on the project root
 pub fn project_cache_suffix() -> String {
@@ -299,21 +267,5 @@ pub fn keywords_from_text(text: &str) -> Vec<String> {
 
 /// Strip surrounding code fences/backticks to avoid emitting markdown into files
 pub fn strip_code_fences(code: &str) -> String {
-    let trimmed = code.trim();
-    if trimmed.starts_with("```") && trimmed.ends_with("```") {
-        let mut lines: Vec<&str> = trimmed.lines().collect();
-        if !lines.is_empty()
-            && lines
-                .first()
-                .map(|l| l.trim().starts_with("```"))
-                .unwrap_or(false)
-        {
-            lines.remove(0);
-        }
-        if !lines.is_empty() && lines.last().map(|l| l.trim() == "```").unwrap_or(false) {
-            lines.pop();
-        }
-        return lines.join("\n").trim().to_string();
-    }
-    trimmed.trim_matches('`').trim().to_string()
+    shared::output_cleanup::strip_code_fences(code)
 }
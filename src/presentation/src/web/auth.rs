@@ -0,0 +1,57 @@
+//! Bearer token check for the API
+//!
+//! When a `web-token` credential is set (`bro --auth token`), every API
+//! request except the health checks must send it as `Authorization: Bearer
+//! <token>`, or as a `token` query parameter for clients that cannot set
+//! headers such as WebSockets. Without a token the API is open to any web
+//! page the user visits, so handlers that approve or answer on the user's
+//! behalf refuse to run with [`ensure_token_set`].
+
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+
+use super::state::AppState;
+
+pub async fn require_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(expected) = &state.auth_token else {
+        return Ok(next.run(request).await);
+    };
+    let path = request.uri().path();
+    if path.ends_with("/health") || path.ends_with("/ready") {
+        return Ok(next.run(request).await);
+    }
+
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+    });
+    match bearer.or(query) {
+        // Hashes compare in constant time
+        Some(token) if blake3::hash(token.as_bytes()) == blake3::hash(expected.as_bytes()) => {
+            Ok(next.run(request).await)
+        }
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+/// `403 Forbidden` unless a token is configured
+pub fn ensure_token_set(state: &AppState) -> Result<(), StatusCode> {
+    match state.auth_token {
+        Some(_) => Ok(()),
+        None => Err(StatusCode::FORBIDDEN),
+    }
+}
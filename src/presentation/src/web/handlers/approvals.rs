@@ -0,0 +1,77 @@
+//! Destructive agent steps waiting for approval from the web UI
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use infrastructure::sandbox::approvals::PendingApproval;
+use serde::Deserialize;
+
+use crate::web::auth::ensure_token_set;
+use crate::web::state::AppState;
+
+/// Recorded as the source of decisions made through this API
+const WEB_SOURCE: &str = "web";
+
+#[derive(Debug, Deserialize)]
+pub struct ApprovalDecisionRequest {
+    pub approve: bool,
+}
+
+pub async fn list_approvals(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PendingApproval>>, StatusCode> {
+    state.approvals.list().map(Json).map_err(|e| {
+        tracing::error!("Failed to list approvals: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn get_approval(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<PendingApproval>, StatusCode> {
+    match state.approvals.get(&id) {
+        Ok(Some(pending)) => Ok(Json(pending)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+pub async fn decide_approval(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<ApprovalDecisionRequest>,
+) -> Result<Json<PendingApproval>, StatusCode> {
+    ensure_token_set(&state)?;
+    match state.approvals.get(&id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::BAD_REQUEST),
+    }
+    // Expired or already decided
+    state
+        .approvals
+        .decide(&id, request.approve, WEB_SOURCE)
+        .map(Json)
+        .map_err(|_| StatusCode::CONFLICT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use infrastructure::config::Config;
+
+    #[tokio::test]
+    async fn test_decisions_need_a_token() {
+        let state = AppState::minimal(Config::load());
+        let decided = decide_approval(
+            State(state),
+            Path("any".to_string()),
+            Json(ApprovalDecisionRequest { approve: true }),
+        )
+        .await;
+        assert_eq!(decided.unwrap_err(), StatusCode::FORBIDDEN);
+    }
+}
@@ -250,6 +250,8 @@ pub struct UpdateTailscaleRequest {
     pub enabled: bool,
     pub hostname: Option<String>,
     pub port: Option<u16>,
+    /// Tailnet hosts `bro --sync` replicates sessions with
+    pub sync_peers: Option<Vec<String>>,
 }
 
 pub async fn update_tailscale_config(
@@ -268,6 +270,9 @@ pub async fn update_tailscale_config(
     if let Some(hostname) = request.hostname {
         tailscale_settings.insert("hostname".to_string(), hostname);
     }
+    if let Some(peers) = request.sync_peers {
+        tailscale_settings.insert("sync_peers".to_string(), peers.join(","));
+    }
     let web_settings = config
         .power_user
         .plugins
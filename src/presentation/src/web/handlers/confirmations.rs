@@ -0,0 +1,94 @@
+//! Pending build confirmations, approved or rejected from the web UI
+
+use application::confirmation_queue::{Decision, PendingConfirmation};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::web::auth::ensure_token_set;
+use crate::web::state::AppState;
+
+/// Recorded as the source of decisions made through this API
+const WEB_SOURCE: &str = "web";
+
+#[derive(Debug, Deserialize)]
+pub struct PlanDecisionRequest {
+    pub approve: bool,
+    /// Apply the decision to every undecided operation as well
+    #[serde(default)]
+    pub all_operations: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OperationDecisionRequest {
+    pub approve: bool,
+}
+
+pub async fn list_confirmations(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PendingConfirmation>>, StatusCode> {
+    state.confirmations.list().map(Json).map_err(|e| {
+        tracing::error!("Failed to list confirmations: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn get_confirmation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<PendingConfirmation>, StatusCode> {
+    match state.confirmations.get(&id) {
+        Ok(Some(pending)) => Ok(Json(pending)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+pub async fn decide_confirmation(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<PlanDecisionRequest>,
+) -> Result<Json<PendingConfirmation>, StatusCode> {
+    ensure_token_set(&state)?;
+    ensure_pending(&state, &id)?;
+    state
+        .confirmations
+        .decide_plan(
+            &id,
+            Decision::from_approved(request.approve),
+            request.all_operations,
+            WEB_SOURCE,
+        )
+        .map(Json)
+        .map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+pub async fn decide_operation(
+    State(state): State<AppState>,
+    Path((id, index)): Path<(String, usize)>,
+    Json(request): Json<OperationDecisionRequest>,
+) -> Result<Json<PendingConfirmation>, StatusCode> {
+    ensure_token_set(&state)?;
+    ensure_pending(&state, &id)?;
+    state
+        .confirmations
+        .decide_operation(
+            &id,
+            index,
+            Decision::from_approved(request.approve),
+            WEB_SOURCE,
+        )
+        .map(Json)
+        .map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+fn ensure_pending(state: &AppState, id: &str) -> Result<(), StatusCode> {
+    match state.confirmations.get(id) {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
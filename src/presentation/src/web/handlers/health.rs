@@ -1,14 +1,18 @@
 //! Health check handlers
 
 use axum::{extract::State, Json};
+use infrastructure::adapters::speech_providers::ProviderKind;
 use serde_json::{json, Value};
 
 use crate::web::state::AppState;
 
-pub async fn health_check() -> Json<Value> {
+/// Liveness plus the audio provider checks run at startup; `degraded` if any failed
+pub async fn health_check(State(state): State<AppState>) -> Json<Value> {
+    let healthy = state.audio_health.iter().all(|health| health.healthy);
     Json(json!({
-        "status": "ok",
+        "status": if healthy { "ok" } else { "degraded" },
         "service": "bro",
+        "audio": *state.audio_health,
         "timestamp": chrono::Utc::now().to_rfc3339()
     }))
 }
@@ -17,7 +21,10 @@ pub async fn ready_check(State(state): State<AppState>) -> Json<Value> {
     let config = state.config.read().await;
     Json(json!({
         "status": "ready",
-        "voice_model_loaded": true,
+        "voice_model_loaded": state
+            .audio_health
+            .iter()
+            .any(|health| health.kind == ProviderKind::SpeechToText && health.healthy),
         "config_loaded": true,
         "tailscale_enabled": config.power_user.plugins.settings.get("tailscale").unwrap_or(&std::collections::HashMap::new()).get("enabled").unwrap_or(&"false".to_string()) == "true"
     }))
@@ -1,13 +1,23 @@
 //! Request handlers for the Axum server
 
+pub mod approvals;
 pub mod config;
+pub mod confirmations;
 pub mod dictation;
 pub mod health;
+pub mod questions;
+pub mod rag;
 pub mod remote;
+pub mod sync;
 pub mod tts;
 
+pub use approvals::*;
 pub use config::*;
+pub use confirmations::*;
 pub use dictation::*;
 pub use health::*;
+pub use questions::*;
+pub use rag::*;
 pub use remote::*;
+pub use sync::*;
 pub use tts::*;
@@ -0,0 +1,60 @@
+//! Questions the agent asks the user, answered from the web UI
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use infrastructure::user_questions::PendingQuestion;
+use serde::Deserialize;
+
+use crate::web::auth::ensure_token_set;
+use crate::web::state::AppState;
+
+/// Recorded as the source of answers given through this API
+const WEB_SOURCE: &str = "web";
+
+#[derive(Debug, Deserialize)]
+pub struct AnswerRequest {
+    /// The answer, or the number of one of the question's options
+    pub answer: String,
+}
+
+pub async fn list_questions(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PendingQuestion>>, StatusCode> {
+    state.questions.list().map(Json).map_err(|e| {
+        tracing::error!("Failed to list questions: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn get_question(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<PendingQuestion>, StatusCode> {
+    match state.questions.get(&id) {
+        Ok(Some(pending)) => Ok(Json(pending)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+pub async fn answer_question(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<AnswerRequest>,
+) -> Result<Json<PendingQuestion>, StatusCode> {
+    ensure_token_set(&state)?;
+    match state.questions.get(&id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::BAD_REQUEST),
+    }
+    // Expired, already answered or empty
+    state
+        .questions
+        .answer(&id, &request.answer, WEB_SOURCE)
+        .map(Json)
+        .map_err(|_| StatusCode::CONFLICT)
+}
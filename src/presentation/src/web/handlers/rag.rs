@@ -0,0 +1,226 @@
+//! RAG query handlers
+//!
+//! Latent for now: the routes are registered by `create_router`, but no binary
+//! starts the web server with a `rag_service` attached, so `rag_query` answers
+//! 503 until one does.
+
+use std::{convert::Infallible, future::Future};
+
+use application::rag_service::{RagAnswer, RagStreamEvent};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use shared::{cancellation::CancellationToken, types::Result};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::UnboundedReceiverStream, StreamExt};
+
+use crate::web::state::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct RagQueryRequest {
+    pub question: String,
+    #[serde(default)]
+    pub feedback: String,
+}
+
+/// Answer a question over the indexed codebase as a Server-Sent Events stream.
+///
+/// Emits `status` (`retrieving`, `generating`), `citations`, `token`, and a
+/// final `done` (answer plus its `sources`) or `error` event. Closing the
+/// connection stops generation.
+pub async fn rag_query(
+    State(state): State<AppState>,
+    Json(request): Json<RagQueryRequest>,
+) -> Response {
+    if request.question.trim().is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Question cannot be empty"
+            })),
+        )
+            .into_response();
+    }
+
+    let Some(rag_service) = state.rag_service.clone() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
+                "error": "RAG service is not available"
+            })),
+        )
+            .into_response();
+    };
+
+    answer_stream(move |sender| async move {
+        rag_service
+            .query_with_feedback_streaming_events(
+                &request.question,
+                &request.feedback,
+                |event| sender.send(event),
+                &sender.cancel,
+            )
+            .await
+    })
+}
+
+/// Forwards query events to the SSE stream, cancelling the query once the
+/// client has gone away
+#[derive(Clone)]
+struct EventSender {
+    tx: mpsc::UnboundedSender<Event>,
+    cancel: CancellationToken,
+}
+
+impl EventSender {
+    fn send(&self, event: RagStreamEvent) {
+        // A failed send means the client went away
+        if self.tx.send(stream_event(event)).is_err() {
+            self.cancel.cancel();
+        }
+    }
+
+    fn finish(self, result: Result<RagAnswer>) {
+        let final_event = match result {
+            Ok(answer) => Event::default().event("done").data(
+                json!({
+                    "answer": answer.answer,
+                    "sources": answer.citations,
+                    "cancelled": self.cancel.is_cancelled()
+                })
+                .to_string(),
+            ),
+            Err(e) => {
+                tracing::error!("RAG query failed: {}", e);
+                Event::default().event("error").data(e.to_string())
+            }
+        };
+        let _ = self.tx.send(final_event);
+    }
+}
+
+/// Run `query` in the background and stream its events, ending with `done` or
+/// `error`
+fn answer_stream<F, Fut>(query: F) -> Response
+where
+    F: FnOnce(EventSender) -> Fut,
+    Fut: Future<Output = Result<RagAnswer>> + Send + 'static,
+{
+    let (tx, rx) = mpsc::unbounded_channel::<Event>();
+    let sender = EventSender {
+        tx,
+        cancel: CancellationToken::new(),
+    };
+    let query = query(sender.clone());
+    tokio::spawn(async move {
+        let result = query.await;
+        sender.finish(result);
+    });
+
+    let stream = UnboundedReceiverStream::new(rx).map(Ok::<_, Infallible>);
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn stream_event(event: RagStreamEvent) -> Event {
+    match event {
+        RagStreamEvent::Retrieving => Event::default().event("status").data("retrieving"),
+        RagStreamEvent::Generating => Event::default().event("status").data("generating"),
+        RagStreamEvent::Citations(citations) => Event::default()
+            .event("citations")
+            .data(serde_json::to_string(&citations).unwrap_or_else(|_| "[]".to_string())),
+        RagStreamEvent::Token(token) => Event::default().event("token").data(token),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use application::rag_service::RagCitation;
+
+    /// `(event, data)` pairs of a finished SSE response
+    async fn events(response: Response) -> Vec<(String, String)> {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .split("\n\n")
+            .filter(|frame| !frame.is_empty())
+            .map(|frame| {
+                let field = |name: &str| {
+                    frame
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                (field("event: "), field("data: "))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_answer_streams_tokens_then_done_with_sources() {
+        let citation = RagCitation {
+            repo: None,
+            path: "src/lib.rs".to_string(),
+            offset: Some(0),
+            start_line: Some(1),
+            end_line: Some(3),
+            score: None,
+            symbol: None,
+            license: None,
+        };
+        let citations = vec![citation];
+        let response = answer_stream(move |sender| async move {
+            sender.send(RagStreamEvent::Retrieving);
+            sender.send(RagStreamEvent::Citations(citations.clone()));
+            sender.send(RagStreamEvent::Generating);
+            sender.send(RagStreamEvent::Token("Hello".to_string()));
+            sender.send(RagStreamEvent::Token(" world".to_string()));
+            Ok(RagAnswer {
+                answer: "Hello world".to_string(),
+                citations,
+            })
+        });
+
+        let events = events(response).await;
+        let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["status", "citations", "status", "token", "token", "done"]
+        );
+        assert_eq!(events[3].1, "Hello");
+        let done: serde_json::Value = serde_json::from_str(&events[5].1).unwrap();
+        assert_eq!(done["answer"], "Hello world");
+        assert_eq!(done["sources"][0]["path"], "src/lib.rs");
+        assert_eq!(done["cancelled"], false);
+    }
+
+    #[tokio::test]
+    async fn test_failed_query_ends_with_error() {
+        let response = answer_stream(|sender| async move {
+            sender.send(RagStreamEvent::Retrieving);
+            Err(anyhow::anyhow!("index missing"))
+        });
+
+        let events = events(response).await;
+        assert_eq!(
+            events,
+            [
+                ("status".to_string(), "retrieving".to_string()),
+                ("error".to_string(), "index missing".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Session sync handlers for peers on the tailnet
+//!
+//! A peer fetches this device's bundle of a project's sessions and caches,
+//! applies it, then posts back the result for this device to apply. Sessions
+//! are private, so sync is refused unless the server requires a token.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use infrastructure::session_store::SessionStore;
+use infrastructure::session_sync::{project_root, SyncBundle, SyncJournal};
+use serde::Deserialize;
+use serde_json::json;
+use shared::platform;
+use std::path::PathBuf;
+
+use crate::web::state::AppState;
+
+/// Largest bundle a peer may post; caches with embeddings are far above the
+/// default request limit
+pub const MAX_SYNC_BUNDLE_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct SyncQuery {
+    /// Project root relative to the home directory
+    pub project: String,
+}
+
+pub async fn get_sync_bundle(
+    State(state): State<AppState>,
+    Query(query): Query<SyncQuery>,
+) -> Response {
+    let root = match sync_root(&state, &query.project) {
+        Ok(root) => root,
+        Err(response) => return *response,
+    };
+    let bundle = SessionStore::new(&root.to_string_lossy())
+        .and_then(|store| SyncBundle::collect(&store, &query.project, &platform::app_data_dir()));
+    match bundle {
+        Ok(bundle) => Json(bundle).into_response(),
+        Err(e) => sync_error(StatusCode::SERVICE_UNAVAILABLE, e.to_string()),
+    }
+}
+
+pub async fn apply_sync_bundle(
+    State(state): State<AppState>,
+    Json(bundle): Json<SyncBundle>,
+) -> Response {
+    let root = match sync_root(&state, &bundle.project) {
+        Ok(root) => root,
+        Err(response) => return *response,
+    };
+    let report = SessionStore::new(&root.to_string_lossy()).and_then(|store| {
+        bundle.apply(
+            &store,
+            &platform::app_data_dir(),
+            &SyncJournal::for_project(&root),
+        )
+    });
+    match report {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => sync_error(StatusCode::SERVICE_UNAVAILABLE, e.to_string()),
+    }
+}
+
+/// This device's root of `project`, once sync is allowed at all
+fn sync_root(state: &AppState, project: &str) -> Result<PathBuf, Box<Response>> {
+    if state.auth_token.is_none() {
+        return Err(Box::new(sync_error(
+            StatusCode::FORBIDDEN,
+            "Sync needs a web token; run `bro --auth token` and set the same one on every device"
+                .to_string(),
+        )));
+    }
+    let root = project_root(project)
+        .map_err(|e| Box::new(sync_error(StatusCode::BAD_REQUEST, e.to_string())))?;
+    if !root.is_dir() {
+        return Err(Box::new(sync_error(
+            StatusCode::NOT_FOUND,
+            format!("No project at {} on this device", root.display()),
+        )));
+    }
+    Ok(root)
+}
+
+fn sync_error(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
@@ -6,13 +6,17 @@
 //! - `handlers` - Request handlers organized by feature
 //! - `extractors` - Custom extractors for request parsing
 
+pub mod auth;
 pub mod handlers;
 pub mod routes;
 pub mod state;
 
 use anyhow::Result;
+use application::rag_service::RagService;
 use application::voice_command_processor::VoiceCommandProcessor;
+use infrastructure::adapters::speech_providers::SpeechProviderRegistry;
 use infrastructure::config::Config;
+use infrastructure::credentials::{CredentialStore, WEB_TOKEN};
 use state::AppState;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -29,9 +33,26 @@ impl AxumServer {
         }
     }
 
-    pub async fn run(self, port: u16) -> Result<()> {
+    pub fn with_rag_service(mut self, rag_service: Arc<RagService>) -> Self {
+        self.state = self.state.with_rag_service(rag_service);
+        self
+    }
+
+    pub async fn run(mut self, port: u16) -> Result<()> {
         let config = self.state.config.read().await;
 
+        let audio_health = SpeechProviderRegistry::with_builtin()
+            .check_health(&config.power_user.audio)
+            .await;
+        for health in audio_health.iter().filter(|health| !health.healthy) {
+            tracing::error!(
+                "{} provider '{}' is unavailable: {}",
+                health.kind.label(),
+                health.provider,
+                health.message
+            );
+        }
+
         let tailscale_enabled = config
             .power_user
             .plugins
@@ -58,7 +79,21 @@ impl AxumServer {
             SocketAddr::from(([127, 0, 0, 1], port))
         };
 
+        let credentials = CredentialStore::new(config.power_user.credentials.clone());
         drop(config);
+        self.state.audio_health = Arc::new(audio_health);
+        match credentials.get(WEB_TOKEN).await {
+            Ok(Some(token)) => self.state.auth_token = Some(Arc::new(token)),
+            Ok(None) if !addr.ip().is_loopback() => {
+                tracing::warn!(
+                    "Serving on {} without a token; run `bro --auth token` to require one",
+                    addr
+                );
+            }
+            Ok(None) => {}
+            // Never serve without the token the user asked for
+            Err(e) => return Err(anyhow::anyhow!("Cannot read the web server token: {}", e)),
+        }
 
         let app = routes::create_router(self.state);
 
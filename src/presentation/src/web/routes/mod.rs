@@ -1,6 +1,8 @@
 //! Route definitions for the Axum server
 
 use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{delete, get, post, put},
     Router,
 };
@@ -10,7 +12,7 @@ use tower_http::{
     trace::TraceLayer,
 };
 
-use super::{handlers, state::AppState};
+use super::{auth, handlers, state::AppState};
 
 pub fn create_router(state: AppState) -> Router {
     let api_routes = Router::new()
@@ -38,9 +40,34 @@ pub fn create_router(state: AppState) -> Router {
         .route("/scripts/:id", get(handlers::get_script))
         .route("/scripts/:id", put(handlers::update_script))
         .route("/scripts/:id", delete(handlers::delete_script))
+        // Build confirmation endpoints
+        .route("/confirmations", get(handlers::list_confirmations))
+        .route("/confirmations/:id", get(handlers::get_confirmation))
+        .route("/confirmations/:id", post(handlers::decide_confirmation))
+        .route(
+            "/confirmations/:id/operations/:index",
+            post(handlers::decide_operation),
+        )
+        // Approval endpoints for destructive agent steps
+        .route("/approvals", get(handlers::list_approvals))
+        .route("/approvals/:id", get(handlers::get_approval))
+        .route("/approvals/:id", post(handlers::decide_approval))
+        // Questions the agent asks the user
+        .route("/questions", get(handlers::list_questions))
+        .route("/questions/:id", get(handlers::get_question))
+        .route("/questions/:id", post(handlers::answer_question))
+        // RAG endpoints
+        .route("/rag/query", post(handlers::rag_query))
         // Tailscale endpoints
         .route("/tailscale/status", get(handlers::get_tailscale_status))
         .route("/tailscale/config", post(handlers::update_tailscale_config))
+        // Session sync endpoints for tailnet peers
+        .route("/sync/bundle", get(handlers::get_sync_bundle))
+        .route(
+            "/sync/bundle",
+            post(handlers::apply_sync_bundle)
+                .layer(DefaultBodyLimit::max(handlers::MAX_SYNC_BUNDLE_BYTES)),
+        )
         // TTS endpoints
         .route("/tts/speak", post(handlers::speak))
         .route("/voice/test", post(handlers::test_voice))
@@ -57,7 +84,11 @@ pub fn create_router(state: AppState) -> Router {
         .route("/dictation/insert", post(handlers::insert_dictation))
         .route("/dictation/type", post(handlers::type_dictation))
         .route("/dictation/backspace", post(handlers::backspace_dictation))
-        .route("/dictation/test-keyboard", get(handlers::test_keyboard));
+        .route("/dictation/test-keyboard", get(handlers::test_keyboard))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_token,
+        ));
 
     // For SPA: serve static files, but fallback to index.html for client-side routing
     let serve_dir = ServeDir::new("frontend/dist")
@@ -1,7 +1,12 @@
 //! Application state for the Axum server
 
+use application::confirmation_queue::ConfirmationQueue;
+use application::rag_service::RagService;
 use application::voice_command_processor::VoiceCommandProcessor;
+use infrastructure::adapters::speech_providers::ProviderHealth;
 use infrastructure::config::Config;
+use infrastructure::sandbox::approvals::ApprovalQueue;
+use infrastructure::user_questions::QuestionQueue;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -10,6 +15,17 @@ use tokio::sync::RwLock;
 pub struct AppState {
     pub voice_processor: Option<Arc<VoiceCommandProcessor>>,
     pub config: Arc<RwLock<Config>>,
+    pub rag_service: Option<Arc<RagService>>,
+    /// Audio provider health checked when the server starts
+    pub audio_health: Arc<Vec<ProviderHealth>>,
+    /// Build plans waiting for approval from a CLI session
+    pub confirmations: Arc<ConfirmationQueue>,
+    /// Destructive agent steps waiting for approval
+    pub approvals: Arc<ApprovalQueue>,
+    /// Questions the agent waits for the user to answer
+    pub questions: Arc<QuestionQueue>,
+    /// Token API requests must carry, when one is set
+    pub auth_token: Option<Arc<String>>,
 }
 
 impl AppState {
@@ -17,6 +33,12 @@ impl AppState {
         Self {
             voice_processor,
             config: Arc::new(RwLock::new(config)),
+            rag_service: None,
+            audio_health: Arc::new(Vec::new()),
+            confirmations: Arc::new(ConfirmationQueue::open_default()),
+            approvals: Arc::new(ApprovalQueue::open_default()),
+            questions: Arc::new(QuestionQueue::open_default()),
+            auth_token: None,
         }
     }
 
@@ -27,6 +49,18 @@ impl AppState {
         Self {
             voice_processor: None,
             config: Arc::new(RwLock::new(config)),
+            rag_service: None,
+            audio_health: Arc::new(Vec::new()),
+            confirmations: Arc::new(ConfirmationQueue::open_default()),
+            approvals: Arc::new(ApprovalQueue::open_default()),
+            questions: Arc::new(QuestionQueue::open_default()),
+            auth_token: None,
         }
     }
+
+    /// Attach an indexed RAG service so `/api/rag/query` can answer questions
+    pub fn with_rag_service(mut self, rag_service: Arc<RagService>) -> Self {
+        self.rag_service = Some(rag_service);
+        self
+    }
 }
@@ -0,0 +1,17 @@
+//! Runs every scenario in `tests/scenarios`, alone in this test binary since
+//! scenarios change the process's working directory and environment
+
+use presentation::scenario::load_dir;
+use std::path::Path;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn scenarios() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios");
+    let mut failures = Vec::new();
+    for scenario in load_dir(&dir).unwrap() {
+        if let Err(e) = scenario.run().await {
+            failures.push(e.to_string());
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
@@ -0,0 +1,115 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use tokio::sync::Notify;
+
+/// Cooperative cancellation signal shared between a long-running operation and
+/// whoever may abort it (Ctrl+C handler, TUI key binding, web abort request).
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        if !self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            self.inner.notify.notify_waiters();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once `cancel` has been called (immediately if it already was)
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+static INTERRUPT_TARGET: Mutex<Option<CancellationToken>> = Mutex::new(None);
+static INTERRUPT_HANDLER: Once = Once::new();
+
+/// Route Ctrl+C to `token` until the returned guard is dropped.
+///
+/// Installing a signal handler replaces the default SIGINT behaviour for the
+/// whole process, so when no token is registered Ctrl+C exits with status 130
+/// just like an unhandled interrupt would. Must be called inside a Tokio runtime.
+pub fn cancel_on_interrupt(token: &CancellationToken) -> InterruptGuard {
+    INTERRUPT_HANDLER.call_once(|| {
+        tokio::spawn(async {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                let target = INTERRUPT_TARGET.lock().ok().and_then(|mut t| t.take());
+                match target {
+                    Some(token) => token.cancel(),
+                    None => std::process::exit(130),
+                }
+            }
+        });
+    });
+
+    if let Ok(mut target) = INTERRUPT_TARGET.lock() {
+        *target = Some(token.clone());
+    }
+    InterruptGuard { _private: () }
+}
+
+/// Restores default Ctrl+C handling when dropped
+pub struct InterruptGuard {
+    _private: (),
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        if let Ok(mut target) = INTERRUPT_TARGET.lock() {
+            *target = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancelled_resolves_after_cancel() {
+        let token = CancellationToken::new();
+        let waiter = token.clone();
+
+        let handle = tokio::spawn(async move { waiter.cancelled().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!token.is_cancelled());
+
+        token.cancel();
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("cancelled() should resolve")
+            .unwrap();
+        assert!(token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_resolves_immediately_when_already_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+        tokio::time::timeout(Duration::from_millis(100), token.cancelled())
+            .await
+            .expect("already-cancelled token should resolve immediately");
+    }
+}
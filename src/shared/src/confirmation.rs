@@ -1,8 +1,44 @@
 use crate::types::Result;
 use colored::Colorize;
-use crossterm::event::{read, Event, KeyCode};
+use crossterm::event::{poll, read, Event, KeyCode};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use dialoguer::console::Term;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often a waiting prompt checks for a decision made elsewhere
+const REMOTE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Answers given to prompts instead of the terminal, set by scenario runs
+static SCRIPTED_ANSWERS: Mutex<Option<VecDeque<String>>> = Mutex::new(None);
+
+/// Answer the following prompts from `answers`, in order, instead of reading
+/// the terminal. Once they run out, prompts fail rather than block on stdin.
+pub fn script_answers(answers: impl IntoIterator<Item = String>) {
+    *SCRIPTED_ANSWERS.lock().unwrap() = Some(answers.into_iter().collect());
+}
+
+/// Return to reading the terminal; yields the answers that were never asked for
+pub fn end_script() -> Vec<String> {
+    SCRIPTED_ANSWERS
+        .lock()
+        .unwrap()
+        .take()
+        .map(Vec::from)
+        .unwrap_or_default()
+}
+
+/// Next scripted answer for `prompt`, or `None` when prompts are interactive
+pub fn scripted_answer(prompt: &str) -> Option<Result<String>> {
+    let mut answers = SCRIPTED_ANSWERS.lock().unwrap();
+    let queue = answers.as_mut()?;
+    Some(
+        queue.pop_front().ok_or_else(|| {
+            anyhow::anyhow!("No scripted answer left for prompt: {}", prompt.trim())
+        }),
+    )
+}
 
 /// Standardized confirmation prompt used across binaries.
 /// Returns immediately on single keypress: y/Y, n/N, or Enter for default.
@@ -12,6 +48,15 @@ pub fn ask_confirmation(prompt: &str, default_yes: bool) -> Result<bool> {
     term.write_str(&format!("{prompt} {default_hint} "))?;
     term.flush()?;
 
+    if let Some(answer) = scripted_answer(prompt) {
+        let result = match answer?.trim().to_lowercase().as_str() {
+            "" => default_yes,
+            answer => answer.starts_with('y'),
+        };
+        term.write_line(if result { "y" } else { "n" })?;
+        return Ok(result);
+    }
+
     enable_raw_mode()?;
     let result = loop {
         match read()? {
@@ -57,12 +102,41 @@ impl ConfirmationChoice {
 
 /// Advanced confirmation prompt with multiple choice options
 pub fn ask_enhanced_confirmation(prompt: &str) -> Result<ConfirmationChoice> {
+    ask_enhanced_confirmation_with_remote(prompt, || None)
+}
+
+/// Like [`ask_enhanced_confirmation`], but also returns as soon as `remote`
+/// yields a choice, e.g. a plan approved from the web UI while the terminal waits
+pub fn ask_enhanced_confirmation_with_remote(
+    prompt: &str,
+    mut remote: impl FnMut() -> Option<ConfirmationChoice>,
+) -> Result<ConfirmationChoice> {
     let term = Term::stdout();
     term.write_str(&format!("{prompt} [y/n/edit/revise/suggest] "))?;
     term.flush()?;
 
+    if let Some(answer) = scripted_answer(prompt) {
+        let result = match answer?.trim().to_lowercase().chars().next() {
+            Some('y') => ConfirmationChoice::Yes,
+            Some('e') => ConfirmationChoice::Edit,
+            Some('r') => ConfirmationChoice::Revise,
+            Some('s') => ConfirmationChoice::Suggest,
+            _ => ConfirmationChoice::No,
+        };
+        term.write_line(result.as_str())?;
+        return Ok(result);
+    }
+
     enable_raw_mode()?;
+    let mut remote_choice = false;
     let result = loop {
+        if !poll(REMOTE_POLL_INTERVAL)? {
+            if let Some(choice) = remote() {
+                remote_choice = true;
+                break choice;
+            }
+            continue;
+        }
         match read()? {
             Event::Key(key) => match key.code {
                 KeyCode::Char('y') | KeyCode::Char('Y') => break ConfirmationChoice::Yes,
@@ -87,7 +161,41 @@ pub fn ask_enhanced_confirmation(prompt: &str) -> Result<ConfirmationChoice> {
         ConfirmationChoice::Suggest => ("suggest".bright_cyan(), true),
     };
 
-    term.write_line(&selection.to_string())?;
+    if remote_choice {
+        term.write_line(&format!("{} {}", selection, "(remote)".dimmed()))?;
+    } else {
+        term.write_line(&selection.to_string())?;
+    }
+
+    Ok(result)
+}
+
+/// Wait until `remote` yields a value; any key pressed in the terminal stops
+/// waiting and returns `None` so the caller can prompt locally instead
+pub fn wait_for_remote<T>(
+    message: &str,
+    mut remote: impl FnMut() -> Option<T>,
+) -> Result<Option<T>> {
+    let term = Term::stdout();
+    term.write_line(&format!(
+        "{} {}",
+        message,
+        "(press any key to answer here)".dimmed()
+    ))?;
+
+    enable_raw_mode()?;
+    let result = loop {
+        if poll(REMOTE_POLL_INTERVAL)? {
+            if let Event::Key(_) = read()? {
+                break None;
+            }
+            continue;
+        }
+        if let Some(value) = remote() {
+            break Some(value);
+        }
+    };
+    disable_raw_mode()?;
 
     Ok(result)
 }
@@ -1,12 +1,19 @@
 pub mod batch_processing;
+pub mod cancellation;
 pub mod confirmation;
 pub mod content_sanitizer;
 pub mod error;
 pub mod memory_pool;
+pub mod output_cleanup;
 pub mod performance;
 pub mod performance_monitor;
+pub mod pii_detector;
+pub mod platform;
+pub mod prompt_redaction;
+pub mod secrets_baseline;
 pub mod secrets_detector;
 pub mod telemetry;
+pub mod terminal;
 pub mod types;
 pub mod ultra_fast_cache;
 pub mod ultra_fast_memory;
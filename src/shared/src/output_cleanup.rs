@@ -0,0 +1,268 @@
+//! Cleanup of model output before it becomes file content
+//!
+//! Models wrap generated files in markdown even when told not to: a fence
+//! with a language tag, a sentence of introduction before it, an explanation
+//! after it, sometimes a bare language name on the first line or a byte order
+//! mark. [`clean_file_content`] removes all of that. Markdown files only lose
+//! a fence around the whole file, since fences inside them are content.
+
+use std::path::Path;
+
+const BOM: char = '\u{feff}';
+
+/// Language names models put on a line of their own, as if opening a fence
+const LANGUAGE_TAGS: &[&str] = &[
+    "bash",
+    "c",
+    "c++",
+    "cpp",
+    "css",
+    "dockerfile",
+    "go",
+    "html",
+    "java",
+    "javascript",
+    "js",
+    "json",
+    "jsx",
+    "kotlin",
+    "lua",
+    "make",
+    "makefile",
+    "php",
+    "plaintext",
+    "py",
+    "python",
+    "rb",
+    "ruby",
+    "rs",
+    "rust",
+    "sh",
+    "shell",
+    "sql",
+    "swift",
+    "text",
+    "toml",
+    "ts",
+    "tsx",
+    "typescript",
+    "xml",
+    "yaml",
+    "yml",
+    "zsh",
+];
+
+/// Strip a fence around the whole of `text`, or stray backticks around it.
+/// For displaying snippets; use [`clean_file_content`] for file content.
+pub fn strip_code_fences(text: &str) -> String {
+    let trimmed = text.trim_start_matches(BOM).trim();
+    let lines: Vec<&str> = trimmed.lines().collect();
+    match outer_fence(&lines) {
+        Some(inner) => inner.join("\n").trim().to_string(),
+        None => trimmed.trim_matches('`').trim().to_string(),
+    }
+}
+
+/// Generated content for the file at `path` with markdown artifacts and
+/// commentary removed, ending in a single newline (empty when nothing is
+/// left). Keeps the CRLF line endings of content that uses them.
+pub fn clean_file_content(content: &str, path: Option<&Path>) -> String {
+    let line_ending = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let lines: Vec<&str> = content
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .collect();
+
+    let mut body = if path.is_some_and(is_markdown) {
+        outer_fence(&lines).unwrap_or(&lines)
+    } else {
+        first_fenced_block(&lines).unwrap_or(&lines)
+    };
+    while let Some((first, rest)) = body.split_first() {
+        if bare(first).is_empty() || is_language_tag(first) {
+            body = rest;
+        } else {
+            break;
+        }
+    }
+    while let Some((last, rest)) = body.split_last() {
+        if bare(last).is_empty() {
+            body = rest;
+        } else {
+            break;
+        }
+    }
+
+    let Some((first, rest)) = body.split_first() else {
+        return String::new();
+    };
+    let mut cleaned = first.trim_start_matches(BOM).to_string();
+    for line in rest {
+        cleaned.push_str(line_ending);
+        cleaned.push_str(line);
+    }
+    cleaned.push_str(line_ending);
+    cleaned
+}
+
+/// A line without surrounding whitespace and byte order marks
+fn bare(line: &str) -> &str {
+    line.trim_matches(|c: char| c.is_whitespace() || c == BOM)
+}
+
+fn is_fence(line: &str) -> bool {
+    let line = bare(line);
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+fn is_language_tag(line: &str) -> bool {
+    let line = bare(line).to_lowercase();
+    LANGUAGE_TAGS.contains(&line.as_str())
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ["md", "markdown", "mdx"]
+                .iter()
+                .any(|md| ext.eq_ignore_ascii_case(md))
+        })
+}
+
+/// Lines inside a fence that encloses all non-blank lines
+fn outer_fence<'a>(lines: &'a [&'a str]) -> Option<&'a [&'a str]> {
+    let first = lines.iter().position(|line| !bare(line).is_empty())?;
+    let last = lines.iter().rposition(|line| !bare(line).is_empty())?;
+    let closes = matches!(bare(lines[last]), "```" | "~~~");
+    (first < last && is_fence(lines[first]) && closes).then(|| &lines[first + 1..last])
+}
+
+/// Lines of the first fenced block, dropping what comes before and after it;
+/// an unclosed block runs to the end
+fn first_fenced_block<'a>(lines: &'a [&'a str]) -> Option<&'a [&'a str]> {
+    let open = lines.iter().position(|line| is_fence(line))?;
+    let close = lines[open + 1..]
+        .iter()
+        .position(|line| is_fence(line))
+        .map_or(lines.len(), |i| open + 1 + i);
+    Some(&lines[open + 1..close])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN_RS: &str = "fn main() {\n    println!(\"hi\");\n}\n";
+
+    /// Deterministic xorshift, so failures reproduce
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Text biased toward the characters that make up markdown artifacts
+        fn adversarial_text(&mut self) -> String {
+            const PIECES: &[&str] = &[
+                "```",
+                "```rust",
+                "~~~",
+                "`",
+                "rust",
+                "json",
+                "\n",
+                "\r\n",
+                "\r",
+                " ",
+                "\t",
+                "\u{feff}",
+                "x",
+                "{",
+                "}",
+                "Here is the file:",
+                "fn a() {}",
+                "    ",
+            ];
+            let len = self.next() % 24;
+            (0..len)
+                .map(|_| PIECES[(self.next() % PIECES.len() as u64) as usize])
+                .collect()
+        }
+    }
+
+    fn wrappings(body: &str) -> Vec<String> {
+        vec![
+            format!("```rust\n{}```", body),
+            format!("~~~\n{}~~~\n", body),
+            format!(
+                "Here is the updated file:\n\n```\n{}```\n\nThis adds a greeting.",
+                body
+            ),
+            format!("\u{feff}```rs\n{}\n```", body),
+            format!("```\n{}", body),
+            format!("rust\n{}", body),
+            format!("```\nRust\n\n{}```\n```\nother block\n```", body),
+            format!("\u{feff}\n\n{}\n\n", body),
+        ]
+    }
+
+    #[test]
+    fn test_artifacts_around_a_file_are_removed() {
+        for wrapped in wrappings(MAIN_RS) {
+            assert_eq!(clean_file_content(&wrapped, None), MAIN_RS, "{:?}", wrapped);
+        }
+        assert_eq!(
+            clean_file_content("```toml\r\n[package]\r\nname = \"a\"\r\n```\r\n", None),
+            "[package]\r\nname = \"a\"\r\n"
+        );
+        assert_eq!(clean_file_content("```\n```", None), "");
+        assert_eq!(strip_code_fences("```rust\nlet x = 1;\n```"), "let x = 1;");
+        assert_eq!(strip_code_fences("`ls -la`"), "ls -la");
+    }
+
+    #[test]
+    fn test_markdown_keeps_inner_fences() {
+        let readme = "# Usage\n\n```sh\ncargo run\n```\n";
+        let path = Path::new("README.md");
+        assert_eq!(clean_file_content(readme, Some(path)), readme);
+        assert_eq!(
+            clean_file_content(&format!("```markdown\n{}```", readme), Some(path)),
+            readme
+        );
+    }
+
+    #[test]
+    fn test_cleaning_is_idempotent_on_adversarial_output() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..5000 {
+            let text = rng.adversarial_text();
+            let cleaned = clean_file_content(&text, None);
+            assert_eq!(clean_file_content(&cleaned, None), cleaned, "{:?}", text);
+            assert!(!cleaned.starts_with(BOM), "{:?}", text);
+            assert!(!cleaned.lines().any(is_fence), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_wrapping_clean_output_does_not_change_it() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let body = clean_file_content(&rng.adversarial_text(), None);
+            if body.is_empty() || body.contains('\r') {
+                continue;
+            }
+            for wrapped in wrappings(&body) {
+                assert_eq!(clean_file_content(&wrapped, None), body, "{:?}", wrapped);
+            }
+        }
+    }
+}
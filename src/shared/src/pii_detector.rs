@@ -0,0 +1,213 @@
+//! Personal data in text sent to a model
+//!
+//! Finds email addresses, phone numbers, national ID numbers (US social
+//! security and UK National Insurance numbers) and IP addresses. Depending on
+//! the [`PiiMode`] each one is masked as `[EMAIL]`, replaced by a short hash
+//! such as `[EMAIL:3f2a9c1d]` so the model can still tell values apart, or the
+//! text is refused. Loopback and unspecified addresses are not personal and
+//! are left alone.
+
+use crate::types::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    Phone,
+    NationalId,
+    IpAddress,
+}
+
+impl PiiKind {
+    pub const ALL: [PiiKind; 4] = [Self::Email, Self::Phone, Self::NationalId, Self::IpAddress];
+
+    /// Label used in masks, e.g. `NATIONAL_ID`
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Email => "EMAIL",
+            Self::Phone => "PHONE",
+            Self::NationalId => "NATIONAL_ID",
+            Self::IpAddress => "IP_ADDRESS",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Email => "an email address",
+            Self::Phone => "a phone number",
+            Self::NationalId => "a national ID number",
+            Self::IpAddress => "an IP address",
+        }
+    }
+}
+
+/// What happens to personal data found in a prompt
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PiiMode {
+    /// Replace it with its kind
+    #[default]
+    Mask,
+    /// Replace it with its kind and a hash of the value
+    Hash,
+    /// Refuse to send the text
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiMatch {
+    pub kind: PiiKind,
+    pub range: Range<usize>,
+}
+
+pub struct PiiDetector {
+    kinds: Vec<PiiKind>,
+    mode: PiiMode,
+    email: Regex,
+    phone: Regex,
+    national_id: Regex,
+    address_token: Regex,
+}
+
+impl PiiDetector {
+    pub fn new(kinds: &[PiiKind], mode: PiiMode) -> Self {
+        Self {
+            kinds: kinds.to_vec(),
+            mode,
+            email: Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b")
+                .expect("valid regex"),
+            phone: Regex::new(
+                r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{3}\)|\b\d{3})[\s.-]\d{3}[\s.-]\d{4}\b|\+\d{1,3}(?:[\s.-]?\d{2,4}){3,5}\b",
+            )
+            .expect("valid regex"),
+            national_id: Regex::new(
+                r"\b\d{3}-\d{2}-\d{4}\b|\b[A-CEGHJ-PR-TW-Z]{2} ?\d{2} ?\d{2} ?\d{2} ?[A-D]\b",
+            )
+            .expect("valid regex"),
+            // Candidates are checked by parsing them as addresses
+            address_token: Regex::new(r"[0-9A-Za-z_:.]+").expect("valid regex"),
+        }
+    }
+
+    pub fn mode(&self) -> PiiMode {
+        self.mode
+    }
+
+    /// Personal data in `text`, in order and without overlaps
+    pub fn find(&self, text: &str) -> Vec<PiiMatch> {
+        let mut matches = Vec::new();
+        for &kind in &self.kinds {
+            let ranges: Vec<Range<usize>> = match kind {
+                PiiKind::Email => self.email.find_iter(text).map(|m| m.range()).collect(),
+                PiiKind::Phone => self.phone.find_iter(text).map(|m| m.range()).collect(),
+                PiiKind::NationalId => self
+                    .national_id
+                    .find_iter(text)
+                    .filter(|m| !m.as_str().starts_with("000"))
+                    .map(|m| m.range())
+                    .collect(),
+                PiiKind::IpAddress => self
+                    .address_token
+                    .find_iter(text)
+                    .filter_map(|m| {
+                        let token = m.as_str().trim_end_matches(['.', ':']);
+                        let address: IpAddr = token.parse().ok()?;
+                        (!address.is_loopback() && !address.is_unspecified())
+                            .then(|| m.start()..m.start() + token.len())
+                    })
+                    .collect(),
+            };
+            matches.extend(ranges.into_iter().map(|range| PiiMatch { kind, range }));
+        }
+
+        // Earlier first, and the longer of two starting together
+        matches.sort_by_key(|m| (m.range.start, std::cmp::Reverse(m.range.end)));
+        let mut end = 0;
+        matches.retain(|m| {
+            let keep = m.range.start >= end;
+            if keep {
+                end = m.range.end;
+            }
+            keep
+        });
+        matches
+    }
+
+    /// `text` with its personal data masked or hashed, or an error naming
+    /// the first finding when blocking
+    pub fn apply(&self, text: &str) -> Result<String> {
+        let matches = self.find(text);
+        if let (PiiMode::Block, Some(first)) = (self.mode, matches.first()) {
+            let line = text[..first.range.start].matches('\n').count() + 1;
+            return Err(anyhow::anyhow!(
+                "Refusing to send {} (line {}) to a remote model; remove it or set the pii mode to \"mask\"",
+                first.kind.description(),
+                line
+            ));
+        }
+
+        let mut filtered = String::with_capacity(text.len());
+        let mut at = 0;
+        for PiiMatch { kind, range } in matches {
+            filtered.push_str(&text[at..range.start]);
+            match self.mode {
+                PiiMode::Hash => {
+                    let hash = blake3::hash(text[range.clone()].as_bytes()).to_hex();
+                    filtered.push_str(&format!("[{}:{}]", kind.label(), &hash[..8]));
+                }
+                _ => filtered.push_str(&format!("[{}]", kind.label())),
+            }
+            at = range.end;
+        }
+        filtered.push_str(&text[at..]);
+        Ok(filtered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "Contact jane.doe@example.com or +44 20 7946 0958.\n\
+        US office: (555) 123-4567, SSN 123-45-6789, NI AB 12 34 56 C\n\
+        Server 203.0.113.42 and 2001:db8::8a2e:370:7334 via std::io on 127.0.0.1 at 12:30:45, v1.2.3\n";
+
+    #[test]
+    fn test_masks_each_kind() {
+        let detector = PiiDetector::new(&PiiKind::ALL, PiiMode::Mask);
+        assert_eq!(
+            detector.apply(TEXT).unwrap(),
+            "Contact [EMAIL] or [PHONE].\n\
+             US office: [PHONE], SSN [NATIONAL_ID], NI [NATIONAL_ID]\n\
+             Server [IP_ADDRESS] and [IP_ADDRESS] via std::io on 127.0.0.1 at 12:30:45, v1.2.3\n"
+        );
+
+        let emails_only = PiiDetector::new(&[PiiKind::Email], PiiMode::Hash);
+        let hashed = emails_only
+            .apply("a@example.com b@example.com a@example.com")
+            .unwrap();
+        let values: Vec<_> = hashed.split(' ').collect();
+        assert!(
+            values[0].starts_with("[EMAIL:") && values[0].len() == 16,
+            "{}",
+            hashed
+        );
+        assert_eq!(values[0], values[2]);
+        assert_ne!(values[0], values[1]);
+    }
+
+    #[test]
+    fn test_block_mode_names_the_finding() {
+        let detector = PiiDetector::new(&PiiKind::ALL, PiiMode::Block);
+        let error = detector
+            .apply("ok\nping 198.51.100.7\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("an IP address (line 2)"), "{}", error);
+        assert_eq!(detector.apply("nothing here").unwrap(), "nothing here");
+    }
+}
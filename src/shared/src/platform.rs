@@ -0,0 +1,185 @@
+//! Platform-specific paths and shell invocation
+//!
+//! Unix keeps the historical `~/.config` / `~/.local/share` layout (honouring
+//! the XDG variables); Windows uses the known folders reported by the
+//! `directories` crate. Shell commands go through [`Shell`] so generated
+//! commands run under `cmd` or PowerShell where `sh` is unavailable.
+
+use directories::BaseDirs;
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Directory name used for config, caches and data
+pub const APP_DIR: &str = "vibe_cli";
+
+/// The user's home directory, or `.` if it cannot be determined
+pub fn home_dir() -> PathBuf {
+    BaseDirs::new()
+        .map(|dirs| dirs.home_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Base directory for user configuration (`~/.config`, `%APPDATA%`)
+pub fn config_home() -> PathBuf {
+    if cfg!(windows) {
+        BaseDirs::new()
+            .map(|dirs| dirs.config_dir().to_path_buf())
+            .unwrap_or_else(|| home_dir().join("AppData").join("Roaming"))
+    } else {
+        xdg_dir("XDG_CONFIG_HOME", ".config")
+    }
+}
+
+/// Base directory for application data (`~/.local/share`, `%LOCALAPPDATA%`)
+pub fn data_home() -> PathBuf {
+    if cfg!(windows) {
+        BaseDirs::new()
+            .map(|dirs| dirs.data_local_dir().to_path_buf())
+            .unwrap_or_else(|| home_dir().join("AppData").join("Local"))
+    } else {
+        xdg_dir("XDG_DATA_HOME", ".local/share")
+    }
+}
+
+/// Configuration directory for this application
+pub fn app_config_dir() -> PathBuf {
+    config_home().join(APP_DIR)
+}
+
+/// Data and cache directory for this application
+pub fn app_data_dir() -> PathBuf {
+    data_home().join(APP_DIR)
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    std::env::var(var)
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home_dir().join(fallback))
+}
+
+/// Shell used to run generated and user-supplied command strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Sh,
+    Bash,
+    Cmd,
+    PowerShell,
+}
+
+impl Shell {
+    /// Shell for this platform, overridable with `VIBE_SHELL` (sh, bash, cmd, powershell)
+    pub fn detect() -> Shell {
+        static DETECTED: OnceLock<Shell> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            std::env::var("VIBE_SHELL")
+                .ok()
+                .and_then(|name| Shell::parse(&name))
+                .unwrap_or_else(Shell::platform_default)
+        })
+    }
+
+    fn platform_default() -> Shell {
+        if cfg!(windows) {
+            Shell::PowerShell
+        } else if std::path::Path::new("/bin/bash").exists() {
+            Shell::Bash
+        } else {
+            Shell::Sh
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Shell> {
+        match name.trim().to_lowercase().as_str() {
+            "sh" => Some(Shell::Sh),
+            "bash" => Some(Shell::Bash),
+            "cmd" | "cmd.exe" => Some(Shell::Cmd),
+            "powershell" | "pwsh" | "powershell.exe" => Some(Shell::PowerShell),
+            _ => None,
+        }
+    }
+
+    /// Executable name
+    pub fn program(&self) -> &'static str {
+        match self {
+            Shell::Sh => "sh",
+            Shell::Bash => "bash",
+            Shell::Cmd => "cmd",
+            Shell::PowerShell => "powershell",
+        }
+    }
+
+    /// Arguments that make the shell run `script` and exit
+    pub fn args(&self, script: &str) -> Vec<String> {
+        let flags: &[&str] = match self {
+            Shell::Sh | Shell::Bash => &["-c"],
+            Shell::Cmd => &["/C"],
+            Shell::PowerShell => &["-NoProfile", "-NonInteractive", "-Command"],
+        };
+        flags
+            .iter()
+            .map(|flag| flag.to_string())
+            .chain(std::iter::once(script.to_string()))
+            .collect()
+    }
+
+    /// A ready-to-spawn command running `script`
+    pub fn command(&self, script: &str) -> Command {
+        let mut command = Command::new(self.program());
+        command.args(self.args(script));
+        command
+    }
+
+    pub fn is_posix(&self) -> bool {
+        matches!(self, Shell::Sh | Shell::Bash)
+    }
+
+    /// Script listing the first `limit` entries of the current directory
+    pub fn directory_listing_script(&self, limit: usize) -> String {
+        match self {
+            Shell::Sh | Shell::Bash => format!("ls -la 2>/dev/null | head -n {}", limit),
+            Shell::Cmd => "dir /a".to_string(),
+            Shell::PowerShell => format!(
+                "Get-ChildItem -Force | Select-Object -First {} | Format-Table -AutoSize",
+                limit
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shell::Sh => write!(f, "sh"),
+            Shell::Bash => write!(f, "bash"),
+            Shell::Cmd => write!(f, "cmd.exe"),
+            Shell::PowerShell => write!(f, "PowerShell"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_invocation() {
+        assert_eq!(Shell::Bash.args("ls -la"), vec!["-c", "ls -la"]);
+        assert_eq!(Shell::Cmd.args("dir"), vec!["/C", "dir"]);
+        assert_eq!(
+            Shell::PowerShell.args("Get-ChildItem"),
+            vec!["-NoProfile", "-NonInteractive", "-Command", "Get-ChildItem"]
+        );
+        assert_eq!(Shell::parse("PWSH"), Some(Shell::PowerShell));
+        assert_eq!(Shell::parse("fish"), None);
+    }
+
+    #[test]
+    fn test_app_dirs_are_namespaced() {
+        assert!(app_config_dir().ends_with(APP_DIR));
+        assert!(app_data_dir().ends_with(APP_DIR));
+    }
+}
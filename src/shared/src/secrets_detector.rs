@@ -1,4 +1,6 @@
+use crate::types::Result;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 /// Secrets detection and protection system
 pub struct SecretsDetector {
@@ -6,15 +8,24 @@ pub struct SecretsDetector {
     compiled_regexes: Vec<Regex>,
 }
 
-#[derive(Debug, Clone)]
+/// A pattern to look for; custom ones come from `secret_patterns` in the
+/// power user config
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecretPattern {
     pub name: String,
     pub regex: String,
+    #[serde(default = "custom_severity")]
     pub severity: SecretSeverity,
+    #[serde(default)]
     pub description: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+fn custom_severity() -> SecretSeverity {
+    SecretSeverity::High
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SecretSeverity {
     High,   // API keys, passwords, private keys
     Medium, // Tokens, session IDs, database credentials
@@ -29,6 +40,8 @@ pub struct SecretFinding {
     pub position: Option<usize>,
     pub description: String,
     pub masked_value: String,
+    /// Hash of the matched text, to recognise the same secret later
+    pub hashed_secret: String,
 }
 
 /// Result of secrets scanning
@@ -131,6 +144,23 @@ impl SecretsDetector {
         }
     }
 
+    /// Built-in patterns plus `custom` ones, which fail on an invalid regex
+    pub fn with_patterns(custom: &[SecretPattern]) -> Result<Self> {
+        let mut detector = Self::new();
+        for pattern in custom {
+            let regex = Regex::new(&pattern.regex).map_err(|e| {
+                anyhow::anyhow!("Invalid regex for secret pattern '{}': {}", pattern.name, e)
+            })?;
+            let mut pattern = pattern.clone();
+            if pattern.description.is_empty() {
+                pattern.description = format!("{} detected", pattern.name);
+            }
+            detector.patterns.push(pattern);
+            detector.compiled_regexes.push(regex);
+        }
+        Ok(detector)
+    }
+
     /// Scan content for secrets
     pub fn scan_content(&self, content: &str) -> SecretsScanResult {
         let mut findings = Vec::new();
@@ -150,6 +180,7 @@ impl SecretsDetector {
                     position: Some(start),
                     description: pattern.description.clone(),
                     masked_value: masked_value.clone(),
+                    hashed_secret: blake3::hash(matched_text.as_bytes()).to_hex().to_string(),
                 });
 
                 // Replace in sanitized content
@@ -182,6 +213,40 @@ impl SecretsDetector {
         }
     }
 
+    /// Byte ranges of secrets in `content` found by the patterns `include`
+    /// accepts, with the pattern that found them, in order and without
+    /// overlaps; the earlier, longer match wins. Patterns are filtered before
+    /// overlaps are resolved, so a skipped match never hides a kept one.
+    pub fn find_secrets(
+        &self,
+        content: &str,
+        include: impl Fn(&SecretPattern) -> bool,
+    ) -> Vec<(std::ops::Range<usize>, &SecretPattern)> {
+        let mut matches: Vec<_> = self
+            .patterns
+            .iter()
+            .zip(&self.compiled_regexes)
+            .filter(|(pattern, _)| include(pattern))
+            .flat_map(|(pattern, regex)| {
+                regex
+                    .find_iter(content)
+                    .filter(|found| !found.is_empty())
+                    .map(move |found| (found.range(), pattern))
+            })
+            .collect();
+        matches.sort_by(|(a, _), (b, _)| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+
+        let mut covered = 0;
+        matches.retain(|(range, _)| {
+            let keep = range.start >= covered;
+            if keep {
+                covered = range.end;
+            }
+            keep
+        });
+        matches
+    }
+
     /// Check if content contains high-severity secrets
     pub fn contains_high_severity_secrets(&self, content: &str) -> bool {
         for (pattern, regex) in self.patterns.iter().zip(&self.compiled_regexes) {
@@ -305,4 +370,24 @@ mod tests {
         assert!(sanitized.contains("sk_t****************cdef"));
         assert!(!sanitized.contains("sk_test_1234567890abcdef"));
     }
+
+    #[test]
+    fn test_custom_patterns() {
+        let pattern: SecretPattern =
+            serde_json::from_str(r#"{"name": "Acme Token", "regex": "acme_[a-z0-9]{12}"}"#)
+                .unwrap();
+        let detector = SecretsDetector::with_patterns(std::slice::from_ref(&pattern)).unwrap();
+
+        let result = detector.scan_content("token = acme_0123456789ab");
+        assert_eq!(result.high_severity_count, 1);
+        assert_eq!(result.findings[0].pattern_name, "Acme Token");
+        assert_eq!(result.findings[0].description, "Acme Token detected");
+        assert!(!result.sanitized_content.contains("acme_0123456789ab"));
+
+        let broken = SecretPattern {
+            regex: "acme_[".to_string(),
+            ..pattern
+        };
+        assert!(SecretsDetector::with_patterns(&[broken]).is_err());
+    }
 }
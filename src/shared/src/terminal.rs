@@ -0,0 +1,289 @@
+//! Terminal capability detection
+//!
+//! Output across the CLI uses ANSI colors, emoji and box-drawing characters.
+//! This module detects when the terminal cannot render them (`NO_COLOR`,
+//! `TERM=dumb`, non-UTF-8 locales, piped output, narrow windows) and provides
+//! helpers that degrade to plain ASCII.
+
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// Width assumed when the terminal size cannot be queried
+const DEFAULT_WIDTH: usize = 80;
+
+/// Below this width tables and rules are wrapped or shortened
+pub const NARROW_WIDTH: usize = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalCapabilities {
+    /// ANSI color and style sequences are allowed
+    pub color: bool,
+    /// Emoji and box-drawing characters render correctly
+    pub unicode: bool,
+    /// Usable columns
+    pub width: usize,
+}
+
+impl Default for TerminalCapabilities {
+    fn default() -> Self {
+        Self {
+            color: true,
+            unicode: true,
+            width: DEFAULT_WIDTH,
+        }
+    }
+}
+
+impl TerminalCapabilities {
+    /// Inspect the environment and stdout
+    pub fn detect() -> Self {
+        let env = |key: &str| std::env::var(key).ok();
+        Self::from_env(env, std::io::stdout().is_terminal(), terminal_width())
+    }
+
+    fn from_env(
+        env: impl Fn(&str) -> Option<String>,
+        is_tty: bool,
+        tty_width: Option<usize>,
+    ) -> Self {
+        let dumb = env("TERM").is_some_and(|term| term == "dumb");
+
+        // https://no-color.org: any non-empty value disables color
+        let no_color = env("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let force_color = env("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0");
+        let color = force_color || (!no_color && !dumb && is_tty);
+
+        let locale = env("LC_ALL")
+            .filter(|v| !v.is_empty())
+            .or_else(|| env("LC_CTYPE").filter(|v| !v.is_empty()))
+            .or_else(|| env("LANG").filter(|v| !v.is_empty()));
+        let utf8_locale = locale.map_or(true, |l| {
+            let l = l.to_lowercase();
+            l.contains("utf-8") || l.contains("utf8")
+        });
+        let ascii_requested = env("VIBE_ASCII").is_some_and(|v| v == "1" || v == "true");
+        let unicode = !dumb && utf8_locale && !ascii_requested;
+
+        let width = env("COLUMNS")
+            .and_then(|c| c.parse().ok())
+            .or(tty_width)
+            .filter(|w| *w > 0)
+            .unwrap_or(DEFAULT_WIDTH);
+
+        Self {
+            color,
+            unicode,
+            width,
+        }
+    }
+
+    pub fn is_narrow(&self) -> bool {
+        self.width < NARROW_WIDTH
+    }
+}
+
+fn terminal_width() -> Option<usize> {
+    crossterm::terminal::size()
+        .ok()
+        .map(|(columns, _)| columns as usize)
+}
+
+static CAPABILITIES: OnceLock<TerminalCapabilities> = OnceLock::new();
+
+/// Detect capabilities once and disable ANSI output process-wide if unsupported
+pub fn init() -> TerminalCapabilities {
+    let caps = *CAPABILITIES.get_or_init(TerminalCapabilities::detect);
+    if !caps.color {
+        colored::control::set_override(false);
+    }
+    caps
+}
+
+/// Capabilities detected by `init`, detecting lazily if it was never called
+pub fn capabilities() -> TerminalCapabilities {
+    *CAPABILITIES.get_or_init(TerminalCapabilities::detect)
+}
+
+/// Pick the unicode glyph or its ASCII fallback
+pub fn icon<'a>(unicode: &'a str, ascii: &'a str) -> &'a str {
+    if capabilities().unicode {
+        unicode
+    } else {
+        ascii
+    }
+}
+
+/// Tree connector for list items
+pub fn tree_branch(last: bool) -> &'static str {
+    match (capabilities().unicode, last) {
+        (true, true) => "└─",
+        (true, false) => "├─",
+        (false, true) => "`-",
+        (false, false) => "|-",
+    }
+}
+
+/// Horizontal rule that fits the terminal, at most `max` columns wide
+pub fn rule(max: usize) -> String {
+    let caps = capabilities();
+    let glyph = if caps.unicode { "━" } else { "-" };
+    glyph.repeat(max.min(caps.width).max(1))
+}
+
+/// Progress bar of `width` cells for `done` out of `total`
+pub fn progress_bar(done: usize, total: usize, width: usize) -> String {
+    let (full, empty) = if capabilities().unicode {
+        ("█", "░")
+    } else {
+        ("#", "-")
+    };
+    let filled = bar_cells(done, total, width);
+    format!("{}{}", full.repeat(filled), empty.repeat(width - filled))
+}
+
+fn bar_cells(done: usize, total: usize, width: usize) -> usize {
+    if total == 0 {
+        return width;
+    }
+    (done.min(total) * width) / total
+}
+
+/// Replace emoji and symbols with ASCII when the terminal cannot render them
+pub fn text(s: &str) -> Cow<'_, str> {
+    if capabilities().unicode {
+        Cow::Borrowed(s)
+    } else {
+        to_ascii(s)
+    }
+}
+
+fn to_ascii(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() {
+        return Cow::Borrowed(s);
+    }
+
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '✓' | '✔' | '✅' => out.push_str("OK"),
+            '✗' | '✘' | '❌' => out.push('X'),
+            '→' | '➜' => out.push_str("->"),
+            '•' | '○' => out.push('*'),
+            '━' | '─' => out.push('-'),
+            '│' => out.push('|'),
+            '├' | '└' => out.push('+'),
+            '‘' | '’' => out.push('\''),
+            '“' | '”' => out.push('"'),
+            '…' => out.push_str("..."),
+            // Variation selectors and joiners only make sense alongside emoji
+            '\u{FE0F}' | '\u{200D}' => {}
+            c if is_symbol(c) => {}
+            c => out.push(c),
+        }
+    }
+
+    // Dropping a leading icon leaves a stray space behind
+    let trimmed = if s.starts_with(|c: char| is_symbol(c)) {
+        out.trim_start().to_string()
+    } else {
+        out
+    };
+    Cow::Owned(trimmed)
+}
+
+fn is_symbol(c: char) -> bool {
+    matches!(c as u32,
+        0x2190..=0x21FF   // arrows
+        | 0x2300..=0x23FF // misc technical (⌛, ⏳)
+        | 0x2500..=0x25FF // box drawing, geometric shapes
+        | 0x2600..=0x27BF // misc symbols, dingbats
+        | 0x2B00..=0x2BFF // misc symbols and arrows
+        | 0x1F000..=0x1FAFF) // emoji
+}
+
+/// Wrap text to the terminal width, indenting continuation lines by `indent`
+pub fn wrap(s: &str, indent: usize) -> String {
+    wrap_to(s, capabilities().width, indent)
+}
+
+fn wrap_to(s: &str, width: usize, indent: usize) -> String {
+    let width = width.max(indent + 10);
+    let pad = " ".repeat(indent);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in s.split_whitespace() {
+        let limit = if lines.is_empty() {
+            width
+        } else {
+            width - indent
+        };
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > limit {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+
+    lines.join(&format!("\n{}", pad))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn caps(vars: &[(&str, &str)], is_tty: bool) -> TerminalCapabilities {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        TerminalCapabilities::from_env(|k| vars.get(k).cloned(), is_tty, Some(120))
+    }
+
+    #[test]
+    fn test_detection_from_environment() {
+        let tty = caps(&[("TERM", "xterm-256color"), ("LANG", "en_US.UTF-8")], true);
+        assert!(tty.color && tty.unicode);
+        assert_eq!(tty.width, 120);
+
+        assert!(!caps(&[("NO_COLOR", "1")], true).color);
+        assert!(caps(&[("NO_COLOR", "")], true).color);
+        assert!(!caps(&[], false).color);
+
+        let dumb = caps(&[("TERM", "dumb")], true);
+        assert!(!dumb.color && !dumb.unicode);
+
+        assert!(!caps(&[("LANG", "C")], true).unicode);
+        assert!(caps(&[("COLUMNS", "40")], true).is_narrow());
+    }
+
+    #[test]
+    fn test_ascii_fallback_and_wrapping() {
+        assert_eq!(
+            to_ascii("🤖 Generating command..."),
+            "Generating command..."
+        );
+        assert_eq!(to_ascii("[✓] done → next"), "[OK] done -> next");
+        assert_eq!(to_ascii("plain"), "plain");
+
+        let wrapped = wrap_to("one two three four five six", 20, 2);
+        assert!(wrapped.lines().all(|l| l.chars().count() <= 20));
+        assert!(wrapped.lines().skip(1).all(|l| l.starts_with("  ")));
+    }
+
+    #[test]
+    fn test_progress_bar_cells() {
+        assert_eq!(bar_cells(0, 10, 20), 0);
+        assert_eq!(bar_cells(5, 10, 20), 10);
+        assert_eq!(bar_cells(12, 10, 20), 20);
+        assert_eq!(bar_cells(0, 0, 20), 20);
+        assert_eq!(progress_bar(3, 4, 8).chars().count(), 8);
+    }
+}
@@ -270,6 +270,141 @@ impl Clone for UltraFastCache {
     }
 }
 
+/// Similarity above which a cached answer is reused for a differently worded query
+pub const DEFAULT_SEMANTIC_THRESHOLD: f32 = 0.92;
+
+/// Upper bound on stored entries; the oldest are dropped first
+const MAX_SEMANTIC_ENTRIES: usize = 1000;
+
+/// Cached value keyed by the embedding of the query that produced it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticCacheEntry {
+    pub query: String,
+    pub embedding: Vec<f32>,
+    pub value: String,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SemanticCacheFile {
+    entries: Vec<SemanticCacheEntry>,
+}
+
+/// Closest cached entry for a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticHit {
+    pub query: String,
+    pub value: String,
+    pub similarity: f32,
+}
+
+/// Persistent cache that matches queries by embedding similarity instead of exact text.
+///
+/// Callers embed the query themselves so this layer stays independent of the
+/// inference backend.
+pub struct SemanticCache {
+    path: PathBuf,
+    threshold: f32,
+    ttl_seconds: u64,
+    entries: Vec<SemanticCacheEntry>,
+}
+
+impl SemanticCache {
+    /// Load the cache at `path`, starting empty if it is missing or unreadable
+    pub fn load(path: PathBuf, threshold: f32, ttl_seconds: u64) -> Self {
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|data| bincode::deserialize::<SemanticCacheFile>(&data).ok())
+            .map(|file| file.entries)
+            .unwrap_or_default();
+
+        let mut cache = Self {
+            path,
+            threshold,
+            ttl_seconds,
+            entries,
+        };
+        cache.evict_expired();
+        cache
+    }
+
+    /// Most similar entry at or above the threshold
+    pub fn lookup(&self, embedding: &[f32]) -> Option<SemanticHit> {
+        self.entries
+            .iter()
+            .map(|entry| (entry, cosine_similarity(&entry.embedding, embedding)))
+            .filter(|(_, similarity)| *similarity >= self.threshold)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(entry, similarity)| SemanticHit {
+                query: entry.query.clone(),
+                value: entry.value.clone(),
+                similarity,
+            })
+    }
+
+    /// Store a value, replacing any entry for the same query
+    pub fn insert(&mut self, query: &str, embedding: Vec<f32>, value: &str) {
+        self.entries.retain(|entry| entry.query != query);
+        self.entries.push(SemanticCacheEntry {
+            query: query.to_string(),
+            embedding,
+            value: value.to_string(),
+            timestamp: unix_now(),
+        });
+        if self.entries.len() > MAX_SEMANTIC_ENTRIES {
+            let excess = self.entries.len() - MAX_SEMANTIC_ENTRIES;
+            self.entries.drain(..excess);
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = SemanticCacheFile {
+            entries: self.entries.clone(),
+        };
+        fs::write(&self.path, bincode::serialize(&file)?)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn evict_expired(&mut self) {
+        let now = unix_now();
+        let ttl = self.ttl_seconds;
+        self.entries
+            .retain(|entry| now.saturating_sub(entry.timestamp) < ttl);
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Cosine similarity of two vectors; 0.0 when lengths differ or either is zero
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -305,4 +440,25 @@ mod tests {
         assert_eq!(stats.memory_entries, 1);
         assert!(stats.memory_usage_mb >= 0);
     }
+
+    #[test]
+    fn test_semantic_cache_matches_similar_queries() {
+        let path =
+            std::env::temp_dir().join(format!("semantic_cache_test_{}.bin", std::process::id()));
+        let mut cache = SemanticCache::load(path.clone(), 0.9, 3600);
+        cache.insert("list files", vec![1.0, 0.0, 0.0], "ls -la");
+
+        let hit = cache
+            .lookup(&[0.98, 0.1, 0.0])
+            .expect("similar query should hit");
+        assert_eq!(hit.value, "ls -la");
+        assert!(hit.similarity > 0.9);
+        assert!(cache.lookup(&[0.0, 1.0, 0.0]).is_none());
+
+        cache.save().unwrap();
+        let reloaded = SemanticCache::load(path.clone(), 0.9, 3600);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(reloaded.len(), 1);
+        assert!(reloaded.lookup(&[1.0, 0.0, 0.0]).is_some());
+    }
 }
@@ -5,6 +5,9 @@ pub fn is_supported_file(path: &Path) -> bool {
     matches!(
         ext,
         "rs" | "md"
+            | "markdown"
+            | "rst"
+            | "adoc"
             | "toml"
             | "json"
             | "graphql"
@@ -17,6 +20,8 @@ pub fn is_supported_file(path: &Path) -> bool {
             | "py"
             | "js"
             | "ts"
+            | "tsx"
+            | "jsx"
             | "java"
             | "go"
             | "rb"
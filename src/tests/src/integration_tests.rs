@@ -64,6 +64,8 @@ async fn test_qdrant_storage_integration() {
         vector: vec![0.1; 768],
         text: "test content".to_string(),
         path: "/test/file.rs".to_string(),
+        symbol: None,
+        commit: None,
     };
 
     let embeddings = vec![test_embedding.clone()];
@@ -134,6 +136,8 @@ async fn test_hybrid_storage_with_qdrant() {
         vector: vec![0.2; 768],
         text: "hybrid test content".to_string(),
         path: "/hybrid/test/file.rs".to_string(),
+        symbol: None,
+        commit: None,
     };
 
     let embeddings = vec![test_embedding.clone()];
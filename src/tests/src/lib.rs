@@ -381,6 +381,8 @@ mod tests {
             max_execution_time_seconds: 1,
             verification_timeout_seconds: 1,
             allow_iteration_on_failure: false,
+            max_session_tokens: None,
+            max_daily_tokens: None,
         };
 
         let bounded_controller = AgentController::with_limits(strict_limits);
@@ -407,10 +409,21 @@ mod tests {
 
     #[tokio::test]
     async fn test_network_security_allowlist() {
-        // Test basic network security functionality
-        // NetworkSecurityManager would need to be implemented with domain checking
-        // For this test, we verify the concept is sound
-        assert!(true, "Network security concepts are implemented");
+        use infrastructure::config::NetworkSecurityConfig;
+        use infrastructure::network_security::NetworkSecurity;
+
+        let security = NetworkSecurity::from_config(&NetworkSecurityConfig {
+            allowed_domains: vec!["*.githubusercontent.com".to_string()],
+            ..NetworkSecurityConfig::default()
+        });
+        let script = |script: &str| vec!["-c".to_string(), script.to_string()];
+        assert!(security
+            .check_command("sh", &script("curl -L https://raw.githubusercontent.com/x"))
+            .is_ok());
+        let denied = security
+            .check_command("sh", &script("pip install requests"))
+            .unwrap_err();
+        assert_eq!(denied.host, "pypi.org");
     }
 
     #[tokio::test]
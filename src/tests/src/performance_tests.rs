@@ -318,6 +318,8 @@ async fn benchmark_dynamic_batch_sizing() {
                 id: format!("test_{}", i),
                 path: format!("test_file_{}.rs", i),
                 text: format!("This is test content {} for performance benchmarking of dynamic batch sizing.", i),
+                symbol: None,
+                commit: None,
             })
             .collect();
 
@@ -412,6 +414,8 @@ async fn test_real_world_rag_scenario() {
             id: path.to_string(),
             path: path.to_string(),
             text: content.to_string(),
+            symbol: None,
+            commit: None,
         })
         .collect();
 
@@ -504,6 +508,8 @@ async fn benchmark_system_load_adaptation() {
                 id: format!("load_test_{}", i),
                 path: format!("test_{}.rs", i),
                 text: format!("Test content {} for load adaptation testing with dynamic batch sizing.", i),
+                symbol: None,
+                commit: None,
             })
             .collect();
 
@@ -550,6 +556,8 @@ async fn test_performance_regression_protection() {
                 id: format!("regression_test_{}_{}", run, i),
                 path: format!("test_{}.rs", i),
                 text: format!("Regression test content {} for run {} to ensure consistent performance.", i, run),
+                symbol: None,
+                commit: None,
             })
             .collect();
 
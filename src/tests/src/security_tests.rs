@@ -40,6 +40,8 @@ async fn test_agent_execution_security_constraints() {
         max_execution_time_seconds: 5,
         verification_timeout_seconds: 2,
         allow_iteration_on_failure: false,
+        max_session_tokens: None,
+        max_daily_tokens: None,
     };
 
     let controller = AgentController::with_limits(strict_limits);
@@ -379,6 +381,9 @@ async fn test_resource_limits_enforcement() {
             max_network_requests: 5,
             sandbox_enabled: true,
             cgroups_enabled: false,
+            max_processes: 256,
+            sandbox_backend: Default::default(),
+            container: Default::default(),
         },
         ..Default::default()
     };